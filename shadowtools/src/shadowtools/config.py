@@ -58,9 +58,17 @@ class GraphFile(TypedDict, total=False):
 
 
 class Graph(TypedDict, total=False):
-    type: Union[Literal["gml"], Literal["1_gbit_switch"]]
+    type: Union[
+        Literal["gml"],
+        Literal["graphml"],
+        Literal["json"],
+        Literal["latency_matrix"],
+        Literal["1_gbit_switch"],
+    ]
     file: GraphFile
     inline: str
+    host_bandwidth_down: Union[str, None]
+    host_bandwidth_up: Union[str, None]
 
 
 class Network(TypedDict, total=False):
@@ -69,12 +77,18 @@ class Network(TypedDict, total=False):
 
 
 class General(TypedDict, total=False):
+    activity_trace: bool
     bootstrap_end_time: Union[str, int]
     data_directory: str
     heartbeat_interval: Union[str, int, None]
     log_level: LogLevel
+    metrics_format: Union[Literal["json-lines"], Literal["csv"]]
+    metrics_interval: Union[str, int, None]
     model_unblocked_syscall_latency: bool
+    packet_trace: bool
     parallelism: int
+    pcap_filter: Union[str, None]
+    pcap_merged: bool
     progress: bool
     seed: int
     stop_time: Union[str, int]
@@ -91,8 +105,12 @@ class Experimental(TypedDict, total=False):
     socket_recv_buffer: Union[str, int]
     socket_send_autotune: bool
     socket_send_buffer: Union[str, int]
+    strace_logging_merged: bool
     strace_logging_mode: Union[
-        Literal["off"], Literal["standard"], Literal["deterministic"]
+        Literal["off"],
+        Literal["standard"],
+        Literal["deterministic"],
+        Literal["json"],
     ]
     unblocked_syscall_latency: str
     unblocked_vdso_latency: str
@@ -109,10 +127,32 @@ class Experimental(TypedDict, total=False):
     use_worker_spinning: bool
 
 
+class QDisc(TypedDict, total=False):
+    type: Union[
+        Literal["noqueue"],
+        Literal["pfifo"],
+        Literal["red"],
+        Literal["codel"],
+        Literal["fq-codel"],
+    ]
+    limit: Union[str, int]
+    limit_bytes: Union[str, int, None]
+    min: Union[str, int]
+    max: Union[str, int]
+    probability: float
+    weight: float
+    target: Union[str, int]
+    interval: Union[str, int]
+    flows: int
+    quantum: Union[str, int]
+
+
 class HostOptions(TypedDict, total=False):
     log_level: Union[LogLevel, None]
     pcap_capture_size: Union[str, int]
     pcap_enabled: bool
+    qdisc: QDisc
+    tcp_congestion_control: Union[Literal["reno"], Literal["cubic"], Literal["bbr"]]
 
 
 class Exited(TypedDict):
@@ -123,6 +163,16 @@ class Signaled(TypedDict):
     signaled: UnixSignal
 
 
+class StartCondition(TypedDict, total=False):
+    type: Union[
+        Literal["process-exited"], Literal["file-exists"], Literal["tcp-listening"]
+    ]
+    host: str
+    process: int
+    path: str
+    port: int
+
+
 class Process(TypedDict, total=False):
     args: Union[str, List[str]]
     environment: Dict[str, str]
@@ -130,6 +180,7 @@ class Process(TypedDict, total=False):
     path: str
     shutdown_signal: UnixSignal
     shutdown_time: Union[str, int, None]
+    start_after: List[StartCondition]
     start_time: Union[str, int]
 
 
@@ -138,6 +189,7 @@ class Host(TypedDict, total=False):
     bandwidth_up: Union[str, int, None]
     ip_addr: Union[str, None]
     network_node_id: int
+    quantity: Union[int, None]
     host_options: HostOptions
     processes: List[Process]
 
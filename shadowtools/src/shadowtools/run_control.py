@@ -0,0 +1,146 @@
+"""
+Client for shadow's run-control socket.
+
+Shadow serves run-control requests on a Unix socket when started with
+`--run-control-socket <path>`. The simulation is paused at the start until a
+client resumes it.
+
+Example:
+
+```
+import subprocess
+from shadowtools.run_control import RunControl
+
+shadow = subprocess.Popen(["shadow", "--run-control-socket", "rc.sock", "shadow.yaml"])
+with RunControl.connect("rc.sock") as rc:
+    rc.resume(until_ns=10 * 10**9)
+    rc.wait(10 * 10**9)
+    for host in rc.hosts():
+        print(host["name"], host["pids"])
+    rc.resume()
+shadow.wait()
+```
+"""
+
+import json
+import socket
+import time
+
+from pathlib import Path
+from typing import Any, Dict, List, Optional, Union
+
+
+class RunControlError(Exception):
+    """A run-control request was rejected by shadow."""
+
+
+class RunControl:
+    """
+    A connection to shadow's run-control socket.
+
+    Each method sends one request and blocks until shadow responds. All times
+    are nanoseconds of simulated time since the start of the simulation.
+    """
+
+    def __init__(self, sock: socket.socket) -> None:
+        self._sock = sock
+        self._file = sock.makefile("rwb")
+
+    @classmethod
+    def connect(
+        cls, path: Union[str, Path], timeout: Optional[float] = 10.0
+    ) -> "RunControl":
+        """
+        Connect to the socket at `path`, retrying until shadow has created it
+        or `timeout` seconds have passed.
+        """
+        deadline = None if timeout is None else time.monotonic() + timeout
+        while True:
+            sock = socket.socket(socket.AF_UNIX, socket.SOCK_STREAM)
+            try:
+                sock.connect(str(path))
+                return cls(sock)
+            except (FileNotFoundError, ConnectionRefusedError):
+                sock.close()
+                if deadline is not None and time.monotonic() >= deadline:
+                    raise
+                time.sleep(0.05)
+
+    def close(self) -> None:
+        self._file.close()
+        self._sock.close()
+
+    def __enter__(self) -> "RunControl":
+        return self
+
+    def __exit__(self, *args: Any) -> None:
+        self.close()
+
+    def request(self, cmd: str, **args: Any) -> Dict[str, Any]:
+        """
+        Send a raw request and return the response. Arguments that are `None`
+        are omitted.
+        """
+        req = {"cmd": cmd}
+        req.update({k: v for (k, v) in args.items() if v is not None})
+        self._file.write(json.dumps(req).encode() + b"\n")
+        self._file.flush()
+
+        line = self._file.readline()
+        if not line:
+            raise ConnectionError("run-control socket closed by shadow")
+        res = json.loads(line)
+        if not res.get("ok"):
+            raise RunControlError(res.get("error", "unknown error"))
+        return res
+
+    def pause(self) -> None:
+        """Pause at the next window boundary."""
+        self.request("pause")
+
+    def resume(
+        self, for_ns: Optional[int] = None, until_ns: Optional[int] = None
+    ) -> None:
+        """
+        Resume the simulation. If `for_ns` or `until_ns` is given, pause again
+        after that much simulated time or at that simulated time.
+        """
+        self.request("continue", for_ns=for_ns, until_ns=until_ns)
+
+    def step(self, windows: int = 1) -> None:
+        """Run `windows` scheduling windows, then pause."""
+        self.request("step", windows=windows)
+
+    def restart(self, run_until_ns: Optional[int] = None) -> None:
+        """Restart the simulation from the start, optionally running to `run_until_ns`."""
+        self.request("restart", run_until_ns=run_until_ns)
+
+    def fault(self, **fault: Any) -> None:
+        """
+        Add a network fault at the next window boundary. The fault is given in
+        the same format as the `network.faults` configuration option, for
+        example `rc.fault(type="heal", time="90 s")`.
+        """
+        self.request("fault", fault=fault)
+
+    def time(self) -> Dict[str, Any]:
+        """Return the current `time_ns`, and whether the simulation is `paused` or has `ended`."""
+        res = self.request("time")
+        del res["ok"]
+        return res
+
+    def hosts(self) -> List[Dict[str, Any]]:
+        """
+        Return the hosts with their `id`, `name`, next event time
+        `next_event_ns`, and the native `pids` of their running processes.
+        """
+        return self.request("hosts")["hosts"]
+
+    def wait(self, time_ns: int) -> Dict[str, Any]:
+        """
+        Block until the simulation arrives at `time_ns` or ends. Returns the
+        `event` (`"arrived"` or `"ended"`) and the current `time_ns`.
+        """
+        res = self.request("wait", time_ns=time_ns)
+        del res["ok"]
+        return res
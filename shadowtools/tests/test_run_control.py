@@ -0,0 +1,110 @@
+import os
+import subprocess
+import tempfile
+import unittest
+import yaml
+
+from pathlib import Path
+
+from shadowtools.run_control import RunControl, RunControlError
+
+SHADOW_BIN = os.environ.get("SHADOW_BIN", "shadow")
+
+SEC = 10**9
+
+
+class TestRunControl(unittest.TestCase):
+    """
+    Drive a small simulation through shadow's run-control socket.
+    """
+
+    def setUp(self) -> None:
+        self.tmpdir = tempfile.TemporaryDirectory()
+        tmp = Path(self.tmpdir.name)
+        self.sock_path = tmp / "rc.sock"
+
+        config = {
+            "general": {
+                "stop_time": "10s",
+                "data_directory": str(tmp / "shadow.data"),
+            },
+            "network": {"graph": {"type": "1_gbit_switch"}},
+            "hosts": {
+                "host": {
+                    "network_node_id": 0,
+                    "processes": [{"path": "sleep", "args": "5"}],
+                },
+            },
+        }
+        config_path = tmp / "shadow.yaml"
+        config_path.write_text(yaml.safe_dump(config))
+
+        self.shadow = subprocess.Popen(
+            [
+                SHADOW_BIN,
+                "--run-control-socket",
+                str(self.sock_path),
+                str(config_path),
+            ],
+            stdin=subprocess.DEVNULL,
+            stdout=subprocess.DEVNULL,
+        )
+
+    def tearDown(self) -> None:
+        if self.shadow.poll() is None:
+            self.shadow.kill()
+            self.shadow.wait()
+        self.tmpdir.cleanup()
+
+    def test_run_control(self) -> None:
+        with RunControl.connect(self.sock_path) as rc:
+            # the host list is collected by shadow once it has paused at the start
+            self.assertEqual([h["name"] for h in rc.hosts()], ["host"])
+
+            state = rc.time()
+            self.assertEqual(state["time_ns"], 0)
+            self.assertTrue(state["paused"])
+            self.assertFalse(state["ended"])
+
+            rc.resume(until_ns=2 * SEC)
+            res = rc.wait(2 * SEC)
+            self.assertEqual(res["event"], "arrived")
+            self.assertGreaterEqual(res["time_ns"], 2 * SEC)
+
+            state = rc.time()
+            self.assertTrue(state["paused"])
+            # the sleep process is still running
+            self.assertEqual(len(rc.hosts()[0]["pids"]), 1)
+
+            t0 = state["time_ns"]
+            rc.step()
+            res = rc.wait(t0 + 1)
+            self.assertEqual(res["event"], "arrived")
+            self.assertGreater(res["time_ns"], t0)
+
+            with self.assertRaises(RunControlError):
+                rc.request("bogus")
+
+            rc.resume()
+            self.assertEqual(rc.wait(100 * SEC)["event"], "ended")
+
+        self.assertEqual(self.shadow.wait(timeout=60), 0)
+
+    def test_fault(self) -> None:
+        with RunControl.connect(self.sock_path) as rc:
+            rc.resume(until_ns=2 * SEC)
+            rc.wait(2 * SEC)
+
+            rc.fault(type="partition", time="3 s", groups=[[0]])
+            rc.fault(type="bandwidth", time="4 s", node=0, bandwidth_down="10 Mbit")
+
+            # faults can't be added in the past or for nodes that don't exist
+            with self.assertRaises(RunControlError):
+                rc.fault(type="heal", time="1 s")
+            with self.assertRaises(RunControlError):
+                rc.fault(type="partition", time="5 s", groups=[[1]])
+
+            rc.resume()
+            self.assertEqual(rc.wait(100 * SEC)["event"], "ended")
+
+        self.assertEqual(self.shadow.wait(timeout=60), 0)
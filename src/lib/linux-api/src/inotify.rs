@@ -0,0 +1,104 @@
+use crate::{bindings, const_conversions};
+
+// Manually translated from linux/inotify.h, which we don't currently pass to
+// bindgen.
+
+bitflags::bitflags! {
+    /// Inotify init flags, as used with `inotify_init1`.
+    #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+    pub struct InotifyInitFlags: i32 {
+        const IN_NONBLOCK = const_conversions::i32_from_u32(bindings::LINUX_O_NONBLOCK);
+        const IN_CLOEXEC = const_conversions::i32_from_u32(bindings::LINUX_O_CLOEXEC);
+    }
+}
+
+bitflags::bitflags! {
+    /// Inotify event types and watch options, as used in the `mask` argument of
+    /// `inotify_add_watch` and the `mask` member of `struct inotify_event`.
+    #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+    pub struct InotifyMask: u32 {
+        /// File was accessed.
+        const IN_ACCESS = 0x00000001;
+        /// File was modified.
+        const IN_MODIFY = 0x00000002;
+        /// Metadata changed.
+        const IN_ATTRIB = 0x00000004;
+        /// Writable file was closed.
+        const IN_CLOSE_WRITE = 0x00000008;
+        /// Unwritable file closed.
+        const IN_CLOSE_NOWRITE = 0x00000010;
+        /// File was opened.
+        const IN_OPEN = 0x00000020;
+        /// File was moved from X.
+        const IN_MOVED_FROM = 0x00000040;
+        /// File was moved to Y.
+        const IN_MOVED_TO = 0x00000080;
+        /// Subfile was created.
+        const IN_CREATE = 0x00000100;
+        /// Subfile was deleted.
+        const IN_DELETE = 0x00000200;
+        /// Self was deleted.
+        const IN_DELETE_SELF = 0x00000400;
+        /// Self was moved.
+        const IN_MOVE_SELF = 0x00000800;
+
+        /// Backing fs was unmounted. Only set by the kernel.
+        const IN_UNMOUNT = 0x00002000;
+        /// Event queue overflowed. Only set by the kernel.
+        const IN_Q_OVERFLOW = 0x00004000;
+        /// File was ignored. Only set by the kernel.
+        const IN_IGNORED = 0x00008000;
+
+        /// Only watch the path if it is a directory.
+        const IN_ONLYDIR = 0x01000000;
+        /// Don't follow a symlink.
+        const IN_DONT_FOLLOW = 0x02000000;
+        /// Exclude events on unlinked objects.
+        const IN_EXCL_UNLINK = 0x04000000;
+        /// Only create watches.
+        const IN_MASK_CREATE = 0x10000000;
+        /// Add to the mask of an already existing watch.
+        const IN_MASK_ADD = 0x20000000;
+        /// Event occurred against a directory.
+        const IN_ISDIR = 0x40000000;
+        /// Only send the event once.
+        const IN_ONESHOT = 0x80000000;
+
+        const IN_CLOSE = Self::IN_CLOSE_WRITE.bits() | Self::IN_CLOSE_NOWRITE.bits();
+        const IN_MOVE = Self::IN_MOVED_FROM.bits() | Self::IN_MOVED_TO.bits();
+        const IN_ALL_EVENTS = Self::IN_ACCESS.bits()
+            | Self::IN_MODIFY.bits()
+            | Self::IN_ATTRIB.bits()
+            | Self::IN_CLOSE_WRITE.bits()
+            | Self::IN_CLOSE_NOWRITE.bits()
+            | Self::IN_OPEN.bits()
+            | Self::IN_MOVED_FROM.bits()
+            | Self::IN_MOVED_TO.bits()
+            | Self::IN_DELETE.bits()
+            | Self::IN_CREATE.bits()
+            | Self::IN_DELETE_SELF.bits()
+            | Self::IN_MOVE_SELF.bits();
+    }
+}
+
+/// The fixed-size header of an event read from an inotify file descriptor. It
+/// is followed by `len` bytes containing the nul-terminated and nul-padded
+/// name of the file, if any.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct linux_inotify_event {
+    /// Watch descriptor.
+    pub wd: core::ffi::c_int,
+    /// Watch mask.
+    pub mask: u32,
+    /// Cookie to synchronize the two events of a rename.
+    pub cookie: u32,
+    /// Length (including nuls) of the name.
+    pub len: u32,
+}
+static_assertions::assert_eq_size!(linux_inotify_event, [u8; 16]);
+
+#[allow(non_camel_case_types)]
+pub type inotify_event = linux_inotify_event;
+unsafe impl shadow_pod::Pod for inotify_event {}
@@ -60,6 +60,7 @@ pub mod exit;
 pub mod fcntl;
 pub mod futex;
 pub mod inet;
+pub mod inotify;
 pub mod ioctls;
 pub mod ldt;
 pub mod limits;
@@ -5,7 +5,7 @@ use num_enum::{IntoPrimitive, TryFromPrimitive};
 use shadow_pod::Pod;
 use vasi::VirtualAddressSpaceIndependent;
 
-use crate::bindings::{self, linux_sigval};
+use crate::bindings;
 use crate::const_conversions;
 use crate::const_conversions::i32_from_u32_allowing_wraparound;
 use crate::errno::Errno;
@@ -574,7 +574,12 @@ impl siginfo_t {
         }
     }
 
-    pub fn new_for_timer(signal: Signal, timer_id: i32, overrun: i32) -> Self {
+    pub fn new_for_timer(
+        signal: Signal,
+        timer_id: i32,
+        overrun: i32,
+        sigval: linux_sigval,
+    ) -> Self {
         // sigaction(2):
         // > Signals sent by POSIX.1b timers (since Linux 2.6) fill in si_overrun and
         // > si_timerid.  The si_timerid field is  an  internal ID  used by the kernel
@@ -591,7 +596,7 @@ impl siginfo_t {
                     l_timer: SigInfoDetailsTimer {
                         l_tid: timer_id,
                         l_overrun: overrun,
-                        l_sigval: core::mem::zeroed(),
+                        l_sigval: sigval,
                         l_sys_private: 0,
                     },
                 },
@@ -1447,6 +1452,61 @@ mod rt_sigaction_tests {
     }
 }
 
+pub use bindings::linux_sigval;
+#[allow(non_camel_case_types)]
+pub type sigval = linux_sigval;
+
+/// How a `sigevent` requests notification. See `sigevent(7)`.
+#[allow(non_camel_case_types)]
+#[repr(i32)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, TryFromPrimitive, IntoPrimitive)]
+pub enum SigevNotify {
+    SIGEV_SIGNAL = const_conversions::i32_from_u32(bindings::LINUX_SIGEV_SIGNAL),
+    SIGEV_NONE = const_conversions::i32_from_u32(bindings::LINUX_SIGEV_NONE),
+    SIGEV_THREAD = const_conversions::i32_from_u32(bindings::LINUX_SIGEV_THREAD),
+    SIGEV_THREAD_ID = const_conversions::i32_from_u32(bindings::LINUX_SIGEV_THREAD_ID),
+}
+
+// Manually translated from asm-generic/siginfo.h, where bindgen doesn't
+// generate the anonymous `_sigev_un` union. Only the `_tid` member is used by
+// the kernel; the rest is padding up to `SIGEV_MAX_SIZE`.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct linux_sigevent {
+    pub sigev_value: linux_sigval,
+    pub sigev_signo: core::ffi::c_int,
+    pub sigev_notify: core::ffi::c_int,
+    /// Target thread for `SIGEV_THREAD_ID`.
+    pub sigev_notify_thread_id: core::ffi::c_int,
+    pub _pad: [core::ffi::c_int; 11],
+}
+static_assertions::assert_eq_size!(
+    linux_sigevent,
+    [u8; bindings::LINUX_SIGEV_MAX_SIZE as usize]
+);
+
+impl core::fmt::Debug for linux_sigevent {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("linux_sigevent")
+            .field("sigev_value", &self.sigev_value)
+            .field("sigev_signo", &self.sigev_signo)
+            .field("sigev_notify", &self.sigev_notify)
+            .field("sigev_notify_thread_id", &self.sigev_notify_thread_id)
+            .finish_non_exhaustive()
+    }
+}
+
+#[allow(non_camel_case_types)]
+pub type sigevent = linux_sigevent;
+unsafe impl Pod for sigevent {}
+
+impl sigevent {
+    pub fn notify(&self) -> Result<SigevNotify, i32> {
+        SigevNotify::try_from(self.sigev_notify).map_err(|e| e.number)
+    }
+}
+
 pub use bindings::linux_stack_t;
 #[allow(non_camel_case_types)]
 pub type stack_t = linux_stack_t;
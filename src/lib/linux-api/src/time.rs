@@ -45,6 +45,14 @@ bitflags::bitflags! {
     }
 }
 
+bitflags::bitflags! {
+    /// Valid flags passed to `timer_settime(2)`.
+    #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+    pub struct TimerSettimeFlags: i32 {
+        const TIMER_ABSTIME = const_conversions::i32_from_u32(bindings::LINUX_TIMER_ABSTIME);
+    }
+}
+
 /// Interval timers
 #[derive(Debug, Copy, Clone, Eq, PartialEq, IntoPrimitive, TryFromPrimitive)]
 // getitimer takes `int`:
@@ -83,6 +91,123 @@ pub type kernel_old_timeval = linux___kernel_old_timeval;
 unsafe impl shadow_pod::Pod for kernel_old_timeval {}
 unsafe impl vasi::VirtualAddressSpaceIndependent for kernel_old_timeval {}
 
+// Manually translated from linux/timex.h, which we don't pass to bindgen since
+// it pulls in conflicting definitions. The anonymous `int :32` bitfields are
+// translated to explicit padding fields.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct linux___kernel_timex_timeval {
+    pub tv_sec: bindings::linux___kernel_time64_t,
+    pub tv_usec: core::ffi::c_longlong,
+}
+
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct linux___kernel_timex {
+    /// Mode selector
+    pub modes: core::ffi::c_uint,
+    pub _pad0: core::ffi::c_int,
+    /// Time offset (usec, or nsec with `ADJ_NANO`)
+    pub offset: core::ffi::c_longlong,
+    /// Frequency offset (scaled ppm)
+    pub freq: core::ffi::c_longlong,
+    /// Maximum error (usec)
+    pub maxerror: core::ffi::c_longlong,
+    /// Estimated error (usec)
+    pub esterror: core::ffi::c_longlong,
+    /// Clock command/status
+    pub status: core::ffi::c_int,
+    pub _pad1: core::ffi::c_int,
+    /// PLL time constant
+    pub constant: core::ffi::c_longlong,
+    /// Clock precision (usec) (read only)
+    pub precision: core::ffi::c_longlong,
+    /// Clock frequency tolerance (ppm) (read only)
+    pub tolerance: core::ffi::c_longlong,
+    /// Current time (read only, except for `ADJ_SETOFFSET`)
+    pub time: linux___kernel_timex_timeval,
+    /// usecs between clock ticks
+    pub tick: core::ffi::c_longlong,
+    /// PPS frequency (scaled ppm) (read only)
+    pub ppsfreq: core::ffi::c_longlong,
+    /// PPS jitter (usec) (read only)
+    pub jitter: core::ffi::c_longlong,
+    /// Interval duration (s) (shift) (read only)
+    pub shift: core::ffi::c_int,
+    pub _pad2: core::ffi::c_int,
+    /// PPS stability (scaled ppm) (read only)
+    pub stabil: core::ffi::c_longlong,
+    /// Jitter limit exceeded (read only)
+    pub jitcnt: core::ffi::c_longlong,
+    /// Calibration intervals (read only)
+    pub calcnt: core::ffi::c_longlong,
+    /// Calibration errors (read only)
+    pub errcnt: core::ffi::c_longlong,
+    /// Stability limit exceeded (read only)
+    pub stbcnt: core::ffi::c_longlong,
+    /// TAI offset (read only)
+    pub tai: core::ffi::c_int,
+    pub _pad3: [core::ffi::c_int; 11],
+}
+static_assertions::assert_eq_size!(linux___kernel_timex, [u8; 208]);
+
+#[allow(non_camel_case_types)]
+pub type timex = linux___kernel_timex;
+unsafe impl shadow_pod::Pod for timex {}
+
+bitflags::bitflags! {
+    /// Mode bits of [`timex::modes`], passed to `adjtimex(2)` and `clock_adjtime(2)`.
+    #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+    pub struct AdjtimexModes: u32 {
+        const ADJ_OFFSET = 0x0001;
+        const ADJ_FREQUENCY = 0x0002;
+        const ADJ_MAXERROR = 0x0004;
+        const ADJ_ESTERROR = 0x0008;
+        const ADJ_STATUS = 0x0010;
+        const ADJ_TIMECONST = 0x0020;
+        const ADJ_TAI = 0x0080;
+        const ADJ_SETOFFSET = 0x0100;
+        const ADJ_MICRO = 0x1000;
+        const ADJ_NANO = 0x2000;
+        const ADJ_TICK = 0x4000;
+        const ADJ_OFFSET_SINGLESHOT = 0x8001;
+        const ADJ_OFFSET_SS_READ = 0xa001;
+    }
+}
+
+bitflags::bitflags! {
+    /// Status bits of [`timex::status`].
+    #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+    pub struct TimexStatus: i32 {
+        const STA_PLL = 0x0001;
+        const STA_PPSFREQ = 0x0002;
+        const STA_PPSTIME = 0x0004;
+        const STA_FLL = 0x0008;
+        const STA_INS = 0x0010;
+        const STA_DEL = 0x0020;
+        const STA_UNSYNC = 0x0040;
+        const STA_FREQHOLD = 0x0080;
+        const STA_PPSSIGNAL = 0x0100;
+        const STA_PPSJITTER = 0x0200;
+        const STA_PPSWANDER = 0x0400;
+        const STA_PPSERROR = 0x0800;
+        const STA_CLOCKERR = 0x1000;
+        const STA_NANO = 0x2000;
+        const STA_MODE = 0x4000;
+        const STA_CLK = 0x8000;
+    }
+}
+
+/// Clock states returned by `adjtimex(2)` and `clock_adjtime(2)`.
+pub const TIME_OK: i32 = 0;
+pub const TIME_INS: i32 = 1;
+pub const TIME_DEL: i32 = 2;
+pub const TIME_OOP: i32 = 3;
+pub const TIME_WAIT: i32 = 4;
+pub const TIME_ERROR: i32 = 5;
+
 pub fn clock_gettime_raw(clockid: linux___kernel_clockid_t) -> Result<timespec, Errno> {
     let mut t = shadow_pod::zeroed();
     unsafe { syscall!(linux_syscall::SYS_clock_gettime, clockid, &mut t) }
@@ -0,0 +1,241 @@
+//! A host's local view of simulated time.
+//!
+//! Each host has its own clocks, which may disagree with the simulator's true
+//! time. We model two kinds of disagreement:
+//!
+//! * A *drift rate*, which makes all of the host's clocks run slightly faster
+//!   or slower than true time, as a real oscillator would.
+//! * A *realtime offset*, which only applies to `CLOCK_REALTIME` and its
+//!   relatives. This models an initial clock error, and can be stepped over
+//!   time (e.g. by a scheduled step change).
+//!
+//! The simulator itself always orders events using true time; these
+//! conversions are only applied to values reported to (or provided by) managed
+//! processes.
+
+use core::sync::atomic::{AtomicI64, Ordering};
+
+use linux_api::time::ClockId;
+use vasi::VirtualAddressSpaceIndependent;
+
+use crate::emulated_time::EmulatedTime;
+use crate::simulation_time::SimulationTime;
+
+const NANOS_PER_SEC: i128 = 1_000_000_000;
+
+/// Whether a clock is subject to the host's realtime offset, or only to its
+/// drift rate.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ClockKind {
+    Realtime,
+    Monotonic,
+}
+
+impl From<ClockId> for ClockKind {
+    fn from(id: ClockId) -> Self {
+        match id {
+            ClockId::CLOCK_REALTIME
+            | ClockId::CLOCK_REALTIME_COARSE
+            | ClockId::CLOCK_REALTIME_ALARM
+            | ClockId::CLOCK_TAI => ClockKind::Realtime,
+            _ => ClockKind::Monotonic,
+        }
+    }
+}
+
+#[derive(VirtualAddressSpaceIndependent)]
+#[repr(C)]
+pub struct HostClock {
+    /// Offset of the realtime clocks from the (drifted) monotonic clocks, in
+    /// nanoseconds.
+    realtime_offset_nanos: AtomicI64,
+    /// Rate at which the host's clocks drift from true time, in parts per
+    /// billion. Must be greater than `-1_000_000_000`.
+    drift_ppb: i64,
+}
+
+impl HostClock {
+    pub fn new(realtime_offset_nanos: i64, drift_ppb: i64) -> Self {
+        assert!(drift_ppb > -(NANOS_PER_SEC as i64));
+        Self {
+            realtime_offset_nanos: AtomicI64::new(realtime_offset_nanos),
+            drift_ppb,
+        }
+    }
+
+    /// A clock that always agrees with true time.
+    pub fn new_exact() -> Self {
+        Self::new(0, 0)
+    }
+
+    pub fn drift_ppb(&self) -> i64 {
+        self.drift_ppb
+    }
+
+    pub fn realtime_offset_nanos(&self) -> i64 {
+        self.realtime_offset_nanos.load(Ordering::Relaxed)
+    }
+
+    pub fn set_realtime_offset_nanos(&self, offset: i64) {
+        self.realtime_offset_nanos.store(offset, Ordering::Relaxed)
+    }
+
+    /// Step the realtime clocks by `delta` nanoseconds.
+    pub fn step_realtime_nanos(&self, delta: i64) {
+        self.realtime_offset_nanos.fetch_add(delta, Ordering::Relaxed);
+    }
+
+    /// Set the realtime clocks so that they read `local` at true time `t`, and
+    /// return the resulting change of the realtime offset. The monotonic
+    /// clocks are unaffected.
+    pub fn set_realtime(&self, t: EmulatedTime, local: EmulatedTime) -> i64 {
+        let monotonic = self.local_time(ClockKind::Monotonic, t);
+        let offset = nanos_since_epoch(local) - nanos_since_epoch(monotonic);
+        let offset = offset.clamp(i64::MIN.into(), i64::MAX.into()) as i64;
+        let old_offset = self.realtime_offset_nanos.swap(offset, Ordering::Relaxed);
+        offset.saturating_sub(old_offset)
+    }
+
+    /// The value of a clock of kind `kind` at true time `t`.
+    pub fn local_time(&self, kind: ClockKind, t: EmulatedTime) -> EmulatedTime {
+        let mut nanos = nanos_since_epoch(t) + self.drift_nanos_at(t);
+        if kind == ClockKind::Realtime {
+            nanos += i128::from(self.realtime_offset_nanos());
+        }
+        emulated_time_from_nanos(nanos)
+    }
+
+    /// The true time at which a clock of kind `kind` reaches `local`. This is
+    /// the inverse of [`HostClock::local_time`], e.g. for converting absolute
+    /// deadlines provided by managed processes.
+    pub fn true_time(&self, kind: ClockKind, local: EmulatedTime) -> EmulatedTime {
+        let mut nanos = nanos_since_epoch(local);
+        if kind == ClockKind::Realtime {
+            nanos -= i128::from(self.realtime_offset_nanos());
+        }
+        if self.drift_ppb == 0 {
+            return emulated_time_from_nanos(nanos);
+        }
+        let start = nanos_since_epoch(EmulatedTime::SIMULATION_START);
+        let local_elapsed = nanos - start;
+        if local_elapsed <= 0 {
+            // Drift is only accumulated after the start of the simulation.
+            return emulated_time_from_nanos(nanos);
+        }
+        let true_elapsed =
+            local_elapsed * NANOS_PER_SEC / (NANOS_PER_SEC + i128::from(self.drift_ppb));
+        emulated_time_from_nanos(start + true_elapsed)
+    }
+
+    /// The duration on the host's clocks of the true duration `d`.
+    pub fn local_duration(&self, d: SimulationTime) -> SimulationTime {
+        if self.drift_ppb == 0 {
+            return d;
+        }
+        let nanos = i128::try_from(d.as_nanos()).unwrap();
+        let nanos = nanos + nanos * i128::from(self.drift_ppb) / NANOS_PER_SEC;
+        SimulationTime::from_nanos(u64::try_from(nanos.max(0)).unwrap_or(u64::MAX))
+    }
+
+    /// Accumulated drift at true time `t`, in nanoseconds.
+    fn drift_nanos_at(&self, t: EmulatedTime) -> i128 {
+        if self.drift_ppb == 0 {
+            return 0;
+        }
+        let elapsed = t.saturating_duration_since(&EmulatedTime::SIMULATION_START);
+        let elapsed = i128::try_from(elapsed.as_nanos()).unwrap();
+        elapsed * i128::from(self.drift_ppb) / NANOS_PER_SEC
+    }
+}
+
+fn nanos_since_epoch(t: EmulatedTime) -> i128 {
+    i128::try_from(t.duration_since(&EmulatedTime::UNIX_EPOCH).as_nanos()).unwrap()
+}
+
+fn emulated_time_from_nanos(nanos: i128) -> EmulatedTime {
+    let nanos = u64::try_from(nanos.max(0)).unwrap_or(u64::MAX);
+    EmulatedTime::UNIX_EPOCH.saturating_add(SimulationTime::from_nanos(nanos))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exact() {
+        let clock = HostClock::new_exact();
+        let t = EmulatedTime::SIMULATION_START + SimulationTime::from_secs(10);
+        assert_eq!(clock.local_time(ClockKind::Realtime, t), t);
+        assert_eq!(clock.local_time(ClockKind::Monotonic, t), t);
+        assert_eq!(clock.true_time(ClockKind::Realtime, t), t);
+    }
+
+    #[test]
+    fn test_offset_only_affects_realtime() {
+        let clock = HostClock::new(-1_000_000_000, 0);
+        let t = EmulatedTime::SIMULATION_START + SimulationTime::from_secs(10);
+        assert_eq!(
+            clock.local_time(ClockKind::Realtime, t),
+            t - SimulationTime::SECOND
+        );
+        assert_eq!(clock.local_time(ClockKind::Monotonic, t), t);
+
+        clock.step_realtime_nanos(3_000_000_000);
+        assert_eq!(
+            clock.local_time(ClockKind::Realtime, t),
+            t + SimulationTime::SECOND * 2
+        );
+    }
+
+    #[test]
+    fn test_set_realtime() {
+        let clock = HostClock::new(0, 100_000);
+        let t = EmulatedTime::SIMULATION_START + SimulationTime::from_secs(100);
+        let target = EmulatedTime::UNIX_EPOCH + SimulationTime::from_secs(1_700_000_000);
+        let delta = clock.set_realtime(t, target);
+        assert_eq!(
+            delta,
+            1_700_000_000_000_000_000 - 946_684_800_000_000_000 - 100_010_000_000
+        );
+        assert_eq!(clock.local_time(ClockKind::Realtime, t), target);
+        // The monotonic clocks keep running from where they were.
+        assert_eq!(
+            clock.local_time(ClockKind::Monotonic, t),
+            t + SimulationTime::from_millis(10)
+        );
+    }
+
+    #[test]
+    fn test_drift() {
+        // 100 ppm fast
+        let clock = HostClock::new(0, 100_000);
+        let t = EmulatedTime::SIMULATION_START + SimulationTime::from_secs(100);
+        let expected = t + SimulationTime::from_millis(10);
+        assert_eq!(clock.local_time(ClockKind::Monotonic, t), expected);
+        assert_eq!(clock.local_time(ClockKind::Realtime, t), expected);
+        assert_eq!(
+            clock.local_duration(SimulationTime::from_secs(100)),
+            SimulationTime::from_secs(100) + SimulationTime::from_millis(10)
+        );
+    }
+
+    #[test]
+    fn test_true_time_inverts_local_time() {
+        for (offset, drift) in [(0, 0), (5_000_000, 0), (0, -250_000), (-7_000_000, 42_000)] {
+            let clock = HostClock::new(offset, drift);
+            for secs in [0, 1, 60, 3600] {
+                let t = EmulatedTime::SIMULATION_START + SimulationTime::from_secs(secs);
+                for kind in [ClockKind::Realtime, ClockKind::Monotonic] {
+                    let local = clock.local_time(kind, t);
+                    let back = clock.true_time(kind, local);
+                    // Integer division may lose up to a nanosecond.
+                    assert!(
+                        back.saturating_duration_since(&t) <= SimulationTime::NANOSECOND
+                            && t.saturating_duration_since(&back) <= SimulationTime::NANOSECOND,
+                        "offset={offset} drift={drift} secs={secs} kind={kind:?}"
+                    );
+                }
+            }
+        }
+    }
+}
@@ -8,6 +8,7 @@ use vasi::VirtualAddressSpaceIndependent;
 
 pub mod emulated_time;
 pub mod explicit_drop;
+pub mod host_clock;
 pub mod ipc;
 pub mod notnull;
 pub mod option;
@@ -24,6 +24,10 @@ pub enum ShadowSyscallNum {
     // debugging purposes, so that it doesn't appear that the managed code
     // issues a SYS_sched_yield.
     shadow_yield = 1005,
+    // Made by the shim when the process's strace log is in the JSON format, which only Shadow
+    // writes, to log a syscall that the shim handled without Shadow. The arguments are the
+    // syscall's number, its return value, and whether it was run natively.
+    shadow_log_syscall = 1006,
 }
 
 impl TryFrom<linux_api::syscall::SyscallNum> for ShadowSyscallNum {
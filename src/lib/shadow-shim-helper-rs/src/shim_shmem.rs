@@ -4,6 +4,7 @@ use vasi::VirtualAddressSpaceIndependent;
 use vasi_sync::scmutex::SelfContainedMutex;
 
 use crate::HostId;
+use crate::host_clock::HostClock;
 use crate::option::FfiOption;
 use crate::{
     emulated_time::{AtomicEmulatedTime, EmulatedTime},
@@ -107,6 +108,9 @@ pub struct HostShmem {
     // Current simulation time.
     pub sim_time: AtomicEmulatedTime,
 
+    // The host's local clocks, which may be skewed relative to `sim_time`.
+    pub clock: HostClock,
+
     pub shim_log_level: logger::LogLevel,
 
     pub manager_shmem: ShMemBlockSerialized,
@@ -123,6 +127,7 @@ impl HostShmem {
         unblocked_vdso_latency: SimulationTime,
         shadow_pid: libc::pid_t,
         tsc_hz: u64,
+        clock: HostClock,
         shim_log_level: ::logger::LogLevel,
         manager_shmem: &ShMemBlock<ManagerShmem>,
     ) -> Self {
@@ -141,6 +146,7 @@ impl HostShmem {
             shadow_pid,
             tsc_hz,
             sim_time: AtomicEmulatedTime::new(EmulatedTime::MIN),
+            clock,
             shim_log_level,
             manager_shmem: manager_shmem.serialize(),
         }
@@ -174,6 +180,9 @@ pub struct ProcessShmem {
     /// Handle to shared memory for the Host
     pub host_shmem: ShMemBlockSerialized,
     pub strace_fd: FfiOption<libc::c_int>,
+    /// Whether the strace log is in the JSON format. The shim reports the syscalls that it
+    /// handles itself to Shadow with `shadow_log_syscall` instead of writing to `strace_fd`.
+    pub strace_json: bool,
 
     pub protected: RootedRefCell<ProcessShmemProtected>,
 }
@@ -185,11 +194,13 @@ impl ProcessShmem {
         host_shmem: ShMemBlockSerialized,
         host_id: HostId,
         strace_fd: Option<libc::c_int>,
+        strace_json: bool,
     ) -> Self {
         Self {
             host_id,
             host_shmem,
             strace_fd: strace_fd.into(),
+            strace_json,
             protected: RootedRefCell::new(
                 host_root,
                 ProcessShmemProtected {
@@ -440,6 +451,7 @@ pub mod export {
     use vasi_sync::scmutex::SelfContainedMutexGuard;
 
     use super::*;
+    use crate::host_clock::ClockKind;
     use crate::{emulated_time::CEmulatedTime, simulation_time::CSimulationTime};
 
     // Legacy type names; keeping the more verbose names for the C API, since
@@ -523,6 +535,26 @@ pub mod export {
         EmulatedTime::to_c_emutime(Some(host_mem.sim_time.load(Ordering::Relaxed)))
     }
 
+    /// Get the host's local time on clock `clockid` at true time `t`.
+    ///
+    /// # Safety
+    ///
+    /// Pointer args must be safely dereferenceable.
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C-unwind" fn shimshmem_getLocalTime(
+        host_mem: *const ShimShmemHost,
+        clockid: linux_api::time::linux___kernel_clockid_t,
+        t: CEmulatedTime,
+    ) -> CEmulatedTime {
+        let host_mem = unsafe { host_mem.as_ref().unwrap() };
+        let kind = match linux_api::time::ClockId::try_from(clockid) {
+            Ok(id) => ClockKind::from(id),
+            Err(_) => ClockKind::Monotonic,
+        };
+        let t = EmulatedTime::from_c_emutime(t).unwrap();
+        EmulatedTime::to_c_emutime(Some(host_mem.clock.local_time(kind, t)))
+    }
+
     /// # Safety
     ///
     /// Pointer args must be safely dereferenceable.
@@ -571,6 +603,17 @@ pub mod export {
         process_mem.strace_fd.unwrap_or(-1)
     }
 
+    /// # Safety
+    ///
+    /// Pointer args must be safely dereferenceable.
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C-unwind" fn shimshmem_getProcessStraceJson(
+        process: *const ShimShmemProcess,
+    ) -> bool {
+        let process_mem = unsafe { process.as_ref().unwrap() };
+        process_mem.strace_json
+    }
+
     /// # Safety
     ///
     /// Pointer args must be safely dereferenceable.
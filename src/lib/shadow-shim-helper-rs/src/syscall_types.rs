@@ -501,6 +501,18 @@ impl From<SyscallReg> for linux_api::close_range::CloseRangeFlags {
     }
 }
 
+impl From<SyscallReg> for linux_api::inotify::InotifyInitFlags {
+    fn from(reg: SyscallReg) -> Self {
+        Self::from_bits_retain(reg.into())
+    }
+}
+
+impl From<SyscallReg> for linux_api::inotify::InotifyMask {
+    fn from(reg: SyscallReg) -> Self {
+        Self::from_bits_retain(reg.into())
+    }
+}
+
 impl TryFrom<SyscallReg> for linux_api::time::ClockId {
     type Error = ();
     fn try_from(reg: SyscallReg) -> Result<Self, Self::Error> {
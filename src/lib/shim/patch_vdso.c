@@ -9,6 +9,7 @@
 #include <unistd.h>
 
 #include "lib/logger/logger.h"
+#include "lib/shim/shim_api.h"
 
 static void _getVdsoBounds(void** start, void** end) {
     assert(start);
@@ -144,14 +145,17 @@ static const Elf64_Sym* _findSymbol(const struct ParsedElf* parsedElf, const cha
     return NULL;
 }
 
+// The time functions are answered directly from the simulation time in shared
+// memory; see `lib/shim/src/vdso.rs`. Unlike going through `syscall`, this
+// doesn't trap through the seccomp filter and `SIGSYS` handler.
 static int _replacement_gettimeofday(void* arg1, void* arg2) {
-    return (int)syscall(SYS_gettimeofday, arg1, arg2);
+    return shim_vdso_gettimeofday(arg1, arg2);
 }
 
-static int _replacement_time(void* arg1) { return (int)syscall(SYS_time, arg1); }
+static long _replacement_time(void* arg1) { return shim_vdso_time(arg1); }
 
 static int _replacement_clock_gettime(void* arg1, void* arg2) {
-    return (int)syscall(SYS_clock_gettime, arg1, arg2);
+    return shim_vdso_clock_gettime((clockid_t)(intptr_t)arg1, arg2);
 }
 
 static int _replacement_getcpu(void* arg1, void* arg2, void* arg3) {
@@ -1,8 +1,9 @@
 #ifndef SHIM_PATCH_VDSO_H
 #define SHIM_PATCH_VDSO_H
 
-// Hot-patch VDSO functions in the current-running programming to call the
-// `syscall(2)` function, which can be intercepted via LD_PRELOAD. 
+// Hot-patch VDSO functions in the current-running programming. The time
+// functions are redirected to the shim's shared-memory implementations, and the
+// rest to the `syscall(2)` function, which can be intercepted via LD_PRELOAD.
 void patch_vdso(void* vdsoBase);
 
 #endif
\ No newline at end of file
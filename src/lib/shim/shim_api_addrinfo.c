@@ -88,8 +88,8 @@ static int _getaddrinfo_service(in_port_t* port, const char* service,
 // Creates an `addrinfo` pointing to `addr`, and adds it to the linked list
 // specified by `head` and `tail`. An empty list can be passed in by setting
 // `*head` and `*tail` to NULL.
-static void _getaddrinfo_append(struct addrinfo** head, struct addrinfo** tail, int socktype,
-                                struct sockaddr* addr, socklen_t addrlen) {
+static void _getaddrinfo_append(struct addrinfo** head, struct addrinfo** tail, int family,
+                                int socktype, struct sockaddr* addr, socklen_t addrlen) {
     int protocol = 0;
     if (socktype == SOCK_DGRAM) {
         protocol = IPPROTO_UDP;
@@ -102,7 +102,7 @@ static void _getaddrinfo_append(struct addrinfo** head, struct addrinfo** tail,
     }
     struct addrinfo* new_tail = malloc(sizeof(*new_tail));
     *new_tail = (struct addrinfo){.ai_flags = 0,
-                                  .ai_family = AF_INET,
+                                  .ai_family = family,
                                   .ai_socktype = socktype,
                                   .ai_protocol = protocol,
                                   .ai_addrlen = addrlen,
@@ -125,25 +125,49 @@ static void _getaddrinfo_appendv4(struct addrinfo** head, struct addrinfo** tail
     if (add_tcp) {
         struct sockaddr_in* sai = malloc(sizeof(*sai));
         *sai = (struct sockaddr_in){.sin_family = AF_INET, .sin_port = port, .sin_addr = {s_addr}};
-        _getaddrinfo_append(head, tail, SOCK_STREAM, (struct sockaddr*)sai, sizeof(*sai));
+        _getaddrinfo_append(head, tail, AF_INET, SOCK_STREAM, (struct sockaddr*)sai, sizeof(*sai));
     }
     if (add_udp) {
         struct sockaddr_in* sai = malloc(sizeof(*sai));
         *sai = (struct sockaddr_in){.sin_family = AF_INET, .sin_port = port, .sin_addr = {s_addr}};
-        _getaddrinfo_append(head, tail, SOCK_DGRAM, (struct sockaddr*)sai, sizeof(*sai));
+        _getaddrinfo_append(head, tail, AF_INET, SOCK_DGRAM, (struct sockaddr*)sai, sizeof(*sai));
     }
     if (add_raw) {
         struct sockaddr_in* sai = malloc(sizeof(*sai));
         *sai = (struct sockaddr_in){.sin_family = AF_INET, .sin_port = port, .sin_addr = {s_addr}};
-        _getaddrinfo_append(head, tail, SOCK_RAW, (struct sockaddr*)sai, sizeof(*sai));
+        _getaddrinfo_append(head, tail, AF_INET, SOCK_RAW, (struct sockaddr*)sai, sizeof(*sai));
     }
 }
 
-// Looks for matching IPv4 addresses in /etc/hosts and them to the list
-// specified by `head` and `tail`.
-static void _getaddrinfo_add_matching_hosts_ipv4(struct addrinfo** head, struct addrinfo** tail,
-                                                 const char* node, bool add_tcp, bool add_udp,
-                                                 bool add_raw, in_port_t port) {
+// IPv6 wrapper for _getaddrinfo_append. Appends an entry for the address and
+// port for each requested socket type.
+static void _getaddrinfo_appendv6(struct addrinfo** head, struct addrinfo** tail, bool add_tcp,
+                                  bool add_udp, bool add_raw, struct in6_addr addr,
+                                  in_port_t port) {
+    if (add_tcp) {
+        struct sockaddr_in6* sai = malloc(sizeof(*sai));
+        *sai = (struct sockaddr_in6){.sin6_family = AF_INET6, .sin6_port = port, .sin6_addr = addr};
+        _getaddrinfo_append(head, tail, AF_INET6, SOCK_STREAM, (struct sockaddr*)sai, sizeof(*sai));
+    }
+    if (add_udp) {
+        struct sockaddr_in6* sai = malloc(sizeof(*sai));
+        *sai = (struct sockaddr_in6){.sin6_family = AF_INET6, .sin6_port = port, .sin6_addr = addr};
+        _getaddrinfo_append(head, tail, AF_INET6, SOCK_DGRAM, (struct sockaddr*)sai, sizeof(*sai));
+    }
+    if (add_raw) {
+        struct sockaddr_in6* sai = malloc(sizeof(*sai));
+        *sai = (struct sockaddr_in6){.sin6_family = AF_INET6, .sin6_port = port, .sin6_addr = addr};
+        _getaddrinfo_append(head, tail, AF_INET6, SOCK_RAW, (struct sockaddr*)sai, sizeof(*sai));
+    }
+}
+
+// Looks for matching addresses of the given `family` (AF_INET or AF_INET6) in
+// /etc/hosts and them to the list specified by `head` and `tail`.
+static void _getaddrinfo_add_matching_hosts(struct addrinfo** head, struct addrinfo** tail,
+                                            const char* node, int family, bool add_tcp,
+                                            bool add_udp, bool add_raw, in_port_t port) {
+    assert(family == AF_INET || family == AF_INET6);
+
     // TODO: Parse hosts file once and keep it in an efficiently-searchable
     // in-memory format.
     GError* error = NULL;
@@ -165,9 +189,12 @@ static void _getaddrinfo_add_matching_hosts_ipv4(struct addrinfo** head, struct
 
     {
         gchar* escaped_node = g_regex_escape_string(node, -1);
-        // Build a regex to match an IPv4 address entry for the given `node` in
-        // /etc/hosts. See HOSTS(5) for format specification.
-        int rv = asprintf(&pattern, "^(\\d+\\.\\d+\\.\\d+\\.\\d+)[^#\n]*\\b%s\\b", escaped_node);
+        // Build a regex to match an address entry of the requested family for
+        // the given `node` in /etc/hosts. See HOSTS(5) for format
+        // specification.
+        const char* address_pattern = family == AF_INET ? "\\d+\\.\\d+\\.\\d+\\.\\d+"
+                                                        : "[[:xdigit:]]*:[[:xdigit:]:.]*";
+        int rv = asprintf(&pattern, "^(%s)[^#\n]*\\b%s\\b", address_pattern, escaped_node);
         g_free(escaped_node);
         if (rv < 0) {
             panic("asprintf failed: %d", rv);
@@ -198,12 +225,22 @@ static void _getaddrinfo_add_matching_hosts_ipv4(struct addrinfo** head, struct
         gchar* address_string = g_match_info_fetch(match_info, 1);
         trace("Node:%s -> address string:%s", node, address_string);
         assert(address_string != NULL);
-        uint32_t addr;
-        int rv = inet_pton(AF_INET, address_string, &addr);
-        if (rv != 1) {
-            panic("Bad address in /etc/hosts: %s\n", address_string);
+        if (family == AF_INET) {
+            uint32_t addr;
+            int rv = inet_pton(AF_INET, address_string, &addr);
+            if (rv != 1) {
+                panic("Bad address in /etc/hosts: %s\n", address_string);
+            } else {
+                _getaddrinfo_appendv4(head, tail, add_tcp, add_udp, add_raw, addr, port);
+            }
         } else {
-            _getaddrinfo_appendv4(head, tail, add_tcp, add_udp, add_raw, addr, port);
+            struct in6_addr addr;
+            int rv = inet_pton(AF_INET6, address_string, &addr);
+            if (rv != 1) {
+                panic("Bad address in /etc/hosts: %s\n", address_string);
+            } else {
+                _getaddrinfo_appendv6(head, tail, add_tcp, add_udp, add_raw, addr, port);
+            }
         }
         g_free(address_string);
     }
@@ -309,10 +346,9 @@ int shimc_api_getaddrinfo(const char* node, const char* service, const struct ad
     // returned only if the local system has at least one IPv6 address
     // configured."
     //
-    // Determining what kind of addresses the local system has configured is
-    // unimplemented. For now we assume it has IPv4 and not IPv6.
+    // Every simulated host has both an IPv4 and an IPv6 address.
     const bool system_has_an_ipv4_address = true;
-    const bool system_has_an_ipv6_address = false;
+    const bool system_has_an_ipv6_address = true;
 
     // "There are several reasons why the linked list may have more than one
     // addrinfo structure, including: the network host is ... accessible  over
@@ -333,6 +369,14 @@ int shimc_api_getaddrinfo(const char* node, const char* service, const struct ad
         return EAI_ADDRFAMILY;
     }
 
+    // Unlike glibc, we only return IPv6 wildcard, loopback, and hostname
+    // results when the caller explicitly asks for AF_INET6. Applications that
+    // bind every returned address typically set IPV6_V6ONLY on the IPv6
+    // socket, and Shadow can't bind an IPv6-only socket to a port that an IPv4
+    // socket is already using (the host's IPv6 address is an alias of its IPv4
+    // address). Numeric IPv6 addresses are still returned for AF_UNSPEC.
+    const bool lookup_ipv6 = add_ipv6 && hints->ai_family == AF_INET6;
+
     // *res will be the head of the linked lists of results. For efficiency we
     // also keep track of the tail of the list.
     *res = NULL;
@@ -351,8 +395,8 @@ int shimc_api_getaddrinfo(const char* node, const char* service, const struct ad
                 _getaddrinfo_appendv4(
                     res, &tail, add_tcp, add_udp, add_raw, ntohl(INADDR_ANY), port);
             }
-            if (add_ipv6) {
-                // TODO: IPv6
+            if (lookup_ipv6) {
+                _getaddrinfo_appendv6(res, &tail, add_tcp, add_udp, add_raw, in6addr_any, port);
             }
         } else {
             // "If the AI_PASSIVE flag is not set in hints.ai_flags, then the
@@ -365,8 +409,9 @@ int shimc_api_getaddrinfo(const char* node, const char* service, const struct ad
                 _getaddrinfo_appendv4(
                     res, &tail, add_tcp, add_udp, add_raw, ntohl(INADDR_LOOPBACK), port);
             }
-            if (add_ipv6) {
-                // TODO: IPv6
+            if (lookup_ipv6) {
+                _getaddrinfo_appendv6(
+                    res, &tail, add_tcp, add_udp, add_raw, in6addr_loopback, port);
             }
         }
         // We've finished adding all relevant addresses.
@@ -379,7 +424,10 @@ int shimc_api_getaddrinfo(const char* node, const char* service, const struct ad
 
     // "`node` specifies either a numerical network address..."
     if (add_ipv6) {
-        // TODO: try parsing as IPv6
+        struct in6_addr addr;
+        if (inet_pton(AF_INET6, node, &addr) == 1) {
+            _getaddrinfo_appendv6(res, &tail, add_tcp, add_udp, add_raw, addr, port);
+        }
     }
     if (add_ipv4) {
         uint32_t addr;
@@ -412,8 +460,14 @@ int shimc_api_getaddrinfo(const char* node, const char* service, const struct ad
     // order in which to try lookups.  We just hard-code trying `files` first
     // (and for now, only). For hosts lookups, the corresponding file is
     // /etc/hosts. See NSSWITCH.CONF(5).
-    if (add_ipv6) {
-        // TODO: look for IPv6 addresses in /etc/hosts.
+    if (lookup_ipv6) {
+        if (strcasecmp(node, "localhost") == 0) {
+            _getaddrinfo_appendv6(res, &tail, add_tcp, add_udp, add_raw, in6addr_loopback, port);
+        } else {
+            // Shadow writes an IPv6 entry for every host to /etc/hosts.
+            _getaddrinfo_add_matching_hosts(
+                res, &tail, node, AF_INET6, add_tcp, add_udp, add_raw, port);
+        }
     }
     if (add_ipv4) {
         // Try first to avoid scanning the /etc/hosts file.
@@ -426,7 +480,8 @@ int shimc_api_getaddrinfo(const char* node, const char* service, const struct ad
             warning("shadow_hostname_to_addr_ipv4 syscall failed for name %s, falling back to less "
                     "efficient scan of '/etc/hosts' file.",
                     node);
-            _getaddrinfo_add_matching_hosts_ipv4(res, &tail, node, add_tcp, add_udp, add_raw, port);
+            _getaddrinfo_add_matching_hosts(
+                res, &tail, node, AF_INET, add_tcp, add_udp, add_raw, port);
         }
     }
 
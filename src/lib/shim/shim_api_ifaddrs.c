@@ -12,6 +12,7 @@
 #include <netdb.h>
 #include <stdarg.h>
 #include <stddef.h>
+#include <stdint.h>
 #include <stdlib.h>
 #include <string.h>
 #include <sys/socket.h>
@@ -20,6 +21,33 @@
 
 void shimc_api_freeifaddrs(struct ifaddrs* ifa);
 
+/* Returns a new ifaddrs entry with an IPv6 address and a netmask of `prefix_len` bits. */
+static struct ifaddrs* _getifaddrs_new_ipv6(const char* name, unsigned int flags,
+                                            const struct in6_addr* addr, int prefix_len) {
+    struct ifaddrs* i = calloc(1, sizeof(struct ifaddrs));
+    i->ifa_flags = flags;
+    i->ifa_name = strdup(name);
+
+    struct sockaddr_in6* sin6_addr = calloc(1, sizeof(struct sockaddr_in6));
+    sin6_addr->sin6_family = AF_INET6;
+    sin6_addr->sin6_addr = *addr;
+    i->ifa_addr = (struct sockaddr*)sin6_addr;
+
+    struct sockaddr_in6* sin6_netmask = calloc(1, sizeof(struct sockaddr_in6));
+    sin6_netmask->sin6_family = AF_INET6;
+    for (int byte = 0; byte < 16; byte++) {
+        int bits = prefix_len - (byte * 8);
+        if (bits >= 8) {
+            sin6_netmask->sin6_addr.s6_addr[byte] = 0xff;
+        } else if (bits > 0) {
+            sin6_netmask->sin6_addr.s6_addr[byte] = (uint8_t)(0xff << (8 - bits));
+        }
+    }
+    i->ifa_netmask = (struct sockaddr*)sin6_netmask;
+
+    return i;
+}
+
 int shimc_api_getifaddrs(struct ifaddrs** ifap) {
     if (!ifap) {
         errno = EFAULT;
@@ -88,6 +116,29 @@ int shimc_api_getifaddrs(struct ifaddrs** ifap) {
         }
     }
 
+    /* the IPv6 addresses follow the IPv4 addresses, as on Linux */
+    struct ifaddrs* tail = i;
+    while (tail->ifa_next != NULL) {
+        tail = tail->ifa_next;
+    }
+
+    tail->ifa_next =
+        _getifaddrs_new_ipv6("lo", (IFF_UP | IFF_RUNNING | IFF_LOOPBACK), &in6addr_loopback, 128);
+    tail = tail->ifa_next;
+
+    if (gethostname(hostname_buf, HOST_NAME_MAX) == 0) {
+        struct addrinfo hints = {.ai_family = AF_INET6, .ai_socktype = SOCK_STREAM};
+        struct addrinfo* host_ai;
+
+        /* lookup the default IPv6 address for the host, which has a /64 prefix */
+        if (getaddrinfo(hostname_buf, NULL, &hints, &host_ai) == 0) {
+            tail->ifa_next = _getifaddrs_new_ipv6(
+                "eth0", (IFF_UP | IFF_RUNNING),
+                &((struct sockaddr_in6*)host_ai->ai_addr)->sin6_addr, 64);
+            freeaddrinfo(host_ai);
+        }
+    }
+
     *ifap = i;
     return 0;
 }
@@ -32,6 +32,12 @@ static CEmulatedTime _shim_sys_get_time() {
     return shimshmem_getEmulatedTime(mem);
 }
 
+// The host's local view of the current time on clock `clk_id`, which may be
+// skewed relative to the simulation time.
+static CEmulatedTime _shim_sys_get_local_time(clockid_t clk_id) {
+    return shimshmem_getLocalTime(shim_hostSharedMem(), clk_id, _shim_sys_get_time());
+}
+
 uint64_t shim_sys_get_simtime_nanos() {
     return emutime_sub_emutime(_shim_sys_get_time(), EMUTIME_SIMULATION_START) /
            SIMTIME_ONE_NANOSECOND;
@@ -70,8 +76,6 @@ bool shim_sys_handle_syscall_locally(long syscall_num, long* rv, va_list args) {
         case SYS_clock_gettime: {
             syscallName = "clock_gettime";
 
-            CEmulatedTime emulated_time = _shim_sys_get_time();
-
             trace("servicing syscall %ld:clock_gettime from the shim", syscall_num);
 
             clockid_t clk_id = va_arg(args, clockid_t);
@@ -81,6 +85,7 @@ bool shim_sys_handle_syscall_locally(long syscall_num, long* rv, va_list args) {
                 trace("found invalid clock id %ld", (long)clk_id);
                 *rv = -EINVAL;
             } else if (tp) {
+                CEmulatedTime emulated_time = _shim_sys_get_local_time(clk_id);
                 *tp = (struct timespec){
                     .tv_sec = emulated_time / SIMTIME_ONE_SECOND,
                     .tv_nsec = emulated_time % SIMTIME_ONE_SECOND,
@@ -98,7 +103,7 @@ bool shim_sys_handle_syscall_locally(long syscall_num, long* rv, va_list args) {
         case SYS_time: {
             syscallName = "time";
 
-            CEmulatedTime emulated_time = _shim_sys_get_time();
+            CEmulatedTime emulated_time = _shim_sys_get_local_time(LINUX_CLOCK_REALTIME);
             time_t now = emulated_time / SIMTIME_ONE_SECOND;
 
             trace("servicing syscall %ld:time from the shim", syscall_num);
@@ -117,7 +122,7 @@ bool shim_sys_handle_syscall_locally(long syscall_num, long* rv, va_list args) {
         case SYS_gettimeofday: {
             syscallName = "gettimeofday";
 
-            CEmulatedTime emulated_time = _shim_sys_get_time();
+            CEmulatedTime emulated_time = _shim_sys_get_local_time(LINUX_CLOCK_REALTIME);
             uint64_t micros = emulated_time / SIMTIME_ONE_MICROSECOND;
 
             trace("servicing syscall %ld:gettimeofday from the shim", syscall_num);
@@ -179,6 +184,12 @@ bool shim_sys_handle_syscall_locally(long syscall_num, long* rv, va_list args) {
         }
     }
 
+    if (shimshmem_getProcessStraceJson(shim_processSharedMem())) {
+        // Only Shadow writes the JSON strace format, so have it log the syscall for us. Like
+        // SHADOW_YIELD below, this is a Shadow syscall that is always passed through to Shadow.
+        syscall(SHADOW_SYSCALL_NUM_SHADOW_LOG_SYSCALL, syscall_num, *rv, 0);
+    }
+
     if (shimshmem_getModelUnblockedSyscallLatency(shim_hostSharedMem())) {
         ShimShmemHostLock* host_lock = shimshmemhost_lock(shim_hostSharedMem());
         shimshmem_incrementUnappliedCpuLatency(
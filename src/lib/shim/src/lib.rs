@@ -39,6 +39,7 @@ pub mod reinit_auxvec_random;
 pub mod shimlogger;
 pub mod syscall;
 pub mod tls;
+pub mod vdso;
 
 pub use shimlogger::export as shimlogger_export;
 
@@ -8,6 +8,7 @@ use linux_syscall::{Result64, syscall};
 use rustix::fd::BorrowedFd;
 use shadow_shim_helper_rs::emulated_time::EmulatedTime;
 use shadow_shim_helper_rs::option::FfiOption;
+use shadow_shim_helper_rs::shadow_syscalls::ShadowSyscallNum;
 use shadow_shim_helper_rs::shim_event::{
     ShimEventAddThreadRes, ShimEventSyscall, ShimEventSyscallComplete, ShimEventToShadow,
     ShimEventToShim,
@@ -15,7 +16,7 @@ use shadow_shim_helper_rs::shim_event::{
 use shadow_shim_helper_rs::syscall_types::{SyscallArgs, SyscallReg};
 use shadow_shim_helper_rs::util::time::TimeParts;
 
-use crate::{bindings, global_host_shmem, tls_ipc, tls_thread_shmem};
+use crate::{bindings, global_host_shmem, tls_ipc, tls_process_shmem, tls_thread_shmem};
 
 /// # Safety
 ///
@@ -120,8 +121,10 @@ pub(crate) unsafe fn emulated_syscall_event(
 
                 let rv = unsafe { native_syscall(&syscall_event.syscall_args) };
 
+                log_syscall_in_shadow(syscall_event.syscall_args.number, rv.into(), true);
+
                 if let FfiOption::Some(strace_fd) =
-                    crate::tls_process_shmem::with(|process| process.strace_fd)
+                    tls_process_shmem::with(|process| process.strace_fd)
                 {
                     let emulated_time = global_host_shmem::get()
                         .sim_time
@@ -179,6 +182,30 @@ pub(crate) unsafe fn emulated_syscall_event(
     }
 }
 
+/// Has Shadow write syscall `number`, which the shim handled without Shadow, to the process's
+/// strace log if the log is in the JSON format. The shim writes the text format itself, but only
+/// Shadow writes JSON records.
+pub(crate) fn log_syscall_in_shadow(number: i64, rv: i64, native: bool) {
+    if !tls_process_shmem::with(|process| process.strace_json) {
+        return;
+    }
+
+    let syscall_event = ShimEventSyscall {
+        syscall_args: SyscallArgs {
+            number: i64::from(u32::from(ShadowSyscallNum::shadow_log_syscall)),
+            args: [
+                number.into(),
+                rv.into(),
+                u64::from(native).into(),
+                0u64.into(),
+                0u64.into(),
+                0u64.into(),
+            ],
+        },
+    };
+    unsafe { emulated_syscall_event(None, &syscall_event) };
+}
+
 pub mod export {
     use crate::ExecutionContext;
 
@@ -0,0 +1,241 @@
+//! Emulation of the vDSO time functions.
+//!
+//! `patch_vdso.c` redirects the vDSO's `clock_gettime`, `gettimeofday`, and
+//! `time` entry points to the functions exported here. These answer directly
+//! from the simulation time and the host's clock model in [`HostShmem`],
+//! without executing a `syscall` instruction. This avoids trapping through the
+//! seccomp filter and the `SIGSYS` handler, which matters for managed programs
+//! (e.g. Go runtimes) that query the clock at a very high rate.
+//!
+//! Shadow itself is only involved when charging the configured vDSO latency
+//! would move time beyond the host's maximum runahead time, in which case we
+//! yield so that Shadow can reschedule the current thread.
+//!
+//! Like the real vDSO, these functions return `0` or a negated errno, rather
+//! than setting `errno`.
+//!
+//! [`HostShmem`]: shadow_shim_helper_rs::shim_shmem::HostShmem
+
+use core::fmt::Write;
+use core::sync::atomic::Ordering;
+
+use formatting_nostd::{BorrowedFdWriter, FormatBuffer};
+use linux_api::errno::Errno;
+use linux_api::time::ClockId;
+use rustix::fd::BorrowedFd;
+use shadow_shim_helper_rs::emulated_time::EmulatedTime;
+use shadow_shim_helper_rs::host_clock::ClockKind;
+use shadow_shim_helper_rs::option::FfiOption;
+use shadow_shim_helper_rs::shadow_syscalls::ShadowSyscallNum;
+use shadow_shim_helper_rs::shim_event::ShimEventSyscall;
+use shadow_shim_helper_rs::simulation_time::SimulationTime;
+use shadow_shim_helper_rs::syscall_types::SyscallArgs;
+use shadow_shim_helper_rs::util::time::TimeParts;
+
+use crate::{ExecutionContext, bindings, global_host_shmem, tls_process_shmem, tls_thread_shmem};
+
+/// Returns the current simulation time, and charges the configured vDSO
+/// latency to the current host.
+///
+/// The returned time is the time *before* the latency is charged, matching
+/// the behavior of `shim_sys_handle_syscall_locally`.
+fn now_and_charge_latency() -> EmulatedTime {
+    debug_assert_eq!(ExecutionContext::current(), ExecutionContext::Shadow);
+    let host = global_host_shmem::get();
+    let now = host.sim_time.load(Ordering::Relaxed);
+
+    if !host.model_unblocked_syscall_latency {
+        return now;
+    }
+
+    let mut host_lock = host.protected().lock();
+    host_lock.unapplied_cpu_latency += host.unblocked_vdso_latency;
+    if host_lock.unapplied_cpu_latency <= host.max_unapplied_cpu_latency {
+        return now;
+    }
+
+    // Try to apply the accumulated latency locally, without involving Shadow.
+    let new_time = now + host_lock.unapplied_cpu_latency;
+    if new_time <= host_lock.max_runahead_time {
+        host.sim_time.store(new_time, Ordering::Relaxed);
+        host_lock.unapplied_cpu_latency = SimulationTime::ZERO;
+        log::trace!(
+            "Reached max_unapplied_cpu_latency in vdso. Updated time locally. ({:?} until max)",
+            host_lock.max_runahead_time.duration_since(&new_time)
+        );
+        return now;
+    }
+    drop(host_lock);
+    drop(host);
+
+    // Moving time forward requires the current thread to be rescheduled.
+    // Shadow will notice the unapplied latency when handling this no-op
+    // syscall, as it does before executing any syscall.
+    log::trace!("Reached max_unapplied_cpu_latency in vdso. Yielding.");
+    let syscall_event = ShimEventSyscall {
+        syscall_args: SyscallArgs {
+            number: i64::from(u32::from(ShadowSyscallNum::shadow_yield)),
+            args: [0.into(); 6],
+        },
+    };
+    unsafe { crate::syscall::emulated_syscall_event(None, &syscall_event) };
+
+    now
+}
+
+/// Whether the fast path can be used from the execution context `prev`, which
+/// is the context that was active when the vDSO function was called.
+fn can_use_fast_path(prev: ExecutionContext) -> bool {
+    // Calls from the shim itself are expected to get native behavior, and
+    // early in initialization we don't have the host's shared memory yet.
+    prev == ExecutionContext::Application && global_host_shmem::try_get().is_some()
+}
+
+/// Handles the call via the regular syscall path, e.g. when the fast path
+/// isn't available.
+///
+/// # Safety
+///
+/// The syscall must be safe to make.
+unsafe fn fallback_syscall(prev: ExecutionContext, n: i64, arg1: i64, arg2: i64) -> i64 {
+    unsafe { bindings::shim_syscall(core::ptr::null_mut(), prev.into(), n, arg1, arg2) }
+}
+
+/// The host's local time on a clock of kind `kind` at true time `t`, as a
+/// duration since the Unix epoch.
+fn local_time_since_epoch(kind: ClockKind, t: EmulatedTime) -> SimulationTime {
+    let local = global_host_shmem::get().clock.local_time(kind, t);
+    local.duration_since(&EmulatedTime::UNIX_EPOCH)
+}
+
+/// Writes the call to the process's strace log, if there is one. Calls answered here never reach
+/// Shadow, so Shadow can't log them itself unless we report them (which we do for the JSON format).
+fn strace_log(name: &str, number: i64, rv: i64) {
+    crate::syscall::log_syscall_in_shadow(number, rv, false);
+
+    let FfiOption::Some(strace_fd) = tls_process_shmem::with(|process| process.strace_fd) else {
+        return;
+    };
+
+    let sim_time =
+        global_host_shmem::get().sim_time.load(Ordering::Relaxed) - EmulatedTime::SIMULATION_START;
+    let tid = tls_thread_shmem::with(|thread| thread.tid);
+    let parts = TimeParts::from_nanos(sim_time.as_nanos());
+    let mut buffer = FormatBuffer::<200>::new();
+    writeln!(
+        &mut buffer,
+        "{} [tid {}] {}(...) = {}",
+        parts.fmt_hr_min_sec_nano(),
+        tid,
+        name,
+        rv
+    )
+    .unwrap();
+
+    // SAFETY: file descriptor should be valid and open.
+    let strace_fd = unsafe { BorrowedFd::borrow_raw(strace_fd) };
+    let mut strace_file_writer = BorrowedFdWriter::new(strace_fd);
+    if let Err(e) = strace_file_writer.write_str(buffer.as_str()) {
+        log::warn!("Couldn't write to strace_fd:{strace_fd:?}: {e:?}");
+    }
+}
+
+mod export {
+    use super::*;
+
+    /// Replacement for `__vdso_clock_gettime`.
+    ///
+    /// # Safety
+    ///
+    /// `tp` must be NULL or dereferenceable.
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C-unwind" fn shim_vdso_clock_gettime(
+        clockid: linux_api::time::linux___kernel_clockid_t,
+        tp: *mut linux_api::time::timespec,
+    ) -> core::ffi::c_int {
+        let prev = ExecutionContext::Shadow.enter();
+        if !can_use_fast_path(prev.ctx()) {
+            let rv = unsafe {
+                fallback_syscall(
+                    prev.ctx(),
+                    libc::SYS_clock_gettime,
+                    clockid.into(),
+                    tp as i64,
+                )
+            };
+            return rv as core::ffi::c_int;
+        }
+
+        let rv = 'rv: {
+            let Ok(clockid) = ClockId::try_from(clockid) else {
+                log::trace!("found invalid clock id {clockid}");
+                break 'rv Errno::EINVAL.to_negated_i32();
+            };
+            let Some(tp) = (unsafe { tp.as_mut() }) else {
+                log::trace!("found NULL timespec pointer in clock_gettime");
+                break 'rv Errno::EFAULT.to_negated_i32();
+            };
+
+            let t = local_time_since_epoch(clockid.into(), now_and_charge_latency());
+            tp.tv_sec = t.as_secs().try_into().unwrap();
+            tp.tv_nsec = t.subsec_nanos().into();
+            0
+        };
+
+        strace_log("clock_gettime", libc::SYS_clock_gettime, rv.into());
+        rv
+    }
+
+    /// Replacement for `__vdso_gettimeofday`.
+    ///
+    /// # Safety
+    ///
+    /// `tv` must be NULL or dereferenceable.
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C-unwind" fn shim_vdso_gettimeofday(
+        tv: *mut linux_api::time::kernel_old_timeval,
+        tz: *mut core::ffi::c_void,
+    ) -> core::ffi::c_int {
+        let prev = ExecutionContext::Shadow.enter();
+        if !can_use_fast_path(prev.ctx()) {
+            let rv = unsafe {
+                fallback_syscall(prev.ctx(), libc::SYS_gettimeofday, tv as i64, tz as i64)
+            };
+            return rv as core::ffi::c_int;
+        }
+
+        let t = local_time_since_epoch(ClockKind::Realtime, now_and_charge_latency());
+        if let Some(tv) = unsafe { tv.as_mut() } {
+            tv.tv_sec = t.as_secs().try_into().unwrap();
+            tv.tv_usec = t.subsec_micros().into();
+        }
+
+        strace_log("gettimeofday", libc::SYS_gettimeofday, 0);
+        0
+    }
+
+    /// Replacement for `__vdso_time`.
+    ///
+    /// # Safety
+    ///
+    /// `tloc` must be NULL or dereferenceable.
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C-unwind" fn shim_vdso_time(tloc: *mut libc::time_t) -> libc::time_t {
+        let prev = ExecutionContext::Shadow.enter();
+        if !can_use_fast_path(prev.ctx()) {
+            return unsafe { fallback_syscall(prev.ctx(), libc::SYS_time, tloc as i64, 0) };
+        }
+
+        let now: libc::time_t =
+            local_time_since_epoch(ClockKind::Realtime, now_and_charge_latency())
+                .as_secs()
+                .try_into()
+                .unwrap();
+        if let Some(tloc) = unsafe { tloc.as_mut() } {
+            *tloc = now;
+        }
+
+        strace_log("time", libc::SYS_time, now);
+        now
+    }
+}
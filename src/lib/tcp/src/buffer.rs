@@ -1,4 +1,4 @@
-use std::collections::LinkedList;
+use std::collections::{LinkedList, VecDeque};
 use std::io::{Read, Write};
 
 use bytes::{Buf, Bytes, BytesMut};
@@ -12,6 +12,8 @@ pub(crate) struct SendQueue<T: Instant> {
     time_last_segment_sent: Option<T>,
     // exclusive
     transmitted_up_to: Seq,
+    // exclusive; this may be larger than `transmitted_up_to` after `retransmit_all()`
+    max_transmitted: Seq,
     // inclusive
     start_seq: Seq,
     // exclusive
@@ -26,6 +28,7 @@ impl<T: Instant> SendQueue<T> {
             segments: LinkedList::new(),
             time_last_segment_sent: None,
             transmitted_up_to: initial_seq,
+            max_transmitted: initial_seq,
             start_seq: initial_seq,
             end_seq: initial_seq,
             fin_added: false,
@@ -127,9 +130,24 @@ impl<T: Instant> SendQueue<T> {
         self.end_seq - self.start_seq
     }
 
+    /// The sequence number following the last transmitted byte.
+    pub fn transmitted_up_to(&self) -> Seq {
+        self.transmitted_up_to
+    }
+
+    /// The sequence number following the last byte that was ever transmitted.
+    pub fn max_transmitted(&self) -> Seq {
+        self.max_transmitted
+    }
+
     pub fn advance_start(&mut self, new_start: Seq) {
         assert!(self.contains(new_start) || new_start == self.end_seq);
 
+        // the acknowledged bytes may not all have been transmitted since `retransmit_all()`
+        if new_start - self.start_seq > self.transmitted_up_to - self.start_seq {
+            self.transmitted_up_to = new_start;
+        }
+
         while self.start_seq != new_start {
             let advance_by = new_start - self.start_seq;
 
@@ -160,12 +178,15 @@ impl<T: Instant> SendQueue<T> {
 
     /// Get the next segment that has not yet been transmitted. The `offset` argument can be used to
     /// return the next segment starting at `offset` bytes from the next non-transmitted segment.
-    // TODO: this is slow and is called often
     pub fn next_not_transmitted(&self, offset: u32) -> Option<(Seq, Segment)> {
-        // the sequence number of the segment we want to return
-        let target_seq = self.transmitted_up_to + offset;
+        self.segment_at(self.transmitted_up_to + offset)
+    }
 
-        // check if we've already transmitted everything in the buffer
+    /// Get the segment containing the sequence number `target_seq`, trimmed so that it starts at
+    /// `target_seq`.
+    // TODO: this is slow and is called often
+    pub fn segment_at(&self, target_seq: Seq) -> Option<(Seq, Segment)> {
+        // check if the sequence number is in the buffer
         if !self.contains(target_seq) {
             return None;
         }
@@ -207,6 +228,15 @@ impl<T: Instant> SendQueue<T> {
         }
 
         self.transmitted_up_to = up_to;
+
+        if up_to - self.start_seq > self.max_transmitted - self.start_seq {
+            self.max_transmitted = up_to;
+        }
+    }
+
+    /// Mark all transmitted segments as not transmitted, so that they will be transmitted again.
+    pub fn retransmit_all(&mut self) {
+        self.transmitted_up_to = self.start_seq;
     }
 }
 
@@ -219,6 +249,11 @@ pub(crate) struct RecvQueue {
     end_seq: Seq,
     syn_added: bool,
     fin_added: bool,
+    /// Data received after a gap in the sequence space (the reassembly queue). The segments are
+    /// ordered, don't overlap, and start after `end_seq`.
+    out_of_order: VecDeque<(Seq, Bytes)>,
+    /// The sequence number of the most recently received out-of-order data.
+    last_out_of_order: Option<Seq>,
 }
 
 impl RecvQueue {
@@ -229,6 +264,8 @@ impl RecvQueue {
             end_seq: initial_seq,
             syn_added: false,
             fin_added: false,
+            out_of_order: VecDeque::new(),
+            last_out_of_order: None,
         }
     }
 
@@ -263,6 +300,125 @@ impl RecvQueue {
         self.segments.push_back(data);
     }
 
+    /// Add any out-of-order data that is now in order, discarding out-of-order data that has
+    /// already been added. Returns the number of bytes added.
+    pub fn reassemble(&mut self) -> u32 {
+        let mut added = 0;
+
+        while let Some((seq, chunk)) = self.out_of_order.front_mut() {
+            // how far the chunk starts before the next sequence number; out-of-order data is
+            // always within the receive window, so a "negative" value means that the chunk starts
+            // after the next sequence number
+            let overlap = self.end_seq - *seq;
+            let chunk_len: u32 = chunk.len().try_into().unwrap();
+
+            if overlap > i32::MAX as u32 {
+                // there's still a gap before this chunk
+                break;
+            }
+
+            if overlap >= chunk_len {
+                // we already have all of this chunk
+                self.out_of_order.pop_front();
+                continue;
+            }
+
+            chunk.advance(overlap.try_into().unwrap());
+            let (_, chunk) = self.out_of_order.pop_front().unwrap();
+            added += chunk_len - overlap;
+            self.add(chunk);
+        }
+
+        added
+    }
+
+    /// Add data that starts after the next sequence number, leaving a gap. It will be added to the
+    /// queue once the data that fills the gap is added. Any data that was already received is
+    /// ignored.
+    pub fn add_out_of_order(&mut self, seq: Seq, mut data: Bytes) {
+        assert!(self.syn_added);
+        assert!(!self.fin_added);
+        assert_ne!(seq, self.end_seq);
+
+        if data.is_empty() {
+            return;
+        }
+
+        self.last_out_of_order = Some(seq);
+
+        // the offset from the next sequence number
+        let offset = |x: Seq| x - self.end_seq;
+
+        let mut seq = seq;
+        let mut index = 0;
+
+        while !data.is_empty() {
+            let start = offset(seq);
+            let end = start + u32::try_from(data.len()).unwrap();
+
+            let Some((existing_seq, existing)) = self.out_of_order.get(index) else {
+                self.out_of_order.push_back((seq, data));
+                break;
+            };
+
+            let existing_start = offset(*existing_seq);
+            let existing_end = existing_start + u32::try_from(existing.len()).unwrap();
+
+            if existing_end <= start {
+                // the existing chunk is before the new data
+                index += 1;
+                continue;
+            }
+
+            if end <= existing_start {
+                // the new data is before the existing chunk
+                self.out_of_order.insert(index, (seq, data));
+                break;
+            }
+
+            if start < existing_start {
+                // add the new data up to the existing chunk
+                let len = existing_start - start;
+                let head = data.split_to(len.try_into().unwrap());
+                self.out_of_order.insert(index, (seq, head));
+                seq += len;
+                index += 1;
+            }
+
+            // skip the new data that overlaps with the existing chunk
+            let skip = std::cmp::min(existing_end - offset(seq), data.len().try_into().unwrap());
+            data.advance(skip.try_into().unwrap());
+            seq += skip;
+            index += 1;
+        }
+    }
+
+    /// Returns the ranges of out-of-order data, starting with the range containing the most
+    /// recently received data (as required for SACK blocks by RFC 2018) followed by the others in
+    /// order.
+    pub fn out_of_order_ranges(&self) -> Vec<SeqRange> {
+        let mut ranges: Vec<SeqRange> = vec![];
+
+        for (seq, chunk) in &self.out_of_order {
+            let end = *seq + u32::try_from(chunk.len()).unwrap();
+            match ranges.last_mut() {
+                // merge adjacent chunks
+                Some(last) if last.end == *seq => last.end = end,
+                _ => ranges.push(SeqRange::new(*seq, end)),
+            }
+        }
+
+        let recent = self
+            .last_out_of_order
+            .and_then(|seq| ranges.iter().position(|x| x.contains(seq)));
+        if let Some(recent) = recent {
+            let range = ranges.remove(recent);
+            ranges.insert(0, range);
+        }
+
+        ranges
+    }
+
     pub fn syn_added(&self) -> bool {
         self.syn_added
     }
@@ -0,0 +1,257 @@
+//! Congestion control ([RFC 5681]) with NewReno fast recovery ([RFC 6582]). Window sizes are in
+//! bytes.
+//!
+//! If the peer sends selective acknowledgements, segments that it has selectively acknowledged are
+//! not counted as in-flight by the connection. In that case the window isn't inflated by duplicate
+//! acknowledgements during fast recovery, since the segments that caused them have already been
+//! removed from the in-flight count.
+//!
+//! [RFC 5681]: https://www.rfc-editor.org/rfc/rfc5681
+//! [RFC 6582]: https://www.rfc-editor.org/rfc/rfc6582
+
+use crate::seq::{Seq, SeqRange};
+
+#[derive(Debug)]
+pub(crate) struct Congestion {
+    /// The sender maximum segment size.
+    mss: u32,
+    cwnd: u32,
+    ssthresh: u32,
+    /// The number of bytes acknowledged during congestion avoidance since the window was last
+    /// increased.
+    bytes_acked: u32,
+    duplicate_acks: u32,
+    /// If in fast recovery, the highest sequence number transmitted when fast recovery started
+    /// ("recover" in RFC 6582).
+    recover: Option<Seq>,
+}
+
+impl Congestion {
+    /// The number of duplicate acknowledgements that trigger a fast retransmit.
+    const DUPLICATE_ACK_THRESHOLD: u32 = 3;
+
+    pub fn new(mss: u32) -> Self {
+        Self {
+            mss,
+            // RFC 6928 2.:
+            // > min (10*MSS, max (2*MSS, 14600))
+            cwnd: std::cmp::min(10 * mss, std::cmp::max(2 * mss, 14600)),
+            // RFC 5681 3.1.:
+            // > The initial value of ssthresh SHOULD be set arbitrarily high
+            ssthresh: u32::MAX,
+            bytes_acked: 0,
+            duplicate_acks: 0,
+            recover: None,
+        }
+    }
+
+    pub fn cwnd(&self) -> u32 {
+        self.cwnd
+    }
+
+    #[cfg(test)]
+    pub fn ssthresh(&self) -> u32 {
+        self.ssthresh
+    }
+
+    pub fn in_fast_recovery(&self) -> bool {
+        self.recover.is_some()
+    }
+
+    /// RFC 5681 3.1.:
+    /// > ssthresh = max (FlightSize / 2, 2*SMSS)
+    fn reduce_ssthresh(&mut self, flight_size: u32) {
+        self.ssthresh = std::cmp::max(flight_size / 2, 2 * self.mss);
+    }
+
+    /// Called when an acknowledgement advances the lowest unacknowledged sequence number from
+    /// `una` to `ack`. Returns true if this is a partial acknowledgement during fast recovery, in
+    /// which case the first unacknowledged segment should be retransmitted.
+    pub fn new_ack(&mut self, una: Seq, ack: Seq) -> bool {
+        let acked = ack - una;
+        self.duplicate_acks = 0;
+
+        if let Some(recover) = self.recover {
+            // if the acknowledgement doesn't cover all of the data that was outstanding when fast
+            // recovery started
+            if SeqRange::new(una, recover).contains(ack) {
+                // RFC 6582 3.2. step 5: deflate the window by the amount of new data acknowledged,
+                // and add back one segment
+                self.cwnd = self.cwnd.saturating_sub(acked);
+                if acked >= self.mss {
+                    self.cwnd += self.mss;
+                }
+                self.cwnd = std::cmp::max(self.cwnd, self.mss);
+                return true;
+            }
+
+            // RFC 6582 3.2. step 6: a full acknowledgement exits fast recovery
+            self.recover = None;
+            self.cwnd = self.ssthresh;
+            self.bytes_acked = 0;
+            return false;
+        }
+
+        if self.cwnd < self.ssthresh {
+            // RFC 5681 3.1.:
+            // > During slow start, a TCP increments cwnd by at most SMSS bytes for each ACK
+            // > received that cumulatively acknowledges new data.
+            self.cwnd = self.cwnd.saturating_add(std::cmp::min(acked, self.mss));
+        } else {
+            // RFC 5681 3.1.: increase the window by one segment per round-trip, counting the
+            // acknowledged bytes
+            self.bytes_acked = self.bytes_acked.saturating_add(acked);
+            if self.bytes_acked >= self.cwnd {
+                self.bytes_acked -= self.cwnd;
+                self.cwnd = self.cwnd.saturating_add(self.mss);
+            }
+        }
+
+        false
+    }
+
+    /// Called when a duplicate acknowledgement is received. `flight_size` is the amount of
+    /// outstanding data, `snd_max` is the highest sequence number transmitted, and `sack` is
+    /// whether the peer is sending selective acknowledgements. Returns true if the first
+    /// unacknowledged segment should be retransmitted (a "fast retransmit").
+    pub fn duplicate_ack(&mut self, flight_size: u32, snd_max: Seq, sack: bool) -> bool {
+        if self.in_fast_recovery() {
+            // RFC 5681 3.2. step 4: inflate the window for each segment that has left the network
+            if !sack {
+                self.cwnd = self.cwnd.saturating_add(self.mss);
+            }
+            return false;
+        }
+
+        self.duplicate_acks += 1;
+
+        if self.duplicate_acks != Self::DUPLICATE_ACK_THRESHOLD {
+            return false;
+        }
+
+        // RFC 5681 3.2. steps 2 and 3
+        self.reduce_ssthresh(flight_size);
+        self.cwnd = if sack {
+            self.ssthresh
+        } else {
+            self.ssthresh + Self::DUPLICATE_ACK_THRESHOLD * self.mss
+        };
+        self.recover = Some(snd_max);
+
+        true
+    }
+
+    /// Called when the retransmission timer expires.
+    pub fn timeout(&mut self, flight_size: u32) {
+        // RFC 5681 3.1.:
+        // > When a TCP sender detects segment loss using the retransmission timer and the given
+        // > segment has not yet been resent by way of the retransmission timer, the value of
+        // > ssthresh MUST be set to no more than the value given in equation (4)
+        //
+        // If the segment was already resent by the retransmission timer, the flight size will be
+        // small and we shouldn't reduce the threshold further.
+        if self.cwnd > self.mss {
+            self.reduce_ssthresh(flight_size);
+        }
+
+        // RFC 5681 3.1.:
+        // > Furthermore, upon a timeout (as specified in [RFC2988]) cwnd MUST be set to no more
+        // > than the loss window, LW, which equals 1 full-sized segment
+        self.cwnd = self.mss;
+        self.bytes_acked = 0;
+        self.duplicate_acks = 0;
+        self.recover = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MSS: u32 = 1000;
+
+    /// Acknowledge `n` segments one at a time, starting at `una`.
+    fn ack_segments(congestion: &mut Congestion, una: &mut Seq, n: u32) {
+        for _ in 0..n {
+            assert!(!congestion.new_ack(*una, *una + MSS));
+            *una += MSS;
+        }
+    }
+
+    #[test]
+    fn test_initial_window() {
+        assert_eq!(Congestion::new(1000).cwnd(), 10_000);
+        assert_eq!(Congestion::new(1460).cwnd(), 14_600);
+        assert_eq!(Congestion::new(8000).cwnd(), 16_000);
+    }
+
+    #[test]
+    fn test_slow_start_and_congestion_avoidance() {
+        let mut congestion = Congestion::new(MSS);
+        let mut una = Seq::new(0);
+
+        // a full window of acknowledgements doubles the window during slow start
+        ack_segments(&mut congestion, &mut una, 10);
+        assert_eq!(congestion.cwnd(), 20 * MSS);
+
+        congestion.timeout(20 * MSS);
+        assert_eq!(congestion.cwnd(), MSS);
+        assert_eq!(congestion.ssthresh(), 10 * MSS);
+
+        // slow start until reaching the threshold
+        for expected in [2, 4, 8, 10] {
+            let segments = congestion.cwnd() / MSS;
+            ack_segments(&mut congestion, &mut una, segments);
+            assert_eq!(congestion.cwnd(), expected * MSS);
+        }
+
+        // then one segment per window of acknowledgements
+        let cwnd = congestion.cwnd();
+        ack_segments(&mut congestion, &mut una, cwnd / MSS);
+        assert_eq!(congestion.cwnd(), cwnd + MSS);
+    }
+
+    #[test]
+    fn test_fast_recovery() {
+        let mut congestion = Congestion::new(MSS);
+        let una = Seq::new(0);
+        let snd_max = una + 10 * MSS;
+
+        // only the third duplicate acknowledgement triggers a fast retransmit
+        assert!(!congestion.duplicate_ack(10 * MSS, snd_max, false));
+        assert!(!congestion.duplicate_ack(10 * MSS, snd_max, false));
+        assert!(congestion.duplicate_ack(10 * MSS, snd_max, false));
+        assert!(congestion.in_fast_recovery());
+        assert_eq!(congestion.ssthresh(), 5 * MSS);
+        assert_eq!(congestion.cwnd(), 8 * MSS);
+
+        // the window is inflated by further duplicate acknowledgements
+        assert!(!congestion.duplicate_ack(10 * MSS, snd_max, false));
+        assert_eq!(congestion.cwnd(), 9 * MSS);
+
+        // a partial acknowledgement deflates the window and requests a retransmission
+        assert!(congestion.new_ack(una, una + 2 * MSS));
+        assert!(congestion.in_fast_recovery());
+        assert_eq!(congestion.cwnd(), 8 * MSS);
+
+        // a full acknowledgement exits fast recovery
+        assert!(!congestion.new_ack(una + 2 * MSS, snd_max));
+        assert!(!congestion.in_fast_recovery());
+        assert_eq!(congestion.cwnd(), 5 * MSS);
+    }
+
+    #[test]
+    fn test_fast_recovery_with_sack() {
+        let mut congestion = Congestion::new(MSS);
+        let snd_max = Seq::new(10 * MSS);
+
+        for _ in 0..3 {
+            congestion.duplicate_ack(10 * MSS, snd_max, true);
+        }
+        assert_eq!(congestion.cwnd(), 5 * MSS);
+
+        // the window isn't inflated
+        congestion.duplicate_ack(10 * MSS, snd_max, true);
+        assert_eq!(congestion.cwnd(), 5 * MSS);
+    }
+}
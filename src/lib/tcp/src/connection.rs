@@ -3,7 +3,10 @@ use std::io::{Read, Write};
 use std::net::SocketAddrV4;
 
 use crate::buffer::{RecvQueue, Segment};
+use crate::congestion::Congestion;
+use crate::rtt::RttEstimator;
 use crate::seq::{Seq, SeqRange};
+use crate::util::SmallArrayBackedSlice;
 use crate::util::time::Instant;
 use crate::window_scaling::WindowScaling;
 use crate::{
@@ -33,6 +36,14 @@ impl<I: Instant> Connection<I> {
     const SEND_BUF_MAX: usize = 100_000;
     const RECV_BUF_MAX: u32 = 100_000;
 
+    /// Roughly represents the MSS.
+    // TODO: handle the MSS properly
+    const MAX_BYTES_PER_PACKET: u32 = 1500;
+
+    /// The number of consecutive retransmission timeouts after which the connection is aborted.
+    /// This is the same as Linux's default `tcp_retries2`.
+    const MAX_RETRANSMISSIONS: u32 = 15;
+
     pub fn new(
         local_addr: SocketAddrV4,
         remote_addr: SocketAddrV4,
@@ -43,7 +54,7 @@ impl<I: Instant> Connection<I> {
             config,
             local_addr,
             remote_addr,
-            send: ConnectionSend::new(send_initial_seq),
+            send: ConnectionSend::new(send_initial_seq, Self::MAX_BYTES_PER_PACKET),
             recv: None,
             need_to_ack: true,
             last_advertised_window: None,
@@ -120,6 +131,7 @@ impl<I: Instant> Connection<I> {
         &mut self,
         header: &TcpHeader,
         payload: Payload,
+        now: I,
     ) -> Result<u32, PushPacketError> {
         if self.is_reset {
             panic!(
@@ -201,9 +213,14 @@ impl<I: Instant> Connection<I> {
         // > The window field in a segment where the SYN bit is set (i.e., a <SYN> or <SYN,ACK>)
         // > MUST NOT be scaled.
         //
-        // TODO: be careful about this if we support a reassembly queue in the future
+        // SYN flags are never stored in the reassembly queue, so the window of a packet that had a
+        // SYN flag is always applied when the packet is received.
         let original_packet_had_syn = header.flags.contains(TcpFlags::SYN);
 
+        // used to check for duplicate acknowledgements, which must not carry any data
+        let original_packet_had_data =
+            !payload.is_empty() || header.flags.intersects(TcpFlags::SYN | TcpFlags::FIN);
+
         // trim the segment so that it only contains data/flags that fit within the receive window
         let recv_window = self.recv_window().unwrap();
         let Some((header, payload)) = trim_segment(header, payload, &recv_window) else {
@@ -275,18 +292,35 @@ impl<I: Instant> Connection<I> {
                     for chunk in payload.0 {
                         recv.buffer.add(chunk);
                     }
+
+                    // this may have filled a gap before previously received out-of-order data
+                    pushed_len += recv.buffer.reassemble();
                 } else {
-                    // TODO: store (truncated?) out-of-order packet
+                    // store the out-of-order data until the gap before it is filled
+                    let mut seq = payload_seq;
+                    for chunk in payload.0 {
+                        let chunk_len: u32 = chunk.len().try_into().unwrap();
+                        recv.buffer.add_out_of_order(seq, chunk);
+                        seq += chunk_len;
+                    }
+
+                    // RFC 5681 4.2.:
+                    // > A TCP receiver SHOULD send an immediate duplicate ACK when an out-of-order
+                    // > segment arrives.
+                    self.need_to_ack = true;
                 }
             }
 
+            // the FIN may have arrived before some of the data preceding it
             if let Some(fin_seq) = fin_seq {
-                if fin_seq == recv.buffer.next_seq() {
-                    recv.buffer.add_fin();
-                    recv.is_closed = true;
-                } else {
-                    // TODO: store (truncated?) out of order packet
-                }
+                recv.fin_seq.get_or_insert(fin_seq);
+            }
+
+            if recv.fin_seq == Some(recv.buffer.next_seq()) {
+                recv.buffer.add_fin();
+                recv.is_closed = true;
+            } else if fin_seq.is_some() {
+                self.need_to_ack = true;
             }
         }
 
@@ -296,6 +330,8 @@ impl<I: Instant> Connection<I> {
             self.need_to_ack = true;
         }
 
+        let old_send_window = self.send.window;
+
         // update the send window, applying the window scale shift only if it wasn't a SYN packet
         // TODO: should we still update the window if the ACK was not in the valid range?
         if original_packet_had_syn {
@@ -306,25 +342,214 @@ impl<I: Instant> Connection<I> {
         }
 
         if header.flags.contains(TcpFlags::ACK) {
-            let valid_ack_range = SeqRange::new(
-                self.send.buffer.start_seq() + 1,
-                self.send.buffer.next_seq() + 1,
-            );
+            let una = self.send.buffer.start_seq();
+            let ack = Seq::new(header.ack);
 
-            if valid_ack_range.contains(Seq::new(header.ack)) {
+            if self.config.selective_acks_enabled
+                && let Some(blocks) = header.selective_acks.as_ref()
+            {
+                self.add_selective_acks(blocks);
+            }
+
+            // only data that we've transmitted can be acknowledged
+            let valid_ack_range = SeqRange::new(una + 1, self.send.buffer.max_transmitted() + 1);
+
+            if valid_ack_range.contains(ack) {
                 // the SYN is always first, so if a new sequence number has been acknowledged, then
                 // either it's acknowledging the SYN, or the SYN has been acknowledged in the past
-                if Seq::new(header.ack) != self.send.buffer.start_seq() {
-                    self.send.syn_acked = true;
-                }
+                self.send.syn_acked = true;
 
-                self.send.buffer.advance_start(Seq::new(header.ack));
+                self.new_ack(una, ack, now);
+            } else if ack == una
+                && !original_packet_had_data
+                && self.send.window == old_send_window
+                && self.flight_size() > 0
+            {
+                // RFC 5681 2.: this is a duplicate acknowledgement
+                self.duplicate_ack();
             }
         }
 
         Ok(pushed_len)
     }
 
+    /// Process an acknowledgement that acknowledges new data, where `una` is the lowest
+    /// unacknowledged sequence number before the acknowledgement.
+    fn new_ack(&mut self, una: Seq, ack: Seq, now: I) {
+        // take a round-trip time measurement if the timed segment was acknowledged
+        if let Some((timed_seq, sent_at)) = self.send.rtt_timed
+            && SeqRange::new(una, ack).contains(timed_seq)
+        {
+            let rtt = now.saturating_duration_since(sent_at);
+            self.send.rtt.add_sample(rtt);
+            self.send.rtt_timed = None;
+        }
+
+        self.send.buffer.advance_start(ack);
+
+        // remove selectively acknowledged blocks that are now cumulatively acknowledged
+        let acked = ack - una;
+        self.send.sacked.retain_mut(|block| {
+            if block.end - una <= acked {
+                return false;
+            }
+            if block.start - una < acked {
+                block.start = ack;
+            }
+            true
+        });
+
+        self.send.rto_count = 0;
+        self.send.retransmitted_bytes = 0;
+
+        let partial_ack = self.send.congestion.new_ack(una, ack);
+
+        if partial_ack {
+            // RFC 6582 3.2. step 5: retransmit the first unacknowledged segment, unless we've
+            // already retransmitted past it
+            let already_retransmitted = self
+                .send
+                .retransmit_next
+                .is_some_and(|next| next - una > acked);
+            if !already_retransmitted {
+                self.send.retransmit_next = Some(ack);
+            }
+        } else {
+            self.send.retransmit_next = None;
+        }
+
+        // RFC 6298 5.2. and 5.3.:
+        // > When all outstanding data has been acknowledged, turn off the retransmission timer.
+        // > When an ACK is received that acknowledges new data, restart the retransmission timer
+        self.send.rto_expiry = (self.flight_size() > 0).then(|| now + self.send.rtt.rto());
+    }
+
+    /// Process a duplicate acknowledgement.
+    fn duplicate_ack(&mut self) {
+        let flight_size = self.flight_size();
+        let snd_max = self.send.buffer.transmitted_up_to();
+        let sack = !self.send.sacked.is_empty();
+
+        let fast_retransmit = self
+            .send
+            .congestion
+            .duplicate_ack(flight_size, snd_max, sack);
+
+        if fast_retransmit {
+            // RFC 5681 3.2. step 2: fast retransmit the first unacknowledged segment
+            self.send.retransmit_next = Some(self.send.buffer.start_seq());
+            self.send.retransmitted_bytes = 0;
+        }
+    }
+
+    /// Record the selective acknowledgement blocks from a received packet (RFC 2018). Blocks that
+    /// don't cover outstanding data are ignored.
+    fn add_selective_acks(&mut self, blocks: &[(u32, u32)]) {
+        let una = self.send.buffer.start_seq();
+        let outstanding = self.flight_size();
+
+        for &(start, end) in blocks {
+            let block = SeqRange::new(Seq::new(start), Seq::new(end));
+
+            let start_offset = block.start - una;
+            let end_offset = block.end - una;
+            if start_offset >= end_offset || end_offset > outstanding {
+                continue;
+            }
+
+            self.send.sacked.push(block);
+        }
+
+        // keep the blocks ordered and merge any that overlap or are adjacent
+        self.send.sacked.sort_by_key(|block| block.start - una);
+        let mut merged: Vec<SeqRange> = Vec::with_capacity(self.send.sacked.len());
+        for block in self.send.sacked.drain(..) {
+            match merged.last_mut() {
+                Some(last) if block.start - una <= last.end - una => {
+                    if block.end - una > last.end - una {
+                        last.end = block.end;
+                    }
+                }
+                _ => merged.push(block),
+            }
+        }
+        self.send.sacked = merged;
+    }
+
+    /// The number of transmitted bytes that haven't been cumulatively acknowledged.
+    fn flight_size(&self) -> u32 {
+        self.send.buffer.transmitted_up_to() - self.send.buffer.start_seq()
+    }
+
+    /// An estimate of the number of bytes that are in the network ("pipe" in RFC 6675), which
+    /// excludes selectively acknowledged bytes and includes retransmitted bytes.
+    fn pipe(&self) -> u32 {
+        let sacked: u32 = self.send.sacked.iter().map(|x| x.len()).sum();
+        self.flight_size()
+            .saturating_sub(sacked)
+            .saturating_add(self.send.retransmitted_bytes)
+    }
+
+    /// Returns the time at which a retransmission timer should be registered, if a timer isn't
+    /// already registered for this time or earlier.
+    pub fn rto_timer_to_register(&mut self) -> Option<I> {
+        let expiry = self.send.rto_expiry?;
+
+        // if a timer will fire before the expiry, we'll register a new timer when it fires
+        if self.send.rto_timer.is_some_and(|timer| timer <= expiry) {
+            return None;
+        }
+
+        self.send.rto_timer = Some(expiry);
+        Some(expiry)
+    }
+
+    /// Should be called when a timer registered using the time from
+    /// [`rto_timer_to_register`](Self::rto_timer_to_register) fires. Returns true if we've
+    /// retransmitted too many times and the connection should be aborted.
+    pub fn rto_timer_fired(&mut self, now: I) -> bool {
+        if self.send.rto_timer == Some(now) {
+            self.send.rto_timer = None;
+        }
+
+        if self.is_reset {
+            return false;
+        }
+
+        // the retransmission timer may have been restarted or stopped since this timer was
+        // registered
+        let Some(expiry) = self.send.rto_expiry else {
+            return false;
+        };
+        if expiry > now {
+            return false;
+        }
+
+        self.send.rto_count += 1;
+        if self.send.rto_count > Self::MAX_RETRANSMISSIONS {
+            return true;
+        }
+
+        // RFC 6298 5.4. - 5.6.: retransmit the earliest unacknowledged segment and back off the
+        // timer (the timer is restarted when the segment is retransmitted)
+        let flight_size = self.flight_size();
+        self.send.congestion.timeout(flight_size);
+        self.send.rtt.back_off();
+
+        // RFC 2018 8.:
+        // > After a retransmit timeout the data sender SHOULD turn off all of the SACKed bits
+        self.send.sacked.clear();
+        self.send.retransmit_next = None;
+        self.send.retransmitted_bytes = 0;
+        self.send.rtt_timed = None;
+        self.send.rto_expiry = None;
+
+        // go back and resend everything, starting with the first unacknowledged segment
+        self.send.buffer.retransmit_all();
+
+        false
+    }
+
     pub fn pop_packet(&mut self, now: I) -> Result<(TcpHeader, Payload), PopPacketError> {
         let (seq_range, mut flags, payload) =
             self.next_segment().ok_or(PopPacketError::NoPacket)?;
@@ -345,7 +570,15 @@ impl<I: Instant> Connection<I> {
         let header_window_size;
         let header_window_scale;
 
-        if flags.contains(TcpFlags::SYN) {
+        if flags.contains(TcpFlags::SYN)
+            && let Some(window_scale) = self.window_scaling.sent_syn_window_scale()
+        {
+            // this is a retransmitted SYN, so we must send the same window scale as before, and
+            // the window still can't be scaled
+            header_window_scale = window_scale;
+            header_window_size = std::cmp::min(self.recv_window_len(), u16::MAX.into());
+            self.last_advertised_window = Some(header_window_size);
+        } else if flags.contains(TcpFlags::SYN) {
             if self.window_scaling.can_send_window_scale() {
                 // The receive buffer capacity at the time the SYN is sent decides the window
                 // scaling to use. This effectively limits future receive buffer capacity increases
@@ -397,7 +630,7 @@ impl<I: Instant> Connection<I> {
             seq: seq_range.start.into(),
             ack: header_ack.into(),
             window_size: header_window_size.try_into().unwrap(),
-            selective_acks: None,
+            selective_acks: self.selective_acks(),
             window_scale: header_window_scale,
             timestamp: None,
             timestamp_echo: None,
@@ -406,8 +639,32 @@ impl<I: Instant> Connection<I> {
         // we're sending the most up-to-date acknowledgement
         self.need_to_ack = false;
 
-        // inform the buffer that we transmitted this segment
-        self.send.buffer.mark_as_transmitted(seq_range.end, now);
+        if !seq_range.is_empty() && self.send.retransmit_next == Some(seq_range.start) {
+            // this segment is being retransmitted during loss recovery
+            self.send.retransmitted_bytes += seq_range.len();
+            self.advance_retransmit(seq_range.end);
+
+            // Karn's algorithm: don't measure the round-trip time of retransmitted segments
+            self.send.rtt_timed = None;
+        } else {
+            // time this segment if it contains new data and we're not already timing a segment
+            if !seq_range.is_empty()
+                && seq_range.start == self.send.buffer.max_transmitted()
+                && self.send.rtt_timed.is_none()
+            {
+                self.send.rtt_timed = Some((seq_range.start, now));
+            }
+
+            // inform the buffer that we transmitted this segment
+            self.send.buffer.mark_as_transmitted(seq_range.end, now);
+        }
+
+        // RFC 6298 5.1.:
+        // > Every time a packet containing data is sent (including a retransmission), if the timer
+        // > is not running, start it running so that it will expire after RTO seconds
+        if !seq_range.is_empty() && self.send.rto_expiry.is_none() {
+            self.send.rto_expiry = Some(now + self.send.rtt.rto());
+        }
 
         if header.flags.contains(TcpFlags::RST) {
             assert!(self.need_to_send_rst);
@@ -417,6 +674,48 @@ impl<I: Instant> Connection<I> {
         Ok((header, payload))
     }
 
+    /// Move the retransmission cursor to `seq`, skipping any selectively acknowledged data. If
+    /// there's no more data to retransmit, the cursor is cleared.
+    fn advance_retransmit(&mut self, seq: Seq) {
+        let una = self.send.buffer.start_seq();
+        let mut next = seq;
+
+        // the blocks are ordered and don't touch, so we only need a single pass
+        for block in &self.send.sacked {
+            if block.contains(next) {
+                next = block.end;
+            }
+        }
+
+        // Only data before the highest selectively acknowledged block is assumed to be lost. If
+        // the peer isn't sending selective acknowledgements, only the first unacknowledged segment
+        // is retransmitted.
+        let highest_sacked = self.send.sacked.last().map(|x| x.start);
+        self.send.retransmit_next = highest_sacked
+            .filter(|highest| next - una < *highest - una)
+            .map(|_| next);
+    }
+
+    /// Returns the selective acknowledgement blocks to send, describing the out-of-order data that
+    /// we've received (RFC 2018).
+    fn selective_acks(&self) -> Option<SmallArrayBackedSlice<4, (u32, u32)>> {
+        if !self.config.selective_acks_enabled {
+            return None;
+        }
+
+        let ranges = self.recv.as_ref()?.buffer.out_of_order_ranges();
+        if ranges.is_empty() {
+            return None;
+        }
+
+        let blocks: Vec<(u32, u32)> = ranges
+            .iter()
+            .take(4)
+            .map(|x| (x.start.into(), x.end.into()))
+            .collect();
+        SmallArrayBackedSlice::new(&blocks)
+    }
+
     /// Returns a segment that is ready to send. This may be a data segment (a segment containing a
     /// SYN/FIN flag and/or payload data), a RST segment, or an empty segment. Even if this returns
     /// an empty segment, it must be sent with the correct acknowledgement number, window size, etc
@@ -461,6 +760,11 @@ impl<I: Instant> Connection<I> {
         }
 
         let (seq_range, syn_fin_flags, payload) = 'packet: {
+            // if we have lost data to retransmit
+            if let Some((seq_range, syn_fin_flags, payload)) = self.next_retransmit_segment() {
+                break 'packet (seq_range, syn_fin_flags, payload);
+            }
+
             // if we have syn/fin/payload data to send
             if let Some((seq_range, syn_fin_flags, payload)) = self.next_data_segment() {
                 break 'packet (seq_range, syn_fin_flags, payload);
@@ -517,25 +821,58 @@ impl<I: Instant> Connection<I> {
     fn next_data_segment(&self) -> Option<(SeqRange, TcpFlags, Payload)> {
         let send_window = self.send_window();
 
+        // RFC 5681 3.1.: the amount of data in the network must not exceed the congestion window
+        let cwnd_available = self.send.congestion.cwnd().saturating_sub(self.pipe());
+        let allowed_len = std::cmp::min(
+            send_window.len(),
+            self.flight_size().saturating_add(cwnd_available),
+        );
+        let allowed = SeqRange::new(send_window.start, send_window.start + allowed_len);
+
+        self.data_segment(self.send.buffer.transmitted_up_to(), allowed)
+    }
+
+    /// Returns a data segment that is being retransmitted during loss recovery, if any.
+    fn next_retransmit_segment(&self) -> Option<(SeqRange, TcpFlags, Payload)> {
+        let seq = self.send.retransmit_next?;
+        let una = self.send.buffer.start_seq();
+
+        // the first unacknowledged segment is always retransmitted (RFC 5681 3.2. step 2), but
+        // other segments must fit within the congestion window (RFC 6675 5.)
+        if seq != una && self.pipe() >= self.send.congestion.cwnd() {
+            return None;
+        }
+
+        // retransmit up to the next selectively acknowledged block
+        let end = self
+            .send
+            .sacked
+            .iter()
+            .map(|x| x.start)
+            .find(|start| *start - una > seq - una)
+            .unwrap_or(self.send.buffer.transmitted_up_to());
+
+        self.data_segment(seq, SeqRange::new(una, end))
+    }
+
+    /// Returns a data segment starting at `start`, containing at most one packet's worth of
+    /// payload and only sequence numbers within `allowed`.
+    fn data_segment(&self, start: Seq, allowed: SeqRange) -> Option<(SeqRange, TcpFlags, Payload)> {
         let mut chunks = Vec::new();
         let mut syn_fin_flags = TcpFlags::empty();
         let mut seq_start = None;
         let mut seq_len = 0;
         let mut payload_bytes_len = 0;
 
-        // roughly represents the MSS
-        // TODO: handle the MSS properly
-        const MAX_BYTES_PER_PACKET: u32 = 1500;
-
         // do we have syn/fin/payload data to send?
-        while let Some((seq, segment)) = self.send.buffer.next_not_transmitted(seq_len) {
-            // if no bytes of this segment fit within the send window
-            if !send_window.contains(seq) {
+        while let Some((seq, segment)) = self.send.buffer.segment_at(start + seq_len) {
+            // if no bytes of this segment are allowed to be sent
+            if !allowed.contains(seq) {
                 break;
             }
 
             // if we can't send any more payload bytes
-            if payload_bytes_len == MAX_BYTES_PER_PACKET {
+            if payload_bytes_len == Self::MAX_BYTES_PER_PACKET {
                 break;
             }
 
@@ -555,8 +892,8 @@ impl<I: Instant> Connection<I> {
                 }
                 Segment::Data(mut chunk) => {
                     let allowed_payload_len =
-                        MAX_BYTES_PER_PACKET.saturating_sub(payload_bytes_len);
-                    let allowed_seq_len = send_window.end - seq;
+                        Self::MAX_BYTES_PER_PACKET.saturating_sub(payload_bytes_len);
+                    let allowed_seq_len = allowed.end - seq;
                     let allowed_len = std::cmp::min(allowed_payload_len, allowed_seq_len);
 
                     chunk.truncate(std::cmp::min(chunk.len(), allowed_len.try_into().unwrap()));
@@ -570,7 +907,7 @@ impl<I: Instant> Connection<I> {
             };
 
             // we shouldn't be sending more than allowed
-            debug_assert!(payload_bytes_len <= MAX_BYTES_PER_PACKET);
+            debug_assert!(payload_bytes_len <= Self::MAX_BYTES_PER_PACKET);
         }
 
         if !chunks.is_empty() || !syn_fin_flags.is_empty() {
@@ -669,16 +1006,42 @@ pub(crate) struct ConnectionSend<I: Instant> {
     pub(crate) window: u32,
     pub(crate) is_closed: bool,
     pub(crate) syn_acked: bool,
+    pub(crate) congestion: Congestion,
+    pub(crate) rtt: RttEstimator<I::Duration>,
+    /// The segment being timed for a round-trip time measurement, and when it was sent.
+    pub(crate) rtt_timed: Option<(Seq, I)>,
+    /// Blocks that the peer has selectively acknowledged. They're ordered, don't overlap, and are
+    /// within the outstanding data.
+    pub(crate) sacked: Vec<SeqRange>,
+    /// The next sequence number to retransmit during loss recovery.
+    pub(crate) retransmit_next: Option<Seq>,
+    /// The number of bytes retransmitted since the last new acknowledgement.
+    pub(crate) retransmitted_bytes: u32,
+    /// When the retransmission timer expires, if it's running.
+    pub(crate) rto_expiry: Option<I>,
+    /// The number of consecutive retransmission timeouts.
+    pub(crate) rto_count: u32,
+    /// The earliest registered timer for the retransmission timeout.
+    pub(crate) rto_timer: Option<I>,
 }
 
 impl<I: Instant> ConnectionSend<I> {
-    pub fn new(initial_seq: Seq) -> Self {
+    pub fn new(initial_seq: Seq, mss: u32) -> Self {
         Self {
             buffer: super::buffer::SendQueue::new(initial_seq),
             // we don't know the peer's receive window, so choose something conservative
             window: 2048,
             is_closed: false,
             syn_acked: false,
+            congestion: Congestion::new(mss),
+            rtt: RttEstimator::new(),
+            rtt_timed: None,
+            sacked: Vec::new(),
+            retransmit_next: None,
+            retransmitted_bytes: 0,
+            rto_expiry: None,
+            rto_count: 0,
+            rto_timer: None,
         }
     }
 }
@@ -687,6 +1050,8 @@ impl<I: Instant> ConnectionSend<I> {
 pub(crate) struct ConnectionRecv {
     pub(crate) buffer: super::buffer::RecvQueue,
     pub(crate) is_closed: bool,
+    /// The sequence number of a received FIN, which may be waiting for earlier data to arrive.
+    pub(crate) fin_seq: Option<Seq>,
 }
 
 impl ConnectionRecv {
@@ -694,6 +1059,7 @@ impl ConnectionRecv {
         Self {
             buffer: super::buffer::RecvQueue::new(initial_seq),
             is_closed: false,
+            fin_seq: None,
         }
     }
 }
@@ -91,7 +91,9 @@ use bytes::{Bytes, BytesMut};
 pub mod util;
 
 mod buffer;
+mod congestion;
 mod connection;
+mod rtt;
 mod seq;
 mod states;
 mod window_scaling;
@@ -645,18 +647,26 @@ bitflags::bitflags! {
 #[non_exhaustive]
 pub struct TcpConfig {
     pub(crate) window_scaling_enabled: bool,
+    pub(crate) selective_acks_enabled: bool,
 }
 
 impl TcpConfig {
     pub fn window_scaling(&mut self, enable: bool) {
         self.window_scaling_enabled = enable;
     }
+
+    /// Send and use selective acknowledgements (SACK). There is no SACK-permitted option, so this
+    /// isn't negotiated with the peer.
+    pub fn selective_acks(&mut self, enable: bool) {
+        self.selective_acks_enabled = enable;
+    }
 }
 
 impl Default for TcpConfig {
     fn default() -> Self {
         Self {
             window_scaling_enabled: true,
+            selective_acks_enabled: true,
         }
     }
 }
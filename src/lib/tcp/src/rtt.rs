@@ -0,0 +1,158 @@
+//! Round-trip time estimation and the retransmission timeout ([RFC 6298]).
+//!
+//! [RFC 6298]: https://www.rfc-editor.org/rfc/rfc6298
+
+use crate::util::time::Duration;
+
+/// Estimates the round-trip time and computes the retransmission timeout (RTO).
+#[derive(Debug)]
+pub(crate) struct RttEstimator<D: Duration> {
+    /// The smoothed round-trip time, if we've measured the round-trip time.
+    srtt: Option<D>,
+    /// The round-trip time variation.
+    rttvar: D,
+    rto: D,
+}
+
+impl<D: Duration> RttEstimator<D> {
+    /// The clock granularity (G).
+    fn granularity() -> D {
+        D::MILLISECOND
+    }
+
+    /// RFC 6298 2.4.:
+    /// > Whenever RTO is computed, if it is less than 1 second, then the RTO SHOULD be rounded up to
+    /// > 1 second.
+    fn min_rto() -> D {
+        D::SECOND
+    }
+
+    /// RFC 6298 2.5.:
+    /// > A maximum value MAY be placed on RTO provided it is at least 60 seconds.
+    fn max_rto() -> D {
+        D::SECOND.saturating_mul(60)
+    }
+
+    pub fn new() -> Self {
+        Self {
+            srtt: None,
+            rttvar: D::ZERO,
+            // RFC 6298 2.1.:
+            // > Until a round-trip time (RTT) measurement has been made for a segment sent between
+            // > the sender and receiver, the sender SHOULD set RTO <- 1 second
+            rto: D::SECOND,
+        }
+    }
+
+    /// The current retransmission timeout.
+    pub fn rto(&self) -> D {
+        self.rto
+    }
+
+    /// The smoothed round-trip time, if any round-trip time has been measured.
+    #[cfg(test)]
+    pub fn srtt(&self) -> Option<D> {
+        self.srtt
+    }
+
+    /// Update the estimate with a new round-trip time measurement. The measurement must not be
+    /// taken from a retransmitted segment (Karn's algorithm).
+    pub fn add_sample(&mut self, rtt: D) {
+        let srtt = match self.srtt {
+            None => {
+                // RFC 6298 2.2.:
+                // > SRTT <- R
+                // > RTTVAR <- R/2
+                self.rttvar = rtt.checked_div(2).unwrap();
+                rtt
+            }
+            Some(srtt) => {
+                // RFC 6298 2.3. (with alpha=1/8 and beta=1/4):
+                // > RTTVAR <- (1 - beta) * RTTVAR + beta * |SRTT - R'|
+                // > SRTT <- (1 - alpha) * SRTT + alpha * R'
+                let diff = std::cmp::max(srtt, rtt) - std::cmp::min(srtt, rtt);
+                self.rttvar = (self.rttvar.saturating_mul(3).saturating_add(diff))
+                    .checked_div(4)
+                    .unwrap();
+                (srtt.saturating_mul(7).saturating_add(rtt))
+                    .checked_div(8)
+                    .unwrap()
+            }
+        };
+        self.srtt = Some(srtt);
+
+        // RFC 6298 2.2. and 2.3.:
+        // > RTO <- SRTT + max (G, K*RTTVAR)
+        let variation = std::cmp::max(Self::granularity(), self.rttvar.saturating_mul(4));
+        self.rto = srtt
+            .saturating_add(variation)
+            .clamp(Self::min_rto(), Self::max_rto());
+    }
+
+    /// Back off the timer after a retransmission timeout.
+    pub fn back_off(&mut self) {
+        // RFC 6298 5.5.:
+        // > The host MUST set RTO <- RTO * 2 ("back off the timer").
+        self.rto = std::cmp::min(self.rto.saturating_mul(2), Self::max_rto());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_initial_rto() {
+        let rtt = RttEstimator::<Duration>::new();
+        assert_eq!(rtt.rto(), Duration::from_secs(1));
+        assert_eq!(rtt.srtt(), None);
+    }
+
+    #[test]
+    fn test_samples() {
+        let mut rtt = RttEstimator::<Duration>::new();
+
+        // srtt=2s, rttvar=1s, rto=2s+4*1s
+        rtt.add_sample(Duration::from_secs(2));
+        assert_eq!(rtt.srtt(), Some(Duration::from_secs(2)));
+        assert_eq!(rtt.rto(), Duration::from_secs(6));
+
+        // rttvar=(3*1s+2s)/4=1.25s, srtt=(7*2s+4s)/8=2.25s, rto=2.25s+4*1.25s
+        rtt.add_sample(Duration::from_secs(4));
+        assert_eq!(rtt.srtt(), Some(Duration::from_millis(2250)));
+        assert_eq!(rtt.rto(), Duration::from_millis(7250));
+    }
+
+    #[test]
+    fn test_rto_bounds() {
+        let mut rtt = RttEstimator::<Duration>::new();
+
+        // small round-trip times are rounded up to the minimum
+        for _ in 0..10 {
+            rtt.add_sample(Duration::from_millis(10));
+        }
+        assert_eq!(rtt.rto(), Duration::from_secs(1));
+
+        // large round-trip times are limited to the maximum
+        rtt.add_sample(Duration::from_secs(100));
+        assert_eq!(rtt.rto(), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_back_off() {
+        let mut rtt = RttEstimator::<Duration>::new();
+
+        let mut rtos = vec![];
+        for _ in 0..8 {
+            rtt.back_off();
+            rtos.push(rtt.rto().as_secs());
+        }
+        assert_eq!(rtos, [2, 4, 8, 16, 32, 60, 60, 60]);
+
+        // a new sample recomputes the timeout
+        rtt.add_sample(Duration::from_millis(100));
+        assert_eq!(rtt.rto(), Duration::from_secs(1));
+    }
+}
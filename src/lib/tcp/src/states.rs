@@ -373,7 +373,9 @@ impl<X: Dependencies> ListenState<X> {
 
             let mut connection =
                 Connection::new(header.dst(), header.src(), Seq::new(0), self.config);
-            connection.push_packet(header, payload).unwrap();
+            connection
+                .push_packet(header, payload, common.current_time())
+                .unwrap();
 
             let new_tcp = SynReceivedState::new(common, connection);
 
@@ -830,11 +832,14 @@ impl<X: Dependencies> TcpStateTrait<X> for SynSentState<X> {
             return (self.into(), Ok(0));
         }
 
-        let pushed_len = match self.connection.push_packet(header, payload) {
+        let now = self.common.current_time();
+        let pushed_len = match self.connection.push_packet(header, payload, now) {
             Ok(v) => v,
             Err(e) => return (self.into(), Err(e)),
         };
 
+        register_rto_timer(&self.common, &mut self.connection);
+
         // if the connection was reset
         if self.connection.is_reset() {
             if header.flags.contains(TcpFlags::RST) {
@@ -869,6 +874,7 @@ impl<X: Dependencies> TcpStateTrait<X> for SynSentState<X> {
         Result<(TcpHeader, Payload), PopPacketError>,
     ) {
         let rv = self.connection.pop_packet(self.common.current_time());
+        register_rto_timer(&self.common, &mut self.connection);
         (self.into(), rv)
     }
 
@@ -991,11 +997,14 @@ impl<X: Dependencies> TcpStateTrait<X> for SynReceivedState<X> {
             return (self.into(), Ok(0));
         }
 
-        let pushed_len = match self.connection.push_packet(header, payload) {
+        let now = self.common.current_time();
+        let pushed_len = match self.connection.push_packet(header, payload, now) {
             Ok(v) => v,
             Err(e) => return (self.into(), Err(e)),
         };
 
+        register_rto_timer(&self.common, &mut self.connection);
+
         // if the connection was reset
         if self.connection.is_reset() {
             if header.flags.contains(TcpFlags::RST) {
@@ -1022,6 +1031,7 @@ impl<X: Dependencies> TcpStateTrait<X> for SynReceivedState<X> {
         Result<(TcpHeader, Payload), PopPacketError>,
     ) {
         let rv = self.connection.pop_packet(self.common.current_time());
+        register_rto_timer(&self.common, &mut self.connection);
         (self.into(), rv)
     }
 
@@ -1130,11 +1140,14 @@ impl<X: Dependencies> TcpStateTrait<X> for EstablishedState<X> {
             return (self.into(), Ok(0));
         }
 
-        let pushed_len = match self.connection.push_packet(header, payload) {
+        let now = self.common.current_time();
+        let pushed_len = match self.connection.push_packet(header, payload, now) {
             Ok(v) => v,
             Err(e) => return (self.into(), Err(e)),
         };
 
+        register_rto_timer(&self.common, &mut self.connection);
+
         // if the connection was reset
         if self.connection.is_reset() {
             if header.flags.contains(TcpFlags::RST) {
@@ -1161,6 +1174,7 @@ impl<X: Dependencies> TcpStateTrait<X> for EstablishedState<X> {
         Result<(TcpHeader, Payload), PopPacketError>,
     ) {
         let rv = self.connection.pop_packet(self.common.current_time());
+        register_rto_timer(&self.common, &mut self.connection);
         (self.into(), rv)
     }
 
@@ -1261,11 +1275,14 @@ impl<X: Dependencies> TcpStateTrait<X> for FinWaitOneState<X> {
             return (self.into(), Ok(0));
         }
 
-        let pushed_len = match self.connection.push_packet(header, payload) {
+        let now = self.common.current_time();
+        let pushed_len = match self.connection.push_packet(header, payload, now) {
             Ok(v) => v,
             Err(e) => return (self.into(), Err(e)),
         };
 
+        register_rto_timer(&self.common, &mut self.connection);
+
         // if the connection was reset
         if self.connection.is_reset() {
             if header.flags.contains(TcpFlags::RST) {
@@ -1304,6 +1321,7 @@ impl<X: Dependencies> TcpStateTrait<X> for FinWaitOneState<X> {
         Result<(TcpHeader, Payload), PopPacketError>,
     ) {
         let rv = self.connection.pop_packet(self.common.current_time());
+        register_rto_timer(&self.common, &mut self.connection);
         (self.into(), rv)
     }
 
@@ -1404,11 +1422,14 @@ impl<X: Dependencies> TcpStateTrait<X> for FinWaitTwoState<X> {
             return (self.into(), Ok(0));
         }
 
-        let pushed_len = match self.connection.push_packet(header, payload) {
+        let now = self.common.current_time();
+        let pushed_len = match self.connection.push_packet(header, payload, now) {
             Ok(v) => v,
             Err(e) => return (self.into(), Err(e)),
         };
 
+        register_rto_timer(&self.common, &mut self.connection);
+
         // if the connection was reset
         if self.connection.is_reset() {
             if header.flags.contains(TcpFlags::RST) {
@@ -1435,6 +1456,7 @@ impl<X: Dependencies> TcpStateTrait<X> for FinWaitTwoState<X> {
         Result<(TcpHeader, Payload), PopPacketError>,
     ) {
         let rv = self.connection.pop_packet(self.common.current_time());
+        register_rto_timer(&self.common, &mut self.connection);
         (self.into(), rv)
     }
 
@@ -1542,11 +1564,14 @@ impl<X: Dependencies> TcpStateTrait<X> for ClosingState<X> {
             return (self.into(), Ok(0));
         }
 
-        let pushed_len = match self.connection.push_packet(header, payload) {
+        let now = self.common.current_time();
+        let pushed_len = match self.connection.push_packet(header, payload, now) {
             Ok(v) => v,
             Err(e) => return (self.into(), Err(e)),
         };
 
+        register_rto_timer(&self.common, &mut self.connection);
+
         // if the connection was reset
         if self.connection.is_reset() {
             if header.flags.contains(TcpFlags::RST) {
@@ -1575,6 +1600,7 @@ impl<X: Dependencies> TcpStateTrait<X> for ClosingState<X> {
         Result<(TcpHeader, Payload), PopPacketError>,
     ) {
         let rv = self.connection.pop_packet(self.common.current_time());
+        register_rto_timer(&self.common, &mut self.connection);
         (self.into(), rv)
     }
 
@@ -1700,11 +1726,14 @@ impl<X: Dependencies> TcpStateTrait<X> for TimeWaitState<X> {
         }
 
         // TODO: send RST for all packets?
-        let pushed_len = match self.connection.push_packet(header, payload) {
+        let now = self.common.current_time();
+        let pushed_len = match self.connection.push_packet(header, payload, now) {
             Ok(v) => v,
             Err(e) => return (self.into(), Err(e)),
         };
 
+        register_rto_timer(&self.common, &mut self.connection);
+
         // if the connection was reset
         if self.connection.is_reset() {
             if header.flags.contains(TcpFlags::RST) {
@@ -1725,6 +1754,7 @@ impl<X: Dependencies> TcpStateTrait<X> for TimeWaitState<X> {
         Result<(TcpHeader, Payload), PopPacketError>,
     ) {
         let rv = self.connection.pop_packet(self.common.current_time());
+        register_rto_timer(&self.common, &mut self.connection);
         (self.into(), rv)
     }
 
@@ -1850,11 +1880,14 @@ impl<X: Dependencies> TcpStateTrait<X> for CloseWaitState<X> {
             return (self.into(), Ok(0));
         }
 
-        let pushed_len = match self.connection.push_packet(header, payload) {
+        let now = self.common.current_time();
+        let pushed_len = match self.connection.push_packet(header, payload, now) {
             Ok(v) => v,
             Err(e) => return (self.into(), Err(e)),
         };
 
+        register_rto_timer(&self.common, &mut self.connection);
+
         // if the connection was reset
         if self.connection.is_reset() {
             if header.flags.contains(TcpFlags::RST) {
@@ -1875,6 +1908,7 @@ impl<X: Dependencies> TcpStateTrait<X> for CloseWaitState<X> {
         Result<(TcpHeader, Payload), PopPacketError>,
     ) {
         let rv = self.connection.pop_packet(self.common.current_time());
+        register_rto_timer(&self.common, &mut self.connection);
         (self.into(), rv)
     }
 
@@ -1984,11 +2018,14 @@ impl<X: Dependencies> TcpStateTrait<X> for LastAckState<X> {
             return (self.into(), Ok(0));
         }
 
-        let pushed_len = match self.connection.push_packet(header, payload) {
+        let now = self.common.current_time();
+        let pushed_len = match self.connection.push_packet(header, payload, now) {
             Ok(v) => v,
             Err(e) => return (self.into(), Err(e)),
         };
 
+        register_rto_timer(&self.common, &mut self.connection);
+
         // if the connection was reset
         if self.connection.is_reset() {
             if header.flags.contains(TcpFlags::RST) {
@@ -2017,6 +2054,7 @@ impl<X: Dependencies> TcpStateTrait<X> for LastAckState<X> {
         Result<(TcpHeader, Payload), PopPacketError>,
     ) {
         let rv = self.connection.pop_packet(self.common.current_time());
+        register_rto_timer(&self.common, &mut self.connection);
         (self.into(), rv)
     }
 
@@ -2338,3 +2376,64 @@ fn connection_was_reset<X: Dependencies>(
         ClosedState::new(common, None, /* was_connected= */ true).into()
     }
 }
+
+/// Register a timer for the connection's retransmission timeout, if needed. When the timer fires
+/// the earliest unacknowledged data is retransmitted, or the connection is aborted if it has been
+/// retransmitted too many times.
+fn register_rto_timer<X: Dependencies>(
+    common: &Common<X>,
+    connection: &mut Connection<X::Instant>,
+) {
+    let Some(time) = connection.rto_timer_to_register() else {
+        return;
+    };
+
+    common.register_timer(time, |mut state| {
+        let (common, connection) = match &mut state {
+            TcpStateEnum::SynSent(x) => (&x.common, &mut x.connection),
+            TcpStateEnum::SynReceived(x) => (&x.common, &mut x.connection),
+            TcpStateEnum::Established(x) => (&x.common, &mut x.connection),
+            TcpStateEnum::FinWaitOne(x) => (&x.common, &mut x.connection),
+            TcpStateEnum::FinWaitTwo(x) => (&x.common, &mut x.connection),
+            TcpStateEnum::Closing(x) => (&x.common, &mut x.connection),
+            TcpStateEnum::TimeWait(x) => (&x.common, &mut x.connection),
+            TcpStateEnum::CloseWait(x) => (&x.common, &mut x.connection),
+            TcpStateEnum::LastAck(x) => (&x.common, &mut x.connection),
+            // the connection no longer exists
+            _ => return state,
+        };
+
+        if connection.rto_timer_fired(common.current_time()) {
+            return retransmission_timed_out(state);
+        }
+
+        register_rto_timer(common, connection);
+        state
+    });
+}
+
+/// Abort the connection after too many retransmission timeouts.
+fn retransmission_timed_out<X: Dependencies>(state: TcpStateEnum<X>) -> TcpStateEnum<X> {
+    let (mut common, connection) = match state {
+        TcpStateEnum::SynSent(mut state) => {
+            // we haven't received a SYN yet, so there's no need to send a RST
+            state.common.error = Some(TcpError::TimedOut);
+
+            let (state, rv) = state.rst_close();
+            assert!(rv.is_ok());
+            return state;
+        }
+        TcpStateEnum::SynReceived(x) => (x.common, x.connection),
+        TcpStateEnum::Established(x) => (x.common, x.connection),
+        TcpStateEnum::FinWaitOne(x) => (x.common, x.connection),
+        TcpStateEnum::FinWaitTwo(x) => (x.common, x.connection),
+        TcpStateEnum::Closing(x) => (x.common, x.connection),
+        TcpStateEnum::TimeWait(x) => (x.common, x.connection),
+        TcpStateEnum::CloseWait(x) => (x.common, x.connection),
+        TcpStateEnum::LastAck(x) => (x.common, x.connection),
+        state => return state,
+    };
+
+    common.error = Some(TcpError::TimedOut);
+    reset_connection(common, connection).into()
+}
@@ -0,0 +1,275 @@
+//! Test retransmissions and reassembly with deterministic packet loss.
+
+use std::cell::{Ref, RefCell};
+use std::rc::Rc;
+
+use bytes::Bytes;
+
+use crate::tests::util::time::Duration;
+use crate::tests::{Host, Scheduler, TcpSocket, TestEnvState, establish_helper};
+use crate::util::SmallArrayBackedSlice;
+use crate::{Ipv4Header, Payload, PollState, TcpError, TcpFlags, TcpHeader, TcpState};
+
+/// Helper to get the state from a socket.
+fn s(tcp: &Rc<RefCell<TcpSocket>>) -> Ref<'_, TcpState<TestEnvState>> {
+    Ref::map(tcp.borrow(), |x| x.tcp_state())
+}
+
+/// Helper to build a packet sent from the peer of a socket returned by [`establish_helper`].
+fn peer_header(host: &Host, flags: TcpFlags, seq: u32, ack: u32) -> TcpHeader {
+    TcpHeader {
+        ip: Ipv4Header {
+            src: "5.6.7.8".parse().unwrap(),
+            dst: host.ip_addr,
+        },
+        flags,
+        src_port: 20,
+        dst_port: 10,
+        seq,
+        ack,
+        window_size: 10000,
+        selective_acks: None,
+        window_scale: None,
+        timestamp: None,
+        timestamp_echo: None,
+    }
+}
+
+/// Helper to pop all packets sent by the socket, returning the sequence range of each.
+fn pop_ranges(scheduler: &Scheduler) -> Vec<(u32, u32)> {
+    let mut ranges = vec![];
+    while let Some((header, payload)) = scheduler.pop_packet() {
+        ranges.push((header.seq, header.seq + payload.len()));
+    }
+    ranges
+}
+
+#[test]
+fn test_retransmit_timeout() {
+    let scheduler = Scheduler::new();
+    let mut host = Host::new();
+
+    // get an established tcp socket
+    let tcp = establish_helper(&scheduler, &mut host);
+
+    // send two packets, which are both lost
+    TcpSocket::sendmsg(&tcp, &[0u8; 3000][..], 3000).unwrap();
+    assert_eq!(pop_ranges(&scheduler), [(1, 1501), (1501, 3001)]);
+
+    // nothing is retransmitted before the initial 1 second timeout
+    scheduler.advance(Duration::from_millis(999));
+    assert_eq!(pop_ranges(&scheduler), []);
+
+    // only the first packet is retransmitted since the congestion window is now one segment
+    scheduler.advance(Duration::from_millis(1));
+    assert_eq!(pop_ranges(&scheduler), [(1, 1501)]);
+
+    // the retransmission is also lost, and the timeout has been doubled to 2 seconds
+    scheduler.advance(Duration::from_millis(1999));
+    assert_eq!(pop_ranges(&scheduler), []);
+    scheduler.advance(Duration::from_millis(1));
+    assert_eq!(pop_ranges(&scheduler), [(1, 1501)]);
+
+    // acknowledge the first packet, and the second packet is then retransmitted
+    let header = peer_header(&host, TcpFlags::ACK, 1, 1501);
+    tcp.borrow_mut().push_in_packet(&header, Payload::default());
+    assert_eq!(pop_ranges(&scheduler), [(1501, 3001)]);
+
+    // acknowledge the second packet, and there's nothing left to retransmit
+    let header = peer_header(&host, TcpFlags::ACK, 1, 3001);
+    tcp.borrow_mut().push_in_packet(&header, Payload::default());
+    scheduler.advance(Duration::from_secs(300));
+    assert_eq!(pop_ranges(&scheduler), []);
+    assert!(s(&tcp).as_established().is_some());
+}
+
+#[test]
+fn test_retransmit_give_up() {
+    let scheduler = Scheduler::new();
+    let mut host = Host::new();
+
+    // get an established tcp socket
+    let tcp = establish_helper(&scheduler, &mut host);
+
+    // send a packet which is lost, along with every retransmission
+    TcpSocket::sendmsg(&tcp, &b"hello"[..], 5).unwrap();
+    assert_eq!(pop_ranges(&scheduler), [(1, 6)]);
+
+    // the timeout doubles after each retransmission, up to a maximum of 60 seconds
+    let mut times = vec![];
+    for secs in 1..=1000 {
+        scheduler.advance(Duration::from_secs(1));
+
+        while let Some((header, _payload)) = scheduler.pop_packet() {
+            if header.flags.contains(TcpFlags::RST) {
+                times.push((secs, "rst"));
+            } else {
+                assert_eq!(header.seq, 1);
+                times.push((secs, "data"));
+            }
+        }
+    }
+
+    let mut expected = vec![];
+    let mut time = 0;
+    let mut rto = 1;
+    for _ in 0..15 {
+        time += rto;
+        rto = std::cmp::min(rto * 2, 60);
+        expected.push((time, "data"));
+    }
+    // after the 15th retransmission times out, the connection is reset
+    expected.push((time + rto, "rst"));
+    assert_eq!(times, expected);
+
+    assert!(s(&tcp).as_closed().is_some());
+    assert!(s(&tcp).poll().contains(PollState::ERROR));
+    let error = tcp.borrow_mut().with_tcp_state(|state| state.clear_error());
+    assert!(matches!(error, Some(TcpError::TimedOut)));
+}
+
+#[test]
+fn test_reassembly() {
+    let scheduler = Scheduler::new();
+    let mut host = Host::new();
+
+    // get an established tcp socket
+    let tcp = establish_helper(&scheduler, &mut host);
+
+    // the second packet (with a FIN) arrives before the first
+    let header = peer_header(&host, TcpFlags::ACK | TcpFlags::FIN, 6, 1);
+    let pushed_len = tcp
+        .borrow_mut()
+        .push_in_packet(&header, Bytes::from(&b"world"[..]).into());
+    assert_eq!(pushed_len, 0);
+
+    // the socket immediately sends a duplicate acknowledgement with a SACK block
+    let (header, _) = scheduler.pop_packet().unwrap();
+    assert_eq!(header.ack, 1);
+    assert_eq!(header.selective_acks.as_deref(), Some(&[(6, 11)][..]));
+    assert!(scheduler.pop_packet().is_none());
+
+    // nothing can be read yet
+    assert!(!s(&tcp).poll().contains(PollState::READABLE));
+    assert!(s(&tcp).as_established().is_some());
+
+    // a third packet is received twice, and is entirely a duplicate of the stored data
+    for _ in 0..2 {
+        let header = peer_header(&host, TcpFlags::ACK, 7, 1);
+        let pushed_len = tcp
+            .borrow_mut()
+            .push_in_packet(&header, Bytes::from(&b"orl"[..]).into());
+        assert_eq!(pushed_len, 0);
+        let (header, _) = scheduler.pop_packet().unwrap();
+        assert_eq!(header.selective_acks.as_deref(), Some(&[(6, 11)][..]));
+    }
+
+    // the first packet fills the gap, and the stored data and FIN are added
+    let header = peer_header(&host, TcpFlags::ACK, 1, 1);
+    let pushed_len = tcp
+        .borrow_mut()
+        .push_in_packet(&header, Bytes::from(&b"hello"[..]).into());
+    assert_eq!(pushed_len, 10);
+
+    let (header, _) = scheduler.pop_packet().unwrap();
+    assert_eq!(header.ack, 12);
+    assert!(header.selective_acks.is_none());
+    assert!(s(&tcp).as_close_wait().is_some());
+
+    let mut recv_buf = vec![0; 10];
+    TcpSocket::recvmsg(&tcp, &mut recv_buf[..], 10).unwrap();
+    assert_eq!(recv_buf, b"helloworld");
+}
+
+#[test]
+fn test_fast_retransmit() {
+    let scheduler = Scheduler::new();
+    let mut host = Host::new();
+
+    // get an established tcp socket
+    let tcp = establish_helper(&scheduler, &mut host);
+
+    // send four packets, and the first is lost
+    TcpSocket::sendmsg(&tcp, &[0u8; 6000][..], 6000).unwrap();
+    assert_eq!(
+        pop_ranges(&scheduler),
+        [(1, 1501), (1501, 3001), (3001, 4501), (4501, 6001)],
+    );
+
+    // the peer sends a duplicate acknowledgement for each of the other three packets, and the third
+    // duplicate acknowledgement triggers a retransmission without waiting for the timeout
+    for i in 0..3 {
+        let header = peer_header(&host, TcpFlags::ACK, 1, 1);
+        tcp.borrow_mut().push_in_packet(&header, Payload::default());
+
+        let expected: &[_] = if i < 2 { &[] } else { &[(1, 1501)] };
+        assert_eq!(pop_ranges(&scheduler), expected);
+    }
+
+    // a packet with a payload isn't a duplicate acknowledgement
+    let header = peer_header(&host, TcpFlags::ACK, 1, 1);
+    tcp.borrow_mut()
+        .push_in_packet(&header, Bytes::from(&b"hello"[..]).into());
+    let (header, payload) = scheduler.pop_packet().unwrap();
+    assert_eq!(header.ack, 6);
+    assert!(payload.is_empty());
+    assert!(scheduler.pop_packet().is_none());
+
+    // the retransmission fills the gap, so the peer acknowledges everything
+    let header = peer_header(&host, TcpFlags::ACK, 6, 6001);
+    tcp.borrow_mut().push_in_packet(&header, Payload::default());
+    assert!(scheduler.pop_packet().is_none());
+
+    // there's nothing left to retransmit
+    scheduler.advance(Duration::from_secs(300));
+    assert_eq!(pop_ranges(&scheduler), []);
+}
+
+#[test]
+fn test_sack_retransmit() {
+    let scheduler = Scheduler::new();
+    let mut host = Host::new();
+
+    // get an established tcp socket
+    let tcp = establish_helper(&scheduler, &mut host);
+
+    // send four packets, and the first and third are lost
+    TcpSocket::sendmsg(&tcp, &[0u8; 6000][..], 6000).unwrap();
+    assert_eq!(
+        pop_ranges(&scheduler),
+        [(1, 1501), (1501, 3001), (3001, 4501), (4501, 6001)],
+    );
+
+    // the peer sends duplicate acknowledgements with SACK blocks for the packets it received
+    let sack_blocks: [&[(u32, u32)]; 3] = [
+        &[(1501, 3001)],
+        &[(4501, 6001), (1501, 3001)],
+        &[(4501, 6001), (1501, 3001)],
+    ];
+    for (i, blocks) in sack_blocks.into_iter().enumerate() {
+        let mut header = peer_header(&host, TcpFlags::ACK, 1, 1);
+        header.selective_acks = Some(SmallArrayBackedSlice::new(blocks).unwrap());
+        tcp.borrow_mut().push_in_packet(&header, Payload::default());
+
+        // the third duplicate acknowledgement triggers a retransmission of the first hole, but the
+        // second hole can't be retransmitted until more data has left the network
+        let expected: &[_] = if i < 2 { &[] } else { &[(1, 1501)] };
+        assert_eq!(pop_ranges(&scheduler), expected);
+    }
+
+    // the peer acknowledges up to the second hole, which is then retransmitted (the selectively
+    // acknowledged packets aren't retransmitted)
+    let mut header = peer_header(&host, TcpFlags::ACK, 1, 3001);
+    header.selective_acks = Some(SmallArrayBackedSlice::new(&[(4501, 6001)]).unwrap());
+    tcp.borrow_mut().push_in_packet(&header, Payload::default());
+    assert_eq!(pop_ranges(&scheduler), [(3001, 4501)]);
+
+    // the peer acknowledges everything
+    let header = peer_header(&host, TcpFlags::ACK, 1, 6001);
+    tcp.borrow_mut().push_in_packet(&header, Payload::default());
+    assert!(scheduler.pop_packet().is_none());
+
+    // there's nothing left to retransmit
+    scheduler.advance(Duration::from_secs(300));
+    assert_eq!(pop_ranges(&scheduler), []);
+}
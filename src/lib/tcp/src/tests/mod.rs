@@ -5,6 +5,7 @@
 // TODO: ideally remove this
 #![allow(dead_code)]
 
+mod loss;
 mod send_recv;
 mod transitions;
 mod window_scale;
@@ -789,17 +790,20 @@ fn test_timer() {
     assert_eq!(s(&tcp).as_listen().unwrap().children.len(), 1);
 
     // the new child state set a timer event at 60 seconds to close if still in the "syn-received"
-    // state, and we can see that event in the event queue
-    assert_eq!(scheduler.event_queue.borrow().len(), 1);
+    // state, and a retransmission timer event at 1 second for the SYN-ACK it sent, and we can see
+    // those events in the event queue
+    assert_eq!(scheduler.event_queue.borrow().len(), 2);
 
-    // at 59 seconds, the child state still exists
+    // at 59 seconds, the child state still exists (the SYN-ACK was retransmitted at 1, 3, 7, 15,
+    // and 31 seconds, and the next retransmission timer event is at 63 seconds)
     scheduler.advance(Duration::from_secs(59));
-    assert_eq!(scheduler.event_queue.borrow().len(), 1);
+    assert_eq!(scheduler.event_queue.borrow().len(), 2);
     assert_eq!(s(&tcp).as_listen().unwrap().children.len(), 1);
 
-    // at 61 seconds, the event has run and the child state has now closed
+    // at 61 seconds, the event has run and the child state has now closed (the retransmission
+    // timer event is still queued, but will do nothing)
     scheduler.advance(Duration::from_secs(2));
-    assert_eq!(scheduler.event_queue.borrow().len(), 0);
+    assert_eq!(scheduler.event_queue.borrow().len(), 1);
     assert_eq!(s(&tcp).as_listen().unwrap().children.len(), 0);
 }
 
@@ -109,6 +109,12 @@ impl WindowScaling {
         !(self.disabled || (self.received_syn && self.send_window_scale_shift.is_none()))
     }
 
+    /// The window scale that was sent in our SYN packet, or `None` if we haven't sent a SYN packet.
+    /// A retransmitted SYN packet must use the same window scale.
+    pub fn sent_syn_window_scale(&self) -> Option<Option<u8>> {
+        self.sent_syn.then_some(self.recv_window_scale_shift)
+    }
+
     /// Has window scaling been configured? This does *not* mean that it's enabled. If this returns
     /// true, then `recv_window_scale_shift()` and `send_window_scale_shift()` should not panic.
     pub fn is_configured(&self) -> bool {
@@ -0,0 +1,333 @@
+//! A trace of the wall-clock time that hosts spend running their events, running managed threads,
+//! and handling syscalls, which shows which hosts and processes are slowing down a simulation. The
+//! trace is written in the Chrome Trace Event format, which can be opened in Perfetto
+//! (<https://ui.perfetto.dev>) or `chrome://tracing`.
+//!
+//! Each host is shown as a trace "process" with the host's id, and the host's event runs are on a
+//! trace "thread" with id 0. Each managed thread is a trace thread with its virtual thread id, with
+//! the thread's syscalls nested inside each of its runs.
+//!
+//! <https://docs.google.com/document/d/1CvAClvFfyA5R-PhYUmn5OOQtYMH4h6I0nSsKchNAySU>
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde_json::json;
+use shadow_shim_helper_rs::HostId;
+use shadow_shim_helper_rs::emulated_time::EmulatedTime;
+use shadow_shim_helper_rs::simulation_time::SimulationTime;
+
+use crate::host::thread::ThreadId;
+
+/// The trace thread of each host's event runs. Virtual thread ids are never 0.
+const EXECUTE_TID: libc::pid_t = 0;
+
+/// Something that a host spent time on.
+#[derive(Debug, Clone)]
+pub enum Activity {
+    /// A run of the host's events until `until`, which ran `num_events` events.
+    Execute {
+        until: EmulatedTime,
+        num_events: u64,
+    },
+    /// A run of a managed thread until it blocked or exited. `result` describes why it stopped.
+    Resume {
+        tid: ThreadId,
+        process_name: String,
+        result: &'static str,
+    },
+    /// A syscall that was handled by Shadow. A syscall that blocks is handled again each time the
+    /// thread is resumed, until it completes.
+    Syscall {
+        tid: ThreadId,
+        name: &'static str,
+        blocked: bool,
+        native: bool,
+    },
+    /// An event that was postponed by `delay` since the host's simulated CPU was busy. This has no
+    /// duration.
+    CpuDelay { delay: SimulationTime },
+}
+
+/// An activity of a host, and when it happened.
+#[derive(Debug, Clone)]
+pub struct ActivityEvent {
+    pub activity: Activity,
+    /// The wall-clock time that the activity started.
+    pub start: Instant,
+    /// The wall-clock time that the activity took.
+    pub duration: Duration,
+    /// The simulation time that the activity started at.
+    pub sim_time: EmulatedTime,
+}
+
+/// Writes the activity of many hosts to a single trace file. Hosts buffer their own activity and
+/// write it after each run of their events, so the events of different hosts are interleaved in
+/// the file. Trace viewers sort the events by time.
+pub struct ActivityTrace {
+    writer: Mutex<TraceWriter<BufWriter<File>>>,
+}
+
+impl ActivityTrace {
+    /// Create the trace file at `path`, with names for each host given as its id and name.
+    /// Timestamps in the trace are relative to when the trace was created.
+    pub fn new<'a>(
+        path: &Path,
+        hosts: impl IntoIterator<Item = (HostId, &'a str)>,
+    ) -> std::io::Result<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        let mut writer = TraceWriter::new(file, Instant::now())?;
+        for (host_id, name) in hosts {
+            writer.add_host(host_id, name)?;
+        }
+
+        Ok(Self {
+            writer: Mutex::new(writer),
+        })
+    }
+
+    pub fn write(&self, host_id: HostId, events: &[ActivityEvent]) -> std::io::Result<()> {
+        let mut writer = self.writer.lock().unwrap();
+        for event in events {
+            writer.write(host_id, event)?;
+        }
+        Ok(())
+    }
+
+    /// End the trace and flush the file.
+    pub fn finish(&self) -> std::io::Result<()> {
+        self.writer.lock().unwrap().finish()
+    }
+}
+
+/// Writes trace events in the JSON array format of the Chrome Trace Event format.
+struct TraceWriter<W: Write> {
+    writer: W,
+    start: Instant,
+    /// The threads that have been given a name, by host id and virtual thread id.
+    named_threads: HashSet<(HostId, libc::pid_t)>,
+    num_written: u64,
+}
+
+impl<W: Write> TraceWriter<W> {
+    fn new(mut writer: W, start: Instant) -> std::io::Result<Self> {
+        writer.write_all(b"[")?;
+        Ok(Self {
+            writer,
+            start,
+            named_threads: HashSet::new(),
+            num_written: 0,
+        })
+    }
+
+    fn add_host(&mut self, host_id: HostId, name: &str) -> std::io::Result<()> {
+        let pid = u32::from(host_id);
+        self.write_json(json!({
+            "name": "process_name",
+            "ph": "M",
+            "pid": pid,
+            "args": {"name": name},
+        }))?;
+        self.write_json(json!({
+            "name": "thread_name",
+            "ph": "M",
+            "pid": pid,
+            "tid": EXECUTE_TID,
+            "args": {"name": "events"},
+        }))
+    }
+
+    fn write(&mut self, host_id: HostId, event: &ActivityEvent) -> std::io::Result<()> {
+        let pid = u32::from(host_id);
+        // chrome traces use microseconds
+        let ts = event.start.saturating_duration_since(self.start).as_nanos() as f64 / 1e3;
+        let dur = event.duration.as_nanos() as f64 / 1e3;
+        let sim_time_ns = u64::try_from(event.sim_time.to_abs_simtime().as_nanos()).unwrap();
+
+        let value = match &event.activity {
+            Activity::Execute { until, num_events } => {
+                let until_ns = u64::try_from(until.to_abs_simtime().as_nanos()).unwrap();
+                json!({
+                    "name": "execute",
+                    "cat": "host",
+                    "ph": "X",
+                    "ts": ts,
+                    "dur": dur,
+                    "pid": pid,
+                    "tid": EXECUTE_TID,
+                    "args": {
+                        "sim_time_ns": sim_time_ns,
+                        "until_ns": until_ns,
+                        "events": num_events,
+                    },
+                })
+            }
+            Activity::Resume {
+                tid,
+                process_name,
+                result,
+            } => {
+                let tid = libc::pid_t::from(*tid);
+                if self.named_threads.insert((host_id, tid)) {
+                    self.write_json(json!({
+                        "name": "thread_name",
+                        "ph": "M",
+                        "pid": pid,
+                        "tid": tid,
+                        "args": {"name": format!("{process_name} ({tid})")},
+                    }))?;
+                }
+                json!({
+                    "name": "run",
+                    "cat": "thread",
+                    "ph": "X",
+                    "ts": ts,
+                    "dur": dur,
+                    "pid": pid,
+                    "tid": tid,
+                    "args": {"sim_time_ns": sim_time_ns, "result": result},
+                })
+            }
+            Activity::Syscall {
+                tid,
+                name,
+                blocked,
+                native,
+            } => json!({
+                "name": name,
+                "cat": "syscall",
+                "ph": "X",
+                "ts": ts,
+                "dur": dur,
+                "pid": pid,
+                "tid": libc::pid_t::from(*tid),
+                "args": {"sim_time_ns": sim_time_ns, "blocked": blocked, "native": native},
+            }),
+            Activity::CpuDelay { delay } => {
+                let delay_ns = u64::try_from(delay.as_nanos()).unwrap();
+                json!({
+                    "name": "cpu delay",
+                    "cat": "host",
+                    "ph": "i",
+                    "s": "t",
+                    "ts": ts,
+                    "pid": pid,
+                    "tid": EXECUTE_TID,
+                    "args": {"sim_time_ns": sim_time_ns, "delay_ns": delay_ns},
+                })
+            }
+        };
+
+        self.write_json(value)
+    }
+
+    fn write_json(&mut self, value: serde_json::Value) -> std::io::Result<()> {
+        if self.num_written > 0 {
+            self.writer.write_all(b",")?;
+        }
+        self.writer.write_all(b"\n")?;
+        serde_json::to_writer(&mut self.writer, &value)?;
+        self.num_written += 1;
+        Ok(())
+    }
+
+    /// Close the array. Trace viewers can also load a trace that wasn't closed, for example if
+    /// the simulation panicked.
+    fn finish(&mut self) -> std::io::Result<()> {
+        self.writer.write_all(b"\n]\n")?;
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace() {
+        let start = Instant::now();
+        let mut writer = TraceWriter::new(Vec::new(), start).unwrap();
+        let host_id = HostId::from(3);
+        writer.add_host(host_id, "server").unwrap();
+
+        let sim_time = EmulatedTime::SIMULATION_START + SimulationTime::from_millis(2);
+        let tid = ThreadId::try_from(1000).unwrap();
+        let events = [
+            ActivityEvent {
+                activity: Activity::Execute {
+                    until: EmulatedTime::SIMULATION_START + SimulationTime::from_millis(10),
+                    num_events: 4,
+                },
+                start,
+                duration: Duration::from_micros(50),
+                sim_time,
+            },
+            ActivityEvent {
+                activity: Activity::Resume {
+                    tid,
+                    process_name: "server.1000".to_string(),
+                    result: "blocked",
+                },
+                start: start + Duration::from_micros(10),
+                duration: Duration::from_micros(30),
+                sim_time,
+            },
+            ActivityEvent {
+                activity: Activity::Resume {
+                    tid,
+                    process_name: "server.1000".to_string(),
+                    result: "exited thread",
+                },
+                start: start + Duration::from_micros(40),
+                duration: Duration::from_micros(5),
+                sim_time,
+            },
+            ActivityEvent {
+                activity: Activity::Syscall {
+                    tid,
+                    name: "read",
+                    blocked: true,
+                    native: false,
+                },
+                start: start + Duration::from_micros(20),
+                duration: Duration::from_micros(2),
+                sim_time,
+            },
+        ];
+        for event in &events {
+            writer.write(host_id, event).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let trace: serde_json::Value = serde_json::from_slice(&writer.writer).unwrap();
+        let trace = trace.as_array().unwrap();
+        // two host names, four events, and one thread name
+        assert_eq!(trace.len(), 7);
+
+        assert_eq!(trace[0]["name"], "process_name");
+        assert_eq!(trace[0]["pid"], 3);
+        assert_eq!(trace[0]["args"]["name"], "server");
+
+        assert_eq!(trace[2]["name"], "execute");
+        assert_eq!(trace[2]["ts"], 0.0);
+        assert_eq!(trace[2]["dur"], 50.0);
+        assert_eq!(trace[2]["args"]["sim_time_ns"], 2_000_000);
+        assert_eq!(trace[2]["args"]["until_ns"], 10_000_000);
+
+        // the thread is only named once
+        assert_eq!(trace[3]["name"], "thread_name");
+        assert_eq!(trace[3]["tid"], 1000);
+        assert_eq!(trace[3]["args"]["name"], "server.1000 (1000)");
+        assert_eq!(trace[4]["name"], "run");
+        assert_eq!(trace[5]["args"]["result"], "exited thread");
+
+        assert_eq!(trace[6]["name"], "read");
+        assert_eq!(trace[6]["cat"], "syscall");
+        assert_eq!(trace[6]["ts"], 20.0);
+        assert_eq!(trace[6]["args"]["blocked"], true);
+    }
+}
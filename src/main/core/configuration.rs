@@ -26,7 +26,7 @@ use serde::{Deserialize, Serialize};
 use shadow_shim_helper_rs::simulation_time::SimulationTime;
 
 use crate::cshadow as c;
-use crate::host::syscall::formatter::FmtOptions;
+use crate::host::syscall::formatter::{FmtOptions, StraceFormat, StraceOptions};
 use crate::utility::units::{self, Unit};
 
 const START_HELP_TEXT: &str = "\
@@ -75,6 +75,12 @@ pub struct CliOptions {
     #[clap(long)]
     pub show_config: bool,
 
+    /// Serve run-control requests (JSON lines) on a Unix socket at this path. The simulation will
+    /// pause at the start until a client resumes it
+    #[cfg(feature = "enable_run_control")]
+    #[clap(long, value_name = "path")]
+    pub run_control_socket: Option<std::path::PathBuf>,
+
     #[clap(flatten)]
     pub general: GeneralOptions,
 
@@ -206,12 +212,20 @@ impl ConfigOptions {
         SimulationTime::from_nanos(nanos)
     }
 
-    pub fn strace_logging_mode(&self) -> Option<FmtOptions> {
-        match self.experimental.strace_logging_mode.as_ref().unwrap() {
-            StraceLoggingMode::Standard => Some(FmtOptions::Standard),
-            StraceLoggingMode::Deterministic => Some(FmtOptions::Deterministic),
-            StraceLoggingMode::Off => None,
-        }
+    pub fn metrics_interval(&self) -> Option<SimulationTime> {
+        let t = self.general.metrics_interval.flatten()?;
+        let nanos = t.convert(units::TimePrefix::Nano).unwrap().value();
+        Some(SimulationTime::from_nanos(nanos))
+    }
+
+    pub fn strace_logging_mode(&self) -> Option<StraceOptions> {
+        let (fmt, format) = match self.experimental.strace_logging_mode.as_ref().unwrap() {
+            StraceLoggingMode::Standard => (FmtOptions::Standard, StraceFormat::Text),
+            StraceLoggingMode::Deterministic => (FmtOptions::Deterministic, StraceFormat::Text),
+            StraceLoggingMode::Json => (FmtOptions::Standard, StraceFormat::Json),
+            StraceLoggingMode::Off => return None,
+        };
+        Some(StraceOptions { fmt, format })
     }
 }
 
@@ -291,6 +305,43 @@ pub struct GeneralOptions {
     #[clap(help = GENERAL_HELP.get("model_unblocked_syscall_latency").unwrap().as_str())]
     #[serde(default = "default_some_false")]
     pub model_unblocked_syscall_latency: Option<bool>,
+
+    /// Interval at which to write samples of each host's network counters and queues to a file
+    /// in the host's data directory. Samples pause while the host has no other events.
+    #[clap(long, value_name = "seconds")]
+    #[clap(help = GENERAL_HELP.get("metrics_interval").unwrap().as_str())]
+    #[serde(default)]
+    pub metrics_interval: Option<NullableOption<units::Time<units::TimePrefix>>>,
+
+    /// File format of the network metrics
+    #[clap(long, value_name = "format")]
+    #[clap(help = GENERAL_HELP.get("metrics_format").unwrap().as_str())]
+    #[serde(default = "default_metrics_format")]
+    pub metrics_format: Option<MetricsFormat>,
+
+    /// Write the packets captured by hosts with pcap enabled to a single pcapng file
+    #[clap(long, value_name = "bool")]
+    #[clap(help = GENERAL_HELP.get("pcap_merged").unwrap().as_str())]
+    #[serde(default = "default_some_false")]
+    pub pcap_merged: Option<bool>,
+
+    /// Only capture packets that match this filter expression
+    #[clap(long, value_name = "filter")]
+    #[clap(help = GENERAL_HELP.get("pcap_filter").unwrap().as_str())]
+    #[serde(default)]
+    pub pcap_filter: Option<NullableOption<String>>,
+
+    /// Write every status change of every packet to a trace file
+    #[clap(long, value_name = "bool")]
+    #[clap(help = GENERAL_HELP.get("packet_trace").unwrap().as_str())]
+    #[serde(default = "default_some_false")]
+    pub packet_trace: Option<bool>,
+
+    /// Write a trace of the wall-clock time that hosts spend running events, threads, and syscalls
+    #[clap(long, value_name = "bool")]
+    #[clap(help = GENERAL_HELP.get("activity_trace").unwrap().as_str())]
+    #[serde(default = "default_some_false")]
+    pub activity_trace: Option<bool>,
 }
 
 impl GeneralOptions {
@@ -324,6 +375,25 @@ pub struct NetworkOptions {
     #[clap(long, value_name = "bool")]
     #[clap(help = NETWORK_HELP.get("use_shortest_path").unwrap().as_str())]
     pub use_shortest_path: Option<bool>,
+
+    /// The distribution of the random delay that is added to a packet's latency, which is scaled
+    /// by the jitter of the packet's path
+    #[serde(default = "default_some_jitter_uniform")]
+    #[clap(long, value_name = "distribution")]
+    #[clap(help = NETWORK_HELP.get("jitter_distribution").unwrap().as_str())]
+    pub jitter_distribution: Option<JitterDistribution>,
+
+    /// Allow jitter to reorder the packets of a flow. If false, a packet is never delivered before
+    /// a packet that was sent earlier on the same flow.
+    #[serde(default = "default_some_false")]
+    #[clap(long, value_name = "bool")]
+    #[clap(help = NETWORK_HELP.get("allow_packet_reordering").unwrap().as_str())]
+    pub allow_packet_reordering: Option<bool>,
+
+    /// Changes to the network during the simulation, such as partitions and link failures
+    #[clap(skip)]
+    #[serde(default)]
+    pub faults: Option<Vec<NetworkFaultOptions>>,
 }
 
 impl NetworkOptions {
@@ -418,6 +488,27 @@ pub struct ExperimentalOptions {
     #[clap(help = EXP_HELP.get("use_dynamic_runahead").unwrap().as_str())]
     pub use_dynamic_runahead: Option<bool>,
 
+    /// Give each host its own round end based on the smallest latencies from other hosts, so that
+    /// packets are always delivered at their exact latency. Overrides the runahead.
+    #[clap(hide_short_help = true)]
+    #[clap(long, value_name = "bool")]
+    #[clap(help = EXP_HELP.get("use_per_host_lookahead").unwrap().as_str())]
+    pub use_per_host_lookahead: Option<bool>,
+
+    /// Addresses (`host:port`) of the Shadow processes that run the simulation together, one per
+    /// process. Each process runs a subset of the hosts, and exchanges packets with the others over
+    /// TCP
+    #[clap(hide_short_help = true)]
+    #[clap(long, value_name = "addresses", value_delimiter = ',')]
+    #[clap(help = EXP_HELP.get("distributed_addresses").unwrap().as_str())]
+    pub distributed_addresses: Option<Vec<String>>,
+
+    /// The index of this process in `distributed_addresses`
+    #[clap(hide_short_help = true)]
+    #[clap(long, value_name = "N")]
+    #[clap(help = EXP_HELP.get("distributed_rank").unwrap().as_str())]
+    pub distributed_rank: Option<u32>,
+
     /// Initial size of the socket's send buffer
     #[clap(hide_short_help = true)]
     #[clap(long, value_name = "bytes")]
@@ -454,6 +545,13 @@ pub struct ExperimentalOptions {
     #[clap(help = EXP_HELP.get("strace_logging_mode").unwrap().as_str())]
     pub strace_logging_mode: Option<StraceLoggingMode>,
 
+    /// Also log the syscalls of all processes to a single "strace.jsonl" file. Requires the "json"
+    /// strace logging mode
+    #[clap(hide_short_help = true)]
+    #[clap(long, value_name = "bool")]
+    #[clap(help = EXP_HELP.get("strace_logging_merged").unwrap().as_str())]
+    pub strace_logging_merged: Option<bool>,
+
     /// Max amount of execution-time latency allowed to accumulate before the
     /// clock is moved forward. Moving the clock forward is a potentially
     /// expensive operation, so larger values reduce simulation overhead, at the
@@ -561,12 +659,16 @@ impl Default for ExperimentalOptions {
                 units::TimePrefix::Milli,
             ))),
             use_dynamic_runahead: Some(false),
+            use_per_host_lookahead: Some(false),
+            distributed_addresses: Some(Vec::new()),
+            distributed_rank: Some(0),
             socket_send_buffer: Some(units::Bytes::new(131_072, units::SiPrefixUpper::Base)),
             socket_send_autotune: Some(true),
             socket_recv_buffer: Some(units::Bytes::new(174_760, units::SiPrefixUpper::Base)),
             socket_recv_autotune: Some(true),
             interface_qdisc: Some(QDiscMode::Fifo),
             strace_logging_mode: Some(StraceLoggingMode::Off),
+            strace_logging_merged: Some(false),
             scheduler: Some(Scheduler::ThreadPerCore),
             report_errors_to_stderr: Some(true),
             use_new_tcp: Some(false),
@@ -608,6 +710,16 @@ pub struct HostDefaultOptions {
     #[clap(long, value_name = "bytes")]
     #[clap(help = HOST_HELP.get("pcap_capture_size").unwrap().as_str())]
     pub pcap_capture_size: Option<units::Bytes<units::SiPrefixUpper>>,
+
+    /// The congestion control algorithm used by new TCP sockets
+    #[clap(long, value_name = "algorithm")]
+    #[clap(help = HOST_HELP.get("tcp_congestion_control").unwrap().as_str())]
+    pub tcp_congestion_control: Option<TcpCongestionControl>,
+
+    /// The queueing discipline for packets sent from the host's network interface
+    #[clap(long, value_name = "qdisc")]
+    #[clap(help = HOST_HELP.get("qdisc").unwrap().as_str())]
+    pub qdisc: Option<QDiscOptions>,
 }
 
 impl HostDefaultOptions {
@@ -619,6 +731,8 @@ impl HostDefaultOptions {
             // capture all the data available from the packet". The maximum length of an IP packet
             // (including the header) is 65535 bytes.
             pcap_capture_size: Some(units::Bytes::new(65535, units::SiPrefixUpper::Base)),
+            tcp_congestion_control: Some(TcpCongestionControl::Reno),
+            qdisc: Some(QDiscOptions::Noqueue),
         }
     }
 
@@ -641,6 +755,8 @@ impl Default for HostDefaultOptions {
             log_level: None,
             pcap_enabled: None,
             pcap_capture_size: None,
+            tcp_congestion_control: None,
+            qdisc: None,
         }
     }
 }
@@ -712,11 +828,48 @@ pub struct ProcessOptions {
     /// if the actual state doesn't match.
     #[serde(default)]
     pub expected_final_state: ProcessFinalState,
+
+    /// Conditions that must all be met before the process is started. The process is started at
+    /// `start_time` or once the conditions are met, whichever is later.
+    #[serde(default)]
+    pub start_after: Vec<StartConditionOptions>,
+}
+
+/// A condition that must be met before a process is started. Each condition is about a host,
+/// which is the process's own host if not given.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum StartConditionOptions {
+    /// A process exited with status 0
+    ProcessExited {
+        #[serde(default)]
+        host: Option<HostName>,
+        /// The index of the process in the host's `processes`
+        process: usize,
+    },
+    /// A file exists in the host's data directory
+    FileExists {
+        #[serde(default)]
+        host: Option<HostName>,
+        /// The path of the file, relative to the host's data directory
+        path: std::path::PathBuf,
+    },
+    /// A TCP socket is listening on a port of the host
+    TcpListening {
+        #[serde(default)]
+        host: Option<HostName>,
+        port: u16,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct HostOptions {
+    /// Number of hosts to create from this entry, which are named by appending their index
+    /// (starting at 1) to the entry's name
+    #[serde(default)]
+    pub quantity: Option<std::num::NonZeroU32>,
+
     /// Network graph node ID to assign the host to
     pub network_node_id: u32,
 
@@ -726,6 +879,10 @@ pub struct HostOptions {
     #[serde(default)]
     pub ip_addr: Option<std::net::Ipv4Addr>,
 
+    /// IPv6 address to assign to the host
+    #[serde(default)]
+    pub ipv6_addr: Option<std::net::Ipv6Addr>,
+
     /// Downstream bandwidth capacity of the host
     #[serde(default)]
     pub bandwidth_down: Option<units::BitsPerSec<units::SiPrefixUpper>>,
@@ -736,6 +893,37 @@ pub struct HostOptions {
 
     #[serde(default)]
     pub host_options: HostDefaultOptions,
+
+    /// Skew of the host's clocks relative to the simulation time
+    #[serde(default)]
+    pub clock: HostClockOptions,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct HostClockOptions {
+    /// Initial offset of the host's realtime clock from the simulation time
+    #[serde(default)]
+    pub offset: units::SignedTime<units::TimePrefix>,
+
+    /// Rate at which all of the host's clocks drift from the simulation time,
+    /// in parts per million. Positive values make the clocks run fast.
+    #[serde(default)]
+    pub drift_ppm: f64,
+
+    /// Step changes of the host's realtime clock
+    #[serde(default)]
+    pub steps: Vec<HostClockStepOptions>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct HostClockStepOptions {
+    /// The simulated time at which to step the clock
+    pub time: units::Time<units::TimePrefix>,
+
+    /// Amount by which to step the host's realtime clock
+    pub offset: units::SignedTime<units::TimePrefix>,
 }
 
 #[derive(Debug, Copy, Clone, Serialize, Deserialize, JsonSchema)]
@@ -783,6 +971,38 @@ impl From<LogLevel> for log::Level {
 #[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq, Serialize, JsonSchema)]
 pub struct HostName(String);
 
+impl HostName {
+    pub fn new(name: impl Into<String>) -> Result<Self, String> {
+        let name = name.into();
+
+        // hostname(7): "Valid characters for hostnames are ASCII(7) letters from a to z, the digits
+        // from 0 to 9, and the hyphen (-)."
+        fn is_allowed(c: char) -> bool {
+            c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '.'
+        }
+        if let Some(invalid_char) = name.chars().find(|x| !is_allowed(*x)) {
+            return Err(format!("invalid hostname character: '{invalid_char}'"));
+        }
+
+        if name.is_empty() {
+            return Err("empty hostname".into());
+        }
+
+        // hostname(7): "A hostname may not start with a hyphen."
+        if name.starts_with('-') {
+            return Err("hostname begins with a '-' character".into());
+        }
+
+        // hostname(7): "Each element of the hostname must be from 1 to 63 characters long and the
+        // entire hostname, including the dots, can be at most 253 characters long."
+        if name.len() > 253 {
+            return Err("hostname exceeds 253 characters".into());
+        }
+
+        Ok(Self(name))
+    }
+}
+
 impl<'de> serde::Deserialize<'de> for HostName {
     fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
         struct HostNameVisitor;
@@ -798,33 +1018,7 @@ impl<'de> serde::Deserialize<'de> for HostName {
             where
                 E: serde::de::Error,
             {
-                // hostname(7): "Valid characters for hostnames are ASCII(7) letters from a to z,
-                // the digits from 0 to 9, and the hyphen (-)."
-                fn is_allowed(c: char) -> bool {
-                    c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '.'
-                }
-                if let Some(invalid_char) = v.chars().find(|x| !is_allowed(*x)) {
-                    return Err(E::custom(format!(
-                        "invalid hostname character: '{invalid_char}'"
-                    )));
-                }
-
-                if v.is_empty() {
-                    return Err(E::custom("empty hostname"));
-                }
-
-                // hostname(7): "A hostname may not start with a hyphen."
-                if v.starts_with('-') {
-                    return Err(E::custom("hostname begins with a '-' character"));
-                }
-
-                // hostname(7): "Each element of the hostname must be from 1 to 63 characters long
-                // and the entire hostname, including the dots, can be at most 253 characters long."
-                if v.len() > 253 {
-                    return Err(E::custom("hostname exceeds 253 characters"));
-                }
-
-                Ok(HostName(v))
+                HostName::new(v).map_err(E::custom)
             }
 
             fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
@@ -953,6 +1147,10 @@ fn default_data_directory() -> Option<String> {
     Some("shadow.data".into())
 }
 
+fn default_metrics_format() -> Option<MetricsFormat> {
+    Some(MetricsFormat::JsonLines)
+}
+
 /// Parse a string as a comma-delimited set of `T` values.
 fn parse_set<T>(s: &str) -> Result<HashSet<T>, <T as FromStr>::Err>
 where
@@ -982,6 +1180,100 @@ impl FromStr for QDiscMode {
     }
 }
 
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+#[repr(C)]
+pub enum TcpCongestionControl {
+    Reno,
+    Cubic,
+    Bbr,
+}
+
+impl FromStr for TcpCongestionControl {
+    type Err = serde_yaml::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_yaml::from_str(s)
+    }
+}
+
+/// The queueing discipline for packets waiting to be sent from a host's network interface.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum QDiscOptions {
+    /// Packets are kept in the socket buffers until the interface can send them
+    Noqueue,
+    /// Tail-drop fifo with a packet limit, and optionally a byte limit
+    Pfifo {
+        #[serde(default = "default_qdisc_limit_1000")]
+        limit: u32,
+        #[serde(default)]
+        limit_bytes: Option<units::Bytes<units::SiPrefixUpper>>,
+    },
+    /// Random early detection
+    Red {
+        #[serde(default = "default_red_limit")]
+        limit: units::Bytes<units::SiPrefixUpper>,
+        #[serde(default = "default_red_min")]
+        min: units::Bytes<units::SiPrefixUpper>,
+        #[serde(default = "default_red_max")]
+        max: units::Bytes<units::SiPrefixUpper>,
+        #[serde(default = "default_red_probability")]
+        probability: f64,
+        #[serde(default = "default_red_weight")]
+        weight: f64,
+    },
+    /// Controlled delay
+    Codel {
+        #[serde(default = "default_qdisc_limit_1000")]
+        limit: u32,
+        #[serde(default = "default_codel_target")]
+        target: units::Time<units::TimePrefix>,
+        #[serde(default = "default_codel_interval")]
+        interval: units::Time<units::TimePrefix>,
+    },
+    /// Flow queue controlled delay
+    FqCodel {
+        #[serde(default = "default_fq_codel_limit")]
+        limit: u32,
+        #[serde(default = "default_codel_target")]
+        target: units::Time<units::TimePrefix>,
+        #[serde(default = "default_codel_interval")]
+        interval: units::Time<units::TimePrefix>,
+        #[serde(default = "default_fq_codel_flows")]
+        flows: u32,
+        #[serde(default = "default_fq_codel_quantum")]
+        quantum: units::Bytes<units::SiPrefixUpper>,
+    },
+}
+
+impl FromStr for QDiscOptions {
+    type Err = serde_yaml::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_yaml::from_str(s)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum JitterDistribution {
+    /// Uniformly distributed between `-jitter` and `+jitter`
+    Uniform,
+    /// Normally distributed with a standard deviation of `jitter`, and truncated at `-3 * jitter`
+    Normal,
+    /// Pareto distributed (never negative) with a mean of `jitter`
+    Pareto,
+}
+
+impl FromStr for JitterDistribution {
+    type Err = serde_yaml::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_yaml::from_str(s)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "kebab-case")]
 pub enum Compression {
@@ -1010,10 +1302,84 @@ pub enum GraphSource {
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum GraphOptions {
     Gml(GraphSource),
+    Graphml(GraphSource),
+    Json(GraphSource),
+    LatencyMatrix {
+        #[serde(flatten)]
+        source: GraphSource,
+        /// Downstream bandwidth of the hosts at every location
+        host_bandwidth_down: Option<units::BitsPerSec<units::SiPrefixUpper>>,
+        /// Upstream bandwidth of the hosts at every location
+        host_bandwidth_up: Option<units::BitsPerSec<units::SiPrefixUpper>>,
+    },
     #[serde(rename = "1_gbit_switch")]
     OneGbitSwitch,
 }
 
+/// A change to the network at a given time.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum NetworkFaultOptions {
+    /// Split the network into groups of graph nodes. Packets between nodes in different groups
+    /// are dropped, and nodes that aren't in any group are unaffected. Replaces any previous
+    /// partition.
+    Partition {
+        /// The simulated time of the change
+        time: units::Time<units::TimePrefix>,
+        /// Groups of graph node IDs
+        groups: Vec<Vec<u32>>,
+    },
+    /// Remove the network partition
+    Heal {
+        /// The simulated time of the change
+        time: units::Time<units::TimePrefix>,
+    },
+    /// Change the graph edge from `source` to `target`. Properties that aren't given are
+    /// unchanged.
+    Link {
+        /// The simulated time of the change
+        time: units::Time<units::TimePrefix>,
+        source: u32,
+        target: u32,
+        /// The edge's new latency
+        #[serde(default)]
+        latency: Option<units::Time<units::TimePrefix>>,
+        /// The edge's new packet loss
+        #[serde(default)]
+        packet_loss: Option<f32>,
+        /// The edge's new jitter
+        #[serde(default)]
+        jitter: Option<units::Time<units::TimePrefix>>,
+        /// Whether the edge can be used, or if packets can't be routed over it
+        #[serde(default)]
+        up: Option<bool>,
+    },
+    /// Change the bandwidth of all hosts attached to a graph node. Bandwidths that aren't given
+    /// are unchanged.
+    Bandwidth {
+        /// The simulated time of the change
+        time: units::Time<units::TimePrefix>,
+        node: u32,
+        /// The new downstream bandwidth of the hosts
+        #[serde(default)]
+        bandwidth_down: Option<units::BitsPerSec<units::SiPrefixUpper>>,
+        /// The new upstream bandwidth of the hosts
+        #[serde(default)]
+        bandwidth_up: Option<units::BitsPerSec<units::SiPrefixUpper>>,
+    },
+}
+
+impl NetworkFaultOptions {
+    pub fn time(&self) -> units::Time<units::TimePrefix> {
+        match self {
+            Self::Partition { time, .. }
+            | Self::Heal { time }
+            | Self::Link { time, .. }
+            | Self::Bandwidth { time, .. } => *time,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, JsonSchema)]
 #[serde(untagged)]
 pub enum ProcessArgs {
@@ -1175,6 +1541,8 @@ pub enum StraceLoggingMode {
     Off,
     Standard,
     Deterministic,
+    /// A JSON object on each line
+    Json,
 }
 
 impl FromStr for StraceLoggingMode {
@@ -1185,6 +1553,22 @@ impl FromStr for StraceLoggingMode {
     }
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum MetricsFormat {
+    /// A JSON object on each line
+    JsonLines,
+    Csv,
+}
+
+impl FromStr for MetricsFormat {
+    type Err = serde_yaml::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_yaml::from_str(s)
+    }
+}
+
 /// This wrapper type allows cli options to specify "null" to overwrite a config file option with
 /// `None`, and is intended to be used for options where "null" is a valid option value.
 ///
@@ -1342,6 +1726,11 @@ fn default_some_false() -> Option<bool> {
     Some(false)
 }
 
+/// Helper function for serde default `Some(JitterDistribution::Uniform)` values.
+fn default_some_jitter_uniform() -> Option<JitterDistribution> {
+    Some(JitterDistribution::Uniform)
+}
+
 /// Helper function for serde default `Some(0)` values.
 fn default_some_0() -> Option<u32> {
     Some(0)
@@ -1363,6 +1752,62 @@ fn default_some_info() -> Option<LogLevel> {
     Some(LogLevel::Info)
 }
 
+/// Helper function for serde default qdisc limits of 1000 packets, the default transmit queue
+/// length of a Linux interface.
+fn default_qdisc_limit_1000() -> u32 {
+    1000
+}
+
+/// Helper function for serde default `red` values, from the example in tc-red(8).
+fn default_red_limit() -> units::Bytes<units::SiPrefixUpper> {
+    units::Bytes::new(400000, units::SiPrefixUpper::Base)
+}
+
+/// Helper function for serde default `red` values, from the example in tc-red(8).
+fn default_red_min() -> units::Bytes<units::SiPrefixUpper> {
+    units::Bytes::new(30000, units::SiPrefixUpper::Base)
+}
+
+/// Helper function for serde default `red` values, from the example in tc-red(8).
+fn default_red_max() -> units::Bytes<units::SiPrefixUpper> {
+    units::Bytes::new(90000, units::SiPrefixUpper::Base)
+}
+
+/// Helper function for serde default `red` values, from the example in tc-red(8).
+fn default_red_probability() -> f64 {
+    0.02
+}
+
+/// Helper function for serde default `red` values, as recommended by Floyd and Jacobson.
+fn default_red_weight() -> f64 {
+    0.002
+}
+
+/// Helper function for serde default CoDel values, from RFC 8289.
+fn default_codel_target() -> units::Time<units::TimePrefix> {
+    units::Time::new(5, units::TimePrefix::Milli)
+}
+
+/// Helper function for serde default CoDel values, from RFC 8289.
+fn default_codel_interval() -> units::Time<units::TimePrefix> {
+    units::Time::new(100, units::TimePrefix::Milli)
+}
+
+/// Helper function for serde default `fq-codel` values, from tc-fq_codel(8).
+fn default_fq_codel_limit() -> u32 {
+    10240
+}
+
+/// Helper function for serde default `fq-codel` values, from tc-fq_codel(8).
+fn default_fq_codel_flows() -> u32 {
+    1024
+}
+
+/// Helper function for serde default `fq-codel` values, from tc-fq_codel(8).
+fn default_fq_codel_quantum() -> units::Bytes<units::SiPrefixUpper> {
+    units::Bytes::new(1514, units::SiPrefixUpper::Base)
+}
+
 // when updating this graph, make sure to also update the copy in docs/shadow_config_spec.md
 pub const ONE_GBIT_SWITCH_GRAPH: &str = r#"graph [
   directed 0
@@ -55,6 +55,8 @@ impl<'a> Controller<'a> {
             random: Xoshiro256PlusPlus::from_rng(&mut sim_config.random),
             ip_assignment: sim_config.ip_assignment,
             routing_info: sim_config.routing_info,
+            links: sim_config.links,
+            network_faults: sim_config.network_faults,
             host_bandwidths: sim_config.host_bandwidths,
             hosts: sim_config.hosts,
         };
@@ -0,0 +1,488 @@
+//! Running a single simulation across several Shadow processes.
+//!
+//! Every process loads the same configuration, and builds the same network and the same list of
+//! hosts, but only runs the hosts that it owns. Hosts are assigned to processes in turn, so a host
+//! is owned by the process whose rank is the host's ID modulo the number of processes. The
+//! processes are connected to each other over TCP.
+//!
+//! A packet that is sent to a host in another process is queued until the end of the scheduling
+//! round. At the end of each round, every process sends every other process the packets that were
+//! sent to its hosts, the time of its next event, and the smallest packet latency that it has
+//! used. Since packets are never delivered in the round that they were sent in, and every process
+//! then computes the same window for the next round, the simulation is identical to running all of
+//! the hosts in a single process.
+
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Mutex, mpsc};
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use shadow_shim_helper_rs::HostId;
+use shadow_shim_helper_rs::emulated_time::EmulatedTime;
+use shadow_shim_helper_rs::simulation_time::SimulationTime;
+
+use crate::network::packet::{Packet, PacketRc};
+
+/// Identifies a connection from another Shadow process.
+const HANDSHAKE_MAGIC: [u8; 8] = *b"shadowdp";
+
+/// How long to keep trying to connect to a process that isn't listening yet.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A packet that was sent to a host in another process.
+#[derive(Debug)]
+pub struct RemotePacket {
+    pub packet: PacketRc,
+    pub dst_host_id: HostId,
+    pub time: EmulatedTime,
+    pub src_host_id: HostId,
+    /// The event ID taken from the source host when the packet was sent, so that the packet event
+    /// is ordered the same as if the hosts were in the same process.
+    pub src_host_event_id: u64,
+}
+
+impl RemotePacket {
+    fn write_to(&self, mut writer: impl Write) -> std::io::Result<()> {
+        writer.write_all(&u32::from(self.dst_host_id).to_le_bytes())?;
+        writer.write_all(&EmulatedTime::to_c_emutime(Some(self.time)).to_le_bytes())?;
+        writer.write_all(&u32::from(self.src_host_id).to_le_bytes())?;
+        writer.write_all(&self.src_host_event_id.to_le_bytes())?;
+        self.packet.write_to(writer)
+    }
+
+    fn read_from(mut reader: impl Read) -> std::io::Result<Self> {
+        let dst_host_id = HostId::from(read_u32(&mut reader)?);
+        let time = EmulatedTime::from_c_emutime(read_u64(&mut reader)?)
+            .ok_or_else(|| invalid_data("Invalid packet time"))?;
+        let src_host_id = HostId::from(read_u32(&mut reader)?);
+        let src_host_event_id = read_u64(&mut reader)?;
+        let packet = PacketRc::from(Packet::read_from(reader)?);
+
+        Ok(Self {
+            packet,
+            dst_host_id,
+            time,
+            src_host_id,
+            src_host_event_id,
+        })
+    }
+}
+
+/// What each process sends to every other process at the end of a round.
+#[derive(Debug)]
+pub struct RoundMessage {
+    /// The packets that were sent to hosts of the receiving process.
+    pub packets: Vec<RemotePacket>,
+    /// The time of the sending process' next event, or [`EmulatedTime::MAX`] if it has none.
+    pub next_event_time: EmulatedTime,
+    /// The smallest packet latency that the sending process has used (only if dynamic runahead is
+    /// enabled).
+    pub min_used_latency: Option<SimulationTime>,
+}
+
+impl RoundMessage {
+    fn read_from(mut reader: impl Read) -> std::io::Result<Self> {
+        let len = read_u64(&mut reader)?;
+        let mut packets_reader = (&mut reader).take(len);
+        let mut packets = Vec::new();
+        while packets_reader.limit() > 0 {
+            packets.push(RemotePacket::read_from(&mut packets_reader)?);
+        }
+
+        let next_event_time = EmulatedTime::from_c_emutime(read_u64(&mut reader)?)
+            .ok_or_else(|| invalid_data("Invalid next event time"))?;
+        let min_used_latency = SimulationTime::from_c_simtime(read_u64(&mut reader)?);
+
+        Ok(Self {
+            packets,
+            next_event_time,
+            min_used_latency,
+        })
+    }
+}
+
+/// A connection to another process.
+#[derive(Debug)]
+struct Peer {
+    writer: Mutex<BufWriter<TcpStream>>,
+    /// Messages read from the other process by a reader thread.
+    messages: Mutex<mpsc::Receiver<std::io::Result<RoundMessage>>>,
+}
+
+/// This process' connections to the other processes of a distributed simulation.
+#[derive(Debug)]
+pub struct Distributed {
+    rank: usize,
+    /// Connections to the other processes, indexed by rank (`None` for this process).
+    peers: Vec<Option<Peer>>,
+    /// The encoded packets that were sent during the current round to the hosts of each process,
+    /// indexed by rank.
+    outgoing: Vec<Mutex<Vec<u8>>>,
+}
+
+impl Distributed {
+    /// Connect to the other processes. `addresses` contains the address of every process, and this
+    /// process listens on `addresses[rank]`. Blocks until every other process has connected.
+    pub fn connect(addresses: &[String], rank: usize, num_hosts: usize) -> anyhow::Result<Self> {
+        let address = addresses.get(rank).with_context(|| {
+            format!(
+                "The distributed rank {rank} is not less than the number of processes {}",
+                addresses.len()
+            )
+        })?;
+        let listener = TcpListener::bind(address)
+            .with_context(|| format!("Could not listen for other processes on {address}"))?;
+
+        Self::connect_with_listener(listener, addresses, rank, num_hosts)
+    }
+
+    fn connect_with_listener(
+        listener: TcpListener,
+        addresses: &[String],
+        rank: usize,
+        num_hosts: usize,
+    ) -> anyhow::Result<Self> {
+        let num_processes = addresses.len();
+        if num_processes > num_hosts {
+            anyhow::bail!(
+                "The simulation has {num_hosts} hosts, which is fewer than the number of \
+                 processes {num_processes}"
+            );
+        }
+
+        let mut streams: Vec<Option<TcpStream>> = (0..num_processes).map(|_| None).collect();
+
+        // connect to the processes with a lower rank, and accept connections from the processes
+        // with a higher rank
+        for (peer_rank, address) in addresses.iter().enumerate().take(rank) {
+            let mut stream = connect_with_retry(address).with_context(|| {
+                format!("Could not connect to process {peer_rank} at {address}")
+            })?;
+            let handshake_rank = handshake(&mut stream, rank, num_processes, num_hosts)
+                .with_context(|| format!("Handshake with process {peer_rank} failed"))?;
+            if handshake_rank != peer_rank {
+                anyhow::bail!("Expected process {peer_rank} at {address}, not {handshake_rank}");
+            }
+            streams[peer_rank] = Some(stream);
+        }
+        for _ in rank + 1..num_processes {
+            let (mut stream, _) = listener
+                .accept()
+                .context("Could not accept a connection from another process")?;
+            let peer_rank = handshake(&mut stream, rank, num_processes, num_hosts)
+                .context("Handshake with another process failed")?;
+            if peer_rank <= rank || streams[peer_rank].is_some() {
+                anyhow::bail!("Unexpected connection from process {peer_rank}");
+            }
+            streams[peer_rank] = Some(stream);
+        }
+
+        let peers = streams
+            .into_iter()
+            .enumerate()
+            .map(|(peer_rank, stream)| stream.map(|x| Peer::new(x, peer_rank)).transpose())
+            .collect::<anyhow::Result<_>>()?;
+
+        log::info!("Connected to the other {} processes", num_processes - 1);
+
+        Ok(Self {
+            rank,
+            peers,
+            outgoing: (0..num_processes).map(|_| Mutex::new(Vec::new())).collect(),
+        })
+    }
+
+    /// Returns true if the host is run by this process.
+    pub fn is_local(&self, host_id: HostId) -> bool {
+        self.owner(host_id) == self.rank
+    }
+
+    fn owner(&self, host_id: HostId) -> usize {
+        usize::try_from(u32::from(host_id)).unwrap() % self.peers.len()
+    }
+
+    /// Queue a packet to be sent to the process that runs the packet's destination host at the end
+    /// of the round.
+    pub fn push_packet(&self, packet: &RemotePacket) {
+        let owner = self.owner(packet.dst_host_id);
+        assert_ne!(owner, self.rank);
+
+        let mut outgoing = self.outgoing[owner].lock().unwrap();
+        // writing to a `Vec` can't fail
+        packet.write_to(&mut *outgoing).unwrap();
+    }
+
+    /// Send every other process the packets that were sent to its hosts during the round along
+    /// with this process' next event time and smallest used latency, and receive the same from
+    /// every other process. Returns the packets that were sent to this process' hosts, the
+    /// earliest next event time, and the smallest used latency of all processes.
+    pub fn finish_round(
+        &self,
+        next_event_time: EmulatedTime,
+        min_used_latency: Option<SimulationTime>,
+    ) -> anyhow::Result<RoundMessage> {
+        for (peer_rank, peer) in self.peers() {
+            let packets = std::mem::take(&mut *self.outgoing[peer_rank].lock().unwrap());
+            let mut writer = peer.writer.lock().unwrap();
+            write_message(&mut *writer, &packets, next_event_time, min_used_latency)
+                .with_context(|| format!("Could not send the round to process {peer_rank}"))?;
+        }
+
+        let mut round = RoundMessage {
+            packets: Vec::new(),
+            next_event_time,
+            min_used_latency,
+        };
+
+        for (peer_rank, peer) in self.peers() {
+            let message = peer
+                .messages
+                .lock()
+                .unwrap()
+                .recv()
+                .ok()
+                .with_context(|| format!("Lost the connection to process {peer_rank}"))?
+                .with_context(|| format!("Could not receive the round from process {peer_rank}"))?;
+
+            round.packets.extend(message.packets);
+            round.next_event_time = std::cmp::min(round.next_event_time, message.next_event_time);
+            round.min_used_latency = [round.min_used_latency, message.min_used_latency]
+                .into_iter()
+                .flatten()
+                .min();
+        }
+
+        Ok(round)
+    }
+
+    fn peers(&self) -> impl Iterator<Item = (usize, &Peer)> {
+        self.peers
+            .iter()
+            .enumerate()
+            .filter_map(|(rank, peer)| Some((rank, peer.as_ref()?)))
+    }
+}
+
+impl Peer {
+    fn new(stream: TcpStream, rank: usize) -> anyhow::Result<Self> {
+        stream.set_nodelay(true)?;
+
+        // messages are read on a separate thread so that large messages can't deadlock two
+        // processes that are both blocked writing to each other
+        let (sender, receiver) = mpsc::channel();
+        let mut reader = BufReader::new(stream.try_clone()?);
+        std::thread::Builder::new()
+            .name(format!("shadow-peer-{rank}"))
+            .spawn(move || {
+                loop {
+                    let message = RoundMessage::read_from(&mut reader);
+                    let is_err = message.is_err();
+                    // stop once the connection is closed or this process no longer needs messages
+                    if sender.send(message).is_err() || is_err {
+                        break;
+                    }
+                }
+            })?;
+
+        Ok(Self {
+            writer: Mutex::new(BufWriter::new(stream)),
+            messages: Mutex::new(receiver),
+        })
+    }
+}
+
+impl Drop for Peer {
+    fn drop(&mut self) {
+        // also stops the reader thread, which has a clone of the stream
+        let writer = self.writer.lock().unwrap();
+        if let Err(e) = writer.get_ref().shutdown(std::net::Shutdown::Both) {
+            log::debug!("Could not shut down the connection to another process: {e}");
+        }
+    }
+}
+
+fn connect_with_retry(address: &str) -> std::io::Result<TcpStream> {
+    let deadline = Instant::now() + CONNECT_TIMEOUT;
+    loop {
+        match TcpStream::connect(address) {
+            Ok(stream) => return Ok(stream),
+            // the other process may not be listening yet
+            Err(e) if e.kind() == std::io::ErrorKind::ConnectionRefused => {
+                if Instant::now() >= deadline {
+                    return Err(e);
+                }
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Exchange ranks with another process, and check that both processes are running the same
+/// simulation. Returns the other process' rank.
+fn handshake(
+    stream: &mut TcpStream,
+    rank: usize,
+    num_processes: usize,
+    num_hosts: usize,
+) -> anyhow::Result<usize> {
+    let fields = [rank, num_processes, num_hosts].map(|x| u64::try_from(x).unwrap());
+
+    let mut bytes = HANDSHAKE_MAGIC.to_vec();
+    for field in fields {
+        bytes.extend(field.to_le_bytes());
+    }
+    stream.write_all(&bytes)?;
+
+    let mut magic = [0; HANDSHAKE_MAGIC.len()];
+    stream.read_exact(&mut magic)?;
+    if magic != HANDSHAKE_MAGIC {
+        anyhow::bail!("The connection is not from a Shadow process");
+    }
+
+    let peer_rank = read_u64(&mut *stream)?;
+    let peer_num_processes = read_u64(&mut *stream)?;
+    let peer_num_hosts = read_u64(&mut *stream)?;
+    if [peer_num_processes, peer_num_hosts] != fields[1..] {
+        anyhow::bail!(
+            "The other process has {peer_num_processes} processes and {peer_num_hosts} hosts, but \
+             this process has {num_processes} processes and {num_hosts} hosts"
+        );
+    }
+    if peer_rank >= fields[1] {
+        anyhow::bail!("The other process has an invalid rank {peer_rank}");
+    }
+
+    Ok(peer_rank.try_into().unwrap())
+}
+
+fn write_message(
+    mut writer: impl Write,
+    packets: &[u8],
+    next_event_time: EmulatedTime,
+    min_used_latency: Option<SimulationTime>,
+) -> std::io::Result<()> {
+    writer.write_all(&u64::try_from(packets.len()).unwrap().to_le_bytes())?;
+    writer.write_all(packets)?;
+    writer.write_all(&EmulatedTime::to_c_emutime(Some(next_event_time)).to_le_bytes())?;
+    writer.write_all(&SimulationTime::to_c_simtime(min_used_latency).to_le_bytes())?;
+    writer.flush()
+}
+
+fn invalid_data(msg: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, msg)
+}
+
+fn read_u32(mut reader: impl Read) -> std::io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(mut reader: impl Read) -> std::io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connect_all(num_processes: usize, num_hosts: usize) -> Vec<Distributed> {
+        let listeners: Vec<_> = (0..num_processes)
+            .map(|_| TcpListener::bind("127.0.0.1:0").unwrap())
+            .collect();
+        let addresses: Vec<_> = listeners
+            .iter()
+            .map(|x| x.local_addr().unwrap().to_string())
+            .collect();
+
+        let threads: Vec<_> = listeners
+            .into_iter()
+            .enumerate()
+            .map(|(rank, listener)| {
+                let addresses = addresses.clone();
+                std::thread::spawn(move || {
+                    Distributed::connect_with_listener(listener, &addresses, rank, num_hosts)
+                })
+            })
+            .collect();
+
+        threads
+            .into_iter()
+            .map(|x| x.join().unwrap().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_is_local() {
+        let processes = connect_all(3, 5);
+        let local = |rank: usize| {
+            (0..5)
+                .filter(|x| processes[rank].is_local(HostId::from(*x)))
+                .collect::<Vec<u32>>()
+        };
+
+        assert_eq!(local(0), [0, 3]);
+        assert_eq!(local(1), [1, 4]);
+        assert_eq!(local(2), [2]);
+    }
+
+    #[test]
+    fn test_finish_round() {
+        let processes = connect_all(2, 2);
+
+        let time = EmulatedTime::SIMULATION_START + SimulationTime::from_millis(10);
+        let packet = PacketRc::new_ipv4_udp_mock();
+        processes[0].push_packet(&RemotePacket {
+            packet: packet.clone(),
+            dst_host_id: HostId::from(1),
+            time,
+            src_host_id: HostId::from(0),
+            src_host_event_id: 7,
+        });
+
+        let next_event_times = [time, EmulatedTime::MAX];
+        let min_used_latencies = [None, Some(SimulationTime::from_millis(5))];
+
+        let rounds: Vec<_> = std::thread::scope(|s| {
+            let threads: Vec<_> = processes
+                .iter()
+                .enumerate()
+                .map(|(rank, process)| {
+                    s.spawn(move || {
+                        process.finish_round(next_event_times[rank], min_used_latencies[rank])
+                    })
+                })
+                .collect();
+            threads
+                .into_iter()
+                .map(|x| x.join().unwrap().unwrap())
+                .collect()
+        });
+
+        for round in &rounds {
+            assert_eq!(round.next_event_time, time);
+            assert_eq!(round.min_used_latency, Some(SimulationTime::from_millis(5)));
+        }
+
+        assert!(rounds[0].packets.is_empty());
+        let [received] = rounds[1].packets.as_slice() else {
+            panic!("Expected one packet, got {:?}", rounds[1].packets);
+        };
+        assert_eq!(received.dst_host_id, HostId::from(1));
+        assert_eq!(received.time, time);
+        assert_eq!(received.src_host_id, HostId::from(0));
+        assert_eq!(received.src_host_event_id, 7);
+        assert_eq!(received.packet.payload(), packet.payload());
+    }
+
+    #[test]
+    fn test_too_many_processes() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addresses = vec![String::new(); 3];
+        assert!(Distributed::connect_with_listener(listener, &addresses, 0, 2).is_err());
+    }
+}
@@ -0,0 +1,331 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use shadow_shim_helper_rs::HostId;
+use shadow_shim_helper_rs::emulated_time::EmulatedTime;
+use shadow_shim_helper_rs::simulation_time::SimulationTime;
+
+use crate::core::configuration::JitterDistribution;
+use crate::network::graph::RoutingInfo;
+use crate::network::jitter;
+
+/// Decides how far each host may run in the next simulation round.
+///
+/// A host may run until the earliest time that a packet from any other host could arrive, which
+/// depends on when the other hosts will next run and on the smallest latency from their graph
+/// nodes. Since a host never runs past the arrival time of a packet, packets can be delivered at
+/// their exact latency rather than being delayed to the end of the round. This is an alternative to
+/// [`Runahead`](crate::core::runahead::Runahead), where all hosts run until the same time.
+#[derive(Debug)]
+pub struct Lookahead {
+    /// The graph nodes that are in use.
+    nodes: Vec<u32>,
+    /// The index in `nodes` of each host's graph node.
+    host_nodes: HashMap<HostId, usize>,
+    /// The smallest latency of a packet between each pair of nodes over the whole simulation,
+    /// indexed by `src * nodes.len() + dst`, or `None` if there's never a path between the nodes.
+    min_latencies: RwLock<Vec<Option<SimulationTime>>>,
+    /// The runahead that all hosts would run for without the per-host lookahead. Only used to warn
+    /// when jitter makes the lookahead much shorter.
+    runahead: SimulationTime,
+}
+
+impl Lookahead {
+    pub fn new(
+        host_nodes: &HashMap<HostId, u32>,
+        routing_info: &RoutingInfo<u32>,
+        jitter_distribution: JitterDistribution,
+        runahead: SimulationTime,
+    ) -> Self {
+        let mut nodes: Vec<u32> = host_nodes.values().copied().collect();
+        nodes.sort();
+        nodes.dedup();
+
+        let host_nodes = host_nodes
+            .iter()
+            .map(|(host, node)| (*host, nodes.binary_search(node).unwrap()))
+            .collect();
+
+        let lookahead = Self {
+            nodes,
+            host_nodes,
+            min_latencies: RwLock::new(Vec::new()),
+            runahead,
+        };
+        lookahead.update_latencies(routing_info, jitter_distribution);
+        lookahead
+    }
+
+    /// Update the smallest latencies between nodes after the paths in `routing_info` have changed.
+    pub fn update_latencies(
+        &self,
+        routing_info: &RoutingInfo<u32>,
+        jitter_distribution: JitterDistribution,
+    ) {
+        let min_latencies = routing_info.min_over_time(|path| {
+            jitter::min_delay_ns(jitter_distribution, path.latency_ns, path.jitter_ns)
+        });
+
+        let min_latencies = self
+            .nodes
+            .iter()
+            .flat_map(|src| self.nodes.iter().map(move |dst| (*src, *dst)))
+            .map(|nodes| {
+                min_latencies
+                    .get(&nodes)
+                    .copied()
+                    .map(SimulationTime::from_nanos)
+            })
+            .collect();
+
+        *self.min_latencies.write().unwrap() = min_latencies;
+
+        // a jitter close to the latency can reduce the lookahead to as little as 1 ns, which makes
+        // the rounds very short
+        if let Some((src, dst, latency)) = self.smallest_latency()
+            && latency < self.runahead
+        {
+            log::warn!(
+                "Jitter reduces the smallest latency from graph node {src} to {dst} to {latency:?}, \
+                 which is less than the runahead of {:?}. Hosts will run in rounds as short as \
+                 this latency, which may slow down the simulation.",
+                self.runahead,
+            );
+        }
+    }
+
+    /// The smallest latency of a packet between any pair of nodes, with the source and
+    /// destination nodes.
+    fn smallest_latency(&self) -> Option<(u32, u32, SimulationTime)> {
+        let min_latencies = self.min_latencies.read().unwrap();
+        let num_nodes = self.nodes.len();
+        min_latencies
+            .iter()
+            .enumerate()
+            .filter_map(|(i, latency)| Some((i / num_nodes, i % num_nodes, (*latency)?)))
+            .min_by_key(|(_, _, latency)| *latency)
+            .map(|(src, dst, latency)| (self.nodes[src], self.nodes[dst], latency))
+    }
+
+    /// Get the time that each host may run until in the next round, given the time of each host's
+    /// next event. No host runs past `end_time`.
+    pub fn window_ends(
+        &self,
+        next_event_times: &HashMap<HostId, Option<EmulatedTime>>,
+        end_time: EmulatedTime,
+    ) -> HashMap<HostId, EmulatedTime> {
+        let min_latencies = self.min_latencies.read().unwrap();
+        let num_nodes = self.nodes.len();
+        let latency = |src: usize, dst: usize| min_latencies[src * num_nodes + dst];
+
+        // the earliest next event time of the hosts at each node (with the host that it belongs
+        // to), and the second-earliest next event time
+        let mut first: Vec<Option<(EmulatedTime, HostId)>> = vec![None; num_nodes];
+        let mut second: Vec<EmulatedTime> = vec![EmulatedTime::MAX; num_nodes];
+        for (host, node) in &self.host_nodes {
+            let Some(time) = next_event_times[host] else {
+                continue;
+            };
+            match first[*node] {
+                Some((first_time, _)) if first_time <= time => {
+                    second[*node] = std::cmp::min(second[*node], time);
+                }
+                Some((first_time, _)) => {
+                    second[*node] = first_time;
+                    first[*node] = Some((time, *host));
+                }
+                None => first[*node] = Some((time, *host)),
+            }
+        }
+
+        // the earliest time that anything could happen at each node, including the arrival of
+        // packets sent from other nodes in response to their events (dijkstra's algorithm)
+        let mut earliest: Vec<EmulatedTime> = first
+            .iter()
+            .map(|x| x.map_or(EmulatedTime::MAX, |(time, _)| time))
+            .collect();
+        let mut visited = vec![false; num_nodes];
+        while let Some(src) = (0..num_nodes)
+            .filter(|x| !visited[*x] && earliest[*x] != EmulatedTime::MAX)
+            .min_by_key(|x| earliest[*x])
+        {
+            visited[src] = true;
+            for dst in (0..num_nodes).filter(|x| *x != src && !visited[*x]) {
+                if let Some(latency) = latency(src, dst) {
+                    earliest[dst] =
+                        std::cmp::min(earliest[dst], earliest[src].saturating_add(latency));
+                }
+            }
+        }
+
+        // the earliest time that a packet from another node could arrive at each node
+        let arrivals: Vec<EmulatedTime> = (0..num_nodes)
+            .map(|dst| {
+                (0..num_nodes)
+                    .filter(|src| *src != dst)
+                    .filter_map(|src| Some(earliest[src].saturating_add(latency(src, dst)?)))
+                    .min()
+                    .unwrap_or(EmulatedTime::MAX)
+            })
+            .collect();
+
+        self.host_nodes
+            .iter()
+            .map(|(host, node)| {
+                // the earliest next event of the other hosts at the same node; packets that a host
+                // sends to itself aren't routed through the network
+                let others = match first[*node] {
+                    Some((_, first_host)) if first_host == *host => second[*node],
+                    Some((first_time, _)) => first_time,
+                    None => EmulatedTime::MAX,
+                };
+                let from_same_node = latency(*node, *node)
+                    .map_or(EmulatedTime::MAX, |latency| others.saturating_add(latency));
+
+                let window_end = [arrivals[*node], from_same_node, end_time]
+                    .into_iter()
+                    .min()
+                    .unwrap();
+                (*host, window_end)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::network::graph::PathProperties;
+
+    use super::*;
+
+    fn at_millis(millis: u64) -> EmulatedTime {
+        EmulatedTime::SIMULATION_START + SimulationTime::from_millis(millis)
+    }
+
+    fn routing_info(latencies_ms: &[((u32, u32), u64)], jitter_ms: u64) -> RoutingInfo<u32> {
+        RoutingInfo::new(
+            latencies_ms
+                .iter()
+                .map(|(nodes, latency)| {
+                    let path = PathProperties {
+                        latency_ns: SimulationTime::from_millis(*latency).as_nanos() as u64,
+                        packet_loss: 0.0,
+                        jitter_ns: SimulationTime::from_millis(jitter_ms).as_nanos() as u64,
+                    };
+                    (*nodes, path)
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_window_ends() {
+        let latencies = [((0, 0), 1), ((0, 1), 10), ((1, 0), 10), ((1, 1), 1)];
+        let routing_info = routing_info(&latencies, 0);
+        let hosts = HashMap::from([(HostId::from(0), 0), (HostId::from(1), 1)]);
+        let lookahead = Lookahead::new(
+            &hosts,
+            &routing_info,
+            JitterDistribution::Uniform,
+            SimulationTime::from_millis(1),
+        );
+
+        let next_event_times = HashMap::from([
+            (HostId::from(0), Some(at_millis(0))),
+            (HostId::from(1), Some(at_millis(5))),
+        ]);
+        let ends = lookahead.window_ends(&next_event_times, at_millis(1000));
+        assert_eq!(ends[&HostId::from(0)], at_millis(15));
+        assert_eq!(ends[&HostId::from(1)], at_millis(10));
+
+        // host 1 could receive a packet from host 0 and reply to it
+        let next_event_times = HashMap::from([
+            (HostId::from(0), Some(at_millis(0))),
+            (HostId::from(1), Some(at_millis(50))),
+        ]);
+        let ends = lookahead.window_ends(&next_event_times, at_millis(1000));
+        assert_eq!(ends[&HostId::from(0)], at_millis(20));
+        assert_eq!(ends[&HostId::from(1)], at_millis(10));
+
+        // a host with no other hosts to receive packets from runs until the end time
+        let next_event_times = HashMap::from([
+            (HostId::from(0), Some(at_millis(0))),
+            (HostId::from(1), None),
+        ]);
+        let ends = lookahead.window_ends(&next_event_times, at_millis(1000));
+        assert_eq!(ends[&HostId::from(0)], at_millis(1000));
+        assert_eq!(ends[&HostId::from(1)], at_millis(10));
+    }
+
+    #[test]
+    fn test_window_ends_same_node() {
+        let routing_info = routing_info(&[((0, 0), 1)], 0);
+        let hosts = HashMap::from([(HostId::from(0), 0), (HostId::from(1), 0)]);
+        let lookahead = Lookahead::new(
+            &hosts,
+            &routing_info,
+            JitterDistribution::Uniform,
+            SimulationTime::from_millis(1),
+        );
+
+        let next_event_times = HashMap::from([
+            (HostId::from(0), Some(at_millis(0))),
+            (HostId::from(1), Some(at_millis(3))),
+        ]);
+        let ends = lookahead.window_ends(&next_event_times, at_millis(1000));
+        assert_eq!(ends[&HostId::from(0)], at_millis(4));
+        assert_eq!(ends[&HostId::from(1)], at_millis(1));
+    }
+
+    #[test]
+    fn test_window_ends_jitter() {
+        // the uniform distribution can reduce the latency by up to the jitter
+        let latencies = [((0, 0), 1), ((0, 1), 10), ((1, 0), 10), ((1, 1), 1)];
+        let routing_info = routing_info(&latencies, 2);
+        let hosts = HashMap::from([(HostId::from(0), 0), (HostId::from(1), 1)]);
+        let lookahead = Lookahead::new(
+            &hosts,
+            &routing_info,
+            JitterDistribution::Uniform,
+            SimulationTime::from_millis(1),
+        );
+
+        let next_event_times = HashMap::from([
+            (HostId::from(0), Some(at_millis(0))),
+            (HostId::from(1), Some(at_millis(50))),
+        ]);
+        let ends = lookahead.window_ends(&next_event_times, at_millis(1000));
+        assert_eq!(ends[&HostId::from(1)], at_millis(8));
+    }
+
+    #[test]
+    fn test_smallest_latency() {
+        let latencies = [((0, 0), 5), ((0, 1), 10), ((1, 0), 3), ((1, 1), 5)];
+        let hosts = HashMap::from([(HostId::from(0), 0), (HostId::from(1), 1)]);
+
+        let no_jitter = routing_info(&latencies, 0);
+        let lookahead = Lookahead::new(
+            &hosts,
+            &no_jitter,
+            JitterDistribution::Uniform,
+            SimulationTime::from_millis(1),
+        );
+        assert_eq!(
+            lookahead.smallest_latency(),
+            Some((1, 0, SimulationTime::from_millis(3))),
+        );
+
+        // the jitter is larger than the latency, so the latency is truncated to 1 ns
+        let large_jitter = routing_info(&latencies, 4);
+        let lookahead = Lookahead::new(
+            &hosts,
+            &large_jitter,
+            JitterDistribution::Uniform,
+            SimulationTime::from_millis(1),
+        );
+        assert_eq!(
+            lookahead.smallest_latency(),
+            Some((1, 0, SimulationTime::from_nanos(1))),
+        );
+    }
+}
@@ -1,15 +1,9 @@
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::ffi::{CStr, CString, OsStr, OsString};
-use std::io::{BufRead, IsTerminal};
 use std::os::unix::ffi::OsStrExt;
 use std::path::PathBuf;
 use std::sync::Arc;
-use std::sync::Condvar;
-use std::sync::Mutex;
-use std::sync::OnceLock;
-use std::sync::atomic::AtomicBool;
-use std::sync::atomic::AtomicU64;
 use std::sync::atomic::AtomicU32;
 use std::sync::atomic::Ordering;
 use std::time::Duration;
@@ -30,86 +24,33 @@ use shadow_shim_helper_rs::shim_shmem::{ManagerShmem, NativePreemptionConfig};
 use shadow_shim_helper_rs::simulation_time::SimulationTime;
 use shadow_shmem::allocator::ShMemBlock;
 
+use crate::core::activity_trace::ActivityTrace;
 use crate::core::configuration::{self, ConfigOptions, Flatten};
 use crate::core::controller::{Controller, ShadowStatusBarState, SimController};
 use crate::core::cpu;
+use crate::core::distributed::Distributed;
+use crate::core::lookahead::Lookahead;
 use crate::core::resource_usage;
+#[cfg(feature = "enable_run_control")]
+use crate::core::run_control;
 use crate::core::runahead::Runahead;
 use crate::core::sim_config::{Bandwidth, HostInfo};
 use crate::core::sim_stats;
+use crate::core::start_conditions::StartConditions;
 use crate::core::worker;
 use crate::cshadow as c;
 use crate::host::host::{Host, HostParameters};
+use crate::host::syscall::formatter::MergedStrace;
+use crate::network::capture::PacketCapture;
 use crate::network::dns::DnsBuilder;
-use crate::network::graph::{IpAssignment, RoutingInfo};
+use crate::network::faults::NetworkFaults;
+use crate::network::graph::{IpAssignment, Link, LinkProperties, RoutingInfo};
+use crate::network::links::LinkQueues;
+use crate::network::packet_trace::PacketTrace;
 use crate::utility;
 use crate::utility::childpid_watcher::ChildPidWatcher;
 use crate::utility::status_bar::Status;
 
-#[cfg(feature = "enable_run_control")]
-static RESTART_RUN_UNTIL_NS: AtomicU64 = AtomicU64::new(u64::MAX);
-
-// Run-control (feature-gated by `enable_run_control`):
-// We implement a *soft pause*: at a window boundary we block on a Condvar until resumed.
-// This avoids stopping the whole process in the middle of host execution or shim IPC.
-//
-// Control is via stdin (interactive terminal):
-// - p<Enter>: pause at next window boundary
-// - c<Enter>: continue (resume)
-// - cN<Enter>: continue for N seconds of *simulated time*, then pause at the next window boundary
-// - n<Enter>: run exactly one more window, then pause (gdb-like next)
-// - s<Enter>: show next-window hosts/PIDs (when paused)
-// - s:<pid><Enter>: print gdb attach command
-// - r<Enter>: restart from t=0s (in-process)
-// - rN<Enter>: restart and run to N seconds
-#[cfg(feature = "enable_run_control")]
-struct RunControl {
-    pause_requested: Arc<AtomicBool>,
-    restart_requested: Arc<AtomicBool>,
-    restart_run_until_ns: Arc<AtomicU64>,
-    // Request to re-print next-window host/PID info while paused.
-    info_requested: Arc<AtomicBool>,
-    skip_start_pause: Arc<AtomicBool>,
-    // If non-zero, request to run for this many ns of simulated time (relative) before pausing.
-    run_for_ns: Arc<AtomicU64>,
-    // Absolute simulated time (ns since SIMULATION_START) at which to pause, or u64::MAX if unset.
-    run_until_abs_ns: Arc<AtomicU64>,
-    // If >0, run this many windows and then pause.
-    step_windows_remaining: Arc<AtomicU64>,
-    paused: Mutex<bool>,
-    cv: Condvar,
-}
-
-#[cfg(feature = "enable_run_control")]
-static RUN_CONTROL: OnceLock<RunControl> = OnceLock::new();
-#[cfg(feature = "enable_run_control")]
-static RUN_CONTROL_STDIN_THREAD_STARTED: OnceLock<()> = OnceLock::new();
-
-#[cfg(feature = "enable_run_control")]
-#[derive(Debug)]
-pub struct RestartRequest {
-    pub run_until_ns: Option<u64>,
-}
-
-#[cfg(feature = "enable_run_control")]
-impl std::fmt::Display for RestartRequest {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if let Some(ns) = self.run_until_ns {
-            write!(f, "Restart requested: run until {ns} ns")
-        } else {
-            write!(f, "Restart requested")
-        }
-    }
-}
-
-#[cfg(feature = "enable_run_control")]
-impl std::error::Error for RestartRequest {}
-
-#[cfg(feature = "enable_run_control")]
-pub fn set_restart_run_until(run_until_ns: Option<u64>) {
-    RESTART_RUN_UNTIL_NS.store(run_until_ns.unwrap_or(u64::MAX), Ordering::Relaxed);
-}
-
 pub struct Manager<'a> {
     manager_config: Option<ManagerConfig>,
     controller: &'a Controller<'a>,
@@ -370,6 +311,34 @@ impl<'a> Manager<'a> {
             x => x.try_into().unwrap(),
         };
 
+        // connect to the other shadow processes before building the hosts, so that each process
+        // only builds its own hosts
+        let distributed_addresses = self.config.experimental.distributed_addresses.as_ref();
+        let distributed = match distributed_addresses.unwrap().as_slice() {
+            [] => None,
+            addresses => {
+                if self.config.experimental.use_per_host_lookahead.unwrap() {
+                    anyhow::bail!(
+                        "The per-host lookahead can't be used when the simulation is distributed"
+                    );
+                }
+                if !manager_config.links.is_empty() {
+                    anyhow::bail!(
+                        "Network graph edges with a bandwidth can't be used when the simulation \
+                         is distributed"
+                    );
+                }
+                let rank = self.config.experimental.distributed_rank.unwrap();
+                let distributed = Distributed::connect(
+                    addresses,
+                    rank.try_into().unwrap(),
+                    manager_config.hosts.len(),
+                )
+                .context("Failed to connect to the other Shadow processes")?;
+                Some(distributed)
+            }
+        };
+
         // Set up the global DNS before building the hosts
         let mut dns_builder = DnsBuilder::new();
 
@@ -386,10 +355,13 @@ impl<'a> Manager<'a> {
             let std::net::IpAddr::V4(addr) = info.ip_addr.unwrap() else {
                 unreachable!("IPv6 not supported");
             };
+            let std::net::IpAddr::V6(addr6) = info.ipv6_addr.unwrap() else {
+                unreachable!("Expected an IPv6 address");
+            };
 
             // Register in the global DNS.
             dns_builder
-                .register(*id, addr, info.name.clone())
+                .register(*id, addr, Some(addr6), info.name.clone())
                 .with_context(|| {
                     format!(
                         "Failed to register a host with id='{:?}', addr='{}', and name='{}' in the DNS module",
@@ -401,6 +373,82 @@ impl<'a> Manager<'a> {
         // Convert to a global read-only DNS struct.
         let dns = dns_builder.into_dns()?;
 
+        // processes with start conditions aren't scheduled when their host is built, and are
+        // instead started once the conditions are met
+        let host_ids: HashMap<&str, HostId> = host_init
+            .iter()
+            .map(|(info, id)| (info.name.as_str(), *id))
+            .collect();
+        let mut start_conditions = StartConditions::new();
+        for (info, id) in &host_init {
+            for (i, proc) in info.processes.iter().enumerate() {
+                if proc.start_after.is_empty() {
+                    continue;
+                }
+                let conditions = proc
+                    .start_after
+                    .iter()
+                    .map(|(name, condition)| (host_ids[name.as_str()], condition.clone()));
+                let start_time = EmulatedTime::SIMULATION_START + proc.start_time;
+                start_conditions.add_process(*id, i, start_time, conditions);
+            }
+        }
+
+        // the conditions are only gathered from this process's hosts at the end of each round, and
+        // processes are started at the end of the round that their conditions were met in
+        if !start_conditions.is_empty() {
+            if distributed.is_some() {
+                anyhow::bail!(
+                    "Process start conditions can't be used when the simulation is distributed"
+                );
+            }
+            if self.config.experimental.use_per_host_lookahead.unwrap() {
+                anyhow::bail!("Process start conditions can't be used with the per-host lookahead");
+            }
+        }
+
+        // hosts with pcap enabled can capture to a single file rather than a file per interface
+        let packet_capture = if self.config.general.pcap_merged.unwrap() {
+            let hosts = host_init.iter().filter_map(|(info, id)| {
+                let capture_size = info.pcap_config.as_ref()?.capture_size;
+                Some((*id, info.name.as_str(), capture_size.try_into().unwrap()))
+            });
+            let path = self.data_path.join("packets.pcapng");
+            let capture = PacketCapture::new(&path, hosts)
+                .with_context(|| format!("Failed to create the packet capture {path:?}"))?;
+            Some(capture)
+        } else {
+            None
+        };
+
+        let packet_trace = if self.config.general.packet_trace.unwrap() {
+            let path = self.data_path.join("packet-trace.jsonl");
+            let trace = PacketTrace::new(&path)
+                .with_context(|| format!("Failed to create the packet trace {path:?}"))?;
+            Some(trace)
+        } else {
+            None
+        };
+
+        let activity_trace = if self.config.general.activity_trace.unwrap() {
+            let hosts = host_init.iter().map(|(info, id)| (*id, info.name.as_str()));
+            let path = self.data_path.join("activity-trace.json");
+            let trace = ActivityTrace::new(&path, hosts)
+                .with_context(|| format!("Failed to create the activity trace {path:?}"))?;
+            Some(trace)
+        } else {
+            None
+        };
+
+        let strace_merged = if self.config.experimental.strace_logging_merged.unwrap() {
+            let path = self.data_path.join("strace.jsonl");
+            let merged = MergedStrace::new(&path)
+                .with_context(|| format!("Failed to create the merged strace log {path:?}"))?;
+            Some(merged)
+        } else {
+            None
+        };
+
         // Now build the hosts using the assigned host ids.
         // note: there are several return points before we add these hosts to the scheduler and we
         // would leak memory if we return before then, but not worrying about that since the issues
@@ -408,6 +456,8 @@ impl<'a> Manager<'a> {
         // then it means there was an error and we're going to exit anyways
         let mut hosts: Vec<_> = host_init
             .iter()
+            // other shadow processes build the hosts that they run
+            .filter(|(_, id)| distributed.as_ref().is_none_or(|x| x.is_local(*id)))
             .map(|(info, id)| {
                 self.build_host(*id, info)
                     .with_context(|| format!("Failed to build host '{}'", info.name))
@@ -443,23 +493,53 @@ impl<'a> Manager<'a> {
         }
         assert_eq!(cpus.len(), parallelism);
 
+        let runahead = Runahead::new(
+            self.config.experimental.use_dynamic_runahead.unwrap(),
+            smallest_latency,
+            min_runahead_config,
+        );
+
+        let link_queues = LinkQueues::new(manager_config.links);
+        link_queues.set_min_offsets(&manager_config.routing_info.min_link_offsets_ns());
+
+        let jitter_distribution = self.config.network.jitter_distribution.unwrap();
+        let lookahead = self
+            .config
+            .experimental
+            .use_per_host_lookahead
+            .unwrap()
+            .then(|| {
+                let host_nodes = hosts.iter().map(|x| (x.id(), x.params.node_id)).collect();
+                Lookahead::new(
+                    &host_nodes,
+                    &manager_config.routing_info,
+                    jitter_distribution,
+                    runahead.get(),
+                )
+            });
+
         // set the simulation's global state
         worker::WORKER_SHARED
             .borrow_mut()
             .replace(worker::WorkerShared {
                 ip_assignment: manager_config.ip_assignment,
                 routing_info: manager_config.routing_info,
+                network_faults: std::sync::Mutex::new(manager_config.network_faults),
+                link_queues,
+                start_conditions,
+                packet_capture,
+                packet_trace,
+                activity_trace,
+                strace_merged,
                 host_bandwidths: manager_config.host_bandwidths,
                 // safe since the DNS type has an internal mutex
                 dns,
                 num_plugin_errors: AtomicU32::new(0),
                 // allow the status logger's state to be updated from anywhere
                 status_logger_state: status_logger_state.map(Arc::clone),
-                runahead: Runahead::new(
-                    self.config.experimental.use_dynamic_runahead.unwrap(),
-                    smallest_latency,
-                    min_runahead_config,
-                ),
+                runahead,
+                lookahead,
+                distributed,
                 child_pid_watcher: ChildPidWatcher::new(),
                 event_queues: hosts
                     .iter()
@@ -467,6 +547,8 @@ impl<'a> Manager<'a> {
                     .collect(),
                 bootstrap_end_time,
                 sim_end_time: self.end_time,
+                jitter_distribution,
+                allow_packet_reordering: self.config.network.allow_packet_reordering.unwrap(),
             });
 
         // Used to carry an in-process restart request out of the scheduler scope.
@@ -526,16 +608,22 @@ impl<'a> Manager<'a> {
 
             // Initialize run-control and (if interactive) spawn stdin thread once per process.
             #[cfg(feature = "enable_run_control")]
-            let rc = init_and_reset_run_control();
+            let rc = run_control::init_and_reset_run_control();
 
             #[cfg(feature = "enable_run_control")]
-            spawn_run_control_stdin_thread_once(rc);
+            run_control::spawn_run_control_stdin_thread_once(rc);
 
             #[cfg(feature = "enable_run_control")]
-            auto_pause_at_start_if_terminal(rc);
+            run_control::auto_pause_at_start(rc);
 
             #[cfg(feature = "enable_run_control")]
-            wait_if_paused_at_start(rc);
+            run_control::wait_if_paused_at_start(
+                rc,
+                &mut RunControlAccess {
+                    scheduler: &mut scheduler,
+                    end_time: self.end_time,
+                },
+            );
 
             // the scheduling loop
             while let Some((window_start, window_end)) = window {
@@ -564,6 +652,15 @@ impl<'a> Manager<'a> {
                         state.current = display_time;
                     });
 
+                // with the per-host lookahead, each host runs until the earliest time that a
+                // packet could arrive from another host, rather than until the end of the window
+                let host_window_ends = worker::WORKER_SHARED
+                    .borrow()
+                    .as_ref()
+                    .unwrap()
+                    .host_window_ends();
+                let host_window_ends = &host_window_ends;
+
                 // run the events
                 scheduler.scope(|s| {
                     // run the closure on each of the scheduler's threads
@@ -578,9 +675,16 @@ impl<'a> Manager<'a> {
                             worker::Worker::set_round_end_time(window_end);
 
                             for_each_host(hosts, |host| {
+                                let host_window_end = host_window_ends
+                                    .as_ref()
+                                    .map_or(window_end, |x| x[&host.id()]);
+                                worker::Worker::set_round_end_time(host_window_end);
+
                                 let host_next_event_time = {
                                     host.lock_shmem();
-                                    host.execute(window_end);
+                                    host.start_ready_applications();
+                                    host.execute(host_window_end);
+                                    host.check_start_conditions();
                                     let host_next_event_time = host.next_event_time();
                                     host.unlock_shmem();
                                     host_next_event_time
@@ -627,6 +731,15 @@ impl<'a> Manager<'a> {
                     .reduce(std::cmp::min)
                     .unwrap_or(EmulatedTime::MAX);
 
+                // if the simulation is distributed, exchange packets with the other processes and
+                // continue from the earliest next event of any process
+                let min_next_event_time = worker::WORKER_SHARED
+                    .borrow()
+                    .as_ref()
+                    .unwrap()
+                    .finish_round(window_end, min_next_event_time)
+                    .context("Failed to finish the round with the other Shadow processes")?;
+
                 #[cfg(feature = "enable_perf_logging")]
                 {
                     log::debug!(
@@ -658,101 +771,21 @@ impl<'a> Manager<'a> {
                 // Run-control handling (only when `enable_run_control` is enabled).
                 #[cfg(feature = "enable_run_control")]
                 {
-                    let fmt_s = |ns: u64| -> String {
-                        if ns % 1_000_000_000 == 0 {
-                            format!("{}s", ns / 1_000_000_000)
-                        } else {
-                            format!("{:.6}s", (ns as f64) / 1_000_000_000.0)
-                        }
-                    };
-
-                    apply_run_control_requests_at_window_boundary(
+                    run_control::apply_run_control_requests_at_window_boundary(
                         rc,
                         min_next_event_time,
                         &mut restart_request,
                     );
 
-                    let mut print_next_window_info = || {
-                        let Some((next_window_start, next_window_end)) = next_window else {
-                            eprintln!("** No next window (simulation ending)");
-                            return;
-                        };
-
-                        let info = Arc::new(Mutex::new(Vec::new()));
-                        scheduler.scope(|s| {
-                            let info = Arc::clone(&info);
-                            s.run_with_hosts(move |_, hosts| {
-                                for_each_host(hosts, |host| {
-                                    let next_time = host.next_event_time();
-                                    if let Some(t) = next_time
-                                        && t < next_window_end
-                                    {
-                                        let mut pids = Vec::new();
-                                        for (_proc_id, proc_rc) in host.processes_borrow().iter() {
-                                            let proc = proc_rc.borrow(host.root());
-                                            if proc.is_running() {
-                                                pids.push(proc.native_pid());
-                                            }
-                                        }
-                                        info.lock().unwrap().push((
-                                            host.id(),
-                                            host.name().to_string(),
-                                            t,
-                                            pids,
-                                        ));
-                                    }
-                                });
-                            });
-                        });
-
-                        let mut info = info.lock().unwrap();
-                        if info.is_empty() {
-                            eprintln!("** No hosts scheduled in next window");
-                            return;
-                        }
-                        info.sort_by_key(|(id, _, _, _)| *id);
-
-                        eprintln!("**");
-                        eprintln!(
-                            "** Next window: t=[{}, {}]",
-                            fmt_s(
-                                (next_window_start - EmulatedTime::SIMULATION_START).as_nanos()
-                                    as u64
-                            ),
-                            fmt_s(
-                                (next_window_end - EmulatedTime::SIMULATION_START).as_nanos()
-                                    as u64
-                            )
-                        );
-                        eprintln!("** Hosts scheduled for next window:");
-                        for (host_id, hostname, next_time, pids) in info.iter() {
-                            eprintln!(
-                                "**   Host {:?} ({}) - next event at t={}",
-                                host_id,
-                                hostname,
-                                fmt_s(
-                                    (*next_time - EmulatedTime::SIMULATION_START).as_nanos() as u64
-                                )
-                            );
-                            if pids.is_empty() {
-                                eprintln!("**     <no running processes>");
-                            } else {
-                                for pid in pids {
-                                    eprintln!(
-                                        "**     pid={} (attach: s:{})",
-                                        pid.as_raw_nonzero().get(),
-                                        pid.as_raw_nonzero().get()
-                                    );
-                                }
-                            }
-                        }
-                    };
-
-                    pause_and_soft_wait_until_resumed(
+                    run_control::pause_and_soft_wait_until_resumed(
                         rc,
                         min_next_event_time,
+                        next_window,
                         &mut restart_request,
-                        &mut print_next_window_info,
+                        &mut RunControlAccess {
+                            scheduler: &mut scheduler,
+                            end_time: self.end_time,
+                        },
                     );
                 }
                 #[cfg(feature = "enable_run_control")]
@@ -772,6 +805,8 @@ impl<'a> Manager<'a> {
             #[cfg(feature = "enable_run_control")]
             if restart_request.is_some() {
                 worker::RESTART_TEARDOWN.store(true, Ordering::Relaxed);
+            } else {
+                rc.simulation_ended(self.end_time);
             }
             scheduler.scope(|s| {
                 s.run_with_hosts(move |_, hosts| {
@@ -807,12 +842,58 @@ impl<'a> Manager<'a> {
                 state.current = self.end_time;
             });
 
+        // processes whose start conditions were never met weren't run at all
+        {
+            let shared = worker::WORKER_SHARED.borrow();
+            let shared = shared.as_ref().unwrap();
+            for (host_id, index) in shared.start_conditions.waiting() {
+                let (info, _) = host_init.iter().find(|(_, id)| *id == host_id).unwrap();
+                log::error!(
+                    "Process {index} of host '{}' was never started since its start conditions \
+                     weren't met",
+                    info.name,
+                );
+                shared.increment_plugin_error_count();
+            }
+        }
+
         let num_plugin_errors = worker::WORKER_SHARED
             .borrow()
             .as_ref()
             .unwrap()
             .plugin_error_count();
 
+        // finish the packet capture and the traces after the last round
+        {
+            let shared = worker::WORKER_SHARED.borrow();
+            if let Some(capture) = &shared.as_ref().unwrap().packet_capture {
+                capture
+                    .finish()
+                    .context("Failed to write the packet capture")?;
+            }
+            if let Some(trace) = &shared.as_ref().unwrap().packet_trace {
+                trace.finish().context("Failed to write the packet trace")?;
+            }
+            if let Some(trace) = &shared.as_ref().unwrap().activity_trace {
+                trace
+                    .finish()
+                    .context("Failed to write the activity trace")?;
+            }
+            if let Some(merged) = &shared.as_ref().unwrap().strace_merged {
+                merged
+                    .finish()
+                    .context("Failed to write the merged strace log")?;
+            }
+        }
+
+        let link_stats = worker::WORKER_SHARED
+            .borrow()
+            .as_ref()
+            .unwrap()
+            .link_queues
+            .stats();
+        worker::with_global_sim_stats(|stats| *stats.link_stats.lock().unwrap() = link_stats);
+
         // drop the simulation's global state
         // must drop before the allocation counters have been checked
         worker::WORKER_SHARED.borrow_mut().take();
@@ -852,7 +933,9 @@ impl<'a> Manager<'a> {
             } else {
                 Some(run_until_ns)
             };
-            return Err(anyhow::Error::new(RestartRequest { run_until_ns }));
+            return Err(anyhow::Error::new(run_control::RestartRequest {
+                run_until_ns,
+            }));
         }
 
         Ok(num_plugin_errors)
@@ -876,6 +959,10 @@ impl<'a> Manager<'a> {
                     // the config only allows ipv4 addresses, so this shouldn't happen
                     std::net::IpAddr::V6(_) => unreachable!("IPv6 not supported"),
                 },
+                ipv6_addr: match host_info.ipv6_addr.unwrap() {
+                    std::net::IpAddr::V6(ip) => ip,
+                    std::net::IpAddr::V4(_) => unreachable!("Expected an IPv6 address"),
+                },
                 sim_end_time: self.end_time,
                 requested_bw_down_bits: host_info.bandwidth_down_bits.unwrap(),
                 requested_bw_up_bits: host_info.bandwidth_up_bits.unwrap(),
@@ -885,12 +972,17 @@ impl<'a> Manager<'a> {
                     .log_level
                     .map(|x| x.to_c_loglevel())
                     .unwrap_or(logger::_LogLevel_LOGLEVEL_UNSET),
-                pcap_config: host_info.pcap_config,
+                pcap_config: host_info.pcap_config.clone(),
+                metrics_interval: self.config.metrics_interval(),
+                metrics_format: self.config.general.metrics_format.unwrap(),
+                activity_trace: self.config.general.activity_trace.unwrap(),
                 qdisc: host_info.qdisc,
+                packet_qdisc: host_info.packet_qdisc,
                 init_sock_recv_buf_size: host_info.recv_buf_size,
                 autotune_recv_buf: host_info.autotune_recv_buf,
                 init_sock_send_buf_size: host_info.send_buf_size,
                 autotune_send_buf: host_info.autotune_send_buf,
+                tcp_congestion_control: host_info.tcp_congestion_control,
                 native_tsc_frequency: self.native_tsc_frequency,
                 model_unblocked_syscall_latency: self.config.model_unblocked_syscall_latency(),
                 max_unapplied_cpu_latency: self.config.max_unapplied_cpu_latency(),
@@ -904,6 +996,8 @@ impl<'a> Manager<'a> {
                 use_new_tcp: self.config.experimental.use_new_tcp.unwrap(),
                 use_mem_mapper: self.config.experimental.use_memory_manager.unwrap(),
                 use_syscall_counters: self.config.experimental.use_syscall_counters.unwrap(),
+                clock: host_info.clock.clone(),
+                bandwidth_changes: host_info.bandwidth_changes.clone(),
             };
 
             Box::new(Host::new(
@@ -917,6 +1011,10 @@ impl<'a> Manager<'a> {
 
         host.lock_shmem();
 
+        host.schedule_clock_steps();
+        host.schedule_bandwidth_changes();
+        host.schedule_network_metrics();
+
         for proc in &host_info.processes {
             let plugin_path =
                 CString::new(proc.plugin.clone().into_os_string().as_bytes()).unwrap();
@@ -953,6 +1051,7 @@ impl<'a> Manager<'a> {
                 envv,
                 pause_for_debugging,
                 proc.expected_final_state,
+                !proc.start_after.is_empty(),
             );
 
             host.stop_execution_timer();
@@ -1094,6 +1193,12 @@ pub struct ManagerConfig {
     // routing information for paths between graph nodes
     pub routing_info: RoutingInfo<u32>,
 
+    // the links in the network graph, which are the edges that have a bandwidth
+    pub links: Vec<(Link, LinkProperties)>,
+
+    // the network graph and changes to it during the simulation
+    pub network_faults: NetworkFaults,
+
     // bandwidths of hosts at ip addresses
     pub host_bandwidths: HashMap<std::net::IpAddr, Bandwidth>,
 
@@ -1113,332 +1218,110 @@ fn for_each_host(host_iter: &mut HostIter<Box<Host>>, mut f: impl FnMut(&Host))
     });
 }
 
+/// Gives run-control access to the hosts and the network from the manager thread.
 #[cfg(feature = "enable_run_control")]
-fn init_and_reset_run_control() -> &'static RunControl {
-    let rc = RUN_CONTROL.get_or_init(|| RunControl {
-        pause_requested: Arc::new(AtomicBool::new(false)),
-        restart_requested: Arc::new(AtomicBool::new(false)),
-        restart_run_until_ns: Arc::new(AtomicU64::new(u64::MAX)),
-        // Request to re-print next-window host/PID info while paused.
-        info_requested: Arc::new(AtomicBool::new(false)),
-        skip_start_pause: Arc::new(AtomicBool::new(false)),
-        // If non-zero, request to run for this many ns of simulated time (relative) before
-        // pausing.
-        run_for_ns: Arc::new(AtomicU64::new(0)),
-        // Absolute simulated time (ns since SIMULATION_START) at which to pause, or u64::MAX
-        // if unset.
-        run_until_abs_ns: Arc::new(AtomicU64::new(u64::MAX)),
-        // If >0, run this many windows and then pause.
-        step_windows_remaining: Arc::new(AtomicU64::new(0)),
-        paused: Mutex::new(false),
-        cv: Condvar::new(),
-    });
-
-    // Clear any prior run-control state (important for in-process restarts).
-    rc.pause_requested.store(false, Ordering::Relaxed);
-    rc.restart_requested.store(false, Ordering::Relaxed);
-    rc.info_requested.store(false, Ordering::Relaxed);
-    rc.run_for_ns.store(0, Ordering::Relaxed);
-    rc.run_until_abs_ns.store(u64::MAX, Ordering::Relaxed);
-    rc.step_windows_remaining.store(0, Ordering::Relaxed);
-    rc.skip_start_pause.store(false, Ordering::Relaxed);
-    rc.restart_run_until_ns.store(u64::MAX, Ordering::Relaxed);
-
-    let pending = RESTART_RUN_UNTIL_NS.swap(u64::MAX, Ordering::Relaxed);
-    if pending != u64::MAX {
-        rc.run_until_abs_ns.store(pending, Ordering::Relaxed);
-        rc.skip_start_pause.store(true, Ordering::Relaxed);
-    }
-
-    rc
+struct RunControlAccess<'a> {
+    scheduler: &'a mut Scheduler<Box<Host>>,
+    end_time: EmulatedTime,
 }
 
 #[cfg(feature = "enable_run_control")]
-fn spawn_run_control_stdin_thread_once(rc: &'static RunControl) {
-    RUN_CONTROL_STDIN_THREAD_STARTED.get_or_init(|| {
-        if !std::io::stdin().is_terminal() {
-            return;
-        }
-
-        let pause_requested = rc.pause_requested.clone();
-        let restart_requested = rc.restart_requested.clone();
-        let restart_run_until_ns = rc.restart_run_until_ns.clone();
-        let info_requested = rc.info_requested.clone();
-        let skip_start_pause = rc.skip_start_pause.clone();
-        let run_for_ns = rc.run_for_ns.clone();
-        let run_until_abs_ns = rc.run_until_abs_ns.clone();
-        let step_windows_remaining = rc.step_windows_remaining.clone();
-
-        // Use raw pointers? avoid; instead capture RUN_CONTROL via OnceLock at runtime.
-        std::thread::spawn(move || {
-            // Print help once (times are in *simulated seconds*).
-            eprintln!(
-                "\
-** Shadow run-control (stdin; simulated time)\n\
-**   p<Enter>: pause at next window boundary\n\
-**   c<Enter>: continue\n\
-**   cN<Enter>: continue for N simulated seconds, then pause at next window boundary (e.g. c10)\n\
-**   n<Enter>: run exactly one window, then pause\n\
-**   s<Enter>: show next-window hosts/PIDs (when paused)\n\
-**   s:<pid><Enter>: print gdb attach command (e.g. s:12345)\n\
-**   info<Enter>: show next-window hosts/PIDs (when paused)\n\
-**   r<Enter>: restart from t=0s (in-process)\n\
-**   rN<Enter>: restart and run to N seconds (e.g. r10)\n"
-            );
-
-            let stdin = std::io::stdin();
-            for line in stdin.lock().lines().flatten() {
-                let cmd = line.trim();
-                if cmd.is_empty() {
-                    continue;
-                }
-
-                if cmd == "p" {
-                    pause_requested.store(true, Ordering::Relaxed);
-                    eprintln!("** run-control: pause requested (will pause at next window boundary)");
-                    continue;
-                }
-
-                if cmd == "n" {
-                    // Run exactly one more window, then pause.
-                    step_windows_remaining.store(1, Ordering::Relaxed);
-                    run_until_abs_ns.store(u64::MAX, Ordering::Relaxed);
-                    if let Some(rc) = RUN_CONTROL.get() {
-                        *rc.paused.lock().unwrap() = false;
-                        rc.cv.notify_all();
-                    }
-                    eprintln!("** run-control: will run 1 window and then pause");
-                    continue;
-                }
-
-                if cmd == "r" {
-                    restart_run_until_ns.store(u64::MAX, Ordering::Relaxed);
-                    skip_start_pause.store(false, Ordering::Relaxed);
-                    restart_requested.store(true, Ordering::Relaxed);
-                    if let Some(rc) = RUN_CONTROL.get() {
-                        rc.cv.notify_all();
-                    }
-                    eprintln!("** run-control: restart requested (in-process)");
-                    continue;
-                }
-
-                if let Some(rest) = cmd.strip_prefix('r') {
-                    if !rest.is_empty() {
-                        if let Ok(secs) = rest.parse::<u64>() {
-                            restart_run_until_ns.store(
-                                secs.saturating_mul(1_000_000_000),
-                                Ordering::Relaxed,
-                            );
-                            skip_start_pause.store(true, Ordering::Relaxed);
-                            restart_requested.store(true, Ordering::Relaxed);
-                            if let Some(rc) = RUN_CONTROL.get() {
-                                rc.cv.notify_all();
-                            }
-                            eprintln!("** run-control: restart requested (run to t={secs}s)");
-                            continue;
-                        }
-                    }
-                }
-
-                if cmd == "s" || cmd == "info" {
-                    info_requested.store(true, Ordering::Relaxed);
-                    if let Some(rc) = RUN_CONTROL.get() {
-                        rc.cv.notify_all();
-                    }
-                    eprintln!("** run-control: info requested (will print while paused)");
-                    continue;
-                }
-
-                if let Some(rest) = cmd.strip_prefix("s:") {
-                    // s:<pid> - attach gdb to the specified PID (manual, no GUI)
-                    if let Ok(pid) = rest.parse::<i32>() {
-                        eprintln!("** run-control: attach gdb manually with: gdb/dlv -p/attach {}", pid);
-                        continue;
-                    } else {
-                        eprintln!("** run-control: invalid PID: '{}'", rest);
-                        continue;
-                    }
-                }
-
-                if cmd == "c" {
-                    // Continue indefinitely.
-                    step_windows_remaining.store(0, Ordering::Relaxed);
-                    run_until_abs_ns.store(u64::MAX, Ordering::Relaxed);
-                    run_for_ns.store(0, Ordering::Relaxed);
-                    if let Some(rc) = RUN_CONTROL.get() {
-                        *rc.paused.lock().unwrap() = false;
-                        rc.cv.notify_all();
-                    }
-                    eprintln!("** run-control: continue");
-                    continue;
-                }
+impl run_control::SimulationAccess for RunControlAccess<'_> {
+    fn host_statuses(&mut self) -> Vec<run_control::HostStatus> {
+        collect_host_statuses(self.scheduler)
+    }
 
-                if let Some(rest) = cmd.strip_prefix('c') {
-                    // cN: run for N simulated seconds, then pause.
-                    if let Ok(secs) = rest.parse::<u64>() {
-                        step_windows_remaining.store(0, Ordering::Relaxed);
-                        run_until_abs_ns.store(u64::MAX, Ordering::Relaxed);
-                        run_for_ns.store(secs.saturating_mul(1_000_000_000), Ordering::Relaxed);
-                        if let Some(rc) = RUN_CONTROL.get() {
-                            *rc.paused.lock().unwrap() = false;
-                            rc.cv.notify_all();
-                        }
-                        eprintln!(
-                            "** run-control: continue for {secs}s simulated time (will pause at a window boundary)"
-                        );
-                        continue;
-                    }
-                }
+    fn add_network_fault(
+        &mut self,
+        fault: &configuration::NetworkFaultOptions,
+    ) -> anyhow::Result<()> {
+        let time = SimulationTime::try_from(Duration::from(fault.time()))
+            .ok()
+            .map(|x| EmulatedTime::SIMULATION_START + x)
+            .filter(|x| *x < self.end_time)
+            .context("The fault time must be earlier than the simulation stop time")?;
+
+        let worker_shared = worker::WORKER_SHARED.borrow();
+        let shared = worker_shared.as_ref().unwrap();
+        let mut network_faults = shared.network_faults.lock().unwrap();
+
+        let Some((node_id, change)) = network_faults.add(fault)? else {
+            // recompute the paths for every period starting at or after the fault
+            shared
+                .routing_info
+                .set_changed_paths_from(time, network_faults.changed_paths_from(time));
+            shared
+                .routing_info
+                .set_changed_links_from(time, network_faults.changed_links_from(time));
+            shared
+                .link_queues
+                .set_min_offsets(&shared.routing_info.min_link_offsets_ns());
+
+            if let Some(lookahead) = &shared.lookahead {
+                lookahead.update_latencies(&shared.routing_info, shared.jitter_distribution);
+                return Ok(());
+            }
 
-                eprintln!(
-                    "** Unknown command: '{cmd}'. Use: p | c | cN (e.g. c10) | n | s | s:<pid> | info | r | rN (e.g. r10)"
+            let runahead = shared.runahead.get();
+            if let Some(latency) = shared.routing_info.get_smallest_latency_ns()
+                && SimulationTime::from_nanos(latency) < runahead
+            {
+                log::warn!(
+                    "The network fault reduces the smallest latency to {latency} ns, which is less \
+                     than the runahead of {runahead:?}. Packets may be delayed beyond their \
+                     latency."
                 );
             }
+            return Ok(());
+        };
+        drop(network_faults);
+        drop(worker_shared);
+
+        self.scheduler.scope(|s| {
+            s.run_with_hosts(move |_, hosts| {
+                for_each_host(hosts, |host| {
+                    if host.params.node_id == node_id {
+                        host.schedule_bandwidth_change(change);
+                    }
+                });
+            });
         });
-    });
-}
-
-#[cfg(feature = "enable_run_control")]
-fn auto_pause_at_start_if_terminal(rc: &'static RunControl) {
-    // Auto-pause at simulated time 0s (before running the first window),
-    // but only when running interactively (stdin is a TTY). This avoids
-    // hanging non-interactive runs.
-    if std::io::stdin().is_terminal() {
-        let mut paused = rc.paused.lock().unwrap();
-        let skip_start_pause = rc.skip_start_pause.load(Ordering::Relaxed);
-        rc.skip_start_pause.store(false, Ordering::Relaxed);
-        if !*paused && !skip_start_pause {
-            *paused = true;
-            eprintln!(
-                "\
-** Shadow paused at start (t=0s)\n\
-** Commands: c | cN (e.g. c10) | n | info | s:<pid> | r | rN"
-            );
-        }
+        Ok(())
     }
 }
 
+/// Get the state of every host for run-control, sorted by host ID.
 #[cfg(feature = "enable_run_control")]
-fn wait_if_paused_at_start(rc: &'static RunControl) {
-    // If we started in paused mode, block here before running the first window.
-    // (We only set paused-at-start when stdin is a TTY.)
-    let mut paused = rc.paused.lock().unwrap();
-    while *paused {
-        paused = rc.cv.wait(paused).unwrap();
-    }
-}
-
-#[cfg(feature = "enable_run_control")]
-fn apply_run_control_requests_at_window_boundary(
-    rc: &'static RunControl,
-    min_next_event_time: EmulatedTime,
-    restart_request: &mut Option<u64>,
-) {
-    let fmt_s = |ns: u64| -> String {
-        if ns % 1_000_000_000 == 0 {
-            format!("{}s", ns / 1_000_000_000)
-        } else {
-            format!("{:.6}s", (ns as f64) / 1_000_000_000.0)
-        }
-    };
-
-    // Apply cN request: convert relative duration -> absolute pause target.
-    let run_for = rc.run_for_ns.swap(0, Ordering::Relaxed);
-    if run_for != 0 {
-        let next_abs_ns = (min_next_event_time - EmulatedTime::SIMULATION_START)
-            .as_nanos() as u64;
-        let target = next_abs_ns.saturating_add(run_for);
-        rc.run_until_abs_ns.store(target, Ordering::Relaxed);
-        eprintln!(
-            "** run-control: will pause at ~t={} (after +{} simulated seconds; next window boundary >= target)",
-            fmt_s(target),
-            run_for / 1_000_000_000
-        );
-    }
-
-    // Apply n request: count down windows.
-    let steps_left = rc.step_windows_remaining.load(Ordering::Relaxed);
-    if steps_left > 0 {
-        // Decrement once per window boundary.
-        let new = rc
-            .step_windows_remaining
-            .fetch_sub(1, Ordering::Relaxed)
-            - 1;
-        if new == 0 {
-            rc.pause_requested.store(true, Ordering::Relaxed);
-        }
-    }
-
-    // Auto-pause if we've reached run-until time.
-    let run_until = rc.run_until_abs_ns.load(Ordering::Relaxed);
-    if run_until != u64::MAX {
-        let next_abs_ns = (min_next_event_time - EmulatedTime::SIMULATION_START)
-            .as_nanos() as u64;
-        if next_abs_ns >= run_until {
-            // Clear so we only pause once.
-            rc.run_until_abs_ns.store(u64::MAX, Ordering::Relaxed);
-            rc.pause_requested.store(true, Ordering::Relaxed);
-        }
-    }
-
-    if rc.restart_requested.swap(false, Ordering::Relaxed) {
-        let run_until = rc.restart_run_until_ns.load(Ordering::Relaxed);
-        *restart_request = Some(run_until);
-        eprintln!("** run-control: restart requested (in-process)");
-    }
-}
-
-#[cfg(feature = "enable_run_control")]
-fn pause_and_soft_wait_until_resumed<F: FnMut()>(
-    rc: &'static RunControl,
-    min_next_event_time: EmulatedTime,
-    restart_request: &mut Option<u64>,
-    print_next_window_info: &mut F,
-) {
-    let fmt_s = |ns: u64| -> String {
-        if ns % 1_000_000_000 == 0 {
-            format!("{}s", ns / 1_000_000_000)
-        } else {
-            format!("{:.6}s", (ns as f64) / 1_000_000_000.0)
-        }
-    };
-
-    if rc.pause_requested.swap(false, Ordering::Relaxed) {
-        let mut paused = rc.paused.lock().unwrap();
-        *paused = true;
-
-        eprintln!(
-            "\
-** Shadow paused at window boundary\n\
-**   next window start: t={}",
-            fmt_s(
-                (min_next_event_time - EmulatedTime::SIMULATION_START).as_nanos() as u64
-            ),
-        );
-
-        print_next_window_info();
-
-        eprintln!("**");
-        eprintln!("** To attach gdb: s:<pid> (e.g. s:12345)");
-        eprintln!("** Commands: c | cN (e.g. c10) | n | p | s | s:<pid> | info | r | rN");
-    }
-
-    // If paused, block here (soft pause) until resumed.
-    let mut paused = rc.paused.lock().unwrap();
-    while *paused {
-        if rc.restart_requested.swap(false, Ordering::Relaxed) {
-            let run_until = rc.restart_run_until_ns.load(Ordering::Relaxed);
-            *restart_request = Some(run_until);
-            *paused = false;
-            break;
-        }
-
-        if rc.info_requested.swap(false, Ordering::Relaxed) {
-            print_next_window_info();
-        }
+fn collect_host_statuses(scheduler: &mut Scheduler<Box<Host>>) -> Vec<run_control::HostStatus> {
+    let statuses = std::sync::Mutex::new(Vec::new());
+    scheduler.scope(|s| {
+        s.run_with_hosts(|_, hosts| {
+            for_each_host(hosts, |host| {
+                let pids = host
+                    .processes_borrow()
+                    .values()
+                    .filter_map(|proc_rc| {
+                        let proc = proc_rc.borrow(host.root());
+                        proc.is_running()
+                            .then(|| proc.native_pid().as_raw_nonzero().get())
+                    })
+                    .collect();
+                let status = run_control::HostStatus {
+                    id: host.id().into(),
+                    name: host.name().to_string(),
+                    next_event_ns: host
+                        .next_event_time()
+                        .map(|t| (t - EmulatedTime::SIMULATION_START).as_nanos() as u64),
+                    pids,
+                };
+                statuses.lock().unwrap().push(status);
+            });
+        });
+    });
 
-        paused = rc.cv.wait(paused).unwrap();
-    }
+    let mut statuses = statuses.into_inner().unwrap();
+    statuses.sort_by_key(|x| x.id);
+    statuses
 }
 
 /// Get the raw speed of the experiment machine.
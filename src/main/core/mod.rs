@@ -1,13 +1,19 @@
 //! The core infrastructure needed to configure and run the simulator.
 
+pub mod activity_trace;
 pub mod configuration;
 pub mod controller;
 pub mod cpu;
+pub mod distributed;
 pub mod logger;
+pub mod lookahead;
 pub mod manager;
 pub mod resource_usage;
 pub mod runahead;
+#[cfg(feature = "enable_run_control")]
+pub mod run_control;
 pub mod sim_config;
 pub mod sim_stats;
+pub mod start_conditions;
 pub mod work;
 pub mod worker;
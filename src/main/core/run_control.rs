@@ -0,0 +1,815 @@
+//! Run-control for pausing, stepping, and restarting a running simulation (feature-gated by
+//! `enable_run_control`).
+//!
+//! We implement a *soft pause*: at a window boundary we block on a Condvar until resumed. This
+//! avoids stopping the whole process in the middle of host execution or shim IPC.
+//!
+//! Control is via stdin (interactive terminal):
+//! - p<Enter>: pause at next window boundary
+//! - c<Enter>: continue (resume)
+//! - cN<Enter>: continue for N seconds of *simulated time*, then pause at the next window boundary
+//! - n<Enter>: run exactly one more window, then pause (gdb-like next)
+//! - s<Enter>: show next-window hosts/PIDs (when paused)
+//! - s:<pid><Enter>: print gdb attach command
+//! - r<Enter>: restart from t=0s (in-process)
+//! - rN<Enter>: restart and run to N seconds
+//!
+//! or via a Unix socket (`--run-control-socket`) that accepts JSON-lines requests, for use by
+//! scripts and tests. Each request is a JSON object with a `cmd` field on its own line, and gets
+//! exactly one JSON response line. Successful responses have `"ok": true`, and failed requests
+//! have `"ok": false` and an `"error"` message. All times are nanoseconds of simulated time since
+//! the start of the simulation.
+//! - `{"cmd": "pause"}`: pause at the next window boundary
+//! - `{"cmd": "continue"}`: continue; with `"for_ns": N` pause after N more nanoseconds, or with
+//!   `"until_ns": T` pause at time T
+//! - `{"cmd": "step", "windows": N}`: run N windows (default 1), then pause
+//! - `{"cmd": "restart", "run_until_ns": T}`: restart from t=0s, optionally running to time T
+//! - `{"cmd": "time"}`: responds with the current `time_ns`, and whether the simulation is
+//!   `paused` or has `ended`
+//! - `{"cmd": "hosts"}`: responds with a list of `hosts`, each with its `id`, `name`, the time of
+//!   its next event `next_event_ns`, and the native `pids` of its running processes
+//! - `{"cmd": "wait", "time_ns": T}`: responds once the simulation arrives at time T with
+//!   `"event": "arrived"` and the current `time_ns`, or with `"event": "ended"` if the simulation
+//!   ends first
+//! - `{"cmd": "fault", "fault": F}`: add the network fault F (in the same format as the
+//!   `network.faults` configuration option) at the next window boundary; its time must not be
+//!   earlier than the start of the next window
+//!
+//! When the socket is enabled, the simulation is paused at the start until a client resumes it.
+//!
+//! Network faults that were added through run-control are kept when restarting, so that a re-run
+//! makes the same changes to the network.
+
+use std::io::{BufRead, BufReader, IsTerminal, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Condvar, Mutex, OnceLock, mpsc};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use shadow_shim_helper_rs::emulated_time::EmulatedTime;
+
+use crate::core::configuration::NetworkFaultOptions;
+
+static RESTART_RUN_UNTIL_NS: AtomicU64 = AtomicU64::new(u64::MAX);
+
+static RUN_CONTROL: OnceLock<RunControl> = OnceLock::new();
+static RUN_CONTROL_STDIN_THREAD_STARTED: OnceLock<()> = OnceLock::new();
+
+pub struct RunControl {
+    pause_requested: AtomicBool,
+    restart_requested: AtomicBool,
+    restart_run_until_ns: AtomicU64,
+    // Request to re-print next-window host/PID info while paused.
+    info_requested: AtomicBool,
+    skip_start_pause: AtomicBool,
+    // If non-zero, request to run for this many ns of simulated time (relative) before pausing.
+    run_for_ns: AtomicU64,
+    // Absolute simulated time (ns since SIMULATION_START) at which to pause, or u64::MAX if unset.
+    run_until_abs_ns: AtomicU64,
+    // If >0, run this many windows and then pause.
+    step_windows_remaining: AtomicU64,
+    // Whether the control socket is being served (if so, we pause at start).
+    socket_enabled: AtomicBool,
+    // Simulated time (ns since SIMULATION_START) that the simulation has arrived at, and whether
+    // it has ended. Clients waiting for the simulation to arrive at a time are notified through
+    // `waiters`, and are protected by the same lock so that clients can't miss a notification.
+    progress: Mutex<Progress>,
+    // Clients waiting for the host list, which can only be collected by the manager thread.
+    host_queries: Mutex<Vec<mpsc::Sender<Vec<HostStatus>>>>,
+    // Clients waiting for a network fault to be added, which can only be done by the manager
+    // thread.
+    fault_requests: Mutex<Vec<(NetworkFaultOptions, mpsc::Sender<Result<(), String>>)>>,
+    // Network faults that were added through run-control, which are added again when the
+    // simulation restarts.
+    added_faults: Mutex<Vec<NetworkFaultOptions>>,
+    paused: Mutex<bool>,
+    cv: Condvar,
+}
+
+struct Progress {
+    time_ns: u64,
+    ended: bool,
+    // The time each client is waiting for, and a channel to send the (time, ended) progress when
+    // the simulation arrives at that time or ends.
+    waiters: Vec<(u64, mpsc::Sender<(u64, bool)>)>,
+}
+
+/// The state of a host, as reported to run-control clients.
+#[derive(Debug, Clone, Serialize)]
+pub struct HostStatus {
+    pub id: u32,
+    pub name: String,
+    /// Time of the host's next event (ns since SIMULATION_START).
+    pub next_event_ns: Option<u64>,
+    /// Native PIDs of the host's running processes.
+    pub pids: Vec<i32>,
+}
+
+/// Access to the simulation for run-control requests, which can only be handled by the manager
+/// thread.
+pub trait SimulationAccess {
+    /// Get the state of every host, sorted by host ID.
+    fn host_statuses(&mut self) -> Vec<HostStatus>;
+
+    /// Add a network fault to the simulation.
+    fn add_network_fault(&mut self, fault: &NetworkFaultOptions) -> anyhow::Result<()>;
+}
+
+#[derive(Debug)]
+pub struct RestartRequest {
+    pub run_until_ns: Option<u64>,
+}
+
+impl std::fmt::Display for RestartRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(ns) = self.run_until_ns {
+            write!(f, "Restart requested: run until {ns} ns")
+        } else {
+            write!(f, "Restart requested")
+        }
+    }
+}
+
+impl std::error::Error for RestartRequest {}
+
+pub fn set_restart_run_until(run_until_ns: Option<u64>) {
+    RESTART_RUN_UNTIL_NS.store(run_until_ns.unwrap_or(u64::MAX), Ordering::Relaxed);
+}
+
+fn ns_since_start(time: EmulatedTime) -> u64 {
+    (time - EmulatedTime::SIMULATION_START).as_nanos() as u64
+}
+
+fn fmt_s(ns: u64) -> String {
+    if ns % 1_000_000_000 == 0 {
+        format!("{}s", ns / 1_000_000_000)
+    } else {
+        format!("{:.6}s", (ns as f64) / 1_000_000_000.0)
+    }
+}
+
+impl RunControl {
+    fn global() -> &'static Self {
+        RUN_CONTROL.get_or_init(|| Self {
+            pause_requested: AtomicBool::new(false),
+            restart_requested: AtomicBool::new(false),
+            restart_run_until_ns: AtomicU64::new(u64::MAX),
+            info_requested: AtomicBool::new(false),
+            skip_start_pause: AtomicBool::new(false),
+            run_for_ns: AtomicU64::new(0),
+            run_until_abs_ns: AtomicU64::new(u64::MAX),
+            step_windows_remaining: AtomicU64::new(0),
+            socket_enabled: AtomicBool::new(false),
+            progress: Mutex::new(Progress {
+                time_ns: 0,
+                ended: false,
+                waiters: Vec::new(),
+            }),
+            host_queries: Mutex::new(Vec::new()),
+            fault_requests: Mutex::new(Vec::new()),
+            added_faults: Mutex::new(Vec::new()),
+            paused: Mutex::new(false),
+            cv: Condvar::new(),
+        })
+    }
+
+    /// Wake the manager thread if it's paused. We take the `paused` lock so that the wakeup
+    /// can't be missed by a manager thread that is about to wait.
+    fn wake(&self) {
+        let _paused = self.paused.lock().unwrap();
+        self.cv.notify_all();
+    }
+
+    fn unpause(&self) {
+        *self.paused.lock().unwrap() = false;
+        self.cv.notify_all();
+    }
+
+    /// Pause at the next window boundary.
+    pub fn request_pause(&self) {
+        self.pause_requested.store(true, Ordering::Relaxed);
+    }
+
+    /// Continue indefinitely.
+    pub fn resume(&self) {
+        self.step_windows_remaining.store(0, Ordering::Relaxed);
+        self.run_until_abs_ns.store(u64::MAX, Ordering::Relaxed);
+        self.run_for_ns.store(0, Ordering::Relaxed);
+        self.unpause();
+    }
+
+    /// Continue for `ns` nanoseconds of simulated time, then pause.
+    pub fn resume_for(&self, ns: u64) {
+        self.step_windows_remaining.store(0, Ordering::Relaxed);
+        self.run_until_abs_ns.store(u64::MAX, Ordering::Relaxed);
+        self.run_for_ns.store(ns, Ordering::Relaxed);
+        self.unpause();
+    }
+
+    /// Continue until the simulated time `abs_ns`, then pause.
+    pub fn resume_until(&self, abs_ns: u64) {
+        self.step_windows_remaining.store(0, Ordering::Relaxed);
+        self.run_for_ns.store(0, Ordering::Relaxed);
+        self.run_until_abs_ns.store(abs_ns, Ordering::Relaxed);
+        self.unpause();
+    }
+
+    /// Run exactly `windows` more windows, then pause.
+    pub fn step(&self, windows: u64) {
+        self.step_windows_remaining
+            .store(windows, Ordering::Relaxed);
+        self.run_until_abs_ns.store(u64::MAX, Ordering::Relaxed);
+        self.unpause();
+    }
+
+    /// Restart from t=0s (in-process), and optionally run to `run_until_ns` without pausing at
+    /// the start.
+    pub fn request_restart(&self, run_until_ns: Option<u64>) {
+        self.restart_run_until_ns
+            .store(run_until_ns.unwrap_or(u64::MAX), Ordering::Relaxed);
+        self.skip_start_pause
+            .store(run_until_ns.is_some(), Ordering::Relaxed);
+        self.restart_requested.store(true, Ordering::Relaxed);
+        self.wake();
+    }
+
+    /// Print the next-window host/PID info (when paused).
+    pub fn request_info(&self) {
+        self.info_requested.store(true, Ordering::Relaxed);
+        self.wake();
+    }
+
+    /// The simulated time that the simulation has arrived at, and whether it has ended.
+    fn time(&self) -> (u64, bool) {
+        let progress = self.progress.lock().unwrap();
+        (progress.time_ns, progress.ended)
+    }
+
+    /// Record that the simulation has arrived at `time_ns`, and notify any waiting clients.
+    fn arrived_at(&self, time_ns: u64) {
+        let mut progress = self.progress.lock().unwrap();
+        progress.time_ns = time_ns;
+        progress.waiters.retain(|(wait_ns, tx)| {
+            if *wait_ns > time_ns {
+                return true;
+            }
+            let _ = tx.send((time_ns, false));
+            false
+        });
+    }
+
+    /// Record that the simulation has ended at `time_ns`, and notify all waiting clients.
+    pub fn simulation_ended(&self, end_time: EmulatedTime) {
+        let time_ns = ns_since_start(end_time);
+        let mut progress = self.progress.lock().unwrap();
+        progress.time_ns = time_ns;
+        progress.ended = true;
+        for (_, tx) in progress.waiters.drain(..) {
+            let _ = tx.send((time_ns, true));
+        }
+        drop(progress);
+
+        // the host list can no longer be collected, and faults can no longer be added
+        self.host_queries.lock().unwrap().clear();
+        self.fault_requests.lock().unwrap().clear();
+    }
+
+    /// Reply to any clients waiting for the host list or for a fault to be added. `time_ns` is the
+    /// time of the window boundary, or `None` if the simulation is ending.
+    fn answer_queries(&self, time_ns: Option<u64>, sim: &mut impl SimulationAccess) {
+        // add faults first so that the host list reflects them
+        let faults = std::mem::take(&mut *self.fault_requests.lock().unwrap());
+        for (fault, tx) in faults {
+            let rv = match time_ns {
+                Some(time_ns) => self.add_fault(fault, time_ns, sim),
+                None => Err("The simulation is ending".to_string()),
+            };
+            let _ = tx.send(rv);
+        }
+
+        let queries = std::mem::take(&mut *self.host_queries.lock().unwrap());
+        if queries.is_empty() {
+            return;
+        }
+
+        let hosts = sim.host_statuses();
+        for tx in queries {
+            let _ = tx.send(hosts.clone());
+        }
+    }
+    /// Add a network fault at the next window boundary.
+    pub fn fault(&self, fault: NetworkFaultOptions) -> Result<(), String> {
+        let (tx, rx) = mpsc::channel();
+        {
+            let progress = self.progress.lock().unwrap();
+            if progress.ended {
+                return Err("The simulation has ended".to_string());
+            }
+            self.fault_requests.lock().unwrap().push((fault, tx));
+        }
+        self.wake();
+
+        rx.recv()
+            .map_err(|_| "The simulation has ended".to_string())?
+    }
+
+    /// Add a network fault at the window boundary at `time_ns`, and record it so that it's added
+    /// again if the simulation restarts.
+    fn add_fault(
+        &self,
+        fault: NetworkFaultOptions,
+        time_ns: u64,
+        sim: &mut impl SimulationAccess,
+    ) -> Result<(), String> {
+        let fault_ns = Duration::from(fault.time()).as_nanos();
+        if fault_ns < u128::from(time_ns) {
+            return Err(format!(
+                "The fault time t={} is earlier than the next window at t={}",
+                fmt_s(fault_ns.try_into().unwrap()),
+                fmt_s(time_ns),
+            ));
+        }
+
+        sim.add_network_fault(&fault)
+            .map_err(|e| format!("Could not add the network fault: {e:#}"))?;
+        log::info!("Added a network fault from run-control: {fault:?}");
+        self.added_faults.lock().unwrap().push(fault);
+        Ok(())
+    }
+
+    /// Add the network faults that were previously added through run-control to the restarted
+    /// simulation.
+    fn add_previous_faults(&self, sim: &mut impl SimulationAccess) {
+        for fault in self.added_faults.lock().unwrap().iter() {
+            if let Err(e) = sim.add_network_fault(fault) {
+                log::warn!("Could not add the network fault {fault:?} after restarting: {e:#}");
+            }
+        }
+    }
+
+    fn handle_request(&self, request: Request) -> Result<serde_json::Value, String> {
+        match request {
+            Request::Pause => self.request_pause(),
+            Request::Continue {
+                for_ns: None,
+                until_ns: None,
+            } => self.resume(),
+            Request::Continue {
+                for_ns: Some(ns),
+                until_ns: None,
+            } => self.resume_for(ns),
+            Request::Continue {
+                for_ns: None,
+                until_ns: Some(ns),
+            } => self.resume_until(ns),
+            Request::Continue { .. } => {
+                return Err("Only one of 'for_ns' and 'until_ns' can be given".to_string());
+            }
+            Request::Step { windows } => {
+                let windows = windows.unwrap_or(1);
+                if windows == 0 {
+                    return Err("'windows' must be greater than 0".to_string());
+                }
+                self.step(windows);
+            }
+            Request::Restart { run_until_ns } => self.request_restart(run_until_ns),
+            Request::Time => {
+                let (time_ns, ended) = self.time();
+                let paused = *self.paused.lock().unwrap();
+                return Ok(serde_json::json!({
+                    "ok": true,
+                    "time_ns": time_ns,
+                    "paused": paused,
+                    "ended": ended,
+                }));
+            }
+            Request::Hosts => {
+                let (tx, rx) = mpsc::channel();
+                {
+                    // hold the progress lock so that the simulation can't end before we've queued
+                    // the query
+                    let progress = self.progress.lock().unwrap();
+                    if progress.ended {
+                        return Err("The simulation has ended".to_string());
+                    }
+                    self.host_queries.lock().unwrap().push(tx);
+                }
+                self.wake();
+
+                let hosts = rx
+                    .recv()
+                    .map_err(|_| "The simulation has ended".to_string())?;
+                return Ok(serde_json::json!({"ok": true, "hosts": hosts}));
+            }
+            Request::Wait { time_ns } => {
+                let (tx, rx) = mpsc::channel();
+                {
+                    let mut progress = self.progress.lock().unwrap();
+                    if progress.ended || progress.time_ns >= time_ns {
+                        return Ok(progress_response(progress.time_ns, progress.ended));
+                    }
+                    progress.waiters.push((time_ns, tx));
+                }
+
+                let (time_ns, ended) = rx
+                    .recv()
+                    .map_err(|_| "The simulation has ended".to_string())?;
+                return Ok(progress_response(time_ns, ended));
+            }
+            Request::Fault { fault } => self.fault(fault)?,
+        }
+
+        Ok(serde_json::json!({"ok": true}))
+    }
+}
+
+fn progress_response(time_ns: u64, ended: bool) -> serde_json::Value {
+    let event = if ended { "ended" } else { "arrived" };
+    serde_json::json!({"ok": true, "event": event, "time_ns": time_ns})
+}
+
+/// A request received on the run-control socket.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case", deny_unknown_fields)]
+enum Request {
+    Pause,
+    Continue {
+        for_ns: Option<u64>,
+        until_ns: Option<u64>,
+    },
+    Step {
+        windows: Option<u64>,
+    },
+    Restart {
+        run_until_ns: Option<u64>,
+    },
+    Time,
+    Hosts,
+    Wait {
+        time_ns: u64,
+    },
+    Fault {
+        fault: NetworkFaultOptions,
+    },
+}
+
+pub fn init_and_reset_run_control() -> &'static RunControl {
+    let rc = RunControl::global();
+
+    // Clear any prior run-control state (important for in-process restarts).
+    rc.pause_requested.store(false, Ordering::Relaxed);
+    rc.restart_requested.store(false, Ordering::Relaxed);
+    rc.info_requested.store(false, Ordering::Relaxed);
+    rc.run_for_ns.store(0, Ordering::Relaxed);
+    rc.run_until_abs_ns.store(u64::MAX, Ordering::Relaxed);
+    rc.step_windows_remaining.store(0, Ordering::Relaxed);
+    rc.skip_start_pause.store(false, Ordering::Relaxed);
+    rc.restart_run_until_ns.store(u64::MAX, Ordering::Relaxed);
+
+    // Clients that are waiting for a time will be notified when the restarted simulation arrives
+    // at that time.
+    {
+        let mut progress = rc.progress.lock().unwrap();
+        progress.time_ns = 0;
+        progress.ended = false;
+    }
+
+    let pending = RESTART_RUN_UNTIL_NS.swap(u64::MAX, Ordering::Relaxed);
+    if pending != u64::MAX {
+        rc.run_until_abs_ns.store(pending, Ordering::Relaxed);
+        rc.skip_start_pause.store(true, Ordering::Relaxed);
+    }
+
+    rc
+}
+
+pub fn spawn_run_control_stdin_thread_once(rc: &'static RunControl) {
+    RUN_CONTROL_STDIN_THREAD_STARTED.get_or_init(|| {
+        if !std::io::stdin().is_terminal() {
+            return;
+        }
+
+        std::thread::spawn(move || {
+            // Print help once (times are in *simulated seconds*).
+            eprintln!(
+                "\
+** Shadow run-control (stdin; simulated time)\n\
+**   p<Enter>: pause at next window boundary\n\
+**   c<Enter>: continue\n\
+**   cN<Enter>: continue for N simulated seconds, then pause at next window boundary (e.g. c10)\n\
+**   n<Enter>: run exactly one window, then pause\n\
+**   s<Enter>: show next-window hosts/PIDs (when paused)\n\
+**   s:<pid><Enter>: print gdb attach command (e.g. s:12345)\n\
+**   info<Enter>: show next-window hosts/PIDs (when paused)\n\
+**   r<Enter>: restart from t=0s (in-process)\n\
+**   rN<Enter>: restart and run to N seconds (e.g. r10)\n"
+            );
+
+            let stdin = std::io::stdin();
+            for line in stdin.lock().lines().flatten() {
+                let cmd = line.trim();
+                if cmd.is_empty() {
+                    continue;
+                }
+
+                if cmd == "p" {
+                    rc.request_pause();
+                    eprintln!("** run-control: pause requested (will pause at next window boundary)");
+                    continue;
+                }
+
+                if cmd == "n" {
+                    rc.step(1);
+                    eprintln!("** run-control: will run 1 window and then pause");
+                    continue;
+                }
+
+                if cmd == "r" {
+                    rc.request_restart(None);
+                    eprintln!("** run-control: restart requested (in-process)");
+                    continue;
+                }
+
+                if let Some(rest) = cmd.strip_prefix('r') {
+                    if !rest.is_empty() {
+                        if let Ok(secs) = rest.parse::<u64>() {
+                            rc.request_restart(Some(secs.saturating_mul(1_000_000_000)));
+                            eprintln!("** run-control: restart requested (run to t={secs}s)");
+                            continue;
+                        }
+                    }
+                }
+
+                if cmd == "s" || cmd == "info" {
+                    rc.request_info();
+                    eprintln!("** run-control: info requested (will print while paused)");
+                    continue;
+                }
+
+                if let Some(rest) = cmd.strip_prefix("s:") {
+                    // s:<pid> - attach gdb to the specified PID (manual, no GUI)
+                    if let Ok(pid) = rest.parse::<i32>() {
+                        eprintln!("** run-control: attach gdb manually with: gdb/dlv -p/attach {}", pid);
+                        continue;
+                    } else {
+                        eprintln!("** run-control: invalid PID: '{}'", rest);
+                        continue;
+                    }
+                }
+
+                if cmd == "c" {
+                    // Continue indefinitely.
+                    rc.resume();
+                    eprintln!("** run-control: continue");
+                    continue;
+                }
+
+                if let Some(rest) = cmd.strip_prefix('c') {
+                    // cN: run for N simulated seconds, then pause.
+                    if let Ok(secs) = rest.parse::<u64>() {
+                        rc.resume_for(secs.saturating_mul(1_000_000_000));
+                        eprintln!(
+                            "** run-control: continue for {secs}s simulated time (will pause at a window boundary)"
+                        );
+                        continue;
+                    }
+                }
+
+                eprintln!(
+                    "** Unknown command: '{cmd}'. Use: p | c | cN (e.g. c10) | n | s | s:<pid> | info | r | rN (e.g. r10)"
+                );
+            }
+        });
+    });
+}
+
+/// Serve the run-control API on a Unix socket at `path`. A stale socket at `path` (for example
+/// from a previous run) is replaced. The simulation will pause at the start until a client resumes
+/// it.
+pub fn spawn_run_control_socket_thread(path: &Path) -> std::io::Result<()> {
+    if let Ok(metadata) = std::fs::symlink_metadata(path)
+        && metadata.file_type().is_socket()
+    {
+        std::fs::remove_file(path)?;
+    }
+
+    let listener = UnixListener::bind(path)?;
+    let rc = RunControl::global();
+    rc.socket_enabled.store(true, Ordering::Relaxed);
+
+    std::thread::Builder::new()
+        .name("run-control".into())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    // each client gets its own thread since some requests block
+                    Ok(stream) => {
+                        std::thread::spawn(move || serve_run_control_client(rc, stream));
+                    }
+                    Err(e) => log::warn!("Failed to accept run-control connection: {e}"),
+                }
+            }
+        })?;
+
+    Ok(())
+}
+
+fn serve_run_control_client(rc: &'static RunControl, stream: UnixStream) {
+    let mut writer = &stream;
+    for line in BufReader::new(&stream).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = serde_json::from_str::<Request>(&line)
+            .map_err(|e| format!("Invalid request: {e}"))
+            .and_then(|request| {
+                log::debug!("run-control request: {request:?}");
+                rc.handle_request(request)
+            })
+            .unwrap_or_else(|e| serde_json::json!({"ok": false, "error": e}));
+
+        if writeln!(writer, "{response}").is_err() {
+            break;
+        }
+    }
+}
+
+pub fn auto_pause_at_start(rc: &'static RunControl) {
+    // Auto-pause at simulated time 0s (before running the first window), but only when running
+    // interactively (stdin is a TTY) or when a run-control socket is being served. This avoids
+    // hanging non-interactive runs.
+    if std::io::stdin().is_terminal() || rc.socket_enabled.load(Ordering::Relaxed) {
+        let mut paused = rc.paused.lock().unwrap();
+        let skip_start_pause = rc.skip_start_pause.load(Ordering::Relaxed);
+        rc.skip_start_pause.store(false, Ordering::Relaxed);
+        if !*paused && !skip_start_pause {
+            *paused = true;
+            eprintln!(
+                "\
+** Shadow paused at start (t=0s)\n\
+** Commands: c | cN (e.g. c10) | n | info | s:<pid> | r | rN"
+            );
+        }
+    }
+}
+
+pub fn wait_if_paused_at_start(rc: &'static RunControl, sim: &mut impl SimulationAccess) {
+    rc.add_previous_faults(sim);
+
+    // If we started in paused mode, block here before running the first window.
+    let mut paused = rc.paused.lock().unwrap();
+    rc.arrived_at(0);
+    rc.answer_queries(Some(0), sim);
+    while *paused {
+        paused = rc.cv.wait(paused).unwrap();
+        rc.answer_queries(Some(0), sim);
+    }
+}
+
+pub fn apply_run_control_requests_at_window_boundary(
+    rc: &'static RunControl,
+    min_next_event_time: EmulatedTime,
+    restart_request: &mut Option<u64>,
+) {
+    // Apply cN request: convert relative duration -> absolute pause target.
+    let run_for = rc.run_for_ns.swap(0, Ordering::Relaxed);
+    if run_for != 0 {
+        let next_abs_ns = ns_since_start(min_next_event_time);
+        let target = next_abs_ns.saturating_add(run_for);
+        rc.run_until_abs_ns.store(target, Ordering::Relaxed);
+        eprintln!(
+            "** run-control: will pause at ~t={} (after +{} simulated seconds; next window boundary >= target)",
+            fmt_s(target),
+            run_for / 1_000_000_000
+        );
+    }
+
+    // Apply n request: count down windows.
+    let steps_left = rc.step_windows_remaining.load(Ordering::Relaxed);
+    if steps_left > 0 {
+        // Decrement once per window boundary.
+        let new = rc.step_windows_remaining.fetch_sub(1, Ordering::Relaxed) - 1;
+        if new == 0 {
+            rc.pause_requested.store(true, Ordering::Relaxed);
+        }
+    }
+
+    // Auto-pause if we've reached run-until time.
+    let run_until = rc.run_until_abs_ns.load(Ordering::Relaxed);
+    if run_until != u64::MAX {
+        let next_abs_ns = ns_since_start(min_next_event_time);
+        if next_abs_ns >= run_until {
+            // Clear so we only pause once.
+            rc.run_until_abs_ns.store(u64::MAX, Ordering::Relaxed);
+            rc.pause_requested.store(true, Ordering::Relaxed);
+        }
+    }
+
+    if rc.restart_requested.swap(false, Ordering::Relaxed) {
+        let run_until = rc.restart_run_until_ns.load(Ordering::Relaxed);
+        *restart_request = Some(run_until);
+        eprintln!("** run-control: restart requested (in-process)");
+    }
+}
+
+fn print_next_window_info(next_window: Option<(EmulatedTime, EmulatedTime)>, hosts: &[HostStatus]) {
+    let Some((next_window_start, next_window_end)) = next_window else {
+        eprintln!("** No next window (simulation ending)");
+        return;
+    };
+
+    let next_window_end_ns = ns_since_start(next_window_end);
+    let mut hosts: Vec<_> = hosts
+        .iter()
+        .filter(|host| host.next_event_ns.is_some_and(|t| t < next_window_end_ns))
+        .collect();
+    if hosts.is_empty() {
+        eprintln!("** No hosts scheduled in next window");
+        return;
+    }
+    hosts.sort_by_key(|host| host.id);
+
+    eprintln!("**");
+    eprintln!(
+        "** Next window: t=[{}, {}]",
+        fmt_s(ns_since_start(next_window_start)),
+        fmt_s(next_window_end_ns)
+    );
+    eprintln!("** Hosts scheduled for next window:");
+    for host in hosts {
+        eprintln!(
+            "**   Host {:?} ({}) - next event at t={}",
+            host.id,
+            host.name,
+            fmt_s(host.next_event_ns.unwrap())
+        );
+        if host.pids.is_empty() {
+            eprintln!("**     <no running processes>");
+        } else {
+            for pid in &host.pids {
+                eprintln!("**     pid={pid} (attach: s:{pid})");
+            }
+        }
+    }
+}
+
+/// Pause if requested and block (soft pause) until resumed. `next_window` is the next window to
+/// run.
+pub fn pause_and_soft_wait_until_resumed(
+    rc: &'static RunControl,
+    min_next_event_time: EmulatedTime,
+    next_window: Option<(EmulatedTime, EmulatedTime)>,
+    restart_request: &mut Option<u64>,
+    sim: &mut impl SimulationAccess,
+) {
+    let mut paused = rc.paused.lock().unwrap();
+
+    if rc.pause_requested.swap(false, Ordering::Relaxed) {
+        *paused = true;
+
+        eprintln!(
+            "\
+** Shadow paused at window boundary\n\
+**   next window start: t={}",
+            fmt_s(ns_since_start(min_next_event_time)),
+        );
+
+        print_next_window_info(next_window, &sim.host_statuses());
+
+        eprintln!("**");
+        eprintln!("** To attach gdb: s:<pid> (e.g. s:12345)");
+        eprintln!("** Commands: c | cN (e.g. c10) | n | p | s | s:<pid> | info | r | rN");
+    }
+
+    // Now that we've paused (if requested), notify clients waiting for this time. The end of the
+    // simulation is reported separately.
+    let time_ns = next_window.map(|(next_window_start, _)| ns_since_start(next_window_start));
+    if let Some(time_ns) = time_ns {
+        rc.arrived_at(time_ns);
+    }
+    rc.answer_queries(time_ns, sim);
+
+    // If paused, block here (soft pause) until resumed.
+    while *paused {
+        if rc.restart_requested.swap(false, Ordering::Relaxed) {
+            let run_until = rc.restart_run_until_ns.load(Ordering::Relaxed);
+            *restart_request = Some(run_until);
+            *paused = false;
+            break;
+        }
+
+        if rc.info_requested.swap(false, Ordering::Relaxed) {
+            print_next_window_info(next_window, &sim.host_statuses());
+        }
+
+        paused = rc.cv.wait(paused).unwrap();
+        rc.answer_queries(time_ns, sim);
+    }
+}
@@ -56,6 +56,12 @@ impl Runahead {
         std::cmp::max(runahead, runahead_config)
     }
 
+    /// The lowest packet latency that has been used so far in the simulation, if dynamic runahead
+    /// is enabled.
+    pub fn min_used_latency(&self) -> Option<SimulationTime> {
+        *self.min_used_latency.read().unwrap()
+    }
+
     /// If dynamic runahead is enabled, will compare and update the stored lowest packet latency.
     /// This may shorten the runahead for future rounds.
     pub fn update_lowest_used_latency(&self, latency: SimulationTime) {
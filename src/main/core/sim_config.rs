@@ -8,20 +8,28 @@ use std::collections::{BTreeMap, HashMap, HashSet};
 use std::ffi::{OsStr, OsString};
 use std::hash::{Hash, Hasher};
 use std::path::PathBuf;
-use std::sync::RwLock;
+use std::sync::{Arc, RwLock};
 use std::time::Duration;
 
 use anyhow::Context;
 use once_cell::sync::Lazy;
 use rand::{Rng, SeedableRng};
 use rand_xoshiro::Xoshiro256PlusPlus;
+use shadow_shim_helper_rs::emulated_time::EmulatedTime;
 use shadow_shim_helper_rs::simulation_time::SimulationTime;
 
 use crate::core::configuration::{
-    ConfigOptions, EnvName, Flatten, HostOptions, LogLevel, ProcessArgs, ProcessFinalState,
-    ProcessOptions, QDiscMode, parse_string_as_args,
+    ConfigOptions, EnvName, Flatten, HostClockOptions, HostName, HostOptions, LogLevel,
+    ProcessArgs, ProcessFinalState, ProcessOptions, QDiscMode, QDiscOptions, StartConditionOptions,
+    TcpCongestionControl, parse_string_as_args,
+};
+use crate::core::start_conditions::StartCondition;
+use crate::host::syscall::formatter::StraceFormat;
+use crate::network::capture::PacketFilter;
+use crate::network::faults::{BandwidthChange, NetworkFaults};
+use crate::network::graph::{
+    IpAssignment, Link, LinkProperties, NetworkGraph, RoutingInfo, load_network_graph,
 };
-use crate::network::graph::{IpAssignment, NetworkGraph, RoutingInfo, load_network_graph};
 use crate::utility::units::{self, Unit};
 use crate::utility::{tilde_expansion, verify_plugin_path};
 
@@ -36,6 +44,12 @@ pub struct SimConfig {
     // routing information for paths between graph nodes
     pub routing_info: RoutingInfo<u32>,
 
+    // the links in the network graph, which are the edges that have a bandwidth
+    pub links: Vec<(Link, LinkProperties)>,
+
+    // the network graph and changes to it during the simulation
+    pub network_faults: NetworkFaults,
+
     // bandwidths of hosts at ip addresses
     pub host_bandwidths: HashMap<std::net::IpAddr, Bandwidth>,
 
@@ -55,7 +69,7 @@ impl SimConfig {
 
         // build the host list
         let mut hosts = vec![];
-        for (name, host_options) in &config.hosts {
+        for (name, host_options) in &expand_hosts(&config.hosts)? {
             let new_host = build_host(
                 config,
                 host_options,
@@ -72,13 +86,34 @@ impl SimConfig {
             ));
         }
 
+        let start_after: Vec<_> = hosts
+            .iter()
+            .map(|host| {
+                let procs = host.processes.iter().map(|x| x.start_after.as_slice());
+                (host.name.as_str(), procs.collect())
+            })
+            .collect();
+        check_start_conditions(&start_after).context("Invalid process start conditions")?;
+
+        if config.metrics_interval().is_some_and(|x| x.is_zero()) {
+            return Err(anyhow::anyhow!(
+                "The network metrics interval must be greater than 0"
+            ));
+        }
+
+        let strace_json = config
+            .strace_logging_mode()
+            .is_some_and(|x| x.format == StraceFormat::Json);
+        if config.experimental.strace_logging_merged.unwrap() && !strace_json {
+            return Err(anyhow::anyhow!(
+                "The merged strace log requires the 'json' strace logging mode"
+            ));
+        }
+
         // load and parse the network graph
-        let graph: String = load_network_graph(config.network.graph.as_ref().unwrap())
+        let graph = load_network_graph(config.network.graph.as_ref().unwrap())
             .map_err(|e| anyhow::anyhow!(e))
             .context("Failed to load the network graph")?;
-        let graph = NetworkGraph::parse(&graph)
-            .map_err(|e| anyhow::anyhow!(e))
-            .context("Failed to parse the network graph")?;
 
         // check that each node ID is valid
         for host in &hosts {
@@ -133,6 +168,21 @@ impl SimConfig {
         // assign IP addresses to hosts and graph nodes
         let ip_assignment = assign_ips(&mut hosts)?;
 
+        // hostnames in the pcap filter are resolved to both of the host's addresses
+        if let Some(filter) = config.general.pcap_filter.flatten_ref() {
+            let filter = PacketFilter::parse(filter, |name| {
+                let host = hosts.iter().find(|x| x.name == name)?;
+                Some(vec![host.ip_addr.unwrap(), host.ipv6_addr.unwrap()])
+            })
+            .map_err(|e| anyhow::anyhow!(e))
+            .context("Invalid pcap filter")?;
+
+            let filter = Arc::new(filter);
+            for pcap in hosts.iter_mut().filter_map(|x| x.pcap_config.as_mut()) {
+                pcap.filter = Some(Arc::clone(&filter));
+            }
+        }
+
         // generate routing info between every pair of in-use nodes
         let routing_info = generate_routing_info(
             &graph,
@@ -140,6 +190,20 @@ impl SimConfig {
             config.network.use_shortest_path.unwrap(),
         )?;
 
+        let links = graph.links();
+
+        // apply the network faults, which may change the routing info and host bandwidths
+        let network_faults = build_network_faults(config, graph, &ip_assignment, &mut hosts)
+            .context("Failed to configure the network faults")?;
+        routing_info.set_changed_paths_from(
+            EmulatedTime::SIMULATION_START,
+            network_faults.changed_paths_from(EmulatedTime::SIMULATION_START),
+        );
+        routing_info.set_changed_links_from(
+            EmulatedTime::SIMULATION_START,
+            network_faults.changed_links_from(EmulatedTime::SIMULATION_START),
+        );
+
         // get all host bandwidths
         let host_bandwidths = hosts
             .iter()
@@ -158,6 +222,8 @@ impl SimConfig {
             random,
             ip_assignment,
             routing_info,
+            links,
+            network_faults,
             host_bandwidths,
             hosts,
         })
@@ -176,6 +242,7 @@ pub struct HostInfo {
     pub bandwidth_down_bits: Option<u64>,
     pub bandwidth_up_bits: Option<u64>,
     pub ip_addr: Option<std::net::IpAddr>,
+    pub ipv6_addr: Option<std::net::IpAddr>,
     pub log_level: Option<LogLevel>,
     pub pcap_config: Option<PcapConfig>,
     pub send_buf_size: u64,
@@ -183,6 +250,60 @@ pub struct HostInfo {
     pub autotune_send_buf: bool,
     pub autotune_recv_buf: bool,
     pub qdisc: QDiscMode,
+    pub packet_qdisc: QDiscConfig,
+    pub tcp_congestion_control: TcpCongestionControl,
+    pub clock: ClockConfig,
+    /// Changes to the host's bandwidth, sorted by time.
+    pub bandwidth_changes: Vec<BandwidthChange>,
+}
+
+/// Skew of a host's clocks relative to the simulation time.
+#[derive(Debug, Clone, Default)]
+pub struct ClockConfig {
+    pub realtime_offset_nanos: i64,
+    pub drift_ppb: i64,
+    /// Step changes of the realtime clock, sorted by time.
+    pub steps: Vec<ClockStep>,
+}
+
+/// The queueing discipline for packets sent from a host's network interface. Byte values are in
+/// bytes, and limits without a unit are in packets.
+#[derive(Debug, Clone, Copy)]
+pub enum QDiscConfig {
+    Noqueue,
+    Pfifo {
+        limit: usize,
+        limit_bytes: Option<usize>,
+    },
+    Red {
+        limit_bytes: usize,
+        min_bytes: usize,
+        max_bytes: usize,
+        probability: f64,
+        weight: f64,
+    },
+    Codel {
+        limit: usize,
+        codel: CoDelConfig,
+    },
+    FqCodel {
+        limit: usize,
+        codel: CoDelConfig,
+        flows: usize,
+        quantum_bytes: usize,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CoDelConfig {
+    pub target: SimulationTime,
+    pub interval: SimulationTime,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ClockStep {
+    pub time: SimulationTime,
+    pub offset_nanos: i64,
 }
 
 #[derive(Clone)]
@@ -194,6 +315,9 @@ pub struct ProcessInfo {
     pub args: Vec<OsString>,
     pub env: BTreeMap<EnvName, String>,
     pub expected_final_state: ProcessFinalState,
+    /// Conditions that must be met before the process is started, and the names of the hosts
+    /// they're about.
+    pub start_after: Vec<(String, StartCondition)>,
 }
 
 #[derive(Debug, Clone)]
@@ -202,9 +326,169 @@ pub struct Bandwidth {
     pub down_bytes: u64,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct PcapConfig {
     pub capture_size: u64,
+    /// Capture to the simulation's merged pcapng file rather than a pcap file per interface.
+    pub merged: bool,
+    pub filter: Option<Arc<PacketFilter>>,
+}
+
+/// Expand the host entries that have a `quantity` into one entry per host, and replace the
+/// templates in the process paths, arguments and environment variables of those hosts. Other hosts
+/// are left as they are, so their processes can use `{{` in their arguments. Every host is given an
+/// IPv4 address so that it can be used in templates, which is the same address that `assign_ips`
+/// would have given it.
+pub fn expand_hosts(
+    hosts: &BTreeMap<HostName, HostOptions>,
+) -> anyhow::Result<BTreeMap<HostName, HostOptions>> {
+    let mut expanded = BTreeMap::new();
+    // the names of the hosts created from each entry that has a quantity
+    let mut groups = Vec::new();
+
+    for (group, host) in hosts {
+        let names: Vec<HostName> = match host.quantity {
+            None => vec![group.clone()],
+            Some(quantity) => {
+                if quantity.get() > 1 && (host.ip_addr.is_some() || host.ipv6_addr.is_some()) {
+                    return Err(anyhow::anyhow!(
+                        "Host '{group}' has a quantity of {quantity}, so it can't have an IP \
+                         address"
+                    ));
+                }
+                (1..=quantity.get())
+                    .map(|i| HostName::new(format!("{group}{i}")).map_err(|e| anyhow::anyhow!(e)))
+                    .collect::<anyhow::Result<_>>()
+                    .with_context(|| format!("Failed to name the hosts of '{group}'"))?
+            }
+        };
+
+        for name in &names {
+            let mut host = host.clone();
+            host.quantity = None;
+            if expanded.insert(name.clone(), host).is_some() {
+                return Err(anyhow::anyhow!(
+                    "The host '{name}' created from '{group}' already exists"
+                ));
+            }
+        }
+        if host.quantity.is_some() {
+            groups.push(names);
+        }
+    }
+
+    // this follows `assign_ips`, which assigns the dynamic addresses in order of hostname after
+    // the static addresses
+    let mut ip_assignment = IpAssignment::new();
+    for (name, host) in &expanded {
+        if let Some(ip) = host.ip_addr {
+            let node_id = host.network_node_id;
+            ip_assignment
+                .assign_ip(node_id, ip.into())
+                .with_context(|| {
+                    format!(
+                        "Failed to assign IP address {ip} for host '{name}' to node '{node_id}'"
+                    )
+                })?;
+        }
+    }
+    for host in expanded.values_mut().filter(|x| x.ip_addr.is_none()) {
+        let std::net::IpAddr::V4(ip) = ip_assignment.assign(host.network_node_id) else {
+            unreachable!();
+        };
+        host.ip_addr = Some(ip);
+    }
+
+    for names in &groups {
+        let ips: Vec<_> = names.iter().map(|x| expanded[x].ip_addr.unwrap()).collect();
+
+        for (i, name) in names.iter().enumerate() {
+            let fields = TemplateFields {
+                index: i + 1,
+                hostname: name,
+                ip: ips[i],
+                peer_ips: &ips
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, _)| *j != i)
+                    .map(|(_, ip)| ip.to_string())
+                    .collect::<Vec<_>>()
+                    .join(","),
+            };
+
+            let host = expanded.get_mut(name).unwrap();
+            for proc in &mut host.processes {
+                expand_process(proc, &fields)
+                    .with_context(|| format!("Failed to expand the templates of host '{name}'"))?;
+            }
+        }
+    }
+
+    Ok(expanded)
+}
+
+/// The values of the fields that can be used in templates.
+struct TemplateFields<'a> {
+    index: usize,
+    hostname: &'a str,
+    ip: std::net::Ipv4Addr,
+    /// The comma-separated addresses of the other hosts created from the same entry.
+    peer_ips: &'a str,
+}
+
+fn expand_process(proc: &mut ProcessOptions, fields: &TemplateFields) -> anyhow::Result<()> {
+    let path = proc
+        .path
+        .to_str()
+        .with_context(|| format!("The process path {:?} isn't valid UTF-8", proc.path))?;
+    proc.path = expand_template(path, fields)?.into();
+
+    match &mut proc.args {
+        ProcessArgs::List(args) => {
+            for arg in args {
+                *arg = expand_template(arg, fields)?;
+            }
+        }
+        ProcessArgs::Str(args) => *args = expand_template(args, fields)?,
+    }
+
+    for value in proc.environment.values_mut() {
+        *value = expand_template(value, fields)?;
+    }
+
+    Ok(())
+}
+
+/// Replace each `{{field}}` in `text` with the field's value, and each `{{{{` with `{{`.
+fn expand_template(text: &str, fields: &TemplateFields) -> anyhow::Result<String> {
+    let mut expanded = String::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find("{{") {
+        expanded.push_str(&rest[..start]);
+        rest = &rest[start + 2..];
+
+        if let Some(escaped) = rest.strip_prefix("{{") {
+            expanded.push_str("{{");
+            rest = escaped;
+            continue;
+        }
+
+        let Some(end) = rest.find("}}") else {
+            return Err(anyhow::anyhow!("Unterminated template in '{text}'"));
+        };
+        match rest[..end].trim() {
+            "index" => expanded.push_str(&fields.index.to_string()),
+            "hostname" => expanded.push_str(fields.hostname),
+            "ip" => expanded.push_str(&fields.ip.to_string()),
+            "peer_ips" => expanded.push_str(fields.peer_ips),
+            x => return Err(anyhow::anyhow!("Unknown template field '{x}' in '{text}'")),
+        }
+        rest = &rest[end + 2..];
+    }
+
+    expanded.push_str(rest);
+    Ok(expanded)
 }
 
 /// For a host entry in the configuration options, build `HostInfo` object.
@@ -230,11 +514,16 @@ fn build_host(
         .processes
         .iter()
         .map(|proc| {
-            build_process(proc, config)
+            build_process(proc, &hostname, config)
                 .with_context(|| format!("Failed to configure process '{}'", proc.path.display()))
         })
         .collect::<anyhow::Result<_>>()?;
 
+    let clock = build_clock(&host.clock, config).context("Failed to configure host clock")?;
+
+    let packet_qdisc = build_qdisc(host.host_options.qdisc.as_ref().unwrap())
+        .context("Failed to configure host qdisc")?;
+
     Ok(HostInfo {
         name: hostname,
         processes,
@@ -254,6 +543,7 @@ fn build_host(
             .map(|x| x.convert(units::SiPrefixUpper::Base).unwrap().value()),
 
         ip_addr: host.ip_addr.map(|x| x.into()),
+        ipv6_addr: host.ipv6_addr.map(|x| x.into()),
         log_level: host.host_options.log_level.flatten(),
         pcap_config: host
             .host_options
@@ -267,6 +557,9 @@ fn build_host(
                     .convert(units::SiPrefixUpper::Base)
                     .unwrap()
                     .value(),
+                merged: config.general.pcap_merged.unwrap(),
+                // set once every host has an address
+                filter: None,
             }),
 
         // some options come from the config options and not the host options
@@ -287,11 +580,154 @@ fn build_host(
         autotune_send_buf: config.experimental.socket_send_autotune.unwrap(),
         autotune_recv_buf: config.experimental.socket_recv_autotune.unwrap(),
         qdisc: config.experimental.interface_qdisc.unwrap(),
+        packet_qdisc,
+        tcp_congestion_control: host.host_options.tcp_congestion_control.unwrap(),
+        clock,
+        // set after the network graph is loaded
+        bandwidth_changes: Vec::new(),
+    })
+}
+
+/// For the clock options of a host entry in the configuration options, build a `ClockConfig`
+/// object.
+fn build_clock(clock: &HostClockOptions, config: &ConfigOptions) -> anyhow::Result<ClockConfig> {
+    let signed_nanos = |x: units::SignedTime<units::TimePrefix>| {
+        x.convert(units::TimePrefix::Nano)
+            .map(|x| x.value())
+            .map_err(|e| anyhow::anyhow!("Invalid clock offset '{x}': {e}"))
+    };
+
+    // a drift of -1e6 ppm or less would stop the clock or make it run backwards
+    if !clock.drift_ppm.is_finite() || clock.drift_ppm.abs() >= 1_000_000.0 {
+        return Err(anyhow::anyhow!(
+            "Clock drift '{}' must be in the range (-1000000, 1000000) ppm",
+            clock.drift_ppm,
+        ));
+    }
+
+    let sim_stop_time =
+        SimulationTime::try_from(Duration::from(config.general.stop_time.unwrap())).unwrap();
+
+    let mut steps = clock
+        .steps
+        .iter()
+        .map(|step| {
+            let time = SimulationTime::try_from(Duration::from(step.time)).unwrap();
+            if time >= sim_stop_time {
+                return Err(anyhow::anyhow!(
+                    "Clock step time '{}' must be earlier than the simulation stop time '{}'",
+                    step.time,
+                    config.general.stop_time.unwrap(),
+                ));
+            }
+            Ok(ClockStep {
+                time,
+                offset_nanos: signed_nanos(step.offset)?,
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    steps.sort_by_key(|step| step.time);
+
+    Ok(ClockConfig {
+        realtime_offset_nanos: signed_nanos(clock.offset)?,
+        drift_ppb: (clock.drift_ppm * 1000.0).round() as i64,
+        steps,
     })
 }
 
+/// For the qdisc options of a host entry in the configuration options, build a `QDiscConfig`
+/// object.
+fn build_qdisc(qdisc: &QDiscOptions) -> anyhow::Result<QDiscConfig> {
+    let bytes = |x: units::Bytes<units::SiPrefixUpper>| {
+        usize::try_from(x.convert(units::SiPrefixUpper::Base).unwrap().value()).unwrap()
+    };
+    let time =
+        |x: units::Time<units::TimePrefix>| SimulationTime::try_from(Duration::from(x)).unwrap();
+    let nonzero = |name: &str, x: usize| {
+        if x == 0 {
+            return Err(anyhow::anyhow!("The qdisc {name} must be greater than 0"));
+        }
+        Ok(x)
+    };
+
+    Ok(match *qdisc {
+        QDiscOptions::Noqueue => QDiscConfig::Noqueue,
+        QDiscOptions::Pfifo { limit, limit_bytes } => QDiscConfig::Pfifo {
+            limit: nonzero("limit", limit.try_into().unwrap())?,
+            limit_bytes: limit_bytes
+                .map(|x| nonzero("limit_bytes", bytes(x)))
+                .transpose()?,
+        },
+        QDiscOptions::Red {
+            limit,
+            min,
+            max,
+            probability,
+            weight,
+        } => {
+            let (limit_bytes, min_bytes, max_bytes) = (bytes(limit), bytes(min), bytes(max));
+            if !(min_bytes < max_bytes && max_bytes <= limit_bytes) {
+                return Err(anyhow::anyhow!(
+                    "The red qdisc requires min < max <= limit, but min={min}, max={max}, \
+                    and limit={limit}",
+                ));
+            }
+            if !(probability > 0.0 && probability <= 1.0) {
+                return Err(anyhow::anyhow!(
+                    "The red qdisc probability '{probability}' must be in the range (0, 1]",
+                ));
+            }
+            if !(weight > 0.0 && weight <= 1.0) {
+                return Err(anyhow::anyhow!(
+                    "The red qdisc weight '{weight}' must be in the range (0, 1]",
+                ));
+            }
+            QDiscConfig::Red {
+                limit_bytes,
+                min_bytes,
+                max_bytes,
+                probability,
+                weight,
+            }
+        }
+        QDiscOptions::Codel {
+            limit,
+            target,
+            interval,
+        } => QDiscConfig::Codel {
+            limit: nonzero("limit", limit.try_into().unwrap())?,
+            codel: build_codel(time(target), time(interval))?,
+        },
+        QDiscOptions::FqCodel {
+            limit,
+            target,
+            interval,
+            flows,
+            quantum,
+        } => QDiscConfig::FqCodel {
+            limit: nonzero("limit", limit.try_into().unwrap())?,
+            codel: build_codel(time(target), time(interval))?,
+            flows: nonzero("flows", flows.try_into().unwrap())?,
+            quantum_bytes: nonzero("quantum", bytes(quantum))?,
+        },
+    })
+}
+
+fn build_codel(target: SimulationTime, interval: SimulationTime) -> anyhow::Result<CoDelConfig> {
+    if target.is_zero() || interval.is_zero() {
+        return Err(anyhow::anyhow!(
+            "The qdisc target and interval must be greater than 0"
+        ));
+    }
+    Ok(CoDelConfig { target, interval })
+}
+
 /// For a process entry in the configuration options, build a `ProcessInfo` object.
-fn build_process(proc: &ProcessOptions, config: &ConfigOptions) -> anyhow::Result<ProcessInfo> {
+fn build_process(
+    proc: &ProcessOptions,
+    hostname: &str,
+    config: &ConfigOptions,
+) -> anyhow::Result<ProcessInfo> {
     let start_time = Duration::from(proc.start_time).try_into().unwrap();
     let shutdown_time = proc
         .shutdown_time
@@ -370,6 +806,12 @@ fn build_process(proc: &ProcessOptions, config: &ConfigOptions) -> anyhow::Resul
     // set argv[0] as the user-provided expanded string, not the canonicalized version
     args.insert(0, expanded_path.into());
 
+    let start_after = proc
+        .start_after
+        .iter()
+        .map(|x| build_start_condition(x, hostname))
+        .collect::<anyhow::Result<_>>()?;
+
     Ok(ProcessInfo {
         plugin: canonical_path,
         start_time,
@@ -378,11 +820,136 @@ fn build_process(proc: &ProcessOptions, config: &ConfigOptions) -> anyhow::Resul
         args,
         env: proc.environment.clone(),
         expected_final_state: proc.expected_final_state,
+        start_after,
     })
 }
 
+/// Build a process start condition and get the name of the host it's about, which is the
+/// process's own host `hostname` if the condition doesn't name one.
+fn build_start_condition(
+    condition: &StartConditionOptions,
+    hostname: &str,
+) -> anyhow::Result<(String, StartCondition)> {
+    let (host, condition) = match condition {
+        StartConditionOptions::ProcessExited { host, process } => {
+            (host, StartCondition::ProcessExited(*process))
+        }
+        StartConditionOptions::FileExists { host, path } => {
+            // the path must stay within the host's data directory
+            if !path
+                .components()
+                .all(|x| matches!(x, std::path::Component::Normal(_)))
+            {
+                return Err(anyhow::anyhow!(
+                    "The path '{}' of a file-exists condition must be relative to the host's \
+                     data directory and can't contain '..'",
+                    path.display(),
+                ));
+            }
+            (host, StartCondition::FileExists(path.clone()))
+        }
+        StartConditionOptions::TcpListening { host, port } => {
+            if *port == 0 {
+                return Err(anyhow::anyhow!(
+                    "The port of a tcp-listening condition can't be 0"
+                ));
+            }
+            (host, StartCondition::TcpListening(*port))
+        }
+    };
+
+    let host = host.as_deref().unwrap_or(hostname).to_string();
+    Ok((host, condition))
+}
+
+/// The start conditions of a process, each with the name of the host it's about.
+type StartAfter = [(String, StartCondition)];
+
+/// Check that the hosts and processes that start conditions are about exist, and that no
+/// processes wait for each other to exit. Takes the name of each host and the start conditions of
+/// each of its processes.
+fn check_start_conditions(hosts: &[(&str, Vec<&StartAfter>)]) -> anyhow::Result<()> {
+    let num_processes: HashMap<&str, usize> = hosts
+        .iter()
+        .map(|(name, procs)| (*name, procs.len()))
+        .collect();
+
+    // the processes that each process waits for to exit
+    let mut exits: BTreeMap<(&str, usize), Vec<(&str, usize)>> = BTreeMap::new();
+
+    for (hostname, procs) in hosts {
+        for (i, start_after) in procs.iter().enumerate() {
+            let waits_for = exits.entry((hostname, i)).or_default();
+
+            for (name, condition) in *start_after {
+                let Some(len) = num_processes.get(name.as_str()) else {
+                    return Err(anyhow::anyhow!(
+                        "Process {i} of host '{hostname}' waits for host '{name}', which doesn't \
+                         exist"
+                    ));
+                };
+
+                if let StartCondition::ProcessExited(index) = condition {
+                    if index >= len {
+                        return Err(anyhow::anyhow!(
+                            "Process {i} of host '{hostname}' waits for process {index} of host \
+                             '{name}', which only has {len} processes"
+                        ));
+                    }
+                    waits_for.push((name.as_str(), *index));
+                }
+            }
+        }
+    }
+
+    // a depth-first search, returning the processes of a cycle if one is found
+    fn visit<'a>(
+        proc: (&'a str, usize),
+        exits: &BTreeMap<(&'a str, usize), Vec<(&'a str, usize)>>,
+        done: &mut HashSet<(&'a str, usize)>,
+        path: &mut Vec<(&'a str, usize)>,
+    ) -> Option<Vec<(&'a str, usize)>> {
+        if let Some(start) = path.iter().position(|x| *x == proc) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(proc);
+            return Some(cycle);
+        }
+        if done.contains(&proc) {
+            return None;
+        }
+
+        path.push(proc);
+        for next in &exits[&proc] {
+            if let Some(cycle) = visit(*next, exits, done, path) {
+                return Some(cycle);
+            }
+        }
+        path.pop();
+        done.insert(proc);
+
+        None
+    }
+
+    let mut done = HashSet::new();
+    for proc in exits.keys() {
+        if let Some(cycle) = visit(*proc, &exits, &mut done, &mut Vec::new()) {
+            let cycle: Vec<_> = cycle
+                .iter()
+                .map(|(host, i)| format!("'{host}' process {i}"))
+                .collect();
+            return Err(anyhow::anyhow!(
+                "Processes wait for each other to exit: {}",
+                cycle.join(" -> "),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
 /// Generate an IP assignment map using hosts' configured IP addresses and graph node IDs. For hosts
-/// without IP addresses, they will be assigned an arbitrary IP address.
+/// without IP addresses, they will be assigned an arbitrary IP address. Every host is assigned both an
+/// IPv4 and an IPv6 address.
 fn assign_ips(hosts: &mut [HostInfo]) -> anyhow::Result<IpAssignment<u32>> {
     let mut ip_assignment = IpAssignment::new();
 
@@ -396,6 +963,16 @@ fn assign_ips(hosts: &mut [HostInfo]) -> anyhow::Result<IpAssignment<u32>> {
         })?;
     }
 
+    // and the hosts that have a specific IPv6 address
+    for host in hosts.iter().filter(|x| x.ipv6_addr.is_some()) {
+        let ip = host.ipv6_addr.unwrap();
+        let hostname = &host.name;
+        let node_id = host.network_node_id;
+        ip_assignment.assign_ip(node_id, ip).with_context(|| {
+            format!("Failed to assign IPv6 address {ip} for host '{hostname}' to node '{node_id}'")
+        })?;
+    }
+
     // then register remaining hosts
     for host in hosts.iter_mut().filter(|x| x.ip_addr.is_none()) {
         let ip = ip_assignment.assign(host.network_node_id);
@@ -403,9 +980,60 @@ fn assign_ips(hosts: &mut [HostInfo]) -> anyhow::Result<IpAssignment<u32>> {
         host.ip_addr = Some(ip);
     }
 
+    for host in hosts.iter_mut().filter(|x| x.ipv6_addr.is_none()) {
+        let ip = ip_assignment.assign_v6(host.network_node_id);
+        host.ipv6_addr = Some(ip);
+    }
+
     Ok(ip_assignment)
 }
 
+/// Build the network faults from the configuration options, and add any changes to bandwidth to
+/// the hosts.
+fn build_network_faults(
+    config: &ConfigOptions,
+    graph: NetworkGraph,
+    ip_assignment: &IpAssignment<u32>,
+    hosts: &mut [HostInfo],
+) -> anyhow::Result<NetworkFaults> {
+    let mut network_faults = NetworkFaults::new(
+        graph,
+        &ip_assignment.get_nodes(),
+        config.network.use_shortest_path.unwrap(),
+    );
+
+    let sim_stop_time =
+        SimulationTime::try_from(Duration::from(config.general.stop_time.unwrap())).unwrap();
+
+    for (i, fault) in config.network.faults.iter().flatten().enumerate() {
+        let time = SimulationTime::try_from(Duration::from(fault.time())).unwrap();
+        if time >= sim_stop_time {
+            return Err(anyhow::anyhow!(
+                "Network fault time '{}' must be earlier than the simulation stop time '{}'",
+                fault.time(),
+                config.general.stop_time.unwrap(),
+            ));
+        }
+
+        let bandwidth_change = network_faults
+            .add(fault)
+            .with_context(|| format!("Invalid network fault {i}"))?;
+
+        if let Some((node, change)) = bandwidth_change {
+            for host in hosts.iter_mut().filter(|x| x.network_node_id == node) {
+                host.bandwidth_changes.push(change);
+            }
+        }
+    }
+
+    for host in hosts {
+        // a stable sort, so that changes with the same time are applied in order
+        host.bandwidth_changes.sort_by_key(|x| x.time);
+    }
+
+    Ok(network_faults)
+}
+
 /// Generate a map containing routing information (latency, packet loss, etc) for each pair of
 /// nodes.
 fn generate_routing_info(
@@ -444,5 +1072,191 @@ fn generate_routing_info(
             .collect()
     };
 
-    Ok(RoutingInfo::new(paths))
+    // the links on each path, which are shared by all packets that travel over them
+    let links = graph
+        .compute_links_with_overrides(&nodes[..], use_shortest_paths, &HashMap::new())
+        .into_iter()
+        .map(|((src, dst), links)| {
+            let src = graph.node_index_to_id(src).unwrap();
+            let dst = graph.node_index_to_id(dst).unwrap();
+            ((src, dst), links.into())
+        })
+        .collect();
+
+    Ok(RoutingInfo::new(paths).with_links(links))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_template() {
+        let fields = TemplateFields {
+            index: 2,
+            hostname: "validator2",
+            ip: std::net::Ipv4Addr::new(11, 0, 0, 2),
+            peer_ips: "11.0.0.1,11.0.0.3",
+        };
+
+        assert_eq!(
+            expand_template("--name={{hostname}} --id {{ index }}", &fields).unwrap(),
+            "--name=validator2 --id 2"
+        );
+        assert_eq!(
+            expand_template("{{ip}}:{{peer_ips}}", &fields).unwrap(),
+            "11.0.0.2:11.0.0.1,11.0.0.3"
+        );
+        assert_eq!(expand_template("{ip} }}", &fields).unwrap(), "{ip} }}");
+        assert_eq!(
+            expand_template("--format '{{{{.Id}}' {{{{{{ip}}", &fields).unwrap(),
+            "--format '{{.Id}}' {{11.0.0.2"
+        );
+
+        assert!(expand_template("{{port}}", &fields).is_err());
+        assert!(expand_template("{{ip", &fields).is_err());
+    }
+
+    #[test]
+    fn test_expand_hosts() {
+        let hosts: BTreeMap<HostName, HostOptions> = serde_yaml::from_str(
+            r#"
+            client:
+              network_node_id: 0
+              processes:
+              - path: /bin/client
+                args: ["--format", "{{.Id}}"]
+            validator:
+              quantity: 3
+              network_node_id: 0
+              processes:
+              - path: /bin/{{hostname}}
+                args: --ip {{ip}} --peers {{peer_ips}}
+                environment:
+                  INDEX: "{{index}}"
+            "#,
+        )
+        .unwrap();
+        let hosts = expand_hosts(&hosts).unwrap();
+
+        let names: Vec<_> = hosts.keys().map(|x| x.as_str()).collect();
+        assert_eq!(names, ["client", "validator1", "validator2", "validator3"]);
+
+        // the addresses are assigned in order of hostname
+        let ip = |name: &str| hosts[&HostName::new(name).unwrap()].ip_addr.unwrap();
+        assert_eq!(ip("client"), std::net::Ipv4Addr::new(11, 0, 0, 1));
+        assert_eq!(ip("validator3"), std::net::Ipv4Addr::new(11, 0, 0, 4));
+
+        // templates are only replaced for hosts with a quantity
+        let ProcessArgs::List(args) = &hosts[&HostName::new("client").unwrap()].processes[0].args
+        else {
+            panic!();
+        };
+        assert_eq!(args, &["--format", "{{.Id}}"]);
+
+        let validator = &hosts[&HostName::new("validator2").unwrap()];
+        assert!(validator.quantity.is_none());
+        let proc = &validator.processes[0];
+        assert_eq!(proc.path, PathBuf::from("/bin/validator2"));
+        let ProcessArgs::Str(args) = &proc.args else {
+            panic!();
+        };
+        assert_eq!(args, "--ip 11.0.0.3 --peers 11.0.0.2,11.0.0.4");
+        assert_eq!(proc.environment[&EnvName::new("INDEX").unwrap()], "2");
+    }
+
+    #[test]
+    fn test_expand_hosts_conflict() {
+        let hosts: BTreeMap<HostName, HostOptions> = serde_yaml::from_str(
+            r#"
+            node:
+              quantity: 2
+              network_node_id: 0
+              processes: []
+            node2:
+              network_node_id: 0
+              processes: []
+            "#,
+        )
+        .unwrap();
+        assert!(expand_hosts(&hosts).is_err());
+    }
+
+    #[test]
+    fn test_build_start_condition() {
+        let condition: StartConditionOptions =
+            serde_yaml::from_str("{type: file-exists, path: keys/ready}").unwrap();
+        assert_eq!(
+            build_start_condition(&condition, "node").unwrap(),
+            (
+                "node".to_string(),
+                StartCondition::FileExists("keys/ready".into())
+            )
+        );
+
+        let condition: StartConditionOptions =
+            serde_yaml::from_str("{type: tcp-listening, host: server, port: 80}").unwrap();
+        assert_eq!(
+            build_start_condition(&condition, "node").unwrap(),
+            ("server".to_string(), StartCondition::TcpListening(80))
+        );
+
+        for condition in [
+            "{type: file-exists, path: /tmp/ready}",
+            "{type: file-exists, path: ../ready}",
+            "{type: tcp-listening, port: 0}",
+        ] {
+            let condition: StartConditionOptions = serde_yaml::from_str(condition).unwrap();
+            assert!(build_start_condition(&condition, "node").is_err());
+        }
+    }
+
+    #[test]
+    fn test_check_start_conditions() {
+        let exited = |host: &str, i| (host.to_string(), StartCondition::ProcessExited(i));
+
+        let genesis = [];
+        let validator = [exited("genesis", 0)];
+        let client = [exited("validator", 0), exited("client", 0)];
+        let hosts = [
+            ("genesis", vec![&genesis[..]]),
+            ("validator", vec![&validator[..]]),
+            ("client", vec![&[][..], &client[..]]),
+        ];
+        check_start_conditions(&hosts).unwrap();
+
+        // a process that doesn't exist
+        let validator = [exited("genesis", 1)];
+        let hosts = [
+            ("genesis", vec![&genesis[..]]),
+            ("validator", vec![&validator[..]]),
+        ];
+        assert!(check_start_conditions(&hosts).is_err());
+
+        // a host that doesn't exist
+        let validator = [exited("bootstrap", 0)];
+        let hosts = [("validator", vec![&validator[..]])];
+        assert!(check_start_conditions(&hosts).is_err());
+
+        // a process that waits for itself
+        let validator = [exited("validator", 0)];
+        let hosts = [("validator", vec![&validator[..]])];
+        assert!(check_start_conditions(&hosts).is_err());
+
+        // processes that wait for each other
+        let genesis = [exited("validator", 0)];
+        let validator = [exited("client", 0)];
+        let client = [exited("genesis", 0)];
+        let hosts = [
+            ("genesis", vec![&genesis[..]]),
+            ("validator", vec![&validator[..]]),
+            ("client", vec![&client[..]]),
+        ];
+        let err = check_start_conditions(&hosts).unwrap_err().to_string();
+        assert_eq!(
+            err,
+            "Processes wait for each other to exit: 'client' process 0 -> 'genesis' process 0 -> \
+             'validator' process 0 -> 'client' process 0"
+        );
+    }
 }
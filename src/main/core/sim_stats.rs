@@ -1,9 +1,13 @@
 use std::cell::RefCell;
+use std::collections::BTreeMap;
 use std::sync::Mutex;
 
 use anyhow::Context;
 use serde::Serialize;
 
+use crate::host::network::qdisc::QDiscStats;
+use crate::network::graph::Link;
+use crate::network::links::LinkStats;
 use crate::utility::counter::Counter;
 
 /// Simulation statistics to be accessed by a single thread.
@@ -12,6 +16,8 @@ pub struct LocalSimStats {
     pub alloc_counts: RefCell<Counter>,
     pub dealloc_counts: RefCell<Counter>,
     pub syscall_counts: RefCell<Counter>,
+    /// Qdisc counters by hostname.
+    pub qdisc_stats: RefCell<BTreeMap<String, QDiscStats>>,
 }
 
 impl LocalSimStats {
@@ -20,6 +26,7 @@ impl LocalSimStats {
             alloc_counts: RefCell::new(Counter::new()),
             dealloc_counts: RefCell::new(Counter::new()),
             syscall_counts: RefCell::new(Counter::new()),
+            qdisc_stats: RefCell::new(BTreeMap::new()),
         }
     }
 }
@@ -36,6 +43,10 @@ pub struct SharedSimStats {
     pub alloc_counts: Mutex<Counter>,
     pub dealloc_counts: Mutex<Counter>,
     pub syscall_counts: Mutex<Counter>,
+    /// Qdisc counters by hostname.
+    pub qdisc_stats: Mutex<BTreeMap<String, QDiscStats>>,
+    /// Counters for the links in the network graph.
+    pub link_stats: Mutex<Vec<(Link, LinkStats)>>,
 }
 
 impl SharedSimStats {
@@ -44,6 +55,8 @@ impl SharedSimStats {
             alloc_counts: Mutex::new(Counter::new()),
             dealloc_counts: Mutex::new(Counter::new()),
             syscall_counts: Mutex::new(Counter::new()),
+            qdisc_stats: Mutex::new(BTreeMap::new()),
+            link_stats: Mutex::new(Vec::new()),
         }
     }
 
@@ -64,6 +77,11 @@ impl SharedSimStats {
         *local_alloc_counts = Counter::new();
         *local_dealloc_counts = Counter::new();
         *local_syscall_counts = Counter::new();
+
+        self.qdisc_stats
+            .lock()
+            .unwrap()
+            .append(&mut local.qdisc_stats.borrow_mut());
     }
 }
 
@@ -78,6 +96,18 @@ impl Default for SharedSimStats {
 struct SimStatsForOutput {
     pub objects: ObjectStatsForOutput,
     pub syscalls: Counter,
+    /// Only hosts with a qdisc are included.
+    pub qdiscs: BTreeMap<String, QDiscStats>,
+    /// Only links that at least one packet travelled over are included.
+    pub links: Vec<LinkStatsForOutput>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+struct LinkStatsForOutput {
+    pub source: u32,
+    pub target: u32,
+    #[serde(flatten)]
+    pub stats: LinkStats,
 }
 
 #[derive(Serialize, Clone, Debug)]
@@ -96,6 +126,15 @@ impl SimStatsForOutput {
                 dealloc_counts: std::mem::take(&mut stats.dealloc_counts.lock().unwrap()),
             },
             syscalls: std::mem::take(&mut stats.syscall_counts.lock().unwrap()),
+            qdiscs: std::mem::take(&mut stats.qdisc_stats.lock().unwrap()),
+            links: std::mem::take(&mut *stats.link_stats.lock().unwrap())
+                .into_iter()
+                .map(|(link, stats)| LinkStatsForOutput {
+                    source: link.source,
+                    target: link.target,
+                    stats,
+                })
+                .collect(),
         }
     }
 }
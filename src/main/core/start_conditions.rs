@@ -0,0 +1,226 @@
+//! Conditions that processes wait for before they're started.
+//!
+//! A process with `start_after` conditions isn't scheduled when its host is built. Instead, the
+//! host that each condition concerns checks it at the end of every scheduling round, after all of
+//! the round's events have run, and the results are gathered between rounds. A process whose
+//! conditions have all been met is started on the next round at the end time of the round where
+//! they were met (or at its `start_time` if that's later). Since hosts only check their own state
+//! and only at round boundaries, the start times don't depend on the order that hosts are run in.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use shadow_shim_helper_rs::HostId;
+use shadow_shim_helper_rs::emulated_time::EmulatedTime;
+
+/// A condition of a host that a process can wait for.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum StartCondition {
+    /// The process with this index in the host's process list exited with a status of 0.
+    ProcessExited(usize),
+    /// A file exists at this path relative to the host's data directory.
+    FileExists(PathBuf),
+    /// A TCP socket is listening on this port.
+    TcpListening(u16),
+}
+
+/// A process that hasn't been started yet.
+#[derive(Debug)]
+struct WaitingProcess {
+    host_id: HostId,
+    /// The index of the process in its host's process list.
+    index: usize,
+    /// The earliest time that the process can be started.
+    start_time: EmulatedTime,
+    /// The indexes of the conditions that the process waits for.
+    conditions: Vec<usize>,
+}
+
+/// The start conditions of all processes in the simulation.
+#[derive(Debug, Default)]
+pub struct StartConditions {
+    /// Each unique condition and the host that it concerns.
+    conditions: Vec<(HostId, StartCondition)>,
+    /// The indexes of the conditions that each host needs to check.
+    by_host: HashMap<HostId, Vec<usize>>,
+    /// Whether each condition has been met. A condition stays met once it has been met, even if
+    /// for example the file is later removed.
+    met: Vec<AtomicBool>,
+    /// Processes whose conditions haven't all been met yet.
+    waiting: Mutex<Vec<WaitingProcess>>,
+    /// Processes for each host that are ready to be started, and the times to start them at.
+    ready: Mutex<HashMap<HostId, Vec<(usize, EmulatedTime)>>>,
+}
+
+impl StartConditions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a process of host `host_id` which must not be started until all of the `conditions`
+    /// have been met.
+    pub fn add_process(
+        &mut self,
+        host_id: HostId,
+        index: usize,
+        start_time: EmulatedTime,
+        conditions: impl IntoIterator<Item = (HostId, StartCondition)>,
+    ) {
+        let mut indexes = Vec::new();
+
+        for condition in conditions {
+            let i = match self.conditions.iter().position(|x| *x == condition) {
+                Some(i) => i,
+                None => {
+                    self.by_host
+                        .entry(condition.0)
+                        .or_default()
+                        .push(self.conditions.len());
+                    self.conditions.push(condition);
+                    self.met.push(AtomicBool::new(false));
+                    self.conditions.len() - 1
+                }
+            };
+            indexes.push(i);
+        }
+
+        self.waiting.get_mut().unwrap().push(WaitingProcess {
+            host_id,
+            index,
+            start_time,
+            conditions: indexes,
+        });
+    }
+
+    /// Returns true if no processes have start conditions.
+    pub fn is_empty(&self) -> bool {
+        self.conditions.is_empty()
+    }
+
+    /// Check the unmet conditions that concern host `host_id` using `is_met`. Should be called by
+    /// each host after it has finished running the events of the round.
+    pub fn check(&self, host_id: HostId, is_met: impl Fn(&StartCondition) -> bool) {
+        let Some(indexes) = self.by_host.get(&host_id) else {
+            return;
+        };
+
+        for i in indexes {
+            if !self.met[*i].load(Ordering::Relaxed) && is_met(&self.conditions[*i].1) {
+                self.met[*i].store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Find the processes whose conditions have all been met during the round ending at
+    /// `round_end`. Returns the earliest time that one of these processes will be started at, if
+    /// any. Must only be called between rounds.
+    pub fn finish_round(&self, round_end: EmulatedTime) -> Option<EmulatedTime> {
+        let mut waiting = self.waiting.lock().unwrap();
+        let mut ready = self.ready.lock().unwrap();
+        let mut earliest = None;
+
+        waiting.retain(|process| {
+            let is_ready = process
+                .conditions
+                .iter()
+                .all(|i| self.met[*i].load(Ordering::Relaxed));
+
+            if is_ready {
+                let time = std::cmp::max(process.start_time, round_end);
+                ready
+                    .entry(process.host_id)
+                    .or_default()
+                    .push((process.index, time));
+                earliest = [earliest, Some(time)].into_iter().flatten().min();
+            }
+
+            !is_ready
+        });
+
+        earliest
+    }
+
+    /// Take the processes of host `host_id` that are ready to be started, and the times to start
+    /// them at.
+    pub fn take_ready(&self, host_id: HostId) -> Vec<(usize, EmulatedTime)> {
+        self.ready
+            .lock()
+            .unwrap()
+            .remove(&host_id)
+            .unwrap_or_default()
+    }
+
+    /// The host and index of each process that is still waiting for its conditions.
+    pub fn waiting(&self) -> Vec<(HostId, usize)> {
+        self.waiting
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|x| (x.host_id, x.index))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use shadow_shim_helper_rs::simulation_time::SimulationTime;
+
+    use super::*;
+
+    fn time(secs: u64) -> EmulatedTime {
+        EmulatedTime::SIMULATION_START + SimulationTime::from_secs(secs)
+    }
+
+    #[test]
+    fn test_start_after_exit() {
+        let (a, b) = (HostId::from(0), HostId::from(1));
+
+        let mut conditions = StartConditions::new();
+        conditions.add_process(b, 0, time(1), [(a, StartCondition::ProcessExited(0))]);
+        conditions.add_process(b, 1, time(10), [(a, StartCondition::ProcessExited(0))]);
+        assert!(!conditions.is_empty());
+
+        // host 'b' has nothing to check
+        conditions.check(b, |_| panic!());
+
+        conditions.check(a, |_| false);
+        assert_eq!(conditions.finish_round(time(2)), None);
+        assert_eq!(conditions.take_ready(b), []);
+
+        conditions.check(a, |x| *x == StartCondition::ProcessExited(0));
+        assert_eq!(conditions.finish_round(time(3)), Some(time(3)));
+        assert_eq!(conditions.take_ready(a), []);
+        assert_eq!(conditions.take_ready(b), [(0, time(3)), (1, time(10))]);
+        assert_eq!(conditions.take_ready(b), []);
+        assert_eq!(conditions.waiting(), []);
+    }
+
+    #[test]
+    fn test_start_after_all() {
+        let (a, b) = (HostId::from(0), HostId::from(1));
+
+        let mut conditions = StartConditions::new();
+        conditions.add_process(
+            a,
+            0,
+            time(0),
+            [
+                (a, StartCondition::FileExists("ready".into())),
+                (b, StartCondition::TcpListening(80)),
+            ],
+        );
+
+        conditions.check(a, |_| true);
+        assert_eq!(conditions.finish_round(time(1)), None);
+        assert_eq!(conditions.waiting(), [(a, 0)]);
+
+        // a condition stays met even if it's no longer true
+        conditions.check(a, |_| false);
+        conditions.check(b, |_| true);
+        assert_eq!(conditions.finish_round(time(2)), Some(time(2)));
+        assert_eq!(conditions.take_ready(a), [(0, time(2))]);
+        assert_eq!(conditions.waiting(), []);
+    }
+}
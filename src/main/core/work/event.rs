@@ -18,13 +18,25 @@ impl Event {
     /// A new packet event, which is an event for packets arriving from the Internet. Packet events
     /// do not include packets on localhost.
     pub fn new_packet(packet: PacketRc, time: EmulatedTime, src_host: &Host) -> Self {
+        Self::new_remote_packet(packet, time, src_host.id(), src_host.get_new_event_id())
+    }
+
+    /// A new packet event for a packet that was sent by a host in another Shadow process, or that
+    /// was held until the end of the round. The source host's event ID must have been taken from
+    /// the source host when the packet was sent.
+    pub fn new_remote_packet(
+        packet: PacketRc,
+        time: EmulatedTime,
+        src_host_id: HostId,
+        src_host_event_id: u64,
+    ) -> Self {
         Self {
             magic: Magic::new(),
             time,
             data: EventData::Packet(PacketEventData {
                 packet,
-                src_host_id: src_host.id(),
-                src_host_event_id: src_host.get_new_event_id(),
+                src_host_id,
+                src_host_event_id,
             }),
             _counter: ObjectCounter::new("Event"),
         }
@@ -3,6 +3,7 @@ use std::collections::HashMap;
 use std::sync::atomic::{AtomicBool, AtomicU32};
 use std::sync::{Arc, Mutex};
 
+use anyhow::Context;
 use atomic_refcell::{AtomicRef, AtomicRefCell};
 use linux_api::posix_types::Pid;
 use once_cell::sync::Lazy;
@@ -14,17 +15,29 @@ use shadow_shim_helper_rs::rootedcell::refcell::RootedRefCell;
 use shadow_shim_helper_rs::simulation_time::SimulationTime;
 
 use super::work::event_queue::EventQueue;
+use crate::core::activity_trace::{ActivityEvent, ActivityTrace};
+use crate::core::configuration::JitterDistribution;
 use crate::core::controller::ShadowStatusBarState;
+use crate::core::distributed::{Distributed, RemotePacket};
+use crate::core::lookahead::Lookahead;
 use crate::core::runahead::Runahead;
 use crate::core::sim_config::Bandwidth;
 use crate::core::sim_stats::{LocalSimStats, SharedSimStats};
+use crate::core::start_conditions::{StartCondition, StartConditions};
 use crate::core::work::event::Event;
 use crate::host::host::Host;
+use crate::host::network::qdisc::QDiscStats;
 use crate::host::process::{Process, ProcessId};
+use crate::host::syscall::formatter::{MergedStrace, SyscallRecord};
 use crate::host::thread::{Thread, ThreadId};
+use crate::network::capture::PacketCapture;
 use crate::network::dns::Dns;
-use crate::network::graph::{IpAssignment, RoutingInfo};
-use crate::network::packet::{PacketRc, PacketStatus};
+use crate::network::faults::NetworkFaults;
+use crate::network::graph::{IpAssignment, PathLink, PathProperties, RoutingInfo};
+use crate::network::jitter;
+use crate::network::links::{LinkPacket, LinkQueues};
+use crate::network::packet::{Packet, PacketId, PacketRc, PacketStatus};
+use crate::network::packet_trace::PacketTrace;
 use crate::utility::childpid_watcher::ChildPidWatcher;
 use crate::utility::counter::Counter;
 use crate::utility::status_bar;
@@ -359,10 +372,15 @@ impl Worker {
         let src_ip = std::net::IpAddr::V4(src_ip);
         let dst_ip = std::net::IpAddr::V4(dst_ip);
 
+        // there may be no path if the network is partitioned or a link is down
+        let Some(path) = Worker::with(|w| w.shared.path(src_ip, dst_ip, current_time)).unwrap()
+        else {
+            packetrc.add_status(PacketStatus::InetDropped);
+            return;
+        };
+
         // check if network reliability forces us to 'drop' the packet
-        let reliability: f64 = Worker::with(|w| w.shared.reliability(src_ip, dst_ip).unwrap())
-            .unwrap()
-            .into();
+        let reliability: f64 = (1.0 - path.packet_loss).into();
         let chance: f64 = src_host.random_mut().random();
 
         // don't drop control packets with length 0, otherwise congestion control has problems
@@ -373,28 +391,63 @@ impl Worker {
             return;
         }
 
-        let delay = Worker::with(|w| w.shared.latency(src_ip, dst_ip).unwrap()).unwrap();
+        // the host's RNG is only used if the path has jitter
+        let jitter_distribution = Worker::with(|w| w.shared.jitter_distribution).unwrap();
+        let delay = SimulationTime::from_nanos(jitter::sample_delay_ns(
+            jitter_distribution,
+            path.latency_ns,
+            path.jitter_ns,
+            &mut *src_host.random_mut(),
+        ));
 
         Worker::update_lowest_used_latency(delay);
         Worker::with(|w| w.shared.increment_packet_count(src_ip, dst_ip)).unwrap();
 
-        // TODO: this should change for sending to remote manager (on a different machine); this is
-        // the only place where tasks are sent between separate host
+        // this is the only place where tasks are sent between separate hosts, and packets to hosts
+        // in other shadow processes are sent at the end of the round (see `push_packet_to_host`)
 
         packetrc.add_status(PacketStatus::InetSent);
 
-        // delay the packet until the next round
+        // delay the packet until the next round; the per-host lookahead guarantees that the
+        // destination host hasn't run past the packet's arrival time, so no delay is needed
         let mut deliver_time = current_time + delay;
-        if deliver_time < round_end_time {
+        let uses_lookahead = Worker::with(|w| w.shared.lookahead.is_some()).unwrap();
+        if deliver_time < round_end_time && !uses_lookahead {
             deliver_time = round_end_time;
         }
 
+        // jitter could deliver this packet before an earlier packet of the same flow
+        if path.jitter_ns > 0 && !Worker::with(|w| w.shared.allow_packet_reordering).unwrap() {
+            deliver_time = src_host.flow_delivery_time(packetrc.flow(), deliver_time);
+        }
+
         // we may have sent this packet after the destination host finished running the current
         // round and calculated its min event time, so we put this in our min event time instead
         Worker::update_next_event_time(deliver_time);
 
         // copy the packet (except the payload) so the dst gets its own header info
         let dst_packet = packetrc.new_copy_inner();
+
+        // packets that travel over links with a bandwidth are queued at the links at the end of
+        // the round, and bandwidth limits don't apply during bootstrapping
+        if !is_bootstrapping
+            && let Some(links) =
+                Worker::with(|w| w.shared.links(src_ip, dst_ip, current_time)).unwrap()
+        {
+            let packet = LinkPacket {
+                packet: dst_packet,
+                links,
+                dst_host_id,
+                send_time: current_time,
+                arrival_time: current_time + delay,
+                deliver_time,
+                src_host_id: src_host.id(),
+                src_host_event_id: src_host.get_new_event_id(),
+            };
+            Worker::with(|w| w.shared.link_queues.push(packet)).unwrap();
+            return;
+        }
+
         Worker::with(|w| {
             w.shared
                 .push_packet_to_host(dst_packet, dst_host_id, deliver_time, src_host)
@@ -464,6 +517,17 @@ impl Worker {
         });
     }
 
+    /// Record the counters of a host's qdisc.
+    pub fn add_qdisc_stats(hostname: &str, stats: QDiscStats) {
+        Worker::with(|w| {
+            w.sim_stats
+                .qdisc_stats
+                .borrow_mut()
+                .insert(hostname.to_string(), stats);
+        })
+        .unwrap()
+    }
+
     pub fn add_to_global_sim_stats() {
         Worker::with(|w| SIM_STATS.add_from_local_stats(&w.sim_stats)).unwrap()
     }
@@ -476,6 +540,73 @@ impl Worker {
         Worker::with(|w| w.shared.increment_plugin_error_count()).unwrap()
     }
 
+    /// Check the process start conditions that are about host `host_id`.
+    pub fn check_start_conditions(host_id: HostId, is_met: impl Fn(&StartCondition) -> bool) {
+        Worker::with(|w| w.shared.start_conditions.check(host_id, is_met)).unwrap()
+    }
+
+    /// Take the processes of host `host_id` whose start conditions have been met, and the times
+    /// to start them at.
+    pub fn take_ready_processes(host_id: HostId) -> Vec<(usize, EmulatedTime)> {
+        Worker::with(|w| w.shared.start_conditions.take_ready(host_id)).unwrap()
+    }
+
+    /// Add a packet that had `status` on the active host's interface named `interface` to the
+    /// simulation's merged packet capture, if there is one.
+    pub fn capture_packet(interface: &str, packet: &Packet, status: PacketStatus) {
+        Worker::with(|w| {
+            let Some(capture) = &w.shared.packet_capture else {
+                return;
+            };
+            let host_id = w.active_host.borrow().as_ref().unwrap().id();
+            let now = w.clock.borrow().now.unwrap();
+            capture.capture(host_id, interface, now, packet, status);
+        })
+        .unwrap()
+    }
+
+    /// Write activity of host `host_id` to the simulation's activity trace, if there is one.
+    pub fn write_activity(host_id: HostId, events: &[ActivityEvent]) {
+        Worker::with(|w| {
+            let Some(trace) = &w.shared.activity_trace else {
+                return;
+            };
+            if let Err(e) = trace.write(host_id, events) {
+                warn_once_then_debug!("Unable to write the activity trace: {e}");
+            }
+        })
+        .unwrap()
+    }
+
+    /// Add a syscall of the active host to the simulation's merged strace log, if there is one.
+    pub fn write_merged_strace(record: SyscallRecord) {
+        Worker::with(|w| {
+            let Some(merged) = &w.shared.strace_merged else {
+                return;
+            };
+            let host_id = w.active_host.borrow().as_ref().unwrap().id();
+            merged.record(host_id, record);
+        })
+        .unwrap()
+    }
+
+    /// Add packet `id` reaching `status` on the active host to the simulation's packet trace, if
+    /// there is one. Packets without an active host, such as those dropped at links between
+    /// rounds, aren't traced here.
+    pub fn trace_packet(id: PacketId, status: PacketStatus) {
+        // there's no worker on the manager thread
+        let _ = Worker::with(|w| {
+            let Some(trace) = &w.shared.packet_trace else {
+                return;
+            };
+            let Some(host_id) = w.active_host.borrow().as_ref().map(|x| x.id()) else {
+                return;
+            };
+            let now = w.clock.borrow().now.unwrap();
+            trace.record(now, Some(host_id), id, status);
+        });
+    }
+
     /// Shadow allows configuration of a "bootstrapping" interval, during which
     /// hosts' network activity does not consume bandwidth. Returns `true` if we
     /// are still within this preliminary interval, or `false` otherwise.
@@ -491,6 +622,16 @@ impl Worker {
         }
     }
 
+    /// Get the IPv4 address of the host that owns the IPv6 address `ip`.
+    pub fn resolve_ipv6_to_ipv4(ip: std::net::Ipv6Addr) -> Option<std::net::Ipv4Addr> {
+        Worker::with_dns(|dns| dns.addr6_to_addr(ip))
+    }
+
+    /// Get the IPv6 address of the host that owns the IPv4 address `ip`.
+    pub fn resolve_ipv4_to_ipv6(ip: std::net::Ipv4Addr) -> Option<std::net::Ipv6Addr> {
+        Worker::with_dns(|dns| dns.addr_to_addr6(ip))
+    }
+
     fn resolve_ip_to_host_id(ip: std::net::Ipv4Addr) -> Option<HostId> {
         Worker::with_dns(|dns| dns.addr_to_host_id(ip))
     }
@@ -500,6 +641,8 @@ impl Worker {
 pub struct WorkerShared {
     pub ip_assignment: IpAssignment<u32>,
     pub routing_info: RoutingInfo<u32>,
+    /// Used to update `routing_info` when network faults are added during the simulation.
+    pub network_faults: Mutex<NetworkFaults>,
     pub host_bandwidths: HashMap<std::net::IpAddr, Bandwidth>,
     pub dns: Dns,
     // allows for easy updating of the status bar's state
@@ -508,11 +651,31 @@ pub struct WorkerShared {
     pub num_plugin_errors: AtomicU32,
     // calculates the runahead for the next simulation round
     pub runahead: Runahead,
+    /// Calculates how far each host may run in the next simulation round, if the per-host
+    /// lookahead is used instead of the runahead.
+    pub lookahead: Option<Lookahead>,
+    /// Connections to the other Shadow processes, if the simulation is distributed.
+    pub distributed: Option<Distributed>,
     pub child_pid_watcher: ChildPidWatcher,
-    /// Event queues for each host. This should only be used to push packet events.
+    /// Queues for the links in the network graph, which are shared by all hosts.
+    pub link_queues: LinkQueues,
+    /// Conditions that processes are waiting for before they're started.
+    pub start_conditions: StartConditions,
+    /// The merged packet capture of all hosts, if enabled.
+    pub packet_capture: Option<PacketCapture>,
+    /// The trace of the status changes of all packets, if enabled.
+    pub packet_trace: Option<PacketTrace>,
+    /// The trace of the wall-clock time that hosts spend on their activities, if enabled.
+    pub activity_trace: Option<ActivityTrace>,
+    /// The strace log of all processes, if enabled.
+    pub strace_merged: Option<MergedStrace>,
+    /// Event queues for each host that is run by this process. This should only be used to push
+    /// packet events.
     pub event_queues: HashMap<HostId, Arc<Mutex<EventQueue>>>,
     pub bootstrap_end_time: EmulatedTime,
     pub sim_end_time: EmulatedTime,
+    pub jitter_distribution: JitterDistribution,
+    pub allow_packet_reordering: bool,
 }
 
 impl WorkerShared {
@@ -520,6 +683,7 @@ impl WorkerShared {
         &self.dns
     }
 
+    /// The latency between two hosts at the start of the simulation.
     pub fn latency(&self, src: std::net::IpAddr, dst: std::net::IpAddr) -> Option<SimulationTime> {
         let src = self.ip_assignment.get_node(src)?;
         let dst = self.ip_assignment.get_node(dst)?;
@@ -529,11 +693,46 @@ impl WorkerShared {
         ))
     }
 
-    pub fn reliability(&self, src: std::net::IpAddr, dst: std::net::IpAddr) -> Option<f32> {
+    /// The path between two hosts at time `time`. Returns `None` if packets can't be routed
+    /// between the hosts at that time.
+    pub fn path(
+        &self,
+        src: std::net::IpAddr,
+        dst: std::net::IpAddr,
+        time: EmulatedTime,
+    ) -> Option<PathProperties> {
         let src = self.ip_assignment.get_node(src)?;
         let dst = self.ip_assignment.get_node(dst)?;
 
-        Some(1.0 - self.routing_info.path(src, dst)?.packet_loss)
+        self.routing_info.path_at(src, dst, time)
+    }
+
+    /// The links on the path between two hosts at time `time`. Returns `None` if the path doesn't
+    /// travel over any links.
+    pub fn links(
+        &self,
+        src: std::net::IpAddr,
+        dst: std::net::IpAddr,
+        time: EmulatedTime,
+    ) -> Option<Arc<[PathLink]>> {
+        let src = self.ip_assignment.get_node(src)?;
+        let dst = self.ip_assignment.get_node(dst)?;
+
+        self.routing_info.links_at(src, dst, time)
+    }
+
+    /// The time that each host may run until in the next simulation round, or `None` if the
+    /// per-host lookahead isn't used.
+    pub fn host_window_ends(&self) -> Option<HashMap<HostId, EmulatedTime>> {
+        let lookahead = self.lookahead.as_ref()?;
+
+        let next_event_times = self
+            .event_queues
+            .iter()
+            .map(|(host, queue)| (*host, queue.lock().unwrap().next_event_time()))
+            .collect();
+
+        Some(lookahead.window_ends(&next_event_times, self.sim_end_time))
     }
 
     pub fn bandwidth(&self, ip: std::net::IpAddr) -> Option<&Bandwidth> {
@@ -607,10 +806,117 @@ impl WorkerShared {
         time: EmulatedTime,
         src_host: &Host,
     ) {
+        let Some(event_queue) = self.event_queues.get(&dst_host_id) else {
+            // the destination host is run by another shadow process
+            let distributed = self.distributed.as_ref().unwrap();
+            distributed.push_packet(&RemotePacket {
+                packet,
+                dst_host_id,
+                time,
+                src_host_id: src_host.id(),
+                src_host_event_id: src_host.get_new_event_id(),
+            });
+            return;
+        };
+
         let event = Event::new_packet(packet, time, src_host);
-        let event_queue = self.event_queues.get(&dst_host_id).unwrap();
         event_queue.lock().unwrap().push(event);
     }
+
+    /// Queue the packets that arrived at links during the round, push the packets that have passed
+    /// all of their links to their destination hosts, and find the processes whose start
+    /// conditions were met during the round ending at `round_end`. If the simulation is
+    /// distributed, exchange the packets that were sent to hosts in other processes during the
+    /// round, and the time of the next event, with the other processes. Returns the time of the
+    /// next event in any process.
+    pub fn finish_round(
+        &self,
+        round_end: EmulatedTime,
+        next_event_time: EmulatedTime,
+    ) -> anyhow::Result<EmulatedTime> {
+        // links can't be used in a distributed simulation, so the destinations are in this process
+        let on_drop = |packet: &LinkPacket, time| {
+            if let Some(trace) = &self.packet_trace
+                && let Some(id) = packet.packet.id()
+            {
+                trace.record(time, None, id, PacketStatus::InetDropped);
+            }
+        };
+        for (packet, time) in self.link_queues.finish_round(round_end, on_drop) {
+            let event = Event::new_remote_packet(
+                packet.packet,
+                time,
+                packet.src_host_id,
+                packet.src_host_event_id,
+            );
+            self.event_queues[&packet.dst_host_id]
+                .lock()
+                .unwrap()
+                .push(event);
+        }
+
+        if let Some(capture) = &self.packet_capture {
+            capture
+                .finish_round()
+                .context("Failed to write the packet capture")?;
+        }
+
+        if let Some(trace) = &self.packet_trace {
+            trace
+                .finish_round()
+                .context("Failed to write the packet trace")?;
+        }
+
+        if let Some(merged) = &self.strace_merged {
+            merged
+                .finish_round()
+                .context("Failed to write the merged strace log")?;
+        }
+
+        // packets that are waiting in front of a link are queued in a later round
+        let next_event_time = match self.link_queues.next_arrival_time() {
+            Some(arrival_time) => std::cmp::min(next_event_time, arrival_time),
+            None => next_event_time,
+        };
+
+        // processes that are ready to start are started by their hosts in the next round
+        let next_event_time = match self.start_conditions.finish_round(round_end) {
+            Some(start_time) => std::cmp::min(next_event_time, start_time),
+            None => next_event_time,
+        };
+
+        let Some(distributed) = &self.distributed else {
+            return Ok(next_event_time);
+        };
+
+        let round = distributed.finish_round(next_event_time, self.runahead.min_used_latency())?;
+
+        for packet in round.packets {
+            let event_queue = self
+                .event_queues
+                .get(&packet.dst_host_id)
+                .with_context(|| {
+                    format!(
+                        "Received a packet for host {:?}, which isn't run by this process",
+                        packet.dst_host_id
+                    )
+                })?;
+            let event = Event::new_remote_packet(
+                packet.packet,
+                packet.time,
+                packet.src_host_id,
+                packet.src_host_event_id,
+            );
+            event_queue.lock().unwrap().push(event);
+        }
+
+        // keep the runahead the same in every process
+        if let Some(latency) = round.min_used_latency {
+            self.runahead.update_lowest_used_latency(latency);
+        }
+
+        Ok(round.next_event_time)
+    }
 }
 
 /// Enable object counters. Should be called near the beginning of the program.
@@ -0,0 +1,594 @@
+//! Inotify instances, as created by `inotify_init1(2)`.
+//!
+//! Watches are kept by path rather than by inode, and are only notified of changes that the
+//! host's processes make through Shadow's file syscall handlers (see
+//! [`Host::notify_file_event`](crate::host::host::Host::notify_file_event)). Changes made by
+//! processes on other hosts or outside of the simulation are not observed.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::ffi::OsString;
+use std::io::Write;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Component, Path, PathBuf};
+
+use linux_api::errno::Errno;
+use linux_api::inotify::{InotifyMask, inotify_event};
+use linux_api::ioctls::IoctlRequest;
+use linux_api::posix_types::kernel_off_t;
+use shadow_shim_helper_rs::syscall_types::ForeignPtr;
+
+use crate::cshadow as c;
+use crate::host::descriptor::listener::{StateEventSource, StateListenHandle, StateListenerFilter};
+use crate::host::descriptor::{FileMode, FileSignals, FileState, FileStatus};
+use crate::host::memory_manager::MemoryManager;
+use crate::host::syscall::io::{IoVec, IoVecWriter};
+use crate::host::syscall::types::{SyscallError, SyscallResult};
+use crate::utility::HostTreePointer;
+use crate::utility::callback_queue::CallbackQueue;
+
+/// The default value of `/proc/sys/fs/inotify/max_queued_events` on Linux.
+const MAX_QUEUED_EVENTS: usize = 16384;
+
+struct Watch {
+    path: PathBuf,
+    mask: InotifyMask,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Event {
+    wd: std::ffi::c_int,
+    mask: InotifyMask,
+    cookie: u32,
+    name: Option<OsString>,
+}
+
+impl Event {
+    const HEADER_LEN: usize = std::mem::size_of::<inotify_event>();
+
+    fn overflow() -> Self {
+        Self {
+            wd: -1,
+            mask: InotifyMask::IN_Q_OVERFLOW,
+            cookie: 0,
+            name: None,
+        }
+    }
+
+    /// The length of the name field, which includes a nul terminator and padding so that the
+    /// next event is aligned.
+    fn name_len(&self) -> usize {
+        match &self.name {
+            Some(name) => (name.len() + 1).next_multiple_of(Self::HEADER_LEN),
+            None => 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        Self::HEADER_LEN + self.name_len()
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let name_len = self.name_len();
+
+        let mut bytes = Vec::with_capacity(self.len());
+        bytes.extend_from_slice(&self.wd.to_ne_bytes());
+        bytes.extend_from_slice(&self.mask.bits().to_ne_bytes());
+        bytes.extend_from_slice(&self.cookie.to_ne_bytes());
+        bytes.extend_from_slice(&u32::try_from(name_len).unwrap().to_ne_bytes());
+        if let Some(name) = &self.name {
+            bytes.extend_from_slice(name.as_bytes());
+            bytes.resize(Self::HEADER_LEN + name_len, 0);
+        }
+
+        debug_assert_eq!(bytes.len(), self.len());
+        bytes
+    }
+}
+
+pub struct Inotify {
+    // Ordered by watch descriptor so that events are queued in a deterministic order.
+    watches: BTreeMap<std::ffi::c_int, Watch>,
+    next_wd: std::ffi::c_int,
+    events: VecDeque<Event>,
+    event_source: StateEventSource,
+    state: FileState,
+    status: FileStatus,
+    // should only be used by `OpenFile` to make sure there is only ever one `OpenFile` instance for
+    // this file
+    has_open_file: bool,
+}
+
+impl Inotify {
+    pub fn new(status: FileStatus) -> Self {
+        Self {
+            watches: BTreeMap::new(),
+            next_wd: 1,
+            events: VecDeque::new(),
+            event_source: StateEventSource::new(),
+            state: FileState::ACTIVE,
+            status,
+            has_open_file: false,
+        }
+    }
+
+    pub fn status(&self) -> FileStatus {
+        self.status
+    }
+
+    pub fn set_status(&mut self, status: FileStatus) {
+        self.status = status;
+    }
+
+    pub fn mode(&self) -> FileMode {
+        FileMode::READ
+    }
+
+    pub fn has_open_file(&self) -> bool {
+        self.has_open_file
+    }
+
+    pub fn supports_sa_restart(&self) -> bool {
+        true
+    }
+
+    pub fn set_has_open_file(&mut self, val: bool) {
+        self.has_open_file = val;
+    }
+
+    /// Add a watch for the absolute path `path`, or modify the existing watch for that path.
+    /// Returns the watch descriptor.
+    pub fn add_watch(&mut self, path: &Path, mask: InotifyMask) -> Result<std::ffi::c_int, Errno> {
+        if !mask.intersects(InotifyMask::IN_ALL_EVENTS) {
+            return Err(Errno::EINVAL);
+        }
+
+        if mask.contains(InotifyMask::IN_MASK_ADD | InotifyMask::IN_MASK_CREATE) {
+            return Err(Errno::EINVAL);
+        }
+
+        // these are only used when adding the watch, and aren't part of the watch's mask
+        let add_flags = InotifyMask::IN_ONLYDIR
+            | InotifyMask::IN_DONT_FOLLOW
+            | InotifyMask::IN_MASK_ADD
+            | InotifyMask::IN_MASK_CREATE;
+
+        let path = normalize_path(path);
+
+        if let Some((wd, watch)) = self.watches.iter_mut().find(|(_, w)| w.path == path) {
+            if mask.contains(InotifyMask::IN_MASK_CREATE) {
+                return Err(Errno::EEXIST);
+            }
+
+            if mask.contains(InotifyMask::IN_MASK_ADD) {
+                watch.mask |= mask - add_flags;
+            } else {
+                watch.mask = mask - add_flags;
+            }
+
+            return Ok(*wd);
+        }
+
+        let wd = self.next_wd;
+        self.next_wd = self.next_wd.checked_add(1).ok_or(Errno::ENOSPC)?;
+
+        let watch = Watch {
+            path,
+            mask: mask - add_flags,
+        };
+        self.watches.insert(wd, watch);
+
+        Ok(wd)
+    }
+
+    /// Remove the watch `wd`, queuing an `IN_IGNORED` event.
+    pub fn rm_watch(
+        &mut self,
+        wd: std::ffi::c_int,
+        cb_queue: &mut CallbackQueue,
+    ) -> Result<(), Errno> {
+        if self.watches.remove(&wd).is_none() {
+            return Err(Errno::EINVAL);
+        }
+
+        self.push_event(Event {
+            wd,
+            mask: InotifyMask::IN_IGNORED,
+            cookie: 0,
+            name: None,
+        });
+        self.refresh_state(FileSignals::READ_BUFFER_GREW, cb_queue);
+
+        Ok(())
+    }
+
+    /// Queue events for any watches that are interested in the event `mask` that occurred for the
+    /// absolute path `path`. The `mask` should contain a single event type, optionally with
+    /// `IN_ISDIR`.
+    ///
+    /// As on Linux, watches on the parent directory are notified before a watch on the path
+    /// itself, so for example an `IN_DELETE` event on a directory is queued before the
+    /// `IN_DELETE_SELF` event on the deleted file.
+    pub fn notify(
+        &mut self,
+        path: &Path,
+        mask: InotifyMask,
+        cookie: u32,
+        cb_queue: &mut CallbackQueue,
+    ) {
+        if self.state.contains(FileState::CLOSED) {
+            return;
+        }
+
+        let path = normalize_path(path);
+        let is_dir = mask & InotifyMask::IN_ISDIR;
+        let event_type = mask & InotifyMask::IN_ALL_EVENTS;
+
+        let events_before = self.events.len();
+        let mut to_remove = Vec::new();
+
+        // events for the watched directory containing `path`
+        if let (Some(parent), Some(name)) = (path.parent(), path.file_name()) {
+            for (wd, watch) in &self.watches {
+                if watch.path != parent || !watch.mask.intersects(event_type) {
+                    continue;
+                }
+
+                let event = Event {
+                    wd: *wd,
+                    mask: event_type | is_dir,
+                    cookie,
+                    name: Some(name.to_owned()),
+                };
+
+                if watch.mask.contains(InotifyMask::IN_ONESHOT) {
+                    to_remove.push(*wd);
+                }
+
+                Self::push_event_to(&mut self.events, event);
+            }
+        }
+
+        // events for the watch on `path` itself
+        let self_event = if event_type.intersects(InotifyMask::IN_CREATE | InotifyMask::IN_MOVED_TO)
+        {
+            None
+        } else if event_type == InotifyMask::IN_DELETE {
+            Some(InotifyMask::IN_DELETE_SELF)
+        } else if event_type == InotifyMask::IN_MOVED_FROM {
+            Some(InotifyMask::IN_MOVE_SELF)
+        } else {
+            Some(event_type | is_dir)
+        };
+
+        if let Some(self_event) = self_event {
+            for (wd, watch) in &self.watches {
+                if watch.path != path {
+                    continue;
+                }
+
+                if watch.mask.intersects(self_event) {
+                    Self::push_event_to(
+                        &mut self.events,
+                        Event {
+                            wd: *wd,
+                            mask: self_event,
+                            cookie: 0,
+                            name: None,
+                        },
+                    );
+
+                    if watch.mask.contains(InotifyMask::IN_ONESHOT) {
+                        to_remove.push(*wd);
+                    }
+                }
+
+                // the watched file no longer exists, so the watch is removed
+                if self_event == InotifyMask::IN_DELETE_SELF {
+                    to_remove.push(*wd);
+                }
+            }
+        }
+
+        to_remove.sort();
+        to_remove.dedup();
+        for wd in to_remove {
+            self.watches.remove(&wd);
+            self.push_event(Event {
+                wd,
+                mask: InotifyMask::IN_IGNORED,
+                cookie: 0,
+                name: None,
+            });
+        }
+
+        if self.events.len() != events_before {
+            self.refresh_state(FileSignals::READ_BUFFER_GREW, cb_queue);
+        }
+    }
+
+    fn push_event(&mut self, event: Event) {
+        Self::push_event_to(&mut self.events, event);
+    }
+
+    fn push_event_to(events: &mut VecDeque<Event>, event: Event) {
+        // inotify(7): "If successive output inotify events produced on the inotify file descriptor
+        // are identical (same wd, mask, cookie, and name), then they are coalesced into a single
+        // event"
+        if events.back() == Some(&event) {
+            return;
+        }
+
+        if events.len() >= MAX_QUEUED_EVENTS {
+            if events.back() != Some(&Event::overflow()) {
+                events.push_back(Event::overflow());
+            }
+            return;
+        }
+
+        events.push_back(event);
+    }
+
+    pub fn readv(
+        &mut self,
+        iovs: &[IoVec],
+        offset: Option<kernel_off_t>,
+        _flags: std::ffi::c_int,
+        mem: &mut MemoryManager,
+        cb_queue: &mut CallbackQueue,
+    ) -> Result<isize, SyscallError> {
+        // inotify instances don't support seeking
+        if offset.is_some() {
+            return Err(Errno::ESPIPE.into());
+        }
+
+        let Some(first) = self.events.front() else {
+            log::trace!("Inotify has no events to read");
+            return Err(Errno::EWOULDBLOCK.into());
+        };
+
+        let len: usize = iovs.iter().map(|x| x.len).sum();
+
+        // inotify(7): "The behavior when the buffer given to read(2) is too small to return
+        // information about the next event depends on the kernel version: since 2.6.21, read(2)
+        // returns the error EINVAL."
+        if len < first.len() {
+            log::trace!(
+                "Reading from inotify requires a buffer of at least {} bytes",
+                first.len()
+            );
+            return Err(Errno::EINVAL.into());
+        }
+
+        // return as many complete events as will fit in the buffer
+        let mut bytes = Vec::new();
+        while let Some(event) = self.events.front() {
+            if bytes.len() + event.len() > len {
+                break;
+            }
+            bytes.extend(self.events.pop_front().unwrap().to_bytes());
+        }
+
+        let mut writer = IoVecWriter::new(iovs, mem);
+        writer.write_all(&bytes)?;
+
+        self.refresh_state(FileSignals::empty(), cb_queue);
+
+        Ok(bytes.len().try_into().unwrap())
+    }
+
+    pub fn writev(
+        &mut self,
+        _iovs: &[IoVec],
+        _offset: Option<kernel_off_t>,
+        _flags: std::ffi::c_int,
+        _mem: &mut MemoryManager,
+        _cb_queue: &mut CallbackQueue,
+    ) -> Result<isize, SyscallError> {
+        // inotify instances don't support writing
+        Err(Errno::EINVAL.into())
+    }
+
+    pub fn close(&mut self, cb_queue: &mut CallbackQueue) -> Result<(), SyscallError> {
+        self.watches.clear();
+        self.events.clear();
+
+        // set the closed flag and remove the active and readable flags
+        self.update_state(
+            FileState::CLOSED | FileState::ACTIVE | FileState::READABLE,
+            FileState::CLOSED,
+            FileSignals::empty(),
+            cb_queue,
+        );
+
+        Ok(())
+    }
+
+    pub fn ioctl(
+        &mut self,
+        request: IoctlRequest,
+        arg_ptr: ForeignPtr<()>,
+        mem: &mut MemoryManager,
+    ) -> SyscallResult {
+        match request {
+            IoctlRequest::FIONREAD => {
+                let len: usize = self.events.iter().map(Event::len).sum();
+                let len: libc::c_int = len.try_into().unwrap();
+
+                let arg_ptr = arg_ptr.cast::<libc::c_int>();
+                mem.write(arg_ptr, &len)?;
+
+                Ok(0.into())
+            }
+            request => {
+                warn_once_then_debug!("We do not yet handle ioctl request {request:?} on inotify");
+                Err(Errno::ENOTTY.into())
+            }
+        }
+    }
+
+    pub fn stat(&self) -> Result<linux_api::stat::stat, SyscallError> {
+        warn_once_then_debug!("We do not yet handle stat calls on inotify");
+        Err(Errno::EINVAL.into())
+    }
+
+    pub fn add_listener(
+        &mut self,
+        monitoring_state: FileState,
+        monitoring_signals: FileSignals,
+        filter: StateListenerFilter,
+        notify_fn: impl Fn(FileState, FileState, FileSignals, &mut CallbackQueue)
+        + Send
+        + Sync
+        + 'static,
+    ) -> StateListenHandle {
+        self.event_source
+            .add_listener(monitoring_state, monitoring_signals, filter, notify_fn)
+    }
+
+    pub fn add_legacy_listener(&mut self, ptr: HostTreePointer<c::StatusListener>) {
+        self.event_source.add_legacy_listener(ptr);
+    }
+
+    pub fn remove_legacy_listener(&mut self, ptr: *mut c::StatusListener) {
+        self.event_source.remove_legacy_listener(ptr);
+    }
+
+    pub fn state(&self) -> FileState {
+        self.state
+    }
+
+    fn refresh_state(&mut self, signals: FileSignals, cb_queue: &mut CallbackQueue) {
+        if self.state.contains(FileState::CLOSED) {
+            return;
+        }
+
+        let readable = if self.events.is_empty() {
+            FileState::empty()
+        } else {
+            FileState::READABLE
+        };
+
+        self.update_state(FileState::READABLE, readable, signals, cb_queue);
+    }
+
+    fn update_state(
+        &mut self,
+        mask: FileState,
+        state: FileState,
+        signals: FileSignals,
+        cb_queue: &mut CallbackQueue,
+    ) {
+        let old_state = self.state;
+
+        // remove the masked flags, then copy the masked flags
+        self.state.remove(mask);
+        self.state.insert(state & mask);
+
+        self.handle_state_change(old_state, signals, cb_queue);
+    }
+
+    fn handle_state_change(
+        &mut self,
+        old_state: FileState,
+        signals: FileSignals,
+        cb_queue: &mut CallbackQueue,
+    ) {
+        let states_changed = self.state ^ old_state;
+
+        // if nothing changed
+        if states_changed.is_empty() && signals.is_empty() {
+            return;
+        }
+
+        self.event_source
+            .notify_listeners(self.state, states_changed, signals, cb_queue);
+    }
+}
+
+/// Lexically remove `.` and `..` components and redundant separators from an absolute path. Shadow
+/// builds paths by joining the working directory and the path given by the process, so the same
+/// file may be referred to by different path strings.
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => normalized.push(component),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::Normal(x) => normalized.push(x),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize() {
+        assert_eq!(normalize_path(Path::new("/a/b/c")), Path::new("/a/b/c"));
+        assert_eq!(normalize_path(Path::new("/a/./b//c/")), Path::new("/a/b/c"));
+        assert_eq!(normalize_path(Path::new("/a/b/../c")), Path::new("/a/c"));
+        assert_eq!(normalize_path(Path::new("/../a")), Path::new("/a"));
+        assert_eq!(normalize_path(Path::new("/")), Path::new("/"));
+    }
+
+    #[test]
+    fn event_len() {
+        let mut event = Event {
+            wd: 1,
+            mask: InotifyMask::IN_CREATE,
+            cookie: 0,
+            name: None,
+        };
+        assert_eq!(event.len(), 16);
+        assert_eq!(event.to_bytes().len(), 16);
+
+        // the name is nul-terminated and padded to a multiple of the header size
+        event.name = Some("file".into());
+        assert_eq!(event.len(), 32);
+
+        event.name = Some("fifteen-bytes!!".into());
+        assert_eq!(event.len(), 32);
+
+        event.name = Some("sixteen-bytes!!!".into());
+        assert_eq!(event.len(), 48);
+
+        let bytes = event.to_bytes();
+        assert_eq!(bytes.len(), 48);
+        assert_eq!(&bytes[0..4], &1i32.to_ne_bytes());
+        assert_eq!(&bytes[12..16], &32u32.to_ne_bytes());
+        assert_eq!(&bytes[16..32], b"sixteen-bytes!!!");
+        assert!(bytes[32..].iter().all(|x| *x == 0));
+    }
+
+    #[test]
+    fn coalesce_and_overflow() {
+        let event = Event {
+            wd: 1,
+            mask: InotifyMask::IN_MODIFY,
+            cookie: 0,
+            name: Some("file".into()),
+        };
+
+        let mut events = VecDeque::new();
+        Inotify::push_event_to(&mut events, event.clone());
+        Inotify::push_event_to(&mut events, event.clone());
+        assert_eq!(events.len(), 1);
+
+        for i in 0..MAX_QUEUED_EVENTS + 10 {
+            Inotify::push_event_to(
+                &mut events,
+                Event {
+                    cookie: i.try_into().unwrap(),
+                    ..event.clone()
+                },
+            );
+        }
+        assert_eq!(events.len(), MAX_QUEUED_EVENTS + 1);
+        assert_eq!(events.back(), Some(&Event::overflow()));
+    }
+}
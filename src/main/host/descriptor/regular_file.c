@@ -14,6 +14,7 @@
 #include <stdlib.h>
 #include <string.h>
 #include <sys/file.h>
+#include <sys/inotify.h>
 #include <sys/ioctl.h>
 #include <sys/stat.h>
 #include <sys/syscall.h>
@@ -138,6 +139,22 @@ static inline bool _fd_isValid(int fd) { return fd >= 0; }
 
 int regularfile_getOSBackedFD(RegularFile* file) { return _regularfile_getOSBackedFD(file); }
 
+/* Notify the host's inotify instances of the event `mask` on an open file. */
+static void _regularfile_notify(RegularFile* file, const Host* host, uint32_t mask) {
+    if (file->type != FILE_TYPE_REGULAR || !file->osfile.absPathAtOpen) {
+        return;
+    }
+
+    if (_fd_isValid(file->osfile.fd)) {
+        struct stat statbuf;
+        if (fstat(file->osfile.fd, &statbuf) == 0 && S_ISDIR(statbuf.st_mode)) {
+            mask |= IN_ISDIR;
+        }
+    }
+
+    host_notifyFileEvent(host, file->osfile.absPathAtOpen, mask);
+}
+
 static void _regularfile_closeHelper(RegularFile* file) {
     if(file && file->type != FILE_TYPE_IN_MEMORY) {
         if (file && _fd_isValid(file->osfile.fd)) {
@@ -157,6 +174,13 @@ static void _regularfile_close(LegacyFile* desc, const Host* host) {
 
     trace("Closing file %p with os-backed file %i", file, _regularfile_getOSBackedFD(file));
 
+    if (file->type != FILE_TYPE_IN_MEMORY && _fd_isValid(file->osfile.fd)) {
+        int accmode = file->osfile.flagsAtOpen & O_ACCMODE;
+        _regularfile_notify(file, host,
+                            (accmode == O_WRONLY || accmode == O_RDWR) ? IN_CLOSE_WRITE
+                                                                       : IN_CLOSE_NOWRITE);
+    }
+
     /* Make sure we mimic the close on the OS-backed file now. */
     _regularfile_closeHelper(file);
 }
@@ -233,6 +257,14 @@ static char* _regularfile_getAbsolutePath(RegularFile* dir, const char* pathname
     return abspath;
 }
 
+/* Notify the current host's inotify instances of the event `mask` on the file at `pathname`. */
+static void _regularfile_notifyAt(RegularFile* dir, const char* pathname, const char* workingDir,
+                                  uint32_t mask) {
+    char* abspath = _regularfile_getAbsolutePath(dir, pathname, workingDir);
+    host_notifyFileEvent(worker_getCurrentHost(), abspath, mask);
+    free(abspath);
+}
+
 #ifdef DEBUG
 #define CHECK_FLAG(flag)                                                                           \
     if (flags & flag) {                                                                            \
@@ -433,6 +465,10 @@ int regularfile_openat(RegularFile* file, RegularFile* dir, const char* pathname
     // we should always use O_CLOEXEC for files opened in shadow
     flags |= O_CLOEXEC;
 
+    /* Check whether the file exists so that we know if opening it will create it. */
+    struct stat statbuf;
+    bool existed = !(flags & O_CREAT) || lstat(abspath, &statbuf) == 0;
+
     // TODO: we should open the os-backed file in non-blocking mode even if a
     // non-block is not requested, and then properly handle the io by, e.g.,
     // epolling on all such files with a shadow support thread.
@@ -461,6 +497,14 @@ int regularfile_openat(RegularFile* file, RegularFile* dir, const char* pathname
     /* The os-backed file is now ready. */
     legacyfile_adjustStatus(&file->super, FileState_ACTIVE, TRUE, 0);
 
+    int accmode = flags & O_ACCMODE;
+    if ((flags & O_CREAT) && !existed) {
+        _regularfile_notify(file, worker_getCurrentHost(), IN_CREATE);
+    } else if ((flags & O_TRUNC) && (accmode == O_WRONLY || accmode == O_RDWR)) {
+        _regularfile_notify(file, worker_getCurrentHost(), IN_MODIFY);
+    }
+    _regularfile_notify(file, worker_getCurrentHost(), IN_OPEN);
+
     return 0;
 }
 
@@ -646,7 +690,13 @@ ssize_t regularfile_write(RegularFile* file, const void* buf, size_t bufSize) {
     /* TODO: this may block the shadow thread until we properly handle
      * os-backed files in non-blocking mode. */
     ssize_t result = write(_regularfile_getOSBackedFD(file), buf, bufSize);
-    return (result < 0) ? -errno : result;
+    if (result < 0) {
+        return -errno;
+    }
+    if (result > 0) {
+        _regularfile_notify(file, worker_getCurrentHost(), IN_MODIFY);
+    }
+    return result;
 }
 
 ssize_t regularfile_pwrite(RegularFile* file, const void* buf, size_t bufSize, off_t offset) {
@@ -666,7 +716,13 @@ ssize_t regularfile_pwrite(RegularFile* file, const void* buf, size_t bufSize, o
     /* TODO: this may block the shadow thread until we properly handle
      * os-backed files in non-blocking mode. */
     ssize_t result = pwrite(_regularfile_getOSBackedFD(file), buf, bufSize, offset);
-    return (result < 0) ? -errno : result;
+    if (result < 0) {
+        return -errno;
+    }
+    if (result > 0) {
+        _regularfile_notify(file, worker_getCurrentHost(), IN_MODIFY);
+    }
+    return result;
 }
 
 ssize_t regularfile_pwritev(RegularFile* file, const struct iovec* iov, int iovcnt, off_t offset) {
@@ -686,7 +742,13 @@ ssize_t regularfile_pwritev(RegularFile* file, const struct iovec* iov, int iovc
     /* TODO: this may block the shadow thread until we properly handle
      * os-backed files in non-blocking mode. */
     ssize_t result = pwritev(_regularfile_getOSBackedFD(file), iov, iovcnt, offset);
-    return (result < 0) ? -errno : result;
+    if (result < 0) {
+        return -errno;
+    }
+    if (result > 0) {
+        _regularfile_notify(file, worker_getCurrentHost(), IN_MODIFY);
+    }
+    return result;
 }
 
 #ifdef SYS_pwritev2
@@ -709,7 +771,13 @@ ssize_t regularfile_pwritev2(RegularFile* file, const struct iovec* iov, int iov
      * os-backed files in non-blocking mode. */
     ssize_t result =
         pwritev2(_regularfile_getOSBackedFD(file), iov, iovcnt, offset, flags);
-    return (result < 0) ? -errno : result;
+    if (result < 0) {
+        return -errno;
+    }
+    if (result > 0) {
+        _regularfile_notify(file, worker_getCurrentHost(), IN_MODIFY);
+    }
+    return result;
 }
 #endif
 
@@ -788,7 +856,11 @@ int regularfile_ftruncate(RegularFile* file, off_t length) {
     trace("RegularFile %p ftruncate os-backed file %i", file, _regularfile_getOSBackedFD(file));
 
     int result = ftruncate(_regularfile_getOSBackedFD(file), length);
-    return (result < 0) ? -errno : result;
+    if (result < 0) {
+        return -errno;
+    }
+    _regularfile_notify(file, worker_getCurrentHost(), IN_MODIFY);
+    return result;
 }
 
 int regularfile_fallocate(RegularFile* file, int mode, off_t offset, off_t length) {
@@ -801,7 +873,11 @@ int regularfile_fallocate(RegularFile* file, int mode, off_t offset, off_t lengt
     trace("RegularFile %p fallocate os-backed file %i", file, _regularfile_getOSBackedFD(file));
 
     int result = fallocate(_regularfile_getOSBackedFD(file), mode, offset, length);
-    return (result < 0) ? -errno : result;
+    if (result < 0) {
+        return -errno;
+    }
+    _regularfile_notify(file, worker_getCurrentHost(), IN_MODIFY);
+    return result;
 }
 
 int regularfile_fadvise(RegularFile* file, off_t offset, off_t len, int advice) {
@@ -1291,7 +1367,11 @@ int regularfile_mkdirat(RegularFile* dir, const char* pathname, mode_t mode,
         free((char*)pathnameTmp);
     }
 
-    return (result < 0) ? -errno : result;
+    if (result < 0) {
+        return -errno;
+    }
+    _regularfile_notifyAt(dir, pathname, workingDir, IN_CREATE | IN_ISDIR);
+    return result;
 }
 
 int regularfile_mknodat(RegularFile* dir, const char* pathname, mode_t mode, dev_t dev,
@@ -1324,7 +1404,11 @@ int regularfile_mknodat(RegularFile* dir, const char* pathname, mode_t mode, dev
         free((char*)pathnameTmp);
     }
 
-    return (result < 0) ? -errno : result;
+    if (result < 0) {
+        return -errno;
+    }
+    _regularfile_notifyAt(dir, pathname, workingDir, IN_CREATE);
+    return result;
 }
 
 int regularfile_linkat(RegularFile* oldDir, const char* oldPath, RegularFile* newDir,
@@ -1367,7 +1451,11 @@ int regularfile_linkat(RegularFile* oldDir, const char* oldPath, RegularFile* ne
         free((char*)newPathTmp);
     }
 
-    return (result < 0) ? -errno : result;
+    if (result < 0) {
+        return -errno;
+    }
+    _regularfile_notifyAt(newDir, newPath, workingDir, IN_CREATE);
+    return result;
 }
 
 int regularfile_unlinkat(RegularFile* dir, const char* pathname, int flags,
@@ -1401,7 +1489,12 @@ int regularfile_unlinkat(RegularFile* dir, const char* pathname, int flags,
         free((char*)pathnameTmp);
     }
 
-    return (result < 0) ? -errno : result;
+    if (result < 0) {
+        return -errno;
+    }
+    _regularfile_notifyAt(
+        dir, pathname, workingDir, IN_DELETE | ((flags & AT_REMOVEDIR) ? IN_ISDIR : 0));
+    return result;
 }
 
 int regularfile_symlinkat(RegularFile* dir, const char* linkpath, const char* target,
@@ -1435,7 +1528,11 @@ int regularfile_symlinkat(RegularFile* dir, const char* linkpath, const char* ta
         free((char*)linkpathTmp);
     }
 
-    return (result < 0) ? -errno : result;
+    if (result < 0) {
+        return -errno;
+    }
+    _regularfile_notifyAt(dir, linkpath, workingDir, IN_CREATE);
+    return result;
 }
 
 ssize_t regularfile_readlinkat(RegularFile* dir, const char* pathname, char* buf, size_t bufsize,
@@ -1519,7 +1616,19 @@ int regularfile_renameat2(RegularFile* oldDir, const char* oldPath, RegularFile*
         free((char*)newPathTmp);
     }
 
-    return (result < 0) ? -errno : result;
+    if (result < 0) {
+        return -errno;
+    }
+
+    char* oldAbsPath = _regularfile_getAbsolutePath(oldDir, oldPath, workingDir);
+    char* newAbsPath = _regularfile_getAbsolutePath(newDir, newPath, workingDir);
+    struct stat statbuf;
+    bool isDir = lstat(newAbsPath, &statbuf) == 0 && S_ISDIR(statbuf.st_mode);
+    host_notifyFileMoved(worker_getCurrentHost(), oldAbsPath, newAbsPath, isDir);
+    free(oldAbsPath);
+    free(newAbsPath);
+
+    return result;
 }
 
 #ifdef SYS_statx
@@ -0,0 +1,137 @@
+//! Address translation for IPv6 inet sockets.
+//!
+//! Shadow's network is IPv4-only internally. Each host is assigned an IPv6 address which is an
+//! alias of its IPv4 address, and IPv6 sockets translate the IPv6 addresses given to them by the
+//! managed process into the corresponding IPv4 addresses (and back again when returning addresses
+//! to the managed process).
+
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddrV4, SocketAddrV6};
+
+use crate::core::worker::Worker;
+
+/// Translate an IPv6 address to the IPv4 address that it's an alias of. The unspecified and
+/// loopback addresses, and IPv4-mapped addresses, are translated directly. All other addresses are
+/// looked up using `lookup`. Returns `None` if the address does not have an IPv4 equivalent.
+pub fn to_ipv4(
+    addr: Ipv6Addr,
+    lookup: impl FnOnce(Ipv6Addr) -> Option<Ipv4Addr>,
+) -> Option<Ipv4Addr> {
+    if addr.is_unspecified() {
+        Some(Ipv4Addr::UNSPECIFIED)
+    } else if addr.is_loopback() {
+        Some(Ipv4Addr::LOCALHOST)
+    } else if let Some(addr) = addr.to_ipv4_mapped() {
+        Some(addr)
+    } else {
+        lookup(addr)
+    }
+}
+
+/// Translate an IPv4 address to the IPv6 address that a managed process should see. Addresses
+/// which don't have an IPv6 alias are returned as IPv4-mapped addresses.
+pub fn to_ipv6(addr: Ipv4Addr, lookup: impl FnOnce(Ipv4Addr) -> Option<Ipv6Addr>) -> Ipv6Addr {
+    ipv6_alias(addr, lookup).unwrap_or_else(|| {
+        if addr.is_unspecified() {
+            Ipv6Addr::UNSPECIFIED
+        } else {
+            addr.to_ipv6_mapped()
+        }
+    })
+}
+
+/// The IPv6 address that `addr` is an alias of, if any. Only the IPv4 loopback address and host
+/// addresses (as given by `lookup`) have IPv6 aliases.
+pub fn ipv6_alias(
+    addr: Ipv4Addr,
+    lookup: impl FnOnce(Ipv4Addr) -> Option<Ipv6Addr>,
+) -> Option<Ipv6Addr> {
+    if addr == Ipv4Addr::LOCALHOST {
+        Some(Ipv6Addr::LOCALHOST)
+    } else if addr.is_unspecified() || addr.is_loopback() {
+        None
+    } else {
+        lookup(addr)
+    }
+}
+
+/// Translate an IPv6 socket address to an IPv4 socket address using the simulation's DNS.
+pub fn sockaddr_to_ipv4(addr: SocketAddrV6) -> Option<SocketAddrV4> {
+    let ip = to_ipv4(*addr.ip(), Worker::resolve_ipv6_to_ipv4)?;
+    Some(SocketAddrV4::new(ip, addr.port()))
+}
+
+/// Translate an IPv4 socket address to an IPv6 socket address using the simulation's DNS.
+pub fn sockaddr_to_ipv6(addr: SocketAddrV4) -> SocketAddrV6 {
+    let ip = to_ipv6(*addr.ip(), Worker::resolve_ipv4_to_ipv6);
+    SocketAddrV6::new(ip, addr.port(), 0, 0)
+}
+
+/// The IPv6 addresses to use for a packet sent between the IPv4 addresses `src` and `dst`, if both
+/// addresses have IPv6 aliases.
+pub fn packet_addresses(src: Ipv4Addr, dst: Ipv4Addr) -> Option<(Ipv6Addr, Ipv6Addr)> {
+    let src = ipv6_alias(src, Worker::resolve_ipv4_to_ipv6)?;
+    let dst = ipv6_alias(dst, Worker::resolve_ipv4_to_ipv6)?;
+    Some((src, dst))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOST_V4: Ipv4Addr = Ipv4Addr::new(11, 0, 0, 1);
+    const HOST_V6: Ipv6Addr = Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 1);
+
+    fn lookup_v4(addr: Ipv6Addr) -> Option<Ipv4Addr> {
+        (addr == HOST_V6).then_some(HOST_V4)
+    }
+
+    fn lookup_v6(addr: Ipv4Addr) -> Option<Ipv6Addr> {
+        (addr == HOST_V4).then_some(HOST_V6)
+    }
+
+    #[test]
+    fn test_to_ipv4() {
+        assert_eq!(
+            to_ipv4(Ipv6Addr::UNSPECIFIED, lookup_v4),
+            Some(Ipv4Addr::UNSPECIFIED)
+        );
+        assert_eq!(
+            to_ipv4(Ipv6Addr::LOCALHOST, lookup_v4),
+            Some(Ipv4Addr::LOCALHOST)
+        );
+        assert_eq!(
+            to_ipv4(Ipv4Addr::new(1, 2, 3, 4).to_ipv6_mapped(), lookup_v4),
+            Some(Ipv4Addr::new(1, 2, 3, 4))
+        );
+        assert_eq!(to_ipv4(HOST_V6, lookup_v4), Some(HOST_V4));
+        assert_eq!(
+            to_ipv4(Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 2), lookup_v4),
+            None
+        );
+    }
+
+    #[test]
+    fn test_to_ipv6() {
+        assert_eq!(
+            to_ipv6(Ipv4Addr::UNSPECIFIED, lookup_v6),
+            Ipv6Addr::UNSPECIFIED
+        );
+        assert_eq!(to_ipv6(Ipv4Addr::LOCALHOST, lookup_v6), Ipv6Addr::LOCALHOST);
+        assert_eq!(
+            to_ipv6(Ipv4Addr::new(127, 0, 0, 2), lookup_v6),
+            Ipv4Addr::new(127, 0, 0, 2).to_ipv6_mapped()
+        );
+        assert_eq!(to_ipv6(HOST_V4, lookup_v6), HOST_V6);
+        assert_eq!(
+            to_ipv6(Ipv4Addr::new(1, 2, 3, 4), lookup_v6),
+            Ipv4Addr::new(1, 2, 3, 4).to_ipv6_mapped()
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for addr in [Ipv4Addr::UNSPECIFIED, Ipv4Addr::LOCALHOST, HOST_V4] {
+            assert_eq!(to_ipv4(to_ipv6(addr, lookup_v6), lookup_v4), Some(addr));
+        }
+    }
+}
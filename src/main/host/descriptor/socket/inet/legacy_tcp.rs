@@ -13,10 +13,11 @@ use shadow_shim_helper_rs::syscall_types::ForeignPtr;
 use crate::core::worker::Worker;
 use crate::cshadow as c;
 use crate::host::descriptor::listener::{StateListenHandle, StateListenerFilter};
-use crate::host::descriptor::socket::inet::{self, InetSocket};
+use crate::host::descriptor::socket::inet::{self, InetFamily, InetSocket};
 use crate::host::descriptor::socket::{RecvmsgArgs, RecvmsgReturn, SendmsgArgs, Socket};
 use crate::host::descriptor::{
     CompatFile, File, FileMode, FileSignals, FileState, FileStatus, OpenFile, SyscallResult,
+    tcp_cong,
 };
 use crate::host::host::Host;
 use crate::host::memory_manager::MemoryManager;
@@ -132,6 +133,23 @@ impl LegacyTcpSocket {
         self.has_open_file = val;
     }
 
+    /// The family is stored in the legacy [`c::TCP`] object so that child sockets created by the
+    /// legacy TCP code have the same family as the listening socket.
+    pub fn family(&self) -> InetFamily {
+        let tcp = self.as_legacy_tcp();
+        let ipv6 = unsafe { c::tcp_isIpv6(tcp) } != 0;
+        let v6only = unsafe { c::tcp_isV6Only(tcp) } != 0;
+        InetFamily::from_legacy(ipv6, v6only)
+    }
+
+    pub fn set_family(&mut self, family: InetFamily) {
+        let (ipv6, v6only) = match family {
+            InetFamily::Ipv4 => (false, false),
+            InetFamily::Ipv6 { v6only } => (true, v6only),
+        };
+        unsafe { c::tcp_setFamily(self.as_legacy_tcp(), ipv6.into(), v6only.into()) };
+    }
+
     pub fn push_in_packet(
         &mut self,
         packet: PacketRc,
@@ -188,6 +206,10 @@ impl LegacyTcpSocket {
         self.peek_packet().is_some()
     }
 
+    pub fn is_listening(&self) -> bool {
+        unsafe { c::tcp_isValidListener(self.as_legacy_tcp()) == 1 }
+    }
+
     pub fn getsockname(&self) -> Result<Option<SockaddrIn>, Errno> {
         let mut ip: libc::in_addr_t = 0;
         let mut port: libc::in_port_t = 0;
@@ -225,7 +247,7 @@ impl LegacyTcpSocket {
     }
 
     pub fn address_family(&self) -> linux_api::socket::AddressFamily {
-        linux_api::socket::AddressFamily::AF_INET
+        self.family.address_family()
     }
 
     pub fn close(&mut self, _cb_queue: &mut CallbackQueue) -> Result<(), SyscallError> {
@@ -276,10 +298,12 @@ impl LegacyTcpSocket {
 
         // this will allow us to receive packets from any peer
         let peer_addr = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0);
+        let versions = socket.borrow().family().ip_versions();
 
         // associate the socket
         let (addr, handle) = inet::associate_socket(
             InetSocket::LegacyTcp(Arc::clone(socket)),
+            versions,
             addr,
             peer_addr,
             /* check_generic_peer= */ true,
@@ -669,6 +693,7 @@ impl LegacyTcpSocket {
             // associate the socket
             let (local_addr, handle) = super::associate_socket(
                 super::InetSocket::LegacyTcp(socket.clone()),
+                socket_ref.family().ip_versions(),
                 local_addr,
                 peer_addr,
                 /* check_generic_peer= */ true,
@@ -779,6 +804,7 @@ impl LegacyTcpSocket {
             // associate the socket
             let (local_addr, handle) = super::associate_socket(
                 super::InetSocket::LegacyTcp(socket.clone()),
+                socket_ref.family().ip_versions(),
                 local_addr,
                 peer_addr,
                 /* check_generic_peer= */ true,
@@ -1010,8 +1036,10 @@ impl LegacyTcpSocket {
                 debug_assert_ne!(!child_local_addr.port(), 0);
             }
 
+            // the legacy TCP code gave the new socket the same family as this listening socket
             let (_addr, handle) = inet::associate_socket(
                 InetSocket::LegacyTcp(Arc::clone(new_socket)),
+                self.family().ip_versions(),
                 SocketAddrV4::from(child_local_addr),
                 SocketAddrV4::from(child_peer_addr),
                 /* Allow the parent/listening socket to be bound to the same address,
@@ -1156,7 +1184,7 @@ impl LegacyTcpSocket {
                 Ok(bytes_written as libc::socklen_t)
             }
             (libc::SOL_SOCKET, libc::SO_DOMAIN) => {
-                let domain = libc::AF_INET;
+                let domain = self.family.domain();
 
                 let optval_ptr = optval_ptr.cast::<libc::c_int>();
                 let bytes_written =
@@ -1267,14 +1295,15 @@ impl LegacyTcpSocket {
                     .map(|x| &name[..x])
                     .unwrap_or(name);
 
-                let reno = unsafe { CStr::from_ptr(c::TCP_CONG_RENO_NAME) }.to_bytes();
-
-                if name != reno {
-                    log::warn!("Shadow sockets only support '{reno:?}' for TCP_CONGESTION");
+                let Some(algorithm) = tcp_cong::algorithm_from_name(name) else {
+                    log::warn!(
+                        "Shadow sockets don't support '{}' for TCP_CONGESTION",
+                        String::from_utf8_lossy(name)
+                    );
                     return Err(Errno::ENOENT.into());
-                }
+                };
 
-                // shadow doesn't support other congestion types, so do nothing
+                unsafe { tcp_cong::set_algorithm(self.as_legacy_tcp(), algorithm) };
             }
             (libc::SOL_SOCKET, libc::SO_SNDBUF) => {
                 type OptType = libc::c_int;
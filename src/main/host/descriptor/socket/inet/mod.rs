@@ -1,4 +1,4 @@
-use std::net::{Ipv4Addr, SocketAddrV4};
+use std::net::{Ipv4Addr, SocketAddrV4, SocketAddrV6};
 use std::sync::{Arc, Weak};
 
 use atomic_refcell::AtomicRefCell;
@@ -15,9 +15,9 @@ use crate::host::descriptor::{
     FileMode, FileSignals, FileState, FileStatus, OpenFile, SyscallResult,
 };
 use crate::host::memory_manager::MemoryManager;
-use crate::host::network::interface::FifoPacketPriority;
+use crate::host::network::interface::{FifoPacketPriority, IpVersions};
 use crate::host::network::namespace::{AssociationHandle, NetworkNamespace};
-use crate::host::syscall::io::IoVec;
+use crate::host::syscall::io::{IoVec, write_partial};
 use crate::host::syscall::types::SyscallError;
 use crate::network::packet::{IanaProtocol, PacketRc};
 use crate::utility::HostTreePointer;
@@ -28,10 +28,61 @@ use self::legacy_tcp::LegacyTcpSocket;
 use self::tcp::TcpSocket;
 use self::udp::UdpSocket;
 
+pub mod ipv6;
 pub mod legacy_tcp;
 pub mod tcp;
 pub mod udp;
 
+/// The address family of an inet socket.
+///
+/// Shadow's network only supports IPv4, so an IPv6 socket is an IPv4 socket that translates
+/// between the IPv6 addresses used by the managed process and the IPv4 addresses used by Shadow
+/// (see [`ipv6`]).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum InetFamily {
+    Ipv4,
+    Ipv6 {
+        /// The `IPV6_V6ONLY` socket option.
+        v6only: bool,
+    },
+}
+
+impl InetFamily {
+    pub fn address_family(&self) -> linux_api::socket::AddressFamily {
+        match self {
+            Self::Ipv4 => linux_api::socket::AddressFamily::AF_INET,
+            Self::Ipv6 { .. } => linux_api::socket::AddressFamily::AF_INET6,
+        }
+    }
+
+    /// The socket domain, as used with `socket()` and `SO_DOMAIN`.
+    pub fn domain(&self) -> libc::c_int {
+        match self {
+            Self::Ipv4 => libc::AF_INET,
+            Self::Ipv6 { .. } => libc::AF_INET6,
+        }
+    }
+
+    /// The family of a legacy TCP socket, which the C code stores as two flags.
+    pub fn from_legacy(ipv6: bool, v6only: bool) -> Self {
+        if ipv6 {
+            Self::Ipv6 { v6only }
+        } else {
+            Self::Ipv4
+        }
+    }
+
+    /// The IP versions of the packets that a socket of this family receives, which are also the
+    /// port namespaces that the socket's port is reserved in.
+    pub fn ip_versions(&self) -> IpVersions {
+        match self {
+            Self::Ipv4 => IpVersions::Ipv4,
+            Self::Ipv6 { v6only: true } => IpVersions::Ipv6,
+            Self::Ipv6 { v6only: false } => IpVersions::Both,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub enum InetSocket {
     LegacyTcp(Arc<AtomicRefCell<LegacyTcpSocket>>),
@@ -93,12 +144,32 @@ impl InetSocket {
         }
     }
 
+    fn family(&self) -> InetFamily {
+        match self {
+            Self::LegacyTcp(socket) => socket.borrow().family(),
+            Self::Tcp(socket) => socket.borrow().family(),
+            Self::Udp(socket) => socket.borrow().family(),
+        }
+    }
+
     pub fn bind(
         &self,
         addr: Option<&SockaddrStorage>,
         net_ns: &NetworkNamespace,
         rng: impl rand::Rng,
     ) -> Result<(), SyscallError> {
+        let addr = match (self.family(), addr) {
+            (InetFamily::Ipv6 { v6only }, Some(addr)) => {
+                // an ipv6 socket can't be bound to an ipv4 address
+                if addr.as_inet().is_some() {
+                    return Err(Errno::EINVAL.into());
+                }
+                Some(addr_to_ipv4(addr, v6only, Errno::EADDRNOTAVAIL)?)
+            }
+            (_, addr) => addr.copied(),
+        };
+        let addr = addr.as_ref();
+
         match self {
             Self::LegacyTcp(socket) => LegacyTcpSocket::bind(socket, addr, net_ns, rng),
             Self::Tcp(socket) => TcpSocket::bind(socket, addr, net_ns, rng),
@@ -129,6 +200,23 @@ impl InetSocket {
         rng: impl rand::Rng,
         cb_queue: &mut CallbackQueue,
     ) -> Result<(), SyscallError> {
+        let addr = match self.family() {
+            InetFamily::Ipv6 { v6only } => {
+                if addr.as_inet().is_some() {
+                    match self {
+                        // ipv6 tcp sockets can't connect to ipv4 addresses
+                        Self::LegacyTcp(_) | Self::Tcp(_) => return Err(Errno::EINVAL.into()),
+                        // dual-stack ipv6 udp sockets can
+                        Self::Udp(_) if v6only => return Err(Errno::EAFNOSUPPORT.into()),
+                        Self::Udp(_) => {}
+                    }
+                }
+                addr_to_ipv4(addr, v6only, Errno::ENETUNREACH)?
+            }
+            InetFamily::Ipv4 => *addr,
+        };
+        let addr = &addr;
+
         match self {
             Self::LegacyTcp(socket) => {
                 LegacyTcpSocket::connect(socket, addr, net_ns, rng, cb_queue)
@@ -140,12 +228,19 @@ impl InetSocket {
 
     pub fn sendmsg(
         &self,
-        args: SendmsgArgs,
+        mut args: SendmsgArgs,
         memory_manager: &mut MemoryManager,
         net_ns: &NetworkNamespace,
         rng: impl rand::Rng,
         cb_queue: &mut CallbackQueue,
     ) -> Result<libc::ssize_t, SyscallError> {
+        if let (InetFamily::Ipv6 { v6only }, Some(addr)) = (self.family(), &args.addr) {
+            if v6only && addr.as_inet().is_some() {
+                return Err(Errno::ENETUNREACH.into());
+            }
+            args.addr = Some(addr_to_ipv4(addr, v6only, Errno::ENETUNREACH)?);
+        }
+
         match self {
             Self::LegacyTcp(socket) => {
                 LegacyTcpSocket::sendmsg(socket, args, memory_manager, net_ns, rng, cb_queue)
@@ -165,13 +260,19 @@ impl InetSocket {
         memory_manager: &mut MemoryManager,
         cb_queue: &mut CallbackQueue,
     ) -> Result<RecvmsgReturn, SyscallError> {
-        match self {
+        let family = self.family();
+
+        let mut rv = match self {
             Self::LegacyTcp(socket) => {
                 LegacyTcpSocket::recvmsg(socket, args, memory_manager, cb_queue)
             }
             Self::Tcp(socket) => TcpSocket::recvmsg(socket, args, memory_manager, cb_queue),
             Self::Udp(socket) => UdpSocket::recvmsg(socket, args, memory_manager, cb_queue),
-        }
+        }?;
+
+        rv.addr = rv.addr.map(|addr| addr_from_ipv4(family, addr));
+
+        Ok(rv)
     }
 }
 
@@ -280,24 +381,30 @@ impl InetSocketRef<'_> {
 // socket-specific functions
 impl InetSocketRef<'_> {
     pub fn getpeername(&self) -> Result<Option<SockaddrStorage>, Errno> {
-        match self {
-            Self::LegacyTcp(socket) => socket.getpeername().map(|opt| opt.map(Into::into)),
-            Self::Tcp(socket) => socket.getpeername().map(|opt| opt.map(Into::into)),
-            Self::Udp(socket) => socket.getpeername().map(|opt| opt.map(Into::into)),
-        }
+        let addr = match self {
+            Self::LegacyTcp(socket) => socket.getpeername()?.map(Into::into),
+            Self::Tcp(socket) => socket.getpeername()?.map(Into::into),
+            Self::Udp(socket) => socket.getpeername()?.map(Into::into),
+        };
+        Ok(addr.map(|addr| addr_from_ipv4(self.family(), addr)))
     }
 
     pub fn getsockname(&self) -> Result<Option<SockaddrStorage>, Errno> {
-        match self {
-            Self::LegacyTcp(socket) => socket.getsockname().map(|opt| opt.map(Into::into)),
-            Self::Tcp(socket) => socket.getsockname().map(|opt| opt.map(Into::into)),
-            Self::Udp(socket) => socket.getsockname().map(|opt| opt.map(Into::into)),
-        }
+        let addr = match self {
+            Self::LegacyTcp(socket) => socket.getsockname()?.map(Into::into),
+            Self::Tcp(socket) => socket.getsockname()?.map(Into::into),
+            Self::Udp(socket) => socket.getsockname()?.map(Into::into),
+        };
+        Ok(addr.map(|addr| addr_from_ipv4(self.family(), addr)))
     }
 
     enum_passthrough!(self, (), LegacyTcp, Tcp, Udp;
         pub fn address_family(&self) -> linux_api::socket::AddressFamily
     );
+
+    enum_passthrough!(self, (), LegacyTcp, Tcp, Udp;
+        pub fn family(&self) -> InetFamily
+    );
 }
 
 // inet socket-specific functions
@@ -308,6 +415,9 @@ impl InetSocketRef<'_> {
     enum_passthrough!(self, (), LegacyTcp, Tcp, Udp;
         pub fn has_data_to_send(&self) -> bool
     );
+    enum_passthrough!(self, (), LegacyTcp, Tcp, Udp;
+        pub fn is_listening(&self) -> bool
+    );
 }
 
 // file functions
@@ -370,43 +480,124 @@ impl InetSocketRefMut<'_> {
 // socket-specific functions
 impl InetSocketRefMut<'_> {
     pub fn getpeername(&self) -> Result<Option<SockaddrStorage>, Errno> {
-        match self {
-            Self::LegacyTcp(socket) => socket.getpeername().map(|opt| opt.map(Into::into)),
-            Self::Tcp(socket) => socket.getpeername().map(|opt| opt.map(Into::into)),
-            Self::Udp(socket) => socket.getpeername().map(|opt| opt.map(Into::into)),
-        }
+        let addr = match self {
+            Self::LegacyTcp(socket) => socket.getpeername()?.map(Into::into),
+            Self::Tcp(socket) => socket.getpeername()?.map(Into::into),
+            Self::Udp(socket) => socket.getpeername()?.map(Into::into),
+        };
+        Ok(addr.map(|addr| addr_from_ipv4(self.family(), addr)))
     }
 
     pub fn getsockname(&self) -> Result<Option<SockaddrStorage>, Errno> {
-        match self {
-            Self::LegacyTcp(socket) => socket.getsockname().map(|opt| opt.map(Into::into)),
-            Self::Tcp(socket) => socket.getsockname().map(|opt| opt.map(Into::into)),
-            Self::Udp(socket) => socket.getsockname().map(|opt| opt.map(Into::into)),
-        }
+        let addr = match self {
+            Self::LegacyTcp(socket) => socket.getsockname()?.map(Into::into),
+            Self::Tcp(socket) => socket.getsockname()?.map(Into::into),
+            Self::Udp(socket) => socket.getsockname()?.map(Into::into),
+        };
+        Ok(addr.map(|addr| addr_from_ipv4(self.family(), addr)))
     }
 
     enum_passthrough!(self, (), LegacyTcp, Tcp, Udp;
         pub fn address_family(&self) -> linux_api::socket::AddressFamily
     );
 
-    enum_passthrough!(self, (level, optname, optval_ptr, optlen, memory_manager, cb_queue), LegacyTcp, Tcp, Udp;
-        pub fn getsockopt(&mut self, level: libc::c_int, optname: libc::c_int, optval_ptr: ForeignPtr<()>,
-                          optlen: libc::socklen_t, memory_manager: &mut MemoryManager, cb_queue: &mut CallbackQueue)
-        -> Result<libc::socklen_t, SyscallError>
+    enum_passthrough!(self, (), LegacyTcp, Tcp, Udp;
+        pub fn family(&self) -> InetFamily
     );
 
-    enum_passthrough!(self, (level, optname, optval_ptr, optlen, memory_manager), LegacyTcp, Tcp, Udp;
-        pub fn setsockopt(&mut self, level: libc::c_int, optname: libc::c_int, optval_ptr: ForeignPtr<()>,
-                          optlen: libc::socklen_t, memory_manager: &MemoryManager)
-        -> Result<(), SyscallError>
+    enum_passthrough!(self, (family), LegacyTcp, Tcp, Udp;
+        pub fn set_family(&mut self, family: InetFamily)
     );
 
+    pub fn getsockopt(
+        &mut self,
+        level: libc::c_int,
+        optname: libc::c_int,
+        optval_ptr: ForeignPtr<()>,
+        optlen: libc::socklen_t,
+        memory_manager: &mut MemoryManager,
+        cb_queue: &mut CallbackQueue,
+    ) -> Result<libc::socklen_t, SyscallError> {
+        if let (InetFamily::Ipv6 { v6only }, libc::IPPROTO_IPV6, libc::IPV6_V6ONLY) =
+            (self.family(), level, optname)
+        {
+            let v6only = libc::c_int::from(v6only);
+
+            let optval_ptr = optval_ptr.cast::<libc::c_int>();
+            let bytes_written =
+                write_partial(memory_manager, &v6only, optval_ptr, optlen as usize)?;
+
+            return Ok(bytes_written as libc::socklen_t);
+        }
+
+        match self {
+            Self::LegacyTcp(socket) => {
+                socket.getsockopt(level, optname, optval_ptr, optlen, memory_manager, cb_queue)
+            }
+            Self::Tcp(socket) => {
+                socket.getsockopt(level, optname, optval_ptr, optlen, memory_manager, cb_queue)
+            }
+            Self::Udp(socket) => {
+                socket.getsockopt(level, optname, optval_ptr, optlen, memory_manager, cb_queue)
+            }
+        }
+    }
+
+    pub fn setsockopt(
+        &mut self,
+        level: libc::c_int,
+        optname: libc::c_int,
+        optval_ptr: ForeignPtr<()>,
+        optlen: libc::socklen_t,
+        memory_manager: &MemoryManager,
+    ) -> Result<(), SyscallError> {
+        if let (InetFamily::Ipv6 { .. }, libc::IPPROTO_IPV6, libc::IPV6_V6ONLY) =
+            (self.family(), level, optname)
+        {
+            type OptType = libc::c_int;
+
+            if usize::try_from(optlen).unwrap() < std::mem::size_of::<OptType>() {
+                return Err(Errno::EINVAL.into());
+            }
+
+            // linux doesn't allow changing this option after the socket has been bound
+            if self
+                .getsockname()?
+                .and_then(|addr| addr.as_inet6().map(|x| x.port()))
+                .is_some_and(|port| port != 0)
+            {
+                return Err(Errno::EINVAL.into());
+            }
+
+            let optval_ptr = optval_ptr.cast::<OptType>();
+            let v6only = memory_manager.read(optval_ptr)? != 0;
+
+            self.set_family(InetFamily::Ipv6 { v6only });
+
+            return Ok(());
+        }
+
+        match self {
+            Self::LegacyTcp(socket) => {
+                socket.setsockopt(level, optname, optval_ptr, optlen, memory_manager)
+            }
+            Self::Tcp(socket) => {
+                socket.setsockopt(level, optname, optval_ptr, optlen, memory_manager)
+            }
+            Self::Udp(socket) => {
+                socket.setsockopt(level, optname, optval_ptr, optlen, memory_manager)
+            }
+        }
+    }
+
     pub fn accept(
         &mut self,
         net_ns: &NetworkNamespace,
         rng: impl rand::Rng,
         cb_queue: &mut CallbackQueue,
     ) -> Result<OpenFile, SyscallError> {
+        // the accepted socket has the same family as the listening socket, which each socket type
+        // sets before associating the accepted socket
         match self {
             Self::LegacyTcp(socket) => socket.accept(net_ns, rng, cb_queue),
             Self::Tcp(socket) => socket.accept(net_ns, rng, cb_queue),
@@ -421,12 +612,38 @@ impl InetSocketRefMut<'_> {
 
 // inet socket-specific functions
 impl InetSocketRefMut<'_> {
-    enum_passthrough!(self, (packet, cb_queue, recv_time), LegacyTcp, Tcp, Udp;
-        pub fn push_in_packet(&mut self, packet: PacketRc, cb_queue: &mut CallbackQueue, recv_time: EmulatedTime)
-    );
-    enum_passthrough!(self, (cb_queue), LegacyTcp, Tcp, Udp;
-        pub fn pull_out_packet(&mut self, cb_queue: &mut CallbackQueue) -> Option<PacketRc>
-    );
+    pub fn push_in_packet(
+        &mut self,
+        packet: PacketRc,
+        cb_queue: &mut CallbackQueue,
+        recv_time: EmulatedTime,
+    ) {
+        match self {
+            Self::LegacyTcp(socket) => socket.push_in_packet(packet, cb_queue, recv_time),
+            Self::Tcp(socket) => socket.push_in_packet(packet, cb_queue, recv_time),
+            Self::Udp(socket) => socket.push_in_packet(packet, cb_queue, recv_time),
+        }
+    }
+
+    pub fn pull_out_packet(&mut self, cb_queue: &mut CallbackQueue) -> Option<PacketRc> {
+        let packet = match self {
+            Self::LegacyTcp(socket) => socket.pull_out_packet(cb_queue),
+            Self::Tcp(socket) => socket.pull_out_packet(cb_queue),
+            Self::Udp(socket) => socket.pull_out_packet(cb_queue),
+        }?;
+
+        // ipv6 sockets send ipv6 packets when both addresses have ipv6 aliases
+        if let InetFamily::Ipv6 { .. } = self.family()
+            && let Some((src, dst)) = ipv6::packet_addresses(
+                *packet.src_ipv4_address().ip(),
+                *packet.dst_ipv4_address().ip(),
+            )
+        {
+            return Some(packet.new_ipv6_copy(src, dst));
+        }
+
+        Some(packet)
+    }
     enum_passthrough!(self, (), LegacyTcp, Tcp, Udp;
         pub fn peek_next_packet_priority(&self) -> Option<FifoPacketPriority>
     );
@@ -486,15 +703,56 @@ impl InetSocketWeak {
     }
 }
 
+/// Translate an address given to an IPv6 socket into the IPv4 address used by Shadow. Non-IPv6
+/// addresses are returned unchanged. Returns `err` if the address has no IPv4 equivalent, or if
+/// it's an IPv4-mapped address and `v6only` is set.
+fn addr_to_ipv4(
+    addr: &SockaddrStorage,
+    v6only: bool,
+    err: Errno,
+) -> Result<SockaddrStorage, Errno> {
+    let Some(addr) = addr.as_inet6() else {
+        return Ok(*addr);
+    };
+    let addr = SocketAddrV6::from(*addr);
+
+    if v6only && addr.ip().to_ipv4_mapped().is_some() {
+        return Err(err);
+    }
+
+    let Some(addr) = ipv6::sockaddr_to_ipv4(addr) else {
+        log::debug!("IPv6 address {addr} does not belong to any host");
+        return Err(err);
+    };
+
+    Ok(addr.into())
+}
+
+/// Translate an IPv4 address used by Shadow into the address that should be returned to the
+/// managed process for a socket of family `family`.
+fn addr_from_ipv4(family: InetFamily, addr: SockaddrStorage) -> SockaddrStorage {
+    let InetFamily::Ipv6 { .. } = family else {
+        return addr;
+    };
+
+    let Some(addr) = addr.as_inet() else {
+        return addr;
+    };
+
+    ipv6::sockaddr_to_ipv6(SocketAddrV4::from(*addr)).into()
+}
+
 /// Associate the socket with a network interface. If the local address is unspecified, the socket
 /// will be associated with every available interface. If the local address has a port of 0, a
 /// non-zero port will be chosen. The final local address will be returned. If the peer address is
 /// unspecified and has a port of 0, the socket will receive packets from every peer address. The
 /// socket will be automatically disassociated when the returned [`AssociationHandle`] is dropped.
 /// If `check_generic_peer` is true, the association will also fail if there is already a socket
-/// associated with the local address `local_addr` and peer address 0.0.0.0:0.
+/// associated with the local address `local_addr` and peer address 0.0.0.0:0. The socket only uses
+/// the port in the port namespaces of `versions`.
 fn associate_socket(
     socket: InetSocket,
+    versions: IpVersions,
     local_addr: SocketAddrV4,
     peer_addr: SocketAddrV4,
     check_generic_peer: bool,
@@ -522,7 +780,7 @@ fn associate_socket(
         local_addr
     } else {
         let Some(new_port) =
-            net_ns.get_random_free_port(protocol, *local_addr.ip(), peer_addr, rng)
+            net_ns.get_random_free_port(protocol, versions, *local_addr.ip(), peer_addr, rng)
         else {
             log::debug!("Association required an ephemeral port but none are available");
             return Err(Errno::EADDRINUSE);
@@ -535,7 +793,7 @@ fn associate_socket(
     };
 
     // make sure the port is available at this address for this protocol
-    match net_ns.is_addr_in_use(protocol, local_addr, peer_addr) {
+    match net_ns.is_addr_in_use(protocol, versions, local_addr, peer_addr) {
         Ok(true) => {
             log::debug!(
                 "The provided addresses (local={local_addr}, peer={peer_addr}) are not available"
@@ -549,6 +807,7 @@ fn associate_socket(
     if check_generic_peer {
         match net_ns.is_addr_in_use(
             protocol,
+            versions,
             local_addr,
             SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0),
         ) {
@@ -565,7 +824,8 @@ fn associate_socket(
     }
 
     // associate the interfaces corresponding to addr with socket
-    let handle = unsafe { net_ns.associate_interface(&socket, protocol, local_addr, peer_addr) };
+    let handle =
+        unsafe { net_ns.associate_interface(&socket, protocol, versions, local_addr, peer_addr) };
 
     Ok((local_addr, handle))
 }
@@ -14,7 +14,7 @@ use crate::core::work::task::TaskRef;
 use crate::core::worker::Worker;
 use crate::cshadow as c;
 use crate::host::descriptor::listener::{StateEventSource, StateListenHandle, StateListenerFilter};
-use crate::host::descriptor::socket::inet;
+use crate::host::descriptor::socket::inet::{self, InetFamily};
 use crate::host::descriptor::socket::{InetSocket, RecvmsgArgs, RecvmsgReturn, SendmsgArgs};
 use crate::host::descriptor::{File, Socket};
 use crate::host::descriptor::{
@@ -37,6 +37,7 @@ pub struct TcpSocket {
     status: FileStatus,
     file_state: FileState,
     association: Option<AssociationHandle>,
+    family: InetFamily,
     connect_result_is_pending: bool,
     shutdown_status: Option<Shutdown>,
     // should only be used by `OpenFile` to make sure there is only ever one `OpenFile` instance for
@@ -64,6 +65,7 @@ impl TcpSocket {
                 // `with_tcp_state` below to update it, but we need ACTIVE set so that epoll works
                 file_state: FileState::ACTIVE,
                 association: None,
+                family: InetFamily::Ipv4,
                 connect_result_is_pending: false,
                 shutdown_status: None,
                 has_open_file: false,
@@ -104,6 +106,14 @@ impl TcpSocket {
         self.has_open_file = val;
     }
 
+    pub fn family(&self) -> InetFamily {
+        self.family
+    }
+
+    pub fn set_family(&mut self, family: InetFamily) {
+        self.family = family;
+    }
+
     fn with_tcp_state<T>(
         &mut self,
         cb_queue: &mut CallbackQueue,
@@ -276,6 +286,10 @@ impl TcpSocket {
         self.tcp_state.wants_to_send()
     }
 
+    pub fn is_listening(&self) -> bool {
+        self.tcp_state.poll().contains(tcp::PollState::LISTENING)
+    }
+
     pub fn getsockname(&self) -> Result<Option<SockaddrIn>, Errno> {
         // The socket state won't always have the local address. For example if the socket was bound
         // but connect() hasn't yet been called, the socket state will not have a local or remote
@@ -308,7 +322,7 @@ impl TcpSocket {
     }
 
     pub fn address_family(&self) -> linux_api::socket::AddressFamily {
-        linux_api::socket::AddressFamily::AF_INET
+        self.family.address_family()
     }
 
     pub fn close(&mut self, cb_queue: &mut CallbackQueue) -> Result<(), SyscallError> {
@@ -358,6 +372,7 @@ impl TcpSocket {
         // associate the socket
         let (_addr, handle) = inet::associate_socket(
             InetSocket::Tcp(Arc::clone(socket)),
+            socket_ref.family.ip_versions(),
             addr,
             peer_addr,
             /* check_generic_peer= */ true,
@@ -557,6 +572,8 @@ impl TcpSocket {
             let associate_fn = || Ok(None);
             socket_ref.with_tcp_state(cb_queue, |state| state.listen(backlog, associate_fn))
         } else {
+            let versions = socket_ref.family.ip_versions();
+
             // if not associated, associate and return the handle
             let associate_fn = || {
                 // implicitly bind to all interfaces
@@ -569,6 +586,7 @@ impl TcpSocket {
                 // associate the socket
                 let (_addr, handle) = inet::associate_socket(
                     InetSocket::Tcp(Arc::clone(&socket)),
+                    versions,
                     local_addr,
                     peer_addr,
                     /* check_generic_peer= */ true,
@@ -663,6 +681,8 @@ impl TcpSocket {
             let associate_fn = || Ok((local_addr, None));
             socket_ref.with_tcp_state(cb_queue, |state| state.connect(peer_addr, associate_fn))
         } else {
+            let versions = socket_ref.family.ip_versions();
+
             // if not associated, associate and return the handle
             let associate_fn = || {
                 // the local address needs to be a specific address (this is normally what a routing
@@ -678,6 +698,7 @@ impl TcpSocket {
 
                 let (local_addr, handle) = inet::associate_socket(
                     InetSocket::Tcp(Arc::clone(socket)),
+                    versions,
                     local_addr,
                     peer_addr,
                     /* check_generic_peer= */ true,
@@ -776,6 +797,8 @@ impl TcpSocket {
                 // `with_tcp_state` below to update it, but we need ACTIVE set so that epoll works
                 file_state: FileState::ACTIVE,
                 association: None,
+                // the accepted socket has the same family as the listening socket
+                family: self.family,
                 connect_result_is_pending: false,
                 shutdown_status: None,
                 has_open_file: false,
@@ -794,6 +817,7 @@ impl TcpSocket {
         // associate the socket
         let (_addr, handle) = inet::associate_socket(
             InetSocket::Tcp(Arc::clone(&new_socket)),
+            self.family.ip_versions(),
             local_addr,
             remote_addr,
             /* check_generic_peer= */ false,
@@ -886,7 +910,7 @@ impl TcpSocket {
                 Ok(bytes_written as libc::socklen_t)
             }
             (libc::SOL_SOCKET, libc::SO_DOMAIN) => {
-                let domain = libc::AF_INET;
+                let domain = self.family.domain();
 
                 let optval_ptr = optval_ptr.cast::<libc::c_int>();
                 let bytes_written = write_partial(mem, &domain, optval_ptr, optlen as usize)?;
@@ -15,7 +15,7 @@ use shadow_shim_helper_rs::syscall_types::ForeignPtr;
 use crate::core::worker::Worker;
 use crate::cshadow as c;
 use crate::host::descriptor::listener::{StateEventSource, StateListenHandle, StateListenerFilter};
-use crate::host::descriptor::socket::inet::{self, InetSocket};
+use crate::host::descriptor::socket::inet::{self, InetFamily, InetSocket};
 use crate::host::descriptor::socket::{RecvmsgArgs, RecvmsgReturn, SendmsgArgs, ShutdownFlags};
 use crate::host::descriptor::{
     File, FileMode, FileSignals, FileState, FileStatus, OpenFile, Socket, SyscallResult,
@@ -44,6 +44,7 @@ pub struct UdpSocket {
     peer_addr: Option<SocketAddrV4>,
     bound_addr: Option<SocketAddrV4>,
     association: Option<AssociationHandle>,
+    family: InetFamily,
     /// The receive time of the last packet returned to the managed process during a call to
     /// `recvmsg()`. Used for `SIOCGSTAMP`.
     recv_time_of_last_read_packet: Option<EmulatedTime>,
@@ -69,6 +70,7 @@ impl UdpSocket {
             peer_addr: None,
             bound_addr: None,
             association: None,
+            family: InetFamily::Ipv4,
             recv_time_of_last_read_packet: None,
             has_open_file: false,
             _counter: ObjectCounter::new("UdpSocket"),
@@ -105,6 +107,14 @@ impl UdpSocket {
         self.has_open_file = val;
     }
 
+    pub fn family(&self) -> InetFamily {
+        self.family
+    }
+
+    pub fn set_family(&mut self, family: InetFamily) {
+        self.family = family;
+    }
+
     pub fn push_in_packet(
         &mut self,
         packet: PacketRc,
@@ -196,6 +206,10 @@ impl UdpSocket {
         !self.send_buffer.is_empty()
     }
 
+    pub fn is_listening(&self) -> bool {
+        false
+    }
+
     pub fn getsockname(&self) -> Result<Option<SockaddrIn>, Errno> {
         let mut addr = self
             .bound_addr
@@ -217,7 +231,7 @@ impl UdpSocket {
     }
 
     pub fn address_family(&self) -> linux_api::socket::AddressFamily {
-        linux_api::socket::AddressFamily::AF_INET
+        self.family.address_family()
     }
 
     pub fn close(&mut self, cb_queue: &mut CallbackQueue) -> Result<(), SyscallError> {
@@ -251,7 +265,7 @@ impl UdpSocket {
 
         let addr: SocketAddrV4 = (*addr).into();
 
-        {
+        let versions = {
             let socket = socket.borrow();
 
             // if the socket is already bound
@@ -265,7 +279,9 @@ impl UdpSocket {
 
             // must not have been associated with the network interface
             assert!(socket.association.is_none());
-        }
+
+            socket.family.ip_versions()
+        };
 
         // this will allow us to receive packets from any peer
         let unspecified_addr = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0);
@@ -273,6 +289,7 @@ impl UdpSocket {
         // associate the socket
         let (addr, handle) = inet::associate_socket(
             InetSocket::Udp(Arc::clone(socket)),
+            versions,
             addr,
             unspecified_addr,
             /* check_generic_peer= */ true,
@@ -399,6 +416,7 @@ impl UdpSocket {
 
             let (local_addr, handle) = super::associate_socket(
                 InetSocket::Udp(Arc::clone(socket)),
+                socket_ref.family.ip_versions(),
                 local_addr,
                 unspecified_addr,
                 /* check_generic_peer= */ true,
@@ -751,6 +769,7 @@ impl UdpSocket {
 
                 let (local_addr, handle) = super::associate_socket(
                     InetSocket::Udp(Arc::clone(socket)),
+                    socket_ref.family.ip_versions(),
                     local_addr,
                     unspecified_addr,
                     /* check_generic_peer= */ true,
@@ -835,7 +854,7 @@ impl UdpSocket {
                 Ok(bytes_written as libc::socklen_t)
             }
             (libc::SOL_SOCKET, libc::SO_DOMAIN) => {
-                let domain = libc::AF_INET;
+                let domain = self.family.domain();
 
                 let optval_ptr = optval_ptr.cast::<libc::c_int>();
                 let bytes_written = write_partial(mem, &domain, optval_ptr, optlen as usize)?;
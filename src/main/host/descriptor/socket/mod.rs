@@ -10,6 +10,7 @@ use shadow_shim_helper_rs::syscall_types::ForeignPtr;
 use unix::UnixSocket;
 
 use crate::cshadow as c;
+use crate::host::descriptor::descriptor_table::DescriptorTable;
 use crate::host::descriptor::listener::{StateListenHandle, StateListenerFilter};
 use crate::host::descriptor::{
     FileMode, FileSignals, FileState, FileStatus, OpenFile, SyscallResult,
@@ -25,6 +26,7 @@ use crate::utility::sockaddr::SockaddrStorage;
 pub mod abstract_unix_ns;
 pub mod inet;
 pub mod netlink;
+pub mod scm;
 pub mod unix;
 
 bitflags::bitflags! {
@@ -392,6 +394,11 @@ pub struct SendmsgArgs<'a> {
     pub control_ptr: ForeignArrayPtr<u8>,
     /// Send flags.
     pub flags: libc::c_int,
+    /// The sending process' descriptor table, used to look up descriptors passed in the control
+    /// data. Is `None` if there is no control data.
+    pub descriptor_table: Option<&'a DescriptorTable>,
+    /// The credentials of the sending process.
+    pub credentials: libc::ucred,
 }
 
 /// Arguments for [`Socket::recvmsg()`].
@@ -402,6 +409,9 @@ pub struct RecvmsgArgs<'a> {
     pub control_ptr: ForeignArrayPtr<u8>,
     /// Recv flags.
     pub flags: libc::c_int,
+    /// The receiving process' descriptor table, which any descriptors received in the control data
+    /// are added to. Is `None` if there is no control data buffer.
+    pub descriptor_table: Option<&'a mut DescriptorTable>,
 }
 
 /// Return values for [`Socket::recvmsg()`].
@@ -1,5 +1,5 @@
 use std::io::{Cursor, ErrorKind, Read, Write};
-use std::net::Ipv4Addr;
+use std::net::{Ipv4Addr, Ipv6Addr};
 use std::sync::{Arc, Weak};
 
 use atomic_refcell::AtomicRefCell;
@@ -56,14 +56,17 @@ impl NetlinkSocket {
             let buffer = SharedBuf::new(usize::MAX);
             let buffer = Arc::new(AtomicRefCell::new(buffer));
 
-            // Get the IP address of the host
-            let default_ip = Worker::with_active_host(|host| host.default_ip()).unwrap();
+            // Get the IP addresses of the host
+            let (default_ip, default_ipv6) =
+                Worker::with_active_host(|host| (host.default_ip(), host.default_ipv6())).unwrap();
             // All the interface configurations are the same as in the getifaddrs function handler
             let interfaces = vec![
                 Interface {
                     address: Ipv4Addr::LOCALHOST,
                     label: String::from("lo"),
                     prefix_len: 8,
+                    ipv6_address: Ipv6Addr::LOCALHOST,
+                    ipv6_prefix_len: 128,
                     if_type: Arphrd::Loopback,
                     mtu: c::CONFIG_MTU,
                     scope: RtScope::Host,
@@ -73,6 +76,8 @@ impl NetlinkSocket {
                     address: default_ip,
                     label: String::from("eth0"),
                     prefix_len: 24,
+                    ipv6_address: default_ipv6,
+                    ipv6_prefix_len: 64,
                     if_type: Arphrd::Ether,
                     mtu: c::CONFIG_MTU,
                     scope: RtScope::Universe,
@@ -795,13 +800,19 @@ impl InitialState {
             return self.handle_error(bytes);
         };
 
-        // The only supported interface address family is AF_INET
-        if *ifaddrmsg.ifa_family() != RtAddrFamily::Unspecified
-            && *ifaddrmsg.ifa_family() != RtAddrFamily::Inet
+        // The only supported interface address families are AF_INET and AF_INET6
+        let family = *ifaddrmsg.ifa_family();
+        if family != RtAddrFamily::Unspecified
+            && family != RtAddrFamily::Inet
+            && family != RtAddrFamily::Inet6
         {
-            log::warn!("Unsupported ifa_family (only AF_UNSPEC and AF_INET are supported)");
+            log::warn!(
+                "Unsupported ifa_family (only AF_UNSPEC, AF_INET, and AF_INET6 are supported)"
+            );
             return self.handle_error(bytes);
         }
+        let send_ipv4 = family != RtAddrFamily::Inet6;
+        let send_ipv6 = family != RtAddrFamily::Inet;
 
         // The rest of the fields are unsupported. We limit only the interest to the zero values
         if *ifaddrmsg.ifa_prefixlen() != 0
@@ -816,8 +827,21 @@ impl InitialState {
         }
 
         let mut buffer = Cursor::new(Vec::new());
-        // Send the interface addresses
-        for interface in &common.interfaces {
+        let mut send_ifaddrmsg = |ifaddrmsg: Ifaddrmsg| {
+            let nlmsg = NlmsghdrBuilder::default()
+                .nl_type(Rtm::Newaddr)
+                // The NLM_F_MULTI flag is used to indicate that we will send multiple messages
+                .nl_flags(NlmF::MULTI)
+                // Use the same sequence number as the request
+                .nl_seq(*nlmsg.nl_seq())
+                .nl_payload(NlPayload::Payload(ifaddrmsg))
+                .build()
+                .expect("NlmsghdrBuilder missing a required field");
+            nlmsg.to_bytes(&mut buffer).unwrap();
+        };
+
+        // Send the IPv4 interface addresses
+        for interface in common.interfaces.iter().filter(|_| send_ipv4) {
             let address = interface.address.octets();
             let broadcast = Ipv4Addr::from(
                 0xffff_ffff_u32
@@ -865,16 +889,28 @@ impl InitialState {
                 .rtattrs(RtBuffer::from_iter(attrs))
                 .build()
                 .expect("IfaddrmsgBuilder missing a required field");
-            let nlmsg = NlmsghdrBuilder::default()
-                .nl_type(Rtm::Newaddr)
-                // The NLM_F_MULTI flag is used to indicate that we will send multiple messages
-                .nl_flags(NlmF::MULTI)
-                // Use the same sequence number as the request
-                .nl_seq(*nlmsg.nl_seq())
-                .nl_payload(NlPayload::Payload(ifaddrmsg))
+            send_ifaddrmsg(ifaddrmsg);
+        }
+
+        // Send the IPv6 interface addresses, which Linux sends after the IPv4 addresses. IPv6
+        // addresses don't have broadcast addresses or labels.
+        for interface in common.interfaces.iter().filter(|_| send_ipv6) {
+            let address = interface.ipv6_address.octets();
+            let attrs = [RtattrBuilder::default()
+                .rta_type(Ifa::Address)
+                .rta_payload(Buffer::from(&address[..]))
                 .build()
-                .expect("NlmsghdrBuilder missing a required field");
-            nlmsg.to_bytes(&mut buffer).unwrap();
+                .unwrap()];
+            let ifaddrmsg = IfaddrmsgBuilder::default()
+                .ifa_family(RtAddrFamily::Inet6)
+                .ifa_prefixlen(interface.ipv6_prefix_len)
+                .ifa_flags(IfaF::PERMANENT)
+                .ifa_scope(interface.scope)
+                .ifa_index(interface.index)
+                .rtattrs(RtBuffer::from_iter(attrs))
+                .build()
+                .expect("IfaddrmsgBuilder missing a required field");
+            send_ifaddrmsg(ifaddrmsg);
         }
         // After sending the messages with the NLM_F_MULTI flag set, we need to send the NLMSG_DONE message
         let done_msg = NlmsghdrBuilder::default()
@@ -1082,6 +1118,8 @@ struct Interface {
     address: Ipv4Addr,
     label: String,
     prefix_len: u8,
+    ipv6_address: Ipv6Addr,
+    ipv6_prefix_len: u8,
     if_type: Arphrd,
     mtu: u32,
     scope: RtScope,
@@ -0,0 +1,243 @@
+//! Socket-level control messages (`SCM_RIGHTS` and `SCM_CREDENTIALS`) sent as ancillary data over
+//! unix sockets.
+
+use std::collections::VecDeque;
+
+use linux_api::errno::Errno;
+use linux_api::fcntl::DescriptorFlags;
+
+use crate::host::descriptor::Descriptor;
+use crate::host::descriptor::descriptor_table::{DescriptorHandle, DescriptorTable};
+use crate::host::memory_manager::MemoryManager;
+use crate::host::syscall::types::ForeignArrayPtr;
+
+/// The maximum number of descriptors that can be passed in a single message (`SCM_MAX_FD` in
+/// Linux).
+const SCM_MAX_FD: usize = 253;
+
+/// The length of a control message header, including any padding before the message data.
+const CMSG_HDR_LEN: usize = cmsg_align(std::mem::size_of::<libc::cmsghdr>());
+
+/// The length of the data of a `SCM_CREDENTIALS` message.
+const UCRED_LEN: usize = std::mem::size_of::<libc::ucred>();
+
+/// Equivalent to the `CMSG_ALIGN` macro.
+const fn cmsg_align(len: usize) -> usize {
+    len.next_multiple_of(std::mem::size_of::<usize>())
+}
+
+/// Equivalent to the `CMSG_LEN` macro.
+const fn cmsg_len(data_len: usize) -> usize {
+    CMSG_HDR_LEN + data_len
+}
+
+/// Equivalent to the `CMSG_SPACE` macro.
+const fn cmsg_space(data_len: usize) -> usize {
+    CMSG_HDR_LEN + cmsg_align(data_len)
+}
+
+/// Ancillary data attached to a message sent over a unix socket.
+#[derive(Default)]
+pub struct ScmData {
+    /// Descriptors passed with `SCM_RIGHTS`, duplicated from the sender's descriptor table.
+    pub rights: Vec<Descriptor>,
+    /// Credentials passed with `SCM_CREDENTIALS`, or added by the socket if `SO_PASSCRED` is
+    /// enabled.
+    pub credentials: Option<libc::ucred>,
+}
+
+impl ScmData {
+    pub fn is_empty(&self) -> bool {
+        self.rights.is_empty() && self.credentials.is_none()
+    }
+
+    /// Parse the control messages in the sender's control buffer. Descriptors passed with
+    /// `SCM_RIGHTS` are looked up in the sender's descriptor table, and credentials passed with
+    /// `SCM_CREDENTIALS` must match the sender's credentials. Control messages that aren't at the
+    /// `SOL_SOCKET` level are ignored, like in Linux.
+    pub fn read(
+        mem: &MemoryManager,
+        control_ptr: ForeignArrayPtr<u8>,
+        descriptor_table: Option<&DescriptorTable>,
+        sender: &libc::ucred,
+    ) -> Result<Self, Errno> {
+        let mut scm = Self::default();
+
+        if control_ptr.ptr().is_null() || control_ptr.is_empty() {
+            return Ok(scm);
+        }
+
+        let control_ref = mem.memory_ref(control_ptr)?;
+        let mut control: &[u8] = &control_ref;
+
+        while control.len() >= CMSG_HDR_LEN {
+            let (len, level, ty) = read_cmsg_header(control);
+
+            if len < CMSG_HDR_LEN || len > control.len() {
+                return Err(Errno::EINVAL);
+            }
+
+            let data = &control[CMSG_HDR_LEN..len];
+
+            match (level, ty) {
+                (libc::SOL_SOCKET, libc::SCM_RIGHTS) => {
+                    for fd in data.chunks_exact(std::mem::size_of::<libc::c_int>()) {
+                        if scm.rights.len() >= SCM_MAX_FD {
+                            return Err(Errno::EINVAL);
+                        }
+
+                        let fd = libc::c_int::from_ne_bytes(fd.try_into().unwrap());
+                        let desc = DescriptorHandle::try_from(fd)
+                            .ok()
+                            .and_then(|fd| descriptor_table?.get(fd))
+                            .ok_or(Errno::EBADF)?;
+
+                        // descriptor flags such as FD_CLOEXEC aren't passed to the receiver
+                        scm.rights.push(desc.dup(DescriptorFlags::empty()));
+                    }
+                }
+                (libc::SOL_SOCKET, libc::SCM_CREDENTIALS) => {
+                    if data.len() != UCRED_LEN {
+                        return Err(Errno::EINVAL);
+                    }
+
+                    let credentials = read_ucred(data);
+
+                    // processes can't claim to be another process or user
+                    if credentials.pid != sender.pid {
+                        return Err(Errno::EPERM);
+                    }
+                    if credentials.uid != sender.uid || credentials.gid != sender.gid {
+                        return Err(Errno::EPERM);
+                    }
+
+                    scm.credentials = Some(credentials);
+                }
+                (libc::SOL_SOCKET, _) => {
+                    log::debug!("Unsupported control message type {ty} for unix sockets");
+                    return Err(Errno::EINVAL);
+                }
+                _ => {}
+            }
+
+            control = &control[std::cmp::min(cmsg_align(len), control.len())..];
+        }
+
+        Ok(scm)
+    }
+
+    /// Write the ancillary data to the receiver's control buffer, adding any passed descriptors to
+    /// the receiver's descriptor table. The credentials are only written if `pass_cred` is set.
+    /// Returns the number of bytes written to the control buffer and whether any of the ancillary
+    /// data was discarded (`MSG_CTRUNC`). Descriptors that weren't added to the descriptor table
+    /// are returned so that the caller can close them.
+    pub fn write(
+        self,
+        mem: &mut MemoryManager,
+        control_ptr: ForeignArrayPtr<u8>,
+        descriptor_table: Option<&mut DescriptorTable>,
+        pass_cred: bool,
+        cloexec: bool,
+    ) -> Result<(usize, bool, Vec<Descriptor>), Errno> {
+        let mut control = Vec::new();
+        let mut truncated = false;
+
+        // without a descriptor table there's nowhere to add descriptors to, so treat it as if
+        // there's no control buffer
+        let space = match descriptor_table {
+            Some(_) if !control_ptr.ptr().is_null() => control_ptr.len(),
+            _ => 0,
+        };
+
+        if pass_cred && let Some(credentials) = self.credentials {
+            if space >= cmsg_len(UCRED_LEN) {
+                write_cmsg_header(&mut control, cmsg_len(UCRED_LEN), libc::SCM_CREDENTIALS);
+                control.extend_from_slice(&credentials.pid.to_ne_bytes());
+                control.extend_from_slice(&credentials.uid.to_ne_bytes());
+                control.extend_from_slice(&credentials.gid.to_ne_bytes());
+                control.resize(std::cmp::min(cmsg_space(UCRED_LEN), space), 0);
+            } else {
+                truncated = true;
+            }
+        }
+
+        let mut rights = VecDeque::from(self.rights);
+
+        if !rights.is_empty() {
+            let fd_len = std::mem::size_of::<libc::c_int>();
+            let max_fds = space.saturating_sub(control.len() + CMSG_HDR_LEN) / fd_len;
+
+            let flags = if cloexec {
+                DescriptorFlags::FD_CLOEXEC
+            } else {
+                DescriptorFlags::empty()
+            };
+
+            let mut fds = Vec::new();
+            if let Some(descriptor_table) = descriptor_table {
+                while fds.len() < max_fds
+                    && let Some(mut desc) = rights.pop_front()
+                {
+                    desc.set_flags(flags);
+                    match descriptor_table.register_descriptor(desc) {
+                        Ok(fd) => fds.push(libc::c_int::from(fd)),
+                        Err(desc) => {
+                            // the descriptor table is full
+                            rights.push_front(desc);
+                            break;
+                        }
+                    }
+                }
+            }
+
+            if !fds.is_empty() {
+                write_cmsg_header(&mut control, cmsg_len(fds.len() * fd_len), libc::SCM_RIGHTS);
+                for fd in &fds {
+                    control.extend_from_slice(&fd.to_ne_bytes());
+                }
+                control.resize(std::cmp::min(cmsg_align(control.len()), space), 0);
+            }
+
+            // any descriptors that didn't fit in the control buffer are discarded
+            if !rights.is_empty() {
+                truncated = true;
+            }
+        }
+
+        if !control.is_empty() {
+            mem.copy_to_ptr(control_ptr.slice(..control.len()), &control)?;
+        }
+
+        Ok((control.len(), truncated, rights.into()))
+    }
+}
+
+fn read_cmsg_header(control: &[u8]) -> (usize, libc::c_int, libc::c_int) {
+    let (len, rest) = control.split_at(std::mem::size_of::<usize>());
+    let (level, rest) = rest.split_at(std::mem::size_of::<libc::c_int>());
+    let ty = &rest[..std::mem::size_of::<libc::c_int>()];
+
+    (
+        usize::from_ne_bytes(len.try_into().unwrap()),
+        libc::c_int::from_ne_bytes(level.try_into().unwrap()),
+        libc::c_int::from_ne_bytes(ty.try_into().unwrap()),
+    )
+}
+
+fn write_cmsg_header(control: &mut Vec<u8>, len: usize, ty: libc::c_int) {
+    control.extend_from_slice(&len.to_ne_bytes());
+    control.extend_from_slice(&libc::SOL_SOCKET.to_ne_bytes());
+    control.extend_from_slice(&ty.to_ne_bytes());
+    control.resize(cmsg_align(control.len()), 0);
+}
+
+fn read_ucred(data: &[u8]) -> libc::ucred {
+    let (pid, rest) = data.split_at(std::mem::size_of::<libc::pid_t>());
+    let (uid, gid) = rest.split_at(std::mem::size_of::<libc::uid_t>());
+
+    libc::ucred {
+        pid: libc::pid_t::from_ne_bytes(pid.try_into().unwrap()),
+        uid: libc::uid_t::from_ne_bytes(uid.try_into().unwrap()),
+        gid: libc::gid_t::from_ne_bytes(gid.try_into().unwrap()),
+    }
+}
@@ -16,6 +16,7 @@ use crate::host::descriptor::shared_buf::{
     BufferHandle, BufferSignals, BufferState, ReaderHandle, SharedBuf, WriterHandle,
 };
 use crate::host::descriptor::socket::abstract_unix_ns::AbstractUnixNamespace;
+use crate::host::descriptor::socket::scm::ScmData;
 use crate::host::descriptor::socket::{RecvmsgArgs, RecvmsgReturn, SendmsgArgs, Socket};
 use crate::host::descriptor::{
     File, FileMode, FileSignals, FileState, FileStatus, OpenFile, SyscallResult,
@@ -61,6 +62,10 @@ impl UnixSocket {
                 socket_type,
                 namespace: Arc::clone(namespace),
                 has_open_file: false,
+                recv_ancillary: VecDeque::new(),
+                recv_write_pos: 0,
+                recv_read_pos: 0,
+                pass_cred: false,
             };
 
             // may generate new events
@@ -320,6 +325,13 @@ impl UnixSocket {
                 let written = write_partial(memory_manager, &proto, optval_ptr, optlen as usize)?;
                 Ok(written as libc::socklen_t)
             }
+            (libc::SOL_SOCKET, libc::SO_PASSCRED) => {
+                let pass_cred = libc::c_int::from(self.common.pass_cred);
+                let optval_ptr = optval_ptr.cast::<libc::c_int>();
+                let written =
+                    write_partial(memory_manager, &pass_cred, optval_ptr, optlen as usize)?;
+                Ok(written as libc::socklen_t)
+            }
             (libc::SOL_SOCKET, libc::SO_ACCEPTCONN) => {
                 let is_listening: libc::c_int = matches!(
                     self.protocol_state,
@@ -377,11 +389,20 @@ impl UnixSocket {
                 self.common.send_limit = val as u64;
                 Ok(())
             }
+            (libc::SOL_SOCKET, libc::SO_PASSCRED) => {
+                type OptType = libc::c_int;
+                if usize::try_from(optlen).unwrap() < std::mem::size_of::<OptType>() {
+                    return Err(Errno::EINVAL.into());
+                }
+                let optval_ptr = optval_ptr.cast::<OptType>();
+                let val = memory_manager.read(optval_ptr)?;
+                self.common.pass_cred = val != 0;
+                Ok(())
+            }
             (libc::SOL_SOCKET, libc::SO_RCVBUF)
             | (libc::SOL_SOCKET, libc::SO_REUSEADDR)
             | (libc::SOL_SOCKET, libc::SO_REUSEPORT)
-            | (libc::SOL_SOCKET, libc::SO_KEEPALIVE)
-            | (libc::SOL_SOCKET, libc::SO_PASSCRED) => {
+            | (libc::SOL_SOCKET, libc::SO_KEEPALIVE) => {
                 // 接受并忽略：与很多应用兼容，避免 ENOSYS/ENOPROTOOPT 造成失败
                 // 仅校验长度有效
                 if usize::try_from(optlen).unwrap() < std::mem::size_of::<libc::c_int>() {
@@ -1704,14 +1725,21 @@ impl Protocol for ConnOrientedConnected {
         mem: &mut MemoryManager,
         cb_queue: &mut CallbackQueue,
     ) -> Result<libc::ssize_t, SyscallError> {
-        if !args.control_ptr.ptr().is_null() {
-            log::debug!("Unix sockets don't yet support control data for sendmsg()");
-            return Err(Errno::EINVAL.into());
-        }
+        let scm = ScmData::read(
+            mem,
+            args.control_ptr,
+            args.descriptor_table,
+            &args.credentials,
+        )?;
 
         let recv_socket = common.resolve_destination(Some(&self.peer), args.addr)?;
         let rv = common.sendmsg(socket, args.iovs, args.flags, &recv_socket, mem, cb_queue)?;
 
+        recv_socket
+            .borrow_mut()
+            .common
+            .add_ancillary(rv, scm, common.pass_cred, args.credentials);
+
         self.refresh_file_state(common, FileSignals::empty(), cb_queue);
 
         Ok(rv.try_into().unwrap())
@@ -1725,13 +1753,8 @@ impl Protocol for ConnOrientedConnected {
         mem: &mut MemoryManager,
         cb_queue: &mut CallbackQueue,
     ) -> Result<RecvmsgReturn, SyscallError> {
-        if !args.control_ptr.ptr().is_null() {
-            log::debug!("Unix sockets don't yet support control data for recvmsg()");
-            return Err(Errno::EINVAL.into());
-        }
-
-        let (rv, num_removed_from_buf, msg_flags) =
-            common.recvmsg(socket, args.iovs, args.flags, mem, cb_queue)?;
+        let (rv, num_removed_from_buf, msg_flags, control_len) =
+            common.recvmsg(socket, args, mem, cb_queue)?;
         let num_removed_from_buf = u64::try_from(num_removed_from_buf).unwrap();
 
         if num_removed_from_buf > 0 {
@@ -1749,7 +1772,7 @@ impl Protocol for ConnOrientedConnected {
             return_val: rv.try_into().unwrap(),
             addr: self.peer_addr.map(Into::into),
             msg_flags,
-            control_len: 0,
+            control_len,
         })
     }
 
@@ -1911,10 +1934,12 @@ impl Protocol for ConnLessInitial {
         mem: &mut MemoryManager,
         cb_queue: &mut CallbackQueue,
     ) -> Result<libc::ssize_t, SyscallError> {
-        if !args.control_ptr.ptr().is_null() {
-            log::debug!("Unix sockets don't yet support control data for sendmsg()");
-            return Err(Errno::EINVAL.into());
-        }
+        let scm = ScmData::read(
+            mem,
+            args.control_ptr,
+            args.descriptor_table,
+            &args.credentials,
+        )?;
 
         let recv_socket = common.resolve_destination(self.peer.as_ref(), args.addr)?;
         let rv = common.sendmsg(socket, args.iovs, args.flags, &recv_socket, mem, cb_queue)?;
@@ -1925,7 +1950,13 @@ impl Protocol for ConnLessInitial {
             num_bytes: rv.try_into().unwrap(),
         };
 
-        match &mut recv_socket.borrow_mut().protocol_state {
+        let mut recv_socket = recv_socket.borrow_mut();
+
+        recv_socket
+            .common
+            .add_ancillary(rv, scm, common.pass_cred, args.credentials);
+
+        match &mut recv_socket.protocol_state {
             ProtocolState::ConnLessInitial(state) => {
                 state.as_mut().unwrap().recv_data.push_back(byte_data);
             }
@@ -1948,13 +1979,8 @@ impl Protocol for ConnLessInitial {
         mem: &mut MemoryManager,
         cb_queue: &mut CallbackQueue,
     ) -> Result<RecvmsgReturn, SyscallError> {
-        if !args.control_ptr.ptr().is_null() {
-            log::debug!("Unix sockets don't yet support control data for recvmsg()");
-            return Err(Errno::EINVAL.into());
-        }
-
-        let (rv, num_removed_from_buf, msg_flags) =
-            common.recvmsg(socket, args.iovs, args.flags, mem, cb_queue)?;
+        let (rv, num_removed_from_buf, msg_flags, control_len) =
+            common.recvmsg(socket, args, mem, cb_queue)?;
         let num_removed_from_buf = u64::try_from(num_removed_from_buf).unwrap();
 
         let byte_data = self.recv_data.pop_front().unwrap();
@@ -1974,7 +2000,7 @@ impl Protocol for ConnLessInitial {
             return_val: rv.try_into().unwrap(),
             addr: byte_data.from_addr.map(Into::into),
             msg_flags,
-            control_len: 0,
+            control_len,
         })
     }
 
@@ -2108,6 +2134,15 @@ struct UnixSocketCommon {
     // should only be used by `OpenFile` to make sure there is only ever one `OpenFile` instance for
     // this file
     has_open_file: bool,
+    /// Ancillary data sent to this socket that hasn't been received yet, in the order it was sent.
+    recv_ancillary: VecDeque<AncillaryData>,
+    /// The position in the recv buffer after the last write. The position is counted in bytes for
+    /// stream sockets and in messages for message-based sockets.
+    recv_write_pos: u64,
+    /// The position in the recv buffer after the last read.
+    recv_read_pos: u64,
+    /// Whether `SO_PASSCRED` is enabled.
+    pass_cred: bool,
 }
 
 impl UnixSocketCommon {
@@ -2131,6 +2166,13 @@ impl UnixSocketCommon {
             debug_panic!("When closing a unix socket, the CLOSED flag was not set");
         }
 
+        // close any descriptors that were never received, but not until we're done processing
+        // this socket since one of them may refer to this socket
+        let ancillary = std::mem::take(&mut self.recv_ancillary);
+        if !ancillary.is_empty() {
+            cb_queue.add(move |_cb_queue| drop(ancillary));
+        }
+
         Ok(())
     }
 
@@ -2353,20 +2395,57 @@ impl UnixSocketCommon {
         Ok(result?)
     }
 
+    /// Record that a message of `len` bytes was written to this socket's recv buffer, along with
+    /// the ancillary data that was sent with it. Must be called after every successful write to the
+    /// recv buffer. Credentials are added if either socket has enabled `SO_PASSCRED`.
+    pub fn add_ancillary(
+        &mut self,
+        len: usize,
+        mut scm: ScmData,
+        sender_pass_cred: bool,
+        credentials: libc::ucred,
+    ) {
+        let len = match self.socket_type {
+            UnixSocketType::Stream => u64::try_from(len).unwrap(),
+            UnixSocketType::Dgram | UnixSocketType::SeqPacket => 1,
+        };
+
+        // a stream socket doesn't write anything for empty messages, so the ancillary data is
+        // dropped
+        if len == 0 {
+            return;
+        }
+
+        let start = self.recv_write_pos;
+        self.recv_write_pos += len;
+
+        if scm.credentials.is_none() && (self.pass_cred || sender_pass_cred) {
+            scm.credentials = Some(credentials);
+        }
+
+        if !scm.is_empty() {
+            self.recv_ancillary.push_back(AncillaryData {
+                start,
+                end: self.recv_write_pos,
+                scm,
+            });
+        }
+    }
+
     pub fn recvmsg(
         &mut self,
         socket: &Arc<AtomicRefCell<UnixSocket>>,
-        iovs: &[IoVec],
-        flags: libc::c_int,
+        args: RecvmsgArgs,
         mem: &mut MemoryManager,
         cb_queue: &mut CallbackQueue,
-    ) -> Result<(usize, usize, libc::c_int), SyscallError> {
-        let supported_flags = MsgFlags::MSG_DONTWAIT | MsgFlags::MSG_TRUNC;
+    ) -> Result<(usize, usize, libc::c_int, libc::size_t), SyscallError> {
+        let supported_flags =
+            MsgFlags::MSG_DONTWAIT | MsgFlags::MSG_TRUNC | MsgFlags::MSG_CMSG_CLOEXEC;
 
         // if there's a flag we don't support, it's probably best to raise an error rather than do
         // the wrong thing
-        let Some(mut flags) = MsgFlags::from_bits(flags) else {
-            log::warn!("Unrecognized recv flags: {flags:#b}");
+        let Some(mut flags) = MsgFlags::from_bits(args.flags) else {
+            log::warn!("Unrecognized recv flags: {:#b}", args.flags);
             return Err(Errno::EINVAL.into());
         };
         if flags.intersects(!supported_flags) {
@@ -2393,13 +2472,65 @@ impl UnixSocketCommon {
                 return Err(Errno::EWOULDBLOCK);
             }
 
+            let next_ancillary = self.recv_ancillary.front().map(|x| (x.start, x.end));
+
+            // like linux, a read from a stream socket stops after the data of a message that was
+            // sent with ancillary data, so that the ancillary data is received with that message
+            let limited_iovs;
+            let iovs = match (self.socket_type, next_ancillary) {
+                (UnixSocketType::Stream, Some((_start, end))) => {
+                    let limit = end - self.recv_read_pos;
+                    limited_iovs = truncate_iovs(args.iovs, limit.try_into().unwrap());
+                    &limited_iovs[..]
+                }
+                _ => args.iovs,
+            };
+
+            let had_data = recv_buffer.has_data();
             let writer = IoVecWriter::new(iovs, mem);
 
             let (num_copied, num_removed_from_buf) = recv_buffer
                 .read(writer, cb_queue)
                 .map_err(|e| Errno::try_from(e).unwrap())?;
 
+            let num_read = match self.socket_type {
+                UnixSocketType::Stream => u64::try_from(num_removed_from_buf).unwrap(),
+                UnixSocketType::Dgram | UnixSocketType::SeqPacket => u64::from(had_data),
+            };
+            let read_end = self.recv_read_pos + num_read;
+            self.recv_read_pos = read_end;
+
+            // the ancillary data is received by the first read that includes any of its message
+            let scm = match next_ancillary {
+                Some((start, _end)) if start < read_end => {
+                    self.recv_ancillary.pop_front().map(|x| x.scm)
+                }
+                _ => None,
+            };
+
             let mut msg_flags = 0;
+            let mut control_len = 0;
+
+            if let Some(scm) = scm {
+                let (len, truncated, discarded) = scm.write(
+                    mem,
+                    args.control_ptr,
+                    args.descriptor_table,
+                    self.pass_cred,
+                    flags.contains(MsgFlags::MSG_CMSG_CLOEXEC),
+                )?;
+
+                control_len = len;
+                if truncated {
+                    msg_flags |= libc::MSG_CTRUNC;
+                }
+
+                // close the descriptors once we're done processing this socket, since one of them
+                // may refer to this socket
+                if !discarded.is_empty() {
+                    cb_queue.add(move |_cb_queue| drop(discarded));
+                }
+            }
 
             if flags.contains(MsgFlags::MSG_TRUNC)
                 && [UnixSocketType::Dgram, UnixSocketType::SeqPacket].contains(&self.socket_type)
@@ -2410,11 +2541,16 @@ impl UnixSocketCommon {
 
                 // we're a message-based socket and MSG_TRUNC is set, so return the total size of
                 // the message, not the number of bytes we read
-                Ok((num_removed_from_buf, num_removed_from_buf, msg_flags))
+                Ok((
+                    num_removed_from_buf,
+                    num_removed_from_buf,
+                    msg_flags,
+                    control_len,
+                ))
             } else {
                 // We're a stream-based socket. Unlike TCP sockets, unix stream sockets ignore the
                 // MSG_TRUNC flag.
-                Ok((num_copied, num_removed_from_buf, msg_flags))
+                Ok((num_copied, num_removed_from_buf, msg_flags, control_len))
             }
         })();
 
@@ -2553,3 +2689,30 @@ struct ByteData {
     from_addr: Option<SockaddrUnix<libc::sockaddr_un>>,
     num_bytes: u64,
 }
+
+/// Ancillary data that was sent with the data between two positions in a socket's recv buffer.
+struct AncillaryData {
+    start: u64,
+    end: u64,
+    scm: ScmData,
+}
+
+/// Returns the [`IoVec`] buffers truncated to a total length of at most `len` bytes.
+fn truncate_iovs(iovs: &[IoVec], mut len: usize) -> Vec<IoVec> {
+    let mut truncated = Vec::new();
+
+    for iov in iovs {
+        if len == 0 {
+            break;
+        }
+
+        let iov_len = std::cmp::min(iov.len, len);
+        truncated.push(IoVec {
+            base: iov.base,
+            len: iov_len,
+        });
+        len -= iov_len;
+    }
+
+    truncated
+}
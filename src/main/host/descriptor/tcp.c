@@ -26,7 +26,6 @@
 #include "main/host/descriptor/descriptor.h"
 #include "main/host/descriptor/socket.h"
 #include "main/host/descriptor/tcp_cong.h"
-#include "main/host/descriptor/tcp_cong_reno.h"
 #include "main/host/descriptor/tcp_retransmit_tally.h"
 #include "main/network/legacypacket.h"
 #include "main/utility/priority_queue.h"
@@ -119,6 +118,10 @@ struct _TCP {
     // can't avoid it because `_flush` calls back into the host
     InetSocketWeak* rustSocket;
 
+    /* the address family (see `tcp_setFamily()`) */
+    gboolean ipv6;
+    gboolean v6only;
+
     enum TCPState state;
     enum TCPState stateLast;
     enum TCPFlags flags;
@@ -208,6 +211,8 @@ struct _TCP {
     struct {
       gint rttSmoothed;
       gint rttVariance;
+      /* the most recent RTT sample, used by congestion control */
+      CSimulationTime rttSample;
     } timing;
 
     /* TODO: these should probably be stamped when the network interface sends
@@ -388,6 +393,16 @@ struct TCPCong_ *tcp_cong(TCP *tcp) {
     return &tcp->cong;
 }
 
+CSimulationTime tcp_getRTTSample(TCP* tcp) {
+    MAGIC_ASSERT(tcp);
+    return tcp->timing.rttSample;
+}
+
+guint32 tcp_getPacketsInFlight(TCP* tcp) {
+    MAGIC_ASSERT(tcp);
+    return tcp->send.next - tcp->send.unacked;
+}
+
 void tcp_clearAllChildrenIfServer(TCP* tcp) {
     MAGIC_ASSERT(tcp);
     if(tcp->server) {
@@ -735,7 +750,8 @@ static void _tcp_setState(TCP* tcp, const Host* host, enum TCPState state) {
                 in_addr_t peer_ip = 0;
                 in_port_t peer_port = 0;
                 legacysocket_getPeerName(&tcp->super, &peer_ip, &peer_port);
-                host_disassociateInterface(host, PTCP, sock_ip, sock_port, peer_ip, peer_port);
+                host_disassociateInterface(
+                    host, PTCP, tcp->ipv6, tcp->v6only, sock_ip, sock_port, peer_ip, peer_port);
             }
             break;
         }
@@ -1966,6 +1982,12 @@ TCPProcessFlags _tcp_ackProcessing(TCP* tcp, const Host* host, Packet* packet,
         flags |= TCP_PF_RWND_UPDATED;
     }
 
+    /* sample the RTT before the congestion hooks run; the smoothed estimate is updated after
+     * the ack is processed */
+    if(header->timestampEcho && tcp->retransmit.backoffCount == 0) {
+        tcp->timing.rttSample = MAX(SIMTIME_ONE_NANOSECOND, now - header->timestampEcho);
+    }
+
     /* duplicate acks indicate out of order data on the other end of connection. */
     bool is_dup = (header->flags & PTCP_DUPACK);
 
@@ -2161,6 +2183,10 @@ static void _tcp_processPacket(LegacySocket* socket, const Host* host, Packet* p
 
                 /* we need to multiplex a new child */
                 TCP* multiplexed = tcp_new(host, recvBufSize, sendBufSize);
+                /* like linux, the child uses the listening socket's congestion control */
+                tcpcong_setAlgorithm(multiplexed, tcpcong_getAlgorithm(tcp));
+                /* the child has the same address family as the listening socket */
+                tcp_setFamily(multiplexed, tcp->ipv6, tcp->v6only);
                 Descriptor* desc = descriptor_fromLegacyTcp(multiplexed, /* flags= */ 0);
                 int handle = thread_registerDescriptor(registerInThread, desc);
 
@@ -2895,6 +2921,22 @@ void tcp_setRustSocket(TCP* tcp, InetSocketWeak* rustSocket) {
     tcp->rustSocket = rustSocket;
 }
 
+void tcp_setFamily(TCP* tcp, gboolean ipv6, gboolean v6only) {
+    MAGIC_ASSERT(tcp);
+    tcp->ipv6 = ipv6;
+    tcp->v6only = v6only;
+}
+
+gboolean tcp_isIpv6(TCP* tcp) {
+    MAGIC_ASSERT(tcp);
+    return tcp->ipv6;
+}
+
+gboolean tcp_isV6Only(TCP* tcp) {
+    MAGIC_ASSERT(tcp);
+    return tcp->v6only;
+}
+
 TCP* tcp_new(const Host* host, guint receiveBufferSize, guint sendBufferSize) {
     TCP* tcp = g_new0(TCP, 1);
     MAGIC_INIT(tcp);
@@ -2905,9 +2947,7 @@ TCP* tcp_new(const Host* host, guint receiveBufferSize, guint sendBufferSize) {
     guint32 initial_window = 10;
     gint tcpSSThresh = 0;
 
-    /* in the future we'd like to support more congestion control types
-     * and allow it to be set as a host option */
-    tcp_cong_reno_init(tcp);
+    tcpcong_init(tcp, host_getTCPCongestionControl(host));
 
     tcp->send.window = initial_window;
     tcp->send.lastWindow = initial_window;
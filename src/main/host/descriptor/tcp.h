@@ -44,6 +44,12 @@ TCP* tcp_new(const Host* host, guint receiveBufferSize, guint sendBufferSize);
 
 void tcp_setRustSocket(TCP* tcp, InetSocketWeak* rustSocket);
 
+/* The socket's address family (see `InetFamily` in the rust code). New sockets are IPv4 sockets,
+ * and the child sockets of a server have the same family as the server. */
+void tcp_setFamily(TCP* tcp, gboolean ipv6, gboolean v6only);
+gboolean tcp_isIpv6(TCP* tcp);
+gboolean tcp_isV6Only(TCP* tcp);
+
 // clang-format off
 /* Returns a positive number to indicate that we have not yet sent a SYN
  * packet, i.e., connect() has not been called.
@@ -72,6 +78,9 @@ gint tcp_acceptServerPeer(TCP* tcp, const Host* host, in_addr_t* ip, in_port_t*
                           gint* acceptedHandle);
 
 struct TCPCong_ *tcp_cong(TCP *tcp);
+/* the round-trip time of the most recently acknowledged packet, or 0 if unknown */
+CSimulationTime tcp_getRTTSample(TCP* tcp);
+guint32 tcp_getPacketsInFlight(TCP* tcp);
 
 void tcp_clearAllChildrenIfServer(TCP* tcp);
 
@@ -0,0 +1,532 @@
+//! BBR congestion control (version 1), following linux's `tcp_bbr.c`.
+//!
+//! Shadow's legacy TCP stack doesn't pace packets, so rather than having separate pacing and
+//! window gains, each mode's gain is applied to the congestion window: the window is the gain times
+//! the estimated bandwidth-delay product. The delivery rate is sampled once per round-trip instead
+//! of for each acknowledged packet.
+
+use std::collections::VecDeque;
+use std::ffi::CStr;
+use std::time::Duration;
+
+use shadow_shim_helper_rs::emulated_time::EmulatedTime;
+use shadow_shim_helper_rs::simulation_time::SimulationTime;
+
+use super::{CongestionControl, ConnectionState, INFINITE_SSTHRESH, INITIAL_CWND};
+
+/// The gain used in startup, which allows the sending rate to double each round-trip.
+const HIGH_GAIN: f64 = 2.0 / std::f64::consts::LN_2;
+
+/// The gain in each phase of the bandwidth probing cycle.
+const PROBE_GAINS: [f64; 8] = [1.25, 0.75, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0];
+
+/// The probing phase entered after draining. Linux enters a random phase, but we use the first
+/// phase that doesn't probe.
+const FIRST_PROBE_PHASE: usize = 2;
+
+/// The number of round-trips that a bandwidth sample is kept for.
+const BANDWIDTH_WINDOW_ROUNDS: u64 = 10;
+
+/// How long a minimum RTT sample is kept for before probing for a new one.
+const MIN_RTT_WINDOW: SimulationTime = SimulationTime::from_duration(Duration::from_secs(10));
+
+/// How long the window is kept small while probing for a new minimum RTT.
+const PROBE_RTT_DURATION: SimulationTime =
+    SimulationTime::from_duration(Duration::from_millis(200));
+
+/// The bandwidth must grow by this factor to not be considered a plateau in startup.
+const FULL_BANDWIDTH_THRESHOLD: f64 = 1.25;
+
+/// The number of round-trips without bandwidth growth before startup ends.
+const FULL_BANDWIDTH_ROUNDS: u32 = 3;
+
+const MIN_CWND: u32 = 4;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Mode {
+    /// Grow the window exponentially until the bandwidth stops increasing.
+    Startup,
+    /// Drain the queue that was built up in startup.
+    Drain,
+    /// Cycle through the probing gains.
+    ProbeBw { phase: usize },
+    /// Reduce the window to measure the minimum RTT.
+    ProbeRtt,
+}
+
+#[derive(Debug)]
+pub struct Bbr {
+    cwnd: u32,
+    mode: Mode,
+    /// The total number of packets acknowledged.
+    delivered: u64,
+    /// The number of round-trips started.
+    round_count: u64,
+    /// The current round-trip ends once this many packets have been acknowledged.
+    next_round_delivered: u64,
+    /// The time and number of delivered packets at the start of the current round-trip.
+    round_start: Option<(EmulatedTime, u64)>,
+    /// Delivery rate samples (in packets per second) with the round-trip that they were taken in.
+    bandwidth_samples: VecDeque<(u64, f64)>,
+    min_rtt: Option<SimulationTime>,
+    min_rtt_stamp: EmulatedTime,
+    full_bandwidth: f64,
+    full_bandwidth_count: u32,
+    full_bandwidth_reached: bool,
+    /// The start of the current probing phase.
+    phase_start: EmulatedTime,
+    /// When probing for the minimum RTT can end, once the window has been reduced.
+    probe_rtt_done: Option<EmulatedTime>,
+    /// The window before probing for the minimum RTT.
+    probe_rtt_prior_cwnd: u32,
+    /// The window to restore after a loss once this many packets have been acknowledged.
+    restore_cwnd: Option<(u32, u64)>,
+    duplicate_acks: u32,
+    in_fast_recovery: bool,
+}
+
+impl Bbr {
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// The maximum delivery rate (in packets per second) over the recent round-trips.
+    fn bandwidth(&self) -> Option<f64> {
+        self.bandwidth_samples
+            .iter()
+            .map(|(_, rate)| *rate)
+            .max_by(f64::total_cmp)
+    }
+
+    /// The window for `gain` times the estimated bandwidth-delay product.
+    fn target_cwnd(&self, gain: f64) -> u32 {
+        let (Some(bandwidth), Some(min_rtt)) = (self.bandwidth(), self.min_rtt) else {
+            return INITIAL_CWND;
+        };
+
+        let bdp = bandwidth * min_rtt.as_nanos_f64() / 1e9;
+        (bdp * gain).ceil() as u32
+    }
+
+    fn gain(&self) -> f64 {
+        match self.mode {
+            Mode::Startup => HIGH_GAIN,
+            Mode::Drain | Mode::ProbeRtt => 1.0,
+            Mode::ProbeBw { phase } => PROBE_GAINS[phase],
+        }
+    }
+
+    /// Count round-trips and take a delivery rate sample at the start of each. Returns true if a
+    /// new round-trip started.
+    fn update_bandwidth(&mut self, state: &ConnectionState) -> bool {
+        if self.delivered <= self.next_round_delivered {
+            return false;
+        }
+
+        if let Some((start, start_delivered)) = self.round_start {
+            if state.now > start {
+                let elapsed = (state.now - start).as_nanos_f64() / 1e9;
+                let rate = (self.delivered - start_delivered) as f64 / elapsed;
+                self.bandwidth_samples.push_back((self.round_count, rate));
+            }
+        }
+
+        self.round_count += 1;
+        self.round_start = Some((state.now, self.delivered));
+        self.next_round_delivered = self.delivered + u64::from(state.packets_in_flight);
+
+        while let Some((round, _)) = self.bandwidth_samples.front() {
+            if round + BANDWIDTH_WINDOW_ROUNDS > self.round_count {
+                break;
+            }
+            self.bandwidth_samples.pop_front();
+        }
+
+        true
+    }
+
+    fn update_probe_phase(&mut self, acked: u32, state: &ConnectionState) {
+        let Mode::ProbeBw { phase } = self.mode else {
+            return;
+        };
+
+        let full_length =
+            state.now - self.phase_start > self.min_rtt.unwrap_or(SimulationTime::ZERO);
+        let prior_in_flight = state.packets_in_flight + acked;
+        let gain = PROBE_GAINS[phase];
+
+        let advance = if gain > 1.0 {
+            // probe until the extra packets are in flight
+            full_length && prior_in_flight >= self.target_cwnd(gain)
+        } else if gain < 1.0 {
+            // drain until any queue has been drained
+            full_length || prior_in_flight <= self.target_cwnd(1.0)
+        } else {
+            full_length
+        };
+
+        if advance {
+            self.mode = Mode::ProbeBw {
+                phase: (phase + 1) % PROBE_GAINS.len(),
+            };
+            self.phase_start = state.now;
+        }
+    }
+
+    fn check_full_bandwidth(&mut self, round_start: bool) {
+        if self.full_bandwidth_reached || !round_start {
+            return;
+        }
+
+        let Some(bandwidth) = self.bandwidth() else {
+            return;
+        };
+
+        if bandwidth >= self.full_bandwidth * FULL_BANDWIDTH_THRESHOLD {
+            self.full_bandwidth = bandwidth;
+            self.full_bandwidth_count = 0;
+            return;
+        }
+
+        self.full_bandwidth_count += 1;
+        self.full_bandwidth_reached = self.full_bandwidth_count >= FULL_BANDWIDTH_ROUNDS;
+    }
+
+    fn check_drain(&mut self, state: &ConnectionState) {
+        if self.mode == Mode::Startup && self.full_bandwidth_reached {
+            self.mode = Mode::Drain;
+        }
+
+        if self.mode == Mode::Drain && state.packets_in_flight <= self.target_cwnd(1.0) {
+            self.enter_probe_bw(state.now);
+        }
+    }
+
+    fn enter_probe_bw(&mut self, now: EmulatedTime) {
+        self.mode = Mode::ProbeBw {
+            phase: FIRST_PROBE_PHASE,
+        };
+        self.phase_start = now;
+    }
+
+    fn update_min_rtt(&mut self, state: &ConnectionState) {
+        let expired = self.min_rtt.is_some() && state.now > self.min_rtt_stamp + MIN_RTT_WINDOW;
+
+        if let Some(rtt) = state.rtt {
+            if self.min_rtt.is_none_or(|x| rtt < x) || expired {
+                self.min_rtt = Some(rtt);
+                self.min_rtt_stamp = state.now;
+            }
+        }
+
+        if expired && self.mode != Mode::ProbeRtt {
+            self.mode = Mode::ProbeRtt;
+            self.probe_rtt_prior_cwnd = self.cwnd;
+            self.probe_rtt_done = None;
+        }
+
+        if self.mode != Mode::ProbeRtt {
+            return;
+        }
+
+        match self.probe_rtt_done {
+            None if state.packets_in_flight <= MIN_CWND => {
+                self.probe_rtt_done = Some(state.now + PROBE_RTT_DURATION);
+            }
+            Some(done) if state.now >= done => {
+                self.min_rtt_stamp = state.now;
+                self.cwnd = std::cmp::max(self.cwnd, self.probe_rtt_prior_cwnd);
+                if self.full_bandwidth_reached {
+                    self.enter_probe_bw(state.now);
+                } else {
+                    self.mode = Mode::Startup;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn update_cwnd(&mut self, acked: u32) {
+        if let Some((cwnd, delivered)) = self.restore_cwnd {
+            if self.delivered >= delivered {
+                self.cwnd = std::cmp::max(self.cwnd, cwnd);
+                self.restore_cwnd = None;
+            }
+        }
+
+        let target = self.target_cwnd(self.gain());
+        if self.full_bandwidth_reached {
+            self.cwnd = std::cmp::min(self.cwnd + acked, target);
+        } else if self.cwnd < target || self.delivered < u64::from(INITIAL_CWND) {
+            self.cwnd += acked;
+        }
+
+        self.cwnd = std::cmp::max(self.cwnd, MIN_CWND);
+        if self.mode == Mode::ProbeRtt {
+            self.cwnd = std::cmp::min(self.cwnd, MIN_CWND);
+        }
+    }
+
+    /// Remember the window so that it can be restored once `delivered` packets have been
+    /// acknowledged.
+    fn save_cwnd(&mut self, delivered: u64) {
+        let prior_cwnd = self.restore_cwnd.map_or(self.cwnd, |(x, _)| x);
+        self.restore_cwnd = Some((std::cmp::max(prior_cwnd, self.cwnd), delivered));
+    }
+}
+
+impl CongestionControl for Bbr {
+    const NAME: &'static CStr = c"bbr";
+
+    fn new(cwnd: u32) -> Self {
+        Self {
+            cwnd,
+            mode: Mode::Startup,
+            delivered: 0,
+            round_count: 0,
+            next_round_delivered: 0,
+            round_start: None,
+            bandwidth_samples: VecDeque::new(),
+            min_rtt: None,
+            min_rtt_stamp: EmulatedTime::SIMULATION_START,
+            full_bandwidth: 0.0,
+            full_bandwidth_count: 0,
+            full_bandwidth_reached: false,
+            phase_start: EmulatedTime::SIMULATION_START,
+            probe_rtt_done: None,
+            probe_rtt_prior_cwnd: 0,
+            restore_cwnd: None,
+            duplicate_acks: 0,
+            in_fast_recovery: false,
+        }
+    }
+
+    fn cwnd(&self) -> u32 {
+        self.cwnd
+    }
+
+    fn ssthresh(&self) -> u32 {
+        // bbr doesn't use a slow start threshold
+        INFINITE_SSTHRESH
+    }
+
+    fn in_fast_recovery(&self) -> bool {
+        self.in_fast_recovery
+    }
+
+    fn new_ack(&mut self, acked: u32, state: &ConnectionState) {
+        self.duplicate_acks = 0;
+        self.in_fast_recovery = false;
+        self.delivered += u64::from(acked);
+
+        let round_start = self.update_bandwidth(state);
+        self.update_probe_phase(acked, state);
+        self.check_full_bandwidth(round_start);
+        self.check_drain(state);
+        self.update_min_rtt(state);
+        self.update_cwnd(acked);
+    }
+
+    fn duplicate_ack(&mut self, state: &ConnectionState) {
+        self.duplicate_acks += 1;
+
+        if self.in_fast_recovery {
+            self.cwnd += 1;
+        } else if self.duplicate_acks == 3 {
+            // packet conservation: only send a new packet for each packet that leaves the network,
+            // and restore the window when the recovery ends
+            self.save_cwnd(self.delivered);
+            self.cwnd = std::cmp::max(state.packets_in_flight, MIN_CWND);
+            self.in_fast_recovery = true;
+        }
+    }
+
+    fn timeout(&mut self, state: &ConnectionState) {
+        // restore the window once the packets that were in flight have been acknowledged
+        self.save_cwnd(self.delivered + u64::from(state.packets_in_flight));
+        self.cwnd = 1;
+        self.duplicate_acks = 0;
+        self.in_fast_recovery = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE_RTT: SimulationTime = SimulationTime::from_duration(Duration::from_millis(100));
+
+    /// The bottleneck sends one packet per millisecond, so the bandwidth-delay product is 100
+    /// packets.
+    const TRANSMIT_TIME: SimulationTime = SimulationTime::from_duration(Duration::from_millis(1));
+
+    /// Send packets over a single bottleneck link until `end`, and return the mode and window
+    /// after each acknowledgement.
+    fn simulate(bbr: &mut Bbr, end: EmulatedTime) -> Vec<(EmulatedTime, Mode, u32)> {
+        let mut now = EmulatedTime::SIMULATION_START;
+        // the ack time and send time of each packet in flight
+        let mut in_flight = VecDeque::<(EmulatedTime, EmulatedTime)>::new();
+        let mut last_departure = now;
+        let mut trace = Vec::new();
+
+        loop {
+            while in_flight.len() < bbr.cwnd() as usize {
+                let departure = std::cmp::max(now, last_departure) + TRANSMIT_TIME;
+                last_departure = departure;
+                in_flight.push_back((departure + BASE_RTT - TRANSMIT_TIME, now));
+            }
+
+            let (ack_time, send_time) = in_flight.pop_front().unwrap();
+            if ack_time > end {
+                return trace;
+            }
+            now = ack_time;
+
+            let state = ConnectionState {
+                now,
+                rtt: Some(now - send_time),
+                packets_in_flight: in_flight.len() as u32,
+            };
+            bbr.new_ack(1, &state);
+            trace.push((now, bbr.mode(), bbr.cwnd()));
+        }
+    }
+
+    /// Merge consecutive acknowledgements in the same mode, and return the window at the end of
+    /// each.
+    fn mode_changes(trace: &[(EmulatedTime, Mode, u32)]) -> Vec<(Mode, u32)> {
+        let mut changes: Vec<(Mode, u32)> = Vec::new();
+        for (_, mode, cwnd) in trace {
+            match changes.last_mut() {
+                Some((last_mode, last_cwnd)) if last_mode == mode => *last_cwnd = *cwnd,
+                _ => changes.push((*mode, *cwnd)),
+            }
+        }
+        changes
+    }
+
+    #[test]
+    fn test_startup() {
+        let mut bbr = Bbr::new(INITIAL_CWND);
+        let end = EmulatedTime::SIMULATION_START + BASE_RTT * 8;
+        let trace = simulate(&mut bbr, end);
+        assert_eq!(bbr.mode(), Mode::Startup);
+
+        let cwnd_before = |rtts: u32| {
+            let time = EmulatedTime::SIMULATION_START + BASE_RTT * rtts;
+            trace
+                .iter()
+                .rfind(|(t, _, _)| *t < time)
+                .map_or(INITIAL_CWND, |(_, _, cwnd)| *cwnd)
+        };
+
+        // the window grows by at least half each round-trip (it's limited by the bandwidth
+        // measured in the previous round-trip) until the bottleneck is full
+        for rtts in 1..7 {
+            let (cwnd, next_cwnd) = (cwnd_before(rtts), cwnd_before(rtts + 1));
+            assert!(
+                f64::from(next_cwnd) >= 1.5 * f64::from(cwnd),
+                "{cwnd} -> {next_cwnd}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_probe_bw_cycle_formula() {
+        // the window at the end of each mode from the start of the connection, as given by the
+        // gains of `tcp_bbr.c` rather than by traces of linux: startup ends at `HIGH_GAIN`
+        // times the bandwidth-delay product (100 packets), drain reduces the window to the
+        // bandwidth-delay product, and then the probing cycle repeats with the gains
+        // [1.25, 0.75, 1, 1, 1, 1, 1, 1]
+        let probe = |phase| Mode::ProbeBw { phase };
+        let expected = [
+            (Mode::Startup, 289),
+            (Mode::Drain, 100),
+            (probe(2), 100),
+            (probe(3), 100),
+            (probe(4), 100),
+            (probe(5), 100),
+            (probe(6), 100),
+            (probe(7), 100),
+            (probe(0), 125),
+            (probe(1), 75),
+            (probe(2), 100),
+            (probe(3), 100),
+            (probe(4), 100),
+            (probe(5), 100),
+            (probe(6), 100),
+            (probe(7), 100),
+            (probe(0), 125),
+            (probe(1), 75),
+        ];
+
+        let mut bbr = Bbr::new(INITIAL_CWND);
+        let end = EmulatedTime::SIMULATION_START + SimulationTime::from_secs(5);
+        let trace = simulate(&mut bbr, end);
+
+        let changes = mode_changes(&trace);
+        assert_eq!(changes[..expected.len()], expected);
+    }
+
+    #[test]
+    fn test_probe_rtt() {
+        let mut bbr = Bbr::new(INITIAL_CWND);
+        let end = EmulatedTime::SIMULATION_START + SimulationTime::from_secs(12);
+        let trace = simulate(&mut bbr, end);
+
+        // the minimum RTT expires after 10 seconds, and the window is reduced for at least 200 ms
+        let start = trace
+            .iter()
+            .position(|(_, mode, _)| *mode == Mode::ProbeRtt)
+            .unwrap();
+        let len = trace[start..]
+            .iter()
+            .position(|(_, mode, _)| *mode != Mode::ProbeRtt)
+            .unwrap();
+        let probe_rtt = &trace[start..][..len];
+        let (start_time, end_time) = (trace[start].0, trace[start + len].0);
+        assert!(start_time > EmulatedTime::SIMULATION_START + SimulationTime::from_secs(10));
+        assert!(end_time - start_time >= PROBE_RTT_DURATION);
+        assert!(probe_rtt.iter().all(|(_, _, cwnd)| *cwnd == MIN_CWND));
+
+        // the window is restored afterwards
+        assert!(matches!(bbr.mode(), Mode::ProbeBw { .. }));
+        assert!(bbr.cwnd() >= 75);
+    }
+
+    #[test]
+    fn test_loss() {
+        let mut bbr = Bbr::new(INITIAL_CWND);
+        let end = EmulatedTime::SIMULATION_START + SimulationTime::from_secs(3);
+        simulate(&mut bbr, end);
+        let cwnd = bbr.cwnd();
+
+        let mut state = ConnectionState {
+            now: end,
+            rtt: Some(BASE_RTT),
+            packets_in_flight: 90,
+        };
+
+        // the window is restored after fast recovery
+        for _ in 0..3 {
+            bbr.duplicate_ack(&state);
+        }
+        assert!(bbr.in_fast_recovery());
+        assert_eq!(bbr.cwnd(), 90);
+        bbr.new_ack(1, &state);
+        assert!(!bbr.in_fast_recovery());
+        assert_eq!(bbr.cwnd(), cwnd);
+
+        // the window is restored after a timeout once the packets in flight have been
+        // acknowledged
+        state.packets_in_flight = 2;
+        bbr.timeout(&state);
+        assert_eq!(bbr.cwnd(), 1);
+        state.packets_in_flight = 0;
+        bbr.new_ack(1, &state);
+        assert_eq!(bbr.cwnd(), MIN_CWND);
+        bbr.new_ack(1, &state);
+        assert_eq!(bbr.cwnd(), cwnd);
+        assert_eq!(bbr.ssthresh(), INFINITE_SSTHRESH);
+    }
+}
@@ -0,0 +1,323 @@
+//! CUBIC congestion control ([RFC 9438]), following linux's `tcp_cubic.c` without HyStart.
+//!
+//! Fast recovery works the same as Shadow's reno implementation: the window is inflated by
+//! duplicate acknowledgements and deflated to the slow start threshold by the next new
+//! acknowledgement.
+//!
+//! [RFC 9438]: https://www.rfc-editor.org/rfc/rfc9438
+
+use std::ffi::CStr;
+
+use shadow_shim_helper_rs::emulated_time::EmulatedTime;
+use shadow_shim_helper_rs::simulation_time::SimulationTime;
+
+use super::{CongestionControl, ConnectionState, INFINITE_SSTHRESH};
+
+/// The multiplicative decrease factor.
+const BETA: f64 = 0.7;
+
+/// The scaling constant of the cubic function (in packets per second cubed).
+const C: f64 = 0.4;
+
+/// The additive increase of the reno-friendly window estimate per round-trip.
+const ALPHA: f64 = 3.0 * (1.0 - BETA) / (1.0 + BETA);
+
+#[derive(Debug)]
+pub struct Cubic {
+    cwnd: u32,
+    ssthresh: u32,
+    /// The number of packets acknowledged since the window was last increased.
+    cwnd_count: u32,
+    duplicate_acks: u32,
+    in_fast_recovery: bool,
+    /// The window before the last reduction (W_max), or 0 after a timeout.
+    last_max_cwnd: u32,
+    /// The start of the current congestion avoidance epoch.
+    epoch_start: Option<EmulatedTime>,
+    /// The window at the plateau of the cubic function in this epoch.
+    origin_point: f64,
+    /// The time from the start of the epoch until the plateau, in seconds.
+    k: f64,
+    /// The estimated window of reno, for the reno-friendly region.
+    reno_cwnd: f64,
+    min_rtt: Option<SimulationTime>,
+}
+
+impl Cubic {
+    /// Reduce the slow start threshold after congestion, and start a new epoch.
+    fn reduce(&mut self) {
+        self.epoch_start = None;
+
+        // fast convergence: release bandwidth for new flows if the window didn't reach the
+        // previous maximum
+        self.last_max_cwnd = if self.cwnd < self.last_max_cwnd {
+            (f64::from(self.cwnd) * (1.0 + BETA) / 2.0) as u32
+        } else {
+            self.cwnd
+        };
+
+        self.ssthresh = std::cmp::max((f64::from(self.cwnd) * BETA) as u32, 2);
+    }
+
+    fn congestion_avoidance(&mut self, acked: u32, now: EmulatedTime) {
+        if self.epoch_start.is_none() {
+            self.epoch_start = Some(now);
+            self.reno_cwnd = f64::from(self.cwnd);
+            if self.last_max_cwnd <= self.cwnd {
+                self.k = 0.0;
+                self.origin_point = f64::from(self.cwnd);
+            } else {
+                self.k = (f64::from(self.last_max_cwnd - self.cwnd) / C).cbrt();
+                self.origin_point = f64::from(self.last_max_cwnd);
+            }
+        }
+        let epoch_start = self.epoch_start.unwrap();
+
+        // the window that the cubic function reaches one round-trip from now
+        let t = now - epoch_start + self.min_rtt.unwrap_or(SimulationTime::ZERO);
+        let t = t.as_nanos_f64() / 1e9;
+        let target = self.origin_point + C * (t - self.k).powi(3);
+
+        // the number of acknowledged packets needed to increase the window by one packet
+        let cwnd = f64::from(self.cwnd);
+        let mut count = if target > cwnd {
+            cwnd / (target - cwnd)
+        } else {
+            100.0 * cwnd
+        };
+
+        if self.last_max_cwnd == 0 {
+            count = count.min(20.0);
+        }
+
+        self.reno_cwnd += ALPHA * f64::from(acked) / cwnd;
+        if self.reno_cwnd > cwnd {
+            count = count.min(cwnd / (self.reno_cwnd - cwnd));
+        }
+
+        // never increase by more than 50% per round-trip
+        let count = std::cmp::max(count as u32, 2);
+
+        // as in linux's `tcp_cong_avoid_ai()`
+        if self.cwnd_count >= count {
+            self.cwnd_count = 0;
+            self.cwnd += 1;
+        }
+        self.cwnd_count += acked;
+        if self.cwnd_count >= count {
+            let delta = self.cwnd_count / count;
+            self.cwnd_count -= delta * count;
+            self.cwnd += delta;
+        }
+    }
+}
+
+impl CongestionControl for Cubic {
+    const NAME: &'static CStr = c"cubic";
+
+    fn new(cwnd: u32) -> Self {
+        Self {
+            cwnd,
+            ssthresh: INFINITE_SSTHRESH,
+            cwnd_count: 0,
+            duplicate_acks: 0,
+            in_fast_recovery: false,
+            last_max_cwnd: 0,
+            epoch_start: None,
+            origin_point: 0.0,
+            k: 0.0,
+            reno_cwnd: 0.0,
+            min_rtt: None,
+        }
+    }
+
+    fn cwnd(&self) -> u32 {
+        self.cwnd
+    }
+
+    fn ssthresh(&self) -> u32 {
+        self.ssthresh
+    }
+
+    fn in_fast_recovery(&self) -> bool {
+        self.in_fast_recovery
+    }
+
+    fn new_ack(&mut self, mut acked: u32, state: &ConnectionState) {
+        self.duplicate_acks = 0;
+
+        if let Some(rtt) = state.rtt {
+            self.min_rtt = Some(self.min_rtt.map_or(rtt, |x| std::cmp::min(x, rtt)));
+        }
+
+        if self.in_fast_recovery {
+            self.in_fast_recovery = false;
+            self.cwnd = self.ssthresh;
+        }
+
+        if self.cwnd < self.ssthresh {
+            // slow start, and continue with congestion avoidance for any leftover packets
+            let cwnd = std::cmp::min(self.cwnd.saturating_add(acked), self.ssthresh);
+            acked -= cwnd - self.cwnd;
+            self.cwnd = cwnd;
+
+            if acked == 0 {
+                return;
+            }
+        }
+
+        self.congestion_avoidance(acked, state.now);
+    }
+
+    fn duplicate_ack(&mut self, _state: &ConnectionState) {
+        self.duplicate_acks += 1;
+
+        if self.in_fast_recovery {
+            self.cwnd += 1;
+        } else if self.duplicate_acks == 3 {
+            log::trace!("[CONG] three duplicate acks, entering fast recovery");
+            self.reduce();
+            self.cwnd = self.ssthresh + 3;
+            self.in_fast_recovery = true;
+        }
+    }
+
+    fn timeout(&mut self, _state: &ConnectionState) {
+        self.reduce();
+        // linux resets the rest of the state when entering the loss state
+        self.last_max_cwnd = 0;
+        self.cwnd = 1;
+        self.cwnd_count = 0;
+        self.duplicate_acks = 0;
+        self.in_fast_recovery = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    const RTT: SimulationTime = SimulationTime::from_duration(Duration::from_millis(100));
+
+    /// Acknowledge a full window one packet at a time (spread evenly over a round-trip) for
+    /// `rounds` round-trips, and return the window at the end of each round-trip.
+    fn run_rounds(cubic: &mut Cubic, start: &mut EmulatedTime, rounds: usize) -> Vec<u32> {
+        (0..rounds)
+            .map(|_| {
+                let cwnd = cubic.cwnd();
+                for i in 0..cwnd {
+                    let state = ConnectionState {
+                        now: *start + RTT * (i + 1) / cwnd,
+                        rtt: Some(RTT),
+                        packets_in_flight: cwnd,
+                    };
+                    cubic.new_ack(1, &state);
+                }
+                *start += RTT;
+                cubic.cwnd()
+            })
+            .collect()
+    }
+
+    fn enter_fast_recovery(cubic: &mut Cubic, now: EmulatedTime) {
+        let state = ConnectionState {
+            now,
+            rtt: Some(RTT),
+            packets_in_flight: cubic.cwnd(),
+        };
+        for _ in 0..3 {
+            cubic.duplicate_ack(&state);
+        }
+    }
+
+    #[test]
+    fn test_slow_start() {
+        let mut cubic = Cubic::new(10);
+        let mut now = EmulatedTime::SIMULATION_START;
+        assert_eq!(run_rounds(&mut cubic, &mut now, 5), [20, 40, 80, 160, 320]);
+    }
+
+    #[test]
+    fn test_cubic_function_formula() {
+        // the window in congestion avoidance after a reduction from 100 packets, evaluated by hand
+        // from the cubic function `W(t) = C * (t - K)^3 + W_max` of RFC 9438 at the end of each
+        // 100 ms round-trip, where `K = cbrt(W_max * (1 - beta) / C)`; this checks the
+        // implementation against the formula, not against traces of linux
+        const EXPECTED: [u32; 80] = [
+            72, 74, 76, 78, 79, 81, 83, 84, 85, 87, 88, 89, 90, 91, 92, 93, 94, 94, 95, 96, 96, 97,
+            97, 98, 98, 98, 99, 99, 99, 99, 99, 100, 100, 100, 100, 100, 100, 100, 100, 100, 100,
+            100, 100, 100, 100, 100, 100, 100, 100, 100, 100, 100, 101, 101, 101, 101, 101, 102,
+            102, 102, 103, 103, 104, 104, 105, 105, 106, 107, 108, 109, 110, 111, 112, 113, 114,
+            115, 117, 118, 120, 122,
+        ];
+
+        let mut cubic = Cubic::new(100);
+        let mut now = EmulatedTime::SIMULATION_START;
+
+        enter_fast_recovery(&mut cubic, now);
+        assert!(cubic.in_fast_recovery());
+        assert_eq!(cubic.ssthresh(), 70);
+        assert_eq!(cubic.cwnd(), 73);
+
+        let state = ConnectionState {
+            now,
+            rtt: Some(RTT),
+            packets_in_flight: 70,
+        };
+        cubic.new_ack(1, &state);
+        assert!(!cubic.in_fast_recovery());
+        assert_eq!(cubic.cwnd(), 70);
+
+        let trace = run_rounds(&mut cubic, &mut now, EXPECTED.len());
+        for (round, (cwnd, expected)) in trace.iter().zip(EXPECTED).enumerate() {
+            assert!(
+                cwnd.abs_diff(expected) <= 2,
+                "round {round}: cwnd {cwnd} doesn't match the cubic function {expected}\n{trace:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_fast_convergence() {
+        let mut cubic = Cubic::new(100);
+        let mut now = EmulatedTime::SIMULATION_START;
+
+        enter_fast_recovery(&mut cubic, now);
+        let state = ConnectionState {
+            now,
+            rtt: Some(RTT),
+            packets_in_flight: 70,
+        };
+        cubic.new_ack(1, &state);
+        run_rounds(&mut cubic, &mut now, 10);
+        let cwnd = cubic.cwnd();
+        assert!(cwnd < 100);
+
+        // a reduction before reaching the previous maximum lowers the plateau below the window
+        enter_fast_recovery(&mut cubic, now);
+        assert_eq!(cubic.last_max_cwnd, (f64::from(cwnd) * 0.85) as u32);
+        assert_eq!(cubic.ssthresh(), (f64::from(cwnd) * 0.7) as u32);
+    }
+
+    #[test]
+    fn test_timeout() {
+        let mut cubic = Cubic::new(100);
+        let mut now = EmulatedTime::SIMULATION_START;
+
+        cubic.timeout(&ConnectionState {
+            now,
+            rtt: None,
+            packets_in_flight: 100,
+        });
+        assert_eq!(cubic.cwnd(), 1);
+        assert_eq!(cubic.ssthresh(), 70);
+
+        // slow start until the threshold, then congestion avoidance
+        assert_eq!(
+            run_rounds(&mut cubic, &mut now, 8),
+            [2, 4, 8, 16, 32, 64, 72, 76]
+        );
+    }
+}
@@ -0,0 +1,270 @@
+//! Congestion control algorithms for the legacy TCP stack.
+//!
+//! The legacy TCP stack calls its congestion control algorithm through the hook table in
+//! `tcp_cong.h`. Reno is implemented in C (`tcp_cong_reno.c`), and the algorithms in this module
+//! are plugged into the same hook table.
+
+use std::ffi::CStr;
+
+use shadow_shim_helper_rs::emulated_time::EmulatedTime;
+use shadow_shim_helper_rs::simulation_time::SimulationTime;
+
+use crate::core::configuration::TcpCongestionControl;
+use crate::core::worker::Worker;
+use crate::cshadow as c;
+
+pub mod bbr;
+pub mod cubic;
+
+/// The initial congestion window (in packets) of the algorithms in this module, as in linux's
+/// `TCP_INIT_CWND`.
+pub const INITIAL_CWND: u32 = 10;
+
+/// The slow start threshold before there has been any congestion, as in linux's
+/// `TCP_INFINITE_SSTHRESH`.
+pub const INFINITE_SSTHRESH: u32 = i32::MAX as u32;
+
+/// The state of a TCP connection when a congestion event occurs.
+#[derive(Debug, Clone, Copy)]
+pub struct ConnectionState {
+    pub now: EmulatedTime,
+    /// The most recent round-trip time sample, if any.
+    pub rtt: Option<SimulationTime>,
+    /// The number of packets that have been sent but not yet acknowledged.
+    pub packets_in_flight: u32,
+}
+
+/// A congestion control algorithm. The congestion window and slow start threshold are measured
+/// in packets.
+pub trait CongestionControl {
+    /// The name that linux gives for this algorithm.
+    const NAME: &'static CStr;
+
+    /// A new instance with congestion window `cwnd`.
+    fn new(cwnd: u32) -> Self;
+
+    fn cwnd(&self) -> u32;
+
+    fn ssthresh(&self) -> u32;
+
+    fn in_fast_recovery(&self) -> bool;
+
+    /// Called when an acknowledgement for `acked` new packets is received.
+    fn new_ack(&mut self, acked: u32, state: &ConnectionState);
+
+    /// Called when a duplicate acknowledgement is received.
+    fn duplicate_ack(&mut self, state: &ConnectionState);
+
+    /// Called when the retransmission timer expires.
+    fn timeout(&mut self, state: &ConnectionState);
+}
+
+static CUBIC_HOOKS: c::TCPCongHooks = hooks::<cubic::Cubic>();
+static BBR_HOOKS: c::TCPCongHooks = hooks::<bbr::Bbr>();
+
+/// Initialize the congestion control of a new socket.
+///
+/// # Safety
+///
+/// `tcp` must be a valid pointer to a TCP socket whose congestion control hasn't been initialized.
+pub unsafe fn init(tcp: *mut c::TCP, algorithm: TcpCongestionControl) {
+    unsafe { init_with_cwnd(tcp, algorithm, None) }
+}
+
+/// Get the congestion control algorithm of a socket.
+///
+/// # Safety
+///
+/// `tcp` must be a valid pointer to a TCP socket.
+pub unsafe fn algorithm(tcp: *mut c::TCP) -> TcpCongestionControl {
+    let cong = unsafe { c::tcp_cong(tcp).as_ref() }.unwrap();
+
+    if std::ptr::eq(cong.hooks, &CUBIC_HOOKS) {
+        TcpCongestionControl::Cubic
+    } else if std::ptr::eq(cong.hooks, &BBR_HOOKS) {
+        TcpCongestionControl::Bbr
+    } else {
+        TcpCongestionControl::Reno
+    }
+}
+
+/// Change the congestion control algorithm of a socket. Like linux, the congestion window is kept
+/// but the rest of the congestion control state is reset.
+///
+/// # Safety
+///
+/// `tcp` must be a valid pointer to a TCP socket.
+pub unsafe fn set_algorithm(tcp: *mut c::TCP, algorithm: TcpCongestionControl) {
+    if unsafe { self::algorithm(tcp) } == algorithm {
+        return;
+    }
+
+    let cong = unsafe { c::tcp_cong(tcp).as_ref() }.unwrap();
+    let cwnd = cong.cwnd;
+    let delete = unsafe { cong.hooks.as_ref() }
+        .unwrap()
+        .tcp_cong_delete
+        .unwrap();
+    unsafe { delete(tcp) };
+
+    unsafe { init_with_cwnd(tcp, algorithm, Some(cwnd)) };
+}
+
+/// Get the algorithm with the name that linux gives it (for example from `TCP_CONGESTION`).
+pub fn algorithm_from_name(name: &[u8]) -> Option<TcpCongestionControl> {
+    let reno = unsafe { CStr::from_ptr(c::TCP_CONG_RENO_NAME) };
+
+    [
+        (reno, TcpCongestionControl::Reno),
+        (cubic::Cubic::NAME, TcpCongestionControl::Cubic),
+        (bbr::Bbr::NAME, TcpCongestionControl::Bbr),
+    ]
+    .into_iter()
+    .find(|(x, _)| x.to_bytes() == name)
+    .map(|(_, algorithm)| algorithm)
+}
+
+unsafe fn init_with_cwnd(tcp: *mut c::TCP, algorithm: TcpCongestionControl, cwnd: Option<u32>) {
+    match algorithm {
+        TcpCongestionControl::Reno => {
+            unsafe { c::tcp_cong_reno_init(tcp) };
+            if let Some(cwnd) = cwnd {
+                unsafe { c::tcp_cong(tcp).as_mut() }.unwrap().cwnd = cwnd;
+            }
+        }
+        TcpCongestionControl::Cubic => unsafe {
+            init_algorithm::<cubic::Cubic>(tcp, &CUBIC_HOOKS, cwnd)
+        },
+        TcpCongestionControl::Bbr => unsafe { init_algorithm::<bbr::Bbr>(tcp, &BBR_HOOKS, cwnd) },
+    }
+}
+
+unsafe fn init_algorithm<T: CongestionControl>(
+    tcp: *mut c::TCP,
+    hooks: &'static c::TCPCongHooks,
+    cwnd: Option<u32>,
+) {
+    let ca = T::new(cwnd.unwrap_or(INITIAL_CWND));
+
+    let cong = unsafe { c::tcp_cong(tcp).as_mut() }.unwrap();
+    cong.cwnd = ca.cwnd();
+    cong.hooks = hooks;
+    cong.ca = Box::into_raw(Box::new(ca)).cast();
+}
+
+/// The hook table for algorithm `T`.
+const fn hooks<T: CongestionControl>() -> c::TCPCongHooks {
+    c::TCPCongHooks {
+        tcp_cong_delete: Some(hook_delete::<T>),
+        tcp_cong_duplicate_ack_ev: Some(hook_duplicate_ack::<T>),
+        tcp_cong_fast_recovery: Some(hook_fast_recovery::<T>),
+        tcp_cong_new_ack_ev: Some(hook_new_ack::<T>),
+        tcp_cong_timeout_ev: Some(hook_timeout::<T>),
+        tcp_cong_ssthresh: Some(hook_ssthresh::<T>),
+        tcp_cong_name_str: Some(hook_name::<T>),
+    }
+}
+
+/// Get the algorithm state of a socket.
+///
+/// # Safety
+///
+/// `tcp` must be a valid pointer to a TCP socket with algorithm `T`, and the returned reference
+/// must not outlive the socket's congestion control state.
+unsafe fn algorithm_state<'a, T: CongestionControl>(tcp: *mut c::TCP) -> &'a mut T {
+    let cong = unsafe { c::tcp_cong(tcp).as_ref() }.unwrap();
+    unsafe { cong.ca.cast::<T>().as_mut() }.unwrap()
+}
+
+/// Run `f` on the algorithm state of a socket, and update the socket's congestion window
+/// afterwards.
+///
+/// # Safety
+///
+/// `tcp` must be a valid pointer to a TCP socket with algorithm `T`.
+unsafe fn update<T: CongestionControl>(tcp: *mut c::TCP, f: impl FnOnce(&mut T, &ConnectionState)) {
+    let rtt = unsafe { c::tcp_getRTTSample(tcp) };
+    let state = ConnectionState {
+        now: Worker::current_time().unwrap(),
+        rtt: SimulationTime::from_c_simtime(rtt).filter(|rtt| rtt.is_positive()),
+        packets_in_flight: unsafe { c::tcp_getPacketsInFlight(tcp) },
+    };
+
+    let ca = unsafe { algorithm_state::<T>(tcp) };
+    f(ca, &state);
+
+    let cwnd = ca.cwnd();
+    unsafe { c::tcp_cong(tcp).as_mut() }.unwrap().cwnd = cwnd;
+}
+
+unsafe extern "C-unwind" fn hook_delete<T: CongestionControl>(tcp: *mut c::TCP) {
+    let cong = unsafe { c::tcp_cong(tcp).as_mut() }.unwrap();
+    drop(unsafe { Box::from_raw(cong.ca.cast::<T>()) });
+    cong.ca = std::ptr::null_mut();
+}
+
+unsafe extern "C-unwind" fn hook_duplicate_ack<T: CongestionControl>(tcp: *mut c::TCP) {
+    unsafe { update::<T>(tcp, |ca, state| ca.duplicate_ack(state)) }
+}
+
+unsafe extern "C-unwind" fn hook_fast_recovery<T: CongestionControl>(tcp: *mut c::TCP) -> bool {
+    unsafe { algorithm_state::<T>(tcp) }.in_fast_recovery()
+}
+
+unsafe extern "C-unwind" fn hook_new_ack<T: CongestionControl>(tcp: *mut c::TCP, n: u32) {
+    unsafe { update::<T>(tcp, |ca, state| ca.new_ack(n, state)) }
+}
+
+unsafe extern "C-unwind" fn hook_timeout<T: CongestionControl>(tcp: *mut c::TCP) {
+    unsafe { update::<T>(tcp, |ca, state| ca.timeout(state)) }
+}
+
+unsafe extern "C-unwind" fn hook_ssthresh<T: CongestionControl>(tcp: *mut c::TCP) -> u32 {
+    unsafe { algorithm_state::<T>(tcp) }.ssthresh()
+}
+
+unsafe extern "C-unwind" fn hook_name<T: CongestionControl>() -> *const libc::c_char {
+    T::NAME.as_ptr()
+}
+
+mod export {
+    use super::*;
+
+    /// Initialize the congestion control of a new socket.
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C-unwind" fn tcpcong_init(
+        tcp: *mut c::TCP,
+        algorithm: TcpCongestionControl,
+    ) {
+        unsafe { init(tcp, algorithm) }
+    }
+
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C-unwind" fn tcpcong_getAlgorithm(tcp: *mut c::TCP) -> TcpCongestionControl {
+        unsafe { algorithm(tcp) }
+    }
+
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C-unwind" fn tcpcong_setAlgorithm(
+        tcp: *mut c::TCP,
+        algorithm: TcpCongestionControl,
+    ) {
+        unsafe { set_algorithm(tcp, algorithm) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_algorithm_from_name() {
+        assert_eq!(
+            algorithm_from_name(b"cubic"),
+            Some(TcpCongestionControl::Cubic)
+        );
+        assert_eq!(algorithm_from_name(b"bbr"), Some(TcpCongestionControl::Bbr));
+        assert_eq!(algorithm_from_name(b"vegas"), None);
+        assert_eq!(algorithm_from_name(b""), None);
+    }
+}
@@ -5,8 +5,10 @@ use atomic_refcell::AtomicRefCell;
 use linux_api::errno::Errno;
 use linux_api::ioctls::IoctlRequest;
 use linux_api::posix_types::kernel_off_t;
+use linux_api::time::ClockId;
 use shadow_shim_helper_rs::{
-    emulated_time::EmulatedTime, simulation_time::SimulationTime, syscall_types::ForeignPtr,
+    emulated_time::EmulatedTime, host_clock::ClockKind, simulation_time::SimulationTime,
+    syscall_types::ForeignPtr,
 };
 
 use crate::cshadow as c;
@@ -22,6 +24,7 @@ use crate::utility::callback_queue::CallbackQueue;
 
 pub struct TimerFd {
     timer: Timer,
+    clockid: ClockId,
     event_source: StateEventSource,
     status: FileStatus,
     state: FileState,
@@ -37,13 +40,14 @@ impl TimerFd {
     /// We wrap the new [`TimerFd`] in an [`Arc<AtomicRefCell>`] because we need to use a weak
     /// reference to internally support setting up callback functions that reference the [`TimerFd`]
     /// on timer expiration.
-    pub fn new(status: FileStatus) -> Arc<AtomicRefCell<Self>> {
+    pub fn new(clockid: ClockId, status: FileStatus) -> Arc<AtomicRefCell<Self>> {
         // We need a circular reference here, so that the inner Timer can refer back to the outer
         // TimerFd when executing a callback that will mutate the TimerFd when the timer expires.
         Arc::new_cyclic(|weak| {
             let weak_cloned = weak.clone();
             AtomicRefCell::new(Self {
                 timer: Timer::new(move |_host| Self::timer_expired(&weak_cloned)),
+                clockid,
                 event_source: StateEventSource::new(),
                 state: FileState::ACTIVE,
                 status,
@@ -84,17 +88,30 @@ impl TimerFd {
         self.timer.expire_interval()
     }
 
+    /// The clock that the timer was created with.
+    pub fn clockid(&self) -> ClockId {
+        self.clockid
+    }
+
     /// Arm the timer by setting its expiration time and interval, enabling support for
     /// timerfd_settime(2). The readable state of the [`TimerFd`] is updated as appropriate.
+    ///
+    /// `abstime` indicates that `expire_time` was derived from an absolute time on the timer's
+    /// clock, in which case the expiration follows any later steps of a realtime clock.
     pub fn arm_timer(
         &mut self,
         host: &Host,
         expire_time: EmulatedTime,
         interval: Option<SimulationTime>,
+        abstime: bool,
         cb_queue: &mut CallbackQueue,
     ) {
+        if abstime && ClockKind::from(self.clockid) == ClockKind::Realtime {
+            self.timer.arm_realtime_abs(host, expire_time, interval);
+        } else {
+            self.timer.arm(host, expire_time, interval);
+        }
         // Make sure to update our READABLE status.
-        self.timer.arm(host, expire_time, interval);
         self.refresh_state(cb_queue);
     }
 
@@ -1,20 +1,22 @@
 //! An emulated Linux system.
 
 use std::cell::{Cell, Ref, RefCell, RefMut, UnsafeCell};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::ffi::{CStr, CString, OsString};
-use std::net::{Ipv4Addr, SocketAddrV4};
+use std::fs::File;
+use std::io::BufWriter;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddrV4};
 use std::ops::{Deref, DerefMut};
 use std::os::unix::prelude::OsStringExt;
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, Weak};
 #[cfg(feature = "enable_perf_logging")]
 use std::sync::atomic::{AtomicU64, Ordering};
-#[cfg(feature = "enable_perf_logging")]
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use asm_util::tsc::Tsc;
 use atomic_refcell::AtomicRefCell;
+use linux_api::inotify::InotifyMask;
 use linux_api::signal::{Signal, siginfo_t};
 use log::{debug, trace};
 use logger::LogLevel;
@@ -24,6 +26,7 @@ use rand_xoshiro::Xoshiro256PlusPlus;
 use shadow_shim_helper_rs::HostId;
 use shadow_shim_helper_rs::emulated_time::EmulatedTime;
 use shadow_shim_helper_rs::explicit_drop::ExplicitDropper;
+use shadow_shim_helper_rs::host_clock::HostClock;
 use shadow_shim_helper_rs::rootedcell::Root;
 use shadow_shim_helper_rs::rootedcell::cell::RootedCell;
 use shadow_shim_helper_rs::rootedcell::rc::RootedRc;
@@ -42,24 +45,36 @@ static HOST_EXEC_ACC_NS: AtomicU64 = AtomicU64::new(0);
 #[cfg(feature = "enable_perf_logging")]
 const HOST_EXEC_LOG_EVERY: u64 = 1_000;
 
-use crate::core::configuration::{ProcessFinalState, QDiscMode};
-use crate::core::sim_config::PcapConfig;
+use crate::core::activity_trace::{Activity, ActivityEvent};
+use crate::core::configuration::{
+    MetricsFormat, ProcessFinalState, QDiscMode, TcpCongestionControl,
+};
+use crate::core::sim_config::{ClockConfig, PcapConfig, QDiscConfig};
+use crate::core::start_conditions::StartCondition;
 use crate::core::work::event::{Event, EventData};
 use crate::core::work::event_queue::EventQueue;
 use crate::core::work::task::TaskRef;
 use crate::core::worker::Worker;
 use crate::cshadow;
+use crate::host::descriptor::inotify::Inotify;
 use crate::host::descriptor::socket::abstract_unix_ns::AbstractUnixNamespace;
 use crate::host::descriptor::socket::inet::InetSocket;
 use crate::host::futex_table::FutexTable;
 use crate::host::network::interface::{FifoPacketPriority, NetworkInterface, PcapOptions};
+use crate::host::network::metrics::{MetricsWriter, open_metrics_writer};
 use crate::host::network::namespace::NetworkNamespace;
+use crate::host::network::qdisc::QDisc;
+use crate::host::ntp::NtpState;
 use crate::host::process::Process;
 use crate::host::thread::{Thread, ThreadId};
+use crate::host::timer::RealtimeTimers;
 use crate::network::PacketDevice;
+use crate::network::faults::BandwidthChange;
+use crate::network::packet::{Packet, PacketFlow, PacketStatus};
 use crate::network::relay::{RateLimit, Relay};
 use crate::network::router::Router;
 use crate::utility;
+use crate::utility::callback_queue::CallbackQueue;
 #[cfg(feature = "perf_timers")]
 use crate::utility::perf_timer::PerfTimer;
 
@@ -71,6 +86,8 @@ pub struct HostParameters {
     pub hostname: CString,
     pub node_id: u32,
     pub ip_addr: libc::in_addr_t,
+    /// IPv6 address of the host, which is an alias of `ip_addr`.
+    pub ipv6_addr: Ipv6Addr,
     pub sim_end_time: EmulatedTime,
     pub requested_bw_down_bits: u64,
     pub requested_bw_up_bits: u64,
@@ -79,26 +96,35 @@ pub struct HostParameters {
     pub cpu_precision: Option<SimulationTime>,
     pub log_level: LogLevel,
     pub pcap_config: Option<PcapConfig>,
+    /// How often to sample the network metrics, if at all.
+    pub metrics_interval: Option<SimulationTime>,
+    pub metrics_format: MetricsFormat,
+    /// Record the host's activity for the simulation's activity trace.
+    pub activity_trace: bool,
     pub qdisc: QDiscMode,
+    pub packet_qdisc: QDiscConfig,
     pub init_sock_recv_buf_size: u64,
     pub autotune_recv_buf: bool,
     pub init_sock_send_buf_size: u64,
     pub autotune_send_buf: bool,
+    pub tcp_congestion_control: TcpCongestionControl,
     pub native_tsc_frequency: u64,
     pub model_unblocked_syscall_latency: bool,
     pub max_unapplied_cpu_latency: SimulationTime,
     pub unblocked_syscall_latency: SimulationTime,
     pub unblocked_vdso_latency: SimulationTime,
-    pub strace_logging_options: Option<FmtOptions>,
+    pub strace_logging_options: Option<StraceOptions>,
     pub shim_log_level: LogLevel,
     pub use_new_tcp: bool,
     pub use_mem_mapper: bool,
     pub use_syscall_counters: bool,
+    pub clock: ClockConfig,
+    pub bandwidth_changes: Vec<BandwidthChange>,
 }
 
 use super::cpu::Cpu;
-use super::process::ProcessId;
-use super::syscall::formatter::FmtOptions;
+use super::process::{ExitStatus, ProcessId};
+use super::syscall::formatter::StraceOptions;
 
 /// Immutable information about the Host.
 #[derive(Debug, Clone)]
@@ -109,6 +135,14 @@ pub struct HostInfo {
     pub log_level: Option<log::LevelFilter>,
 }
 
+/// The state of a process from the host's configuration.
+#[derive(Debug, Copy, Clone)]
+enum ApplicationState {
+    NotStarted,
+    Running(ProcessId),
+    Exited(ExitStatus),
+}
+
 /// A simulated Host.
 pub struct Host {
     // Store immutable info in an Arc, that we can safely clone into the
@@ -141,6 +175,10 @@ pub struct Host {
     // Forwards packets from the localhost interface back to itself.
     relay_loopback: Arc<Relay>,
 
+    // The current bandwidth, which may differ from the requested bandwidth after network faults.
+    bw_down_bits: Cell<u64>,
+    bw_up_bits: Cell<u64>,
+
     // map address to futex objects
     futex_table: RefCell<FutexTable>,
 
@@ -153,6 +191,16 @@ pub struct Host {
 
     net_ns: NetworkNamespace,
 
+    // Writes samples of the network interfaces, if network metrics are enabled.
+    network_metrics: RefCell<Option<MetricsWriter<BufWriter<File>>>>,
+    // Whether the network metrics samples stopped because the host had no other events. They
+    // resume when the host runs its next event.
+    network_metrics_idle: Cell<bool>,
+
+    // The activity recorded during the current run of the host's events, if the activity trace
+    // is enabled.
+    activity: Option<RefCell<Vec<ActivityEvent>>>,
+
     // Store as a CString so that we can return a borrowed pointer to C code
     // instead of having to allocate a new string.
     //
@@ -172,9 +220,19 @@ pub struct Host {
     // track the order in which the application sent us application data
     packet_priority_counter: Cell<FifoPacketPriority>,
 
+    // the latest delivery time of the packets sent on each flow, used to keep jittered packets in
+    // order
+    flow_delivery_times: RefCell<HashMap<PacketFlow, EmulatedTime>>,
+
     // Owned pointers to processes.
     processes: RefCell<BTreeMap<ProcessId, RootedRc<RootedRefCell<Process>>>>,
 
+    // The processes from the host's configuration, in the order they were added.
+    applications: RefCell<Vec<ApplicationState>>,
+    // Tasks that spawn the processes from the host's configuration which are waiting for their
+    // start conditions, keyed by the process's index in `applications`.
+    waiting_applications: RefCell<BTreeMap<usize, TaskRef>>,
+
     tsc: Tsc,
     // Cached lock for shim_shmem. `[Host::shmem_lock]` uses unsafe code to give it
     // a 'static lifetime.
@@ -203,6 +261,16 @@ pub struct Host {
 
     in_notify_socket_has_packets: RootedCell<bool>,
 
+    // Timers with deadlines on the realtime clock, which move when that clock is stepped.
+    realtime_timers: RefCell<RealtimeTimers>,
+
+    // Kernel NTP state, for `adjtimex`.
+    ntp: RefCell<NtpState>,
+
+    // Inotify instances of the host's processes, in the order they were created.
+    inotify_instances: RefCell<Vec<Weak<AtomicRefCell<Inotify>>>>,
+    inotify_cookie_counter: Cell<u32>,
+
     /// Paths to be added to LD_PRELOAD of managed processes.
     preload_paths: Arc<Vec<PathBuf>>,
 }
@@ -249,6 +317,7 @@ impl Host {
             params.unblocked_vdso_latency,
             nix::unistd::getpid().as_raw(),
             params.native_tsc_frequency,
+            HostClock::new(params.clock.realtime_offset_nanos, params.clock.drift_ppb),
             params.shim_log_level,
             manager_shmem,
         );
@@ -273,9 +342,27 @@ impl Host {
         let pcap_options = params.pcap_config.as_ref().map(|x| PcapOptions {
             path: data_dir_path.clone(),
             capture_size_bytes: x.capture_size.try_into().unwrap(),
+            merged: x.merged,
+            filter: x.filter.clone(),
         });
 
-        let net_ns = NetworkNamespace::new(public_ip, pcap_options, params.qdisc);
+        let packet_qdisc = QDisc::new(
+            &params.packet_qdisc,
+            params.requested_bw_up_bits / 8,
+            random.borrow_mut().deref_mut(),
+        );
+
+        let net_ns = NetworkNamespace::new(public_ip, pcap_options, params.qdisc, packet_qdisc);
+
+        let network_metrics = params.metrics_interval.and_then(|_| {
+            match open_metrics_writer(&data_dir_path, params.metrics_format) {
+                Ok(writer) => Some(writer),
+                Err(e) => {
+                    log::warn!("Unable to set up the network metrics writer: {e}");
+                    None
+                }
+            }
+        });
 
         // Packets that are not for localhost or our public ip go to the router.
         // Use `Ipv4Addr::UNSPECIFIED` for the router to encode this for our
@@ -296,10 +383,14 @@ impl Host {
 
         let in_notify_socket_has_packets = RootedCell::new(&root, false);
 
+        let activity = params.activity_trace.then(RefCell::default);
+
         let res = Self {
             info: OnceCell::new(),
             root,
             event_queue: Arc::new(Mutex::new(EventQueue::new())),
+            bw_down_bits: Cell::new(params.requested_bw_down_bits),
+            bw_up_bits: Cell::new(params.requested_bw_up_bits),
             params,
             router: RefCell::new(router),
             relay_inet_out: Arc::new(relay_inet_out),
@@ -311,18 +402,28 @@ impl Host {
             shim_shmem_lock: RefCell::new(None),
             cpu,
             net_ns,
+            network_metrics: RefCell::new(network_metrics),
+            network_metrics_idle: Cell::new(false),
+            activity,
             data_dir_path,
             data_dir_path_cstring,
             thread_id_counter,
             event_id_counter,
             packet_id_counter,
             packet_priority_counter,
+            flow_delivery_times: RefCell::new(HashMap::new()),
             determinism_sequence_counter,
             tsc,
             processes: RefCell::new(BTreeMap::new()),
+            applications: RefCell::new(Vec::new()),
+            waiting_applications: RefCell::new(BTreeMap::new()),
             #[cfg(feature = "perf_timers")]
             execution_timer,
             in_notify_socket_has_packets,
+            realtime_timers: RefCell::new(RealtimeTimers::default()),
+            ntp: RefCell::new(NtpState::default()),
+            inotify_instances: RefCell::new(Vec::new()),
+            inotify_cookie_counter: Cell::new(0),
             preload_paths,
         };
 
@@ -384,9 +485,16 @@ impl Host {
         envv: Vec<CString>,
         pause_for_debugging: bool,
         expected_final_state: ProcessFinalState,
+        wait_for_start_conditions: bool,
     ) {
         debug_assert!(shutdown_time.is_none() || shutdown_time.unwrap() > start_time);
 
+        let index = {
+            let mut applications = self.applications.borrow_mut();
+            applications.push(ApplicationState::NotStarted);
+            applications.len() - 1
+        };
+
         // Schedule spawning the process.
         let task = TaskRef::new(move |host| {
             // We can't move out of these captured variables, since TaskRef takes
@@ -411,6 +519,7 @@ impl Host {
                 (process.id(), process.thread_group_leader_id())
             };
             host.processes.borrow_mut().insert(process_id, process);
+            host.applications.borrow_mut()[index] = ApplicationState::Running(process_id);
 
             if let Some(shutdown_time) = shutdown_time {
                 let task = TaskRef::new(move |host| {
@@ -428,15 +537,62 @@ impl Host {
                     );
                     process.signal(host, None, &siginfo);
                 });
-                host.schedule_task_at_emulated_time(
-                    task,
+                // a process that waited for its start conditions may have been started after its
+                // shutdown time
+                let shutdown_time = std::cmp::max(
                     EmulatedTime::SIMULATION_START + shutdown_time,
+                    Worker::current_time().unwrap(),
                 );
+                host.schedule_task_at_emulated_time(task, shutdown_time);
             }
 
             host.resume(process_id, thread_id);
         });
-        self.schedule_task_at_emulated_time(task, EmulatedTime::SIMULATION_START + start_time);
+
+        if wait_for_start_conditions {
+            // the start time is handled when the conditions are met
+            self.waiting_applications.borrow_mut().insert(index, task);
+        } else {
+            self.schedule_task_at_emulated_time(task, EmulatedTime::SIMULATION_START + start_time);
+        }
+    }
+
+    /// Schedule spawning the processes from the host's configuration whose start conditions were
+    /// met in an earlier round.
+    pub fn start_ready_applications(&self) {
+        for (index, time) in Worker::take_ready_processes(self.id()) {
+            let task = self
+                .waiting_applications
+                .borrow_mut()
+                .remove(&index)
+                .unwrap();
+            self.schedule_task_at_emulated_time(task, time);
+        }
+    }
+
+    /// Check the process start conditions that are about this host. Should be called after the
+    /// host has run the events of the round.
+    pub fn check_start_conditions(&self) {
+        Worker::check_start_conditions(self.id(), |condition| match condition {
+            StartCondition::ProcessExited(index) => matches!(
+                self.applications.borrow()[*index],
+                ApplicationState::Exited(ExitStatus::Normal(0))
+            ),
+            StartCondition::FileExists(path) => self.data_dir_path.join(path).exists(),
+            StartCondition::TcpListening(port) => {
+                self.net_ns.internet.borrow().is_tcp_listening(*port)
+                    || self.net_ns.localhost.borrow().is_tcp_listening(*port)
+            }
+        });
+    }
+
+    /// Record the exit status of process `pid` if it's a process from the host's configuration.
+    pub fn record_process_exit(&self, pid: ProcessId, exit_status: ExitStatus) {
+        for application in self.applications.borrow_mut().iter_mut() {
+            if matches!(application, ApplicationState::Running(x) if *x == pid) {
+                *application = ApplicationState::Exited(exit_status);
+            }
+        }
     }
 
     pub fn add_and_schedule_forked_process(
@@ -593,6 +749,11 @@ impl Host {
         self.net_ns.default_ip
     }
 
+    /// The host's IPv6 address, which is an alias of [`Self::default_ip`].
+    pub fn default_ipv6(&self) -> Ipv6Addr {
+        self.params.ipv6_addr
+    }
+
     pub fn abstract_unix_namespace(
         &self,
     ) -> impl Deref<Target = Arc<AtomicRefCell<AbstractUnixNamespace>>> + '_ {
@@ -626,12 +787,12 @@ impl Host {
 
     #[allow(non_snake_case)]
     pub fn bw_up_kiBps(&self) -> u64 {
-        self.params.requested_bw_up_bits / (8 * 1024)
+        self.bw_up_bits.get() / (8 * 1024)
     }
 
     #[allow(non_snake_case)]
     pub fn bw_down_kiBps(&self) -> u64 {
-        self.params.requested_bw_down_bits / (8 * 1024)
+        self.bw_down_bits.get() / (8 * 1024)
     }
 
     /// Returns `None` if there is no such interface.
@@ -690,6 +851,22 @@ impl Host {
         res
     }
 
+    /// Get the time to deliver a packet that was sent on `flow` and would be delivered at
+    /// `deliver_time`, so that it isn't delivered before an earlier packet on the same flow.
+    pub fn flow_delivery_time(&self, flow: PacketFlow, deliver_time: EmulatedTime) -> EmulatedTime {
+        let mut flow_delivery_times = self.flow_delivery_times.borrow_mut();
+
+        // flows whose packets have all been delivered no longer constrain new packets
+        if flow_delivery_times.len() >= 1024 {
+            let now = Worker::current_time().unwrap();
+            flow_delivery_times.retain(|_, time| *time > now);
+        }
+
+        let time = flow_delivery_times.entry(flow).or_insert(deliver_time);
+        *time = std::cmp::max(*time, deliver_time);
+        *time
+    }
+
     pub fn continue_execution_timer(&self) {
         #[cfg(feature = "perf_timers")]
         self.execution_timer.borrow_mut().start();
@@ -700,6 +877,201 @@ impl Host {
         self.execution_timer.borrow_mut().stop();
     }
 
+    /// Schedule the configured step changes of the host's realtime clock.
+    pub fn schedule_clock_steps(&self) {
+        for step in &self.params.clock.steps {
+            let offset_nanos = step.offset_nanos;
+            let task = TaskRef::new(move |host| {
+                trace!("Stepping realtime clock by {offset_nanos} ns");
+                host.step_realtime_clock(offset_nanos);
+            });
+            self.schedule_task_at_emulated_time(task, EmulatedTime::SIMULATION_START + step.time);
+        }
+    }
+
+    /// Schedule the configured changes to the host's bandwidth.
+    pub fn schedule_bandwidth_changes(&self) {
+        for change in &self.params.bandwidth_changes {
+            self.schedule_bandwidth_change(*change);
+        }
+    }
+
+    /// Schedule a change to the host's bandwidth at `change.time`.
+    pub fn schedule_bandwidth_change(&self, change: BandwidthChange) {
+        let task = TaskRef::new(move |host| {
+            if let Some(bits) = change.down_bits {
+                trace!("Changing downstream bandwidth to {bits} bits/s");
+                host.bw_down_bits.set(bits);
+                host.relay_inet_in
+                    .set_rate_limit(RateLimit::BytesPerSecond(bits / 8));
+            }
+            if let Some(bits) = change.up_bits {
+                trace!("Changing upstream bandwidth to {bits} bits/s");
+                host.bw_up_bits.set(bits);
+                host.relay_inet_out
+                    .set_rate_limit(RateLimit::BytesPerSecond(bits / 8));
+            }
+        });
+        self.schedule_task_at_emulated_time(task, change.time);
+    }
+
+    /// Schedule the periodic samples of the network metrics, if they're enabled.
+    pub fn schedule_network_metrics(&self) {
+        let Some(interval) = self.params.metrics_interval else {
+            return;
+        };
+        self.schedule_task_at_emulated_time(
+            Self::network_metrics_task(interval),
+            EmulatedTime::SIMULATION_START + interval,
+        );
+    }
+
+    /// A task that writes a sample of each network interface and then schedules itself again
+    /// after `interval`. If the host has no other events, the task isn't scheduled again until
+    /// [`Self::resume_network_metrics`] runs, so that the samples alone don't keep the simulation
+    /// running until its stop time.
+    fn network_metrics_task(interval: SimulationTime) -> TaskRef {
+        TaskRef::new(move |host| {
+            host.write_network_metrics();
+            if host.event_queue.lock().unwrap().next_event_time().is_none() {
+                host.network_metrics_idle.set(true);
+                return;
+            }
+            host.schedule_task_with_delay(Self::network_metrics_task(interval), interval);
+        })
+    }
+
+    /// Schedule the network metrics samples again if they stopped while the host was idle. The
+    /// next sample is taken at the next multiple of the interval, as if they had never stopped.
+    fn resume_network_metrics(&self) {
+        if !self.network_metrics_idle.replace(false) {
+            return;
+        }
+        let interval = self.params.metrics_interval.unwrap();
+        let interval_ns = u64::try_from(interval.as_nanos()).unwrap();
+        let elapsed = Worker::current_time().unwrap() - EmulatedTime::SIMULATION_START;
+        let elapsed_ns = u64::try_from(elapsed.as_nanos()).unwrap();
+        let next_ns = (elapsed_ns / interval_ns + 1) * interval_ns;
+        self.schedule_task_at_emulated_time(
+            Self::network_metrics_task(interval),
+            EmulatedTime::SIMULATION_START + SimulationTime::from_nanos(next_ns),
+        );
+    }
+
+    fn write_network_metrics(&self) {
+        let mut writer = self.network_metrics.borrow_mut();
+        let Some(metrics) = writer.as_mut() else {
+            return;
+        };
+
+        let elapsed = Worker::current_time().unwrap() - EmulatedTime::SIMULATION_START;
+        let time_ns = u64::try_from(elapsed.as_nanos()).unwrap();
+
+        let mut lo = self.net_ns.localhost.borrow().metrics_sample(time_ns, "lo");
+        lo.relay_queue_packets = self.relay_loopback.queued_packets();
+
+        let mut eth0 = self
+            .net_ns
+            .internet
+            .borrow()
+            .metrics_sample(time_ns, "eth0");
+        eth0.relay_queue_packets = self.relay_inet_out.queued_packets();
+        eth0.router_queue_packets = self.router.borrow().queued_packets();
+
+        for sample in [lo, eth0] {
+            if let Err(e) = metrics.write(&sample) {
+                log::warn!("Unable to write the network metrics; stopping them: {e}");
+                writer.take();
+                return;
+            }
+        }
+    }
+
+    /// Record a packet that was dropped with `status` in the network metrics and capture of the
+    /// interface that it was sent or received on.
+    pub fn record_dropped_packet(&self, packet: &Packet, status: PacketStatus) {
+        let interface = if packet.dst_ipv4_address().ip().is_loopback() {
+            &self.net_ns.localhost
+        } else {
+            &self.net_ns.internet
+        };
+        interface.borrow().record_dropped_packet(packet, status);
+    }
+
+    /// Step the host's realtime clock by `delta_nanos`. The monotonic clocks are unaffected.
+    pub fn step_realtime_clock(&self, delta_nanos: i64) {
+        self.shim_shmem().clock.step_realtime_nanos(delta_nanos);
+        self.move_realtime_timers(delta_nanos);
+    }
+
+    /// Set the host's realtime clock so that it currently reads `t`. The monotonic clocks are
+    /// unaffected.
+    pub fn set_realtime_clock(&self, t: EmulatedTime) {
+        let now = Worker::current_time().unwrap();
+        let delta_nanos = self.shim_shmem().clock.set_realtime(now, t);
+        self.move_realtime_timers(delta_nanos);
+    }
+
+    /// Move the deadlines of any timers that were armed with an absolute realtime deadline, after
+    /// the realtime clock was stepped by `delta_nanos`.
+    fn move_realtime_timers(&self, delta_nanos: i64) {
+        self.realtime_timers
+            .borrow_mut()
+            .clock_stepped(self, delta_nanos);
+    }
+
+    pub fn realtime_timers_borrow_mut(&self) -> RefMut<'_, RealtimeTimers> {
+        self.realtime_timers.borrow_mut()
+    }
+
+    pub fn ntp_borrow_mut(&self) -> RefMut<'_, NtpState> {
+        self.ntp.borrow_mut()
+    }
+
+    /// Register an inotify instance to be notified of file system changes made by the host's
+    /// processes. The host only holds a weak reference to it.
+    pub fn register_inotify(&self, inotify: &Arc<AtomicRefCell<Inotify>>) {
+        self.inotify_instances
+            .borrow_mut()
+            .push(Arc::downgrade(inotify));
+    }
+
+    /// Notify the host's inotify instances that the event `mask` occurred for the file at the
+    /// absolute path `path`. Instances are notified in the order they were created.
+    pub fn notify_file_event(&self, path: &Path, mask: InotifyMask, cookie: u32) {
+        let instances: Vec<_> = {
+            let mut instances = self.inotify_instances.borrow_mut();
+            instances.retain(|x| x.strong_count() > 0);
+            instances.iter().filter_map(Weak::upgrade).collect()
+        };
+
+        if instances.is_empty() {
+            return;
+        }
+
+        CallbackQueue::queue_and_run_with_legacy(|cb_queue| {
+            for inotify in instances {
+                inotify.borrow_mut().notify(path, mask, cookie, cb_queue);
+            }
+        });
+    }
+
+    /// Notify the host's inotify instances that the file at `old_path` was renamed to `new_path`.
+    pub fn notify_file_moved(&self, old_path: &Path, new_path: &Path, is_dir: bool) {
+        // the cookie connects the two events of the rename, and is never 0
+        let cookie = self.inotify_cookie_counter.get().wrapping_add(1).max(1);
+        self.inotify_cookie_counter.set(cookie);
+
+        let is_dir = if is_dir {
+            InotifyMask::IN_ISDIR
+        } else {
+            InotifyMask::empty()
+        };
+
+        self.notify_file_event(old_path, InotifyMask::IN_MOVED_FROM | is_dir, cookie);
+        self.notify_file_event(new_path, InotifyMask::IN_MOVED_TO | is_dir, cookie);
+    }
+
     pub fn schedule_task_at_emulated_time(&self, task: TaskRef, t: EmulatedTime) -> bool {
         let event = Event::new_local(task, t, self);
         self.push_local_event(event)
@@ -727,6 +1099,19 @@ impl Host {
 
         debug!("shutting down host {}", self.name());
 
+        if let Some(stats) = self.net_ns.internet.borrow().qdisc_stats() {
+            Worker::add_qdisc_stats(self.name(), stats);
+        }
+
+        if let Some(metrics) = self.network_metrics.borrow_mut().as_mut()
+            && let Err(e) = metrics.flush()
+        {
+            log::warn!("Unable to flush the network metrics: {e}");
+        }
+
+        // activity from killing the processes
+        self.write_activity();
+
         // the network namespace object needs to be cleaned up before it's dropped
         self.net_ns.cleanup();
 
@@ -764,6 +1149,10 @@ impl Host {
         #[cfg(feature = "enable_perf_logging")]
         let real_start = Instant::now();
 
+        let activity_start = self.is_tracing_activity().then(Instant::now);
+        let mut first_event_time = None;
+        let mut num_events = 0;
+
         loop {
             let mut event = {
                 let mut event_queue = self.event_queue.lock().unwrap();
@@ -781,6 +1170,13 @@ impl Host {
                 if cpu_delay > SimulationTime::ZERO {
                     trace!("event blocked on CPU, rescheduled for {cpu_delay:?} from now");
 
+                    self.record_activity(ActivityEvent {
+                        activity: Activity::CpuDelay { delay: cpu_delay },
+                        start: Instant::now(),
+                        duration: Duration::ZERO,
+                        sim_time: event.time(),
+                    });
+
                     // reschedule the event after the CPU delay time
                     event.set_time(event.time() + cpu_delay);
                     self.push_local_event(event);
@@ -791,7 +1187,10 @@ impl Host {
             }
 
             // run the event
+            first_event_time.get_or_insert(event.time());
+            num_events += 1;
             Worker::set_current_time(event.time());
+            self.resume_network_metrics();
             self.continue_execution_timer();
             match event.data() {
                 EventData::Packet(data) => {
@@ -805,6 +1204,18 @@ impl Host {
             Worker::clear_current_time();
         }
 
+        if let Some(start) = activity_start {
+            if let Some(sim_time) = first_event_time {
+                self.record_activity(ActivityEvent {
+                    activity: Activity::Execute { until, num_events },
+                    start,
+                    duration: start.elapsed(),
+                    sim_time,
+                });
+            }
+            self.write_activity();
+        }
+
         #[cfg(feature = "enable_perf_logging")]
         {
             let real_elapsed = real_start.elapsed();
@@ -829,6 +1240,30 @@ impl Host {
         }
     }
 
+    /// Returns true if the host's activity is recorded for the simulation's activity trace.
+    pub fn is_tracing_activity(&self) -> bool {
+        self.activity.is_some()
+    }
+
+    /// Record an activity for the simulation's activity trace. Does nothing if the activity trace
+    /// isn't enabled.
+    pub fn record_activity(&self, event: ActivityEvent) {
+        if let Some(activity) = &self.activity {
+            activity.borrow_mut().push(event);
+        }
+    }
+
+    /// Write the activity that was recorded during the run of the host's events.
+    fn write_activity(&self) {
+        let Some(activity) = &self.activity else {
+            return;
+        };
+        let events = std::mem::take(&mut *activity.borrow_mut());
+        if !events.is_empty() {
+            Worker::write_activity(self.id(), &events);
+        }
+    }
+
     pub fn next_event_time(&self) -> Option<EmulatedTime> {
         self.event_queue.lock().unwrap().next_event_time()
     }
@@ -1010,6 +1445,8 @@ impl Drop for Host {
 }
 
 mod export {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
     use std::{os::raw::c_char, time::Duration};
 
     use libc::{in_addr_t, in_port_t};
@@ -1018,6 +1455,7 @@ mod export {
 
     use super::*;
     use crate::cshadow::{CEmulatedTime, CSimulationTime};
+    use crate::host::descriptor::socket::inet::InetFamily;
     use crate::network::packet::IanaProtocol;
 
     #[unsafe(no_mangle)]
@@ -1104,6 +1542,14 @@ mod export {
         hostrc.params.init_sock_send_buf_size
     }
 
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C-unwind" fn host_getTCPCongestionControl(
+        hostrc: *const Host,
+    ) -> TcpCongestionControl {
+        let hostrc = unsafe { hostrc.as_ref().unwrap() };
+        hostrc.params.tcp_congestion_control
+    }
+
     #[unsafe(no_mangle)]
     pub unsafe extern "C-unwind" fn host_getUpstreamRouter(hostrc: *const Host) -> *mut Router {
         let hostrc = unsafe { hostrc.as_ref().unwrap() };
@@ -1134,6 +1580,8 @@ mod export {
     pub unsafe extern "C-unwind" fn host_disassociateInterface(
         hostrc: *const Host,
         c_protocol: cshadow::ProtocolType,
+        ipv6: bool,
+        v6only: bool,
         bind_ip: in_addr_t,
         bind_port: in_port_t,
         peer_ip: in_addr_t,
@@ -1150,17 +1598,20 @@ mod export {
         let peer_addr = SocketAddrV4::new(peer_ip, peer_port);
 
         let protocol = IanaProtocol::from(c_protocol);
+        let versions = InetFamily::from_legacy(ipv6, v6only).ip_versions();
 
         // associate the interfaces corresponding to bind_addr with socket
         hostrc
             .net_ns
-            .disassociate_interface(protocol, bind_addr, peer_addr);
+            .disassociate_interface(protocol, versions, bind_addr, peer_addr);
     }
 
     #[unsafe(no_mangle)]
     pub unsafe extern "C-unwind" fn host_getRandomFreePort(
         hostrc: *const Host,
         c_protocol: cshadow::ProtocolType,
+        ipv6: bool,
+        v6only: bool,
         interface_ip: in_addr_t,
         peer_ip: in_addr_t,
         peer_port: in_port_t,
@@ -1174,11 +1625,13 @@ mod export {
         );
 
         let protocol = IanaProtocol::from(c_protocol);
+        let versions = InetFamily::from_legacy(ipv6, v6only).ip_versions();
 
         hostrc
             .net_ns
             .get_random_free_port(
                 protocol,
+                versions,
                 interface_ip,
                 peer_addr,
                 hostrc.random.borrow_mut().deref_mut(),
@@ -1369,4 +1822,37 @@ mod export {
         let host = unsafe { host.as_ref().unwrap() };
         host.resume(pid.try_into().unwrap(), tid.try_into().unwrap())
     }
+
+    /// Notify the host's inotify instances that the event `mask` (an `IN_*` event type,
+    /// optionally with `IN_ISDIR`) occurred for the file at the absolute path `path`.
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C-unwind" fn host_notifyFileEvent(
+        host: *const Host,
+        path: *const c_char,
+        mask: u32,
+    ) {
+        let host = unsafe { host.as_ref().unwrap() };
+        let path = unsafe { CStr::from_ptr(path) };
+        let path = Path::new(OsStr::from_bytes(path.to_bytes()));
+        host.notify_file_event(path, InotifyMask::from_bits_retain(mask), 0)
+    }
+
+    /// Notify the host's inotify instances that the file at the absolute path `old_path` was
+    /// renamed to `new_path`.
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C-unwind" fn host_notifyFileMoved(
+        host: *const Host,
+        old_path: *const c_char,
+        new_path: *const c_char,
+        is_dir: bool,
+    ) {
+        let host = unsafe { host.as_ref().unwrap() };
+        let old_path = unsafe { CStr::from_ptr(old_path) };
+        let new_path = unsafe { CStr::from_ptr(new_path) };
+        host.notify_file_moved(
+            Path::new(OsStr::from_bytes(old_path.to_bytes())),
+            Path::new(OsStr::from_bytes(new_path.to_bytes())),
+            is_dir,
+        )
+    }
 }
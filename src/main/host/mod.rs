@@ -13,6 +13,8 @@ pub mod host;
 pub mod managed_thread;
 pub mod memory_manager;
 pub mod network;
+pub mod ntp;
+pub mod posix_timer;
 pub mod process;
 pub mod status_listener;
 pub mod syscall;
@@ -1,17 +1,21 @@
 use std::cell::RefCell;
-use std::collections::HashMap;
 use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::BufWriter;
 use std::net::{Ipv4Addr, SocketAddrV4};
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use crate::core::configuration::QDiscMode;
 use crate::core::worker::Worker;
 use crate::host::descriptor::socket::inet::InetSocket;
+use crate::host::network::metrics::{InterfaceCounters, InterfaceSample};
+use crate::host::network::qdisc::{QDisc, QDiscStats};
 use crate::host::network::queuing::{NetworkQueue, NetworkQueueKind};
 use crate::network::PacketDevice;
-use crate::network::packet::{IanaProtocol, PacketRc, PacketStatus};
+use crate::network::capture::PacketFilter;
+use crate::network::packet::{IanaProtocol, Packet, PacketRc, PacketStatus};
 use crate::utility::ObjectCounter;
 use crate::utility::callback_queue::CallbackQueue;
 use crate::utility::pcap_writer::{PacketDisplay, PcapWriter};
@@ -23,19 +27,46 @@ pub type FifoPacketPriority = u64;
 pub struct PcapOptions {
     pub path: PathBuf,
     pub capture_size_bytes: u32,
+    /// Capture to the simulation's merged pcapng file instead of a pcap file for the interface.
+    pub merged: bool,
+    /// Only packets that match the filter are captured.
+    pub filter: Option<Arc<PacketFilter>>,
+}
+
+/// The IP versions whose packets a socket association receives. Each IP version has its own port
+/// namespace, so an IPv4 socket and an IPv6-only socket can be bound to the same port. A dual-stack
+/// IPv6 socket uses the port in both namespaces.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum IpVersions {
+    Ipv4,
+    Ipv6,
+    Both,
+}
+
+impl IpVersions {
+    /// For each IP version, whether it's IPv6.
+    fn is_ipv6(self) -> &'static [bool] {
+        match self {
+            Self::Ipv4 => &[false],
+            Self::Ipv6 => &[true],
+            Self::Both => &[false, true],
+        }
+    }
 }
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 struct AssociatedSocketKey {
     protocol: IanaProtocol,
+    ipv6: bool,
     local: SocketAddrV4,
     remote: SocketAddrV4,
 }
 
 impl AssociatedSocketKey {
-    fn new(protocol: IanaProtocol, local: SocketAddrV4, remote: SocketAddrV4) -> Self {
+    fn new(protocol: IanaProtocol, ipv6: bool, local: SocketAddrV4, remote: SocketAddrV4) -> Self {
         Self {
             protocol,
+            ipv6,
             local,
             remote,
         }
@@ -55,14 +86,23 @@ fn setup_pcap_writer(
 // in a `RefCell`. We should remove the `RefCell`s to simplify the code and fix any circular
 // code paths that exist.
 pub struct NetworkInterface {
+    name: String,
     addr: Ipv4Addr,
     /// The sockets from which we will pull out packets so that we can send them over the network.
     send_sockets: RefCell<NetworkQueue<InetSocket>>,
+    /// If configured, the packets pulled out of the sending sockets wait here until they're sent.
+    qdisc: RefCell<Option<QDisc>>,
     /// The sockets to which we will push incoming packets so they can be received by the network
     /// stack and their payloads read by the managed process.
     recv_sockets: RefCell<HashMap<AssociatedSocketKey, InetSocket>>,
     /// If configured, assists us in writing out pcap files of our packet flows.
     pcap: RefCell<Option<PcapWriter<BufWriter<File>>>>,
+    /// If true, packets are captured to the simulation's merged pcapng file instead of `pcap`.
+    pcap_merged: bool,
+    /// If configured, only packets that match are captured.
+    pcap_filter: Option<Arc<PacketFilter>>,
+    /// Counters of the packets sent, received, and dropped, for the network metrics.
+    counters: RefCell<InterfaceCounters>,
     /// Used to prevent recursion during cleanup.
     // TODO: remove when the legacy stack is removed.
     cleanup_in_progress: RefCell<bool>,
@@ -73,19 +113,26 @@ pub struct NetworkInterface {
 impl NetworkInterface {
     /// Create a new network interface for the assigned `addr`. The configured `name` will be used
     /// to construct a filesystem path for the pcap file (if enabled), so take care in choosing a
-    /// filesystem-appropriate static string.
+    /// filesystem-appropriate static string. The `qdisc` chooses the socket to send from next, and
+    /// the `packet_qdisc` (if any) queues the packets after they leave the sockets.
     pub fn new(
         name: &str,
         addr: Ipv4Addr,
         pcap_options: Option<PcapOptions>,
         qdisc: QDiscMode,
+        packet_qdisc: Option<QDisc>,
     ) -> Self {
+        let pcap_merged = pcap_options.as_ref().is_some_and(|x| x.merged);
+        let pcap_filter = pcap_options.as_ref().and_then(|x| x.filter.clone());
+
         // Try to set up the pcap writer if configured.
-        let pcap = pcap_options.and_then(|opt| match setup_pcap_writer(name, &opt) {
-            Ok(writer) => Some(writer),
-            Err(e) => {
-                log::warn!("Unable to set up the configured pcap writer for '{name}': {e}");
-                None
+        let pcap = pcap_options.filter(|x| !x.merged).and_then(|opt| {
+            match setup_pcap_writer(name, &opt) {
+                Ok(writer) => Some(writer),
+                Err(e) => {
+                    log::warn!("Unable to set up the configured pcap writer for '{name}': {e}");
+                    None
+                }
             }
         });
 
@@ -106,10 +153,15 @@ impl NetworkInterface {
         };
 
         Self {
+            name: name.to_string(),
             addr,
             send_sockets: RefCell::new(NetworkQueue::new(queue_kind)),
+            qdisc: RefCell::new(packet_qdisc),
             recv_sockets: RefCell::new(HashMap::new()),
             pcap: RefCell::new(pcap),
+            pcap_merged,
+            pcap_filter,
+            counters: RefCell::new(InterfaceCounters::default()),
             cleanup_in_progress: RefCell::new(false),
             _counter: ObjectCounter::new("NetworkInterface"),
         }
@@ -119,46 +171,84 @@ impl NetworkInterface {
         &self,
         socket: &InetSocket,
         protocol: IanaProtocol,
+        versions: IpVersions,
         port: u16,
         peer: SocketAddrV4,
     ) {
         let local = SocketAddrV4::new(self.addr, port);
-        let key = AssociatedSocketKey::new(protocol, local, peer);
-        log::trace!("Associating socket key {key:?}");
 
-        if let Entry::Vacant(entry) = self.recv_sockets.borrow_mut().entry(key) {
-            entry.insert(socket.clone());
-        } else {
-            // TODO: Return an error if the association fails.
-            debug_panic!("Entry is unexpectedly occupied");
+        for &ipv6 in versions.is_ipv6() {
+            let key = AssociatedSocketKey::new(protocol, ipv6, local, peer);
+            log::trace!("Associating socket key {key:?}");
+
+            if let Entry::Vacant(entry) = self.recv_sockets.borrow_mut().entry(key) {
+                entry.insert(socket.clone());
+            } else {
+                // TODO: Return an error if the association fails.
+                debug_panic!("Entry is unexpectedly occupied");
+            }
         }
     }
 
-    pub fn disassociate(&self, protocol: IanaProtocol, port: u16, peer: SocketAddrV4) {
+    pub fn disassociate(
+        &self,
+        protocol: IanaProtocol,
+        versions: IpVersions,
+        port: u16,
+        peer: SocketAddrV4,
+    ) {
         if *self.cleanup_in_progress.borrow() {
             return;
         }
 
         let local = SocketAddrV4::new(self.addr, port);
-        let key = AssociatedSocketKey::new(protocol, local, peer);
-        log::trace!("Disassociating socket key {key:?}");
-
-        // TODO: Return an error if the disassociation fails. Generally the calling code should only
-        // try to disassociate a socket if it thinks that the socket is actually associated with
-        // this interface, and if it's not, then it's probably an error. But TCP sockets will
-        // disassociate all sockets (including ones that have never been associated) and will try to
-        // disassociate the same socket multiple times, so we can't just add an assert here.
-        if self.recv_sockets.borrow_mut().remove(&key).is_none() {
-            // Since this always occurs with our legacy TCP stack and is not really a bug, we log at
-            // trace instead of warn level for now until the legacy TCP stack is removed.
-            log::trace!("Attempted to disassociate a vacant socket key");
+
+        for &ipv6 in versions.is_ipv6() {
+            let key = AssociatedSocketKey::new(protocol, ipv6, local, peer);
+            log::trace!("Disassociating socket key {key:?}");
+
+            // TODO: Return an error if the disassociation fails. Generally the calling code should
+            // only try to disassociate a socket if it thinks that the socket is actually associated
+            // with this interface, and if it's not, then it's probably an error. But TCP sockets
+            // will disassociate all sockets (including ones that have never been associated) and
+            // will try to disassociate the same socket multiple times, so we can't just add an
+            // assert here.
+            if self.recv_sockets.borrow_mut().remove(&key).is_none() {
+                // Since this always occurs with our legacy TCP stack and is not really a bug, we
+                // log at trace instead of warn level for now until the legacy TCP stack is removed.
+                log::trace!("Attempted to disassociate a vacant socket key");
+            }
         }
     }
 
-    pub fn is_addr_in_use(&self, protocol: IanaProtocol, port: u16, peer: SocketAddrV4) -> bool {
+    /// Returns true if a socket is associated with `port` and `peer` in the port namespace of any
+    /// of the IP versions.
+    pub fn is_addr_in_use(
+        &self,
+        protocol: IanaProtocol,
+        versions: IpVersions,
+        port: u16,
+        peer: SocketAddrV4,
+    ) -> bool {
+        let local = SocketAddrV4::new(self.addr, port);
+        let recv_sockets = self.recv_sockets.borrow();
+        versions.is_ipv6().iter().any(|&ipv6| {
+            let key = AssociatedSocketKey::new(protocol, ipv6, local, peer);
+            recv_sockets.contains_key(&key)
+        })
+    }
+
+    /// Returns true if a TCP socket of either IP version is listening on `port` of this interface.
+    pub fn is_tcp_listening(&self, port: u16) -> bool {
         let local = SocketAddrV4::new(self.addr, port);
-        let key = AssociatedSocketKey::new(protocol, local, peer);
-        self.recv_sockets.borrow().contains_key(&key)
+        let peer = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0);
+        let recv_sockets = self.recv_sockets.borrow();
+        [false, true].into_iter().any(|ipv6| {
+            let key = AssociatedSocketKey::new(IanaProtocol::Tcp, ipv6, local, peer);
+            recv_sockets
+                .get(&key)
+                .is_some_and(|x| x.borrow().is_listening())
+        })
     }
 
     // Add the socket to the list of sockets that have data ready for us to send out to the network.
@@ -186,10 +276,104 @@ impl NetworkInterface {
         *self.cleanup_in_progress.borrow_mut() = true;
         self.recv_sockets.borrow_mut().clear();
         self.send_sockets.borrow_mut().clear();
+        if let Some(qdisc) = self.qdisc.borrow_mut().as_mut() {
+            qdisc.clear();
+        }
         *self.cleanup_in_progress.borrow_mut() = false;
     }
 
-    fn capture_if_configured(&self, packet: &PacketRc) {
+    /// The qdisc counters, or `None` if the interface has no qdisc.
+    pub fn qdisc_stats(&self) -> Option<QDiscStats> {
+        self.qdisc.borrow().as_ref().map(|x| x.stats().clone())
+    }
+
+    /// Count a packet that was dropped with `status` on its way to or from this interface, and
+    /// capture it if the interface captures to the merged pcapng file.
+    pub fn record_dropped_packet(&self, packet: &Packet, status: PacketStatus) {
+        self.counters.borrow_mut().count_drop(status);
+        self.capture_if_configured(packet, status);
+    }
+
+    /// A sample of the interface's counters and queues for the network metrics. The relay and
+    /// router are owned by the host, so their queues are left for the caller to fill in.
+    pub fn metrics_sample(&self, time_ns: u64, interface: &'static str) -> InterfaceSample {
+        let (qdisc_backlog_packets, qdisc_backlog_bytes) =
+            self.qdisc.borrow().as_ref().map_or((0, 0), |x| x.backlog());
+
+        // dual-stack sockets have an association for each IP version, but are only counted once
+        let recv_sockets = self.recv_sockets.borrow();
+        let count_sockets = |protocol| {
+            recv_sockets
+                .iter()
+                .filter(|(key, _)| key.protocol == protocol)
+                .map(|(_, socket)| socket)
+                .collect::<HashSet<_>>()
+                .len()
+        };
+
+        InterfaceSample {
+            time_ns,
+            interface,
+            counters: self.counters.borrow().clone(),
+            send_queue_sockets: self.send_sockets.borrow().len(),
+            qdisc_backlog_packets,
+            qdisc_backlog_bytes,
+            relay_queue_packets: 0,
+            router_queue_packets: 0,
+            tcp_sockets: count_sockets(IanaProtocol::Tcp),
+            udp_sockets: count_sockets(IanaProtocol::Udp),
+        }
+    }
+
+    /// Pulls the next packet out of the sockets that have data to send.
+    fn pull_from_sockets(&self) -> Option<PacketRc> {
+        loop {
+            // Choose the next socket that will send a packet.
+            let Some(socket) = self.send_sockets.borrow_mut().pop() else {
+                log::trace!(
+                    "Interface {} is now idle with no sockets containing sendable packets.",
+                    self.addr
+                );
+                return None;
+            };
+
+            // The socket was in our sendable queue, so it _should_ have a packet.
+            let Some(packet) = CallbackQueue::queue_and_run_with_legacy(|cb_queue| {
+                socket.borrow_mut().pull_out_packet(cb_queue)
+            }) else {
+                // It is possible that the socket changed state since it was added to our queue, so
+                // we tolerate the case that it no longer has a sendable packet.
+                continue;
+            };
+
+            // If socket has more packets, keep tracking it for future sends. Note that it is
+            // possible that the socket was already re-added to the send queue above during the call
+            // to `pull_out_packet()`.
+            if socket.borrow().has_data_to_send() {
+                self.add_data_source(&socket);
+            }
+
+            return Some(packet);
+        }
+    }
+
+    fn capture_if_configured(&self, packet: &Packet, status: PacketStatus) {
+        if let Some(filter) = &self.pcap_filter
+            && !filter.matches(packet)
+        {
+            return;
+        }
+
+        if self.pcap_merged {
+            Worker::capture_packet(&self.name, packet, status);
+            return;
+        }
+
+        // The pcap format has nowhere to say that a packet was dropped.
+        if status.is_drop() {
+            return;
+        }
+
         // Avoid double mutable borrow of pcap.
         let mut pcap_borrowed = self.pcap.borrow_mut();
 
@@ -222,37 +406,35 @@ impl PacketDevice for NetworkInterface {
 
     // Pops a packet from the interface to send over the simulated network.
     fn pop(&self) -> Option<PacketRc> {
-        loop {
-            // Choose the next socket that will send a packet.
-            let Some(socket) = self.send_sockets.borrow_mut().pop() else {
-                log::trace!(
-                    "Interface {} is now idle with no sockets containing sendable packets.",
-                    self.addr
-                );
-                return None;
-            };
-
-            // The socket was in our sendable queue, so it _should_ have a packet.
-            let Some(packet) = CallbackQueue::queue_and_run_with_legacy(|cb_queue| {
-                socket.borrow_mut().pull_out_packet(cb_queue)
-            }) else {
-                // It is possible that the socket changed state since it was added to our queue, so
-                // we tolerate the case that it no longer has a sendable packet.
-                continue;
-            };
-
-            // If socket has more packets, keep tracking it for future sends. Note that it is
-            // possible that the socket was already re-added to the send queue above during the call
-            // to `pull_out_packet()`.
-            if socket.borrow().has_data_to_send() {
-                self.add_data_source(&socket);
+        let packet = if self.qdisc.borrow().is_some() {
+            let now = Worker::current_time().unwrap();
+
+            // Move everything the sockets have ready into the qdisc so that it can queue or drop
+            // the packets. Pulling from a socket runs the socket's callbacks, so we don't hold the
+            // qdisc borrow while doing so.
+            while let Some(packet) = self.pull_from_sockets() {
+                self.qdisc
+                    .borrow_mut()
+                    .as_mut()
+                    .unwrap()
+                    .enqueue(packet, now);
             }
 
-            packet.add_status(PacketStatus::SndInterfaceSent);
-            self.capture_if_configured(&packet);
+            self.qdisc.borrow_mut().as_mut().unwrap().dequeue(now)?
+        } else {
+            self.pull_from_sockets()?
+        };
 
-            return Some(packet);
+        packet.add_status(PacketStatus::SndInterfaceSent);
+        self.capture_if_configured(&packet, PacketStatus::SndInterfaceSent);
+
+        {
+            let mut counters = self.counters.borrow_mut();
+            counters.sent_packets += 1;
+            counters.sent_bytes += u64::try_from(packet.len()).unwrap();
         }
+
+        Some(packet)
     }
 
     // Pushes a packet from the simulated network into the interface.
@@ -260,28 +442,45 @@ impl PacketDevice for NetworkInterface {
         // The packet is successfully received by this interface.
         packet.add_status(PacketStatus::RcvInterfaceReceived);
 
+        {
+            let mut counters = self.counters.borrow_mut();
+            counters.received_packets += 1;
+            counters.received_bytes += u64::try_from(packet.len()).unwrap();
+        }
+
         // Record the packet before we process it, otherwise we may send more packets before we
         // record this one and the order will be incorrect.
-        self.capture_if_configured(&packet);
+        self.capture_if_configured(&packet, PacketStatus::RcvInterfaceReceived);
 
         // Find the socket that should process the packet.
         let protocol = packet.iana_protocol();
         let local = SocketAddrV4::new(self.addr, packet.dst_ipv4_address().port());
         let peer = packet.src_ipv4_address();
-        let key = AssociatedSocketKey::new(protocol, local, peer);
+        let wildcard = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0);
+
+        // Look in the port namespace of the packet's IP version first. Dual-stack sockets send
+        // IPv6 packets to IPv4 peers (see `InetSocketRefMut::pull_out_packet`), so IPv6 packets
+        // fall back to IPv4 sockets if there's no IPv6 socket to receive them.
+        let versions: &[bool] = if packet.is_ipv6() {
+            &[true, false]
+        } else {
+            &[false]
+        };
 
-        // First check for a socket with the specific association.
-        log::trace!("Looking for socket associated with specific key {key:?}");
         let maybe_socket = {
             let associated = self.recv_sockets.borrow();
-            associated
-                .get(&key)
-                .or_else(|| {
-                    // Then fall back to checking for the wildcard association.
-                    let wildcard = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0);
-                    let key = AssociatedSocketKey::new(protocol, local, wildcard);
-                    log::trace!("Looking for socket associated with general key {key:?}");
-                    associated.get(&key)
+            versions
+                .iter()
+                .find_map(|&ipv6| {
+                    // First check for a socket with the specific association.
+                    let key = AssociatedSocketKey::new(protocol, ipv6, local, peer);
+                    log::trace!("Looking for socket associated with specific key {key:?}");
+                    associated.get(&key).or_else(|| {
+                        // Then fall back to checking for the wildcard association.
+                        let key = AssociatedSocketKey::new(protocol, ipv6, local, wildcard);
+                        log::trace!("Looking for socket associated with general key {key:?}");
+                        associated.get(&key)
+                    })
                 })
                 // Pushing a packet to the socket may cause the socket to be disassociated, so we
                 // can't hold on to the borrow of `recv_sockets` when we call `push_in_packet`. We
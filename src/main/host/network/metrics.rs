@@ -0,0 +1,213 @@
+//! Periodic samples of the packet counters and queues of a host's network interfaces, which are
+//! written to a file in the host's data directory.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::core::configuration::MetricsFormat;
+use crate::network::packet::PacketStatus;
+
+/// Counters of the packets that passed through a network interface, or that were dropped on their
+/// way to or from it. Drops are counted by the status that the packet was dropped with.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct InterfaceCounters {
+    pub sent_packets: u64,
+    pub sent_bytes: u64,
+    pub received_packets: u64,
+    pub received_bytes: u64,
+    pub dropped_snd_interface: u64,
+    pub dropped_inet: u64,
+    pub dropped_router: u64,
+    pub dropped_rcv_interface: u64,
+    pub dropped_rcv_socket: u64,
+}
+
+impl InterfaceCounters {
+    /// Count a packet that was dropped with `status`. Statuses that aren't drops are ignored.
+    pub fn count_drop(&mut self, status: PacketStatus) {
+        let counter = match status {
+            PacketStatus::SndInterfaceDropped => &mut self.dropped_snd_interface,
+            PacketStatus::InetDropped => &mut self.dropped_inet,
+            PacketStatus::RouterDropped => &mut self.dropped_router,
+            PacketStatus::RcvInterfaceDropped => &mut self.dropped_rcv_interface,
+            PacketStatus::RcvSocketDropped => &mut self.dropped_rcv_socket,
+            _ => return,
+        };
+        *counter += 1;
+    }
+}
+
+/// A sample of a network interface. The counters are totals since the start of the simulation,
+/// and the other values are the state at the time of the sample.
+#[derive(Debug, Clone, Serialize)]
+pub struct InterfaceSample {
+    /// Nanoseconds since the start of the simulation.
+    pub time_ns: u64,
+    pub interface: &'static str,
+    #[serde(flatten)]
+    pub counters: InterfaceCounters,
+    /// Sockets that have packets waiting to be sent by the interface.
+    pub send_queue_sockets: usize,
+    pub qdisc_backlog_packets: usize,
+    pub qdisc_backlog_bytes: usize,
+    /// Packets held by the relay that forwards packets from the interface, which are waiting for
+    /// bandwidth.
+    pub relay_queue_packets: usize,
+    /// Packets from the network that are waiting in the host's router to be received by the
+    /// interface.
+    pub router_queue_packets: usize,
+    /// TCP sockets that are associated with the interface, including accepted connections.
+    pub tcp_sockets: usize,
+    /// UDP sockets that are associated with the interface.
+    pub udp_sockets: usize,
+}
+
+/// The columns of the csv format, which are the fields of [`InterfaceSample`].
+const CSV_COLUMNS: &[&str] = &[
+    "time_ns",
+    "interface",
+    "sent_packets",
+    "sent_bytes",
+    "received_packets",
+    "received_bytes",
+    "dropped_snd_interface",
+    "dropped_inet",
+    "dropped_router",
+    "dropped_rcv_interface",
+    "dropped_rcv_socket",
+    "send_queue_sockets",
+    "qdisc_backlog_packets",
+    "qdisc_backlog_bytes",
+    "relay_queue_packets",
+    "router_queue_packets",
+    "tcp_sockets",
+    "udp_sockets",
+];
+
+/// Create the `network-metrics` file in the data directory `dir`, with an extension for `format`.
+pub fn open_metrics_writer(
+    dir: &Path,
+    format: MetricsFormat,
+) -> std::io::Result<MetricsWriter<BufWriter<File>>> {
+    let extension = match format {
+        MetricsFormat::JsonLines => "jsonl",
+        MetricsFormat::Csv => "csv",
+    };
+    let file = File::create(dir.join(format!("network-metrics.{extension}")))?;
+    MetricsWriter::new(BufWriter::new(file), format)
+}
+
+/// Writes interface samples as json lines or csv.
+pub struct MetricsWriter<W: Write> {
+    writer: W,
+    format: MetricsFormat,
+}
+
+impl<W: Write> MetricsWriter<W> {
+    pub fn new(mut writer: W, format: MetricsFormat) -> std::io::Result<Self> {
+        if format == MetricsFormat::Csv {
+            writeln!(writer, "{}", CSV_COLUMNS.join(","))?;
+        }
+
+        Ok(Self { writer, format })
+    }
+
+    pub fn write(&mut self, sample: &InterfaceSample) -> std::io::Result<()> {
+        match self.format {
+            MetricsFormat::JsonLines => {
+                serde_json::to_writer(&mut self.writer, sample)?;
+                writeln!(self.writer)
+            }
+            MetricsFormat::Csv => {
+                let serde_json::Value::Object(fields) = serde_json::to_value(sample)? else {
+                    unreachable!();
+                };
+                let row: Vec<_> = CSV_COLUMNS
+                    .iter()
+                    .map(|x| match &fields[*x] {
+                        // the interface names don't need quoting
+                        serde_json::Value::String(s) => s.clone(),
+                        value => value.to_string(),
+                    })
+                    .collect();
+                writeln!(self.writer, "{}", row.join(","))
+            }
+        }
+    }
+
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> InterfaceSample {
+        let mut counters = InterfaceCounters {
+            sent_packets: 3,
+            sent_bytes: 1500,
+            ..Default::default()
+        };
+        counters.count_drop(PacketStatus::InetDropped);
+        counters.count_drop(PacketStatus::RcvSocketDropped);
+        counters.count_drop(PacketStatus::InetDropped);
+        counters.count_drop(PacketStatus::InetSent);
+
+        InterfaceSample {
+            time_ns: 1_000_000_000,
+            interface: "eth0",
+            counters,
+            send_queue_sockets: 1,
+            qdisc_backlog_packets: 2,
+            qdisc_backlog_bytes: 3000,
+            relay_queue_packets: 0,
+            router_queue_packets: 4,
+            tcp_sockets: 5,
+            udp_sockets: 6,
+        }
+    }
+
+    #[test]
+    fn test_json_lines() {
+        let mut writer = MetricsWriter::new(Vec::new(), MetricsFormat::JsonLines).unwrap();
+        writer.write(&sample()).unwrap();
+        writer.write(&sample()).unwrap();
+
+        let output = String::from_utf8(writer.writer).unwrap();
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+
+        let value: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(value["interface"], "eth0");
+        assert_eq!(value["dropped_inet"], 2);
+        assert_eq!(value["dropped_rcv_socket"], 1);
+        assert_eq!(value["router_queue_packets"], 4);
+
+        // the csv columns are the same as the json fields
+        let mut keys: Vec<_> = value.as_object().unwrap().keys().collect();
+        let mut columns = CSV_COLUMNS.to_vec();
+        keys.sort();
+        columns.sort();
+        assert_eq!(keys, columns);
+    }
+
+    #[test]
+    fn test_csv() {
+        let mut writer = MetricsWriter::new(Vec::new(), MetricsFormat::Csv).unwrap();
+        writer.write(&sample()).unwrap();
+
+        let output = String::from_utf8(writer.writer).unwrap();
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("time_ns,interface,sent_packets,"));
+        assert_eq!(
+            lines[1],
+            "1000000000,eth0,3,1500,0,0,0,2,0,0,1,1,2,3000,0,4,5,6"
+        );
+    }
+}
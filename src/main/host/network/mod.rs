@@ -1,3 +1,5 @@
 pub mod interface;
+pub mod metrics;
 pub mod namespace;
+pub mod qdisc;
 mod queuing;
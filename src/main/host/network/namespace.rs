@@ -9,7 +9,8 @@ use crate::core::configuration::QDiscMode;
 use crate::core::worker::Worker;
 use crate::host::descriptor::socket::abstract_unix_ns::AbstractUnixNamespace;
 use crate::host::descriptor::socket::inet::InetSocket;
-use crate::host::network::interface::{NetworkInterface, PcapOptions};
+use crate::host::network::interface::{IpVersions, NetworkInterface, PcapOptions};
+use crate::host::network::qdisc::QDisc;
 use crate::network::packet::IanaProtocol;
 
 // The start of our random port range in host order, used if application doesn't
@@ -36,10 +37,17 @@ pub struct NetworkNamespace {
 }
 
 impl NetworkNamespace {
-    pub fn new(public_ip: Ipv4Addr, pcap: Option<PcapOptions>, qdisc: QDiscMode) -> Self {
-        let localhost = NetworkInterface::new("lo", Ipv4Addr::LOCALHOST, pcap.clone(), qdisc);
-
-        let internet = NetworkInterface::new("eth0", public_ip, pcap, qdisc);
+    /// The `packet_qdisc` is only used for the internet interface. Like in Linux, the localhost
+    /// interface doesn't queue packets.
+    pub fn new(
+        public_ip: Ipv4Addr,
+        pcap: Option<PcapOptions>,
+        qdisc: QDiscMode,
+        packet_qdisc: Option<QDisc>,
+    ) -> Self {
+        let localhost = NetworkInterface::new("lo", Ipv4Addr::LOCALHOST, pcap.clone(), qdisc, None);
+
+        let internet = NetworkInterface::new("eth0", public_ip, pcap, qdisc, packet_qdisc);
 
         Self {
             unix: Arc::new(AtomicRefCell::new(AbstractUnixNamespace::new())),
@@ -110,6 +118,7 @@ impl NetworkNamespace {
     pub fn is_addr_in_use(
         &self,
         protocol_type: IanaProtocol,
+        versions: IpVersions,
         src: SocketAddrV4,
         dst: SocketAddrV4,
     ) -> Result<bool, NoInterface> {
@@ -117,14 +126,14 @@ impl NetworkNamespace {
             Ok(self
                 .localhost
                 .borrow()
-                .is_addr_in_use(protocol_type, src.port(), dst)
+                .is_addr_in_use(protocol_type, versions, src.port(), dst)
                 || self
                     .internet
                     .borrow()
-                    .is_addr_in_use(protocol_type, src.port(), dst))
+                    .is_addr_in_use(protocol_type, versions, src.port(), dst))
         } else {
             match self.interface_borrow(*src.ip()) {
-                Some(i) => Ok(i.is_addr_in_use(protocol_type, src.port(), dst)),
+                Some(i) => Ok(i.is_addr_in_use(protocol_type, versions, src.port(), dst)),
                 None => Err(NoInterface),
             }
         }
@@ -134,6 +143,7 @@ impl NetworkNamespace {
     pub fn get_random_free_port(
         &self,
         protocol_type: IanaProtocol,
+        versions: IpVersions,
         interface_ip: Ipv4Addr,
         peer: SocketAddrV4,
         mut rng: impl rand::Rng,
@@ -152,6 +162,7 @@ impl NetworkNamespace {
             let specific_in_use = self
                 .is_addr_in_use(
                     protocol_type,
+                    versions,
                     SocketAddrV4::new(interface_ip, random_port),
                     peer,
                 )
@@ -159,6 +170,7 @@ impl NetworkNamespace {
             let generic_in_use = self
                 .is_addr_in_use(
                     protocol_type,
+                    versions,
                     SocketAddrV4::new(interface_ip, random_port),
                     SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0),
                 )
@@ -174,11 +186,17 @@ impl NetworkNamespace {
         let start = rng.random_range(MIN_RANDOM_PORT..=u16::MAX);
         for port in (start..=u16::MAX).chain(MIN_RANDOM_PORT..start) {
             let specific_in_use = self
-                .is_addr_in_use(protocol_type, SocketAddrV4::new(interface_ip, port), peer)
+                .is_addr_in_use(
+                    protocol_type,
+                    versions,
+                    SocketAddrV4::new(interface_ip, port),
+                    peer,
+                )
                 .unwrap_or(true);
             let generic_in_use = self
                 .is_addr_in_use(
                     protocol_type,
+                    versions,
                     SocketAddrV4::new(interface_ip, port),
                     SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0),
                 )
@@ -202,26 +220,36 @@ impl NetworkNamespace {
         &self,
         socket: &InetSocket,
         protocol: IanaProtocol,
+        versions: IpVersions,
         bind_addr: SocketAddrV4,
         peer_addr: SocketAddrV4,
     ) -> AssociationHandle {
         if bind_addr.ip().is_unspecified() {
             // need to associate all interfaces
-            self.localhost
-                .borrow()
-                .associate(socket, protocol, bind_addr.port(), peer_addr);
-            self.internet
-                .borrow()
-                .associate(socket, protocol, bind_addr.port(), peer_addr);
+            self.localhost.borrow().associate(
+                socket,
+                protocol,
+                versions,
+                bind_addr.port(),
+                peer_addr,
+            );
+            self.internet.borrow().associate(
+                socket,
+                protocol,
+                versions,
+                bind_addr.port(),
+                peer_addr,
+            );
         } else {
             // TODO: return error if interface does not exist
             if let Some(iface) = self.interface_borrow(*bind_addr.ip()) {
-                iface.associate(socket, protocol, bind_addr.port(), peer_addr);
+                iface.associate(socket, protocol, versions, bind_addr.port(), peer_addr);
             }
         }
 
         AssociationHandle {
             protocol,
+            versions,
             local_addr: bind_addr,
             remote_addr: peer_addr,
         }
@@ -235,6 +263,7 @@ impl NetworkNamespace {
     pub fn disassociate_interface(
         &self,
         protocol: IanaProtocol,
+        versions: IpVersions,
         bind_addr: SocketAddrV4,
         peer_addr: SocketAddrV4,
     ) {
@@ -242,15 +271,15 @@ impl NetworkNamespace {
             // need to disassociate all interfaces
             self.localhost
                 .borrow()
-                .disassociate(protocol, bind_addr.port(), peer_addr);
+                .disassociate(protocol, versions, bind_addr.port(), peer_addr);
 
             self.internet
                 .borrow()
-                .disassociate(protocol, bind_addr.port(), peer_addr);
+                .disassociate(protocol, versions, bind_addr.port(), peer_addr);
         } else {
             // TODO: return error if interface does not exist
             if let Some(iface) = self.interface_borrow(*bind_addr.ip()) {
-                iface.disassociate(protocol, bind_addr.port(), peer_addr);
+                iface.disassociate(protocol, versions, bind_addr.port(), peer_addr);
             }
         }
     }
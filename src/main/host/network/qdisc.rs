@@ -0,0 +1,884 @@
+//! Queueing disciplines (qdiscs) for the packets that a host sends out of its network interface.
+//!
+//! Without a qdisc, the interface pulls a packet out of a socket only when the link is ready to
+//! send it, so packets wait in the socket buffers instead. With a qdisc, the interface moves the
+//! sockets' packets into the qdisc as soon as it can, and the qdisc decides which packets to
+//! queue, drop, and send. This models the transmit queue of a Linux interface, which is where
+//! bufferbloat happens.
+//!
+//! More info:
+//!  - <https://man7.org/linux/man-pages/man8/tc-pfifo.8.html>
+//!  - <https://man7.org/linux/man-pages/man8/tc-red.8.html>
+//!  - <https://tools.ietf.org/html/rfc8289> (CoDel)
+//!  - <https://tools.ietf.org/html/rfc8290> (FQ-CoDel)
+
+use std::collections::{BTreeMap, VecDeque};
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro256PlusPlus;
+use serde::Serialize;
+use shadow_shim_helper_rs::emulated_time::EmulatedTime;
+use shadow_shim_helper_rs::simulation_time::SimulationTime;
+
+use crate::core::sim_config::{CoDelConfig, QDiscConfig};
+use crate::cshadow as c;
+use crate::network::packet::{PacketRc, PacketStatus};
+
+/// The average packet size used to estimate how many packets could have been sent while the
+/// [`Red`] queue was idle (`avpkt` in tc-red(8)).
+const RED_AVPKT: u64 = 1000;
+
+/// Counters for the packets that passed through a qdisc.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct QDiscStats {
+    pub sent_packets: u64,
+    pub sent_bytes: u64,
+    /// Packets that were dropped because the qdisc was full.
+    pub limit_drops: u64,
+    /// Packets that were dropped early by RED or CoDel.
+    pub aqm_drops: u64,
+    pub max_backlog_packets: u64,
+    pub max_backlog_bytes: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum DropReason {
+    Limit,
+    Aqm,
+}
+
+fn drop_packet(packet: PacketRc, reason: DropReason, stats: &mut QDiscStats) {
+    match reason {
+        DropReason::Limit => stats.limit_drops += 1,
+        DropReason::Aqm => stats.aqm_drops += 1,
+    }
+    packet.add_status(PacketStatus::SndInterfaceDropped);
+}
+
+/// A qdisc at a network interface.
+pub struct QDisc {
+    discipline: Discipline,
+    stats: QDiscStats,
+}
+
+enum Discipline {
+    Pfifo(Pfifo),
+    Red(Red),
+    Codel(CoDelQueue),
+    FqCodel(FqCodel),
+}
+
+impl QDisc {
+    /// Returns `None` if the config is [`QDiscConfig::Noqueue`]. The `bandwidth` (in bytes per
+    /// second) is the rate of the interface's link. The `rng` is only used by qdiscs that make
+    /// random decisions.
+    pub fn new(config: &QDiscConfig, bandwidth: u64, rng: &mut impl Rng) -> Option<Self> {
+        let discipline = match *config {
+            QDiscConfig::Noqueue => return None,
+            QDiscConfig::Pfifo { limit, limit_bytes } => Discipline::Pfifo(Pfifo {
+                fifo: PacketFifo::new(),
+                limit,
+                limit_bytes,
+            }),
+            QDiscConfig::Red {
+                limit_bytes,
+                min_bytes,
+                max_bytes,
+                probability,
+                weight,
+            } => Discipline::Red(Red {
+                fifo: PacketFifo::new(),
+                limit_bytes,
+                min: min_bytes as f64,
+                max: max_bytes as f64,
+                probability,
+                weight,
+                avpkt_time: RED_AVPKT
+                    .checked_mul(1_000_000_000)
+                    .and_then(|x| x.checked_div(bandwidth))
+                    .map(SimulationTime::from_nanos),
+                avg: 0.0,
+                count: -1,
+                idle_since: None,
+                rng: Xoshiro256PlusPlus::seed_from_u64(rng.random()),
+            }),
+            QDiscConfig::Codel { limit, codel } => Discipline::Codel(CoDelQueue {
+                fifo: PacketFifo::new(),
+                codel: CoDel::new(codel),
+                limit,
+            }),
+            QDiscConfig::FqCodel {
+                limit,
+                codel,
+                flows,
+                quantum_bytes,
+            } => Discipline::FqCodel(FqCodel {
+                flows: BTreeMap::new(),
+                new_flows: VecDeque::new(),
+                old_flows: VecDeque::new(),
+                num_flows: flows,
+                perturbation: rng.random(),
+                limit,
+                quantum: quantum_bytes.try_into().unwrap(),
+                codel,
+                len: 0,
+                bytes: 0,
+            }),
+        };
+
+        Some(Self {
+            discipline,
+            stats: QDiscStats::default(),
+        })
+    }
+
+    /// Adds a packet to the qdisc, which may drop it or another packet instead.
+    pub fn enqueue(&mut self, packet: PacketRc, now: EmulatedTime) {
+        let stats = &mut self.stats;
+        match &mut self.discipline {
+            Discipline::Pfifo(x) => x.enqueue(packet, now, stats),
+            Discipline::Red(x) => x.enqueue(packet, now, stats),
+            Discipline::Codel(x) => x.enqueue(packet, now, stats),
+            Discipline::FqCodel(x) => x.enqueue(packet, now, stats),
+        }
+
+        let (packets, bytes) = self.backlog();
+        let stats = &mut self.stats;
+        stats.max_backlog_packets = stats.max_backlog_packets.max(packets.try_into().unwrap());
+        stats.max_backlog_bytes = stats.max_backlog_bytes.max(bytes.try_into().unwrap());
+    }
+
+    /// Returns the next packet to send, or `None` if the qdisc is empty. Packets may be dropped
+    /// during the dequeue.
+    pub fn dequeue(&mut self, now: EmulatedTime) -> Option<PacketRc> {
+        let stats = &mut self.stats;
+        let packet = match &mut self.discipline {
+            Discipline::Pfifo(x) => x.dequeue(now, stats),
+            Discipline::Red(x) => x.dequeue(now, stats),
+            Discipline::Codel(x) => x.dequeue(now, stats),
+            Discipline::FqCodel(x) => x.dequeue(now, stats),
+        }?;
+
+        self.stats.sent_packets += 1;
+        self.stats.sent_bytes += u64::try_from(packet.len()).unwrap();
+
+        Some(packet)
+    }
+
+    /// The number of packets and bytes in the qdisc.
+    pub fn backlog(&self) -> (usize, usize) {
+        match &self.discipline {
+            Discipline::Pfifo(x) => (x.fifo.len(), x.fifo.bytes),
+            Discipline::Red(x) => (x.fifo.len(), x.fifo.bytes),
+            Discipline::Codel(x) => (x.fifo.len(), x.fifo.bytes),
+            Discipline::FqCodel(x) => (x.len, x.bytes),
+        }
+    }
+
+    pub fn stats(&self) -> &QDiscStats {
+        &self.stats
+    }
+
+    /// Removes all packets from the qdisc without counting them as dropped.
+    pub fn clear(&mut self) {
+        match &mut self.discipline {
+            Discipline::Pfifo(x) => x.fifo.clear(),
+            Discipline::Red(x) => x.fifo.clear(),
+            Discipline::Codel(x) => x.fifo.clear(),
+            Discipline::FqCodel(x) => {
+                x.flows.clear();
+                x.new_flows.clear();
+                x.old_flows.clear();
+                x.len = 0;
+                x.bytes = 0;
+            }
+        }
+    }
+}
+
+/// A fifo of packets and the times that they were enqueued.
+struct PacketFifo {
+    packets: VecDeque<(PacketRc, EmulatedTime)>,
+    /// The sum of the sizes of the packets in the fifo.
+    bytes: usize,
+}
+
+impl PacketFifo {
+    fn new() -> Self {
+        Self {
+            packets: VecDeque::new(),
+            bytes: 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.packets.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.packets.is_empty()
+    }
+
+    fn push(&mut self, packet: PacketRc, now: EmulatedTime) {
+        self.bytes += packet.len();
+        self.packets.push_back((packet, now));
+    }
+
+    fn pop(&mut self) -> Option<(PacketRc, EmulatedTime)> {
+        let (packet, enqueue_time) = self.packets.pop_front()?;
+        self.bytes -= packet.len();
+        Some((packet, enqueue_time))
+    }
+
+    fn clear(&mut self) {
+        self.packets.clear();
+        self.bytes = 0;
+    }
+}
+
+/// A tail-drop fifo.
+struct Pfifo {
+    fifo: PacketFifo,
+    limit: usize,
+    limit_bytes: Option<usize>,
+}
+
+impl Pfifo {
+    fn enqueue(&mut self, packet: PacketRc, now: EmulatedTime, stats: &mut QDiscStats) {
+        let over_bytes = self
+            .limit_bytes
+            .is_some_and(|limit| self.fifo.bytes + packet.len() > limit);
+
+        if self.fifo.len() >= self.limit || over_bytes {
+            drop_packet(packet, DropReason::Limit, stats);
+        } else {
+            self.fifo.push(packet, now);
+        }
+    }
+
+    fn dequeue(&mut self, _now: EmulatedTime, _stats: &mut QDiscStats) -> Option<PacketRc> {
+        self.fifo.pop().map(|(packet, _)| packet)
+    }
+}
+
+/// Random early detection, as described by Floyd and Jacobson. The average queue size is measured
+/// in bytes.
+struct Red {
+    fifo: PacketFifo,
+    limit_bytes: usize,
+    min: f64,
+    max: f64,
+    /// The drop probability when the average queue size reaches `max`.
+    probability: f64,
+    /// The weight of the current queue size in the moving average.
+    weight: f64,
+    /// The time to send an average-sized packet, or `None` if the bandwidth is unknown.
+    avpkt_time: Option<SimulationTime>,
+    avg: f64,
+    /// The number of packets since the last drop while the average was between `min` and `max`,
+    /// or -1 if the average was below `min`.
+    count: i64,
+    /// When the queue became empty.
+    idle_since: Option<EmulatedTime>,
+    rng: Xoshiro256PlusPlus,
+}
+
+impl Red {
+    fn enqueue(&mut self, packet: PacketRc, now: EmulatedTime, stats: &mut QDiscStats) {
+        match self.idle_since.take() {
+            Some(idle_since) => {
+                // decay the average as if empty-queue samples were taken for each packet that
+                // could have been sent while idle
+                let idle_packets = match self.avpkt_time {
+                    Some(avpkt_time) if !avpkt_time.is_zero() => {
+                        now.saturating_duration_since(&idle_since).as_nanos_f64()
+                            / avpkt_time.as_nanos_f64()
+                    }
+                    _ => f64::INFINITY,
+                };
+                self.avg *= (1.0 - self.weight).powf(idle_packets);
+            }
+            None => {
+                self.avg = (1.0 - self.weight) * self.avg + self.weight * self.fifo.bytes as f64;
+            }
+        }
+
+        let early_drop = if self.avg < self.min {
+            self.count = -1;
+            false
+        } else if self.avg < self.max {
+            self.count += 1;
+            let pb = self.probability * (self.avg - self.min) / (self.max - self.min);
+            // spread the drops out evenly rather than in clusters
+            let pa = match self.count as f64 * pb {
+                x if x >= 1.0 => 1.0,
+                x => pb / (1.0 - x),
+            };
+            self.rng.random::<f64>() < pa
+        } else {
+            true
+        };
+
+        if early_drop {
+            self.count = 0;
+            drop_packet(packet, DropReason::Aqm, stats);
+        } else if self.fifo.bytes + packet.len() > self.limit_bytes {
+            drop_packet(packet, DropReason::Limit, stats);
+        } else {
+            self.fifo.push(packet, now);
+        }
+    }
+
+    fn dequeue(&mut self, now: EmulatedTime, _stats: &mut QDiscStats) -> Option<PacketRc> {
+        let Some((packet, _)) = self.fifo.pop() else {
+            self.idle_since.get_or_insert(now);
+            return None;
+        };
+
+        if self.fifo.is_empty() {
+            self.idle_since = Some(now);
+        }
+
+        Some(packet)
+    }
+}
+
+/// A single fifo managed by CoDel.
+struct CoDelQueue {
+    fifo: PacketFifo,
+    codel: CoDel,
+    limit: usize,
+}
+
+impl CoDelQueue {
+    fn enqueue(&mut self, packet: PacketRc, now: EmulatedTime, stats: &mut QDiscStats) {
+        if self.fifo.len() >= self.limit {
+            drop_packet(packet, DropReason::Limit, stats);
+        } else {
+            self.fifo.push(packet, now);
+        }
+    }
+
+    fn dequeue(&mut self, now: EmulatedTime, stats: &mut QDiscStats) -> Option<PacketRc> {
+        self.codel.dequeue(&mut self.fifo, now, stats)
+    }
+}
+
+/// The CoDel state of a fifo. This follows the pseudocode in RFC 8289, but unlike the router's
+/// `CoDelQueue` the target and interval are configurable and the state is kept separate from the
+/// fifo so that fq_codel can run CoDel on each of its flows.
+struct CoDel {
+    config: CoDelConfig,
+    dropping: bool,
+    /// If set, the time at which the sojourn time will have been above the target for an
+    /// interval.
+    first_above_time: Option<EmulatedTime>,
+    drop_next: EmulatedTime,
+    count: u64,
+    last_count: u64,
+}
+
+impl CoDel {
+    fn new(config: CoDelConfig) -> Self {
+        Self {
+            config,
+            dropping: false,
+            first_above_time: None,
+            drop_next: EmulatedTime::SIMULATION_START,
+            count: 0,
+            last_count: 0,
+        }
+    }
+
+    fn dequeue(
+        &mut self,
+        fifo: &mut PacketFifo,
+        now: EmulatedTime,
+        stats: &mut QDiscStats,
+    ) -> Option<PacketRc> {
+        let Some((mut packet, ok_to_drop)) = self.dodequeue(fifo, now) else {
+            self.dropping = false;
+            return None;
+        };
+
+        if self.dropping {
+            if !ok_to_drop {
+                // the sojourn time went below the target
+                self.dropping = false;
+            }
+
+            while self.dropping && now >= self.drop_next {
+                drop_packet(packet, DropReason::Aqm, stats);
+                self.count += 1;
+
+                let Some((next, ok_to_drop)) = self.dodequeue(fifo, now) else {
+                    self.dropping = false;
+                    return None;
+                };
+                packet = next;
+
+                if ok_to_drop {
+                    self.drop_next = self.control_law(self.drop_next);
+                } else {
+                    self.dropping = false;
+                }
+            }
+        } else if ok_to_drop {
+            drop_packet(packet, DropReason::Aqm, stats);
+            let next = self.dodequeue(fifo, now);
+            self.dropping = true;
+
+            // if we were in the dropping state recently, start from the drop rate that last
+            // controlled the queue
+            let delta = self.count.saturating_sub(self.last_count);
+            let recently = now.saturating_duration_since(&self.drop_next)
+                < self.config.interval.saturating_mul(16);
+            self.count = if delta > 1 && recently { delta } else { 1 };
+            self.drop_next = self.control_law(now);
+            self.last_count = self.count;
+
+            packet = next?.0;
+        }
+
+        Some(packet)
+    }
+
+    /// Pops the next packet, and returns whether it's ok to drop it.
+    fn dodequeue(&mut self, fifo: &mut PacketFifo, now: EmulatedTime) -> Option<(PacketRc, bool)> {
+        let Some((packet, enqueue_time)) = fifo.pop() else {
+            self.first_above_time = None;
+            return None;
+        };
+
+        let sojourn_time = now.saturating_duration_since(&enqueue_time);
+        let mtu = usize::try_from(c::CONFIG_MTU).unwrap();
+
+        if sojourn_time < self.config.target || fifo.bytes <= mtu {
+            self.first_above_time = None;
+            return Some((packet, false));
+        }
+
+        let ok_to_drop = match self.first_above_time {
+            Some(first_above_time) => now >= first_above_time,
+            None => {
+                self.first_above_time = Some(now.saturating_add(self.config.interval));
+                false
+            }
+        };
+
+        Some((packet, ok_to_drop))
+    }
+
+    /// `time + interval / sqrt(count)`
+    fn control_law(&self, time: EmulatedTime) -> EmulatedTime {
+        let count = std::cmp::max(self.count, 1) as f64;
+        let increment = (self.config.interval.as_nanos_f64() / count.sqrt()).round() as u64;
+        time.saturating_add(SimulationTime::from_nanos(increment))
+    }
+}
+
+/// Flow queue CoDel. Packets are hashed by their flow into one of `num_flows` queues, which are
+/// each managed by CoDel and served by deficit round robin with priority for new flows.
+struct FqCodel {
+    /// Flows that have received packets, by hash bucket.
+    flows: BTreeMap<usize, Flow>,
+    new_flows: VecDeque<usize>,
+    old_flows: VecDeque<usize>,
+    num_flows: usize,
+    /// Mixed into the flow hash, like the random perturbation in Linux.
+    perturbation: u64,
+    limit: usize,
+    quantum: i64,
+    codel: CoDelConfig,
+    /// The number of packets in all flows.
+    len: usize,
+    /// The number of bytes in all flows.
+    bytes: usize,
+}
+
+struct Flow {
+    fifo: PacketFifo,
+    codel: CoDel,
+    deficit: i64,
+    /// Whether the flow is in either the new or old flows list.
+    active: bool,
+}
+
+impl FqCodel {
+    fn flow_index(&self, packet: &PacketRc) -> usize {
+        let mut hasher = DefaultHasher::new();
+        self.perturbation.hash(&mut hasher);
+        packet.flow().hash(&mut hasher);
+        (hasher.finish() % u64::try_from(self.num_flows).unwrap()) as usize
+    }
+
+    fn enqueue(&mut self, packet: PacketRc, now: EmulatedTime, stats: &mut QDiscStats) {
+        let index = self.flow_index(&packet);
+        let codel = self.codel;
+        let flow = self.flows.entry(index).or_insert_with(|| Flow {
+            fifo: PacketFifo::new(),
+            codel: CoDel::new(codel),
+            deficit: 0,
+            active: false,
+        });
+
+        self.len += 1;
+        self.bytes += packet.len();
+        flow.fifo.push(packet, now);
+
+        if !flow.active {
+            flow.active = true;
+            flow.deficit = self.quantum;
+            self.new_flows.push_back(index);
+        }
+
+        if self.len > self.limit {
+            self.drop_from_fattest_flow(stats);
+        }
+    }
+
+    /// Drop the head packet of the flow with the largest backlog. Linux drops up to half of the
+    /// flow's backlog at once to save cpu time, but we only drop the one packet needed to get back
+    /// within the limit.
+    fn drop_from_fattest_flow(&mut self, stats: &mut QDiscStats) {
+        let Some(flow) = self
+            .flows
+            .values_mut()
+            .max_by_key(|flow| flow.fifo.bytes)
+            .filter(|flow| !flow.fifo.is_empty())
+        else {
+            return;
+        };
+
+        let (packet, _) = flow.fifo.pop().unwrap();
+        self.len -= 1;
+        self.bytes -= packet.len();
+        drop_packet(packet, DropReason::Limit, stats);
+    }
+
+    fn dequeue(&mut self, now: EmulatedTime, stats: &mut QDiscStats) -> Option<PacketRc> {
+        loop {
+            let (index, is_new) = match self.new_flows.front() {
+                Some(index) => (*index, true),
+                None => (*self.old_flows.front()?, false),
+            };
+            let flow = self.flows.get_mut(&index).unwrap();
+
+            if flow.deficit <= 0 {
+                // the flow used up its quantum, so move it to the end of the old flows
+                flow.deficit += self.quantum;
+                self.pop_front(is_new);
+                self.old_flows.push_back(index);
+                continue;
+            }
+
+            let (len, bytes) = (flow.fifo.len(), flow.fifo.bytes);
+            let packet = flow.codel.dequeue(&mut flow.fifo, now, stats);
+            self.len -= len - flow.fifo.len();
+            self.bytes -= bytes - flow.fifo.bytes;
+
+            let Some(packet) = packet else {
+                // the flow is empty; a new flow moves to the old flows so that it can't get
+                // priority again by becoming empty and then new again, unless there are no other
+                // flows
+                let move_to_old = is_new && !self.old_flows.is_empty();
+                if !move_to_old {
+                    flow.active = false;
+                }
+                self.pop_front(is_new);
+                if move_to_old {
+                    self.old_flows.push_back(index);
+                }
+                continue;
+            };
+
+            flow.deficit -= i64::try_from(packet.len()).unwrap();
+            return Some(packet);
+        }
+    }
+
+    fn pop_front(&mut self, is_new: bool) {
+        if is_new {
+            self.new_flows.pop_front();
+        } else {
+            self.old_flows.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddrV4};
+
+    use super::*;
+    use crate::network::tests::mock_time_millis;
+
+    // These tests don't run in miri because they cause c::packet* functions to be called.
+
+    fn rng() -> Xoshiro256PlusPlus {
+        Xoshiro256PlusPlus::seed_from_u64(0)
+    }
+
+    fn codel() -> CoDelConfig {
+        CoDelConfig {
+            target: SimulationTime::from_millis(5),
+            interval: SimulationTime::from_millis(100),
+        }
+    }
+
+    fn packet(src_port: u16) -> PacketRc {
+        PacketRc::new_ipv4_udp(
+            SocketAddrV4::new(Ipv4Addr::new(11, 0, 0, 1), src_port),
+            SocketAddrV4::new(Ipv4Addr::new(11, 0, 0, 2), 80),
+            bytes::Bytes::from(vec![0u8; 1000]),
+            0,
+        )
+    }
+
+    /// Returns a source port whose flow isn't hashed to the same fq_codel queue as port 1.
+    fn other_flow_port(qdisc: &QDisc) -> u16 {
+        let Discipline::FqCodel(fq_codel) = &qdisc.discipline else {
+            unreachable!();
+        };
+        let index = fq_codel.flow_index(&packet(1));
+        (2..)
+            .find(|port| fq_codel.flow_index(&packet(*port)) != index)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_noqueue() {
+        assert!(QDisc::new(&QDiscConfig::Noqueue, 1_000_000, &mut rng()).is_none());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_pfifo_packet_limit() {
+        let now = mock_time_millis(1000);
+        let config = QDiscConfig::Pfifo {
+            limit: 3,
+            limit_bytes: None,
+        };
+        let mut qdisc = QDisc::new(&config, 1_000_000, &mut rng()).unwrap();
+
+        let packets: Vec<_> = (0..5).map(packet).collect();
+        for p in &packets {
+            qdisc.enqueue(p.clone(), now);
+        }
+
+        assert_eq!(qdisc.backlog().0, 3);
+        assert_eq!(qdisc.stats().limit_drops, 2);
+
+        // the first packets were kept
+        for p in &packets[..3] {
+            assert_eq!(qdisc.dequeue(now).unwrap().flow(), p.flow());
+        }
+        assert!(qdisc.dequeue(now).is_none());
+
+        assert_eq!(qdisc.stats().sent_packets, 3);
+        assert_eq!(qdisc.stats().max_backlog_packets, 3);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_pfifo_byte_limit() {
+        let now = mock_time_millis(1000);
+        let len = packet(1).len();
+        let config = QDiscConfig::Pfifo {
+            limit: 1000,
+            limit_bytes: Some(len * 2 + len / 2),
+        };
+        let mut qdisc = QDisc::new(&config, 1_000_000, &mut rng()).unwrap();
+
+        for i in 0..4 {
+            qdisc.enqueue(packet(i), now);
+        }
+
+        assert_eq!(qdisc.backlog(), (2, len * 2));
+        assert_eq!(qdisc.stats().limit_drops, 2);
+        assert_eq!(
+            qdisc.stats().max_backlog_bytes,
+            u64::try_from(len).unwrap() * 2
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_red() {
+        let len = packet(1).len();
+        let config = QDiscConfig::Red {
+            limit_bytes: len * 100,
+            min_bytes: len * 5,
+            max_bytes: len * 20,
+            probability: 0.1,
+            weight: 0.5,
+        };
+        let mut qdisc = QDisc::new(&config, 1_000_000, &mut rng()).unwrap();
+
+        // a short queue is never dropped
+        let now = mock_time_millis(1000);
+        for i in 0..4 {
+            qdisc.enqueue(packet(i), now);
+        }
+        assert_eq!(qdisc.backlog().0, 4);
+        assert_eq!(qdisc.stats().aqm_drops, 0);
+
+        // a long queue is dropped early, before it reaches the limit
+        for i in 0..100 {
+            qdisc.enqueue(packet(i), now);
+        }
+        assert!(qdisc.stats().aqm_drops > 0);
+        assert_eq!(qdisc.stats().limit_drops, 0);
+        assert!(qdisc.backlog().1 <= len * 25);
+
+        // after draining the queue and staying idle, the average decays and nothing is dropped
+        while qdisc.dequeue(now).is_some() {}
+        let drops = qdisc.stats().aqm_drops;
+        let later = mock_time_millis(2000);
+        for i in 0..4 {
+            qdisc.enqueue(packet(i), later);
+        }
+        assert_eq!(qdisc.stats().aqm_drops, drops);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_codel_limit() {
+        let now = mock_time_millis(1000);
+        let config = QDiscConfig::Codel {
+            limit: 2,
+            codel: codel(),
+        };
+        let mut qdisc = QDisc::new(&config, 1_000_000, &mut rng()).unwrap();
+
+        for i in 0..3 {
+            qdisc.enqueue(packet(i), now);
+        }
+        assert_eq!(qdisc.stats().limit_drops, 1);
+        assert_eq!(qdisc.backlog().0, 2);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_codel_drops_standing_queue() {
+        let one = SimulationTime::from_millis(1);
+        let start = mock_time_millis(1000);
+        let target = codel().target;
+        let interval = codel().interval;
+        let config = QDiscConfig::Codel {
+            limit: 1000,
+            codel: codel(),
+        };
+        let mut qdisc = QDisc::new(&config, 1_000_000, &mut rng()).unwrap();
+
+        const N: usize = 20;
+        for i in 0..N {
+            qdisc.enqueue(packet(i as u16), start);
+        }
+
+        // below the target
+        assert!(qdisc.dequeue(start + target - one).is_some());
+        // above the target, but not for an interval
+        assert!(qdisc.dequeue(start + target).is_some());
+        assert!(qdisc.dequeue(start + target + interval - one).is_some());
+        assert_eq!(qdisc.stats().aqm_drops, 0);
+
+        // above the target for an interval, so one packet is dropped
+        assert!(qdisc.dequeue(start + target + interval).is_some());
+        assert_eq!(qdisc.stats().aqm_drops, 1);
+        assert_eq!(qdisc.backlog().0, N - 5);
+
+        // much later, the control law drops packets until the queue is almost empty
+        while qdisc.dequeue(mock_time_millis(1_000_000)).is_some() {}
+        let stats = qdisc.stats();
+        assert_eq!(stats.sent_packets + stats.aqm_drops, N as u64);
+        assert!(stats.aqm_drops > 1);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_codel_control_law() {
+        let now = mock_time_millis(1000);
+        let mut codel = CoDel::new(self::codel());
+
+        for count in 0..2 {
+            codel.count = count;
+            assert_eq!(
+                codel.control_law(now).duration_since(&now),
+                SimulationTime::from_millis(100)
+            );
+        }
+
+        codel.count = 4;
+        assert_eq!(
+            codel.control_law(now).duration_since(&now),
+            SimulationTime::from_millis(50)
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_fq_codel_round_robin() {
+        let now = mock_time_millis(1000);
+        let config = QDiscConfig::FqCodel {
+            limit: 1000,
+            codel: codel(),
+            flows: 1024,
+            quantum_bytes: 1,
+        };
+        let mut qdisc = QDisc::new(&config, 1_000_000, &mut rng()).unwrap();
+
+        let sparse = other_flow_port(&qdisc);
+
+        // a bulk flow, and then a sparse flow
+        for _ in 0..10 {
+            qdisc.enqueue(packet(1), now);
+        }
+        qdisc.enqueue(packet(sparse), now);
+
+        // the bulk flow sends one packet and uses up its quantum, so the new sparse flow is next
+        assert_eq!(qdisc.dequeue(now).unwrap().src_ipv4_address().port(), 1);
+        assert_eq!(
+            qdisc.dequeue(now).unwrap().src_ipv4_address().port(),
+            sparse
+        );
+
+        for _ in 0..9 {
+            assert_eq!(qdisc.dequeue(now).unwrap().src_ipv4_address().port(), 1);
+        }
+        assert!(qdisc.dequeue(now).is_none());
+        assert_eq!(qdisc.backlog(), (0, 0));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_fq_codel_limit_drops_fattest_flow() {
+        let now = mock_time_millis(1000);
+        let config = QDiscConfig::FqCodel {
+            limit: 5,
+            codel: codel(),
+            flows: 1024,
+            quantum_bytes: 1514,
+        };
+        let mut qdisc = QDisc::new(&config, 1_000_000, &mut rng()).unwrap();
+
+        let sparse = other_flow_port(&qdisc);
+
+        for _ in 0..5 {
+            qdisc.enqueue(packet(1), now);
+        }
+        qdisc.enqueue(packet(sparse), now);
+
+        assert_eq!(qdisc.stats().limit_drops, 1);
+        assert_eq!(qdisc.backlog().0, 5);
+
+        // the sparse flow's packet wasn't dropped
+        let mut ports = Vec::new();
+        while let Some(p) = qdisc.dequeue(now) {
+            ports.push(p.src_ipv4_address().port());
+        }
+        assert_eq!(ports.iter().filter(|x| **x == sparse).count(), 1);
+        assert_eq!(ports.len(), 5);
+    }
+}
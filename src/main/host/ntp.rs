@@ -0,0 +1,129 @@
+//! The per-host kernel NTP state, as read and written by `adjtimex(2)` and
+//! `clock_adjtime(2)`.
+//!
+//! We only model the parts of the kernel's NTP state that NTP daemons read
+//! back. Adjustments that would make the kernel slew the clock (`ADJ_OFFSET`
+//! and `ADJ_OFFSET_SINGLESHOT`) are instead applied immediately as a step of
+//! the host's realtime clock by the syscall handlers, and frequency adjustments
+//! (`ADJ_FREQUENCY` and `ADJ_TICK`) are recorded but don't change the rate of
+//! the host's clocks.
+
+use linux_api::time::{AdjtimexModes, TIME_ERROR, TIME_OK, TimexStatus, timex};
+
+/// The kernel's maximum frequency adjustment of 500 ppm, in units of scaled
+/// ppm (ppm << 16).
+const MAX_FREQ_SCALED: i64 = 500 << 16;
+
+/// The kernel's maximum error, in microseconds.
+const MAX_ERROR_USEC: i64 = 16_000_000;
+
+/// Microseconds between clock ticks with `USER_HZ` of 100.
+const DEFAULT_TICK_USEC: i64 = 10_000;
+
+/// Status bits that can't be set by the user.
+const STATUS_READ_ONLY: TimexStatus = TimexStatus::STA_PPSSIGNAL
+    .union(TimexStatus::STA_PPSJITTER)
+    .union(TimexStatus::STA_PPSWANDER)
+    .union(TimexStatus::STA_PPSERROR)
+    .union(TimexStatus::STA_CLOCKERR)
+    .union(TimexStatus::STA_NANO)
+    .union(TimexStatus::STA_MODE)
+    .union(TimexStatus::STA_CLK);
+
+#[derive(Debug, Clone)]
+pub struct NtpState {
+    freq: i64,
+    maxerror: i64,
+    esterror: i64,
+    status: TimexStatus,
+    constant: i64,
+    tick: i64,
+    tai: i32,
+}
+
+impl Default for NtpState {
+    fn default() -> Self {
+        Self {
+            freq: 0,
+            maxerror: MAX_ERROR_USEC,
+            esterror: MAX_ERROR_USEC,
+            status: TimexStatus::STA_UNSYNC,
+            constant: 2,
+            tick: DEFAULT_TICK_USEC,
+            tai: 0,
+        }
+    }
+}
+
+impl NtpState {
+    /// Apply the fields of `txc` selected by `modes`. Fields that the kernel
+    /// would validate should have already been validated by the caller. Time
+    /// adjustments (`ADJ_OFFSET` and `ADJ_SETOFFSET`) are not handled here.
+    pub fn update(&mut self, modes: AdjtimexModes, txc: &timex) {
+        if modes.contains(AdjtimexModes::ADJ_STATUS) {
+            let status = TimexStatus::from_bits_truncate(txc.status);
+            self.status = (self.status & STATUS_READ_ONLY) | (status - STATUS_READ_ONLY);
+        }
+        if modes.contains(AdjtimexModes::ADJ_NANO) {
+            self.status.insert(TimexStatus::STA_NANO);
+        }
+        if modes.contains(AdjtimexModes::ADJ_MICRO) {
+            self.status.remove(TimexStatus::STA_NANO);
+        }
+        if modes.contains(AdjtimexModes::ADJ_FREQUENCY) {
+            self.freq = txc.freq.clamp(-MAX_FREQ_SCALED, MAX_FREQ_SCALED);
+        }
+        if modes.contains(AdjtimexModes::ADJ_MAXERROR) {
+            self.maxerror = txc.maxerror.clamp(0, MAX_ERROR_USEC);
+        }
+        if modes.contains(AdjtimexModes::ADJ_ESTERROR) {
+            self.esterror = txc.esterror.clamp(0, MAX_ERROR_USEC);
+        }
+        if modes.contains(AdjtimexModes::ADJ_TIMECONST) {
+            self.constant = txc.constant.clamp(0, 10);
+        }
+        if modes.contains(AdjtimexModes::ADJ_TAI) && txc.constant >= 0 {
+            // Yes, the kernel takes the TAI offset from the `constant` field.
+            self.tai = txc.constant.try_into().unwrap_or(i32::MAX);
+        }
+        if modes.contains(AdjtimexModes::ADJ_TICK) {
+            self.tick = txc.tick;
+        }
+    }
+
+    /// Whether time values are in nanoseconds rather than microseconds.
+    pub fn nano(&self) -> bool {
+        self.status.contains(TimexStatus::STA_NANO)
+    }
+
+    /// Write the current state into `txc`, except for the `time` field.
+    pub fn fill(&self, txc: &mut timex) {
+        txc.offset = 0;
+        txc.freq = self.freq;
+        txc.maxerror = self.maxerror;
+        txc.esterror = self.esterror;
+        txc.status = self.status.bits();
+        txc.constant = self.constant;
+        txc.precision = 1;
+        txc.tolerance = MAX_FREQ_SCALED;
+        txc.tick = self.tick;
+        txc.ppsfreq = 0;
+        txc.jitter = 0;
+        txc.shift = 0;
+        txc.stabil = 0;
+        txc.jitcnt = 0;
+        txc.calcnt = 0;
+        txc.errcnt = 0;
+        txc.stbcnt = 0;
+        txc.tai = self.tai;
+    }
+
+    /// The clock state returned by `adjtimex(2)`.
+    pub fn clock_state(&self) -> i32 {
+        if self.status.contains(TimexStatus::STA_UNSYNC) {
+            TIME_ERROR
+        } else {
+            TIME_OK
+        }
+    }
+}
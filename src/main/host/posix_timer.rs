@@ -0,0 +1,247 @@
+//! POSIX per-process timers, as created by `timer_create(2)`.
+
+use std::cell::Cell;
+use std::collections::BTreeMap;
+
+use linux_api::errno::Errno;
+use linux_api::posix_types::kernel_timer_t;
+use linux_api::signal::{SigInfoDetails, Signal, siginfo_t, sigval};
+use linux_api::time::ClockId;
+use log::debug;
+use shadow_shim_helper_rs::syscall_types::ForeignPtr;
+
+use super::host::Host;
+use super::process::{Process, ProcessId};
+use super::thread::ThreadId;
+use super::timer::Timer;
+
+/// How a [`PosixTimer`] notifies the process when it expires.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PosixTimerNotify {
+    /// `SIGEV_NONE`: no notification. The timer can still be polled with
+    /// `timer_gettime(2)`.
+    None,
+    /// `SIGEV_SIGNAL`: send a signal to the process.
+    Process(Signal),
+    /// `SIGEV_THREAD_ID`: send a signal to a specific thread of the process.
+    Thread(Signal, ThreadId),
+}
+
+pub struct PosixTimer {
+    timer: Timer,
+    clockid: ClockId,
+    notify: PosixTimerNotify,
+    // The `sigev_value` to send with the signal. This is either an integer or a
+    // pointer in the managed process.
+    value: ForeignPtr<()>,
+    // The number of additional expirations that occurred while the signal of
+    // an earlier expiration was still pending, or before the timer's start
+    // time, as returned by `timer_getoverrun(2)`.
+    overrun: Cell<i32>,
+    // The number of intervals that had already elapsed when the timer was
+    // armed with an absolute time in the past, to be counted as overruns of
+    // the next signal.
+    missed: Cell<i32>,
+}
+
+impl PosixTimer {
+    pub fn clockid(&self) -> ClockId {
+        self.clockid
+    }
+
+    pub fn timer(&self) -> &Timer {
+        &self.timer
+    }
+
+    /// The underlying timer. Re-arming the timer should be accompanied by
+    /// [`PosixTimer::reset_overrun`].
+    pub fn timer_mut(&mut self) -> &mut Timer {
+        &mut self.timer
+    }
+
+    pub fn overrun(&self) -> i32 {
+        self.overrun.get()
+    }
+
+    pub fn reset_overrun(&self) {
+        self.overrun.set(0);
+        self.missed.set(0);
+    }
+
+    /// Count `missed` intervals, which elapsed before the start time the timer
+    /// was armed with, as overruns of the timer's next signal.
+    pub fn set_missed_intervals(&self, missed: i32) {
+        self.missed.set(missed)
+    }
+
+    fn sigval(&self) -> sigval {
+        sigval {
+            sival_ptr: self.value.into_raw_mut().cast(),
+        }
+    }
+
+    fn expire(&self, host: &Host, process: &Process, id: kernel_timer_t) {
+        let (signal, tid) = match self.notify {
+            PosixTimerNotify::None => return,
+            PosixTimerNotify::Process(signal) => (signal, None),
+            PosixTimerNotify::Thread(signal, tid) => (signal, Some(tid)),
+        };
+
+        let thread = match tid {
+            Some(tid) => {
+                let Some(thread) = process.thread_borrow(tid) else {
+                    debug!("Target thread {tid} of timer {id} no longer exists");
+                    return;
+                };
+                Some(thread)
+            }
+            None => None,
+        };
+        let thread = thread.as_ref().map(|t| t.borrow(host.root()));
+
+        // If the signal from an earlier expiration is still pending, the kernel
+        // doesn't queue another one, and instead counts an overrun.
+        {
+            let host_shmem = host.shim_shmem_lock_borrow().unwrap();
+            match &thread {
+                None => {
+                    let process_shmem = process.shmem();
+                    let mut protected = process_shmem.protected.borrow_mut(&host_shmem.root);
+                    let pending = protected.pending_standard_siginfo(signal);
+                    if let Some(siginfo) = self.overrun_pending(pending, id) {
+                        protected.set_pending_standard_siginfo(signal, &siginfo);
+                        return;
+                    }
+                }
+                Some(thread) => {
+                    let thread_shmem = thread.shmem();
+                    let mut protected = thread_shmem.protected.borrow_mut(&host_shmem.root);
+                    let pending = protected.pending_standard_siginfo(signal);
+                    if let Some(siginfo) = self.overrun_pending(pending, id) {
+                        protected.set_pending_standard_siginfo(signal, &siginfo);
+                        return;
+                    }
+                }
+            }
+        }
+
+        let overrun = self.missed.take();
+        self.overrun.set(overrun);
+        let siginfo = siginfo_t::new_for_timer(signal, id, overrun, self.sigval());
+        match &thread {
+            None => process.signal(host, None, &siginfo),
+            Some(thread) => thread.signal(host, process, None, &siginfo),
+        }
+    }
+
+    /// If `pending` is a signal from this timer, increments the overrun count
+    /// and returns the signal updated with the new count.
+    fn overrun_pending(
+        &self,
+        pending: Option<&siginfo_t>,
+        id: kernel_timer_t,
+    ) -> Option<siginfo_t> {
+        let pending = pending?;
+        // SAFETY: We don't dereference any pointers in the details.
+        let Some(SigInfoDetails::Timer(details)) = (unsafe { pending.details() }) else {
+            return None;
+        };
+        if details.l_tid != id {
+            return None;
+        }
+        let overrun = self
+            .overrun
+            .get()
+            .saturating_add(1)
+            .saturating_add(self.missed.take());
+        self.overrun.set(overrun);
+        Some(siginfo_t::new_for_timer(
+            pending.signal().unwrap(),
+            id,
+            overrun,
+            self.sigval(),
+        ))
+    }
+}
+
+/// The POSIX timers of a process.
+#[derive(Default)]
+pub struct PosixTimerTable {
+    timers: BTreeMap<kernel_timer_t, PosixTimer>,
+    next_id: kernel_timer_t,
+}
+
+impl PosixTimerTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a new timer for process `pid`, returning its id. If `value` is
+    /// `None`, the timer's id is used as the signal value, as for a NULL
+    /// `sevp` in `timer_create(2)`.
+    pub fn add(
+        &mut self,
+        pid: ProcessId,
+        clockid: ClockId,
+        notify: PosixTimerNotify,
+        value: Option<ForeignPtr<()>>,
+    ) -> Result<kernel_timer_t, Errno> {
+        let id = self.next_free_id().ok_or(Errno::EAGAIN)?;
+        self.next_id = id.checked_add(1).unwrap_or(0);
+
+        let value = value.unwrap_or_else(|| ForeignPtr::from(u64::from(id.unsigned_abs())));
+        let timer = PosixTimer {
+            timer: Timer::new(move |host| posix_timer_expiration(host, pid, id)),
+            clockid,
+            notify,
+            value,
+            overrun: Cell::new(0),
+            missed: Cell::new(0),
+        };
+        self.timers.insert(id, timer);
+        Ok(id)
+    }
+
+    pub fn get(&self, id: kernel_timer_t) -> Option<&PosixTimer> {
+        self.timers.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: kernel_timer_t) -> Option<&mut PosixTimer> {
+        self.timers.get_mut(&id)
+    }
+
+    pub fn remove(&mut self, id: kernel_timer_t) -> Option<PosixTimer> {
+        self.timers.remove(&id)
+    }
+
+    /// Delete all timers, e.g. on `exec`.
+    pub fn clear(&mut self) {
+        self.timers.clear();
+        self.next_id = 0;
+    }
+
+    // Ids are allocated cyclically, skipping ids that are still in use.
+    fn next_free_id(&self) -> Option<kernel_timer_t> {
+        (self.next_id..=kernel_timer_t::MAX)
+            .chain(0..self.next_id)
+            .find(|id| !self.timers.contains_key(id))
+    }
+}
+
+fn posix_timer_expiration(host: &Host, pid: ProcessId, id: kernel_timer_t) {
+    let Some(process) = host.process_borrow(pid) else {
+        debug!("Process {pid:?} no longer exists");
+        return;
+    };
+    let process = process.borrow(host.root());
+    let Some(runnable) = process.borrow_as_runnable() else {
+        debug!("Process {:?} no longer running", &*process.name());
+        return;
+    };
+    let timers = runnable.posix_timers_borrow();
+    let Some(timer) = timers.get(id) else {
+        debug!("Timer {id} of process {pid:?} no longer exists");
+        return;
+    };
+    timer.expire(host, &process, id);
+}
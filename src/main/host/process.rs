@@ -12,6 +12,7 @@ use std::sync::Arc;
 use std::sync::atomic::Ordering;
 #[cfg(feature = "perf_timers")]
 use std::time::Duration;
+use std::time::Instant;
 
 use linux_api::errno::Errno;
 use linux_api::fcntl::OFlag;
@@ -19,7 +20,7 @@ use linux_api::posix_types::Pid;
 use linux_api::sched::{CloneFlags, SuidDump};
 use linux_api::signal::{
     LinuxDefaultAction, SigActionFlags, Signal, SignalFromI32Error, defaultaction, siginfo_t,
-    sigset_t,
+    sigset_t, sigval,
 };
 use log::{debug, trace, warn};
 use rustix::process::{WaitOptions, WaitStatus};
@@ -38,10 +39,12 @@ use super::descriptor::listener::StateEventSource;
 use super::descriptor::{FileSignals, FileState};
 use super::host::Host;
 use super::memory_manager::{MemoryManager, ProcessMemoryRef, ProcessMemoryRefMut};
+use super::posix_timer::PosixTimerTable;
 use super::syscall::formatter::StraceFmtMode;
 use super::syscall::types::ForeignArrayPtr;
 use super::thread::{Thread, ThreadId};
 use super::timer::Timer;
+use crate::core::activity_trace::{Activity, ActivityEvent};
 use crate::core::configuration::{ProcessFinalState, RunningVal};
 use crate::core::work::task::TaskRef;
 use crate::core::worker::Worker;
@@ -49,7 +52,7 @@ use crate::cshadow;
 use crate::host::context::ProcessContext;
 use crate::host::descriptor::Descriptor;
 use crate::host::managed_thread::ManagedThread;
-use crate::host::syscall::formatter::FmtOptions;
+use crate::host::syscall::formatter::{StraceFormat, StraceOptions};
 use crate::utility::callback_queue::CallbackQueue;
 #[cfg(feature = "perf_timers")]
 use crate::utility::perf_timer::PerfTimer;
@@ -133,7 +136,21 @@ pub enum ExitStatus {
 #[derive(Debug)]
 struct StraceLogging {
     file: RootedRefCell<std::fs::File>,
-    options: FmtOptions,
+    options: StraceOptions,
+}
+
+impl StraceLogging {
+    /// The descriptor that the shim writes natively-run syscalls to. The shim only writes the
+    /// text format, so it isn't given the file if the log is in another format.
+    fn shim_fd(&self, root: &Root) -> Option<libc::c_int> {
+        (self.options.format == StraceFormat::Text).then(|| self.file.borrow(root).as_raw_fd())
+    }
+
+    /// Whether the shim reports the syscalls that it handles itself to Shadow, so that Shadow can
+    /// write them to the JSON log.
+    fn shim_json(&self) -> bool {
+        self.options.format == StraceFormat::Json
+    }
 }
 
 /// Parts of the process that are present in all states.
@@ -263,6 +280,9 @@ pub struct RunnableProcess {
 
     itimer_real: RefCell<Timer>,
 
+    // Timers created by `timer_create(2)`.
+    posix_timers: RefCell<PosixTimerTable>,
+
     // The `RootedRc` lets us hold a reference to a thread without holding a
     // reference to the thread list. e.g. this lets us implement the `clone`
     // syscall, which adds a thread to the list while we have a reference to the
@@ -389,7 +409,7 @@ impl RunnableProcess {
         self.memory_manager.borrow_mut()
     }
 
-    pub fn strace_logging_options(&self) -> Option<FmtOptions> {
+    pub fn strace_logging_options(&self) -> Option<StraceOptions> {
         self.strace_logging.as_ref().map(|x| x.options)
     }
 
@@ -429,6 +449,16 @@ impl RunnableProcess {
         self.first_live_thread(root)
     }
 
+    #[track_caller]
+    pub fn posix_timers_borrow(&self) -> impl Deref<Target = PosixTimerTable> + '_ {
+        self.posix_timers.borrow()
+    }
+
+    #[track_caller]
+    pub fn posix_timers_borrow_mut(&self) -> impl DerefMut<Target = PosixTimerTable> + '_ {
+        self.posix_timers.borrow_mut()
+    }
+
     #[track_caller]
     fn thread(&self, virtual_tid: ThreadId) -> Option<Ref<'_, RootedRc<RootedRefCell<Thread>>>> {
         Ref::filter_map(self.threads.borrow(), |threads| threads.get(&virtual_tid)).ok()
@@ -644,9 +674,8 @@ impl RunnableProcess {
             &host.shim_shmem_lock_borrow().unwrap().root,
             host.shim_shmem().serialize(),
             host.id(),
-            strace_logging
-                .as_ref()
-                .map(|x| x.file.borrow(host.root()).as_raw_fd()),
+            strace_logging.as_ref().and_then(|x| x.shim_fd(host.root())),
+            strace_logging.as_ref().is_some_and(|x| x.shim_json()),
         );
         let shim_shared_mem_block = shadow_shmem::allocator::shmalloc(shim_shared_mem);
 
@@ -662,6 +691,7 @@ impl RunnableProcess {
             #[cfg(feature = "perf_timers")]
             total_run_time: Cell::new(Duration::ZERO),
             itimer_real,
+            posix_timers: RefCell::new(PosixTimerTable::new()),
             threads,
             unsafe_borrow_mut: RefCell::new(None),
             unsafe_borrows: RefCell::new(Vec::new()),
@@ -896,7 +926,10 @@ fn itimer_real_expiration(host: &Host, pid: ProcessId) {
     // The siginfo_t structure only has an i32. Presumably we want to just truncate in
     // case of overflow.
     let expiration_count = timer.expiration_count() as i32;
-    let siginfo_t = siginfo_t::new_for_timer(Signal::SIGALRM, 0, expiration_count);
+    let sigval = sigval {
+        sival_ptr: std::ptr::null_mut(),
+    };
+    let siginfo_t = siginfo_t::new_for_timer(Signal::SIGALRM, 0, expiration_count, sigval);
     process.signal(host, None, &siginfo_t);
 }
 
@@ -953,7 +986,7 @@ impl Process {
         argv: Vec<CString>,
         envv: Vec<CString>,
         pause_for_debugging: bool,
-        strace_logging_options: Option<FmtOptions>,
+        strace_logging_options: Option<StraceOptions>,
         expected_final_state: ProcessFinalState,
     ) -> Result<RootedRc<RootedRefCell<Process>>, Errno> {
         debug!("starting process '{plugin_name:?}'");
@@ -996,9 +1029,8 @@ impl Process {
             &host.shim_shmem_lock_borrow().unwrap().root,
             host.shim_shmem().serialize(),
             host.id(),
-            strace_logging
-                .as_ref()
-                .map(|x| x.file.borrow(host.root()).as_raw_fd()),
+            strace_logging.as_ref().and_then(|x| x.shim_fd(host.root())),
+            strace_logging.as_ref().is_some_and(|x| x.shim_json()),
         );
         let shim_shared_mem_block = shadow_shmem::allocator::shmalloc(shim_shared_mem);
 
@@ -1133,6 +1165,7 @@ impl Process {
                         shim_shared_mem_block,
                         memory_manager: Box::new(RefCell::new(memory_manager)),
                         itimer_real,
+                        posix_timers: RefCell::new(PosixTimerTable::new()),
                         strace_logging,
                         dumpable: Cell::new(SuidDump::SUID_DUMP_USER),
                         native_pid,
@@ -1179,6 +1212,17 @@ impl Process {
         self.common().session_id.set(id)
     }
 
+    /// The credentials of this process, as passed over unix sockets with `SCM_CREDENTIALS`. The
+    /// uid and gid are passed through to the managed process natively, so they're the same as
+    /// shadow's.
+    pub fn credentials(&self) -> libc::ucred {
+        libc::ucred {
+            pid: self.id().into(),
+            uid: nix::unistd::getuid().as_raw(),
+            gid: nix::unistd::getgid().as_raw(),
+        }
+    }
+
     pub fn host_id(&self) -> HostId {
         self.common().host_id
     }
@@ -1251,9 +1295,29 @@ impl Process {
             .unwrap()
             .unapplied_cpu_latency = SimulationTime::ZERO;
 
+        let activity_start = host.is_tracing_activity().then(Instant::now);
+
         let ctx = ProcessContext::new(host, self);
         let res = thread.resume(&ctx);
 
+        if let Some(start) = activity_start {
+            let result = match &res {
+                crate::host::thread::ResumeResult::Blocked => "blocked",
+                crate::host::thread::ResumeResult::ExitedThread(_) => "exited thread",
+                crate::host::thread::ResumeResult::ExitedProcess => "exited process",
+            };
+            host.record_activity(ActivityEvent {
+                activity: Activity::Resume {
+                    tid,
+                    process_name: self.name().to_string(),
+                    result,
+                },
+                start,
+                duration: start.elapsed(),
+                sim_time: Worker::current_time().unwrap(),
+            });
+        }
+
         #[cfg(feature = "perf_timers")]
         {
             let delay = self.stop_cpu_delay_timer(host);
@@ -1427,7 +1491,7 @@ impl Process {
     }
 
     /// Deprecated wrapper for `RunnableProcess::strace_logging_options`
-    pub fn strace_logging_options(&self) -> Option<FmtOptions> {
+    pub fn strace_logging_options(&self) -> Option<StraceOptions> {
         self.as_runnable().unwrap().strace_logging_options()
     }
 
@@ -1457,6 +1521,22 @@ impl Process {
         })
     }
 
+    /// Deprecated wrapper for `RunnableProcess::posix_timers_borrow`
+    #[track_caller]
+    pub fn posix_timers_borrow(&self) -> impl Deref<Target = PosixTimerTable> + '_ {
+        std_util::nested_ref::NestedRef::map(self.as_runnable().unwrap(), |runnable| {
+            runnable.posix_timers.borrow()
+        })
+    }
+
+    /// Deprecated wrapper for `RunnableProcess::posix_timers_borrow_mut`
+    #[track_caller]
+    pub fn posix_timers_borrow_mut(&self) -> impl DerefMut<Target = PosixTimerTable> + '_ {
+        std_util::nested_ref::NestedRefMut::map(self.as_runnable().unwrap(), |runnable| {
+            runnable.posix_timers.borrow_mut()
+        })
+    }
+
     /// Deprecated wrapper for `RunnableProcess::first_live_thread_borrow`
     #[track_caller]
     pub fn first_live_thread_borrow(
@@ -1585,6 +1665,8 @@ impl Process {
         };
         log::log!(log_level, "{main_result_string}");
 
+        host.record_process_exit(runnable.common.id(), exit_status);
+
         let zombie = ZombieProcess {
             common: runnable.into_common(),
             exit_status,
@@ -1821,6 +1903,9 @@ impl Process {
         // Exit signal is reset to SIGCHLD.
         runnable.common.exit_signal = Some(Signal::SIGCHLD);
 
+        // `execve(2)`: POSIX timers are not preserved (timer_create(2)).
+        runnable.posix_timers.borrow_mut().clear();
+
         // Reset signal actions to default.
         // `execve(2)`:
         // POSIX.1 specifies that the dispositions of any signals that
@@ -2264,7 +2349,7 @@ mod export {
         proc: *const Process,
     ) -> StraceFmtMode {
         let proc = unsafe { proc.as_ref().unwrap() };
-        proc.strace_logging_options().into()
+        proc.strace_logging_options().map(|x| x.fmt).into()
     }
 
     #[unsafe(no_mangle)]
@@ -89,6 +89,12 @@ impl SyscallConditionRefMut<'_> {
         let timeout = EmulatedTime::to_c_emutime(timeout);
         unsafe { cshadow::syscallcondition_setTimeout(self.c_ptr.ptr(), timeout) };
     }
+
+    /// Make the timeout an absolute time on the host's realtime clock, which follows steps of
+    /// that clock. See [`Timer::arm_realtime_abs`](crate::host::timer::Timer::arm_realtime_abs).
+    pub fn set_timeout_realtime_abs(&mut self) {
+        unsafe { cshadow::syscallcondition_setTimeoutRealtimeAbs(self.c_ptr.ptr()) };
+    }
 }
 
 impl<'a> std::ops::Deref for SyscallConditionRefMut<'a> {
@@ -1,16 +1,23 @@
 use std::any::TypeId;
 use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufWriter, Write};
 use std::marker::PhantomData;
+use std::path::Path;
 
+use serde::{Deserialize, Serialize};
+use shadow_shim_helper_rs::HostId;
 use shadow_shim_helper_rs::emulated_time::EmulatedTime;
+use shadow_shim_helper_rs::simulation_time::SimulationTime;
 use shadow_shim_helper_rs::syscall_types::SyscallReg;
 use shadow_shim_helper_rs::util::time::TimeParts;
 
 use crate::core::worker::Worker;
 use crate::host::memory_manager::MemoryManager;
-use crate::host::process::Process;
+use crate::host::process::{Process, ProcessId};
 use crate::host::syscall::types::{SyscallError, SyscallResult};
 use crate::host::thread::ThreadId;
+use crate::utility::round_writer::RoundWriter;
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum FmtOptions {
@@ -18,6 +25,23 @@ pub enum FmtOptions {
     Deterministic,
 }
 
+/// The format of a process's strace log.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StraceFormat {
+    /// A line of text for each syscall, similar to strace.
+    Text,
+    /// A JSON object on each line for each syscall. See [`SyscallRecord`].
+    Json,
+}
+
+/// The options of a process's strace log.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct StraceOptions {
+    /// How syscall arguments and return values are formatted.
+    pub fmt: FmtOptions,
+    pub format: StraceFormat,
+}
+
 // this type is required until we no longer need to access the format options from C
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[repr(C)]
@@ -149,7 +173,7 @@ where
     }
 }
 
-impl<'a, A, B, C, D, E, F> Display for SyscallArgsFmt<'a, A, B, C, D, E, F>
+impl<'a, A, B, C, D, E, F> SyscallArgsFmt<'a, A, B, C, D, E, F>
 where
     SyscallVal<'a, A>: Display,
     SyscallVal<'a, B>: Display,
@@ -164,29 +188,65 @@ where
     E: 'static,
     F: 'static,
 {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let args: [&dyn Display; 6] = [&self.a, &self.b, &self.c, &self.d, &self.e, &self.f];
-
-        let types: [TypeId; 6] = [
-            TypeId::of::<A>(),
-            TypeId::of::<B>(),
-            TypeId::of::<C>(),
-            TypeId::of::<D>(),
-            TypeId::of::<E>(),
-            TypeId::of::<F>(),
+    /// The arguments with their registers and type names.
+    fn iter(&self) -> impl Iterator<Item = (&dyn Display, SyscallReg, &'static str)> {
+        let args: [(&dyn Display, SyscallReg); 6] = [
+            (&self.a, self.a.reg),
+            (&self.b, self.b.reg),
+            (&self.c, self.c.reg),
+            (&self.d, self.d.reg),
+            (&self.e, self.e.reg),
+            (&self.f, self.f.reg),
         ];
 
-        let mut first = true;
-        for (arg, arg_type) in args.iter().zip(types) {
-            if arg_type == TypeId::of::<NoArg>() {
-                // the user didn't override this generic type, so it and any following types/args
-                // should not be shown
-                break;
-            }
+        let types: [(TypeId, &'static str); 6] = [
+            (TypeId::of::<A>(), std::any::type_name::<A>()),
+            (TypeId::of::<B>(), std::any::type_name::<B>()),
+            (TypeId::of::<C>(), std::any::type_name::<C>()),
+            (TypeId::of::<D>(), std::any::type_name::<D>()),
+            (TypeId::of::<E>(), std::any::type_name::<E>()),
+            (TypeId::of::<F>(), std::any::type_name::<F>()),
+        ];
+
+        args.into_iter()
+            .zip(types)
+            // the user didn't override this generic type, so it and any following types/args
+            // should not be shown
+            .take_while(|(_, (arg_type, _))| *arg_type != TypeId::of::<NoArg>())
+            .map(|((arg, reg), (_, type_name))| (arg, reg, type_name))
+    }
 
-            if first {
+    /// The decoded arguments, for the JSON format.
+    pub fn records(&self) -> Vec<SyscallArgRecord> {
+        self.iter()
+            .map(|(arg, reg, type_name)| SyscallArgRecord {
+                type_name: short_type_name(type_name),
+                raw: reg.into(),
+                decoded: arg.to_string(),
+            })
+            .collect()
+    }
+}
+
+impl<'a, A, B, C, D, E, F> Display for SyscallArgsFmt<'a, A, B, C, D, E, F>
+where
+    SyscallVal<'a, A>: Display,
+    SyscallVal<'a, B>: Display,
+    SyscallVal<'a, C>: Display,
+    SyscallVal<'a, D>: Display,
+    SyscallVal<'a, E>: Display,
+    SyscallVal<'a, F>: Display,
+    A: 'static,
+    B: 'static,
+    C: 'static,
+    D: 'static,
+    E: 'static,
+    F: 'static,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, (arg, _, _)) in self.iter().enumerate() {
+            if i == 0 {
                 write!(f, "{arg}")?;
-                first = false;
             } else {
                 write!(f, ", {arg}")?;
             }
@@ -196,6 +256,30 @@ where
     }
 }
 
+/// Remove the module paths from a type name, for example
+/// "*const libc::unix::linux_like::linux::sockaddr" becomes "*const sockaddr".
+fn short_type_name(name: &str) -> String {
+    let mut short = String::with_capacity(name.len());
+    // where the current path in `short` starts
+    let mut path_start = 0;
+    let mut chars = name.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == ':' && chars.peek() == Some(&':') {
+            chars.next();
+            short.truncate(path_start);
+            continue;
+        }
+
+        short.push(c);
+        if !(c.is_alphanumeric() || c == '_') {
+            path_start = short.len();
+        }
+    }
+
+    short
+}
+
 /// A formatting wrapper for the syscall result.
 pub struct SyscallResultFmt<'a, RV>
 where
@@ -257,6 +341,95 @@ where
     }
 }
 
+impl<'a, RV> SyscallResultFmt<'a, RV>
+where
+    SyscallVal<'a, RV>: Display,
+    RV: std::fmt::Debug,
+{
+    /// The decoded result, for the JSON format.
+    pub fn record(&self) -> SyscallResultRecord {
+        match self.rv {
+            SyscallResult::Ok(x) => {
+                let rv = SyscallVal::<'_, RV>::new(*x, self.args, self.options, self.mem);
+                SyscallResultRecord {
+                    rv: Some(i64::from(*x)),
+                    rv_decoded: Some(rv.to_string()),
+                    ..Default::default()
+                }
+            }
+            SyscallResult::Err(SyscallError::Failed(failed)) => {
+                let errno = failed.errno;
+                let reg = SyscallReg::from(errno.to_negated_i64());
+                let rv = SyscallVal::<'_, RV>::new(reg, self.args, self.options, self.mem);
+                SyscallResultRecord {
+                    rv: Some(errno.to_negated_i64()),
+                    rv_decoded: Some(rv.to_string()),
+                    errno: Some(errno.to_string()),
+                    ..Default::default()
+                }
+            }
+            SyscallResult::Err(SyscallError::Native) => SyscallResultRecord {
+                native: true,
+                ..Default::default()
+            },
+            SyscallResult::Err(SyscallError::Blocked(_)) => SyscallResultRecord {
+                blocked: true,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// A syscall in the JSON strace format, for example:
+///
+/// ```json
+/// {"time_ns":1000000000,"host":"client","pid":1000,"tid":1000,"name":"close","args":[{"type":"i32","raw":3,"decoded":"3"}],"rv":0,"rv_decoded":"0","errno":null,"blocked":false,"native":false}
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyscallRecord {
+    /// Nanoseconds since the start of the simulation.
+    pub time_ns: u64,
+    pub host: String,
+    pub pid: libc::pid_t,
+    pub tid: libc::pid_t,
+    pub name: String,
+    /// The arguments, or `None` if Shadow doesn't decode the syscall's arguments (for example if
+    /// the syscall is unsupported or run natively).
+    pub args: Option<Vec<SyscallArgRecord>>,
+    #[serde(flatten)]
+    pub result: SyscallResultRecord,
+}
+
+/// A syscall argument in the JSON strace format.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyscallArgRecord {
+    /// The type that Shadow decodes the argument as, without module paths.
+    #[serde(rename = "type")]
+    pub type_name: String,
+    /// The value of the argument's register.
+    pub raw: u64,
+    /// The argument as it's written in the text format, including the contents of any strings or
+    /// structs that it points to.
+    pub decoded: String,
+}
+
+/// The result of a syscall in the JSON strace format.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyscallResultRecord {
+    /// The value returned to the process, which is the negated errno if the syscall failed. This
+    /// is `None` if the syscall blocked or was passed to the process to run natively.
+    pub rv: Option<i64>,
+    /// The return value as it's written in the text format.
+    pub rv_decoded: Option<String>,
+    /// The name of the error if the syscall failed, for example "EAGAIN".
+    pub errno: Option<String>,
+    /// The syscall blocked. It will be handled and written again when the thread is resumed.
+    pub blocked: bool,
+    /// The syscall was run natively by the process. It's written without a result when Shadow
+    /// passes it to the process, and again with its result once the process has run it.
+    pub native: bool,
+}
+
 /// Format and write the syscall.
 pub fn write_syscall(
     mut writer: impl std::io::Write,
@@ -273,10 +446,45 @@ pub fn write_syscall(
     writeln!(writer, "{sim_time} [tid {tid}] {name}({args}) = {rv}")
 }
 
+/// Write the syscall as a line of JSON, and add it to the simulation's merged strace log if there
+/// is one. Must be called with an active host.
+pub fn write_syscall_json(
+    mut writer: impl std::io::Write,
+    sim_time: &EmulatedTime,
+    pid: ProcessId,
+    tid: ThreadId,
+    name: &str,
+    args: Option<Vec<SyscallArgRecord>>,
+    result: SyscallResultRecord,
+) -> std::io::Result<()> {
+    let time_ns = sim_time
+        .duration_since(&EmulatedTime::SIMULATION_START)
+        .as_nanos();
+
+    let record = SyscallRecord {
+        time_ns: u64::try_from(time_ns).unwrap(),
+        host: Worker::with_active_host(|host| host.name().to_string()).unwrap(),
+        pid: libc::pid_t::from(pid),
+        tid: libc::pid_t::from(tid),
+        name: name.to_string(),
+        args,
+        result,
+    };
+
+    // write the line all at once since the file may be shared with forked processes
+    let mut line = serde_json::to_vec(&record)?;
+    line.push(b'\n');
+    writer.write_all(&line)?;
+
+    Worker::write_merged_strace(record);
+
+    Ok(())
+}
+
 /// For logging unknown syscalls.
 pub fn log_syscall_simple(
     proc: &Process,
-    logging_mode: Option<FmtOptions>,
+    logging_mode: Option<StraceOptions>,
     tid: ThreadId,
     syscall_name: &str,
     args_str: &str,
@@ -289,13 +497,19 @@ pub fn log_syscall_simple(
 
     let args = [SyscallReg::from(0i64); 6];
     let mem = proc.memory_borrow();
-    let rv = SyscallResultFmt::<libc::c_long>::new(result, args, logging_mode, &mem);
+    let rv = SyscallResultFmt::<libc::c_long>::new(result, args, logging_mode.fmt, &mem);
 
     proc.with_strace_file(|file| {
         let time = Worker::current_time();
 
         if let Some(time) = time {
-            write_syscall(file, &time, tid, syscall_name, args_str, rv)
+            match logging_mode.format {
+                StraceFormat::Text => write_syscall(file, &time, tid, syscall_name, args_str, rv),
+                // the arguments of these syscalls aren't decoded
+                StraceFormat::Json => {
+                    write_syscall_json(file, &time, proc.id(), tid, syscall_name, None, rv.record())
+                }
+            }
         } else {
             log::warn!("Could not log syscall {syscall_name} with time {time:?}");
             Ok(())
@@ -306,6 +520,41 @@ pub fn log_syscall_simple(
     Ok(())
 }
 
+/// Writes the JSON strace logs of all processes to a single file. Syscalls are recorded by hosts in
+/// parallel and written between rounds, ordered by time and then by host so that the file is
+/// deterministic.
+pub struct MergedStrace {
+    writer: RoundWriter<SyscallRecord, BufWriter<File>>,
+}
+
+impl MergedStrace {
+    /// Create the log file at `path`.
+    pub fn new(path: &Path) -> std::io::Result<Self> {
+        Ok(Self {
+            writer: RoundWriter::new(BufWriter::new(File::create(path)?)),
+        })
+    }
+
+    pub fn record(&self, host_id: HostId, record: SyscallRecord) {
+        let time = EmulatedTime::SIMULATION_START + SimulationTime::from_nanos(record.time_ns);
+        self.writer.record(time, Some(host_id), record);
+    }
+
+    /// Write the syscalls that were recorded during the round. Must only be called between rounds.
+    pub fn finish_round(&self) -> std::io::Result<()> {
+        self.writer.finish_round(|writer, record| {
+            serde_json::to_writer(&mut *writer, &record)?;
+            writeln!(writer)
+        })
+    }
+
+    /// Write the remaining syscalls and flush the file.
+    pub fn finish(&self) -> std::io::Result<()> {
+        self.finish_round()?;
+        self.writer.with_writer(|writer| writer.flush())
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::process::Command;
@@ -336,4 +585,84 @@ mod test {
         proc.kill().unwrap();
         proc.wait().unwrap();
     }
+
+    #[test]
+    fn test_short_type_name() {
+        assert_eq!(short_type_name("i32"), "i32");
+        assert_eq!(
+            short_type_name("*const libc::unix::linux_like::linux::sockaddr"),
+            "*const sockaddr"
+        );
+        assert_eq!(
+            short_type_name("shadow_rs::host::syscall::type_formatting::SyscallBufferArg<2>"),
+            "SyscallBufferArg<2>"
+        );
+        assert_eq!(
+            short_type_name("core::option::Option<alloc::string::String>"),
+            "Option<String>"
+        );
+    }
+
+    fn record(time_ns: u64, host: &str, name: &str) -> SyscallRecord {
+        SyscallRecord {
+            time_ns,
+            host: host.to_string(),
+            pid: 1000,
+            tid: 1001,
+            name: name.to_string(),
+            args: Some(vec![SyscallArgRecord {
+                type_name: "i32".to_string(),
+                raw: 3,
+                decoded: "3".to_string(),
+            }]),
+            result: SyscallResultRecord {
+                rv: Some(-11),
+                rv_decoded: Some("-11".to_string()),
+                errno: Some("EAGAIN".to_string()),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_record_format() {
+        let record = record(5, "client", "read");
+        let json = serde_json::to_string(&record).unwrap();
+        assert_eq!(
+            json,
+            concat!(
+                r#"{"time_ns":5,"host":"client","pid":1000,"tid":1001,"name":"read","#,
+                r#""args":[{"type":"i32","raw":3,"decoded":"3"}],"rv":-11,"rv_decoded":"-11","#,
+                r#""errno":"EAGAIN","blocked":false,"native":false}"#,
+            )
+        );
+        assert_eq!(
+            serde_json::from_str::<SyscallRecord>(&json).unwrap(),
+            record
+        );
+    }
+
+    #[test]
+    // can't call foreign function: open
+    #[cfg_attr(miri, ignore)]
+    fn test_merged_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("strace.jsonl");
+        let merged = MergedStrace::new(&path).unwrap();
+
+        merged.record(HostId::from(2), record(10, "server", "write"));
+        merged.record(HostId::from(1), record(10, "client", "read"));
+        merged.record(HostId::from(2), record(5, "server", "close"));
+        merged.record(HostId::from(2), record(10, "server", "open"));
+        merged.finish_round().unwrap();
+        merged.record(HostId::from(1), record(20, "client", "write"));
+        merged.finish().unwrap();
+
+        let names: Vec<_> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|x| serde_json::from_str::<SyscallRecord>(x).unwrap().name)
+            .collect();
+        assert_eq!(names, ["close", "read", "write", "open", "write"]);
+    }
 }
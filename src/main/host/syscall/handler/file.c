@@ -89,6 +89,21 @@ static SyscallReturn _syscallhandler_fsyncHelper(SyscallHandler* sys, int fd) {
     return syscallreturn_makeDoneI64(regularfile_fsync(file_desc));
 }
 
+static SyscallReturn _syscallhandler_unlinkHelper(SyscallHandler* sys,
+                                                  UntypedForeignPtr pathnamePtr, int flags) {
+    /* Get the path string from the plugin. */
+    const char* pathname;
+    int errcode = process_getReadableString(
+        rustsyscallhandler_getProcess(sys), pathnamePtr, PATH_MAX, &pathname, NULL);
+    if (errcode < 0) {
+        return syscallreturn_makeDoneErrno(-errcode);
+    }
+
+    const char* plugin_cwd = process_getWorkingDir(rustsyscallhandler_getProcess(sys));
+
+    return syscallreturn_makeDoneI64(regularfile_unlinkat(NULL, pathname, flags, plugin_cwd));
+}
+
 ///////////////////////////////////////////////////////////
 // System Calls
 ///////////////////////////////////////////////////////////
@@ -427,3 +442,53 @@ SyscallReturn syscallhandler_getdents64(SyscallHandler* sys, const SyscallArgs*
 
     return syscallreturn_makeDoneI64(regularfile_getdents64(file_desc, dirp, count));
 }
+
+SyscallReturn syscallhandler_mkdir(SyscallHandler* sys, const SyscallArgs* args) {
+    UntypedForeignPtr pathnamePtr = args->args[0].as_ptr; // const char*
+    mode_t mode = args->args[1].as_u64;
+
+    /* Get the path string from the plugin. */
+    const char* pathname;
+    int errcode = process_getReadableString(
+        rustsyscallhandler_getProcess(sys), pathnamePtr, PATH_MAX, &pathname, NULL);
+    if (errcode < 0) {
+        return syscallreturn_makeDoneErrno(-errcode);
+    }
+
+    const char* plugin_cwd = process_getWorkingDir(rustsyscallhandler_getProcess(sys));
+
+    return syscallreturn_makeDoneI64(regularfile_mkdirat(NULL, pathname, mode, plugin_cwd));
+}
+
+SyscallReturn syscallhandler_rename(SyscallHandler* sys, const SyscallArgs* args) {
+    UntypedForeignPtr oldpathPtr = args->args[0].as_ptr; // const char*
+    UntypedForeignPtr newpathPtr = args->args[1].as_ptr; // const char*
+
+    /* Get the path strings from the plugin. */
+    const char* oldpath;
+    int errcode = process_getReadableString(
+        rustsyscallhandler_getProcess(sys), oldpathPtr, PATH_MAX, &oldpath, NULL);
+    if (errcode < 0) {
+        return syscallreturn_makeDoneErrno(-errcode);
+    }
+
+    const char* newpath;
+    errcode = process_getReadableString(
+        rustsyscallhandler_getProcess(sys), newpathPtr, PATH_MAX, &newpath, NULL);
+    if (errcode < 0) {
+        return syscallreturn_makeDoneErrno(-errcode);
+    }
+
+    const char* plugin_cwd = process_getWorkingDir(rustsyscallhandler_getProcess(sys));
+
+    return syscallreturn_makeDoneI64(
+        regularfile_renameat2(NULL, oldpath, NULL, newpath, 0, plugin_cwd));
+}
+
+SyscallReturn syscallhandler_rmdir(SyscallHandler* sys, const SyscallArgs* args) {
+    return _syscallhandler_unlinkHelper(sys, args->args[0].as_ptr, AT_REMOVEDIR);
+}
+
+SyscallReturn syscallhandler_unlink(SyscallHandler* sys, const SyscallArgs* args) {
+    return _syscallhandler_unlinkHelper(sys, args->args[0].as_ptr, 0);
+}
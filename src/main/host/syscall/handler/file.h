@@ -26,9 +26,13 @@ SYSCALL_HANDLER(ftruncate);
 SYSCALL_HANDLER(getdents);
 SYSCALL_HANDLER(getdents64);
 SYSCALL_HANDLER(lseek);
+SYSCALL_HANDLER(mkdir);
 SYSCALL_HANDLER(open);
 SYSCALL_HANDLER(readahead);
+SYSCALL_HANDLER(rename);
+SYSCALL_HANDLER(rmdir);
 SYSCALL_HANDLER(sync_file_range);
 SYSCALL_HANDLER(syncfs);
+SYSCALL_HANDLER(unlink);
 
 #endif /* SRC_MAIN_HOST_SYSCALL_FILE_H_ */
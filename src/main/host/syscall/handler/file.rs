@@ -226,6 +226,16 @@ impl SyscallHandler {
         }
     }
 
+    log_syscall!(
+        mkdir,
+        /* rv */ std::ffi::c_int,
+        /* pathname */ SyscallStringArg,
+        /* mode */ linux_api::types::umode_t
+    );
+    pub fn mkdir(ctx: &mut SyscallContext) -> SyscallResult {
+        Self::legacy_syscall(cshadow::syscallhandler_mkdir, ctx)
+    }
+
     log_syscall!(
         readahead,
         /* rv */ std::ffi::c_int,
@@ -251,6 +261,25 @@ impl SyscallHandler {
         Err(SyscallError::Native)
     }
 
+    log_syscall!(
+        rename,
+        /* rv */ std::ffi::c_int,
+        /* oldname */ SyscallStringArg,
+        /* newname */ SyscallStringArg
+    );
+    pub fn rename(ctx: &mut SyscallContext) -> SyscallResult {
+        Self::legacy_syscall(cshadow::syscallhandler_rename, ctx)
+    }
+
+    log_syscall!(
+        rmdir,
+        /* rv */ std::ffi::c_int,
+        /* pathname */ SyscallStringArg
+    );
+    pub fn rmdir(ctx: &mut SyscallContext) -> SyscallResult {
+        Self::legacy_syscall(cshadow::syscallhandler_rmdir, ctx)
+    }
+
     log_syscall!(
         sync_file_range,
         /* rv */ std::ffi::c_int,
@@ -271,4 +300,13 @@ impl SyscallHandler {
     pub fn syncfs(ctx: &mut SyscallContext) -> SyscallResult {
         Self::legacy_syscall(cshadow::syscallhandler_syncfs, ctx)
     }
+
+    log_syscall!(
+        unlink,
+        /* rv */ std::ffi::c_int,
+        /* pathname */ SyscallStringArg
+    );
+    pub fn unlink(ctx: &mut SyscallContext) -> SyscallResult {
+        Self::legacy_syscall(cshadow::syscallhandler_unlink, ctx)
+    }
 }
@@ -1,29 +1,76 @@
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::sync::Arc;
+
+use atomic_refcell::AtomicRefCell;
 use linux_api::errno::Errno;
-use nix::sys::eventfd::EfdFlags;
+use linux_api::fcntl::DescriptorFlags;
+use linux_api::inotify::{InotifyInitFlags, InotifyMask};
+use shadow_shim_helper_rs::syscall_types::ForeignPtr;
 
 use crate::host::descriptor::descriptor_table::DescriptorHandle;
+use crate::host::descriptor::inotify::Inotify;
+use crate::host::descriptor::{CompatFile, Descriptor, File, FileStatus, OpenFile};
 use crate::host::syscall::handler::{SyscallContext, SyscallHandler};
 use crate::host::syscall::type_formatting::SyscallStringArg;
-use shadow_shim_helper_rs::syscall_types::ForeignPtr;
+use crate::host::syscall::types::ForeignArrayPtr;
+use crate::utility::callback_queue::CallbackQueue;
 
 impl SyscallHandler {
+    log_syscall!(inotify_init, /* rv */ std::ffi::c_int);
+    pub fn inotify_init(ctx: &mut SyscallContext) -> Result<DescriptorHandle, Errno> {
+        Self::inotify_init_helper(ctx, 0)
+    }
+
     log_syscall!(
         inotify_init1,
         /* rv */ std::ffi::c_int,
-        /* flags */ std::ffi::c_int,
+        /* flags */ linux_api::inotify::InotifyInitFlags,
     );
-    pub fn inotify_init1(ctx: &mut SyscallContext, flags: std::ffi::c_int) -> Result<DescriptorHandle, Errno> {
-        // Map a subset of inotify flags to eventfd flags for minimal behavior.
-        let mut efd_flags = 0;
-        // IN_NONBLOCK and IN_CLOEXEC share values with O_NONBLOCK/O_CLOEXEC on Linux.
-        const IN_NONBLOCK: i32 = libc::IN_NONBLOCK as i32;
-        const IN_CLOEXEC: i32 = libc::IN_CLOEXEC as i32;
-        if (flags & IN_NONBLOCK) != 0 { efd_flags |= EfdFlags::EFD_NONBLOCK.bits(); }
-        if (flags & IN_CLOEXEC) != 0 { efd_flags |= EfdFlags::EFD_CLOEXEC.bits(); }
-
-        // Create an eventfd to represent the inotify instance; it'll remain non-readable
-        // unless we later implement delivering events.
-        Self::eventfd_helper(ctx, 0, efd_flags)
+    pub fn inotify_init1(
+        ctx: &mut SyscallContext,
+        flags: std::ffi::c_int,
+    ) -> Result<DescriptorHandle, Errno> {
+        Self::inotify_init_helper(ctx, flags)
+    }
+
+    fn inotify_init_helper(
+        ctx: &mut SyscallContext,
+        flags: std::ffi::c_int,
+    ) -> Result<DescriptorHandle, Errno> {
+        let Some(flags) = InotifyInitFlags::from_bits(flags) else {
+            log::debug!("Invalid inotify flags: {flags}");
+            return Err(Errno::EINVAL);
+        };
+
+        let mut file_flags = FileStatus::empty();
+        let mut descriptor_flags = DescriptorFlags::empty();
+
+        if flags.contains(InotifyInitFlags::IN_NONBLOCK) {
+            file_flags.insert(FileStatus::NONBLOCK);
+        }
+
+        if flags.contains(InotifyInitFlags::IN_CLOEXEC) {
+            descriptor_flags.insert(DescriptorFlags::FD_CLOEXEC);
+        }
+
+        let file = Arc::new(AtomicRefCell::new(Inotify::new(file_flags)));
+        ctx.objs.host.register_inotify(&file);
+
+        let mut desc = Descriptor::new(CompatFile::New(OpenFile::new(File::Inotify(file))));
+        desc.set_flags(descriptor_flags);
+
+        let fd = ctx
+            .objs
+            .thread
+            .descriptor_table_borrow_mut(ctx.objs.host)
+            .register_descriptor(desc)
+            .or(Err(Errno::ENFILE))?;
+
+        log::trace!("inotify_init1() returning fd {fd}");
+
+        Ok(fd)
     }
 
     log_syscall!(
@@ -31,16 +78,45 @@ impl SyscallHandler {
         /* rv */ std::ffi::c_int,
         /* fd */ std::ffi::c_int,
         /* pathname */ SyscallStringArg,
-        /* mask */ libc::c_uint,
+        /* mask */ linux_api::inotify::InotifyMask,
     );
     pub fn inotify_add_watch(
-        _ctx: &mut SyscallContext,
-        _fd: std::ffi::c_int,
-        _pathname: ForeignPtr<()>,
-        _mask: libc::c_uint,
+        ctx: &mut SyscallContext,
+        fd: std::ffi::c_int,
+        pathname: ForeignPtr<std::ffi::c_char>,
+        mask: u32,
     ) -> Result<std::ffi::c_int, Errno> {
-        // Minimal stub: succeed and return a fake positive watch descriptor.
-        Ok(1)
+        let inotify = get_inotify(ctx, fd)?;
+        let mask = InotifyMask::from_bits_retain(mask);
+
+        let mut path_buf = [0u8; linux_api::limits::PATH_MAX];
+        let path_buf_capacity = path_buf.len();
+        let path = ctx.objs.process.memory_borrow().copy_str_from_ptr(
+            &mut path_buf,
+            ForeignArrayPtr::new(pathname.cast::<u8>(), path_buf_capacity),
+        )?;
+
+        if path.is_empty() {
+            return Err(Errno::ENOENT);
+        }
+
+        let cwd = ctx.objs.process.current_working_dir();
+        let cwd = Path::new(OsStr::from_bytes(cwd.to_bytes()));
+        let path = cwd.join(OsStr::from_bytes(path.to_bytes()));
+
+        // the watched file must exist, and the process must be able to read it
+        let metadata = if mask.contains(InotifyMask::IN_DONT_FOLLOW) {
+            std::fs::symlink_metadata(&path)
+        } else {
+            std::fs::metadata(&path)
+        };
+        let metadata = metadata.map_err(|e| Errno::try_from(e).unwrap_or(Errno::EINVAL))?;
+
+        if mask.contains(InotifyMask::IN_ONLYDIR) && !metadata.is_dir() {
+            return Err(Errno::ENOTDIR);
+        }
+
+        inotify.borrow_mut().add_watch(&path, mask)
     }
 
     log_syscall!(
@@ -50,13 +126,34 @@ impl SyscallHandler {
         /* wd */ std::ffi::c_int,
     );
     pub fn inotify_rm_watch(
-        _ctx: &mut SyscallContext,
-        _fd: std::ffi::c_int,
-        _wd: std::ffi::c_int,
-    ) -> Result<std::ffi::c_int, Errno> {
-        // Minimal stub: succeed.
-        Ok(0)
+        ctx: &mut SyscallContext,
+        fd: std::ffi::c_int,
+        wd: std::ffi::c_int,
+    ) -> Result<(), Errno> {
+        let inotify = get_inotify(ctx, fd)?;
+
+        CallbackQueue::queue_and_run_with_legacy(|cb_queue| {
+            inotify.borrow_mut().rm_watch(wd, cb_queue)
+        })
     }
 }
 
+/// Get the inotify instance for `fd`. Returns `EINVAL` if the file is not an inotify instance.
+fn get_inotify(
+    ctx: &mut SyscallContext,
+    fd: std::ffi::c_int,
+) -> Result<Arc<AtomicRefCell<Inotify>>, Errno> {
+    let desc_table = ctx.objs.thread.descriptor_table_borrow(ctx.objs.host);
+    let desc = SyscallHandler::get_descriptor(&desc_table, fd)?;
 
+    // our inotify is a new Rust type, so if we get a legacy C type it must not be an inotify
+    let CompatFile::New(file) = desc.file() else {
+        return Err(Errno::EINVAL);
+    };
+
+    let File::Inotify(inotify) = file.inner_file() else {
+        return Err(Errno::EINVAL);
+    };
+
+    Ok(Arc::clone(inotify))
+}
@@ -1,7 +1,7 @@
 use std::borrow::Cow;
-
 #[cfg(feature = "perf_timers")]
 use std::time::Duration;
+use std::time::Instant;
 
 use linux_api::errno::Errno;
 use linux_api::syscall::SyscallNum;
@@ -12,6 +12,7 @@ use shadow_shim_helper_rs::syscall_types::SyscallArgs;
 use shadow_shim_helper_rs::syscall_types::SyscallReg;
 use shadow_shim_helper_rs::util::SendPointer;
 
+use crate::core::activity_trace::{Activity, ActivityEvent};
 use crate::core::worker::Worker;
 use crate::cshadow as c;
 use crate::host::context::ThreadContext;
@@ -163,9 +164,11 @@ impl SyscallHandler {
 
         // Count the frequency of each syscall, but only on the initial call. This avoids double
         // counting in the case where the initial call blocked at first, but then later became
-        // unblocked and is now being handled again here.
+        // unblocked and is now being handled again here. Syscalls made to log a syscall in the
+        // JSON strace log aren't counted, so that the counts don't depend on the strace mode.
         if let Some(syscall_counter) = self.syscall_counter.as_mut()
             && !was_blocked
+            && syscall != SyscallNum::from(ShadowSyscallNum::shadow_log_syscall)
         {
             syscall_counter.add_one(syscall_name);
         }
@@ -173,8 +176,24 @@ impl SyscallHandler {
         #[cfg(feature = "perf_timers")]
         let timer = PerfTimer::new_started();
 
+        let activity_start = ctx.host.is_tracing_activity().then(Instant::now);
+
         let mut rv = self.run_handler(ctx, args);
 
+        if let Some(start) = activity_start {
+            ctx.host.record_activity(ActivityEvent {
+                activity: Activity::Syscall {
+                    tid: ctx.thread.id(),
+                    name: syscall_name,
+                    blocked: matches!(rv, Err(SyscallError::Blocked(_))),
+                    native: matches!(rv, Err(SyscallError::Native)),
+                },
+                start,
+                duration: start.elapsed(),
+                sim_time: Worker::current_time().unwrap(),
+            });
+        }
+
         #[cfg(feature = "perf_timers")]
         {
             // add the cumulative elapsed seconds
@@ -348,13 +367,14 @@ impl SyscallHandler {
                 let rv = SyscallHandlerFn::call(Self::$f, &mut ctx);
 
                 // log the syscall if enabled
-                if let Some(strace_fmt_options) = ctx.objs.process.strace_logging_options() {
+                if let Some(strace_options) = ctx.objs.process.strace_logging_options() {
                     ctx.objs.process.with_strace_file(|file| {
                         crate::utility::macros::SyscallLogger::$f(
                             file,
                             ctx.args.args,
                             &rv,
-                            strace_fmt_options,
+                            strace_options,
+                            ctx.objs.process.id(),
                             ctx.objs.thread.id(),
                             &*ctx.objs.process.memory_borrow(),
                         )
@@ -371,6 +391,7 @@ impl SyscallHandler {
             //
             SyscallNum::NR_accept => handle!(accept),
             SyscallNum::NR_accept4 => handle!(accept4),
+            SyscallNum::NR_adjtimex => handle!(adjtimex),
             SyscallNum::NR_arch_prctl => handle!(arch_prctl),
             SyscallNum::NR_alarm => handle!(alarm),
             SyscallNum::NR_bind => handle!(bind),
@@ -378,8 +399,10 @@ impl SyscallHandler {
             SyscallNum::NR_capget => handle!(capget),
             SyscallNum::NR_capset => handle!(capset),
             SyscallNum::NR_chdir => handle!(chdir),
+            SyscallNum::NR_clock_adjtime => handle!(clock_adjtime),
             SyscallNum::NR_clock_getres => handle!(clock_getres),
             SyscallNum::NR_clock_nanosleep => handle!(clock_nanosleep),
+            SyscallNum::NR_clock_settime => handle!(clock_settime),
             SyscallNum::NR_clone => handle!(clone),
             SyscallNum::NR_clone3 => handle!(clone3),
             SyscallNum::NR_close => handle!(close),
@@ -438,15 +461,16 @@ impl SyscallHandler {
             SyscallNum::NR_getsockname => handle!(getsockname),
             SyscallNum::NR_getsockopt => handle!(getsockopt),
             SyscallNum::NR_gettid => handle!(gettid),
+            SyscallNum::NR_inotify_add_watch => handle!(inotify_add_watch),
+            SyscallNum::NR_inotify_init => handle!(inotify_init),
+            SyscallNum::NR_inotify_init1 => handle!(inotify_init1),
+            SyscallNum::NR_inotify_rm_watch => handle!(inotify_rm_watch),
             SyscallNum::NR_ioctl => handle!(ioctl),
             SyscallNum::NR_kill => handle!(kill),
             SyscallNum::NR_linkat => handle!(linkat),
-            // inotify minimal stubs
-            SyscallNum::NR_inotify_add_watch => handle!(inotify_add_watch),
-            SyscallNum::NR_inotify_rm_watch => handle!(inotify_rm_watch),
-            SyscallNum::NR_inotify_init1 => handle!(inotify_init1),
             SyscallNum::NR_listen => handle!(listen),
             SyscallNum::NR_lseek => handle!(lseek),
+            SyscallNum::NR_mkdir => handle!(mkdir),
             SyscallNum::NR_mkdirat => handle!(mkdirat),
             SyscallNum::NR_mknodat => handle!(mknodat),
             SyscallNum::NR_mmap => handle!(mmap),
@@ -477,8 +501,10 @@ impl SyscallHandler {
             SyscallNum::NR_readv => handle!(readv),
             SyscallNum::NR_recvfrom => handle!(recvfrom),
             SyscallNum::NR_recvmsg => handle!(recvmsg),
+            SyscallNum::NR_rename => handle!(rename),
             SyscallNum::NR_renameat => handle!(renameat),
             SyscallNum::NR_renameat2 => handle!(renameat2),
+            SyscallNum::NR_rmdir => handle!(rmdir),
             SyscallNum::NR_rseq => handle!(rseq),
             SyscallNum::NR_rt_sigaction => handle!(rt_sigaction),
             SyscallNum::NR_rt_sigprocmask => handle!(rt_sigprocmask),
@@ -494,6 +520,7 @@ impl SyscallHandler {
             SyscallNum::NR_setpgid => handle!(setpgid),
             SyscallNum::NR_setsid => handle!(setsid),
             SyscallNum::NR_setsockopt => handle!(setsockopt),
+            SyscallNum::NR_settimeofday => handle!(settimeofday),
             SyscallNum::NR_shutdown => handle!(shutdown),
             SyscallNum::NR_sigaltstack => handle!(sigaltstack),
             SyscallNum::NR_socket => handle!(socket),
@@ -504,11 +531,17 @@ impl SyscallHandler {
             SyscallNum::NR_syncfs => handle!(syncfs),
             SyscallNum::NR_sysinfo => handle!(sysinfo),
             SyscallNum::NR_tgkill => handle!(tgkill),
+            SyscallNum::NR_timer_create => handle!(timer_create),
+            SyscallNum::NR_timer_delete => handle!(timer_delete),
+            SyscallNum::NR_timer_getoverrun => handle!(timer_getoverrun),
+            SyscallNum::NR_timer_gettime => handle!(timer_gettime),
+            SyscallNum::NR_timer_settime => handle!(timer_settime),
             SyscallNum::NR_timerfd_create => handle!(timerfd_create),
             SyscallNum::NR_timerfd_gettime => handle!(timerfd_gettime),
             SyscallNum::NR_timerfd_settime => handle!(timerfd_settime),
             SyscallNum::NR_tkill => handle!(tkill),
             SyscallNum::NR_uname => handle!(uname),
+            SyscallNum::NR_unlink => handle!(unlink),
             SyscallNum::NR_unlinkat => handle!(unlinkat),
             SyscallNum::NR_utimensat => handle!(utimensat),
             SyscallNum::NR_vfork => handle!(vfork),
@@ -528,6 +561,10 @@ impl SyscallHandler {
                         handle!(shadow_init_memory_manager)
                     }
                     ShadowSyscallNum::shadow_yield => handle!(shadow_yield),
+                    // not logged itself, since it logs another syscall
+                    ShadowSyscallNum::shadow_log_syscall => {
+                        SyscallHandlerFn::call(Self::shadow_log_syscall, &mut ctx)
+                    }
                 }
             }
             //
@@ -567,11 +604,8 @@ impl SyscallHandler {
             | SyscallNum::NR_lsetxattr
             | SyscallNum::NR_lstat
             | SyscallNum::NR_madvise
-            | SyscallNum::NR_mkdir
             | SyscallNum::NR_mknod
             | SyscallNum::NR_removexattr
-            | SyscallNum::NR_rename
-            | SyscallNum::NR_rmdir
             | SyscallNum::NR_rt_sigreturn
             | SyscallNum::NR_setfsgid
             | SyscallNum::NR_setfsuid
@@ -586,7 +620,6 @@ impl SyscallHandler {
             | SyscallNum::NR_statfs
             | SyscallNum::NR_symlink
             | SyscallNum::NR_truncate
-            | SyscallNum::NR_unlink
             | SyscallNum::NR_utime
             | SyscallNum::NR_utimes => {
                 log::trace!("Native syscall {} ({})", syscall_name, ctx.args.number);
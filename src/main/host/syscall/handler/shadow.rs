@@ -1,7 +1,9 @@
 use linux_api::errno::Errno;
+use linux_api::syscall::SyscallNum;
 use shadow_shim_helper_rs::syscall_types::ForeignPtr;
 
 use crate::core::worker::Worker;
+use crate::host::syscall::formatter::{StraceFormat, SyscallResultRecord, write_syscall_json};
 use crate::host::syscall::handler::{SyscallContext, SyscallHandler};
 use crate::host::syscall::types::ForeignArrayPtr;
 use crate::utility::case_insensitive_eq;
@@ -12,6 +14,56 @@ impl SyscallHandler {
         Ok(())
     }
 
+    /// Writes syscall `number`, which the shim handled without Shadow, to the JSON strace log.
+    /// This syscall isn't logged itself.
+    pub fn shadow_log_syscall(
+        ctx: &mut SyscallContext,
+        number: i64,
+        rv: i64,
+        native: u64,
+    ) -> Result<(), Errno> {
+        let is_json = ctx
+            .objs
+            .process
+            .strace_logging_options()
+            .is_some_and(|x| x.format == StraceFormat::Json);
+        if !is_json {
+            return Ok(());
+        }
+
+        let number = u32::try_from(number).map_err(|_| Errno::EINVAL)?;
+        let name = SyscallNum::new(number)
+            .to_str()
+            .unwrap_or("unknown-syscall");
+        // like the kernel, a return value in [-4095, -1] is a negated errno
+        let errno = (-4095..0)
+            .contains(&rv)
+            .then(|| Errno::try_from(-rv).ok())
+            .flatten();
+        let result = SyscallResultRecord {
+            rv: Some(rv),
+            rv_decoded: Some(rv.to_string()),
+            errno: errno.map(|x| x.to_string()),
+            blocked: false,
+            native: native != 0,
+        };
+
+        ctx.objs.process.with_strace_file(|file| {
+            write_syscall_json(
+                file,
+                &Worker::current_time().unwrap(),
+                ctx.objs.process.id(),
+                ctx.objs.thread.id(),
+                name,
+                None,
+                result,
+            )
+            .unwrap()
+        });
+
+        Ok(())
+    }
+
     log_syscall!(shadow_init_memory_manager, /* rv */ std::ffi::c_int);
     pub fn shadow_init_memory_manager(ctx: &mut SyscallContext) -> Result<(), Errno> {
         if !ctx.objs.host.params.use_mem_mapper {
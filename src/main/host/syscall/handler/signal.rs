@@ -1,8 +1,5 @@
 use linux_api::errno::Errno;
-use linux_api::signal::{
-    LinuxDefaultAction, SigAltStackFlags, SigProcMaskAction, Signal, SignalHandler, defaultaction,
-    siginfo_t,
-};
+use linux_api::signal::{SigAltStackFlags, SigProcMaskAction, Signal, siginfo_t};
 use shadow_shim_helper_rs::explicit_drop::{ExplicitDrop, ExplicitDropper};
 use shadow_shim_helper_rs::syscall_types::ForeignPtr;
 
@@ -170,80 +167,16 @@ impl SyscallHandler {
             return Err(Errno::ENOTSUP);
         }
 
-        // need to scope the shmem lock since `wakeup_for_signal` below takes its own shmem lock
-        let mut cond = {
-            let shmem_lock = &*objs.host.shim_shmem_lock_borrow().unwrap();
+        let target_process = objs
+            .host
+            .process_borrow(target_thread.process_id())
+            .unwrap();
+        let target_process = &*target_process.borrow(objs.host.root());
 
-            let target_process = objs
-                .host
-                .process_borrow(target_thread.process_id())
-                .unwrap();
-            let target_process = &*target_process.borrow(objs.host.root());
+        let sender_pid = objs.process.id();
+        let siginfo = siginfo_t::new_for_tkill(signal, sender_pid.into(), 0);
 
-            let process_shmem = target_process.borrow_as_runnable().unwrap();
-            let process_shmem = &*process_shmem.shmem();
-            let process_protected = process_shmem.protected.borrow(&shmem_lock.root);
-
-            let thread_shmem = target_thread.shmem();
-            let mut thread_protected = thread_shmem.protected.borrow_mut(&shmem_lock.root);
-
-            let action = unsafe { process_protected.signal_action(signal) };
-            let action_handler = unsafe { action.handler() };
-
-            let signal_is_ignored = match action_handler {
-                SignalHandler::SigIgn => true,
-                SignalHandler::SigDfl => defaultaction(signal) == LinuxDefaultAction::IGN,
-                _ => false,
-            };
-
-            if signal_is_ignored {
-                // don't deliver an ignored signal
-                return Ok(());
-            }
-
-            if thread_protected.pending_signals.has(signal) {
-                // Signal is already pending. From signal(7): In the case where a standard signal is
-                // already pending, the siginfo_t structure (see sigaction(2)) associated with that
-                // signal is not overwritten on arrival of subsequent instances of the same signal.
-                return Ok(());
-            }
-
-            thread_protected.pending_signals.add(signal);
-
-            let sender_pid = objs.process.id();
-            let sender_tid = objs.thread.id();
-
-            let siginfo = siginfo_t::new_for_tkill(signal, sender_pid.into(), 0);
-
-            thread_protected.set_pending_standard_siginfo(signal, &siginfo);
-
-            if sender_tid == target_thread.id() {
-                // Target is the current thread. It'll be handled synchronously when the current
-                // syscall returns (if it's unblocked).
-                return Ok(());
-            }
-
-            if thread_protected.blocked_signals.has(signal) {
-                // Target thread has the signal blocked. We'll leave it pending, but no need to
-                // schedule an event to process the signal. It'll get processed synchronously when
-                // the thread executes a syscall that would unblock the signal.
-                return Ok(());
-            }
-
-            let Some(cond) = target_thread.syscall_condition_mut() else {
-                // We may be able to get here if a thread is signalled before it runs for the first
-                // time. Just return; the signal will be delivered when the thread runs.
-                return Ok(());
-            };
-
-            cond
-        };
-
-        let was_scheduled = cond.wakeup_for_signal(objs.host, signal);
-
-        // it won't be scheduled if the signal is blocked, but we previously checked if the signal
-        // was blocked above
-        assert!(was_scheduled);
+        target_thread.signal(objs.host, target_process, Some(objs.thread), &siginfo);
 
         Ok(())
     }
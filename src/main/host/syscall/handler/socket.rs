@@ -6,10 +6,10 @@ use nix::sys::socket::SockFlag;
 use shadow_shim_helper_rs::syscall_types::ForeignPtr;
 
 use crate::host::descriptor::descriptor_table::DescriptorHandle;
-use crate::host::descriptor::socket::inet::InetSocket;
 use crate::host::descriptor::socket::inet::legacy_tcp::LegacyTcpSocket;
 use crate::host::descriptor::socket::inet::tcp::TcpSocket;
 use crate::host::descriptor::socket::inet::udp::UdpSocket;
+use crate::host::descriptor::socket::inet::{InetFamily, InetSocket};
 use crate::host::descriptor::socket::netlink::{NetlinkFamily, NetlinkSocket, NetlinkSocketType};
 use crate::host::descriptor::socket::unix::{UnixSocket, UnixSocketType};
 use crate::host::descriptor::socket::{RecvmsgArgs, RecvmsgReturn, SendmsgArgs, Socket};
@@ -75,37 +75,46 @@ impl SyscallHandler {
                     &ctx.objs.host.abstract_unix_namespace(),
                 ))
             }
-            libc::AF_INET => match socket_type {
-                libc::SOCK_STREAM => {
-                    if protocol != 0 && protocol != libc::IPPROTO_TCP {
-                        log::debug!("Unsupported inet stream socket protocol {protocol}");
-                        return Err(Errno::EPROTONOSUPPORT);
+            libc::AF_INET | libc::AF_INET6 => {
+                let socket = match socket_type {
+                    libc::SOCK_STREAM => {
+                        if protocol != 0 && protocol != libc::IPPROTO_TCP {
+                            log::debug!("Unsupported inet stream socket protocol {protocol}");
+                            return Err(Errno::EPROTONOSUPPORT);
+                        }
+
+                        if ctx.objs.host.params.use_new_tcp {
+                            InetSocket::Tcp(TcpSocket::new(file_flags))
+                        } else {
+                            InetSocket::LegacyTcp(LegacyTcpSocket::new(file_flags, ctx.objs.host))
+                        }
                     }
-
-                    if ctx.objs.host.params.use_new_tcp {
-                        Socket::Inet(InetSocket::Tcp(TcpSocket::new(file_flags)))
-                    } else {
-                        Socket::Inet(InetSocket::LegacyTcp(LegacyTcpSocket::new(
+                    libc::SOCK_DGRAM => {
+                        if protocol != 0 && protocol != libc::IPPROTO_UDP {
+                            log::debug!("Unsupported inet dgram socket protocol {protocol}");
+                            return Err(Errno::EPROTONOSUPPORT);
+                        }
+                        let send_buf_size = ctx.objs.host.params.init_sock_send_buf_size;
+                        let recv_buf_size = ctx.objs.host.params.init_sock_recv_buf_size;
+                        InetSocket::Udp(UdpSocket::new(
                             file_flags,
-                            ctx.objs.host,
-                        )))
-                    }
-                }
-                libc::SOCK_DGRAM => {
-                    if protocol != 0 && protocol != libc::IPPROTO_UDP {
-                        log::debug!("Unsupported inet dgram socket protocol {protocol}");
-                        return Err(Errno::EPROTONOSUPPORT);
+                            send_buf_size.try_into().unwrap(),
+                            recv_buf_size.try_into().unwrap(),
+                        ))
                     }
-                    let send_buf_size = ctx.objs.host.params.init_sock_send_buf_size;
-                    let recv_buf_size = ctx.objs.host.params.init_sock_recv_buf_size;
-                    Socket::Inet(InetSocket::Udp(UdpSocket::new(
-                        file_flags,
-                        send_buf_size.try_into().unwrap(),
-                        recv_buf_size.try_into().unwrap(),
-                    )))
+                    _ => return Err(Errno::ESOCKTNOSUPPORT),
+                };
+
+                // ipv6 sockets are dual-stack by default, as with linux's default
+                // "net.ipv6.bindv6only" sysctl
+                if domain == libc::AF_INET6 {
+                    socket
+                        .borrow_mut()
+                        .set_family(InetFamily::Ipv6 { v6only: false });
                 }
-                _ => return Err(Errno::ESOCKTNOSUPPORT),
-            },
+
+                Socket::Inet(socket)
+            }
             libc::AF_NETLINK => {
                 let socket_type = match NetlinkSocketType::try_from(socket_type) {
                     Ok(x) => x,
@@ -244,6 +253,8 @@ impl SyscallHandler {
             iovs: &[iov],
             control_ptr: ForeignArrayPtr::new(ForeignPtr::null(), 0),
             flags,
+            descriptor_table: None,
+            credentials: ctx.objs.process.credentials(),
         };
 
         // call the socket's sendmsg(), and run any resulting events
@@ -307,6 +318,8 @@ impl SyscallHandler {
         let mut rng = ctx.objs.host.random_mut();
         let net_ns = ctx.objs.host.network_namespace_borrow();
 
+        let desc_table = ctx.objs.thread.descriptor_table_borrow(ctx.objs.host);
+
         let msg = io::read_msghdr(&mem, msg_ptr)?;
 
         let args = SendmsgArgs {
@@ -315,6 +328,8 @@ impl SyscallHandler {
             control_ptr: ForeignArrayPtr::new(msg.control, msg.control_len),
             // note: "the msg_flags field is ignored" for sendmsg; see send(2)
             flags,
+            descriptor_table: (!msg.control.is_null()).then_some(&*desc_table),
+            credentials: ctx.objs.process.credentials(),
         };
 
         // call the socket's sendmsg(), and run any resulting events
@@ -392,6 +407,7 @@ impl SyscallHandler {
             iovs: &[iov],
             control_ptr: ForeignArrayPtr::new(ForeignPtr::null(), 0),
             flags,
+            descriptor_table: None,
         };
 
         // call the socket's recvmsg(), and run any resulting events
@@ -462,12 +478,16 @@ impl SyscallHandler {
 
         let mut mem = ctx.objs.process.memory_borrow_mut();
 
+        let mut desc_table = ctx.objs.thread.descriptor_table_borrow_mut(ctx.objs.host);
+
         let mut msg = io::read_msghdr(&mem, msg_ptr)?;
 
         let args = RecvmsgArgs {
             iovs: &msg.iovs,
             control_ptr: ForeignArrayPtr::new(msg.control, msg.control_len),
             flags,
+            // any descriptors received in the control data are added to this table
+            descriptor_table: (!msg.control.is_null()).then_some(&mut *desc_table),
         };
 
         // call the socket's recvmsg(), and run any resulting events
@@ -1,13 +1,17 @@
 use linux_api::errno::Errno;
-use linux_api::time::{ClockId, ClockNanosleepFlags, ITimerId};
+use linux_api::signal::{SigevNotify, Signal};
+use linux_api::time::{AdjtimexModes, ClockId, ClockNanosleepFlags, ITimerId, TimerSettimeFlags};
 use log::*;
 use shadow_shim_helper_rs::emulated_time::EmulatedTime;
+use shadow_shim_helper_rs::host_clock::ClockKind;
 use shadow_shim_helper_rs::simulation_time::SimulationTime;
 use shadow_shim_helper_rs::syscall_types::ForeignPtr;
 
 use crate::core::worker::Worker;
+use crate::host::posix_timer::PosixTimerNotify;
 use crate::host::syscall::handler::{SyscallContext, SyscallHandler};
 use crate::host::syscall::types::SyscallError;
+use crate::host::thread::ThreadId;
 use crate::host::timer::Timer;
 
 fn kernel_old_itimerval_from_timer(timer: &Timer) -> linux_api::time::kernel_old_itimerval {
@@ -163,6 +167,282 @@ impl SyscallHandler {
         Ok(prev_remaining_secs)
     }
 
+    log_syscall!(
+        timer_create,
+        /* rv */ std::ffi::c_int,
+        /* clockid */ linux_api::time::ClockId,
+        /* sevp */ *const linux_api::signal::sigevent,
+        /* timerid */ *const linux_api::posix_types::kernel_timer_t,
+    );
+    pub fn timer_create(
+        ctx: &mut SyscallContext,
+        clockid: linux_api::time::linux___kernel_clockid_t,
+        sevp_ptr: ForeignPtr<linux_api::signal::sigevent>,
+        timerid_ptr: ForeignPtr<linux_api::posix_types::kernel_timer_t>,
+    ) -> Result<(), SyscallError> {
+        let Ok(clockid) = ClockId::try_from(clockid) else {
+            debug!("Invalid clockid: {clockid}");
+            return Err(Errno::EINVAL.into());
+        };
+
+        if !matches!(
+            clockid,
+            ClockId::CLOCK_REALTIME | ClockId::CLOCK_MONOTONIC | ClockId::CLOCK_BOOTTIME
+        ) {
+            warn_once_then_debug!("Unsupported clockid {clockid:?}");
+            return Err(Errno::EINVAL.into());
+        }
+
+        let (notify, value) = if sevp_ptr.is_null() {
+            // timer_create(2): Specifying sevp as NULL is equivalent to specifying a pointer to a
+            // sigevent structure in which sigev_notify is SIGEV_SIGNAL, sigev_signo is SIGALRM,
+            // and sigev_value.sival_int is the timer ID.
+            (PosixTimerNotify::Process(Signal::SIGALRM), None)
+        } else {
+            let sev = ctx.objs.process.memory_borrow().read(sevp_ptr)?;
+            let notify = Self::posix_timer_notify(ctx, &sev)?;
+            // SAFETY: Both members of the union are plain data, and reading the pointer member
+            // covers the whole union.
+            let value = ForeignPtr::from_raw_ptr(unsafe { sev.sigev_value.sival_ptr }).cast::<()>();
+            (notify, Some(value))
+        };
+
+        let pid = ctx.objs.process.id();
+        let id = ctx
+            .objs
+            .process
+            .posix_timers_borrow_mut()
+            .add(pid, clockid, notify, value)?;
+
+        if let Err(e) = ctx.objs.process.memory_borrow_mut().write(timerid_ptr, &id) {
+            ctx.objs.process.posix_timers_borrow_mut().remove(id);
+            return Err(e.into());
+        }
+
+        Ok(())
+    }
+
+    /// Validates the notification requested by `sev`.
+    fn posix_timer_notify(
+        ctx: &SyscallContext,
+        sev: &linux_api::signal::sigevent,
+    ) -> Result<PosixTimerNotify, Errno> {
+        let Ok(notify) = sev.notify() else {
+            debug!("Invalid sigev_notify: {}", sev.sigev_notify);
+            return Err(Errno::EINVAL);
+        };
+
+        if notify == SigevNotify::SIGEV_NONE {
+            return Ok(PosixTimerNotify::None);
+        }
+
+        let signal = match Signal::try_from(sev.sigev_signo) {
+            Ok(signal) => signal,
+            Err(_) => {
+                debug!("Invalid sigev_signo: {}", sev.sigev_signo);
+                return Err(Errno::EINVAL);
+            }
+        };
+
+        // Realtime signals aren't supported by Shadow. This includes glibc's `SIGEV_THREAD` timers,
+        // which glibc creates as `SIGEV_THREAD_ID` timers that notify a helper thread with
+        // `SIGRTMIN`.
+        if signal.is_realtime() {
+            warn_once_then_debug!(
+                "Unimplemented realtime signal {signal:?} for POSIX timer (glibc's SIGEV_THREAD \
+                timers use realtime signals)"
+            );
+            return Err(Errno::ENOTSUP);
+        }
+
+        match notify {
+            SigevNotify::SIGEV_NONE => unreachable!(),
+            // The kernel treats `SIGEV_THREAD` like `SIGEV_SIGNAL`, since the notification thread
+            // is implemented by libc. glibc never passes `SIGEV_THREAD` to the kernel, but other
+            // libcs might.
+            SigevNotify::SIGEV_SIGNAL | SigevNotify::SIGEV_THREAD => {
+                Ok(PosixTimerNotify::Process(signal))
+            }
+            SigevNotify::SIGEV_THREAD_ID => {
+                // The target must be a thread in the calling process.
+                let tid = ThreadId::try_from(sev.sigev_notify_thread_id).or(Err(Errno::EINVAL))?;
+                if ctx.objs.process.thread_borrow(tid).is_none() {
+                    debug!("Thread {tid} is not in the calling process");
+                    return Err(Errno::EINVAL);
+                }
+                Ok(PosixTimerNotify::Thread(signal, tid))
+            }
+        }
+    }
+
+    log_syscall!(
+        timer_settime,
+        /* rv */ std::ffi::c_int,
+        /* timerid */ linux_api::posix_types::kernel_timer_t,
+        /* flags */ std::ffi::c_int,
+        /* new_value */ *const linux_api::time::itimerspec,
+        /* old_value */ *const linux_api::time::itimerspec,
+    );
+    pub fn timer_settime(
+        ctx: &mut SyscallContext,
+        timerid: linux_api::posix_types::kernel_timer_t,
+        flags: std::ffi::c_int,
+        new_value_ptr: ForeignPtr<linux_api::time::itimerspec>,
+        old_value_ptr: ForeignPtr<linux_api::time::itimerspec>,
+    ) -> Result<(), SyscallError> {
+        // Linux ignores unknown flags.
+        let flags = TimerSettimeFlags::from_bits_truncate(flags);
+
+        let new_value = ctx.objs.process.memory_borrow().read(new_value_ptr)?;
+        let value = SimulationTime::try_from(new_value.it_value).or(Err(Errno::EINVAL))?;
+        let interval = SimulationTime::try_from(new_value.it_interval).or(Err(Errno::EINVAL))?;
+
+        if ctx
+            .objs
+            .process
+            .posix_timers_borrow()
+            .get(timerid)
+            .is_none()
+        {
+            return Err(Errno::EINVAL.into());
+        }
+
+        if !old_value_ptr.is_null() {
+            // The old value is always relative, even if TIMER_ABSTIME is set.
+            Self::timer_gettime_helper(ctx, timerid, old_value_ptr)?;
+        }
+
+        let host = ctx.objs.host;
+        let mut timers = ctx.objs.process.posix_timers_borrow_mut();
+        let timer = timers.get_mut(timerid).unwrap();
+        timer.reset_overrun();
+
+        if value.is_zero() {
+            // A value of 0 disarms the timer; it_interval is ignored.
+            timer.timer_mut().disarm();
+            return Ok(());
+        }
+
+        let now = Worker::current_time().unwrap();
+        let abstime = flags.contains(TimerSettimeFlags::TIMER_ABSTIME);
+        let clock_kind = ClockKind::from(timer.clockid());
+
+        let expire_time = {
+            // The value is measured by the host's (possibly skewed) clock.
+            let clock = &host.shim_shmem().clock;
+            let base = match abstime {
+                true => EmulatedTime::UNIX_EPOCH,
+                false => clock.local_time(clock_kind, now),
+            };
+            clock.true_time(clock_kind, base + value)
+        };
+        let interval = interval.is_positive().then_some(interval);
+
+        // timer_settime(2): If the specified absolute time has already passed, then the timer
+        // expires immediately, and the overrun count is set correctly. The intervals that have
+        // already elapsed are overruns of that first expiration.
+        let expire_time = match interval {
+            Some(interval) if expire_time < now => {
+                let missed = now.duration_since(&expire_time).as_nanos() / interval.as_nanos();
+                timer.set_missed_intervals(i32::try_from(missed).unwrap_or(i32::MAX));
+                // Arm with the last start of an interval that isn't in the future, so that the
+                // timer expires now and then stays on its schedule.
+                let missed = u64::try_from(missed).unwrap();
+                expire_time + interval.checked_mul(missed).unwrap()
+            }
+            _ => EmulatedTime::max(expire_time, now),
+        };
+
+        if abstime && clock_kind == ClockKind::Realtime {
+            timer
+                .timer_mut()
+                .arm_realtime_abs(host, expire_time, interval);
+        } else {
+            timer.timer_mut().arm(host, expire_time, interval);
+        }
+
+        Ok(())
+    }
+
+    log_syscall!(
+        timer_gettime,
+        /* rv */ std::ffi::c_int,
+        /* timerid */ linux_api::posix_types::kernel_timer_t,
+        /* curr_value */ *const linux_api::time::itimerspec,
+    );
+    pub fn timer_gettime(
+        ctx: &mut SyscallContext,
+        timerid: linux_api::posix_types::kernel_timer_t,
+        curr_value_ptr: ForeignPtr<linux_api::time::itimerspec>,
+    ) -> Result<(), SyscallError> {
+        Self::timer_gettime_helper(ctx, timerid, curr_value_ptr)?;
+        Ok(())
+    }
+
+    fn timer_gettime_helper(
+        ctx: &mut SyscallContext,
+        timerid: linux_api::posix_types::kernel_timer_t,
+        value_ptr: ForeignPtr<linux_api::time::itimerspec>,
+    ) -> Result<(), Errno> {
+        let (remaining, interval) = {
+            let timers = ctx.objs.process.posix_timers_borrow();
+            let Some(timer) = timers.get(timerid) else {
+                return Err(Errno::EINVAL);
+            };
+            let timer = timer.timer();
+            (
+                timer.remaining_time().unwrap_or(SimulationTime::ZERO),
+                timer.expire_interval().unwrap_or(SimulationTime::ZERO),
+            )
+        };
+
+        // The remaining time is measured by the host's (possibly skewed) clock.
+        let remaining = ctx.objs.host.shim_shmem().clock.local_duration(remaining);
+
+        let result = linux_api::time::itimerspec {
+            it_value: remaining.try_into().unwrap(),
+            it_interval: interval.try_into().unwrap(),
+        };
+        ctx.objs
+            .process
+            .memory_borrow_mut()
+            .write(value_ptr, &result)?;
+
+        Ok(())
+    }
+
+    log_syscall!(
+        timer_getoverrun,
+        /* rv */ std::ffi::c_int,
+        /* timerid */ linux_api::posix_types::kernel_timer_t,
+    );
+    pub fn timer_getoverrun(
+        ctx: &mut SyscallContext,
+        timerid: linux_api::posix_types::kernel_timer_t,
+    ) -> Result<std::ffi::c_int, SyscallError> {
+        let timers = ctx.objs.process.posix_timers_borrow();
+        let Some(timer) = timers.get(timerid) else {
+            return Err(Errno::EINVAL.into());
+        };
+        Ok(timer.overrun())
+    }
+
+    log_syscall!(
+        timer_delete,
+        /* rv */ std::ffi::c_int,
+        /* timerid */ linux_api::posix_types::kernel_timer_t,
+    );
+    pub fn timer_delete(
+        ctx: &mut SyscallContext,
+        timerid: linux_api::posix_types::kernel_timer_t,
+    ) -> Result<(), SyscallError> {
+        // Dropping the timer cancels any future expirations.
+        let Some(_timer) = ctx.objs.process.posix_timers_borrow_mut().remove(timerid) else {
+            return Err(Errno::EINVAL.into());
+        };
+        Ok(())
+    }
+
     log_syscall!(
         clock_getres,
         /* rv */ std::ffi::c_int,
@@ -189,6 +469,188 @@ impl SyscallHandler {
         Ok(())
     }
 
+    log_syscall!(
+        clock_settime,
+        /* rv */ std::ffi::c_int,
+        /* clock_id */ linux_api::time::ClockId,
+        /* tp */ *const linux_api::time::timespec,
+    );
+    pub fn clock_settime(
+        ctx: &mut SyscallContext,
+        clock_id: linux_api::time::linux___kernel_clockid_t,
+        tp_ptr: ForeignPtr<linux_api::time::timespec>,
+    ) -> Result<(), SyscallError> {
+        let clock_id = ClockId::try_from(clock_id).map_err(|_| Errno::EINVAL)?;
+
+        // Linux only supports setting the realtime clock.
+        if clock_id != ClockId::CLOCK_REALTIME {
+            debug!("Can't set clock {clock_id:?}");
+            return Err(Errno::EINVAL.into());
+        }
+
+        let tp = ctx.objs.process.memory_borrow().read(tp_ptr)?;
+        let t = SimulationTime::try_from(tp).or(Err(Errno::EINVAL))?;
+
+        ctx.objs
+            .host
+            .set_realtime_clock(EmulatedTime::UNIX_EPOCH + t);
+
+        Ok(())
+    }
+
+    log_syscall!(
+        settimeofday,
+        /* rv */ std::ffi::c_int,
+        /* tv */ *const linux_api::time::kernel_old_timeval,
+        /* tz */ *const std::ffi::c_void,
+    );
+    pub fn settimeofday(
+        ctx: &mut SyscallContext,
+        tv_ptr: ForeignPtr<linux_api::time::kernel_old_timeval>,
+        tz_ptr: ForeignPtr<std::ffi::c_void>,
+    ) -> Result<(), SyscallError> {
+        let tv = if tv_ptr.is_null() {
+            None
+        } else {
+            let tv = ctx.objs.process.memory_borrow().read(tv_ptr)?;
+            Some(SimulationTime::try_from(tv).or(Err(Errno::EINVAL))?)
+        };
+
+        if !tz_ptr.is_null() {
+            // The kernel timezone is obsolete, and isn't used by glibc.
+            warn_once_then_debug!("Ignoring timezone passed to settimeofday");
+        }
+
+        if let Some(tv) = tv {
+            ctx.objs
+                .host
+                .set_realtime_clock(EmulatedTime::UNIX_EPOCH + tv);
+        }
+
+        Ok(())
+    }
+
+    log_syscall!(
+        adjtimex,
+        /* rv */ std::ffi::c_int,
+        /* buf */ *const linux_api::time::timex,
+    );
+    pub fn adjtimex(
+        ctx: &mut SyscallContext,
+        buf_ptr: ForeignPtr<linux_api::time::timex>,
+    ) -> Result<std::ffi::c_int, SyscallError> {
+        Self::adjtimex_helper(ctx, buf_ptr)
+    }
+
+    log_syscall!(
+        clock_adjtime,
+        /* rv */ std::ffi::c_int,
+        /* clock_id */ linux_api::time::ClockId,
+        /* buf */ *const linux_api::time::timex,
+    );
+    pub fn clock_adjtime(
+        ctx: &mut SyscallContext,
+        clock_id: linux_api::time::linux___kernel_clockid_t,
+        buf_ptr: ForeignPtr<linux_api::time::timex>,
+    ) -> Result<std::ffi::c_int, SyscallError> {
+        let clock_id = ClockId::try_from(clock_id).map_err(|_| Errno::EINVAL)?;
+
+        // Linux only supports adjusting the realtime clock.
+        if clock_id != ClockId::CLOCK_REALTIME {
+            debug!("Can't adjust clock {clock_id:?}");
+            return Err(Errno::EOPNOTSUPP.into());
+        }
+
+        Self::adjtimex_helper(ctx, buf_ptr)
+    }
+
+    fn adjtimex_helper(
+        ctx: &mut SyscallContext,
+        buf_ptr: ForeignPtr<linux_api::time::timex>,
+    ) -> Result<std::ffi::c_int, SyscallError> {
+        let mut txc = ctx.objs.process.memory_borrow().read(buf_ptr)?;
+        let modes = AdjtimexModes::from_bits_truncate(txc.modes);
+
+        // The old `adjtime` interface can't be combined with other modes.
+        let singleshot = modes.contains(AdjtimexModes::ADJ_OFFSET_SINGLESHOT);
+        if singleshot
+            && modes != AdjtimexModes::ADJ_OFFSET_SINGLESHOT
+            && modes != AdjtimexModes::ADJ_OFFSET_SS_READ
+        {
+            return Err(Errno::EINVAL.into());
+        }
+
+        let mut ntp = ctx.objs.host.ntp_borrow_mut();
+
+        // A step of the realtime clock, in nanoseconds.
+        let mut step_nanos: i64 = 0;
+
+        if modes.contains(AdjtimexModes::ADJ_SETOFFSET) {
+            let nano = modes.contains(AdjtimexModes::ADJ_NANO);
+            let usec_limit = if nano { 1_000_000_000 } else { 1_000_000 };
+            if !(0..usec_limit).contains(&txc.time.tv_usec) {
+                return Err(Errno::EINVAL.into());
+            }
+            let subsec_nanos = if nano {
+                txc.time.tv_usec
+            } else {
+                txc.time.tv_usec * 1000
+            };
+            step_nanos = txc
+                .time
+                .tv_sec
+                .checked_mul(1_000_000_000)
+                .and_then(|x| x.checked_add(subsec_nanos))
+                .ok_or(Errno::EINVAL)?;
+        }
+
+        if modes == AdjtimexModes::ADJ_OFFSET_SINGLESHOT
+            || (!singleshot && modes.contains(AdjtimexModes::ADJ_OFFSET))
+        {
+            // The kernel would gradually slew the clock by this offset. We don't model
+            // slewing, so we step the clock instead. The kernel limits offsets to 0.5 s.
+            let nano = !singleshot && (ntp.nano() || modes.contains(AdjtimexModes::ADJ_NANO));
+            let offset_nanos = if nano {
+                txc.offset.clamp(-500_000_000, 500_000_000)
+            } else {
+                txc.offset.clamp(-500_000, 500_000) * 1000
+            };
+            step_nanos = step_nanos.saturating_add(offset_nanos);
+        }
+
+        if !singleshot {
+            ntp.update(modes, &txc);
+        }
+
+        if step_nanos != 0 {
+            debug!("adjtimex stepping realtime clock by {step_nanos} ns");
+            ctx.objs.host.step_realtime_clock(step_nanos);
+        }
+
+        // Write back the current state.
+        let now = Worker::current_time().unwrap();
+        let realtime = ctx
+            .objs
+            .host
+            .shim_shmem()
+            .clock
+            .local_time(ClockKind::Realtime, now)
+            .duration_since(&EmulatedTime::UNIX_EPOCH);
+        ntp.fill(&mut txc);
+        txc.time.tv_sec = realtime.as_secs().try_into().unwrap();
+        txc.time.tv_usec = if ntp.nano() {
+            realtime.subsec_nanos().into()
+        } else {
+            realtime.subsec_micros().into()
+        };
+        let clock_state = ntp.clock_state();
+        drop(ntp);
+
+        ctx.objs.process.memory_borrow_mut().write(buf_ptr, &txc)?;
+
+        Ok(clock_state)
+    }
+
     log_syscall!(
         clock_nanosleep,
         /* rv */ std::ffi::c_int,
@@ -220,7 +682,14 @@ impl SyscallHandler {
             // Simulated in Shadow; Linux allows unspec bitflags, but not for the *ALARM clocks.
             let allow_unspec_bitflags =
                 ![ClockId::CLOCK_REALTIME_ALARM, ClockId::CLOCK_BOOTTIME_ALARM].contains(&clock_id);
-            Self::nanosleep_helper(ctx, flags, request_ptr, remain_ptr, allow_unspec_bitflags)
+            Self::nanosleep_helper(
+                ctx,
+                clock_id,
+                flags,
+                request_ptr,
+                remain_ptr,
+                allow_unspec_bitflags,
+            )
         } else if [ClockId::CLOCK_THREAD_CPUTIME_ID].contains(&clock_id) {
             // Invalid in Linux.
             log::debug!("Invalid clock id {clock_id:?}.",);
@@ -256,11 +725,12 @@ impl SyscallHandler {
         req: ForeignPtr<linux_api::time::timespec>,
         rem: ForeignPtr<linux_api::time::timespec>,
     ) -> Result<(), SyscallError> {
-        Self::nanosleep_helper(ctx, 0, req, rem, false)
+        Self::nanosleep_helper(ctx, ClockId::CLOCK_MONOTONIC, 0, req, rem, false)
     }
 
     fn nanosleep_helper(
         ctx: &mut SyscallContext,
+        clock_id: ClockId,
         flags: std::ffi::c_int,
         request_ptr: ForeignPtr<linux_api::time::timespec>,
         remain_ptr: ForeignPtr<linux_api::time::timespec>,
@@ -275,12 +745,18 @@ impl SyscallHandler {
         };
 
         let now = Worker::current_time().unwrap();
+        let clock = &ctx.objs.host.shim_shmem().clock;
+        let clock_kind = ClockKind::from(clock_id);
+
+        let abstime = flags.contains(ClockNanosleepFlags::TIMER_ABSTIME);
 
-        // The requested wakeup time may be absolute or relative.
-        let abs_wakeup_time = if flags.contains(ClockNanosleepFlags::TIMER_ABSTIME) {
-            EmulatedTime::UNIX_EPOCH + request_time
+        // The requested wakeup time may be absolute or relative, and is measured by the host's
+        // (possibly skewed) clock rather than the simulation time.
+        let abs_wakeup_time = if abstime {
+            clock.true_time(clock_kind, EmulatedTime::UNIX_EPOCH + request_time)
         } else {
-            now + request_time
+            let local_now = clock.local_time(clock_kind, now);
+            clock.true_time(clock_kind, local_now + request_time)
         };
 
         // A wakeup time in the past means we return without sleeping.
@@ -291,20 +767,33 @@ impl SyscallHandler {
         // Condition will exist after a wakeup.
         let Some(cond) = ctx.objs.thread.syscall_condition() else {
             // Didn't sleep yet; block the thread now.
-            return Err(SyscallError::new_blocked_until(abs_wakeup_time, false));
+            let mut err = SyscallError::new_blocked_until(abs_wakeup_time, false);
+            if abstime && clock_kind == ClockKind::Realtime {
+                // Like an absolute timer, wake up when the realtime clock reaches the requested
+                // time even if the clock is stepped while sleeping.
+                err.blocked_condition().unwrap().set_timeout_realtime_abs();
+            }
+            return Err(err);
         };
 
-        // Woke up from sleep. We must have set a timeout to sleep.
-        let expected_wakeup_time = cond.timeout().unwrap();
+        // Woke up from sleep. We must have set a timeout to sleep. An absolute wakeup time was
+        // recomputed above, so it accounts for any steps of the clock while sleeping.
+        let expected_wakeup_time = match abstime {
+            true => abs_wakeup_time,
+            false => cond.timeout().unwrap(),
+        };
 
         if expected_wakeup_time <= now {
             // Successful sleep and wakeup!
             Ok(())
         } else {
             // Possibly write out the remaining time until the expected wakeup.
-            if !remain_ptr.is_null() && !flags.contains(ClockNanosleepFlags::TIMER_ABSTIME) {
-                let remain_time =
-                    linux_api::time::timespec::try_from(expected_wakeup_time - now).unwrap();
+            if !remain_ptr.is_null() && !abstime {
+                let clock = &ctx.objs.host.shim_shmem().clock;
+                let remain_time = linux_api::time::timespec::try_from(
+                    clock.local_duration(expected_wakeup_time - now),
+                )
+                .unwrap();
                 ctx.objs
                     .process
                     .memory_borrow_mut()
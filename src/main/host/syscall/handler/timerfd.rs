@@ -6,7 +6,8 @@ use linux_api::fcntl::DescriptorFlags;
 use linux_api::time::{ClockId, itimerspec};
 use nix::sys::timerfd::{TimerFlags, TimerSetTimeFlags};
 use shadow_shim_helper_rs::{
-    emulated_time::EmulatedTime, simulation_time::SimulationTime, syscall_types::ForeignPtr,
+    emulated_time::EmulatedTime, host_clock::ClockKind, simulation_time::SimulationTime,
+    syscall_types::ForeignPtr,
 };
 
 use crate::core::worker::Worker;
@@ -56,7 +57,7 @@ impl SyscallHandler {
             desc_flags.insert(DescriptorFlags::FD_CLOEXEC);
         }
 
-        let file = TimerFd::new(file_flags);
+        let file = TimerFd::new(clockid, file_flags);
         let mut desc = Descriptor::new(CompatFile::New(OpenFile::new(File::TimerFd(file))));
         desc.set_flags(desc_flags);
 
@@ -181,15 +182,19 @@ impl SyscallHandler {
         } else {
             // Need to arm the timer, value may be absolute or relative.
             let now = Worker::current_time().unwrap();
+            let abstime = flags.contains(TimerSetTimeFlags::TFD_TIMER_ABSTIME);
 
             let expire_time = {
-                let base = match flags.contains(TimerSetTimeFlags::TFD_TIMER_ABSTIME) {
+                // The value is measured by the host's (possibly skewed) clock.
+                let clock = &ctx.objs.host.shim_shmem().clock;
+                let clock_kind = ClockKind::from(timerfd.borrow().clockid());
+                let base = match abstime {
                     true => EmulatedTime::UNIX_EPOCH,
-                    false => now,
+                    false => clock.local_time(clock_kind, now),
                 };
                 // The man page does not specify what happens if the configured time is in the past.
                 // On Linux, the result is an immediate timer expiration.
-                EmulatedTime::max(base + value, now)
+                EmulatedTime::max(clock.true_time(clock_kind, base + value), now)
             };
 
             CallbackQueue::queue_and_run_with_legacy(|cb_queue| {
@@ -197,6 +202,7 @@ impl SyscallHandler {
                     ctx.objs.host,
                     expire_time,
                     interval.is_positive().then_some(interval),
+                    abstime,
                     cb_queue,
                 );
             });
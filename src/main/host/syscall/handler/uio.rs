@@ -252,6 +252,7 @@ impl SyscallHandler {
                 iovs,
                 control_ptr: ForeignArrayPtr::new(ForeignPtr::null(), 0),
                 flags: 0,
+                descriptor_table: None,
             };
 
             // call the socket's recvmsg(), and run any resulting events
@@ -533,6 +534,8 @@ impl SyscallHandler {
                 iovs,
                 control_ptr: ForeignArrayPtr::new(ForeignPtr::null(), 0),
                 flags: 0,
+                descriptor_table: None,
+                credentials: ctx.objs.process.credentials(),
             };
 
             // call the socket's sendmsg(), and run any resulting events
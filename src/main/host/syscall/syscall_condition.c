@@ -26,6 +26,11 @@ struct _SysCallCondition {
     CEmulatedTime timeoutExpiration;
     // Timeout object waiting for timeoutExpiration.
     Timer* timeout;
+    // Whether timeoutExpiration is an absolute time on the host's realtime
+    // clock. The timeout then follows steps of that clock, and has expired
+    // once its timer has fired rather than at timeoutExpiration.
+    bool timeoutRealtimeAbs;
+    bool timeoutFired;
     // The active file in the blocked syscall. This is state used when resuming a blocked syscall.
     OpenFile* activeFile;
     // Non-null if we are listening for status updates on a trigger object
@@ -110,6 +115,12 @@ void syscallcondition_setTimeout(SysCallCondition* cond, CEmulatedTime t) {
     cond->timeoutExpiration = t;
 }
 
+void syscallcondition_setTimeoutRealtimeAbs(SysCallCondition* cond) {
+    MAGIC_ASSERT(cond);
+
+    cond->timeoutRealtimeAbs = true;
+}
+
 void syscallcondition_setActiveFile(SysCallCondition* cond, OpenFile* file) {
     MAGIC_ASSERT(cond);
 
@@ -293,8 +304,12 @@ static void _syscallcondition_logListeningState(SysCallCondition* cond, const Pr
     }
 
     if (cond->timeoutExpiration != EMUTIME_INVALID) {
-        utility_debugAssert(cond->timeoutExpiration >= worker_getCurrentEmulatedTime());
-        CSimulationTime remainingTime = cond->timeoutExpiration - worker_getCurrentEmulatedTime();
+        CEmulatedTime now = worker_getCurrentEmulatedTime();
+        // A realtime timeout may have been moved past timeoutExpiration by a
+        // step of the clock.
+        utility_debugAssert(cond->timeoutRealtimeAbs || cond->timeoutExpiration >= now);
+        CSimulationTime remainingTime =
+            cond->timeoutExpiration > now ? cond->timeoutExpiration - now : 0;
         g_string_append_printf(string, "a timeout with %llu.%09llu seconds remaining",
                                remainingTime / SIMTIME_ONE_SECOND,
                                (remainingTime % SIMTIME_ONE_SECOND) / SIMTIME_ONE_NANOSECOND);
@@ -341,9 +356,18 @@ static bool _syscallcondition_statusIsValid(SysCallCondition* cond) {
     return false;
 }
 
+static bool _syscallcondition_timedOut(SysCallCondition* cond) {
+    if (cond->timeoutExpiration == EMUTIME_INVALID) {
+        return false;
+    }
+    if (cond->timeoutRealtimeAbs) {
+        return cond->timeoutFired;
+    }
+    return worker_getCurrentEmulatedTime() >= cond->timeoutExpiration;
+}
+
 static bool _syscallcondition_satisfied(SysCallCondition* cond, const Host* host, const Thread* thread) {
-    if (cond->timeoutExpiration != EMUTIME_INVALID &&
-        worker_getCurrentEmulatedTime() >= cond->timeoutExpiration) {
+    if (_syscallcondition_timedOut(cond)) {
         // Timed out.
         return true;
     }
@@ -479,6 +503,8 @@ static void _syscallcondition_notifyTimeoutExpired(const Host* host, void* obj,
     _syscallcondition_logListeningState(cond, proc, "timeout expired while");
 #endif
 
+    cond->timeoutFired = true;
+
     _syscallcondition_scheduleWakeupTask(cond, host);
 }
 
@@ -504,7 +530,12 @@ void syscallcondition_waitNonblock(SysCallCondition* cond, const Host* host, con
             taskref_drop(task);
         }
 
-        timer_arm(cond->timeout, host, cond->timeoutExpiration, 0);
+        cond->timeoutFired = false;
+        if (cond->timeoutRealtimeAbs) {
+            timer_arm_realtime_abs(cond->timeout, host, cond->timeoutExpiration, 0);
+        } else {
+            timer_arm(cond->timeout, host, cond->timeoutExpiration, 0);
+        }
     }
 
     /* Now set up the listeners. */
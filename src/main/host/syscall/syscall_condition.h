@@ -62,6 +62,11 @@ SysCallCondition* syscallcondition_newWithAbsTimeout(CEmulatedTime abs_trigger_t
  * `worker_getCurrentEmulatedTime`. */
 void syscallcondition_setTimeout(SysCallCondition* cond, CEmulatedTime t);
 
+/* Make the timeout an absolute time on the host's realtime clock, so that it
+ * still triggers when that clock reaches the same time if the clock is stepped
+ * (e.g. by `clock_settime`). */
+void syscallcondition_setTimeoutRealtimeAbs(SysCallCondition* cond);
+
 /* Add a file to the condition which can be used in the syscall handler once it becomes unblocked,
  * without needing to lookup the file again in the descriptor table (since it may no longer exist in
  * the descriptor table). */
@@ -204,6 +204,9 @@ deref_pointer_impl!(linux_api::time::timespec);
 deref_pointer_impl!(linux_api::time::kernel_timespec);
 deref_pointer_impl!(linux_api::time::kernel_old_timeval);
 deref_pointer_impl!(linux_api::time::kernel_old_itimerval);
+deref_pointer_impl!(linux_api::time::timex);
+deref_pointer_impl!(linux_api::time::itimerspec);
+deref_pointer_impl!(linux_api::signal::sigevent);
 
 deref_array_impl!(i8, i16, i32, i64, isize);
 deref_array_impl!(u8, u16, u32, u64, usize);
@@ -228,6 +231,8 @@ simple_display_impl!(linux_api::socket::AddressFamily);
 
 bitflags_impl!(linux_api::close_range::CloseRangeFlags);
 bitflags_impl!(linux_api::fcntl::OFlag);
+bitflags_impl!(linux_api::inotify::InotifyInitFlags);
+bitflags_impl!(linux_api::inotify::InotifyMask);
 bitflags_impl!(linux_api::mman::ProtFlags);
 bitflags_impl!(linux_api::mman::MapFlags);
 bitflags_impl!(linux_api::mman::MRemapFlags);
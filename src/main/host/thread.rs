@@ -7,7 +7,9 @@ use linux_api::errno::Errno;
 use linux_api::fcntl::DescriptorFlags;
 use linux_api::mman::{MapFlags, ProtFlags};
 use linux_api::posix_types::Pid;
-use linux_api::signal::stack_t;
+use linux_api::signal::{
+    LinuxDefaultAction, SignalFromI32Error, SignalHandler, defaultaction, siginfo_t, stack_t,
+};
 use shadow_shim_helper_rs::HostId;
 use shadow_shim_helper_rs::explicit_drop::ExplicitDrop;
 use shadow_shim_helper_rs::rootedcell::rc::RootedRc;
@@ -553,6 +555,88 @@ impl Thread {
 
         !(pending_signals & unblocked_signals).is_empty()
     }
+
+    /// Send the signal described in `siginfo` to this thread, which belongs to
+    /// `process`. `current_thread` should be set if there is one (e.g. if this
+    /// is being called from a syscall handler), and `None` otherwise (e.g. when
+    /// called from a timer expiration event).
+    pub fn signal(
+        &self,
+        host: &Host,
+        process: &Process,
+        current_thread: Option<&Thread>,
+        siginfo: &siginfo_t,
+    ) {
+        debug_assert_eq!(process.id(), self.process_id);
+
+        let signal = match siginfo.signal() {
+            Ok(s) => s,
+            Err(SignalFromI32Error(0)) => return,
+            Err(SignalFromI32Error(n)) => panic!("Bad signo {n}"),
+        };
+
+        // need to scope the shmem lock since `wakeup_for_signal` below takes its own shmem lock
+        let mut cond = {
+            let shmem_lock = &*host.shim_shmem_lock_borrow().unwrap();
+
+            let process_shmem = process.shmem();
+            let process_protected = process_shmem.protected.borrow(&shmem_lock.root);
+
+            let thread_shmem = self.shmem();
+            let mut thread_protected = thread_shmem.protected.borrow_mut(&shmem_lock.root);
+
+            let action = unsafe { process_protected.signal_action(signal) };
+            let action_handler = unsafe { action.handler() };
+
+            let signal_is_ignored = match action_handler {
+                SignalHandler::SigIgn => true,
+                SignalHandler::SigDfl => defaultaction(signal) == LinuxDefaultAction::IGN,
+                _ => false,
+            };
+
+            if signal_is_ignored {
+                // don't deliver an ignored signal
+                return;
+            }
+
+            if thread_protected.pending_signals.has(signal) {
+                // Signal is already pending. From signal(7): In the case where a standard signal is
+                // already pending, the siginfo_t structure (see sigaction(2)) associated with that
+                // signal is not overwritten on arrival of subsequent instances of the same signal.
+                return;
+            }
+
+            thread_protected.pending_signals.add(signal);
+            thread_protected.set_pending_standard_siginfo(signal, siginfo);
+
+            if current_thread.is_some_and(|t| t.id() == self.id()) {
+                // Target is the current thread. It'll be handled synchronously when the current
+                // syscall returns (if it's unblocked).
+                return;
+            }
+
+            if thread_protected.blocked_signals.has(signal) {
+                // Target thread has the signal blocked. We'll leave it pending, but no need to
+                // schedule an event to process the signal. It'll get processed synchronously when
+                // the thread executes a syscall that would unblock the signal.
+                return;
+            }
+
+            let Some(cond) = self.syscall_condition_mut() else {
+                // We may be able to get here if a thread is signalled before it runs for the first
+                // time. Just return; the signal will be delivered when the thread runs.
+                return;
+            };
+
+            cond
+        };
+
+        let was_scheduled = cond.wakeup_for_signal(host, signal);
+
+        // it won't be scheduled if the signal is blocked, but we previously checked if the signal
+        // was blocked above
+        assert!(was_scheduled);
+    }
 }
 
 impl Drop for Thread {
@@ -3,6 +3,7 @@ use std::sync::{Arc, Weak};
 use atomic_refcell::AtomicRefCell;
 use log::trace;
 use shadow_shim_helper_rs::emulated_time::EmulatedTime;
+use shadow_shim_helper_rs::host_clock::ClockKind;
 use shadow_shim_helper_rs::simulation_time::SimulationTime;
 
 use super::host::Host;
@@ -27,6 +28,12 @@ struct TimerInternal {
     expiration_count: u64,
     next_expire_id: u64,
     min_valid_expire_id: u64,
+    // Whether `next_expire_time` is an absolute time on the host's realtime clock, and so must
+    // move when that clock is stepped.
+    realtime_abs: bool,
+    // Whether the timer is in its host's `RealtimeTimers`, so that re-arming it doesn't add it
+    // again.
+    realtime_registered: bool,
     on_expire: Box<dyn Fn(&Host) + Send + Sync>,
 }
 
@@ -40,6 +47,85 @@ impl TimerInternal {
         self.expiration_count = 0;
         self.next_expire_time = next_expire_time;
         self.expire_interval = expire_interval;
+        self.realtime_abs = false;
+    }
+}
+
+/// The timers of a host that were armed with an absolute time on the host's realtime clock. See
+/// [`Timer::arm_realtime_abs`].
+#[derive(Default)]
+pub struct RealtimeTimers {
+    timers: Vec<Weak<AtomicRefCell<TimerInternal>>>,
+}
+
+impl RealtimeTimers {
+    /// Add the timer if it isn't already registered. Timers stay registered until the next clock
+    /// step after they're disarmed or dropped.
+    fn register(&mut self, internal: &Arc<AtomicRefCell<TimerInternal>>) {
+        let mut internal_brw = internal.borrow_mut();
+        if !internal_brw.realtime_registered {
+            internal_brw.realtime_registered = true;
+            self.timers.push(Arc::downgrade(internal));
+        }
+    }
+
+    /// Move the expirations of the timers to account for the host's realtime clock having been
+    /// stepped by `delta_nanos`, so that they still expire when the realtime clock reaches the
+    /// same value.
+    pub fn clock_stepped(&mut self, host: &Host, delta_nanos: i64) {
+        let clock = &host.shim_shmem().clock;
+        let now = Worker::current_time().unwrap();
+
+        self.move_expirations(
+            |old_expire_time| {
+                // The realtime clock is the monotonic clock plus an offset, so moving the offset
+                // forward by `delta` means the deadline is reached `delta` earlier on the
+                // monotonic clock.
+                let monotonic = clock.local_time(ClockKind::Monotonic, old_expire_time);
+                let delta = SimulationTime::from_nanos(delta_nanos.unsigned_abs());
+                let monotonic = if delta_nanos >= 0 {
+                    monotonic.saturating_sub(delta)
+                } else {
+                    monotonic.saturating_add(delta)
+                };
+                EmulatedTime::max(clock.true_time(ClockKind::Monotonic, monotonic), now)
+            },
+            |internal, internal_ptr| Timer::schedule_new_expire_event(internal, internal_ptr, host),
+        );
+    }
+
+    /// Move the expiration of each timer that's still armed with a realtime deadline to the time
+    /// returned by `new_expire_time`, and schedule it with `schedule`. Other timers are
+    /// unregistered.
+    fn move_expirations(
+        &mut self,
+        mut new_expire_time: impl FnMut(EmulatedTime) -> EmulatedTime,
+        mut schedule: impl FnMut(&mut TimerInternal, Weak<AtomicRefCell<TimerInternal>>),
+    ) {
+        self.timers.retain(|internal_ptr| {
+            let Some(internal) = Weak::upgrade(internal_ptr) else {
+                return false;
+            };
+            let mut internal_brw = internal.borrow_mut();
+            let old_expire_time = internal_brw
+                .next_expire_time
+                .filter(|_| internal_brw.realtime_abs);
+            let Some(old_expire_time) = old_expire_time else {
+                internal_brw.realtime_registered = false;
+                return false;
+            };
+
+            let expire_time = new_expire_time(old_expire_time);
+            trace!(
+                "realtime clock stepped; moving timer from {old_expire_time:?} to {expire_time:?}"
+            );
+
+            // Cancel any previously scheduled expiration events, and schedule a new one.
+            internal_brw.min_valid_expire_id = internal_brw.next_expire_id;
+            internal_brw.next_expire_time = Some(expire_time);
+            schedule(&mut internal_brw, internal_ptr.clone());
+            true
+        });
     }
 }
 
@@ -58,6 +144,8 @@ impl Timer {
                 expiration_count: 0,
                 next_expire_id: 0,
                 min_valid_expire_id: 0,
+                realtime_abs: false,
+                realtime_registered: false,
                 on_expire: Box::new(on_expire),
             })),
         }
@@ -169,10 +257,13 @@ impl Timer {
         let early_expire_time_since_start =
             SimulationTime::from_secs(since_start.as_secs()) + SimulationTime::SECOND * 2;
 
+        // The next expiration may already have passed if the timer was armed with a time in the
+        // past.
         let time = std::cmp::min(
             internal_ref.next_expire_time.unwrap(),
             EmulatedTime::SIMULATION_START + early_expire_time_since_start,
         );
+        let time = std::cmp::max(time, now);
         let expire_id = internal_ref.next_expire_id;
         internal_ref.next_expire_id += 1;
         let task = TaskRef::new(move |host| Self::timer_expire(&internal_ptr, host, expire_id));
@@ -187,7 +278,10 @@ impl Timer {
     /// callbacks every interval of time; if `None`, the timer is configured in one-shot mode and
     /// will become disarmed after the first expiration.
     ///
-    /// Panics if `expire_time` is in the past or if `expire_interval` is `Some` but not positive.
+    /// If `expire_time` is in the past, the timer expires immediately. A periodic timer's later
+    /// expirations stay on the schedule starting at `expire_time`.
+    ///
+    /// Panics if `expire_interval` is `Some` but not positive.
     pub fn arm(
         &mut self,
         host: &Host,
@@ -195,7 +289,6 @@ impl Timer {
         expire_interval: Option<SimulationTime>,
     ) {
         self.magic.debug_check();
-        debug_assert!(expire_time >= Worker::current_time().unwrap());
 
         // None is a valid expire interval, but zero is not.
         if let Some(interval) = expire_interval {
@@ -206,6 +299,21 @@ impl Timer {
         internal.reset(Some(expire_time), expire_interval);
         Self::schedule_new_expire_event(&mut internal, Arc::downgrade(&self.internal), host);
     }
+
+    /// Like [`Timer::arm`], but where `expire_time` was derived from an absolute time on the
+    /// host's realtime clock. If the realtime clock is later stepped (e.g. by `clock_settime`),
+    /// the expiration moves so that the timer still expires when the realtime clock reaches that
+    /// absolute time.
+    pub fn arm_realtime_abs(
+        &mut self,
+        host: &Host,
+        expire_time: EmulatedTime,
+        expire_interval: Option<SimulationTime>,
+    ) {
+        self.arm(host, expire_time, expire_interval);
+        self.internal.borrow_mut().realtime_abs = true;
+        host.realtime_timers_borrow_mut().register(&self.internal);
+    }
 }
 
 pub mod export {
@@ -258,6 +366,31 @@ pub mod export {
         )
     }
 
+    /// Like `timer_arm`, but for an absolute time on the host's realtime clock. See
+    /// [`Timer::arm_realtime_abs`].
+    ///
+    /// # Safety
+    ///
+    /// Pointer args must be safely dereferenceable.
+    #[unsafe(no_mangle)]
+    #[allow(non_snake_case)]
+    pub unsafe extern "C-unwind" fn timer_arm_realtime_abs(
+        timer: *mut Timer,
+        host: *const Host,
+        nextExpireTime: CEmulatedTime,
+        expireInterval: CSimulationTime,
+    ) {
+        let timer = unsafe { timer.as_mut() }.unwrap();
+        let host = unsafe { host.as_ref().unwrap() };
+        let nextExpireTime = EmulatedTime::from_c_emutime(nextExpireTime).unwrap();
+        let expireInterval = SimulationTime::from_c_simtime(expireInterval).unwrap();
+        timer.arm_realtime_abs(
+            host,
+            nextExpireTime,
+            expireInterval.is_positive().then_some(expireInterval),
+        )
+    }
+
     /// # Safety
     ///
     /// Pointer args must be safely dereferenceable.
@@ -267,3 +400,45 @@ pub mod export {
         timer.disarm()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_realtime_rearm() {
+        let timer = Timer::new(|_| {});
+        let mut timers = RealtimeTimers::default();
+        let expire_time = EmulatedTime::SIMULATION_START + SimulationTime::from_secs(10);
+
+        // re-arm the timer several times, as `Timer::arm_realtime_abs` does
+        for _ in 0..5 {
+            timer.internal.borrow_mut().reset(Some(expire_time), None);
+            timer.internal.borrow_mut().realtime_abs = true;
+            timers.register(&timer.internal);
+        }
+        assert_eq!(timers.timers.len(), 1);
+
+        let mut num_scheduled = 0;
+        timers.move_expirations(
+            |t| t + SimulationTime::from_secs(3),
+            |_, _| num_scheduled += 1,
+        );
+        assert_eq!(num_scheduled, 1);
+        assert_eq!(
+            timer.internal.borrow().next_expire_time,
+            Some(expire_time + SimulationTime::from_secs(3))
+        );
+
+        // a timer that's no longer armed with a realtime deadline is unregistered, and can be
+        // registered again
+        timer.internal.borrow_mut().reset(Some(expire_time), None);
+        timers.move_expirations(|t| t, |_, _| num_scheduled += 1);
+        assert_eq!(num_scheduled, 1);
+        assert!(timers.timers.is_empty());
+
+        timer.internal.borrow_mut().realtime_abs = true;
+        timers.register(&timer.internal);
+        assert_eq!(timers.timers.len(), 1);
+    }
+}
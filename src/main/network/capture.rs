@@ -0,0 +1,403 @@
+//! A capture of the packets of all hosts in a single pcapng file, and filters to choose which
+//! packets are captured.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::net::IpAddr;
+use std::path::Path;
+
+use shadow_shim_helper_rs::HostId;
+use shadow_shim_helper_rs::emulated_time::EmulatedTime;
+
+use crate::network::packet::{IanaProtocol, Packet, PacketStatus};
+use crate::utility::pcap_writer::PacketDisplay;
+use crate::utility::pcapng_writer::{PacketDirection, PcapngWriter};
+use crate::utility::round_writer::RoundWriter;
+
+/// The interfaces of each host, in the order that their interface blocks are written.
+const INTERFACES: [&str; 2] = ["lo", "eth0"];
+
+/// Which address or port of a packet a filter primitive applies to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Direction {
+    Src,
+    Dst,
+    SrcOrDst,
+}
+
+/// A packet filter using a subset of the tcpdump filter syntax. The primitives are `tcp`, `udp`,
+/// `[src|dst] host <address or hostname>`, and `[src|dst] port <port>`, which can be combined
+/// with `not` (or `!`), `and` (or `&&`), `or` (or `||`), and parentheses. `not` binds tightest
+/// and `or` loosest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PacketFilter {
+    Protocol(IanaProtocol),
+    Host(Direction, Vec<IpAddr>),
+    Port(Direction, u16),
+    Not(Box<PacketFilter>),
+    And(Box<PacketFilter>, Box<PacketFilter>),
+    Or(Box<PacketFilter>, Box<PacketFilter>),
+}
+
+impl PacketFilter {
+    /// Parse a filter expression. Hostnames in `host` primitives are resolved to the host's
+    /// addresses using `resolve`.
+    pub fn parse(s: &str, resolve: impl Fn(&str) -> Option<Vec<IpAddr>>) -> Result<Self, String> {
+        let tokens = tokenize(s);
+        let mut parser = Parser {
+            tokens: &tokens,
+            resolve: &resolve,
+        };
+
+        let filter = parser.parse_or()?;
+        if let Some(token) = parser.tokens.first() {
+            return Err(format!("Unexpected '{token}' in the packet filter"));
+        }
+
+        Ok(filter)
+    }
+
+    pub fn matches(&self, packet: &Packet) -> bool {
+        match self {
+            Self::Protocol(protocol) => packet.iana_protocol() == *protocol,
+            Self::Host(direction, addrs) => {
+                let mut src = vec![IpAddr::V4(*packet.src_ipv4_address().ip())];
+                let mut dst = vec![IpAddr::V4(*packet.dst_ipv4_address().ip())];
+                if let Some((src_v6, dst_v6)) = packet.ipv6_addresses() {
+                    src.push(IpAddr::V6(src_v6));
+                    dst.push(IpAddr::V6(dst_v6));
+                }
+                let is_match = |x: &[IpAddr]| x.iter().any(|x| addrs.contains(x));
+                match direction {
+                    Direction::Src => is_match(&src),
+                    Direction::Dst => is_match(&dst),
+                    Direction::SrcOrDst => is_match(&src) || is_match(&dst),
+                }
+            }
+            Self::Port(direction, port) => {
+                let src = packet.src_ipv4_address().port();
+                let dst = packet.dst_ipv4_address().port();
+                match direction {
+                    Direction::Src => src == *port,
+                    Direction::Dst => dst == *port,
+                    Direction::SrcOrDst => src == *port || dst == *port,
+                }
+            }
+            Self::Not(x) => !x.matches(packet),
+            Self::And(a, b) => a.matches(packet) && b.matches(packet),
+            Self::Or(a, b) => a.matches(packet) || b.matches(packet),
+        }
+    }
+}
+
+/// Split a filter expression into words, operators, and parentheses.
+fn tokenize(s: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut rest = s.trim_start();
+
+    while let Some(c) = rest.chars().next() {
+        let len = match c {
+            '(' | ')' | '!' => 1,
+            '&' if rest.starts_with("&&") => 2,
+            '|' if rest.starts_with("||") => 2,
+            _ => rest
+                .find(|x: char| x.is_whitespace() || "()!&|".contains(x))
+                .map_or(rest.len(), |x| x.max(1)),
+        };
+        tokens.push(&rest[..len]);
+        rest = rest[len..].trim_start();
+    }
+
+    tokens
+}
+
+struct Parser<'a, F> {
+    tokens: &'a [&'a str],
+    resolve: &'a F,
+}
+
+impl<'a, F: Fn(&str) -> Option<Vec<IpAddr>>> Parser<'a, F> {
+    fn next(&mut self) -> Option<&'a str> {
+        let (token, rest) = self.tokens.split_first()?;
+        self.tokens = rest;
+        Some(token)
+    }
+
+    /// Consume the next token if it's one of `tokens`.
+    fn next_if(&mut self, tokens: &[&str]) -> bool {
+        let is_match = self.tokens.first().is_some_and(|x| tokens.contains(x));
+        if is_match {
+            self.tokens = &self.tokens[1..];
+        }
+        is_match
+    }
+
+    fn parse_or(&mut self) -> Result<PacketFilter, String> {
+        let mut filter = self.parse_and()?;
+        while self.next_if(&["or", "||"]) {
+            filter = PacketFilter::Or(Box::new(filter), Box::new(self.parse_and()?));
+        }
+        Ok(filter)
+    }
+
+    fn parse_and(&mut self) -> Result<PacketFilter, String> {
+        let mut filter = self.parse_not()?;
+        while self.next_if(&["and", "&&"]) {
+            filter = PacketFilter::And(Box::new(filter), Box::new(self.parse_not()?));
+        }
+        Ok(filter)
+    }
+
+    fn parse_not(&mut self) -> Result<PacketFilter, String> {
+        if self.next_if(&["not", "!"]) {
+            return Ok(PacketFilter::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_primitive()
+    }
+
+    fn parse_primitive(&mut self) -> Result<PacketFilter, String> {
+        let direction = if self.next_if(&["src"]) {
+            Direction::Src
+        } else if self.next_if(&["dst"]) {
+            Direction::Dst
+        } else {
+            Direction::SrcOrDst
+        };
+
+        let token = self.next().ok_or("The packet filter ended unexpectedly")?;
+
+        match token {
+            "(" if direction == Direction::SrcOrDst => {
+                let filter = self.parse_or()?;
+                if !self.next_if(&[")"]) {
+                    return Err("Missing ')' in the packet filter".into());
+                }
+                Ok(filter)
+            }
+            "tcp" if direction == Direction::SrcOrDst => {
+                Ok(PacketFilter::Protocol(IanaProtocol::Tcp))
+            }
+            "udp" if direction == Direction::SrcOrDst => {
+                Ok(PacketFilter::Protocol(IanaProtocol::Udp))
+            }
+            "host" => {
+                let host = self
+                    .next()
+                    .ok_or("Expected an address or hostname after 'host'")?;
+                let addrs = match host.parse() {
+                    Ok(addr) => vec![addr],
+                    Err(_) => (self.resolve)(host)
+                        .ok_or_else(|| format!("Unknown host '{host}' in the packet filter"))?,
+                };
+                Ok(PacketFilter::Host(direction, addrs))
+            }
+            "port" => {
+                let port = self.next().ok_or("Expected a port after 'port'")?;
+                let port = port
+                    .parse()
+                    .map_err(|_| format!("Invalid port '{port}' in the packet filter"))?;
+                Ok(PacketFilter::Port(direction, port))
+            }
+            _ => Err(format!("Unexpected '{token}' in the packet filter")),
+        }
+    }
+}
+
+/// A captured packet that hasn't been written yet.
+struct CapturedPacket {
+    time: EmulatedTime,
+    interface_id: u32,
+    data: Vec<u8>,
+    original_len: u32,
+    direction: PacketDirection,
+    comment: String,
+}
+
+/// Captures the packets of many hosts to a single pcapng file, with an interface block for each
+/// interface of each host. Packets are captured from hosts in parallel and written between rounds,
+/// ordered by time and then by host so that the file is deterministic.
+pub struct PacketCapture {
+    /// The id of the first interface block and the capture length of each host.
+    hosts: HashMap<HostId, (u32, u32)>,
+    writer: RoundWriter<CapturedPacket, PcapngWriter<BufWriter<File>>>,
+}
+
+impl PacketCapture {
+    /// Create the capture file at `path`, with interfaces for each host given as its id, name, and
+    /// the length to truncate its packets to.
+    pub fn new<'a>(
+        path: &Path,
+        hosts: impl IntoIterator<Item = (HostId, &'a str, u32)>,
+    ) -> std::io::Result<Self> {
+        let mut writer = PcapngWriter::new(BufWriter::new(File::create(path)?))?;
+        let mut host_interfaces = HashMap::new();
+
+        for (host_id, name, capture_len) in hosts {
+            let mut ids = INTERFACES
+                .iter()
+                .map(|interface| writer.add_interface(&format!("{name}:{interface}"), capture_len));
+            let first_id = ids.next().unwrap()?;
+            for id in ids {
+                id?;
+            }
+            host_interfaces.insert(host_id, (first_id, capture_len));
+        }
+
+        Ok(Self {
+            hosts: host_interfaces,
+            writer: RoundWriter::new(writer),
+        })
+    }
+
+    /// Capture a packet that had `status` at `time` on the interface named `interface` of host
+    /// `host_id`. Packets of hosts that aren't part of the capture are ignored.
+    pub fn capture(
+        &self,
+        host_id: HostId,
+        interface: &str,
+        time: EmulatedTime,
+        packet: &Packet,
+        status: PacketStatus,
+    ) {
+        let Some((first_id, capture_len)) = self.hosts.get(&host_id) else {
+            return;
+        };
+        let index = INTERFACES.iter().position(|x| *x == interface).unwrap();
+
+        let mut data = Vec::new();
+        // writing to a vec can't fail
+        packet.display_bytes(&mut data).unwrap();
+        let original_len = u32::try_from(data.len()).unwrap();
+        data.truncate(usize::try_from(*capture_len).unwrap());
+
+        let direction = if status_is_inbound(status) {
+            PacketDirection::Inbound
+        } else {
+            PacketDirection::Outbound
+        };
+
+        let comment = match packet.id() {
+            Some(id) => format!("id={id} status={status:?}"),
+            None => format!("status={status:?}"),
+        };
+
+        let packet = CapturedPacket {
+            time,
+            interface_id: first_id + u32::try_from(index).unwrap(),
+            data,
+            original_len,
+            direction,
+            comment,
+        };
+        self.writer.record(time, Some(host_id), packet);
+    }
+
+    /// Write the packets that were captured during the round. Must only be called between rounds.
+    pub fn finish_round(&self) -> std::io::Result<()> {
+        self.writer.finish_round(|writer, packet| {
+            let timestamp_ns = packet.time.to_abs_simtime().as_nanos();
+            writer.write_packet(
+                packet.interface_id,
+                u64::try_from(timestamp_ns).unwrap(),
+                &packet.data,
+                packet.original_len,
+                Some(packet.direction),
+                Some(&packet.comment),
+            )
+        })
+    }
+
+    /// Write the remaining packets and flush the file.
+    pub fn finish(&self) -> std::io::Result<()> {
+        self.finish_round()?;
+        self.writer.with_writer(|writer| writer.flush())
+    }
+}
+
+/// Returns true if a packet with `status` was on its way in to the host rather than out.
+fn status_is_inbound(status: PacketStatus) -> bool {
+    matches!(
+        status,
+        PacketStatus::RouterEnqueued
+            | PacketStatus::RouterDequeued
+            | PacketStatus::RouterDropped
+            | PacketStatus::RcvInterfaceReceived
+            | PacketStatus::RcvInterfaceDropped
+            | PacketStatus::RcvSocketProcessed
+            | PacketStatus::RcvSocketDropped
+            | PacketStatus::RcvTcpEnqueueUnordered
+            | PacketStatus::RcvSocketBuffered
+            | PacketStatus::RcvSocketDelivered
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddrV4};
+
+    use super::*;
+
+    fn resolve(name: &str) -> Option<Vec<IpAddr>> {
+        (name == "server").then(|| vec![IpAddr::V4(Ipv4Addr::new(11, 0, 0, 2))])
+    }
+
+    fn udp_packet(src: (u8, u16), dst: (u8, u16)) -> Packet {
+        Packet::new_ipv4_udp(
+            SocketAddrV4::new(Ipv4Addr::new(11, 0, 0, src.0), src.1),
+            SocketAddrV4::new(Ipv4Addr::new(11, 0, 0, dst.0), dst.1),
+            bytes::Bytes::new(),
+            0,
+        )
+    }
+
+    #[test]
+    fn test_parse() {
+        let parse = |s| PacketFilter::parse(s, resolve);
+
+        assert_eq!(parse("tcp"), Ok(PacketFilter::Protocol(IanaProtocol::Tcp)));
+        assert_eq!(
+            parse("src port 80"),
+            Ok(PacketFilter::Port(Direction::Src, 80))
+        );
+        assert_eq!(
+            parse("host server"),
+            Ok(PacketFilter::Host(
+                Direction::SrcOrDst,
+                vec![IpAddr::V4(Ipv4Addr::new(11, 0, 0, 2))]
+            ))
+        );
+        assert_eq!(
+            parse("!udp||tcp&&(port 1)"),
+            Ok(PacketFilter::Or(
+                Box::new(PacketFilter::Not(Box::new(PacketFilter::Protocol(
+                    IanaProtocol::Udp
+                )))),
+                Box::new(PacketFilter::And(
+                    Box::new(PacketFilter::Protocol(IanaProtocol::Tcp)),
+                    Box::new(PacketFilter::Port(Direction::SrcOrDst, 1))
+                ))
+            ))
+        );
+
+        assert!(parse("").is_err());
+        assert!(parse("host client").is_err());
+        assert!(parse("port 65536").is_err());
+        assert!(parse("src tcp").is_err());
+        assert!(parse("(tcp").is_err());
+        assert!(parse("tcp udp").is_err());
+    }
+
+    #[test]
+    fn test_matches() {
+        let filter = |s| PacketFilter::parse(s, resolve).unwrap();
+        let packet = udp_packet((1, 5000), (2, 80));
+
+        assert!(filter("udp").matches(&packet));
+        assert!(!filter("tcp").matches(&packet));
+        assert!(filter("dst host server and dst port 80").matches(&packet));
+        assert!(!filter("src host server").matches(&packet));
+        assert!(filter("host 11.0.0.1 and not port 443").matches(&packet));
+        assert!(!filter("src port 80 or dst port 5000").matches(&packet));
+    }
+}
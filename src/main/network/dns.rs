@@ -3,7 +3,7 @@ use std::collections::hash_map::Entry;
 use std::fmt::Display;
 use std::fs::File;
 use std::io::Write;
-use std::net::Ipv4Addr;
+use std::net::{Ipv4Addr, Ipv6Addr};
 use std::os::fd::AsRawFd;
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -19,12 +19,15 @@ struct Database {
     // configured host names to a subset of ascii, which are always valid utf-8.
     name_index: HashMap<String, Arc<Record>>,
     addr_index: HashMap<Ipv4Addr, Arc<Record>>,
+    addr6_index: HashMap<Ipv6Addr, Arc<Record>>,
 }
 
 #[derive(Debug)]
 struct Record {
     id: HostId,
     addr: Ipv4Addr,
+    /// An optional IPv6 address that is an alias of `addr`.
+    addr6: Option<Ipv6Addr>,
     name: String,
 }
 
@@ -34,8 +37,10 @@ pub enum RegistrationError {
     LoopbackAddrInvalid(Ipv4Addr),
     MulticastAddrInvalid(Ipv4Addr),
     UnspecifiedAddrInvalid,
+    Addr6Invalid(Ipv6Addr),
     NameInvalid(String),
     AddrExists(Ipv4Addr),
+    Addr6Exists(Ipv6Addr),
     NameExists(String),
 }
 
@@ -58,6 +63,9 @@ impl Display for RegistrationError {
                 "unspecified address '{}' is invalid in DNS",
                 Ipv4Addr::UNSPECIFIED
             ),
+            RegistrationError::Addr6Invalid(addr) => {
+                write!(f, "IPv6 address '{addr}' is invalid in DNS")
+            }
             RegistrationError::NameInvalid(name) => write!(f, "name '{name}' is invalid in DNS"),
             RegistrationError::NameExists(name) => {
                 write!(
@@ -71,6 +79,12 @@ impl Display for RegistrationError {
                     "a DNS registration record already exists for address '{addr}'"
                 )
             }
+            RegistrationError::Addr6Exists(addr) => {
+                write!(
+                    f,
+                    "a DNS registration record already exists for address '{addr}'"
+                )
+            }
         }
     }
 }
@@ -88,6 +102,7 @@ impl DnsBuilder {
             db: Database {
                 name_index: HashMap::new(),
                 addr_index: HashMap::new(),
+                addr6_index: HashMap::new(),
             },
         }
     }
@@ -96,6 +111,7 @@ impl DnsBuilder {
         &mut self,
         id: HostId,
         addr: Ipv4Addr,
+        addr6: Option<Ipv6Addr>,
         name: String,
     ) -> Result<(), RegistrationError> {
         // Make sure we don't register reserved addresses or names.
@@ -111,6 +127,18 @@ impl DnsBuilder {
             return Err(RegistrationError::NameInvalid(name));
         }
 
+        if let Some(addr6) = addr6 {
+            if addr6.is_unspecified()
+                || addr6.is_loopback()
+                || addr6.is_multicast()
+                || addr6.to_ipv4_mapped().is_some()
+            {
+                return Err(RegistrationError::Addr6Invalid(addr6));
+            } else if self.db.addr6_index.contains_key(&addr6) {
+                return Err(RegistrationError::Addr6Exists(addr6));
+            }
+        }
+
         // A single HostId is allowed to register multiple name/addr mappings,
         // but only vacant addresses and names are allowed.
         match self.db.addr_index.entry(addr) {
@@ -118,7 +146,15 @@ impl DnsBuilder {
             Entry::Vacant(addr_entry) => match self.db.name_index.entry(name.clone()) {
                 Entry::Occupied(_) => Err(RegistrationError::NameExists(name)),
                 Entry::Vacant(name_entry) => {
-                    let record = Arc::new(Record { id, addr, name });
+                    let record = Arc::new(Record {
+                        id,
+                        addr,
+                        addr6,
+                        name,
+                    });
+                    if let Some(addr6) = addr6 {
+                        self.db.addr6_index.insert(addr6, record.clone());
+                    }
                     addr_entry.insert(record.clone());
                     name_entry.insert(record);
                     Ok(())
@@ -143,12 +179,20 @@ impl DnsBuilder {
         records.sort_by_key(|x| x.addr);
 
         writeln!(file, "127.0.0.1 localhost")?;
+        writeln!(file, "::1 localhost")?;
         for record in records.iter() {
             // Make it easier to debug if somehow we ever got a name with whitespace.
             assert!(!record.name.as_bytes().iter().any(u8::is_ascii_whitespace));
             writeln!(file, "{} {}", record.addr, record.name)?;
         }
 
+        // IPv6 entries follow all IPv4 entries so that IPv4 lookups are preferred.
+        let mut records: Vec<&Arc<Record>> = self.db.addr6_index.values().collect();
+        records.sort_by_key(|x| x.addr6);
+        for record in records.iter() {
+            writeln!(file, "{} {}", record.addr6.unwrap(), record.name)?;
+        }
+
         Ok(Dns {
             db: self.db,
             hosts_file: file,
@@ -187,6 +231,23 @@ impl Dns {
         self.db.name_index.get(name).map(|record| record.addr)
     }
 
+    pub fn name_to_addr6(&self, name: &str) -> Option<Ipv6Addr> {
+        self.db.name_index.get(name).and_then(|record| record.addr6)
+    }
+
+    /// The IPv4 address that the IPv6 address `addr6` is an alias of.
+    pub fn addr6_to_addr(&self, addr6: Ipv6Addr) -> Option<Ipv4Addr> {
+        self.db.addr6_index.get(&addr6).map(|record| record.addr)
+    }
+
+    /// The IPv6 alias of the IPv4 address `addr`, if it has one.
+    pub fn addr_to_addr6(&self, addr: Ipv4Addr) -> Option<Ipv6Addr> {
+        self.db
+            .addr_index
+            .get(&addr)
+            .and_then(|record| record.addr6)
+    }
+
     pub fn hosts_path(&self) -> PathBuf {
         PathBuf::from(format!("/proc/self/fd/{}", self.hosts_file.as_raw_fd()))
     }
@@ -210,6 +271,14 @@ mod tests {
         (id, addr, name)
     }
 
+    fn addr6_a() -> Ipv6Addr {
+        Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 1)
+    }
+
+    fn addr6_b() -> Ipv6Addr {
+        Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 2)
+    }
+
     #[test]
     fn register() {
         let (id_a, addr_a, name_a) = host_a();
@@ -217,43 +286,80 @@ mod tests {
 
         let mut builder = DnsBuilder::new();
 
-        assert!(builder.register(id_a, addr_a, name_a.clone()).is_ok());
+        assert!(
+            builder
+                .register(id_a, addr_a, Some(addr6_a()), name_a.clone())
+                .is_ok()
+        );
 
         assert_eq!(
-            builder.register(id_b, Ipv4Addr::UNSPECIFIED, name_b.clone()),
+            builder.register(id_b, Ipv4Addr::UNSPECIFIED, None, name_b.clone()),
             Err(RegistrationError::UnspecifiedAddrInvalid)
         );
         assert_eq!(
-            builder.register(id_b, Ipv4Addr::BROADCAST, name_b.clone()),
+            builder.register(id_b, Ipv4Addr::BROADCAST, None, name_b.clone()),
             Err(RegistrationError::BroadcastAddrInvalid)
         );
         let multicast_example_addr = Ipv4Addr::new(224, 0, 0, 1);
         assert_eq!(
             // Multicast addresses not allowed.
-            builder.register(id_b, multicast_example_addr, name_b.clone()),
+            builder.register(id_b, multicast_example_addr, None, name_b.clone()),
             Err(RegistrationError::MulticastAddrInvalid(
                 multicast_example_addr
             ))
         );
         assert_eq!(
-            builder.register(id_b, Ipv4Addr::LOCALHOST, name_b.clone()),
+            builder.register(id_b, Ipv4Addr::LOCALHOST, None, name_b.clone()),
             Err(RegistrationError::LoopbackAddrInvalid(Ipv4Addr::LOCALHOST))
         );
         let localhost_string = String::from("localhost");
         assert_eq!(
-            builder.register(id_b, addr_b, localhost_string.clone()),
+            builder.register(id_b, addr_b, None, localhost_string.clone()),
             Err(RegistrationError::NameInvalid(localhost_string))
         );
         assert_eq!(
-            builder.register(id_b, addr_a, name_b.clone()),
+            builder.register(id_b, addr_a, None, name_b.clone()),
             Err(RegistrationError::AddrExists(addr_a))
         );
         assert_eq!(
-            builder.register(id_b, addr_b, name_a.clone()),
+            builder.register(id_b, addr_b, None, name_a.clone()),
             Err(RegistrationError::NameExists(name_a))
         );
+        assert_eq!(
+            builder.register(id_b, addr_b, Some(Ipv6Addr::LOCALHOST), name_b.clone()),
+            Err(RegistrationError::Addr6Invalid(Ipv6Addr::LOCALHOST))
+        );
+        assert_eq!(
+            builder.register(id_b, addr_b, Some(addr6_a()), name_b.clone()),
+            Err(RegistrationError::Addr6Exists(addr6_a()))
+        );
+
+        assert!(builder.register(id_b, addr_b, None, name_b.clone()).is_ok());
+    }
+
+    #[test]
+    fn lookups_ipv6() {
+        let (id_a, addr_a, name_a) = host_a();
+        let (id_b, addr_b, name_b) = host_b();
+
+        let mut builder = DnsBuilder::new();
+        builder
+            .register(id_a, addr_a, Some(addr6_a()), name_a.clone())
+            .unwrap();
+        builder
+            .register(id_b, addr_b, None, name_b.clone())
+            .unwrap();
+        let dns = builder.into_dns().unwrap();
+
+        assert_eq!(dns.addr6_to_addr(addr6_a()), Some(addr_a));
+        assert_eq!(dns.addr6_to_addr(addr6_b()), None);
+
+        assert_eq!(dns.addr_to_addr6(addr_a), Some(addr6_a()));
+        assert_eq!(dns.addr_to_addr6(addr_b), None);
 
-        assert!(builder.register(id_b, addr_b, name_b.clone()).is_ok());
+        assert_eq!(dns.name_to_addr6(&name_a), Some(addr6_a()));
+        assert_eq!(dns.name_to_addr6(&name_b), None);
+        assert_eq!(dns.name_to_addr6("localhost"), None);
     }
 
     #[test]
@@ -262,8 +368,12 @@ mod tests {
         let (id_b, addr_b, name_b) = host_b();
 
         let mut builder = DnsBuilder::new();
-        builder.register(id_a, addr_a, name_a.clone()).unwrap();
-        builder.register(id_b, addr_b, name_b.clone()).unwrap();
+        builder
+            .register(id_a, addr_a, None, name_a.clone())
+            .unwrap();
+        builder
+            .register(id_b, addr_b, None, name_b.clone())
+            .unwrap();
         let dns = builder.into_dns().unwrap();
 
         assert_eq!(dns.addr_to_host_id(addr_a), Some(id_a));
@@ -287,15 +397,21 @@ mod tests {
         let (id_b, addr_b, name_b) = host_b();
 
         let mut builder = DnsBuilder::new();
-        builder.register(id_a, addr_a, name_a.clone()).unwrap();
-        builder.register(id_b, addr_b, name_b.clone()).unwrap();
+        builder
+            .register(id_a, addr_a, Some(addr6_a()), name_a.clone())
+            .unwrap();
+        builder
+            .register(id_b, addr_b, Some(addr6_b()), name_b.clone())
+            .unwrap();
         let dns = builder.into_dns().unwrap();
 
         let contents = std::fs::read_to_string(dns.hosts_path()).unwrap();
 
-        let expected = "127.0.0.1 localhost\n100.1.2.3 myhost\n200.3.2.1 theirhost\n";
+        let expected = "127.0.0.1 localhost\n::1 localhost\n100.1.2.3 myhost\n200.3.2.1 theirhost\n\
+                        fd00::1 myhost\nfd00::2 theirhost\n";
         assert_eq!(contents.as_str(), expected);
-        let unexpected = "127.0.0.1 localhost\n200.3.2.1 theirhost\n100.1.2.3 myhost\n";
+        let unexpected = "127.0.0.1 localhost\n::1 localhost\n200.3.2.1 theirhost\n100.1.2.3 myhost\n\
+                          fd00::1 myhost\nfd00::2 theirhost\n";
         assert_ne!(contents.as_str(), unexpected);
     }
 }
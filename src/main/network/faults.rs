@@ -0,0 +1,293 @@
+//! Changes to the network during the simulation, such as partitions and link failures.
+//!
+//! Changes to routing are applied by computing the paths between graph nodes for each period
+//! between changes ahead of time (see [`RoutingInfo::path_at`]), so that a packet uses the paths
+//! that were in effect at the time it was sent. Changes to bandwidth are applied by the hosts
+//! attached to the graph node.
+//!
+//! [`RoutingInfo::path_at`]: crate::network::graph::RoutingInfo::path_at
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use petgraph::graph::{EdgeIndex, NodeIndex};
+use shadow_shim_helper_rs::emulated_time::EmulatedTime;
+use shadow_shim_helper_rs::simulation_time::SimulationTime;
+
+use crate::core::configuration::NetworkFaultOptions;
+use crate::network::graph::{NetworkGraph, PathLink, PathProperties};
+use crate::utility::units::{self, Unit};
+
+/// A change to the bandwidth of the hosts attached to a graph node.
+#[derive(Debug, Clone, Copy)]
+pub struct BandwidthChange {
+    pub time: EmulatedTime,
+    pub down_bits: Option<u64>,
+    pub up_bits: Option<u64>,
+}
+
+/// A change to how packets are routed between graph nodes.
+#[derive(Debug, Clone)]
+enum RoutingChange {
+    Partition(Vec<Vec<u32>>),
+    Heal,
+    Link {
+        edge: EdgeIndex<u32>,
+        latency_ns: Option<u64>,
+        packet_loss: Option<f32>,
+        jitter_ns: Option<u64>,
+        up: Option<bool>,
+    },
+}
+
+/// The network faults of a simulation, which are used to compute the paths between graph nodes
+/// after each change to the network.
+#[derive(Debug)]
+pub struct NetworkFaults {
+    graph: NetworkGraph,
+    /// The graph nodes that are in use.
+    nodes: Vec<NodeIndex>,
+    use_shortest_paths: bool,
+    /// Changes to routing, sorted by time. Changes with the same time are in the order they were
+    /// added.
+    routing_changes: Vec<(EmulatedTime, RoutingChange)>,
+}
+
+impl NetworkFaults {
+    pub fn new(graph: NetworkGraph, nodes: &HashSet<u32>, use_shortest_paths: bool) -> Self {
+        let mut nodes: Vec<_> = nodes
+            .iter()
+            .map(|x| *graph.node_id_to_index(*x).unwrap())
+            .collect();
+        nodes.sort();
+
+        Self {
+            graph,
+            nodes,
+            use_shortest_paths,
+            routing_changes: Vec::new(),
+        }
+    }
+
+    /// Add a fault. A change to bandwidth isn't recorded, and is instead returned with the ID of
+    /// the graph node that it applies to since it must be applied by the node's hosts.
+    pub fn add(
+        &mut self,
+        fault: &NetworkFaultOptions,
+    ) -> anyhow::Result<Option<(u32, BandwidthChange)>> {
+        let time = SimulationTime::try_from(Duration::from(fault.time())).unwrap();
+        let time = EmulatedTime::SIMULATION_START + time;
+
+        let change = match fault {
+            NetworkFaultOptions::Partition { groups, .. } => {
+                let mut seen = HashSet::new();
+                for node in groups.iter().flatten() {
+                    self.node_index(*node)?;
+                    if !seen.insert(*node) {
+                        anyhow::bail!("Network node {node} is in more than one partition group");
+                    }
+                }
+                RoutingChange::Partition(groups.clone())
+            }
+            NetworkFaultOptions::Heal { .. } => RoutingChange::Heal,
+            NetworkFaultOptions::Link {
+                source,
+                target,
+                latency,
+                packet_loss,
+                jitter,
+                up,
+                ..
+            } => {
+                let (edge, _) = self
+                    .graph
+                    .get_edge(&self.node_index(*source)?, &self.node_index(*target)?)
+                    .map_err(|e| anyhow::anyhow!(e))?;
+
+                let nanos = |x: units::Time<units::TimePrefix>| {
+                    x.convert(units::TimePrefix::Nano).unwrap().value()
+                };
+                let latency_ns = latency.map(nanos);
+                if latency_ns == Some(0) {
+                    anyhow::bail!("Link latency must not be 0");
+                }
+                if packet_loss.is_some_and(|x| !(0.0..=1.0).contains(&x)) {
+                    anyhow::bail!("Link packet loss is not in the range [0,1]");
+                }
+
+                RoutingChange::Link {
+                    edge,
+                    latency_ns,
+                    packet_loss: *packet_loss,
+                    jitter_ns: jitter.map(nanos),
+                    up: *up,
+                }
+            }
+            NetworkFaultOptions::Bandwidth {
+                node,
+                bandwidth_down,
+                bandwidth_up,
+                ..
+            } => {
+                self.node_index(*node)?;
+                if bandwidth_down.is_none() && bandwidth_up.is_none() {
+                    anyhow::bail!("No bandwidth was provided for network node {node}");
+                }
+
+                let bits = |x: units::BitsPerSec<units::SiPrefixUpper>| {
+                    x.convert(units::SiPrefixUpper::Base).unwrap().value()
+                };
+                let change = BandwidthChange {
+                    time,
+                    down_bits: bandwidth_down.map(bits),
+                    up_bits: bandwidth_up.map(bits),
+                };
+                return Ok(Some((*node, change)));
+            }
+        };
+
+        let idx = self.routing_changes.partition_point(|(t, _)| *t <= time);
+        self.routing_changes.insert(idx, (time, change));
+        Ok(None)
+    }
+
+    fn node_index(&self, id: u32) -> anyhow::Result<NodeIndex> {
+        self.graph
+            .node_id_to_index(id)
+            .copied()
+            .with_context(|| format!("Network node {id} does not exist"))
+    }
+
+    /// Get the paths between the in-use graph nodes after each change to routing at or after
+    /// `from`, sorted by time.
+    pub fn changed_paths_from(
+        &self,
+        from: EmulatedTime,
+    ) -> Vec<(EmulatedTime, HashMap<(u32, u32), PathProperties>)> {
+        self.changes_from(from)
+            .into_iter()
+            .map(|(time, overrides, partition)| (time, self.compute_paths(&overrides, partition)))
+            .collect()
+    }
+
+    /// Get the links on the paths between the in-use graph nodes after each change to routing at
+    /// or after `from`, sorted by time.
+    pub fn changed_links_from(
+        &self,
+        from: EmulatedTime,
+    ) -> Vec<(EmulatedTime, HashMap<(u32, u32), Arc<[PathLink]>>)> {
+        self.changes_from(from)
+            .into_iter()
+            .map(|(time, overrides, _)| {
+                let links: HashMap<_, Arc<[PathLink]>> = self
+                    .graph
+                    .compute_links_with_overrides(&self.nodes, self.use_shortest_paths, &overrides)
+                    .into_iter()
+                    .map(|((src, dst), links)| {
+                        let src = self.graph.node_index_to_id(src).unwrap();
+                        let dst = self.graph.node_index_to_id(dst).unwrap();
+                        ((src, dst), links.into())
+                    })
+                    .collect();
+                (time, links)
+            })
+            .collect()
+    }
+
+    /// Get the state of the network after each change to routing at or after `from`, sorted by
+    /// time. The state is the properties of each changed edge (`None` if it's down) and the
+    /// partition groups.
+    #[allow(clippy::type_complexity)]
+    fn changes_from(
+        &self,
+        from: EmulatedTime,
+    ) -> Vec<(
+        EmulatedTime,
+        HashMap<EdgeIndex<u32>, Option<PathProperties>>,
+        Option<&[Vec<u32>]>,
+    )> {
+        let mut partition: Option<&[Vec<u32>]> = None;
+        // the properties of each changed edge, and whether it's up
+        let mut edges: HashMap<EdgeIndex<u32>, (PathProperties, bool)> = HashMap::new();
+
+        let mut changes = Vec::new();
+        for (i, (time, change)) in self.routing_changes.iter().enumerate() {
+            match change {
+                RoutingChange::Partition(groups) => partition = Some(groups.as_slice()),
+                RoutingChange::Heal => partition = None,
+                RoutingChange::Link {
+                    edge,
+                    latency_ns,
+                    packet_loss,
+                    jitter_ns,
+                    up,
+                } => {
+                    let (properties, is_up) = edges.entry(*edge).or_insert_with(|| {
+                        let weight = self.graph.graph().edge_weight(*edge).unwrap();
+                        (weight.into(), true)
+                    });
+                    if let Some(latency_ns) = latency_ns {
+                        properties.latency_ns = *latency_ns;
+                    }
+                    if let Some(packet_loss) = packet_loss {
+                        properties.packet_loss = *packet_loss;
+                    }
+                    if let Some(jitter_ns) = jitter_ns {
+                        properties.jitter_ns = *jitter_ns;
+                    }
+                    if let Some(up) = up {
+                        *is_up = *up;
+                    }
+                }
+            }
+
+            // use the state once all changes at this time have been applied
+            let is_last_at_time = self
+                .routing_changes
+                .get(i + 1)
+                .is_none_or(|(next_time, _)| next_time != time);
+            if *time < from || !is_last_at_time {
+                continue;
+            }
+
+            let overrides = edges
+                .iter()
+                .map(|(edge, (properties, is_up))| (*edge, is_up.then_some(*properties)))
+                .collect();
+            changes.push((*time, overrides, partition));
+        }
+
+        changes
+    }
+
+    fn compute_paths(
+        &self,
+        overrides: &HashMap<EdgeIndex<u32>, Option<PathProperties>>,
+        partition: Option<&[Vec<u32>]>,
+    ) -> HashMap<(u32, u32), PathProperties> {
+        // the partition group of each node in the partition
+        let groups: HashMap<u32, usize> = partition
+            .into_iter()
+            .flatten()
+            .enumerate()
+            .flat_map(|(i, group)| group.iter().map(move |node| (*node, i)))
+            .collect();
+
+        self.graph
+            .compute_paths_with_overrides(&self.nodes, self.use_shortest_paths, overrides)
+            .into_iter()
+            .map(|((src, dst), path)| {
+                let src = self.graph.node_index_to_id(src).unwrap();
+                let dst = self.graph.node_index_to_id(dst).unwrap();
+                ((src, dst), path)
+            })
+            // nodes in different partition groups can't communicate
+            .filter(|((src, dst), _)| match (groups.get(src), groups.get(dst)) {
+                (Some(src_group), Some(dst_group)) => src_group == dst_group,
+                _ => true,
+            })
+            .collect()
+    }
+}
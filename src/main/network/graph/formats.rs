@@ -0,0 +1,647 @@
+//! Network graph formats other than GML. Each format is converted to the same nodes and edges as a
+//! GML graph, so the same attributes are supported and they're checked in the same way. Errors
+//! include the position of the element or CSV cell that caused them.
+
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
+use gml_parser::gml::{Edge, Node, Value};
+
+use crate::network::graph::{ShadowEdge, ShadowNode};
+use crate::utility::units;
+
+/// The nodes and edges of a parsed graph, which haven't been checked for edges between nodes that
+/// don't exist.
+#[derive(Debug)]
+pub struct GraphParts {
+    pub directed: bool,
+    pub nodes: Vec<ShadowNode>,
+    pub edges: Vec<ShadowEdge>,
+}
+
+/// Parse a graph in the [GraphML](http://graphml.graphdrawing.org/) format. Node IDs must be
+/// non-negative integers, and node and edge attributes are given as `<data>` elements whose keys
+/// have the attribute's name as their `attr.name`.
+pub fn parse_graphml(text: &str) -> Result<GraphParts, String> {
+    let doc = roxmltree::Document::parse(text).map_err(|e| format!("Invalid XML: {e}"))?;
+
+    // a description of an element for error messages
+    let at = |element: roxmltree::Node| {
+        let pos = doc.text_pos_at(element.range().start);
+        format!(
+            "<{}> at line {}, column {}",
+            element.tag_name().name(),
+            pos.row,
+            pos.col,
+        )
+    };
+
+    let root = doc.root_element();
+    if !root.has_tag_name("graphml") {
+        return Err(format!("{}: expected a <graphml> element", at(root)));
+    }
+
+    struct Key<'a> {
+        id: &'a str,
+        name: &'a str,
+        domain: &'a str,
+        ty: &'a str,
+        default: Option<&'a str>,
+    }
+
+    let mut keys = Vec::new();
+    for key in root.children().filter(|x| x.has_tag_name("key")) {
+        let attribute = |name: &str| {
+            key.attribute(name)
+                .ok_or_else(|| format!("{}: missing the '{name}' attribute", at(key)))
+        };
+        let ty = key.attribute("attr.type").unwrap_or("string");
+        if !["string", "float", "double", "int", "long", "boolean"].contains(&ty) {
+            return Err(format!("{}: unsupported attr.type '{ty}'", at(key)));
+        }
+        keys.push(Key {
+            id: attribute("id")?,
+            name: attribute("attr.name")?,
+            domain: key.attribute("for").unwrap_or("all"),
+            ty,
+            default: key
+                .children()
+                .find(|x| x.has_tag_name("default"))
+                .map(|x| x.text().unwrap_or("")),
+        });
+    }
+
+    let graphs: Vec<_> = root
+        .children()
+        .filter(|x| x.has_tag_name("graph"))
+        .collect();
+    let [graph] = graphs[..] else {
+        return Err(format!(
+            "{}: expected exactly one <graph> element, found {}",
+            at(root),
+            graphs.len(),
+        ));
+    };
+
+    let directed = match graph.attribute("edgedefault") {
+        Some("directed") => true,
+        Some("undirected") | None => false,
+        Some(x) => return Err(format!("{}: invalid edgedefault '{x}'", at(graph))),
+    };
+
+    let value = |key: &Key, text: &str, element: roxmltree::Node| {
+        let text = text.trim();
+        let invalid = || format!("{}: '{text}' is not a valid {}", at(element), key.ty);
+        Ok(match key.ty {
+            "string" => Value::Str(Cow::Owned(text.to_string())),
+            "float" | "double" => Value::Float(text.parse().map_err(|_| invalid())?),
+            "int" | "long" => Value::Int(text.parse().map_err(|_| invalid())?),
+            "boolean" => Value::Int(match text {
+                "true" => 1,
+                "false" => 0,
+                _ => return Err(invalid()),
+            }),
+            _ => unreachable!(),
+        })
+    };
+
+    // the attributes of a <node> or <edge> element, including the keys' default values
+    let attributes = |element: roxmltree::Node, domain: &str| {
+        let applies = |key: &&Key| key.domain == domain || key.domain == "all";
+
+        let mut attributes = HashMap::new();
+        for key in keys.iter().filter(applies) {
+            if let Some(default) = key.default {
+                attributes.insert(key.name.to_string(), value(key, default, element)?);
+            }
+        }
+        for data in element.children().filter(|x| x.has_tag_name("data")) {
+            let key_id = data
+                .attribute("key")
+                .ok_or_else(|| format!("{}: missing the 'key' attribute", at(data)))?;
+            let key = keys
+                .iter()
+                .filter(applies)
+                .find(|x| x.id == key_id)
+                .ok_or_else(|| format!("{}: no key '{key_id}' for <{domain}>", at(data)))?;
+            let value = value(key, data.text().unwrap_or(""), data)?;
+            attributes.insert(key.name.to_string(), value);
+        }
+        Ok::<_, String>(attributes)
+    };
+
+    let node_id = |element: roxmltree::Node, name: &str| {
+        let id = element
+            .attribute(name)
+            .ok_or_else(|| format!("{}: missing the '{name}' attribute", at(element)))?;
+        id.parse::<u32>().map_err(|_| {
+            format!(
+                "{}: node ID '{id}' is not a non-negative integer",
+                at(element)
+            )
+        })
+    };
+
+    let mut nodes = Vec::new();
+    let mut node_ids = HashSet::new();
+    for element in graph.children().filter(|x| x.has_tag_name("node")) {
+        let id = node_id(element, "id")?;
+        if !node_ids.insert(id) {
+            return Err(format!("{}: duplicate node ID {id}", at(element)));
+        }
+        let node = Node::new(Some(id), attributes(element, "node")?);
+        nodes.push(ShadowNode::try_from(node).map_err(|e| format!("{}: {e}", at(element)))?);
+    }
+
+    let mut edges = Vec::new();
+    for element in graph.children().filter(|x| x.has_tag_name("edge")) {
+        let source = node_id(element, "source")?;
+        let target = node_id(element, "target")?;
+        for id in [source, target] {
+            if !node_ids.contains(&id) {
+                return Err(format!("{}: node {id} doesn't exist", at(element)));
+            }
+        }
+        if let Some(x) = element.attribute("directed")
+            && (x == "true") != directed
+        {
+            return Err(format!(
+                "{}: graphs with both directed and undirected edges aren't supported",
+                at(element),
+            ));
+        }
+        let edge = Edge::new(source, target, attributes(element, "edge")?);
+        edges.push(ShadowEdge::try_from(edge).map_err(|e| format!("{}: {e}", at(element)))?);
+    }
+
+    Ok(GraphParts {
+        directed,
+        nodes,
+        edges,
+    })
+}
+
+/// Parse a graph in the JSON "node-link" format used by NetworkX. Edges may be in a `links` or an
+/// `edges` array.
+pub fn parse_json(text: &str) -> Result<GraphParts, String> {
+    let graph: serde_json::Value =
+        serde_json::from_str(text).map_err(|e| format!("Invalid JSON: {e}"))?;
+    let graph = graph.as_object().ok_or("The JSON graph is not an object")?;
+
+    let directed = match graph.get("directed") {
+        Some(x) => x
+            .as_bool()
+            .ok_or("The graph's 'directed' is not a boolean")?,
+        None => false,
+    };
+
+    let array = |name: &str| match graph.get(name) {
+        Some(x) => x
+            .as_array()
+            .map(Some)
+            .ok_or(format!("The graph's '{name}' is not an array")),
+        None => Ok(None),
+    };
+    let nodes = array("nodes")?.ok_or("The graph has no 'nodes' array")?;
+    let (edges_name, edges) = match (array("links")?, array("edges")?) {
+        (Some(links), None) => ("links", links),
+        (None, Some(edges)) => ("edges", edges),
+        (None, None) => return Err("The graph has no 'links' or 'edges' array".into()),
+        (Some(_), Some(_)) => return Err("The graph has both 'links' and 'edges' arrays".into()),
+    };
+
+    // the attributes of a node or edge, excluding the given keys
+    let attributes = |object: &serde_json::Map<String, serde_json::Value>, exclude: &[&str]| {
+        object
+            .iter()
+            .filter(|(name, _)| !exclude.contains(&name.as_str()))
+            .filter_map(|(name, value)| {
+                let value = match value {
+                    serde_json::Value::String(x) => Value::Str(Cow::Owned(x.clone())),
+                    serde_json::Value::Number(x) => Value::Float(x.as_f64()? as f32),
+                    serde_json::Value::Bool(x) => Value::Int((*x).into()),
+                    // other attributes may be used by other tools
+                    _ => return None,
+                };
+                Some((name.clone(), value))
+            })
+            .collect::<HashMap<_, _>>()
+    };
+
+    let node_id =
+        |object: &serde_json::Map<String, serde_json::Value>, name: &str, location: &str| {
+            object
+                .get(name)
+                .and_then(|x| x.as_u64())
+                .and_then(|x| u32::try_from(x).ok())
+                .ok_or(format!(
+                    "{location}: '{name}' is not a non-negative integer"
+                ))
+        };
+
+    let mut shadow_nodes = Vec::new();
+    let mut node_ids = HashSet::new();
+    for (i, node) in nodes.iter().enumerate() {
+        let location = format!("nodes[{i}]");
+        let node = node
+            .as_object()
+            .ok_or(format!("{location}: not an object"))?;
+        let id = node_id(node, "id", &location)?;
+        if !node_ids.insert(id) {
+            return Err(format!("{location}: duplicate node ID {id}"));
+        }
+        let node = Node::new(Some(id), attributes(node, &["id"]));
+        shadow_nodes.push(ShadowNode::try_from(node).map_err(|e| format!("{location}: {e}"))?);
+    }
+
+    let mut shadow_edges = Vec::new();
+    for (i, edge) in edges.iter().enumerate() {
+        let location = format!("{edges_name}[{i}]");
+        let edge = edge
+            .as_object()
+            .ok_or(format!("{location}: not an object"))?;
+        let source = node_id(edge, "source", &location)?;
+        let target = node_id(edge, "target", &location)?;
+        for id in [source, target] {
+            if !node_ids.contains(&id) {
+                return Err(format!("{location}: node {id} doesn't exist"));
+            }
+        }
+        let edge = Edge::new(
+            source,
+            target,
+            attributes(edge, &["source", "target", "key"]),
+        );
+        shadow_edges.push(ShadowEdge::try_from(edge).map_err(|e| format!("{location}: {e}"))?);
+    }
+
+    Ok(GraphParts {
+        directed,
+        nodes: shadow_nodes,
+        edges: shadow_edges,
+    })
+}
+
+/// Parse a CSV matrix of the latencies between locations, in milliseconds. The first row and
+/// column label the locations in the same order, and the cell in row `a` and column `b` is the
+/// latency from `a` to `b`. Each location is a graph node whose ID is its position in the first
+/// row, starting at 0, and the hosts attached to every node have the given bandwidths.
+pub fn parse_latency_matrix(
+    text: &str,
+    bandwidth_down: Option<units::BitsPerSec<units::SiPrefixUpper>>,
+    bandwidth_up: Option<units::BitsPerSec<units::SiPrefixUpper>>,
+) -> Result<GraphParts, String> {
+    let mut rows = csv_rows(text)?.into_iter();
+
+    let Some((_, header)) = rows.next() else {
+        return Err("The latency matrix is empty".into());
+    };
+    let labels = &header[1..];
+    if labels.is_empty() {
+        return Err("Row 1: the first row has no locations".into());
+    }
+    let mut seen = HashSet::new();
+    for (i, label) in labels.iter().enumerate() {
+        if !seen.insert(label) {
+            return Err(format!(
+                "Row 1, column {}: duplicate location '{label}'",
+                i + 2
+            ));
+        }
+    }
+
+    let nodes = (0..labels.len())
+        .map(|id| ShadowNode {
+            id: id.try_into().unwrap(),
+            bandwidth_down,
+            bandwidth_up,
+        })
+        .collect();
+
+    let mut edges = Vec::new();
+    let mut num_rows = 0;
+    for (source, (line, row)) in rows.enumerate() {
+        num_rows += 1;
+        let Some(expected) = labels.get(source) else {
+            return Err(format!(
+                "Row {line}: there are more rows than the {} locations in the first row",
+                labels.len(),
+            ));
+        };
+        if row.len() != labels.len() + 1 {
+            return Err(format!(
+                "Row {line}: expected {} columns, found {}",
+                labels.len() + 1,
+                row.len(),
+            ));
+        }
+        if &row[0] != expected {
+            return Err(format!(
+                "Row {line}, column 1: expected location '{expected}' to match the order of the \
+                 first row, found '{}'",
+                row[0],
+            ));
+        }
+
+        for (target, cell) in row[1..].iter().enumerate() {
+            let location = format!("Row {line}, column {}", target + 2);
+            let latency_ms: f64 = cell
+                .parse()
+                .map_err(|_| format!("{location}: '{cell}' is not a number"))?;
+            let latency_ns = (latency_ms * 1_000_000.0).round();
+            if !latency_ns.is_finite() || latency_ns < 1.0 || latency_ns > u64::MAX as f64 {
+                return Err(format!(
+                    "{location}: the latency must be greater than 0 ms, found '{cell}'"
+                ));
+            }
+
+            edges.push(ShadowEdge {
+                source: source.try_into().unwrap(),
+                target: target.try_into().unwrap(),
+                latency: units::Time::new(latency_ns as u64, units::TimePrefix::Nano),
+                jitter: units::Time::new(0, units::TimePrefix::Milli),
+                packet_loss: 0.0,
+                bandwidth: None,
+                queue_size: None,
+            });
+        }
+    }
+
+    if num_rows != labels.len() {
+        return Err(format!(
+            "Expected {} rows of latencies, found {num_rows}",
+            labels.len(),
+        ));
+    }
+
+    Ok(GraphParts {
+        directed: true,
+        nodes,
+        edges,
+    })
+}
+
+/// Split CSV text into rows of trimmed fields, with the line number that each row starts on.
+/// Fields may be quoted with `"`, and a quote within a quoted field is escaped as `""`. Empty lines
+/// are skipped.
+fn csv_rows(text: &str) -> Result<Vec<(usize, Vec<String>)>, String> {
+    let mut rows = Vec::new();
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+
+    while chars.peek().is_some() {
+        let start_line = line;
+        let mut row = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+        let mut was_quoted = false;
+
+        loop {
+            let c = chars.next();
+            match (c, quoted) {
+                (Some('"'), true) if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                (Some('"'), true) => quoted = false,
+                (Some('"'), false) if field.trim().is_empty() && !was_quoted => {
+                    field.clear();
+                    quoted = true;
+                    was_quoted = true;
+                }
+                (Some('\n'), true) => {
+                    line += 1;
+                    field.push('\n');
+                }
+                (Some(c), true) => field.push(c),
+                (Some(','), false) => {
+                    row.push(std::mem::take(&mut field).trim().to_string());
+                    was_quoted = false;
+                }
+                (Some('\n') | None, false) => {
+                    if c.is_some() {
+                        line += 1;
+                    }
+                    row.push(std::mem::take(&mut field).trim().to_string());
+                    break;
+                }
+                (Some(c), false) => field.push(c),
+                (None, true) => return Err(format!("Row {start_line}: unterminated quoted field")),
+            }
+        }
+
+        if row.len() > 1 || !row[0].is_empty() || was_quoted {
+            rows.push((start_line, row));
+        }
+    }
+
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utility::units::Unit;
+
+    /// The nodes and edges of a GML graph, to compare with the other formats.
+    fn gml_parts(text: &str) -> GraphParts {
+        let graph = gml_parser::parse(text).unwrap();
+        GraphParts {
+            directed: graph.directed,
+            nodes: graph
+                .nodes
+                .into_iter()
+                .map(|x| x.try_into().unwrap())
+                .collect(),
+            edges: graph
+                .edges
+                .into_iter()
+                .map(|x| x.try_into().unwrap())
+                .collect(),
+        }
+    }
+
+    fn assert_same(a: &GraphParts, b: &GraphParts) {
+        assert_eq!(a.directed, b.directed);
+        assert_eq!(a.nodes, b.nodes);
+        assert_eq!(a.edges, b.edges);
+    }
+
+    const GML: &str = r#"graph [
+      directed 0
+      node [
+        id 0
+        host_bandwidth_down "100 Mbit"
+        host_bandwidth_up "10 Mbit"
+      ]
+      node [
+        id 1
+        host_bandwidth_down "100 Mbit"
+        host_bandwidth_up "100 Mbit"
+      ]
+      edge [
+        source 0
+        target 1
+        latency "10 ms"
+        packet_loss 0.5
+      ]
+      edge [
+        source 1
+        target 1
+        latency "1 ms"
+        packet_loss 0.0
+      ]
+    ]"#;
+
+    const GRAPHML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+  <key id="d0" for="node" attr.name="host_bandwidth_down" attr.type="string">
+    <default>100 Mbit</default>
+  </key>
+  <key id="d1" for="node" attr.name="host_bandwidth_up" attr.type="string"/>
+  <key id="d2" for="edge" attr.name="latency" attr.type="string"/>
+  <key id="d3" for="edge" attr.name="packet_loss" attr.type="double"/>
+  <graph edgedefault="undirected">
+    <node id="0">
+      <data key="d1">10 Mbit</data>
+    </node>
+    <node id="1">
+      <data key="d1">100 Mbit</data>
+    </node>
+    <edge source="0" target="1">
+      <data key="d2">10 ms</data>
+      <data key="d3">0.5</data>
+    </edge>
+    <edge source="1" target="1">
+      <data key="d2">1 ms</data>
+      <data key="d3">0</data>
+    </edge>
+  </graph>
+</graphml>"#;
+
+    const JSON: &str = r#"{
+      "directed": false,
+      "multigraph": false,
+      "graph": {},
+      "nodes": [
+        {"id": 0, "host_bandwidth_down": "100 Mbit", "host_bandwidth_up": "10 Mbit"},
+        {"id": 1, "host_bandwidth_down": "100 Mbit", "host_bandwidth_up": "100 Mbit"}
+      ],
+      "links": [
+        {"source": 0, "target": 1, "latency": "10 ms", "packet_loss": 0.5},
+        {"source": 1, "target": 1, "latency": "1 ms", "packet_loss": 0}
+      ]
+    }"#;
+
+    #[test]
+    fn test_graphml() {
+        assert_same(&parse_graphml(GRAPHML).unwrap(), &gml_parts(GML));
+    }
+
+    #[test]
+    fn test_graphml_errors() {
+        let err =
+            parse_graphml(&GRAPHML.replace(r#"<node id="1">"#, r#"<node id="n1">"#)).unwrap_err();
+        assert_eq!(
+            err,
+            "<node> at line 13, column 5: node ID 'n1' is not a non-negative integer"
+        );
+
+        let err = parse_graphml(&GRAPHML.replace(">10 ms<", ">ten ms<")).unwrap_err();
+        assert!(
+            err.starts_with("<edge> at line 16, column 5: Edge 'latency'"),
+            "{err}"
+        );
+
+        let err = parse_graphml(&GRAPHML.replace(">0.5<", ">half<")).unwrap_err();
+        assert_eq!(
+            err,
+            "<data> at line 18, column 7: 'half' is not a valid double"
+        );
+
+        let err = parse_graphml(&GRAPHML.replace(r#"target="1">"#, r#"target="2">"#)).unwrap_err();
+        assert_eq!(err, "<edge> at line 16, column 5: node 2 doesn't exist");
+    }
+
+    #[test]
+    fn test_json() {
+        assert_same(&parse_json(JSON).unwrap(), &gml_parts(GML));
+
+        // newer versions of NetworkX use "edges" instead of "links"
+        let json = JSON.replace(r#""links""#, r#""edges""#);
+        assert_same(&parse_json(&json).unwrap(), &gml_parts(GML));
+    }
+
+    #[test]
+    fn test_json_errors() {
+        let err = parse_json(&JSON.replace(r#""id": 1"#, r#""id": -1"#)).unwrap_err();
+        assert_eq!(err, "nodes[1]: 'id' is not a non-negative integer");
+
+        let err = parse_json(&JSON.replace(r#""latency": "1 ms""#, r#""latency": 1"#)).unwrap_err();
+        assert_eq!(err, "links[1]: Edge 'latency' is not a string");
+
+        let err = parse_json(&JSON.replace(r#""target": 1"#, r#""target": 3"#)).unwrap_err();
+        assert_eq!(err, "links[0]: node 3 doesn't exist");
+
+        let err = parse_json("{").unwrap_err();
+        assert!(err.contains("line 1"), "{err}");
+    }
+
+    #[test]
+    fn test_latency_matrix() {
+        let csv = "\
+            city,amsterdam,\"washington, dc\"\n\
+            amsterdam,0.5,80.25\n\
+            \"washington, dc\",81,1\n";
+        let bandwidth = Some("1 Gbit".parse().unwrap());
+        let parts = parse_latency_matrix(csv, bandwidth, bandwidth).unwrap();
+
+        assert!(parts.directed);
+        assert_eq!(parts.nodes.len(), 2);
+        assert_eq!(parts.nodes[1].id, 1);
+        assert_eq!(parts.nodes[1].bandwidth_up, bandwidth);
+
+        let latency_ns = |source, target| {
+            let edge = parts
+                .edges
+                .iter()
+                .find(|x| x.source == source && x.target == target)
+                .unwrap();
+            edge.latency
+                .convert(units::TimePrefix::Nano)
+                .unwrap()
+                .value()
+        };
+        assert_eq!(parts.edges.len(), 4);
+        assert_eq!(latency_ns(0, 0), 500_000);
+        assert_eq!(latency_ns(0, 1), 80_250_000);
+        assert_eq!(latency_ns(1, 0), 81_000_000);
+        assert_eq!(latency_ns(1, 1), 1_000_000);
+    }
+
+    #[test]
+    fn test_latency_matrix_errors() {
+        let parse = |csv| parse_latency_matrix(csv, None, None).unwrap_err();
+
+        assert_eq!(parse(""), "The latency matrix is empty");
+        assert_eq!(
+            parse(",a,b\na,1,2\nb,3,x\n"),
+            "Row 3, column 3: 'x' is not a number"
+        );
+        assert_eq!(
+            parse(",a,b\na,1,2\n\nb,3,0\n"),
+            "Row 4, column 3: the latency must be greater than 0 ms, found '0'"
+        );
+        assert_eq!(
+            parse(",a,b\nb,1,2\na,3,4\n"),
+            "Row 2, column 1: expected location 'a' to match the order of the first row, found 'b'"
+        );
+        assert_eq!(parse(",a,b\na,1\n"), "Row 2: expected 3 columns, found 2");
+        assert_eq!(
+            parse(",a,b\na,1,2\n"),
+            "Expected 2 rows of latencies, found 1"
+        );
+        assert_eq!(parse(",a,a\n"), "Row 1, column 3: duplicate location 'a'");
+        assert_eq!(parse(",a\n\"a,1\n"), "Row 2: unterminated quoted field");
+    }
+}
@@ -1,16 +1,21 @@
+mod formats;
 mod petgraph_wrapper;
 
 use std::collections::HashMap;
 use std::collections::hash_map::Entry;
 use std::error::Error;
 use std::hash::Hash;
+use std::sync::Arc;
 
 use anyhow::Context;
 use log::*;
-use petgraph::graph::NodeIndex;
+use petgraph::graph::{EdgeIndex, EdgeReference, NodeIndex};
+use petgraph::visit::{EdgeFiltered, EdgeRef};
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use shadow_shim_helper_rs::emulated_time::EmulatedTime;
 
 use crate::core::configuration::{self, Compression, FileSource, GraphOptions, GraphSource};
+use crate::network::graph::formats::GraphParts;
 use crate::network::graph::petgraph_wrapper::GraphWrapper;
 use crate::utility::tilde_expansion;
 use crate::utility::units::{self, Unit};
@@ -65,6 +70,8 @@ pub struct ShadowEdge {
     pub latency: units::Time<units::TimePrefix>,
     pub jitter: units::Time<units::TimePrefix>,
     pub packet_loss: f32,
+    pub bandwidth: Option<units::BitsPerSec<units::SiPrefixUpper>>,
+    pub queue_size: Option<units::Bytes<units::SiPrefixUpper>>,
 }
 
 impl TryFrom<gml_parser::gml::Edge<'_>> for ShadowEdge {
@@ -94,6 +101,27 @@ impl TryFrom<gml_parser::gml::Edge<'_>> for ShadowEdge {
                 Some(x) => x.as_float().ok_or("Edge 'packet_loss' is not a float")?,
                 None => 0.0,
             },
+            bandwidth: gml_edge
+                .other
+                .remove("bandwidth")
+                .map(|bandwidth| {
+                    bandwidth
+                        .as_str()
+                        .ok_or("Edge 'bandwidth' is not a string")?
+                        .parse()
+                        .map_err(|e| format!("Edge 'bandwidth' is not a valid unit: {e}"))
+                })
+                .transpose()?,
+            queue_size: gml_edge
+                .other
+                .remove("queue_size")
+                .map(|size| {
+                    size.as_str()
+                        .ok_or("Edge 'queue_size' is not a string")?
+                        .parse()
+                        .map_err(|e| format!("Edge 'queue_size' is not a valid unit: {e}"))
+                })
+                .transpose()?,
         };
 
         if rv.packet_loss < 0f32 || rv.packet_loss > 1f32 {
@@ -104,10 +132,74 @@ impl TryFrom<gml_parser::gml::Edge<'_>> for ShadowEdge {
             return Err("Edge 'latency' must not be 0".into());
         }
 
+        if rv.bandwidth.is_some_and(|x| x.value() == 0) {
+            return Err("Edge 'bandwidth' must not be 0".into());
+        }
+
+        if let Some(queue_size) = rv.queue_size {
+            if rv.bandwidth.is_none() {
+                return Err("Edge 'queue_size' was provided without a 'bandwidth'".into());
+            }
+            if queue_size.value() == 0 {
+                return Err("Edge 'queue_size' must not be 0".into());
+            }
+        }
+
         Ok(rv)
     }
 }
 
+impl ShadowEdge {
+    /// The properties of the edge's link, or `None` if the edge has no bandwidth.
+    pub fn link_properties(&self) -> Option<LinkProperties> {
+        let bandwidth_bits = self
+            .bandwidth?
+            .convert(units::SiPrefixUpper::Base)
+            .unwrap()
+            .value();
+
+        // by default, the queue holds 100 ms of packets at the link's bandwidth
+        let queue_bytes = match self.queue_size {
+            Some(size) => size.convert(units::SiPrefixUpper::Base).unwrap().value(),
+            None => std::cmp::max(bandwidth_bits / 8 / 10, 1),
+        };
+
+        Some(LinkProperties {
+            bandwidth_bits,
+            queue_bytes,
+        })
+    }
+}
+
+/// The capacity of a graph edge that has a bandwidth. Packets from all hosts that travel over the
+/// edge in the same direction share this capacity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LinkProperties {
+    /// Bandwidth in bits per second.
+    pub bandwidth_bits: u64,
+    /// The number of bytes that can wait in the edge's queue before packets are dropped.
+    pub queue_bytes: u64,
+}
+
+/// One direction of travel over a graph edge that has a bandwidth. Packets travelling in opposite
+/// directions over an undirected edge don't share its bandwidth.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Link {
+    pub edge: EdgeIndex<u32>,
+    /// The GML ID of the node that packets travel from.
+    pub source: u32,
+    /// The GML ID of the node that packets travel to.
+    pub target: u32,
+}
+
+/// A link on the path between two nodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathLink {
+    pub link: Link,
+    /// The latency from the start of the path to the link, in nanoseconds.
+    pub offset_ns: u64,
+}
+
 /// A network graph containing the petgraph graph and a map from gml node ids to petgraph node
 /// indexes.
 #[derive(Debug)]
@@ -129,21 +221,39 @@ impl NetworkGraph {
         self.graph.node_weight(index).map(|w| w.id)
     }
 
+    /// Parse a graph in the GML format.
     pub fn parse(graph_text: &str) -> Result<Self, NetGraphError> {
         let gml_graph = gml_parser::parse(graph_text)?;
 
-        let mut g = match gml_graph.directed {
+        Self::from_parts(GraphParts {
+            directed: gml_graph.directed,
+            nodes: gml_graph
+                .nodes
+                .into_iter()
+                .map(ShadowNode::try_from)
+                .collect::<Result<_, _>>()?,
+            edges: gml_graph
+                .edges
+                .into_iter()
+                .map(ShadowEdge::try_from)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Build a graph from nodes and edges that were parsed from any of the graph formats.
+    fn from_parts(parts: GraphParts) -> Result<Self, NetGraphError> {
+        let mut g = match parts.directed {
             true => GraphWrapper::Directed(
                 petgraph::graph::Graph::<_, _, petgraph::Directed, _>::with_capacity(
-                    gml_graph.nodes.len(),
-                    gml_graph.edges.len(),
+                    parts.nodes.len(),
+                    parts.edges.len(),
                 ),
             ),
             false => {
                 GraphWrapper::Undirected(
                     petgraph::graph::Graph::<_, _, petgraph::Undirected, _>::with_capacity(
-                        gml_graph.nodes.len(),
-                        gml_graph.edges.len(),
+                        parts.nodes.len(),
+                        parts.edges.len(),
                     ),
                 )
             }
@@ -152,16 +262,13 @@ impl NetworkGraph {
         // map from GML id to petgraph id
         let mut id_map = HashMap::new();
 
-        for x in gml_graph.nodes.into_iter() {
-            let x: ShadowNode = x.try_into()?;
+        for x in parts.nodes.into_iter() {
             let gml_id = x.id;
             let petgraph_id = g.add_node(x);
             id_map.insert(gml_id, petgraph_id);
         }
 
-        for x in gml_graph.edges.into_iter() {
-            let x: ShadowEdge = x.try_into()?;
-
+        for x in parts.edges.into_iter() {
             let source = *id_map
                 .get(&x.source)
                 .ok_or(format!("Edge source {} doesn't exist", x.source))?;
@@ -249,6 +356,265 @@ impl NetworkGraph {
         Ok(paths)
     }
 
+    /// Get the paths between nodes after some edges have been changed. Each edge in `overrides`
+    /// has new path properties, or `None` if the edge can't be used. Unlike
+    /// [`compute_shortest_paths`](Self::compute_shortest_paths) and
+    /// [`get_direct_paths`](Self::get_direct_paths), the graph doesn't need to be connected and
+    /// paths between nodes that aren't connected are omitted.
+    pub fn compute_paths_with_overrides(
+        &self,
+        nodes: &[NodeIndex],
+        use_shortest_paths: bool,
+        overrides: &HashMap<EdgeIndex<u32>, Option<PathProperties>>,
+    ) -> HashMap<(NodeIndex, NodeIndex), PathProperties> {
+        let properties = |edge: EdgeIndex<u32>, weight: &ShadowEdge| match overrides.get(&edge) {
+            Some(x) => *x,
+            None => Some(weight.into()),
+        };
+
+        // the properties of the edge from `src` to `dst`, which is used for direct paths and for
+        // paths from a node to itself
+        let edge_properties = |src: NodeIndex, dst: NodeIndex| {
+            let edge = self.graph.find_edge(src, dst)?;
+            properties(edge, self.graph.edge_weight(edge).unwrap())
+        };
+
+        if !use_shortest_paths {
+            return nodes
+                .iter()
+                .flat_map(|src| nodes.iter().map(move |dst| (*src, *dst)))
+                .filter_map(|(src, dst)| Some(((src, dst), edge_properties(src, dst)?)))
+                .collect();
+        }
+
+        nodes
+            .into_par_iter()
+            .flat_map(|src| {
+                let usable =
+                    |e: EdgeReference<ShadowEdge, u32>| properties(e.id(), e.weight()).is_some();
+                let cost =
+                    |e: EdgeReference<ShadowEdge, u32>| properties(e.id(), e.weight()).unwrap();
+                let mut paths = match &self.graph {
+                    GraphWrapper::Directed(graph) => petgraph::algo::dijkstra(
+                        &EdgeFiltered::from_fn(graph, usable),
+                        *src,
+                        None,
+                        cost,
+                    ),
+                    GraphWrapper::Undirected(graph) => petgraph::algo::dijkstra(
+                        &EdgeFiltered::from_fn(graph, usable),
+                        *src,
+                        None,
+                        cost,
+                    ),
+                }
+                .into_iter()
+                // ignore nodes that aren't in use, and the src node
+                .filter(|(dst, _)| nodes.contains(dst) && dst != src)
+                .map(|(dst, path)| ((*src, dst), path))
+                .collect::<HashMap<(_, _), _>>();
+
+                if let Some(path) = edge_properties(*src, *src) {
+                    paths.insert((*src, *src), path);
+                }
+
+                paths
+            })
+            .collect()
+    }
+
+    /// Get every link in the graph, which is each direction of travel over an edge that has a
+    /// bandwidth, and its properties.
+    pub fn links(&self) -> Vec<(Link, LinkProperties)> {
+        let node_id = |x| self.node_index_to_id(x).unwrap();
+        let (edges, directed) = match &self.graph {
+            GraphWrapper::Directed(graph) => (graph.raw_edges(), true),
+            GraphWrapper::Undirected(graph) => (graph.raw_edges(), false),
+        };
+
+        let mut links = Vec::new();
+        for (i, edge) in edges.iter().enumerate() {
+            let Some(properties) = edge.weight.link_properties() else {
+                continue;
+            };
+            let (source, target) = (node_id(edge.source()), node_id(edge.target()));
+            let edge = EdgeIndex::new(i);
+
+            links.push((
+                Link {
+                    edge,
+                    source,
+                    target,
+                },
+                properties,
+            ));
+            if !directed && source != target {
+                let (source, target) = (target, source);
+                links.push((
+                    Link {
+                        edge,
+                        source,
+                        target,
+                    },
+                    properties,
+                ));
+            }
+        }
+
+        links
+    }
+
+    /// Get the links on the paths between nodes, using the same paths as
+    /// [`compute_paths_with_overrides`](Self::compute_paths_with_overrides). Paths that don't
+    /// travel over any links are omitted.
+    pub fn compute_links_with_overrides(
+        &self,
+        nodes: &[NodeIndex],
+        use_shortest_paths: bool,
+        overrides: &HashMap<EdgeIndex<u32>, Option<PathProperties>>,
+    ) -> HashMap<(NodeIndex, NodeIndex), Vec<PathLink>> {
+        let (edges, directed) = match &self.graph {
+            GraphWrapper::Directed(graph) => (graph.raw_edges(), true),
+            GraphWrapper::Undirected(graph) => (graph.raw_edges(), false),
+        };
+
+        // the common case, and there's no need to search the graph
+        if edges.iter().all(|e| e.weight.bandwidth.is_none()) {
+            return HashMap::new();
+        }
+
+        let properties = |edge: EdgeIndex<u32>, weight: &ShadowEdge| match overrides.get(&edge) {
+            Some(x) => *x,
+            None => Some(weight.into()),
+        };
+
+        // the link when travelling over `edge` from node `from` to node `to`
+        let link = |edge: EdgeIndex<u32>, from: NodeIndex, to: NodeIndex| {
+            self.graph.edge_weight(edge).unwrap().bandwidth?;
+            Some(Link {
+                edge,
+                source: self.node_index_to_id(from).unwrap(),
+                target: self.node_index_to_id(to).unwrap(),
+            })
+        };
+
+        if !use_shortest_paths {
+            return nodes
+                .iter()
+                .flat_map(|src| nodes.iter().map(move |dst| (*src, *dst)))
+                .filter_map(|(src, dst)| {
+                    let edge = self.graph.find_edge(src, dst)?;
+                    properties(edge, self.graph.edge_weight(edge).unwrap())?;
+                    let link = link(edge, src, dst)?;
+                    Some(((src, dst), vec![PathLink { link, offset_ns: 0 }]))
+                })
+                .collect();
+        }
+
+        // the usable edges into each node, and the nodes that they're from
+        let mut edges_into: HashMap<NodeIndex, Vec<(EdgeIndex<u32>, NodeIndex)>> = HashMap::new();
+        for (i, edge) in edges.iter().enumerate() {
+            let id = EdgeIndex::new(i);
+            if edge.source() == edge.target() || properties(id, &edge.weight).is_none() {
+                continue;
+            }
+            edges_into
+                .entry(edge.target())
+                .or_default()
+                .push((id, edge.source()));
+            if !directed {
+                edges_into
+                    .entry(edge.source())
+                    .or_default()
+                    .push((id, edge.target()));
+            }
+        }
+
+        nodes
+            .into_par_iter()
+            .flat_map(|src| {
+                let usable =
+                    |e: EdgeReference<ShadowEdge, u32>| properties(e.id(), e.weight()).is_some();
+                let cost =
+                    |e: EdgeReference<ShadowEdge, u32>| properties(e.id(), e.weight()).unwrap();
+                let distances = match &self.graph {
+                    GraphWrapper::Directed(graph) => petgraph::algo::dijkstra(
+                        &EdgeFiltered::from_fn(graph, usable),
+                        *src,
+                        None,
+                        cost,
+                    ),
+                    GraphWrapper::Undirected(graph) => petgraph::algo::dijkstra(
+                        &EdgeFiltered::from_fn(graph, usable),
+                        *src,
+                        None,
+                        cost,
+                    ),
+                };
+
+                let mut links = HashMap::new();
+                for dst in nodes.iter().filter(|dst| *dst != src) {
+                    let Some(distance) = distances.get(dst) else {
+                        continue;
+                    };
+
+                    // walk backwards from the destination, following any edge that is on a
+                    // shortest path; the latency of each edge is non-zero, so this ends at `src`
+                    let mut path = Vec::new();
+                    let (mut node, mut distance) = (*dst, *distance);
+                    while node != *src {
+                        let (edge, prev, prev_distance) = edges_into[&node]
+                            .iter()
+                            .find_map(|(edge, prev)| {
+                                let prev_distance = *distances.get(prev)?;
+                                let weight = self.graph.edge_weight(*edge).unwrap();
+                                let weight = properties(*edge, weight).unwrap();
+                                (prev_distance + weight == distance).then_some((
+                                    *edge,
+                                    *prev,
+                                    prev_distance,
+                                ))
+                            })
+                            .unwrap();
+                        if let Some(link) = link(edge, prev, node) {
+                            path.push(PathLink {
+                                link,
+                                offset_ns: prev_distance.latency_ns,
+                            });
+                        }
+                        (node, distance) = (prev, prev_distance);
+                    }
+
+                    if !path.is_empty() {
+                        path.reverse();
+                        links.insert((*src, *dst), path);
+                    }
+                }
+
+                if let Some(edge) = self.graph.find_edge(*src, *src)
+                    && properties(edge, self.graph.edge_weight(edge).unwrap()).is_some()
+                    && let Some(link) = link(edge, *src, *src)
+                {
+                    links.insert((*src, *src), vec![PathLink { link, offset_ns: 0 }]);
+                }
+
+                links
+            })
+            .collect()
+    }
+
+    /// Get the edge from one node to another, and its path properties. Returns an error if there
+    /// is not exactly one edge between them.
+    pub fn get_edge(
+        &self,
+        src: &NodeIndex,
+        dst: &NodeIndex,
+    ) -> Result<(EdgeIndex<u32>, PathProperties), NetGraphError> {
+        let weight = self.get_edge_weight(src, dst)?;
+        let edge = self.graph.find_edge(*src, *dst).unwrap();
+        Ok((edge, weight.into()))
+    }
+
     /// Get the weight for the edge between two nodes. Returns an error if there
     /// is not exactly one edge between them.
     fn get_edge_weight(
@@ -294,6 +660,8 @@ pub struct PathProperties {
     pub latency_ns: u64,
     /// Packet loss as fraction.
     pub packet_loss: f32,
+    /// Jitter in nanoseconds, which is the sum of the jitter of each edge in the path.
+    pub jitter_ns: u64,
 }
 
 impl PartialOrd for PathProperties {
@@ -320,6 +688,7 @@ impl core::ops::Add for PathProperties {
         Self {
             latency_ns: self.latency_ns + other.latency_ns,
             packet_loss: 1f32 - (1f32 - self.packet_loss) * (1f32 - other.packet_loss),
+            jitter_ns: self.jitter_ns + other.jitter_ns,
         }
     }
 }
@@ -329,6 +698,7 @@ impl std::convert::From<&ShadowEdge> for PathProperties {
         Self {
             latency_ns: e.latency.convert(units::TimePrefix::Nano).unwrap().value(),
             packet_loss: e.packet_loss,
+            jitter_ns: e.jitter.convert(units::TimePrefix::Nano).unwrap().value(),
         }
     }
 }
@@ -350,6 +720,8 @@ pub struct IpAssignment<T: Copy + Eq + Hash + std::fmt::Display> {
     map: HashMap<std::net::IpAddr, T>,
     /// The last dynamically assigned address.
     last_assigned_addr: std::net::IpAddr,
+    /// The last dynamically assigned IPv6 address.
+    last_assigned_addr_v6: std::net::IpAddr,
 }
 
 impl<T: Copy + Eq + Hash + std::fmt::Display> IpAssignment<T> {
@@ -357,16 +729,33 @@ impl<T: Copy + Eq + Hash + std::fmt::Display> IpAssignment<T> {
         Self {
             map: HashMap::new(),
             last_assigned_addr: std::net::IpAddr::V4(std::net::Ipv4Addr::new(11, 0, 0, 0)),
+            // a unique local address (fc00::/7) prefix
+            last_assigned_addr_v6: std::net::IpAddr::V6(std::net::Ipv6Addr::new(
+                0xfd00, 0, 0, 0, 0, 0, 0, 0,
+            )),
         }
     }
 
     /// Get an unused address and assign it to a node.
     pub fn assign(&mut self, node_id: T) -> std::net::IpAddr {
+        Self::assign_next(&mut self.map, &mut self.last_assigned_addr, node_id)
+    }
+
+    /// Get an unused IPv6 address and assign it to a node.
+    pub fn assign_v6(&mut self, node_id: T) -> std::net::IpAddr {
+        Self::assign_next(&mut self.map, &mut self.last_assigned_addr_v6, node_id)
+    }
+
+    fn assign_next(
+        map: &mut HashMap<std::net::IpAddr, T>,
+        last_assigned_addr: &mut std::net::IpAddr,
+        node_id: T,
+    ) -> std::net::IpAddr {
         // loop until we find an unused address
         loop {
-            let ip_addr = Self::increment_address(&self.last_assigned_addr);
-            self.last_assigned_addr = ip_addr;
-            if let std::collections::hash_map::Entry::Vacant(e) = self.map.entry(ip_addr) {
+            let ip_addr = Self::increment_address(last_assigned_addr);
+            *last_assigned_addr = ip_addr;
+            if let std::collections::hash_map::Entry::Vacant(e) = map.entry(ip_addr) {
                 e.insert(node_id);
                 break ip_addr;
             }
@@ -412,7 +801,19 @@ impl<T: Copy + Eq + Hash + std::fmt::Display> IpAssignment<T> {
                     }
                 }
             }
-            std::net::IpAddr::V6(_) => unimplemented!(),
+            std::net::IpAddr::V6(x) => {
+                let addr_bits = u128::from(*x);
+                let mut increment = 1;
+                loop {
+                    // increment the address
+                    let next_addr = std::net::Ipv6Addr::from(addr_bits + increment);
+                    match next_addr.segments()[7] {
+                        // if the address ends in "::0" (subnet-router anycast), try the next
+                        0 => increment += 1,
+                        _ => break std::net::IpAddr::V6(next_addr),
+                    }
+                }
+            }
         }
     }
 }
@@ -427,6 +828,13 @@ impl<T: Copy + Eq + Hash + std::fmt::Display> Default for IpAssignment<T> {
 #[derive(Debug)]
 pub struct RoutingInfo<T: Eq + Hash + std::fmt::Display + Clone + Copy> {
     paths: HashMap<(T, T), PathProperties>,
+    /// Paths after changes to the network, sorted by the time they take effect. Pairs of nodes
+    /// without a path can't communicate during that period.
+    changed_paths: std::sync::RwLock<Vec<(EmulatedTime, HashMap<(T, T), PathProperties>)>>,
+    /// The links on each path, for paths that travel over at least one link.
+    links: HashMap<(T, T), Arc<[PathLink]>>,
+    /// The links on each path after changes to the network, sorted by the time they take effect.
+    changed_links: std::sync::RwLock<Vec<(EmulatedTime, HashMap<(T, T), Arc<[PathLink]>>)>>,
     packet_counters: std::sync::RwLock<HashMap<(T, T), u64>>,
 }
 
@@ -434,15 +842,113 @@ impl<T: Eq + Hash + std::fmt::Display + Clone + Copy> RoutingInfo<T> {
     pub fn new(paths: HashMap<(T, T), PathProperties>) -> Self {
         Self {
             paths,
+            changed_paths: std::sync::RwLock::new(Vec::new()),
+            links: HashMap::new(),
+            changed_links: std::sync::RwLock::new(Vec::new()),
             packet_counters: std::sync::RwLock::new(HashMap::new()),
         }
     }
 
-    /// Get properties for the path from one node to another.
+    /// Set the links on each path at the start of the simulation.
+    pub fn with_links(mut self, links: HashMap<(T, T), Arc<[PathLink]>>) -> Self {
+        self.links = links;
+        self
+    }
+
+    /// Get properties for the path from one node to another at the start of the simulation.
     pub fn path(&self, start: T, end: T) -> Option<PathProperties> {
         self.paths.get(&(start, end)).copied()
     }
 
+    /// Get properties for the path from one node to another at time `time`. Returns `None` if
+    /// packets can't be routed between the nodes at that time.
+    pub fn path_at(&self, start: T, end: T, time: EmulatedTime) -> Option<PathProperties> {
+        let changed_paths = self.changed_paths.read().unwrap();
+        let idx = changed_paths.partition_point(|(t, _)| *t <= time);
+        match idx.checked_sub(1) {
+            Some(idx) => changed_paths[idx].1.get(&(start, end)).copied(),
+            None => self.path(start, end),
+        }
+    }
+
+    /// Replace the changed paths that take effect at or after `time` with `changed_paths`, which
+    /// must be sorted by time and take effect at or after `time`.
+    pub fn set_changed_paths_from(
+        &self,
+        time: EmulatedTime,
+        changed_paths: Vec<(EmulatedTime, HashMap<(T, T), PathProperties>)>,
+    ) {
+        assert!(changed_paths.is_sorted_by_key(|(t, _)| *t));
+        assert!(changed_paths.iter().all(|(t, _)| *t >= time));
+
+        let mut current = self.changed_paths.write().unwrap();
+        current.retain(|(t, _)| *t < time);
+        current.extend(changed_paths);
+    }
+
+    /// Get the links on the path from one node to another at time `time`. Returns `None` if the
+    /// path doesn't travel over any links.
+    pub fn links_at(&self, start: T, end: T, time: EmulatedTime) -> Option<Arc<[PathLink]>> {
+        let changed_links = self.changed_links.read().unwrap();
+        let idx = changed_links.partition_point(|(t, _)| *t <= time);
+        match idx.checked_sub(1) {
+            Some(idx) => changed_links[idx].1.get(&(start, end)).cloned(),
+            None => self.links.get(&(start, end)).cloned(),
+        }
+    }
+
+    /// Replace the changed links that take effect at or after `time` with `changed_links`, which
+    /// must be sorted by time and take effect at or after `time`. This should be called along
+    /// with [`set_changed_paths_from`](Self::set_changed_paths_from).
+    pub fn set_changed_links_from(
+        &self,
+        time: EmulatedTime,
+        changed_links: Vec<(EmulatedTime, HashMap<(T, T), Arc<[PathLink]>>)>,
+    ) {
+        assert!(changed_links.is_sorted_by_key(|(t, _)| *t));
+        assert!(changed_links.iter().all(|(t, _)| *t >= time));
+
+        let mut current = self.changed_links.write().unwrap();
+        current.retain(|(t, _)| *t < time);
+        current.extend(changed_links);
+    }
+
+    /// Get the smallest value of `f` for each path over the whole simulation, including the paths
+    /// after each change to the network. Pairs of nodes without any path are omitted.
+    pub fn min_over_time(&self, f: impl Fn(&PathProperties) -> u64) -> HashMap<(T, T), u64> {
+        let changed_paths = self.changed_paths.read().unwrap();
+        let mut min = HashMap::new();
+        for (nodes, path) in self
+            .paths
+            .iter()
+            .chain(changed_paths.iter().flat_map(|(_, paths)| paths.iter()))
+        {
+            let value = f(path);
+            min.entry(*nodes)
+                .and_modify(|x: &mut u64| *x = std::cmp::min(*x, value))
+                .or_insert(value);
+        }
+        min
+    }
+
+    /// Get the smallest latency from the start of any path to each link over the whole
+    /// simulation, in nanoseconds. Links that aren't on any path are omitted.
+    pub fn min_link_offsets_ns(&self) -> HashMap<Link, u64> {
+        let changed_links = self.changed_links.read().unwrap();
+        let mut min = HashMap::new();
+        for link in self
+            .links
+            .values()
+            .chain(changed_links.iter().flat_map(|(_, links)| links.values()))
+            .flat_map(|links| links.iter())
+        {
+            min.entry(link.link)
+                .and_modify(|x: &mut u64| *x = std::cmp::min(*x, link.offset_ns))
+                .or_insert(link.offset_ns);
+        }
+        min
+    }
+
     /// Increment the number of packets sent from one node to another.
     pub fn increment_packet_count(&self, start: T, end: T) {
         let key = (start, end);
@@ -469,8 +975,14 @@ impl<T: Eq + Hash + std::fmt::Display + Clone + Copy> RoutingInfo<T> {
         }
     }
 
+    /// The smallest latency of any path, including paths after changes to the network.
     pub fn get_smallest_latency_ns(&self) -> Option<u64> {
-        self.paths.values().map(|x| x.latency_ns).min()
+        let changed_paths = self.changed_paths.read().unwrap();
+        self.paths
+            .values()
+            .chain(changed_paths.iter().flat_map(|(_, paths)| paths.values()))
+            .map(|x| x.latency_ns)
+            .min()
     }
 }
 
@@ -489,25 +1001,49 @@ fn read_xz<P: AsRef<std::path::Path>>(path: P) -> Result<String, NetGraphError>
     Ok(String::from_utf8(decomp)?)
 }
 
-/// Get the network graph as a string.
-pub fn load_network_graph(graph_options: &GraphOptions) -> Result<String, NetGraphError> {
-    Ok(match graph_options {
-        GraphOptions::Gml(GraphSource::File(FileSource {
+/// Get the contents of a graph file or inline graph.
+fn read_graph_source(source: &GraphSource) -> Result<String, NetGraphError> {
+    Ok(match source {
+        GraphSource::File(FileSource {
             compression: None,
             path: f,
-        })) => std::fs::read_to_string(tilde_expansion(f))
+        }) => std::fs::read_to_string(tilde_expansion(f))
             .with_context(|| format!("Failed to read file: {f}"))?,
-        GraphOptions::Gml(GraphSource::File(FileSource {
+        GraphSource::File(FileSource {
             compression: Some(Compression::Xz),
             path: f,
-        })) => read_xz(tilde_expansion(f))?,
-        GraphOptions::Gml(GraphSource::Inline(s)) => s.clone(),
-        GraphOptions::OneGbitSwitch => configuration::ONE_GBIT_SWITCH_GRAPH.to_string(),
+        }) => read_xz(tilde_expansion(f))?,
+        GraphSource::Inline(s) => s.clone(),
     })
 }
 
+/// Load and parse the network graph in any of the supported formats.
+pub fn load_network_graph(graph_options: &GraphOptions) -> Result<NetworkGraph, NetGraphError> {
+    let parts = match graph_options {
+        GraphOptions::Gml(source) => return NetworkGraph::parse(&read_graph_source(source)?),
+        GraphOptions::Graphml(source) => formats::parse_graphml(&read_graph_source(source)?)?,
+        GraphOptions::Json(source) => formats::parse_json(&read_graph_source(source)?)?,
+        GraphOptions::LatencyMatrix {
+            source,
+            host_bandwidth_down,
+            host_bandwidth_up,
+        } => formats::parse_latency_matrix(
+            &read_graph_source(source)?,
+            *host_bandwidth_down,
+            *host_bandwidth_up,
+        )?,
+        GraphOptions::OneGbitSwitch => {
+            return NetworkGraph::parse(configuration::ONE_GBIT_SWITCH_GRAPH);
+        }
+    };
+
+    NetworkGraph::from_parts(parts)
+}
+
 #[cfg(test)]
 mod tests {
+    use shadow_shim_helper_rs::simulation_time::SimulationTime;
+
     use super::*;
 
     #[test]
@@ -515,15 +1051,18 @@ mod tests {
         let p1 = PathProperties {
             latency_ns: 23,
             packet_loss: 0.35,
+            jitter_ns: 5,
         };
         let p2 = PathProperties {
             latency_ns: 11,
             packet_loss: 0.85,
+            jitter_ns: 0,
         };
 
         let p3 = p1 + p2;
         assert_eq!(p3.latency_ns, 34);
         assert!((p3.packet_loss - 0.9025).abs() < 0.01);
+        assert_eq!(p3.jitter_ns, 5);
     }
 
     #[test]
@@ -643,6 +1182,250 @@ mod tests {
         }
     }
 
+    // disabled under miri due to https://github.com/rayon-rs/rayon/issues/952
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_paths_with_overrides() {
+        let graph = r#"graph [
+              directed 0
+              node [
+                id 0
+              ]
+              node [
+                id 1
+              ]
+              node [
+                id 2
+              ]
+              edge [
+                source 0
+                target 0
+                latency "1 ns"
+              ]
+              edge [
+                source 1
+                target 1
+                latency "1 ns"
+              ]
+              edge [
+                source 2
+                target 2
+                latency "1 ns"
+              ]
+              edge [
+                source 0
+                target 1
+                latency "3 ns"
+              ]
+              edge [
+                source 0
+                target 2
+                latency "7 ns"
+              ]
+              edge [
+                source 2
+                target 1
+                latency "11 ns"
+              ]
+            ]"#;
+        let graph = NetworkGraph::parse(graph).unwrap();
+        let node_0 = *graph.node_id_to_index(0).unwrap();
+        let node_1 = *graph.node_id_to_index(1).unwrap();
+        let node_2 = *graph.node_id_to_index(2).unwrap();
+        let nodes = [node_0, node_1, node_2];
+
+        let (edge_0_1, _) = graph.get_edge(&node_0, &node_1).unwrap();
+        let (edge_0_2, _) = graph.get_edge(&node_0, &node_2).unwrap();
+        let (edge_2_1, _) = graph.get_edge(&node_2, &node_1).unwrap();
+
+        // take the edge 0-1 down, so packets are routed through node 2
+        let overrides = HashMap::from([(edge_0_1, None)]);
+        let paths = graph.compute_paths_with_overrides(&nodes, true, &overrides);
+        assert_eq!(paths[&(node_0, node_1)].latency_ns, 18);
+        assert_eq!(paths[&(node_1, node_0)].latency_ns, 18);
+        assert_eq!(paths[&(node_0, node_0)].latency_ns, 1);
+
+        // without shortest paths, there's no path between nodes 0 and 1
+        let paths = graph.compute_paths_with_overrides(&nodes, false, &overrides);
+        assert!(!paths.contains_key(&(node_0, node_1)));
+        assert_eq!(paths[&(node_0, node_2)].latency_ns, 7);
+
+        // node 1 is unreachable if all of its edges are down
+        let overrides = HashMap::from([(edge_0_1, None), (edge_2_1, None)]);
+        let paths = graph.compute_paths_with_overrides(&nodes, true, &overrides);
+        assert!(!paths.contains_key(&(node_0, node_1)));
+        assert!(!paths.contains_key(&(node_2, node_1)));
+        assert_eq!(paths[&(node_1, node_1)].latency_ns, 1);
+
+        // a changed latency may change the shortest path
+        let latency = |latency_ns| PathProperties {
+            latency_ns,
+            packet_loss: 0.0,
+            jitter_ns: 0,
+        };
+        let overrides = HashMap::from([(edge_0_2, Some(latency(20)))]);
+        let paths = graph.compute_paths_with_overrides(&nodes, true, &overrides);
+        assert_eq!(paths[&(node_0, node_2)].latency_ns, 14);
+    }
+
+    #[test]
+    fn test_edge_bandwidth() {
+        let parse = |attributes: &str| {
+            let graph = format!(
+                r#"graph [
+                  node [
+                    id 0
+                  ]
+                  edge [
+                    source 0
+                    target 0
+                    latency "1 ms"
+                    {attributes}
+                  ]
+                ]"#
+            );
+            NetworkGraph::parse(&graph).map(|graph| graph.links())
+        };
+
+        assert!(parse("").unwrap().is_empty());
+
+        let links = parse(r#"bandwidth "10 Mbit""#).unwrap();
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].1.bandwidth_bits, 10_000_000);
+        assert_eq!(links[0].1.queue_bytes, 125_000);
+
+        let links = parse(r#"bandwidth "10 Mbit" queue_size "1 MB""#).unwrap();
+        assert_eq!(links[0].1.queue_bytes, 1_000_000);
+
+        parse(r#"bandwidth "0 Mbit""#).unwrap_err();
+        parse(r#"bandwidth "10 Mbit" queue_size "0 B""#).unwrap_err();
+        parse(r#"queue_size "1 MB""#).unwrap_err();
+    }
+
+    // disabled under miri due to https://github.com/rayon-rs/rayon/issues/952
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_links_on_paths() {
+        let graph = r#"graph [
+              directed 0
+              node [
+                id 0
+              ]
+              node [
+                id 1
+              ]
+              node [
+                id 2
+              ]
+              edge [
+                source 0
+                target 0
+                latency "1 ns"
+              ]
+              edge [
+                source 1
+                target 1
+                latency "1 ns"
+                bandwidth "1 Gbit"
+              ]
+              edge [
+                source 2
+                target 2
+                latency "1 ns"
+              ]
+              edge [
+                source 0
+                target 1
+                latency "3 ns"
+                bandwidth "10 Mbit"
+              ]
+              edge [
+                source 1
+                target 2
+                latency "5 ns"
+                bandwidth "1 Gbit"
+              ]
+              edge [
+                source 0
+                target 2
+                latency "20 ns"
+              ]
+            ]"#;
+        let graph = NetworkGraph::parse(graph).unwrap();
+        let node_0 = *graph.node_id_to_index(0).unwrap();
+        let node_1 = *graph.node_id_to_index(1).unwrap();
+        let node_2 = *graph.node_id_to_index(2).unwrap();
+        let nodes = [node_0, node_1, node_2];
+
+        // each direction of an undirected edge is a separate link
+        assert_eq!(graph.links().len(), 5);
+
+        let (edge_0_1, _) = graph.get_edge(&node_0, &node_1).unwrap();
+        let (edge_1_2, _) = graph.get_edge(&node_1, &node_2).unwrap();
+        let hops = |links: &[PathLink]| {
+            links
+                .iter()
+                .map(|x| (x.link.edge, x.link.source, x.link.target, x.offset_ns))
+                .collect::<Vec<_>>()
+        };
+
+        let links = graph.compute_links_with_overrides(&nodes, true, &HashMap::new());
+        assert_eq!(
+            hops(&links[&(node_0, node_2)]),
+            [(edge_0_1, 0, 1, 0), (edge_1_2, 1, 2, 3)],
+        );
+        assert_eq!(
+            hops(&links[&(node_2, node_0)]),
+            [(edge_1_2, 2, 1, 0), (edge_0_1, 1, 0, 5)],
+        );
+        assert_eq!(links[&(node_1, node_1)].len(), 1);
+        assert!(!links.contains_key(&(node_0, node_0)));
+
+        // direct paths only use the edge between the nodes
+        let links = graph.compute_links_with_overrides(&nodes, false, &HashMap::new());
+        assert_eq!(hops(&links[&(node_0, node_1)]), [(edge_0_1, 0, 1, 0)]);
+        assert!(!links.contains_key(&(node_0, node_2)));
+
+        // with the edge 0-1 down, packets from node 0 to node 2 don't travel over any links
+        let overrides = HashMap::from([(edge_0_1, None)]);
+        let links = graph.compute_links_with_overrides(&nodes, true, &overrides);
+        assert!(!links.contains_key(&(node_0, node_2)));
+        assert_eq!(hops(&links[&(node_0, node_1)]), [(edge_1_2, 2, 1, 20)]);
+    }
+
+    #[test]
+    fn test_path_at() {
+        let path = |latency_ns| PathProperties {
+            latency_ns,
+            packet_loss: 0.0,
+            jitter_ns: 0,
+        };
+        let at = |secs| EmulatedTime::SIMULATION_START + SimulationTime::from_secs(secs);
+
+        let routing_info = RoutingInfo::new(HashMap::from([((0, 1), path(1))]));
+        routing_info.set_changed_paths_from(
+            at(0),
+            vec![
+                (at(10), HashMap::new()),
+                (at(20), HashMap::from([((0, 1), path(2))])),
+            ],
+        );
+
+        let latency_at = |secs| routing_info.path_at(0, 1, at(secs)).map(|x| x.latency_ns);
+        assert_eq!(latency_at(0), Some(1));
+        assert_eq!(latency_at(10), None);
+        assert_eq!(latency_at(19), None);
+        assert_eq!(latency_at(20), Some(2));
+        assert_eq!(routing_info.get_smallest_latency_ns(), Some(1));
+
+        // replace the changes from 15 s
+        routing_info
+            .set_changed_paths_from(at(15), vec![(at(15), HashMap::from([((0, 1), path(3))]))]);
+        assert_eq!(latency_at(10), None);
+        assert_eq!(latency_at(15), Some(3));
+        assert_eq!(latency_at(20), Some(3));
+    }
+
     #[test]
     fn test_increment_address_skip_broadcast() {
         let addr = std::net::IpAddr::V4(std::net::Ipv4Addr::new(11, 0, 0, 254));
@@ -653,4 +1436,30 @@ mod tests {
             std::net::IpAddr::V4(std::net::Ipv4Addr::new(11, 0, 0, 255))
         );
     }
+
+    #[test]
+    fn test_increment_address_v6() {
+        let addr = std::net::IpAddr::V6(std::net::Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 0xffff));
+        let incremented = IpAssignment::<i32>::increment_address(&addr);
+        assert_eq!(
+            incremented,
+            std::net::IpAddr::V6(std::net::Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 1, 1))
+        );
+    }
+
+    #[test]
+    fn test_assign_v6() {
+        let mut assignment = IpAssignment::<i32>::new();
+        let ip_1 = assignment.assign_v6(1);
+        let ip_2 = assignment.assign_v6(2);
+        assert!(ip_1.is_ipv6());
+        assert_ne!(ip_1, ip_2);
+        assert_eq!(assignment.get_node(ip_1), Some(1));
+        assert_eq!(assignment.get_node(ip_2), Some(2));
+
+        // ipv4 assignment is independent
+        let ip_3 = assignment.assign(3);
+        assert!(ip_3.is_ipv4());
+        assert_eq!(assignment.get_node(ip_3), Some(3));
+    }
 }
@@ -0,0 +1,134 @@
+//! Random variation of packet latencies.
+
+use rand::Rng;
+
+use crate::core::configuration::JitterDistribution;
+
+/// The shape parameter of the Pareto distribution. A larger shape gives a lighter tail.
+const PARETO_SHAPE: f64 = 3.0;
+
+/// The number of standard deviations below the mean at which the normal distribution is truncated.
+const NORMAL_TRUNCATION: u64 = 3;
+
+/// The smallest delay of a packet on a path with latency `latency_ns` and jitter `jitter_ns`. This
+/// is never less than 1 nanosecond.
+pub fn min_delay_ns(distribution: JitterDistribution, latency_ns: u64, jitter_ns: u64) -> u64 {
+    let max_offset_ns = match distribution {
+        JitterDistribution::Uniform => jitter_ns,
+        JitterDistribution::Normal => jitter_ns.saturating_mul(NORMAL_TRUNCATION),
+        JitterDistribution::Pareto => 0,
+    };
+    std::cmp::max(latency_ns.saturating_sub(max_offset_ns), 1)
+}
+
+/// Sample the delay of a packet on a path with latency `latency_ns` and jitter `jitter_ns`. The
+/// delay is never less than [`min_delay_ns`].
+pub fn sample_delay_ns(
+    distribution: JitterDistribution,
+    latency_ns: u64,
+    jitter_ns: u64,
+    rng: &mut impl Rng,
+) -> u64 {
+    if jitter_ns == 0 {
+        return latency_ns;
+    }
+
+    let offset_ns: f64 = match distribution {
+        JitterDistribution::Uniform => {
+            let jitter_ns = jitter_ns as f64;
+            rng.random_range(-jitter_ns..=jitter_ns)
+        }
+        JitterDistribution::Normal => {
+            // Box-Muller transform; `u1` is in (0, 1] so that its log is finite
+            let u1 = 1.0 - rng.random::<f64>();
+            let u2 = rng.random::<f64>();
+            let z = (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos();
+            z * jitter_ns as f64
+        }
+        JitterDistribution::Pareto => {
+            // a Pareto distribution with minimum `scale` has a mean of `scale * shape / (shape -
+            // 1)`, so we subtract `scale` and choose the scale so that the mean is `jitter_ns`
+            let scale = jitter_ns as f64 * (PARETO_SHAPE - 1.0);
+            let u = 1.0 - rng.random::<f64>();
+            scale * (u.powf(-1.0 / PARETO_SHAPE) - 1.0)
+        }
+    };
+
+    let min_delay_ns = min_delay_ns(distribution, latency_ns, jitter_ns);
+    let delay_ns = (latency_ns as f64 + offset_ns).round();
+    if delay_ns <= min_delay_ns as f64 {
+        min_delay_ns
+    } else if delay_ns >= u64::MAX as f64 {
+        u64::MAX
+    } else {
+        delay_ns as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256PlusPlus;
+
+    use super::*;
+
+    fn samples(distribution: JitterDistribution, latency_ns: u64, jitter_ns: u64) -> Vec<u64> {
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(1);
+        (0..10_000)
+            .map(|_| sample_delay_ns(distribution, latency_ns, jitter_ns, &mut rng))
+            .collect()
+    }
+
+    fn mean(samples: &[u64]) -> f64 {
+        samples.iter().map(|x| *x as f64).sum::<f64>() / samples.len() as f64
+    }
+
+    #[test]
+    fn test_no_jitter() {
+        for distribution in [
+            JitterDistribution::Uniform,
+            JitterDistribution::Normal,
+            JitterDistribution::Pareto,
+        ] {
+            assert!(samples(distribution, 1000, 0).iter().all(|x| *x == 1000));
+        }
+    }
+
+    #[test]
+    fn test_uniform() {
+        let samples = samples(JitterDistribution::Uniform, 1000, 100);
+        assert!(samples.iter().all(|x| (900..=1100).contains(x)));
+        assert!((mean(&samples) - 1000.0).abs() < 5.0);
+    }
+
+    #[test]
+    fn test_normal() {
+        let samples = samples(JitterDistribution::Normal, 1000, 100);
+        assert!((mean(&samples) - 1000.0).abs() < 5.0);
+        let within_one_sd = samples.iter().filter(|x| (900..=1100).contains(*x)).count();
+        assert!((within_one_sd as f64 / samples.len() as f64 - 0.68).abs() < 0.02);
+    }
+
+    #[test]
+    fn test_pareto() {
+        let samples = samples(JitterDistribution::Pareto, 1000, 100);
+        assert!(samples.iter().all(|x| *x >= 1000));
+        assert!((mean(&samples) - 1100.0).abs() < 10.0);
+    }
+
+    #[test]
+    fn test_minimum_delay() {
+        let small_latency = samples(JitterDistribution::Normal, 10, 1000);
+        assert!(small_latency.iter().all(|x| *x >= 1));
+        assert!(small_latency.contains(&1));
+
+        // the normal distribution is truncated
+        let truncated = samples(JitterDistribution::Normal, 1000, 100);
+        assert!(truncated.iter().all(|x| *x >= 700));
+
+        assert_eq!(min_delay_ns(JitterDistribution::Uniform, 1000, 100), 900);
+        assert_eq!(min_delay_ns(JitterDistribution::Normal, 1000, 100), 700);
+        assert_eq!(min_delay_ns(JitterDistribution::Pareto, 1000, 100), 1000);
+        assert_eq!(min_delay_ns(JitterDistribution::Uniform, 10, 100), 1);
+    }
+}
@@ -0,0 +1,437 @@
+//! Queues for the graph edges that have a bandwidth.
+//!
+//! A packet that travels over a link (one direction of an edge with a bandwidth) must wait for
+//! the packets ahead of it in the link's queue, and then for its own transmission at the link's
+//! bandwidth. Packets from every host share the same queue, so hosts that send over the same link
+//! compete for its capacity, and packets that arrive when the queue is full are dropped.
+//!
+//! Hosts run in parallel during a round, so packets can't be queued as they're sent without
+//! making the simulation nondeterministic. Instead they're collected during the round and queued
+//! at the end of the round. Each link queues packets in the order they arrive at it, which depends
+//! on the latency to the link from where each packet was sent. A packet that arrives at a link
+//! after the end of the round could be overtaken by a packet sent in a later round over a path
+//! that reaches the link sooner, so it waits in front of the link until the rounds have advanced
+//! far enough that no later packet can arrive before it.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use shadow_shim_helper_rs::HostId;
+use shadow_shim_helper_rs::emulated_time::EmulatedTime;
+use shadow_shim_helper_rs::simulation_time::SimulationTime;
+
+use crate::network::graph::{Link, LinkProperties, PathLink};
+use crate::network::packet::{PacketRc, PacketStatus};
+
+/// Counters for the packets that travelled over a link.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LinkStats {
+    pub sent_packets: u64,
+    pub sent_bytes: u64,
+    /// Packets that were dropped because the link's queue was full.
+    pub dropped_packets: u64,
+    pub dropped_bytes: u64,
+    pub max_backlog_bytes: u64,
+}
+
+/// The queue of a single link.
+#[derive(Debug)]
+struct LinkQueue {
+    properties: LinkProperties,
+    /// The time that the link finishes transmitting the packets in its queue.
+    busy_until: EmulatedTime,
+    /// The smallest latency from the start of any path to the link. A packet sent at time `t`
+    /// can't arrive at the link before `t + min_offset`.
+    min_offset: SimulationTime,
+    stats: LinkStats,
+}
+
+impl LinkQueue {
+    fn new(properties: LinkProperties) -> Self {
+        Self {
+            properties,
+            busy_until: EmulatedTime::SIMULATION_START,
+            min_offset: SimulationTime::ZERO,
+            stats: LinkStats::default(),
+        }
+    }
+
+    /// Queue a packet of `size` bytes that reaches the link at time `arrival`. Returns the time
+    /// that the link finishes transmitting it, or `None` if the queue is full.
+    fn enqueue(&mut self, arrival: EmulatedTime, size: u64) -> Option<EmulatedTime> {
+        let bandwidth = u128::from(self.properties.bandwidth_bits);
+
+        // the bytes that the link hasn't finished transmitting when the packet arrives
+        let waiting = self.busy_until.saturating_duration_since(&arrival);
+        let backlog = waiting.as_nanos() * bandwidth / 8 / 1_000_000_000;
+        let backlog = u64::try_from(backlog).unwrap();
+
+        // a packet is never dropped if the queue is empty, even if it's larger than the queue
+        if backlog > 0 && backlog + size > self.properties.queue_bytes {
+            self.stats.dropped_packets += 1;
+            self.stats.dropped_bytes += size;
+            return None;
+        }
+
+        let transmit_ns = (u128::from(size) * 8 * 1_000_000_000).div_ceil(bandwidth);
+        let transmit = SimulationTime::from_nanos(u64::try_from(transmit_ns).unwrap());
+
+        self.busy_until = std::cmp::max(arrival, self.busy_until) + transmit;
+        self.stats.sent_packets += 1;
+        self.stats.sent_bytes += size;
+        self.stats.max_backlog_bytes = std::cmp::max(self.stats.max_backlog_bytes, backlog + size);
+
+        Some(self.busy_until)
+    }
+}
+
+/// A packet that will travel over one or more links.
+#[derive(Debug)]
+pub struct LinkPacket {
+    pub packet: PacketRc,
+    pub links: Arc<[PathLink]>,
+    pub dst_host_id: HostId,
+    pub send_time: EmulatedTime,
+    /// The time that the packet would arrive at the destination if the links were idle.
+    pub arrival_time: EmulatedTime,
+    /// The earliest time that the packet may be delivered to the destination, which is later
+    /// than `arrival_time` if the packet must wait for the next round or for an earlier packet of
+    /// the same flow.
+    pub deliver_time: EmulatedTime,
+    pub src_host_id: HostId,
+    /// The event ID taken from the source host when the packet was sent.
+    pub src_host_event_id: u64,
+}
+
+/// A packet on its way to the next link on its path.
+#[derive(Debug)]
+struct InFlight {
+    packet: LinkPacket,
+    /// The index in `packet.links` of the next link.
+    hop: usize,
+    /// The time the packet has spent in queues and being transmitted so far.
+    delay: SimulationTime,
+}
+
+impl InFlight {
+    /// The time that the packet arrives at its next link.
+    fn arrival(&self) -> EmulatedTime {
+        let offset = SimulationTime::from_nanos(self.packet.links[self.hop].offset_ns);
+        self.packet.send_time + offset + self.delay
+    }
+
+    /// Orders packets by the time they arrive at their next link, and then by the order they
+    /// were sent in.
+    fn key(&self) -> (EmulatedTime, EmulatedTime, HostId, u64) {
+        (
+            self.arrival(),
+            self.packet.send_time,
+            self.packet.src_host_id,
+            self.packet.src_host_event_id,
+        )
+    }
+}
+
+/// The queues of all links in the network graph.
+#[derive(Debug)]
+pub struct LinkQueues {
+    queues: Mutex<BTreeMap<Link, LinkQueue>>,
+    /// Packets that were sent during the current round.
+    packets: Mutex<Vec<LinkPacket>>,
+    /// Packets from earlier rounds that haven't finished travelling over their links, ordered by
+    /// the time they arrive at their next link.
+    in_flight: Mutex<BTreeMap<(EmulatedTime, EmulatedTime, HostId, u64), InFlight>>,
+}
+
+impl LinkQueues {
+    pub fn new(links: impl IntoIterator<Item = (Link, LinkProperties)>) -> Self {
+        Self {
+            queues: Mutex::new(
+                links
+                    .into_iter()
+                    .map(|(link, properties)| (link, LinkQueue::new(properties)))
+                    .collect(),
+            ),
+            packets: Mutex::new(Vec::new()),
+            in_flight: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Set the smallest latency from the start of any path to each link, in nanoseconds. Links
+    /// that are missing from `offsets` are given an offset of 0.
+    pub fn set_min_offsets(&self, offsets: &HashMap<Link, u64>) {
+        for (link, queue) in self.queues.lock().unwrap().iter_mut() {
+            queue.min_offset = SimulationTime::from_nanos(offsets.get(link).copied().unwrap_or(0));
+        }
+    }
+
+    /// Add a packet that was sent during the current round. It won't be queued until
+    /// [`finish_round`](Self::finish_round) is called.
+    pub fn push(&self, packet: LinkPacket) {
+        self.packets.lock().unwrap().push(packet);
+    }
+
+    /// Queue the packets that have arrived at links during the round ending at `round_end`.
+    /// Returns the packets that have finished travelling over their links, and the times they
+    /// should be delivered. `on_drop` is called with each dropped packet and the time that it
+    /// arrived at the link that dropped it.
+    ///
+    /// Packets that arrive at a link after `round_end` are only queued if no packet sent in a later
+    /// round could arrive at the link before them, and otherwise wait for a later round.
+    pub fn finish_round(
+        &self,
+        round_end: EmulatedTime,
+        mut on_drop: impl FnMut(&LinkPacket, EmulatedTime),
+    ) -> Vec<(LinkPacket, EmulatedTime)> {
+        let packets = std::mem::take(&mut *self.packets.lock().unwrap());
+        let mut queues = self.queues.lock().unwrap();
+        let mut in_flight = self.in_flight.lock().unwrap();
+
+        // the threads that sent the packets may have pushed them in any order, but they're ordered
+        // by their keys
+        for packet in packets {
+            let packet = InFlight {
+                packet,
+                hop: 0,
+                delay: SimulationTime::ZERO,
+            };
+            in_flight.insert(packet.key(), packet);
+        }
+
+        let mut delivered = Vec::new();
+
+        // queue the packets at their next links in the order they arrive at them; a packet that
+        // passes a link arrives at its next link no earlier than it arrived at that link
+        while let Some(entry) = in_flight.first_entry() {
+            let arrival = entry.key().0;
+            let link = entry.get().packet.links[entry.get().hop].link;
+            let queue = queues.get_mut(&link).unwrap();
+
+            // a packet sent in the next round could still arrive at the link before this packet;
+            // the packets behind this one must also wait, since after passing the link this packet
+            // could arrive at their links before them
+            if arrival >= round_end + queue.min_offset {
+                break;
+            }
+
+            let mut flight = entry.remove();
+            let size = u64::try_from(flight.packet.packet.len()).unwrap();
+            let Some(departure) = queue.enqueue(arrival, size) else {
+                flight.packet.packet.add_status(PacketStatus::InetDropped);
+                on_drop(&flight.packet, arrival);
+                continue;
+            };
+            flight.delay += departure - arrival;
+            flight.hop += 1;
+
+            if flight.hop < flight.packet.links.len() {
+                in_flight.insert(flight.key(), flight);
+                continue;
+            }
+
+            // hosts have already run until the end of the round
+            let deliver_time = [
+                flight.packet.deliver_time,
+                flight.packet.arrival_time + flight.delay,
+                round_end,
+            ]
+            .into_iter()
+            .max()
+            .unwrap();
+            delivered.push((flight.packet, deliver_time));
+        }
+
+        delivered
+    }
+
+    /// The earliest time that a packet from an earlier round arrives at its next link, if any
+    /// packets are still travelling over their links. The simulation must continue until then so
+    /// that the packets can be queued.
+    pub fn next_arrival_time(&self) -> Option<EmulatedTime> {
+        let in_flight = self.in_flight.lock().unwrap();
+        in_flight.first_key_value().map(|(key, _)| key.0)
+    }
+
+    /// The counters of each link that at least one packet travelled over.
+    pub fn stats(&self) -> Vec<(Link, LinkStats)> {
+        self.queues
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, queue)| queue.stats.sent_packets > 0 || queue.stats.dropped_packets > 0)
+            .map(|(link, queue)| (*link, queue.stats.clone()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use petgraph::graph::EdgeIndex;
+
+    use super::*;
+
+    fn at_micros(micros: u64) -> EmulatedTime {
+        EmulatedTime::SIMULATION_START + SimulationTime::from_micros(micros)
+    }
+
+    fn queue(bandwidth_bits: u64, queue_bytes: u64) -> LinkQueue {
+        LinkQueue::new(LinkProperties {
+            bandwidth_bits,
+            queue_bytes,
+        })
+    }
+
+    #[test]
+    fn test_transmit_time() {
+        // 1000 bytes at 8 Mbit/s takes 1 ms
+        let mut queue = queue(8_000_000, 10_000);
+        assert_eq!(queue.enqueue(at_micros(0), 1000), Some(at_micros(1000)));
+
+        // an idle link sends the packet when it arrives
+        assert_eq!(queue.enqueue(at_micros(5000), 1000), Some(at_micros(6000)));
+    }
+
+    #[test]
+    fn test_shared_capacity() {
+        let mut queue = queue(8_000_000, 10_000);
+
+        // packets that arrive at the same time are sent one after another
+        for i in 1..=5 {
+            assert_eq!(queue.enqueue(at_micros(0), 1000), Some(at_micros(i * 1000)));
+        }
+        assert_eq!(queue.stats.sent_packets, 5);
+        assert_eq!(queue.stats.max_backlog_bytes, 5000);
+    }
+
+    #[test]
+    fn test_full_queue() {
+        let mut queue = queue(8_000_000, 2500);
+
+        assert!(queue.enqueue(at_micros(0), 1000).is_some());
+        assert!(queue.enqueue(at_micros(0), 1000).is_some());
+        // there are 2000 bytes waiting, so there's no room for another packet
+        assert!(queue.enqueue(at_micros(0), 1000).is_none());
+        assert_eq!(queue.stats.dropped_packets, 1);
+        assert_eq!(queue.stats.dropped_bytes, 1000);
+
+        // once the first packet has been sent, there's room again
+        assert_eq!(queue.enqueue(at_micros(1000), 1000), Some(at_micros(3000)));
+    }
+
+    #[test]
+    fn test_large_packet_on_empty_queue() {
+        let mut queue = queue(8_000_000, 100);
+        assert_eq!(queue.enqueue(at_micros(0), 1000), Some(at_micros(1000)));
+        assert!(queue.enqueue(at_micros(0), 1000).is_none());
+    }
+
+    #[test]
+    fn test_finish_round() {
+        let link = Link {
+            edge: EdgeIndex::new(0),
+            source: 0,
+            target: 1,
+        };
+        let properties = LinkProperties {
+            bandwidth_bits: 8_000_000,
+            queue_bytes: 1_000_000,
+        };
+        let queues = LinkQueues::new([(link, properties)]);
+        let links: Arc<[PathLink]> = Arc::new([PathLink { link, offset_ns: 0 }]);
+
+        let packet = |src_host_id: u32| LinkPacket {
+            packet: PacketRc::new_ipv4_udp_mock(),
+            links: Arc::clone(&links),
+            dst_host_id: HostId::from(2),
+            send_time: at_micros(0),
+            arrival_time: at_micros(10),
+            deliver_time: at_micros(10),
+            src_host_id: HostId::from(src_host_id),
+            src_host_event_id: 0,
+        };
+
+        // the packets are queued in the order they were sent, not the order they were pushed
+        queues.push(packet(1));
+        queues.push(packet(0));
+        let delivered = queues.finish_round(at_micros(10), |_, _| {});
+        assert_eq!(delivered.len(), 2);
+        assert_eq!(delivered[0].0.src_host_id, HostId::from(0));
+        assert!(delivered[0].1 > at_micros(10));
+        assert!(delivered[1].1 > delivered[0].1);
+
+        assert!(queues.finish_round(at_micros(20), |_, _| {}).is_empty());
+        assert_eq!(queues.stats()[0].1.sent_packets, 2);
+    }
+
+    #[test]
+    fn test_arrival_order() {
+        let link = Link {
+            edge: EdgeIndex::new(0),
+            source: 0,
+            target: 1,
+        };
+        // a 1028 byte packet takes 1 ms to transmit
+        let properties = LinkProperties {
+            bandwidth_bits: 8_224_000,
+            queue_bytes: 1_000_000,
+        };
+        let queues = LinkQueues::new([(link, properties)]);
+        queues.set_min_offsets(&HashMap::from([(link, 0)]));
+
+        // both paths take 10 ms from the link to the destination, but one path reaches the link
+        // 5 ms later
+        let near: Arc<[PathLink]> = Arc::new([PathLink { link, offset_ns: 0 }]);
+        let far: Arc<[PathLink]> = Arc::new([PathLink {
+            link,
+            offset_ns: 5_000_000,
+        }]);
+
+        let packet = |links: &Arc<[PathLink]>, send_micros: u64, src_host_id: u32| {
+            let arrival_time = at_micros(send_micros)
+                + SimulationTime::from_nanos(links[0].offset_ns)
+                + SimulationTime::from_millis(10);
+            LinkPacket {
+                packet: PacketRc::new_ipv4_udp_mock(),
+                links: Arc::clone(links),
+                dst_host_id: HostId::from(2),
+                send_time: at_micros(send_micros),
+                arrival_time,
+                deliver_time: arrival_time,
+                src_host_id: HostId::from(src_host_id),
+                src_host_event_id: 0,
+            }
+        };
+        let delivered = |queues: &LinkQueues, round_end: u64| -> Vec<(u32, EmulatedTime)> {
+            queues
+                .finish_round(at_micros(round_end), |_, _| {})
+                .into_iter()
+                .map(|(packet, time)| (u32::from(packet.src_host_id), time))
+                .collect()
+        };
+
+        // a packet sent later over the near path reaches the link first, and the packet on the far
+        // path waits in front of the link since packets in later rounds could still overtake it
+        queues.push(packet(&far, 0, 0));
+        queues.push(packet(&near, 1000, 1));
+        assert_eq!(delivered(&queues, 2000), [(1, at_micros(12_000))]);
+        assert_eq!(queues.next_arrival_time(), Some(at_micros(5000)));
+
+        queues.push(packet(&near, 3000, 2));
+        assert_eq!(delivered(&queues, 4000), [(2, at_micros(14_000))]);
+
+        // a packet from this round arrives before the packet from the first round, which then
+        // waits for it to be transmitted
+        queues.push(packet(&near, 4500, 3));
+        assert_eq!(
+            delivered(&queues, 6000),
+            [(3, at_micros(15_500)), (0, at_micros(16_500))],
+        );
+        assert_eq!(queues.next_arrival_time(), None);
+
+        // each packet only waited for the packets that arrived at the link before it
+        let stats = &queues.stats()[0].1;
+        assert_eq!(stats.sent_packets, 4);
+        assert_eq!(stats.max_backlog_bytes, 514 + 1028);
+    }
+}
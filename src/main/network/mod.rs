@@ -7,9 +7,14 @@ use std::net::Ipv4Addr;
 
 use crate::network::packet::PacketRc;
 
+pub mod capture;
 pub mod dns;
+pub mod faults;
 pub mod graph;
+pub mod jitter;
+pub mod links;
 pub mod packet;
+pub mod packet_trace;
 pub mod relay;
 pub mod router;
 
@@ -1,18 +1,20 @@
-use std::io::Write;
+use std::io::{Read, Write};
 use std::mem::MaybeUninit;
-use std::net::{IpAddr, SocketAddrV4};
+use std::net::{IpAddr, Ipv6Addr, SocketAddrV4};
 use std::sync::Arc;
 
+use crate::core::worker::Worker;
 use crate::host::network::interface::FifoPacketPriority;
 use crate::utility::ObjectCounter;
 use crate::utility::pcap_writer::PacketDisplay;
 
 use atomic_refcell::AtomicRefCell;
 use bytes::Bytes;
+use serde::{Deserialize, Serialize};
 use shadow_shim_helper_rs::HostId;
 
 /// Represents different checkpoints that a packet reaches as it is being moved around in Shadow.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum PacketStatus {
     SndCreated,
     SndTcpEnqueueThrottled,
@@ -21,6 +23,7 @@ pub enum PacketStatus {
     SndTcpRetransmitted,
     SndSocketBuffered,
     SndInterfaceSent,
+    SndInterfaceDropped,
     InetSent,
     InetDropped,
     RouterEnqueued,
@@ -38,6 +41,49 @@ pub enum PacketStatus {
     RelayForwarded,
 }
 
+impl PacketStatus {
+    /// Returns true if the packet was dropped.
+    pub fn is_drop(&self) -> bool {
+        matches!(
+            self,
+            Self::SndInterfaceDropped
+                | Self::InetDropped
+                | Self::RouterDropped
+                | Self::RcvInterfaceDropped
+                | Self::RcvSocketDropped
+        )
+    }
+}
+
+/// Identifies a packet by the host that created it and the number of packets that the host had
+/// created before it. Copies of a packet have the same id.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq, Ord, PartialOrd)]
+pub struct PacketId {
+    pub host_id: HostId,
+    pub counter: u64,
+}
+
+impl std::fmt::Display for PacketId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", u32::from(self.host_id), self.counter)
+    }
+}
+
+impl std::str::FromStr for PacketId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = || {
+            let (host_id, counter) = s.split_once(':')?;
+            Some(Self {
+                host_id: HostId::from(host_id.parse::<u32>().ok()?),
+                counter: counter.parse().ok()?,
+            })
+        };
+        parse().ok_or_else(|| format!("Packet id '{s}' is not of the form 'host:counter'"))
+    }
+}
+
 /// Official IANA-assigned protocols supported in our packets.
 #[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
 pub enum IanaProtocol {
@@ -45,6 +91,15 @@ pub enum IanaProtocol {
     Udp,
 }
 
+/// The protocol and source and destination addresses of a packet, which identify the flow that the
+/// packet belongs to.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct PacketFlow {
+    pub protocol: IanaProtocol,
+    pub src: SocketAddrV4,
+    pub dst: SocketAddrV4,
+}
+
 impl IanaProtocol {
     /// The IANA-assigned protocol number. This value is guaranteed to be unique for distinct
     /// protocol variants.
@@ -146,6 +201,16 @@ impl PacketRc {
         Self::from(self.inner.as_ref().clone())
     }
 
+    /// Creates a thread-safe shared reference to a new `Packet` that is a copy of the provided
+    /// referenced `Packet` (see `new_copy_inner()`), but which is sent using the given IPv6
+    /// addresses. The IPv6 addresses must be aliases of the packet's IPv4 source and destination
+    /// addresses; Shadow continues to route the packet using its IPv4 addresses.
+    pub fn new_ipv6_copy(&self, src: Ipv6Addr, dst: Ipv6Addr) -> Self {
+        let mut packet = self.inner.as_ref().clone();
+        packet.header.ipv6 = Some((src, dst));
+        Self::from(packet)
+    }
+
     /// Transfers ownership of the given packet_ptr reference into a new `PacketRc` object. The provided
     /// pointer must have been obtained from a call to the Rust function `PacketRc::into_raw()` or
     /// the C function `packet_new_tcp()`.
@@ -294,7 +359,7 @@ impl Packet {
         let tcp_packet = TcpData::new(TcpHeader::from(hdr), payload.0);
         let data = Data::from(tcp_packet);
 
-        let meta = Metadata::new(priority);
+        let meta = Metadata::new_from_active_host(priority);
 
         Self::new(header, data, meta)
     }
@@ -312,7 +377,7 @@ impl Packet {
         let udp_packet = UdpData::new(udp_header, payload);
         let data = Data::from(udp_packet);
 
-        let meta = Metadata::new(priority);
+        let meta = Metadata::new_from_active_host(priority);
 
         Self::new(header, data, meta)
     }
@@ -397,7 +462,9 @@ impl Packet {
 
     /// Appends the provided packet status to the list of the packet's status checkpoints.
     ///
-    /// This function has no effect unless `log::Level::Trace` is enabled.
+    /// The list is only kept if `log::Level::Trace` is enabled. The status is also written to the
+    /// simulation's packet trace if it's enabled, and drops are always recorded in the network
+    /// metrics and packet capture of the active host.
     pub fn add_status(&self, status: PacketStatus) {
         if log::log_enabled!(log::Level::Trace) {
             if let Some(vec) = self.meta.statuses.as_ref() {
@@ -405,6 +472,25 @@ impl Packet {
             }
             log::trace!("[{status:?}] {self:?}");
         }
+
+        if let Some(id) = self.id() {
+            Worker::trace_packet(id, status);
+        }
+
+        if status.is_drop() {
+            // record the drop in the network metrics and capture of the host that dropped the
+            // packet; packets dropped between rounds (at links) have no active host and aren't
+            // recorded
+            let _ = Worker::with_active_host(|host| host.record_dropped_packet(self, status));
+        }
+    }
+
+    /// Returns the id of the packet, or `None` if the packet wasn't created by a host.
+    pub fn id(&self) -> Option<PacketId> {
+        Some(PacketId {
+            host_id: self.meta.host_id?,
+            counter: self.meta.packet_id?,
+        })
     }
 
     /// Returns the packet's IPv4 source address and source port.
@@ -445,6 +531,17 @@ impl Packet {
         SocketAddrV4::new(addr, port)
     }
 
+    /// Returns the packet's IPv6 source and destination addresses if it is sent as an IPv6 packet.
+    /// Otherwise, returns `None`.
+    pub fn ipv6_addresses(&self) -> Option<(Ipv6Addr, Ipv6Addr)> {
+        self.header.ipv6
+    }
+
+    /// Returns true if the packet is sent as an IPv6 packet.
+    pub fn is_ipv6(&self) -> bool {
+        self.header.ipv6.is_some()
+    }
+
     /// Returns the priority set at packet creation time.
     pub fn priority(&self) -> FifoPacketPriority {
         self.meta.priority
@@ -454,6 +551,89 @@ impl Packet {
     pub fn iana_protocol(&self) -> IanaProtocol {
         self.data.iana_protocol()
     }
+
+    /// Returns the flow that the packet belongs to.
+    pub fn flow(&self) -> PacketFlow {
+        PacketFlow {
+            protocol: self.iana_protocol(),
+            src: self.src_ipv4_address(),
+            dst: self.dst_ipv4_address(),
+        }
+    }
+
+    /// Writes the packet in a format that can be read by `Packet::read_from()`, so that the packet
+    /// can be sent to another Shadow process. The packet's status checkpoints are not written.
+    pub fn write_to(&self, mut writer: impl Write) -> std::io::Result<()> {
+        write_ip_addr(self.header.src, &mut writer)?;
+        write_ip_addr(self.header.dst, &mut writer)?;
+        match self.header.ipv6 {
+            Some((src, dst)) => {
+                writer.write_all(&[1])?;
+                writer.write_all(&src.octets())?;
+                writer.write_all(&dst.octets())?;
+            }
+            None => writer.write_all(&[0])?,
+        }
+
+        match &self.data {
+            Data::LegacyTcp(tcp_ref) => {
+                writer.write_all(&[0])?;
+                write_tcpdata(&tcp_ref.borrow(), &mut writer)?;
+            }
+            Data::Tcp(tcp) => {
+                writer.write_all(&[1])?;
+                write_tcpdata(tcp, &mut writer)?;
+            }
+            Data::Udp(udp) => {
+                writer.write_all(&[2])?;
+                writer.write_all(&udp.header.src_port.to_le_bytes())?;
+                writer.write_all(&udp.header.dst_port.to_le_bytes())?;
+                write_payload(&udp.payload, &mut writer)?;
+            }
+        }
+
+        writer.write_all(&self.meta.priority.to_le_bytes())?;
+        write_option(self.meta.host_id.map(u32::from), &mut writer, |x, w| {
+            w.write_all(&x.to_le_bytes())
+        })?;
+        write_option(self.meta.packet_id, &mut writer, |x, w| {
+            w.write_all(&x.to_le_bytes())
+        })?;
+
+        Ok(())
+    }
+
+    /// Reads a packet that was written by `Packet::write_to()`.
+    pub fn read_from(mut reader: impl Read) -> std::io::Result<Self> {
+        let src = read_ip_addr(&mut reader)?;
+        let dst = read_ip_addr(&mut reader)?;
+        let mut header = Header::new(src, dst);
+        header.ipv6 = read_option(&mut reader, |r| {
+            let src = Ipv6Addr::from(read_array::<16>(r)?);
+            let dst = Ipv6Addr::from(read_array::<16>(r)?);
+            Ok((src, dst))
+        })?;
+
+        let data = match read_array::<1>(&mut reader)? {
+            [0] => Data::LegacyTcp(AtomicRefCell::new(read_tcpdata(&mut reader)?)),
+            [1] => Data::Tcp(read_tcpdata(&mut reader)?),
+            [2] => {
+                let src_port = u16::from_le_bytes(read_array(&mut reader)?);
+                let dst_port = u16::from_le_bytes(read_array(&mut reader)?);
+                let payload = read_payload(&mut reader)?;
+                Data::Udp(UdpData::new(UdpHeader::new(src_port, dst_port), payload))
+            }
+            [x] => return Err(invalid_data(format!("Unknown packet data type {x}"))),
+        };
+
+        let mut meta = Metadata::new(u64::from_le_bytes(read_array(&mut reader)?));
+        meta.host_id = read_option(&mut reader, |r| {
+            Ok(HostId::from(u32::from_le_bytes(read_array(r)?)))
+        })?;
+        meta.packet_id = read_option(&mut reader, |r| Ok(u64::from_le_bytes(read_array(r)?)))?;
+
+        Ok(Self::new(header, data, meta))
+    }
 }
 
 /// Stores the IP header information.
@@ -461,6 +641,9 @@ impl Packet {
 struct Header {
     src: IpAddr,
     dst: IpAddr,
+    /// IPv6 source and destination addresses that are aliases of `src` and `dst`. If set, the
+    /// packet is an IPv6 packet, but is still routed using `src` and `dst`.
+    ipv6: Option<(Ipv6Addr, Ipv6Addr)>,
     _tos: TypeOfService,
 }
 
@@ -470,11 +653,16 @@ impl Header {
         Self {
             src,
             dst,
+            ipv6: None,
             _tos: TypeOfService::Normal,
         }
     }
 
     pub fn len(&self) -> usize {
+        if self.ipv6.is_some() {
+            return 40usize;
+        }
+
         match &self.dst {
             // 20 bytes without options: https://en.wikipedia.org/wiki/IPv4
             IpAddr::V4(_) => 20usize,
@@ -751,33 +939,37 @@ struct Metadata {
     /// network.
     statuses: Option<AtomicRefCell<Vec<PacketStatus>>>,
     /// The id of the host that created the packet.
-    ///
-    /// # Deprecation
-    ///
-    /// This is currently set by the legacy C TCP stack to help uniquely identify a packet while
-    /// debugging. It can be removed when the legacy TCP stack and our C packet API is removed.
-    _host_id: Option<HostId>,
-    /// The id of the packet.
-    ///
-    /// # Deprecation
-    ///
-    /// This is currently set by the legacy C TCP stack to help uniquely identify a packet while
-    /// debugging. It can be removed when the legacy TCP stack and our C packet API is removed.
-    _packet_id: Option<u64>,
+    host_id: Option<HostId>,
+    /// The packet's counter from the host that created it, which together with `host_id` uniquely
+    /// identifies the packet.
+    packet_id: Option<u64>,
 }
 
 impl Metadata {
     pub fn new(priority: FifoPacketPriority) -> Self {
         Self {
             priority,
-            _host_id: None,
-            _packet_id: None,
+            host_id: None,
+            packet_id: None,
             // For efficiency, we only store statuses when tracing is enabled because they are
             // logged at trace level and won't be displayed at other levels anyway.
             statuses: log::log_enabled!(log::Level::Trace).then(AtomicRefCell::default),
         }
     }
 
+    /// Metadata for a packet created by the active host, which gives the packet an id from the
+    /// host's packet counter. The packet has no id if there is no active host.
+    fn new_from_active_host(priority: FifoPacketPriority) -> Self {
+        let mut meta = Self::new(priority);
+        if let Some((host_id, packet_id)) =
+            Worker::with_active_host(|host| (host.id(), host.get_new_packet_id()))
+        {
+            meta.host_id = Some(host_id);
+            meta.packet_id = Some(packet_id);
+        }
+        meta
+    }
+
     /// Supports creation of legacy TCP stack metadata.
     ///
     /// # Deprecation
@@ -787,8 +979,8 @@ impl Metadata {
     fn new_legacy(priority: FifoPacketPriority, host_id: HostId, packet_id: u64) -> Self {
         Self {
             priority,
-            _host_id: Some(host_id),
-            _packet_id: Some(packet_id),
+            host_id: Some(host_id),
+            packet_id: Some(packet_id),
             // For efficiency, we only store statuses when tracing is enabled because they are
             // logged at trace level and won't be displayed at other levels anyway.
             statuses: log::log_enabled!(log::Level::Trace).then(AtomicRefCell::default),
@@ -800,35 +992,11 @@ impl PacketDisplay for Packet {
     fn display_bytes(&self, mut writer: impl Write) -> std::io::Result<()> {
         // write the IP header
 
-        let version_and_header_length: u8 = 0x45;
-        let fields: u8 = 0x0;
-        let total_length: u16 = self.len().try_into().unwrap();
-        let identification: u16 = 0x0;
-        let flags_and_fragment: u16 = 0x4000;
-        let time_to_live: u8 = 64;
-        let iana_protocol: u8 = self.data.iana_protocol().number();
-        let header_checksum: u16 = 0x0;
-        let source_ip: [u8; 4] = self.src_ipv4_address().ip().to_bits().to_be_bytes();
-        let dest_ip: [u8; 4] = self.dst_ipv4_address().ip().to_bits().to_be_bytes();
-
-        // version and header length: 1 byte
-        // DSCP + ECN: 1 byte
-        writer.write_all(&[version_and_header_length, fields])?;
-        // total length: 2 bytes
-        writer.write_all(&total_length.to_be_bytes())?;
-        // identification: 2 bytes
-        writer.write_all(&identification.to_be_bytes())?;
-        // flags + fragment offset: 2 bytes
-        writer.write_all(&flags_and_fragment.to_be_bytes())?;
-        // ttl: 1 byte
-        // protocol: 1 byte
-        writer.write_all(&[time_to_live, iana_protocol])?;
-        // header checksum: 2 bytes
-        writer.write_all(&header_checksum.to_be_bytes())?;
-        // source IP: 4 bytes
-        writer.write_all(&source_ip)?;
-        // destination IP: 4 bytes
-        writer.write_all(&dest_ip)?;
+        if let Some((src, dst)) = self.header.ipv6 {
+            write_ipv6_header(self, src, dst, &mut writer)?;
+        } else {
+            write_ipv4_header(self, &mut writer)?;
+        }
 
         // write protocol-specific data
 
@@ -842,6 +1010,68 @@ impl PacketDisplay for Packet {
     }
 }
 
+fn write_ipv4_header(packet: &Packet, mut writer: impl Write) -> std::io::Result<()> {
+    let version_and_header_length: u8 = 0x45;
+    let fields: u8 = 0x0;
+    let total_length: u16 = packet.len().try_into().unwrap();
+    let identification: u16 = 0x0;
+    let flags_and_fragment: u16 = 0x4000;
+    let time_to_live: u8 = 64;
+    let iana_protocol: u8 = packet.data.iana_protocol().number();
+    let header_checksum: u16 = 0x0;
+    let source_ip: [u8; 4] = packet.src_ipv4_address().ip().to_bits().to_be_bytes();
+    let dest_ip: [u8; 4] = packet.dst_ipv4_address().ip().to_bits().to_be_bytes();
+
+    // version and header length: 1 byte
+    // DSCP + ECN: 1 byte
+    writer.write_all(&[version_and_header_length, fields])?;
+    // total length: 2 bytes
+    writer.write_all(&total_length.to_be_bytes())?;
+    // identification: 2 bytes
+    writer.write_all(&identification.to_be_bytes())?;
+    // flags + fragment offset: 2 bytes
+    writer.write_all(&flags_and_fragment.to_be_bytes())?;
+    // ttl: 1 byte
+    // protocol: 1 byte
+    writer.write_all(&[time_to_live, iana_protocol])?;
+    // header checksum: 2 bytes
+    writer.write_all(&header_checksum.to_be_bytes())?;
+    // source IP: 4 bytes
+    writer.write_all(&source_ip)?;
+    // destination IP: 4 bytes
+    writer.write_all(&dest_ip)?;
+
+    Ok(())
+}
+
+fn write_ipv6_header(
+    packet: &Packet,
+    src: Ipv6Addr,
+    dst: Ipv6Addr,
+    mut writer: impl Write,
+) -> std::io::Result<()> {
+    // version (6), traffic class, and flow label
+    let version_class_and_flow: u32 = 0x6000_0000;
+    // the payload length doesn't include the 40 byte IPv6 header
+    let payload_length: u16 = packet.data.len().try_into().unwrap();
+    let next_header: u8 = packet.data.iana_protocol().number();
+    let hop_limit: u8 = 64;
+
+    // version + traffic class + flow label: 4 bytes
+    writer.write_all(&version_class_and_flow.to_be_bytes())?;
+    // payload length: 2 bytes
+    writer.write_all(&payload_length.to_be_bytes())?;
+    // next header: 1 byte
+    // hop limit: 1 byte
+    writer.write_all(&[next_header, hop_limit])?;
+    // source IP: 16 bytes
+    writer.write_all(&src.octets())?;
+    // destination IP: 16 bytes
+    writer.write_all(&dst.octets())?;
+
+    Ok(())
+}
+
 fn write_tcpdata_bytes(data: &TcpData, mut writer: impl Write) -> std::io::Result<()> {
     // process TCP options
 
@@ -940,6 +1170,157 @@ fn write_udpdata_bytes(data: &UdpData, mut writer: impl Write) -> std::io::Resul
     Ok(())
 }
 
+fn invalid_data(msg: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, msg)
+}
+
+fn read_array<const N: usize>(mut reader: impl Read) -> std::io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn write_option<T, W: Write>(
+    value: Option<T>,
+    mut writer: W,
+    f: impl FnOnce(T, &mut W) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    match value {
+        Some(value) => {
+            writer.write_all(&[1])?;
+            f(value, &mut writer)
+        }
+        None => writer.write_all(&[0]),
+    }
+}
+
+fn read_option<T, R: Read>(
+    mut reader: R,
+    f: impl FnOnce(&mut R) -> std::io::Result<T>,
+) -> std::io::Result<Option<T>> {
+    match read_array::<1>(&mut reader)? {
+        [0] => Ok(None),
+        [1] => Ok(Some(f(&mut reader)?)),
+        [x] => Err(invalid_data(format!("Invalid option tag {x}"))),
+    }
+}
+
+fn write_ip_addr(addr: IpAddr, mut writer: impl Write) -> std::io::Result<()> {
+    match addr {
+        IpAddr::V4(addr) => {
+            writer.write_all(&[4])?;
+            writer.write_all(&addr.octets())
+        }
+        IpAddr::V6(addr) => {
+            writer.write_all(&[6])?;
+            writer.write_all(&addr.octets())
+        }
+    }
+}
+
+fn read_ip_addr(mut reader: impl Read) -> std::io::Result<IpAddr> {
+    match read_array::<1>(&mut reader)? {
+        [4] => Ok(IpAddr::from(read_array::<4>(&mut reader)?)),
+        [6] => Ok(IpAddr::from(read_array::<16>(&mut reader)?)),
+        [x] => Err(invalid_data(format!("Invalid IP address version {x}"))),
+    }
+}
+
+fn write_payload(payload: &Bytes, mut writer: impl Write) -> std::io::Result<()> {
+    let len = u32::try_from(payload.len()).unwrap();
+    writer.write_all(&len.to_le_bytes())?;
+    writer.write_all(payload)
+}
+
+fn read_payload(mut reader: impl Read) -> std::io::Result<Bytes> {
+    let len = u32::from_le_bytes(read_array(&mut reader)?);
+    let mut payload = vec![0; len.try_into().unwrap()];
+    reader.read_exact(&mut payload)?;
+    Ok(Bytes::from(payload))
+}
+
+fn write_tcpdata(data: &TcpData, mut writer: impl Write) -> std::io::Result<()> {
+    let hdr = &data.header;
+    writer.write_all(&hdr.src_port.to_le_bytes())?;
+    writer.write_all(&hdr.dst_port.to_le_bytes())?;
+    writer.write_all(&[hdr.flags.bits()])?;
+    writer.write_all(&hdr.sequence.to_le_bytes())?;
+    writer.write_all(&hdr.acknowledgement.to_le_bytes())?;
+    writer.write_all(&hdr.window_size.to_le_bytes())?;
+    write_option(hdr.selective_acks, &mut writer, |x, w| {
+        w.write_all(&[x.len])?;
+        for (start, end) in x.ranges {
+            w.write_all(&start.to_le_bytes())?;
+            w.write_all(&end.to_le_bytes())?;
+        }
+        Ok(())
+    })?;
+    write_option(hdr.window_scale, &mut writer, |x, w| w.write_all(&[x]))?;
+    write_option(hdr.timestamp, &mut writer, |x, w| {
+        w.write_all(&x.to_le_bytes())
+    })?;
+    write_option(hdr.timestamp_echo, &mut writer, |x, w| {
+        w.write_all(&x.to_le_bytes())
+    })?;
+
+    let num_chunks = u32::try_from(data.payload.len()).unwrap();
+    writer.write_all(&num_chunks.to_le_bytes())?;
+    for bytes in &data.payload {
+        write_payload(bytes, &mut writer)?;
+    }
+
+    Ok(())
+}
+
+fn read_tcpdata(mut reader: impl Read) -> std::io::Result<TcpData> {
+    fn read_u32(reader: impl Read) -> std::io::Result<u32> {
+        Ok(u32::from_le_bytes(read_array(reader)?))
+    }
+
+    let src_port = u16::from_le_bytes(read_array(&mut reader)?);
+    let dst_port = u16::from_le_bytes(read_array(&mut reader)?);
+    // retain all bits since the legacy TCP stack stores non-TCP flags in the ECE and CWR bits
+    let flags = tcp::TcpFlags::from_bits_retain(read_array::<1>(&mut reader)?[0]);
+    let sequence = read_u32(&mut reader)?;
+    let acknowledgement = read_u32(&mut reader)?;
+    let window_size = u16::from_le_bytes(read_array(&mut reader)?);
+    let selective_acks = read_option(&mut reader, |r| {
+        let [len] = read_array::<1>(&mut *r)?;
+        if len > 4 {
+            return Err(invalid_data(format!(
+                "Invalid number of selective acks {len}"
+            )));
+        }
+        let mut ranges = [(0, 0); 4];
+        for range in &mut ranges {
+            *range = (read_u32(&mut *r)?, read_u32(&mut *r)?);
+        }
+        Ok(TcpSelectiveAcks { len, ranges })
+    })?;
+    let window_scale = read_option(&mut reader, |r| Ok(read_array::<1>(r)?[0]))?;
+    let timestamp = read_option(&mut reader, read_u32)?;
+    let timestamp_echo = read_option(&mut reader, read_u32)?;
+
+    let num_chunks = read_u32(&mut reader)?;
+    let payload = (0..num_chunks)
+        .map(|_| read_payload(&mut reader))
+        .collect::<std::io::Result<_>>()?;
+
+    let header = TcpHeader {
+        src_port,
+        dst_port,
+        flags,
+        sequence,
+        acknowledgement,
+        window_size,
+        selective_acks,
+        window_scale,
+        timestamp,
+        timestamp_echo,
+    };
+    Ok(TcpData::new(header, payload))
+}
+
 #[cfg(test)]
 mod tests {
     use std::net::Ipv4Addr;
@@ -984,6 +1365,45 @@ mod tests {
         assert_eq!(0, chunks.first().unwrap().len());
     }
 
+    #[test]
+    fn ipv6_udp() {
+        let src = SocketAddrV4::new(Ipv4Addr::new(192, 168, 1, 1), 10_000);
+        let dst = SocketAddrV4::new(Ipv4Addr::new(192, 168, 1, 2), 80);
+        let src6 = Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 1);
+        let dst6 = Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 2);
+        let payload = Bytes::from_static(b"Hello World!");
+
+        let packetrc = PacketRc::new_ipv4_udp(src, dst, payload.clone(), 123);
+        assert!(!packetrc.is_ipv6());
+
+        let packetrc_v6 = packetrc.new_ipv6_copy(src6, dst6);
+        assert!(packetrc_v6.is_ipv6());
+        assert_eq!(Some((src6, dst6)), packetrc_v6.ipv6_addresses());
+
+        // the packet is still routed using its ipv4 addresses
+        assert_eq!(src, packetrc_v6.src_ipv4_address());
+        assert_eq!(dst, packetrc_v6.dst_ipv4_address());
+
+        // the ipv6 header is 20 bytes larger than the ipv4 header
+        assert_eq!(packetrc.len() + 20, packetrc_v6.len());
+
+        let mut bytes = Vec::new();
+        packetrc_v6.display_bytes(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), packetrc_v6.len());
+        // version
+        assert_eq!(bytes[0] >> 4, 6);
+        // payload length
+        assert_eq!(
+            u16::from_be_bytes([bytes[4], bytes[5]]) as usize,
+            8 + payload.len()
+        );
+        // next header
+        assert_eq!(bytes[6], IanaProtocol::Udp.number());
+        assert_eq!(&bytes[8..24], &src6.octets());
+        assert_eq!(&bytes[24..40], &dst6.octets());
+        assert_eq!(&bytes[48..], &payload[..]);
+    }
+
     fn make_tcp_header(src: SocketAddrV4, dst: SocketAddrV4) -> tcp::TcpHeader {
         // Selective acks with two ranges: [1-3) and [5-6).
         let sel_acks =
@@ -1065,6 +1485,50 @@ mod tests {
         assert_eq!(0, chunks.first().unwrap().len());
         assert_eq!(0, chunks.last().unwrap().len());
     }
+
+    #[test]
+    fn write_and_read() {
+        let src = SocketAddrV4::new(Ipv4Addr::new(192, 168, 1, 1), 10_000);
+        let dst = SocketAddrV4::new(Ipv4Addr::new(192, 168, 1, 2), 80);
+        let src6 = Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 1);
+        let dst6 = Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 2);
+        let payload = tcp::Payload(vec![
+            Bytes::from_static(b"Hello"),
+            Bytes::from_static(b" World!"),
+        ]);
+
+        let packets = [
+            Packet::new_ipv4_tcp(make_tcp_header(src, dst), payload, 123),
+            Packet::new_ipv4_udp(src, dst, Bytes::from_static(b"Hello World!"), 456),
+            Packet::clone(
+                &PacketRc::new_ipv4_udp(src, dst, Bytes::new(), 0).new_ipv6_copy(src6, dst6),
+            ),
+        ];
+
+        for packet in packets {
+            let mut bytes = Vec::new();
+            packet.write_to(&mut bytes).unwrap();
+            let read = Packet::read_from(&bytes[..]).unwrap();
+
+            assert_eq!(packet.flow(), read.flow());
+            assert_eq!(packet.priority(), read.priority());
+            assert_eq!(packet.ipv6_addresses(), read.ipv6_addresses());
+            assert_eq!(packet.payload(), read.payload());
+            assert_eq!(
+                packet.ipv4_tcp_header().map(TcpHeader::from),
+                read.ipv4_tcp_header().map(TcpHeader::from)
+            );
+
+            let mut display = Vec::new();
+            let mut read_display = Vec::new();
+            packet.display_bytes(&mut display).unwrap();
+            read.display_bytes(&mut read_display).unwrap();
+            assert_eq!(display, read_display);
+
+            // a truncated packet can't be read
+            assert!(Packet::read_from(&bytes[..bytes.len() - 1]).is_err());
+        }
+    }
 }
 
 /// This module provides a C API to create and operate on packets.
@@ -0,0 +1,287 @@
+//! A trace of every status change of every packet, written to a JSON lines file, and a reader for
+//! the trace to analyze the paths and delays of packets.
+//!
+//! Each line of the file is a [`PacketTraceEvent`], for example:
+//!
+//! ```json
+//! {"time_ns":1000150000,"host_id":2,"packet":"1:7","status":"RouterEnqueued"}
+//! ```
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufWriter, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use shadow_shim_helper_rs::HostId;
+use shadow_shim_helper_rs::emulated_time::EmulatedTime;
+use shadow_shim_helper_rs::simulation_time::SimulationTime;
+
+use crate::network::packet::{PacketId, PacketStatus};
+use crate::utility::round_writer::RoundWriter;
+
+/// A packet reaching a status.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PacketTraceEvent {
+    /// Nanoseconds since the start of the simulation.
+    pub time_ns: u64,
+    /// The host that the packet was at, or `None` if it was in the network between hosts (for
+    /// example if it was dropped at a congested link of the network graph).
+    pub host_id: Option<u32>,
+    /// The packet's id, written as "host:counter" like in the merged packet capture.
+    #[serde(with = "packet_id_str")]
+    pub packet: PacketId,
+    pub status: PacketStatus,
+}
+
+impl PacketTraceEvent {
+    pub fn time(&self) -> SimulationTime {
+        SimulationTime::from_nanos(self.time_ns)
+    }
+}
+
+mod packet_id_str {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::network::packet::PacketId;
+
+    pub fn serialize<S: Serializer>(id: &PacketId, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(id)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PacketId, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Traces the packets of all hosts to a single file. Events are recorded by hosts in parallel and
+/// written between rounds, ordered by time and then by host so that the file is deterministic.
+pub struct PacketTrace {
+    writer: RoundWriter<PacketTraceEvent, BufWriter<File>>,
+}
+
+impl PacketTrace {
+    /// Create the trace file at `path`.
+    pub fn new(path: &Path) -> std::io::Result<Self> {
+        Ok(Self {
+            writer: RoundWriter::new(BufWriter::new(File::create(path)?)),
+        })
+    }
+
+    /// Record that packet `id` reached `status` at `time` on host `host_id`, or in the network if
+    /// `host_id` is `None`.
+    pub fn record(
+        &self,
+        time: EmulatedTime,
+        host_id: Option<HostId>,
+        id: PacketId,
+        status: PacketStatus,
+    ) {
+        let time_ns = time.to_abs_simtime().as_nanos();
+        let event = PacketTraceEvent {
+            time_ns: u64::try_from(time_ns).unwrap(),
+            host_id: host_id.map(u32::from),
+            packet: id,
+            status,
+        };
+        self.writer.record(time, host_id, event);
+    }
+
+    /// Write the events that were recorded during the round. Must only be called between rounds.
+    pub fn finish_round(&self) -> std::io::Result<()> {
+        self.writer.finish_round(|writer, event| {
+            serde_json::to_writer(&mut *writer, &event)?;
+            writeln!(writer)
+        })
+    }
+
+    /// Write the remaining events and flush the file.
+    pub fn finish(&self) -> std::io::Result<()> {
+        self.finish_round()?;
+        self.writer.with_writer(|writer| writer.flush())
+    }
+}
+
+/// Read the events of a packet trace.
+pub fn read_events(
+    reader: impl BufRead,
+) -> impl Iterator<Item = std::io::Result<PacketTraceEvent>> {
+    reader
+        .lines()
+        .filter(|line| !line.as_ref().is_ok_and(|x| x.trim().is_empty()))
+        .map(|line| Ok(serde_json::from_str(&line?)?))
+}
+
+/// Read a packet trace and group its events by packet. The packets are ordered by id.
+pub fn read_lifecycles(reader: impl BufRead) -> std::io::Result<Vec<PacketLifecycle>> {
+    let mut packets = BTreeMap::<PacketId, Vec<PacketTraceEvent>>::new();
+    for event in read_events(reader) {
+        let event = event?;
+        packets.entry(event.packet).or_default().push(event);
+    }
+
+    Ok(packets
+        .into_iter()
+        .map(|(id, mut events)| {
+            // events are only ordered by time within each round, for example a packet that was
+            // dropped at a link may be written before events of an earlier time from the next
+            // round
+            events.sort_by_key(|x| x.time_ns);
+            PacketLifecycle { id, events }
+        })
+        .collect())
+}
+
+/// The events of a single packet, ordered by time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PacketLifecycle {
+    pub id: PacketId,
+    pub events: Vec<PacketTraceEvent>,
+}
+
+impl PacketLifecycle {
+    /// The first event with a status that matches `f`.
+    pub fn find(&self, f: impl Fn(PacketStatus) -> bool) -> Option<&PacketTraceEvent> {
+        self.events.iter().find(|x| f(x.status))
+    }
+
+    /// The event where the packet was dropped, if it was.
+    pub fn drop_event(&self) -> Option<&PacketTraceEvent> {
+        self.find(|x| x.is_drop())
+    }
+
+    /// The time between the packet last leaving the sender's interface and first arriving at the
+    /// receiver's interface, or `None` if the packet never arrived.
+    pub fn propagation_delay(&self) -> Option<SimulationTime> {
+        let received = self
+            .events
+            .iter()
+            .position(|x| x.status == PacketStatus::RcvInterfaceReceived)?;
+        let sent = self.events[..received]
+            .iter()
+            .rfind(|x| x.status == PacketStatus::SndInterfaceSent)?;
+        Some(self.events[received].time() - sent.time())
+    }
+
+    /// The time between the packet being created by the sender's socket and being buffered by the
+    /// receiver's socket, or `None` if the packet was never buffered.
+    pub fn delivery_delay(&self) -> Option<SimulationTime> {
+        let created = self.find(|x| x == PacketStatus::SndCreated)?;
+        let delivered = self.find(|x| {
+            matches!(
+                x,
+                PacketStatus::RcvSocketBuffered | PacketStatus::RcvSocketDelivered
+            )
+        })?;
+        Some(delivered.time() - created.time())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(time_ns: u64, host_id: Option<u32>, counter: u64, status: PacketStatus) -> String {
+        let event = PacketTraceEvent {
+            time_ns,
+            host_id,
+            packet: PacketId {
+                host_id: HostId::from(1),
+                counter,
+            },
+            status,
+        };
+        serde_json::to_string(&event).unwrap()
+    }
+
+    #[test]
+    fn test_event_format() {
+        assert_eq!(
+            event(5, Some(2), 7, PacketStatus::RouterEnqueued),
+            r#"{"time_ns":5,"host_id":2,"packet":"1:7","status":"RouterEnqueued"}"#
+        );
+        assert_eq!(
+            event(5, None, 7, PacketStatus::InetDropped),
+            r#"{"time_ns":5,"host_id":null,"packet":"1:7","status":"InetDropped"}"#
+        );
+    }
+
+    #[test]
+    fn test_lifecycles() {
+        let trace = [
+            event(0, Some(1), 1, PacketStatus::SndCreated),
+            event(0, Some(1), 2, PacketStatus::SndCreated),
+            event(10, Some(1), 1, PacketStatus::SndInterfaceSent),
+            event(10, Some(1), 1, PacketStatus::InetSent),
+            event(20, Some(1), 2, PacketStatus::SndInterfaceSent),
+            event(20, Some(1), 2, PacketStatus::InetSent),
+            // a later round
+            event(110, Some(2), 1, PacketStatus::RouterEnqueued),
+            event(110, Some(2), 1, PacketStatus::RouterDequeued),
+            event(110, Some(2), 1, PacketStatus::RcvInterfaceReceived),
+            event(110, Some(2), 1, PacketStatus::RcvSocketProcessed),
+            event(110, Some(2), 1, PacketStatus::RcvSocketBuffered),
+        ]
+        .join("\n");
+
+        let lifecycles = read_lifecycles(trace.as_bytes()).unwrap();
+        assert_eq!(lifecycles.len(), 2);
+        assert_eq!(lifecycles[0].id.counter, 1);
+        assert_eq!(lifecycles[0].events.len(), 8);
+        assert_eq!(
+            lifecycles[0].propagation_delay(),
+            Some(SimulationTime::from_nanos(100))
+        );
+        assert_eq!(
+            lifecycles[0].delivery_delay(),
+            Some(SimulationTime::from_nanos(110))
+        );
+        assert_eq!(lifecycles[0].drop_event(), None);
+
+        assert_eq!(lifecycles[1].events.len(), 3);
+        assert_eq!(lifecycles[1].propagation_delay(), None);
+        assert_eq!(lifecycles[1].delivery_delay(), None);
+    }
+
+    #[test]
+    fn test_drop_at_link() {
+        // the drop is written in an earlier round than the events before it
+        let trace = [
+            event(50, None, 1, PacketStatus::InetDropped),
+            event(0, Some(1), 1, PacketStatus::SndCreated),
+            event(10, Some(1), 1, PacketStatus::SndInterfaceSent),
+            event(10, Some(1), 1, PacketStatus::InetSent),
+        ]
+        .join("\n");
+
+        let lifecycles = read_lifecycles(trace.as_bytes()).unwrap();
+        assert_eq!(lifecycles.len(), 1);
+
+        let statuses: Vec<_> = lifecycles[0].events.iter().map(|x| x.status).collect();
+        assert_eq!(
+            statuses,
+            [
+                PacketStatus::SndCreated,
+                PacketStatus::SndInterfaceSent,
+                PacketStatus::InetSent,
+                PacketStatus::InetDropped,
+            ]
+        );
+
+        let dropped = lifecycles[0].drop_event().unwrap();
+        assert_eq!(dropped.host_id, None);
+        assert_eq!(dropped.time_ns, 50);
+    }
+
+    #[test]
+    fn test_invalid_line() {
+        let trace = [
+            event(0, Some(1), 1, PacketStatus::SndCreated),
+            r#"{"time_ns":0,"host_id":1,"packet":"1","status":"SndCreated"}"#.to_string(),
+        ]
+        .join("\n");
+        assert!(read_lifecycles(trace.as_bytes()).is_err());
+    }
+}
@@ -89,15 +89,10 @@ impl Relay {
     /// internally schedules tasks as needed to ensure packets continue to be
     /// forwarded over time without exceeding the configured `RateLimit`.
     pub fn new(rate: RateLimit, src_dev_address: Ipv4Addr) -> Self {
-        let rate_limiter = match rate {
-            RateLimit::BytesPerSecond(bytes) => Some(create_token_bucket(bytes)),
-            RateLimit::Unlimited => None,
-        };
-
         Self {
             internal: AtomicRefCell::new(RelayInternal {
                 _counter: ObjectCounter::new("Relay"),
-                rate_limiter,
+                rate_limiter: create_rate_limiter(rate),
                 src_dev_address,
                 state: RelayState::Idle,
                 next_packet: None,
@@ -105,6 +100,21 @@ impl Relay {
         }
     }
 
+    /// Change the `RateLimit` that the relay enforces. Packets that are forwarded after this call
+    /// will follow the new `RateLimit`, starting with a full token bucket.
+    pub fn set_rate_limit(&self, rate: RateLimit) {
+        self.internal.borrow_mut().rate_limiter = create_rate_limiter(rate);
+    }
+
+    /// The number of packets that the relay is holding until it has the bandwidth to forward them.
+    pub fn queued_packets(&self) -> usize {
+        // the relay only holds a packet while it's waiting for its forwarding task, so it isn't
+        // borrowed when it has one
+        self.internal
+            .try_borrow()
+            .map_or(0, |x| x.next_packet.iter().count())
+    }
+
     /// Notify the relay that its packet source now has packets available for
     /// relaying to the packet sink. This must be called when the source changes
     /// state from empty to non-empty to signal the relay to resume forwarding.
@@ -273,6 +283,13 @@ impl Relay {
     }
 }
 
+fn create_rate_limiter(rate: RateLimit) -> Option<TokenBucket> {
+    match rate {
+        RateLimit::BytesPerSecond(bytes) => Some(create_token_bucket(bytes)),
+        RateLimit::Unlimited => None,
+    }
+}
+
 /// Configures a token bucket according the the given bytes_per_second rate
 /// limit. We always refill at least 1 byte per millisecond.
 fn create_token_bucket(bytes_per_second: u64) -> TokenBucket {
@@ -50,6 +50,11 @@ impl Router {
         Worker::with_active_host(|src_host| Worker::send_packet(src_host, packet)).unwrap();
     }
 
+    /// The number of packets inbound to the host that are queued.
+    pub fn queued_packets(&self) -> usize {
+        self.inbound_packets.borrow().len()
+    }
+
     /// Routes the packet from the virtual internet into our CoDel queue, which
     /// can then be received by the destiantion host by calling pop().
     pub fn route_incoming_packet(&self, packet: PacketRc) {
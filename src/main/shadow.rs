@@ -15,10 +15,10 @@ use signal_hook::{consts, iterator::Signals};
 
 use crate::core::configuration::{CliOptions, ConfigFileOptions, ConfigOptions};
 use crate::core::controller::Controller;
-#[cfg(feature = "enable_run_control")]
-use crate::core::manager::{RestartRequest, set_restart_run_until};
 use crate::core::logger::shadow_logger;
-use crate::core::sim_config::SimConfig;
+#[cfg(feature = "enable_run_control")]
+use crate::core::run_control::{self, RestartRequest, set_restart_run_until};
+use crate::core::sim_config::{SimConfig, expand_hosts};
 use crate::core::worker;
 use crate::cshadow as c;
 use crate::utility::shm_cleanup;
@@ -100,6 +100,11 @@ pub fn run_shadow(args: Vec<&OsStr>) -> anyhow::Result<()> {
     let shadow_config = ConfigOptions::new(config_file, options.clone());
 
     if options.show_config {
+        // show each host that the host entries expand to
+        let shadow_config = ConfigOptions {
+            hosts: expand_hosts(&shadow_config.hosts).context("Failed to expand the hosts")?,
+            ..shadow_config
+        };
         eprintln!("{shadow_config:#?}");
         return Ok(());
     }
@@ -222,6 +227,29 @@ pub fn run_shadow(args: Vec<&OsStr>) -> anyhow::Result<()> {
 
     let debug_hosts = options.debug_hosts.clone().unwrap_or_default();
 
+    #[cfg(feature = "enable_run_control")]
+    if !shadow_config
+        .experimental
+        .distributed_addresses
+        .as_ref()
+        .unwrap()
+        .is_empty()
+        && options.run_control_socket.is_some()
+    {
+        anyhow::bail!("Run-control can't be used when the simulation is distributed");
+    }
+
+    #[cfg(feature = "enable_run_control")]
+    if let Some(path) = &options.run_control_socket {
+        run_control::spawn_run_control_socket_thread(path).with_context(|| {
+            format!(
+                "Could not create the run-control socket at {}",
+                path.display()
+            )
+        })?;
+        log::info!("Serving run-control requests on {}", path.display());
+    }
+
     loop {
         let sim_config = SimConfig::new(&shadow_config, &debug_hosts)
             .context("Failed to initialize the simulation")?;
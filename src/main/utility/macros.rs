@@ -153,22 +153,39 @@ macro_rules! log_syscall {
                     writer: impl std::io::Write,
                     args: [shadow_shim_helper_rs::syscall_types::SyscallReg; 6],
                     rv: &crate::host::syscall::types::SyscallResult,
-                    fmt: crate::host::syscall::formatter::FmtOptions,
+                    options: crate::host::syscall::formatter::StraceOptions,
+                    pid: crate::host::process::ProcessId,
                     tid: crate::host::thread::ThreadId,
                     mem: &crate::host::memory_manager::MemoryManager,
                 ) -> std::io::Result<()>
                 {
-                    let syscall_args = <crate::host::syscall::formatter::SyscallArgsFmt::<$($args),*>>::new(args, fmt, mem);
-                    let syscall_rv = crate::host::syscall::formatter::SyscallResultFmt::<$rv>::new(&rv, args, fmt, mem);
-
-                    crate::host::syscall::formatter::write_syscall(
-                        writer,
-                        &crate::host::syscall::handler::Worker::current_time().unwrap(),
-                        tid,
-                        std::stringify!($name),
-                        syscall_args,
-                        syscall_rv,
-                    )
+                    let syscall_args = <crate::host::syscall::formatter::SyscallArgsFmt::<$($args),*>>::new(args, options.fmt, mem);
+                    let syscall_rv = crate::host::syscall::formatter::SyscallResultFmt::<$rv>::new(&rv, args, options.fmt, mem);
+                    let time = crate::host::syscall::handler::Worker::current_time().unwrap();
+
+                    match options.format {
+                        crate::host::syscall::formatter::StraceFormat::Text => {
+                            crate::host::syscall::formatter::write_syscall(
+                                writer,
+                                &time,
+                                tid,
+                                std::stringify!($name),
+                                syscall_args,
+                                syscall_rv,
+                            )
+                        }
+                        crate::host::syscall::formatter::StraceFormat::Json => {
+                            crate::host::syscall::formatter::write_syscall_json(
+                                writer,
+                                &time,
+                                pid,
+                                tid,
+                                std::stringify!($name),
+                                Some(syscall_args.records()),
+                                syscall_rv.record(),
+                            )
+                        }
+                    }
                 }
             }
         };
@@ -15,8 +15,10 @@ pub mod interval_map;
 pub mod legacy_callback_queue;
 pub mod once_set;
 pub mod pcap_writer;
+pub mod pcapng_writer;
 pub mod perf_timer;
 pub mod proc_maps;
+pub mod round_writer;
 pub mod shm_cleanup;
 pub mod sockaddr;
 pub mod status_bar;
@@ -0,0 +1,232 @@
+//! A writer of the pcapng capture format, which unlike the classic pcap format can hold the packets
+//! of many interfaces and comments for each packet.
+//!
+//! <https://www.ietf.org/archive/id/draft-ietf-opsawg-pcapng-03.html>
+
+use std::io::Write;
+
+// block types
+const SECTION_HEADER_BLOCK: u32 = 0x0A0D0D0A;
+const INTERFACE_DESCRIPTION_BLOCK: u32 = 0x00000001;
+const ENHANCED_PACKET_BLOCK: u32 = 0x00000006;
+
+// option codes
+const OPT_ENDOFOPT: u16 = 0;
+const OPT_COMMENT: u16 = 1;
+const IF_NAME: u16 = 2;
+const IF_TSRESOL: u16 = 9;
+const EPB_FLAGS: u16 = 2;
+
+/// The direction of a captured packet, relative to its interface.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PacketDirection {
+    Inbound,
+    Outbound,
+}
+
+pub struct PcapngWriter<W: Write> {
+    writer: W,
+    num_interfaces: u32,
+}
+
+impl<W: Write> PcapngWriter<W> {
+    /// A new pcapng writer, which starts a section with no interfaces.
+    pub fn new(writer: W) -> std::io::Result<Self> {
+        // magic number to show endianness
+        const BYTE_ORDER_MAGIC: u32 = 0x1A2B3C4D;
+        const VERSION_MAJOR: u16 = 1;
+        const VERSION_MINOR: u16 = 0;
+        // the section length isn't known
+        const SECTION_LENGTH: i64 = -1;
+
+        let mut rv = Self {
+            writer,
+            num_interfaces: 0,
+        };
+
+        let mut body = Vec::new();
+        body.extend_from_slice(&BYTE_ORDER_MAGIC.to_ne_bytes());
+        body.extend_from_slice(&VERSION_MAJOR.to_ne_bytes());
+        body.extend_from_slice(&VERSION_MINOR.to_ne_bytes());
+        body.extend_from_slice(&SECTION_LENGTH.to_ne_bytes());
+        rv.write_block(SECTION_HEADER_BLOCK, &body)?;
+
+        Ok(rv)
+    }
+
+    /// Add an interface named `name`, whose packets will be truncated to a length `capture_len`.
+    /// Returns the id of the interface. Timestamps of the interface's packets are in nanoseconds.
+    pub fn add_interface(&mut self, name: &str, capture_len: u32) -> std::io::Result<u32> {
+        // data link type (LINKTYPE_RAW)
+        const LINK_TYPE: u16 = 101;
+        // timestamps are in units of 10^-9 seconds
+        const TIMESTAMP_RESOLUTION: u8 = 9;
+
+        let mut body = Vec::new();
+        body.extend_from_slice(&LINK_TYPE.to_ne_bytes());
+        // reserved
+        body.extend_from_slice(&0u16.to_ne_bytes());
+        body.extend_from_slice(&capture_len.to_ne_bytes());
+        write_option(&mut body, IF_NAME, name.as_bytes());
+        write_option(&mut body, IF_TSRESOL, &[TIMESTAMP_RESOLUTION]);
+        write_option(&mut body, OPT_ENDOFOPT, &[]);
+        self.write_block(INTERFACE_DESCRIPTION_BLOCK, &body)?;
+
+        let id = self.num_interfaces;
+        self.num_interfaces += 1;
+        Ok(id)
+    }
+
+    /// Write the captured bytes `packet` of a packet that was `original_len` bytes long. The
+    /// captured bytes should already be truncated to the interface's capture length.
+    pub fn write_packet(
+        &mut self,
+        interface_id: u32,
+        timestamp_ns: u64,
+        packet: &[u8],
+        original_len: u32,
+        direction: Option<PacketDirection>,
+        comment: Option<&str>,
+    ) -> std::io::Result<()> {
+        assert!(interface_id < self.num_interfaces);
+
+        let mut body = Vec::new();
+        body.extend_from_slice(&interface_id.to_ne_bytes());
+        // timestamp (high and low 32 bits)
+        body.extend_from_slice(&((timestamp_ns >> 32) as u32).to_ne_bytes());
+        body.extend_from_slice(&(timestamp_ns as u32).to_ne_bytes());
+        body.extend_from_slice(&u32::try_from(packet.len()).unwrap().to_ne_bytes());
+        body.extend_from_slice(&original_len.to_ne_bytes());
+        body.extend_from_slice(packet);
+        pad(&mut body);
+
+        if let Some(comment) = comment {
+            write_option(&mut body, OPT_COMMENT, comment.as_bytes());
+        }
+        if let Some(direction) = direction {
+            let flags: u32 = match direction {
+                PacketDirection::Inbound => 0b01,
+                PacketDirection::Outbound => 0b10,
+            };
+            write_option(&mut body, EPB_FLAGS, &flags.to_ne_bytes());
+        }
+        if comment.is_some() || direction.is_some() {
+            write_option(&mut body, OPT_ENDOFOPT, &[]);
+        }
+
+        self.write_block(ENHANCED_PACKET_BLOCK, &body)
+    }
+
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Write a block whose body has already been padded to a multiple of 4 bytes.
+    fn write_block(&mut self, block_type: u32, body: &[u8]) -> std::io::Result<()> {
+        debug_assert_eq!(body.len() % 4, 0);
+        // block type, two block lengths, and the body
+        let len = u32::try_from(4 + 4 + body.len() + 4).unwrap();
+
+        self.writer.write_all(&block_type.to_ne_bytes())?;
+        self.writer.write_all(&len.to_ne_bytes())?;
+        self.writer.write_all(body)?;
+        self.writer.write_all(&len.to_ne_bytes())?;
+
+        Ok(())
+    }
+}
+
+/// Append an option to a block body, padded to a multiple of 4 bytes.
+fn write_option(body: &mut Vec<u8>, code: u16, value: &[u8]) {
+    body.extend_from_slice(&code.to_ne_bytes());
+    body.extend_from_slice(&u16::try_from(value.len()).unwrap().to_ne_bytes());
+    body.extend_from_slice(value);
+    pad(body);
+}
+
+fn pad(body: &mut Vec<u8>) {
+    body.resize(body.len().next_multiple_of(4), 0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u32s(buf: &[u8]) -> Vec<u32> {
+        buf.chunks(4)
+            .map(|x| u32::from_ne_bytes(x.try_into().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_section_header() {
+        let mut buf = vec![];
+        PcapngWriter::new(&mut buf).unwrap();
+
+        assert_eq!(buf.len(), 28);
+        assert_eq!(u32s(&buf[..12]), [SECTION_HEADER_BLOCK, 28, 0x1A2B3C4D]);
+        assert_eq!(
+            buf[12..16],
+            [1u16.to_ne_bytes(), 0u16.to_ne_bytes()].concat()
+        );
+        assert_eq!(buf[16..24], [0xFF; 8]);
+        assert_eq!(u32s(&buf[24..]), [28]);
+    }
+
+    #[test]
+    fn test_interface() {
+        let mut buf = vec![];
+        let mut pcapng = PcapngWriter::new(&mut buf).unwrap();
+        assert_eq!(pcapng.add_interface("host:eth0", 65535).unwrap(), 0);
+        assert_eq!(pcapng.add_interface("host:lo", 100).unwrap(), 1);
+
+        let idb = &buf[28..];
+        // header and link type, snap length, name option (4 + 12), tsresol option (4 + 4), end
+        // option, and the length
+        let len = 8 + 4 + 4 + 16 + 8 + 4 + 4;
+        assert_eq!(u32s(&idb[..8]), [INTERFACE_DESCRIPTION_BLOCK, len]);
+        assert_eq!(u32s(&idb[12..16]), [65535]);
+        assert_eq!(&idb[20..29], b"host:eth0");
+        assert_eq!(&idb[29..32], [0, 0, 0]);
+        assert_eq!(idb[36], 9);
+        assert_eq!(u32s(&idb[44..48]), [len]);
+        // the second interface's name is padded to 8 bytes instead of 12
+        assert_eq!(idb.len(), len as usize + (len as usize - 4));
+    }
+
+    #[test]
+    fn test_write_packet() {
+        let mut buf = vec![];
+        let mut pcapng = PcapngWriter::new(&mut buf).unwrap();
+        pcapng.add_interface("eth0", 65535).unwrap();
+        pcapng
+            .write_packet(
+                0,
+                (1 << 32) + 5,
+                &[0x01, 0x02, 0x03],
+                10,
+                Some(PacketDirection::Outbound),
+                Some("id=0:1"),
+            )
+            .unwrap();
+
+        // after the section header and interface blocks
+        let epb = &buf[28 + 40..];
+        // header, interface id, timestamp, lengths, padded data, comment (4 + 8), flags (4 + 4),
+        // end option, and the length
+        let len = 8 + 4 + 8 + 8 + 4 + 12 + 8 + 4 + 4;
+        assert_eq!(
+            u32s(&epb[..28]),
+            [ENHANCED_PACKET_BLOCK, len, 0, 1, 5, 3, 10]
+        );
+        assert_eq!(&epb[28..32], [0x01, 0x02, 0x03, 0x00]);
+        assert_eq!(
+            epb[32..36],
+            [OPT_COMMENT.to_ne_bytes(), 6u16.to_ne_bytes()].concat()
+        );
+        assert_eq!(&epb[36..44], b"id=0:1\0\0");
+        assert_eq!(u32s(&epb[48..52]), [0b10]);
+        assert_eq!(u32s(&epb[52..60]), [0, len]);
+        assert_eq!(epb.len(), len as usize);
+    }
+}
@@ -0,0 +1,84 @@
+//! A writer for items that hosts record in parallel during a round, which are written in a
+//! deterministic order between rounds.
+
+use std::sync::Mutex;
+
+use shadow_shim_helper_rs::HostId;
+use shadow_shim_helper_rs::emulated_time::EmulatedTime;
+
+/// Buffers the items that hosts record during a round, and writes them to `W` between rounds
+/// ordered by time and then by host. The items of a host that have the same time are written in
+/// the order that the host recorded them.
+pub struct RoundWriter<T, W> {
+    items: Mutex<Vec<(EmulatedTime, Option<HostId>, T)>>,
+    writer: Mutex<W>,
+}
+
+impl<T, W> RoundWriter<T, W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            items: Mutex::new(Vec::new()),
+            writer: Mutex::new(writer),
+        }
+    }
+
+    /// Record `item` at `time` on host `host_id`, or outside of any host if `host_id` is `None`.
+    /// Items without a host are written before the items of hosts that have the same time.
+    pub fn record(&self, time: EmulatedTime, host_id: Option<HostId>, item: T) {
+        self.items.lock().unwrap().push((time, host_id, item));
+    }
+
+    /// Write the items that were recorded during the round using `write`. Must only be called
+    /// between rounds.
+    pub fn finish_round(
+        &self,
+        mut write: impl FnMut(&mut W, T) -> std::io::Result<()>,
+    ) -> std::io::Result<()> {
+        let mut items = std::mem::take(&mut *self.items.lock().unwrap());
+        // a stable sort, so each host's items at the same time stay in the order it recorded them
+        items.sort_by_key(|(time, host_id, _)| (*time, *host_id));
+
+        let mut writer = self.writer.lock().unwrap();
+        for (_, _, item) in items {
+            write(&mut writer, item)?;
+        }
+
+        Ok(())
+    }
+
+    /// Run `f` with the writer, for example to flush it after the last round.
+    pub fn with_writer<R>(&self, f: impl FnOnce(&mut W) -> R) -> R {
+        f(&mut self.writer.lock().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use shadow_shim_helper_rs::simulation_time::SimulationTime;
+
+    use super::*;
+
+    fn time(ns: u64) -> EmulatedTime {
+        EmulatedTime::SIMULATION_START + SimulationTime::from_nanos(ns)
+    }
+
+    #[test]
+    fn test_order() {
+        let writer = RoundWriter::new(Vec::new());
+        let write = |w: &mut Vec<&str>, x| {
+            w.push(x);
+            Ok(())
+        };
+
+        writer.record(time(10), Some(HostId::from(2)), "a");
+        writer.record(time(10), Some(HostId::from(1)), "b");
+        writer.record(time(5), Some(HostId::from(2)), "c");
+        writer.record(time(10), None, "d");
+        writer.record(time(10), Some(HostId::from(2)), "e");
+        writer.finish_round(write).unwrap();
+        writer.record(time(1), Some(HostId::from(1)), "f");
+        writer.finish_round(write).unwrap();
+
+        writer.with_writer(|w| assert_eq!(w, &["c", "d", "b", "a", "e", "f"]));
+    }
+}
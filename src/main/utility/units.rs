@@ -556,6 +556,17 @@ impl From<Time<TimePrefixUpper>> for std::time::Duration {
     }
 }
 
+/// An amount of time that may be negative, such as a clock offset. Should only
+/// use the time prefix types ([`TimePrefix`] and [`TimePrefixUpper`]) with this
+/// type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SignedTime<T: Prefix> {
+    value: i64,
+    prefix: T,
+}
+
+unit_impl!(SignedTime, i64, [""]);
+
 /// A number of bytes.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Bytes<T: Prefix> {
@@ -631,6 +642,16 @@ mod tests {
         assert!(Time::<TimePrefix>::from_str("10 abc").is_err());
         assert!(Time::<TimePrefixUpper>::from_str("10 ms").is_err());
 
+        assert_eq!(
+            SignedTime::from_str("-10 ms").unwrap(),
+            SignedTime::new(-10, TimePrefix::Milli)
+        );
+        assert_eq!(
+            SignedTime::from_str("+10 s").unwrap(),
+            SignedTime::new(10, TimePrefix::Sec)
+        );
+        assert!(SignedTime::<TimePrefix>::from_str("-10.5 ms").is_err());
+
         assert_eq!(
             Bytes::from_str("10").unwrap(),
             Bytes::new(10, SiPrefixUpper::Base)
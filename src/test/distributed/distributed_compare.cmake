@@ -0,0 +1,35 @@
+macro(EXEC_DIFF_CHECK FILE1 FILE2)
+    execute_process(
+        COMMAND ${CMAKE_COMMAND} -E compare_files ${FILE1} ${FILE2}
+        RESULT_VARIABLE RESULT
+        OUTPUT_VARIABLE STDOUTPUT
+        ERROR_VARIABLE STDERROR)
+    message(STATUS "Diff returned ${RESULT} for 'diff ${FILE1} ${FILE2}'")
+    if(RESULT)
+        message(STATUS "Diff stdout is: ${STDOUTPUT}")
+        message(STATUS "Diff stderr is: ${STDERROR}")
+        message(FATAL_ERROR "Differences found; test failed")
+    endif()
+endmacro()
+
+set(SINGLE_DIR ${CMAKE_BINARY_DIR}/distributed-phold-single-shadow.data/hosts)
+set(RANK0_DIR ${CMAKE_BINARY_DIR}/distributed-phold-rank0.data/hosts)
+set(RANK1_DIR ${CMAKE_BINARY_DIR}/distributed-phold-rank1.data/hosts)
+
+foreach(LOOPIDX RANGE 1 10)
+    set(HOST peer${LOOPIDX})
+
+    # each host is run by exactly one of the processes, which writes the host's output to its own
+    # data directory
+    if(EXISTS ${RANK0_DIR}/${HOST} AND NOT EXISTS ${RANK1_DIR}/${HOST})
+        set(HOST_DIR ${RANK0_DIR}/${HOST})
+    elseif(EXISTS ${RANK1_DIR}/${HOST} AND NOT EXISTS ${RANK0_DIR}/${HOST})
+        set(HOST_DIR ${RANK1_DIR}/${HOST})
+    else()
+        message(FATAL_ERROR "Host ${HOST} wasn't run by exactly one of the processes")
+    endif()
+
+    foreach(FILE test-phold.1000.stdout test-phold.1000.strace eth0.pcap)
+        exec_diff_check(${SINGLE_DIR}/${HOST}/${FILE} ${HOST_DIR}/${FILE})
+    endforeach()
+endforeach(LOOPIDX)
@@ -0,0 +1,42 @@
+#!/bin/sh
+
+# Run a simulation split across two Shadow processes on this machine. Each process writes to its
+# own data directory "<name>-rank<N>.data", and its log to "<name>-rank<N>.log".
+#
+# usage: run_distributed.sh <shadow> <config> <name> [shadow args...]
+
+set -u
+
+SHADOW=$1
+CONFIG=$2
+NAME=$3
+shift 3
+
+# the processes connect to each other on these ports, so tests using this script must not run in
+# parallel with each other
+ADDRESSES=127.0.0.1:38411,127.0.0.1:38412
+
+run_rank() {
+    RANK=$1
+    shift
+    rm -rf "$NAME-rank$RANK.data"
+    "$SHADOW" \
+        --distributed-addresses "$ADDRESSES" \
+        --distributed-rank "$RANK" \
+        --data-directory "$NAME-rank$RANK.data" \
+        "$@" \
+        "$CONFIG" > "$NAME-rank$RANK.log" 2>&1
+}
+
+run_rank 0 "$@" &
+PID0=$!
+run_rank 1 "$@"
+RV1=$?
+wait "$PID0"
+RV0=$?
+
+if [ "$RV0" -ne 0 ] || [ "$RV1" -ne 0 ]; then
+    echo "Shadow processes exited with $RV0 (rank 0) and $RV1 (rank 1)"
+    cat "$NAME-rank0.log" "$NAME-rank1.log"
+    exit 1
+fi
@@ -0,0 +1,463 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+use std::ffi::{CString, OsStr};
+use std::io::Write;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::RawFd;
+use std::path::Path;
+use std::time::Duration;
+
+use nix::errno::Errno;
+use nix::sys::epoll::{self, EpollEvent, EpollFlags, EpollOp};
+use nix::unistd::close;
+use test_utils::TestEnvironment as TestEnv;
+use test_utils::{ensure_ord, set};
+
+const EVENT_SIZE: usize = std::mem::size_of::<libc::inotify_event>();
+
+/// An event read from an inotify file descriptor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Event {
+    wd: i32,
+    mask: u32,
+    cookie: u32,
+    name: Option<String>,
+}
+
+impl Event {
+    fn new(wd: i32, mask: u32, name: &str) -> Self {
+        Self {
+            wd,
+            mask,
+            cookie: 0,
+            name: Some(name.to_string()),
+        }
+    }
+}
+
+fn inotify_init1(flags: i32) -> nix::Result<RawFd> {
+    Errno::result(unsafe { libc::inotify_init1(flags) })
+}
+
+fn inotify_add_watch(fd: RawFd, path: &Path, mask: u32) -> nix::Result<i32> {
+    let path = CString::new(path.as_os_str().as_bytes()).unwrap();
+    Errno::result(unsafe { libc::inotify_add_watch(fd, path.as_ptr(), mask) })
+}
+
+fn inotify_rm_watch(fd: RawFd, wd: i32) -> nix::Result<()> {
+    Errno::result(unsafe { libc::inotify_rm_watch(fd, wd) }).map(|_| ())
+}
+
+/// Read and parse as many events as are available in a single `read()`.
+fn read_events(fd: RawFd) -> nix::Result<Vec<Event>> {
+    let mut buf = vec![0u8; 4096];
+    let len = nix::unistd::read(fd, &mut buf)?;
+    let buf = &buf[..len];
+
+    let mut events = Vec::new();
+    let mut offset = 0;
+    while offset < buf.len() {
+        let header: libc::inotify_event =
+            unsafe { std::ptr::read_unaligned(buf[offset..].as_ptr().cast()) };
+        let name_start = offset + EVENT_SIZE;
+        let name_end = name_start + usize::try_from(header.len).unwrap();
+        let name = (header.len > 0).then(|| {
+            let name = &buf[name_start..name_end];
+            let nul = name.iter().position(|x| *x == 0).unwrap();
+            OsStr::from_bytes(&name[..nul])
+                .to_string_lossy()
+                .into_owned()
+        });
+        events.push(Event {
+            wd: header.wd,
+            mask: header.mask,
+            cookie: header.cookie,
+            name,
+        });
+        offset = name_end;
+    }
+
+    Ok(events)
+}
+
+fn main() -> anyhow::Result<()> {
+    // should we restrict the tests we run?
+    let filter_shadow_passing = std::env::args().any(|x| x == "--shadow-passing");
+    let filter_libc_passing = std::env::args().any(|x| x == "--libc-passing");
+
+    // should we summarize the results rather than exit on a failed test
+    let summarize = std::env::args().any(|x| x == "--summarize");
+
+    let all_envs = set![TestEnv::Libc, TestEnv::Shadow];
+    let mut tests: Vec<test_utils::ShadowTest<_, _>> = vec![
+        test_utils::ShadowTest::new("test_invalid_flags", test_invalid_flags, all_envs.clone()),
+        test_utils::ShadowTest::new("test_invalid_fd", test_invalid_fd, all_envs.clone()),
+        test_utils::ShadowTest::new(
+            "test_add_watch_errors",
+            test_add_watch_errors,
+            all_envs.clone(),
+        ),
+        test_utils::ShadowTest::new(
+            "test_add_watch_same_path",
+            test_add_watch_same_path,
+            all_envs.clone(),
+        ),
+        test_utils::ShadowTest::new(
+            "test_create_modify_close",
+            test_create_modify_close,
+            all_envs.clone(),
+        ),
+        test_utils::ShadowTest::new("test_delete", test_delete, all_envs.clone()),
+        test_utils::ShadowTest::new("test_mkdir_rmdir", test_mkdir_rmdir, all_envs.clone()),
+        test_utils::ShadowTest::new("test_rename", test_rename, all_envs.clone()),
+        test_utils::ShadowTest::new("test_watch_file", test_watch_file, all_envs.clone()),
+        test_utils::ShadowTest::new("test_rm_watch", test_rm_watch, all_envs.clone()),
+        test_utils::ShadowTest::new("test_oneshot", test_oneshot, all_envs.clone()),
+        test_utils::ShadowTest::new("test_nonblock", test_nonblock, all_envs.clone()),
+        test_utils::ShadowTest::new("test_small_buffer", test_small_buffer, all_envs.clone()),
+        test_utils::ShadowTest::new("test_fionread", test_fionread, all_envs.clone()),
+        test_utils::ShadowTest::new("test_epoll", test_epoll, all_envs.clone()),
+        test_utils::ShadowTest::new("test_blocking_read", test_blocking_read, all_envs),
+    ];
+
+    if filter_shadow_passing {
+        tests.retain(|x| x.passing(TestEnv::Shadow));
+    }
+    if filter_libc_passing {
+        tests.retain(|x| x.passing(TestEnv::Libc));
+    }
+
+    test_utils::run_tests(&tests, summarize)?;
+
+    println!("Success.");
+    Ok(())
+}
+
+/// Run `f` with a new inotify fd and an empty temporary directory, closing the
+/// fd afterwards.
+fn with_inotify(
+    flags: i32,
+    f: impl FnOnce(RawFd, &Path) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let fd = inotify_init1(flags)?;
+    let rv = f(fd, dir.path());
+    close(fd)?;
+    rv
+}
+
+fn test_invalid_flags() -> anyhow::Result<()> {
+    ensure_ord!(inotify_init1(libc::O_RDWR), ==, Err(Errno::EINVAL));
+    Ok(())
+}
+
+fn test_invalid_fd() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+
+    ensure_ord!(inotify_add_watch(-1, dir.path(), libc::IN_ALL_EVENTS), ==, Err(Errno::EBADF));
+
+    // an fd that isn't an inotify instance
+    let efd = nix::sys::eventfd::eventfd(0, nix::sys::eventfd::EfdFlags::empty())?;
+    let rv = inotify_add_watch(efd, dir.path(), libc::IN_ALL_EVENTS);
+    close(efd)?;
+    ensure_ord!(rv, ==, Err(Errno::EINVAL));
+
+    Ok(())
+}
+
+fn test_add_watch_errors() -> anyhow::Result<()> {
+    with_inotify(libc::IN_NONBLOCK, |fd, dir| {
+        // no events
+        ensure_ord!(inotify_add_watch(fd, dir, 0), ==, Err(Errno::EINVAL));
+
+        // missing path
+        ensure_ord!(inotify_add_watch(fd, &dir.join("missing"), libc::IN_ALL_EVENTS), ==, Err(Errno::ENOENT));
+
+        // not a directory
+        let file = dir.join("file");
+        std::fs::write(&file, b"")?;
+        ensure_ord!(inotify_add_watch(fd, &file, libc::IN_ALL_EVENTS | libc::IN_ONLYDIR), ==, Err(Errno::ENOTDIR));
+
+        Ok(())
+    })
+}
+
+fn test_add_watch_same_path() -> anyhow::Result<()> {
+    with_inotify(libc::IN_NONBLOCK, |fd, dir| {
+        let wd = inotify_add_watch(fd, dir, libc::IN_CREATE)?;
+        ensure_ord!(wd, >, 0);
+
+        // watching the same path again returns the same wd, even through a
+        // different path string
+        let wd_again = inotify_add_watch(fd, &dir.join("."), libc::IN_DELETE)?;
+        ensure_ord!(wd, ==, wd_again);
+
+        ensure_ord!(inotify_add_watch(fd, dir, libc::IN_CREATE | libc::IN_MASK_CREATE), ==, Err(Errno::EEXIST));
+
+        // the previous call replaced the mask, so only deletions are reported
+        std::fs::write(dir.join("a"), b"")?;
+        std::fs::remove_file(dir.join("a"))?;
+        ensure_ord!(read_events(fd)?, ==, vec![Event::new(wd, libc::IN_DELETE, "a")]);
+
+        Ok(())
+    })
+}
+
+fn test_create_modify_close() -> anyhow::Result<()> {
+    with_inotify(libc::IN_NONBLOCK, |fd, dir| {
+        let wd = inotify_add_watch(
+            fd,
+            dir,
+            libc::IN_CREATE | libc::IN_MODIFY | libc::IN_CLOSE_WRITE,
+        )?;
+
+        let mut file = std::fs::File::create(dir.join("a"))?;
+        file.write_all(b"hello")?;
+        drop(file);
+
+        ensure_ord!(read_events(fd)?, ==, vec![
+            Event::new(wd, libc::IN_CREATE, "a"),
+            Event::new(wd, libc::IN_MODIFY, "a"),
+            Event::new(wd, libc::IN_CLOSE_WRITE, "a"),
+        ]);
+
+        Ok(())
+    })
+}
+
+fn test_delete() -> anyhow::Result<()> {
+    with_inotify(libc::IN_NONBLOCK, |fd, dir| {
+        std::fs::write(dir.join("a"), b"")?;
+        std::fs::write(dir.join("b"), b"")?;
+
+        let wd = inotify_add_watch(fd, dir, libc::IN_DELETE)?;
+
+        std::fs::remove_file(dir.join("b"))?;
+        std::fs::remove_file(dir.join("a"))?;
+
+        ensure_ord!(read_events(fd)?, ==, vec![
+            Event::new(wd, libc::IN_DELETE, "b"),
+            Event::new(wd, libc::IN_DELETE, "a"),
+        ]);
+
+        Ok(())
+    })
+}
+
+fn test_mkdir_rmdir() -> anyhow::Result<()> {
+    with_inotify(libc::IN_NONBLOCK, |fd, dir| {
+        let wd = inotify_add_watch(fd, dir, libc::IN_CREATE | libc::IN_DELETE)?;
+
+        std::fs::create_dir(dir.join("sub"))?;
+        std::fs::remove_dir(dir.join("sub"))?;
+
+        ensure_ord!(read_events(fd)?, ==, vec![
+            Event::new(wd, libc::IN_CREATE | libc::IN_ISDIR, "sub"),
+            Event::new(wd, libc::IN_DELETE | libc::IN_ISDIR, "sub"),
+        ]);
+
+        Ok(())
+    })
+}
+
+fn test_rename() -> anyhow::Result<()> {
+    with_inotify(libc::IN_NONBLOCK, |fd, dir| {
+        std::fs::write(dir.join("a"), b"")?;
+
+        let wd = inotify_add_watch(fd, dir, libc::IN_MOVE)?;
+
+        std::fs::rename(dir.join("a"), dir.join("b"))?;
+
+        let events = read_events(fd)?;
+        ensure_ord!(events.len(), ==, 2);
+
+        // both halves of the rename share a nonzero cookie
+        let cookie = events[0].cookie;
+        ensure_ord!(cookie, !=, 0);
+        ensure_ord!(events, ==, vec![
+            Event {
+                cookie,
+                ..Event::new(wd, libc::IN_MOVED_FROM, "a")
+            },
+            Event {
+                cookie,
+                ..Event::new(wd, libc::IN_MOVED_TO, "b")
+            },
+        ]);
+
+        Ok(())
+    })
+}
+
+fn test_watch_file() -> anyhow::Result<()> {
+    with_inotify(libc::IN_NONBLOCK, |fd, dir| {
+        let path = dir.join("a");
+        std::fs::write(&path, b"")?;
+
+        let wd = inotify_add_watch(fd, &path, libc::IN_MODIFY | libc::IN_DELETE_SELF)?;
+
+        std::fs::write(&path, b"hello")?;
+        std::fs::remove_file(&path)?;
+
+        // events on the watched file itself have no name, and the watch is
+        // removed once the file is deleted
+        let no_name = |mask| Event {
+            wd,
+            mask,
+            cookie: 0,
+            name: None,
+        };
+        ensure_ord!(read_events(fd)?, ==, vec![
+            no_name(libc::IN_MODIFY),
+            no_name(libc::IN_DELETE_SELF),
+            no_name(libc::IN_IGNORED),
+        ]);
+
+        ensure_ord!(inotify_rm_watch(fd, wd), ==, Err(Errno::EINVAL));
+
+        Ok(())
+    })
+}
+
+fn test_rm_watch() -> anyhow::Result<()> {
+    with_inotify(libc::IN_NONBLOCK, |fd, dir| {
+        let wd = inotify_add_watch(fd, dir, libc::IN_CREATE)?;
+
+        inotify_rm_watch(fd, wd)?;
+        ensure_ord!(inotify_rm_watch(fd, wd), ==, Err(Errno::EINVAL));
+
+        // no more events after the watch was removed
+        std::fs::write(dir.join("a"), b"")?;
+
+        ensure_ord!(read_events(fd)?, ==, vec![Event {
+            wd,
+            mask: libc::IN_IGNORED,
+            cookie: 0,
+            name: None,
+        }]);
+
+        Ok(())
+    })
+}
+
+fn test_oneshot() -> anyhow::Result<()> {
+    with_inotify(libc::IN_NONBLOCK, |fd, dir| {
+        let wd = inotify_add_watch(fd, dir, libc::IN_CREATE | libc::IN_ONESHOT)?;
+
+        std::fs::write(dir.join("a"), b"")?;
+        std::fs::write(dir.join("b"), b"")?;
+
+        ensure_ord!(read_events(fd)?, ==, vec![
+            Event::new(wd, libc::IN_CREATE, "a"),
+            Event {
+                wd,
+                mask: libc::IN_IGNORED,
+                cookie: 0,
+                name: None,
+            },
+        ]);
+
+        Ok(())
+    })
+}
+
+fn test_nonblock() -> anyhow::Result<()> {
+    with_inotify(libc::IN_NONBLOCK, |fd, dir| {
+        inotify_add_watch(fd, dir, libc::IN_ALL_EVENTS)?;
+        ensure_ord!(read_events(fd), ==, Err(Errno::EAGAIN));
+        Ok(())
+    })
+}
+
+fn test_small_buffer() -> anyhow::Result<()> {
+    with_inotify(libc::IN_NONBLOCK, |fd, dir| {
+        let wd = inotify_add_watch(fd, dir, libc::IN_CREATE)?;
+        std::fs::write(dir.join("a"), b"")?;
+
+        // too small to fit the event's header
+        let mut buf = [0u8; EVENT_SIZE - 1];
+        ensure_ord!(nix::unistd::read(fd, &mut buf), ==, Err(Errno::EINVAL));
+
+        // the event is still queued
+        ensure_ord!(read_events(fd)?, ==, vec![Event::new(wd, libc::IN_CREATE, "a")]);
+
+        Ok(())
+    })
+}
+
+fn test_fionread() -> anyhow::Result<()> {
+    with_inotify(libc::IN_NONBLOCK, |fd, dir| {
+        inotify_add_watch(fd, dir, libc::IN_CREATE)?;
+
+        let fionread = || -> nix::Result<libc::c_int> {
+            let mut n: libc::c_int = 0;
+            Errno::result(unsafe { libc::ioctl(fd, libc::FIONREAD, &mut n) })?;
+            Ok(n)
+        };
+
+        ensure_ord!(fionread()?, ==, 0);
+
+        std::fs::write(dir.join("a"), b"")?;
+
+        // the kernel pads names to a multiple of the header size
+        let expected = (EVENT_SIZE * 2) as libc::c_int;
+        ensure_ord!(fionread()?, ==, expected);
+
+        Ok(())
+    })
+}
+
+fn test_epoll() -> anyhow::Result<()> {
+    with_inotify(libc::IN_NONBLOCK, |fd, dir| {
+        inotify_add_watch(fd, dir, libc::IN_CREATE)?;
+
+        let epoll_fd = epoll::epoll_create()?;
+        let mut event = EpollEvent::new(EpollFlags::EPOLLIN, 0);
+        epoll::epoll_ctl(epoll_fd, EpollOp::EpollCtlAdd, fd, &mut event)?;
+
+        let mut events = [EpollEvent::empty(); 10];
+        let rv = (|| -> anyhow::Result<()> {
+            let count = epoll::epoll_wait(epoll_fd, &mut events, 0)?;
+            ensure_ord!(count, ==, 0);
+
+            std::fs::write(dir.join("a"), b"")?;
+
+            let count = epoll::epoll_wait(epoll_fd, &mut events, 0)?;
+            ensure_ord!(count, ==, 1);
+            ensure_ord!(events[0].events(), ==, EpollFlags::EPOLLIN);
+
+            // draining the events makes it unreadable again
+            read_events(fd)?;
+            let count = epoll::epoll_wait(epoll_fd, &mut events, 0)?;
+            ensure_ord!(count, ==, 0);
+
+            Ok(())
+        })();
+
+        close(epoll_fd)?;
+        rv
+    })
+}
+
+fn test_blocking_read() -> anyhow::Result<()> {
+    with_inotify(0, |fd, dir| {
+        let wd = inotify_add_watch(fd, dir, libc::IN_CREATE)?;
+
+        let path = dir.join("a");
+        let thread = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            std::fs::write(path, b"").unwrap();
+        });
+
+        // blocks until the other thread creates the file
+        let events = read_events(fd)?;
+        thread.join().unwrap();
+
+        ensure_ord!(events, ==, vec![Event::new(wd, libc::IN_CREATE, "a")]);
+
+        Ok(())
+    })
+}
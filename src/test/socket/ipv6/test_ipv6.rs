@@ -0,0 +1,322 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+use std::ffi::{CStr, CString};
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6, TcpListener, TcpStream, UdpSocket};
+use std::os::unix::io::{FromRawFd, RawFd};
+
+use nix::errno::Errno;
+use nix::sys::socket::{
+    AddressFamily, SockFlag, SockType, SockaddrIn, SockaddrIn6, bind, connect, getsockopt, listen,
+    setsockopt, socket, sockopt,
+};
+use nix::unistd::close;
+use test_utils::TestEnvironment as TestEnv;
+use test_utils::{ensure_ord, set};
+
+fn main() -> anyhow::Result<()> {
+    // should we restrict the tests we run?
+    let filter_shadow_passing = std::env::args().any(|x| x == "--shadow-passing");
+    let filter_libc_passing = std::env::args().any(|x| x == "--libc-passing");
+
+    // should we summarize the results rather than exit on a failed test
+    let summarize = std::env::args().any(|x| x == "--summarize");
+
+    let all_envs = set![TestEnv::Libc, TestEnv::Shadow];
+    let mut tests: Vec<test_utils::ShadowTest<_, _>> = vec![
+        test_utils::ShadowTest::new("test_tcp_loopback", test_tcp_loopback, all_envs.clone()),
+        test_utils::ShadowTest::new("test_udp_loopback", test_udp_loopback, all_envs.clone()),
+        test_utils::ShadowTest::new("test_v6only", test_v6only, all_envs.clone()),
+        test_utils::ShadowTest::new(
+            "test_v6only_mapped_connect",
+            test_v6only_mapped_connect,
+            all_envs.clone(),
+        ),
+        test_utils::ShadowTest::new("test_bind_ipv4_addr", test_bind_ipv4_addr, all_envs.clone()),
+        test_utils::ShadowTest::new("test_v6only_port", test_v6only_port, all_envs.clone()),
+        test_utils::ShadowTest::new(
+            "test_dual_stack_server",
+            test_dual_stack_server,
+            all_envs.clone(),
+        ),
+        test_utils::ShadowTest::new(
+            "test_getaddrinfo_numeric",
+            test_getaddrinfo_numeric,
+            all_envs,
+        ),
+        // the host's IPv6 address is assigned by Shadow
+        test_utils::ShadowTest::new(
+            "test_tcp_host_address",
+            test_tcp_host_address,
+            set![TestEnv::Shadow],
+        ),
+    ];
+
+    if filter_shadow_passing {
+        tests.retain(|x| x.passing(TestEnv::Shadow));
+    }
+    if filter_libc_passing {
+        tests.retain(|x| x.passing(TestEnv::Libc));
+    }
+
+    test_utils::run_tests(&tests, summarize)?;
+
+    println!("Success.");
+    Ok(())
+}
+
+/// Run `f` with a new IPv6 socket of type `sock_type`, closing the socket afterwards.
+fn with_ipv6_socket(
+    sock_type: SockType,
+    f: impl FnOnce(RawFd) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let fd = socket(AddressFamily::Inet6, sock_type, SockFlag::empty(), None)?;
+    let rv = f(fd);
+    close(fd)?;
+    rv
+}
+
+/// Connect a TCP stream to `listener`, then send data in both directions.
+fn tcp_exchange(listener: &TcpListener, connect_addr: SocketAddr) -> anyhow::Result<()> {
+    let mut client = TcpStream::connect(connect_addr)?;
+    let (mut server, peer) = listener.accept()?;
+
+    ensure_ord!(client.peer_addr()?, ==, connect_addr);
+    ensure_ord!(server.local_addr()?, ==, connect_addr);
+    ensure_ord!(peer, ==, client.local_addr()?);
+    ensure_ord!(server.peer_addr()?, ==, peer);
+
+    client.write_all(b"hello")?;
+    let mut buf = [0u8; 5];
+    server.read_exact(&mut buf)?;
+    ensure_ord!(&buf, ==, b"hello");
+
+    server.write_all(b"world")?;
+    client.read_exact(&mut buf)?;
+    ensure_ord!(&buf, ==, b"world");
+
+    Ok(())
+}
+
+fn test_tcp_loopback() -> anyhow::Result<()> {
+    let listener = TcpListener::bind((Ipv6Addr::LOCALHOST, 0))?;
+    let addr = listener.local_addr()?;
+
+    ensure_ord!(addr.ip(), ==, Ipv6Addr::LOCALHOST);
+    ensure_ord!(addr.port(), !=, 0);
+
+    tcp_exchange(&listener, addr)
+}
+
+fn test_udp_loopback() -> anyhow::Result<()> {
+    let a = UdpSocket::bind((Ipv6Addr::LOCALHOST, 0))?;
+    let b = UdpSocket::bind((Ipv6Addr::LOCALHOST, 0))?;
+
+    a.send_to(b"hello", b.local_addr()?)?;
+
+    let mut buf = [0u8; 10];
+    let (len, src) = b.recv_from(&mut buf)?;
+    ensure_ord!(&buf[..len], ==, b"hello");
+    ensure_ord!(src, ==, a.local_addr()?);
+    ensure_ord!(src.is_ipv6(), ==, true);
+
+    Ok(())
+}
+
+fn test_v6only() -> anyhow::Result<()> {
+    for sock_type in [SockType::Stream, SockType::Datagram] {
+        with_ipv6_socket(sock_type, |fd| {
+            ensure_ord!(getsockopt(fd, sockopt::Ipv6V6Only)?, ==, false);
+            setsockopt(fd, sockopt::Ipv6V6Only, &true)?;
+            ensure_ord!(getsockopt(fd, sockopt::Ipv6V6Only)?, ==, true);
+            setsockopt(fd, sockopt::Ipv6V6Only, &false)?;
+            ensure_ord!(getsockopt(fd, sockopt::Ipv6V6Only)?, ==, false);
+            Ok(())
+        })?;
+    }
+
+    // the option can't be changed after the socket is bound
+    with_ipv6_socket(SockType::Datagram, |fd| {
+        bind(
+            fd,
+            &SockaddrIn6::from(SocketAddrV6::new(Ipv6Addr::LOCALHOST, 0, 0, 0)),
+        )?;
+        ensure_ord!(setsockopt(fd, sockopt::Ipv6V6Only, &true), ==, Err(Errno::EINVAL));
+        Ok(())
+    })?;
+
+    Ok(())
+}
+
+fn test_v6only_mapped_connect() -> anyhow::Result<()> {
+    let mapped = SocketAddrV6::new(Ipv4Addr::LOCALHOST.to_ipv6_mapped(), 9, 0, 0);
+
+    with_ipv6_socket(SockType::Datagram, |fd| {
+        setsockopt(fd, sockopt::Ipv6V6Only, &true)?;
+        ensure_ord!(connect(fd, &SockaddrIn6::from(mapped)), ==, Err(Errno::ENETUNREACH));
+        Ok(())
+    })?;
+
+    // without IPV6_V6ONLY the connection is made over IPv4
+    with_ipv6_socket(SockType::Datagram, |fd| {
+        connect(fd, &SockaddrIn6::from(mapped))?;
+        Ok(())
+    })
+}
+
+fn test_bind_ipv4_addr() -> anyhow::Result<()> {
+    with_ipv6_socket(SockType::Stream, |fd| {
+        let addr = SockaddrIn::new(127, 0, 0, 1, 0);
+        ensure_ord!(bind(fd, &addr), ==, Err(Errno::EINVAL));
+        Ok(())
+    })
+}
+
+/// Create an IPv6-only socket of type `sock_type` bound to the wildcard address and `port`.
+fn bind_v6only(sock_type: SockType, port: u16) -> anyhow::Result<RawFd> {
+    let fd = socket(AddressFamily::Inet6, sock_type, SockFlag::empty(), None)?;
+    let addr = SocketAddrV6::new(Ipv6Addr::UNSPECIFIED, port, 0, 0);
+    let rv =
+        setsockopt(fd, sockopt::Ipv6V6Only, &true).and_then(|_| bind(fd, &SockaddrIn6::from(addr)));
+    if let Err(e) = rv {
+        close(fd)?;
+        return Err(e.into());
+    }
+    Ok(fd)
+}
+
+fn test_v6only_port() -> anyhow::Result<()> {
+    // an IPv6-only socket can use the same port as an IPv4 socket
+    let udp_v4 = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    let port = udp_v4.local_addr()?.port();
+    let udp_v6 = unsafe { UdpSocket::from_raw_fd(bind_v6only(SockType::Datagram, port)?) };
+
+    // but a dual-stack socket can't
+    with_ipv6_socket(SockType::Datagram, |fd| {
+        let addr = SockaddrIn6::from(SocketAddrV6::new(Ipv6Addr::UNSPECIFIED, port, 0, 0));
+        ensure_ord!(bind(fd, &addr), ==, Err(Errno::EADDRINUSE));
+        Ok(())
+    })?;
+
+    // each socket receives the datagrams sent to its own address family
+    let client_v4 = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0))?;
+    let client_v6 = UdpSocket::bind((Ipv6Addr::LOCALHOST, 0))?;
+    client_v4.send_to(b"ipv4", (Ipv4Addr::LOCALHOST, port))?;
+    client_v6.send_to(b"ipv6", (Ipv6Addr::LOCALHOST, port))?;
+
+    let mut buf = [0u8; 10];
+    let (len, src) = udp_v4.recv_from(&mut buf)?;
+    ensure_ord!(&buf[..len], ==, b"ipv4");
+    ensure_ord!(src, ==, client_v4.local_addr()?);
+
+    let (len, src) = udp_v6.recv_from(&mut buf)?;
+    ensure_ord!(&buf[..len], ==, b"ipv6");
+    ensure_ord!(src, ==, client_v6.local_addr()?);
+
+    // the same for TCP listeners
+    let tcp_v4 = TcpListener::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    let port = tcp_v4.local_addr()?.port();
+    let fd = bind_v6only(SockType::Stream, port)?;
+    let tcp_v6 = unsafe { TcpListener::from_raw_fd(fd) };
+    listen(fd, 10)?;
+
+    tcp_exchange(&tcp_v4, SocketAddr::from((Ipv4Addr::LOCALHOST, port)))?;
+    tcp_exchange(&tcp_v6, SocketAddr::from((Ipv6Addr::LOCALHOST, port)))?;
+
+    Ok(())
+}
+
+fn test_dual_stack_server() -> anyhow::Result<()> {
+    // a server bound to the IPv6 wildcard address accepts connections from IPv4 clients
+    let listener = TcpListener::bind((Ipv6Addr::UNSPECIFIED, 0))?;
+    let port = listener.local_addr()?.port();
+
+    let mut client = TcpStream::connect(("127.0.0.1", port))?;
+    let (mut server, _peer) = listener.accept()?;
+
+    ensure_ord!(server.local_addr()?.is_ipv6(), ==, true);
+
+    client.write_all(b"hello")?;
+    let mut buf = [0u8; 5];
+    server.read_exact(&mut buf)?;
+    ensure_ord!(&buf, ==, b"hello");
+
+    server.write_all(b"world")?;
+    client.read_exact(&mut buf)?;
+    ensure_ord!(&buf, ==, b"world");
+
+    Ok(())
+}
+
+fn test_getaddrinfo_numeric() -> anyhow::Result<()> {
+    let addrs = getaddrinfo(c"::1", libc::AF_UNSPEC)?;
+
+    ensure_ord!(addrs.is_empty(), ==, false);
+    for addr in addrs {
+        ensure_ord!(addr, ==, Ipv6Addr::LOCALHOST);
+    }
+
+    Ok(())
+}
+
+/// Look up the IPv6 addresses of `node` using `getaddrinfo()`. Returns an error if any IPv4
+/// addresses are returned.
+fn getaddrinfo(node: &CStr, family: libc::c_int) -> anyhow::Result<Vec<Ipv6Addr>> {
+    let hints = libc::addrinfo {
+        ai_flags: 0,
+        ai_family: family,
+        ai_socktype: libc::SOCK_STREAM,
+        ai_protocol: 0,
+        ai_addrlen: 0,
+        ai_addr: std::ptr::null_mut(),
+        ai_canonname: std::ptr::null_mut(),
+        ai_next: std::ptr::null_mut(),
+    };
+    let mut res: *mut libc::addrinfo = std::ptr::null_mut();
+    let rv = unsafe { libc::getaddrinfo(node.as_ptr(), std::ptr::null(), &hints, &mut res) };
+    anyhow::ensure!(rv == 0, "getaddrinfo failed: {rv}");
+
+    let mut families = Vec::new();
+    let mut addrs = Vec::new();
+    let mut next = res;
+    while !next.is_null() {
+        let ai = unsafe { &*next };
+        families.push(ai.ai_family);
+        if ai.ai_family == libc::AF_INET6 {
+            let sin6 = unsafe { &*(ai.ai_addr as *const libc::sockaddr_in6) };
+            addrs.push(Ipv6Addr::from(sin6.sin6_addr.s6_addr));
+        }
+        next = ai.ai_next;
+    }
+    unsafe { libc::freeaddrinfo(res) };
+
+    ensure_ord!(families, ==, vec![libc::AF_INET6; addrs.len()]);
+    Ok(addrs)
+}
+
+/// Look up the IPv6 address of this host.
+fn host_ipv6_addr() -> anyhow::Result<Ipv6Addr> {
+    let hostname = nix::unistd::gethostname()?;
+    let hostname = CString::new(hostname.into_encoded_bytes())?;
+
+    let addrs = getaddrinfo(&hostname, libc::AF_INET6)?;
+    addrs
+        .first()
+        .copied()
+        .ok_or_else(|| anyhow::anyhow!("no IPv6 address for this host"))
+}
+
+fn test_tcp_host_address() -> anyhow::Result<()> {
+    let addr = host_ipv6_addr()?;
+
+    // Shadow assigns addresses starting from fd00:: by default
+    ensure_ord!(addr.segments()[0], ==, 0xfd00);
+
+    let listener = TcpListener::bind((Ipv6Addr::UNSPECIFIED, 0))?;
+    let port = listener.local_addr()?.port();
+
+    tcp_exchange(&listener, SocketAddr::from((addr, port)))
+}
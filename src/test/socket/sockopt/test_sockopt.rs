@@ -1010,6 +1010,24 @@ fn test_tcp_congestion(domain: libc::c_int, sock_type: libc::c_int) -> Result<()
         };
         check_setsockopt_call(&mut set_args_2, &expected_errnos)?;
 
+        if sock_type != libc::SOCK_STREAM {
+            return Ok(());
+        }
+
+        // try changing the algorithm
+        let mut set_args = SetsockoptArguments::new(fd, level, optname, Some("cubic".into()));
+        check_setsockopt_call(&mut set_args, &[])?;
+
+        let mut get_args = GetsockoptArguments::new(fd, level, optname, Some(vec![0u8; 16]));
+        check_getsockopt_call(&mut get_args, &[])?;
+
+        let returned_str = get_args.optval.as_ref().unwrap();
+        test_utils::result_assert_eq(
+            &returned_str[..6],
+            &b"cubic\0"[..],
+            "Unexpected value for TCP_CONGESTION",
+        )?;
+
         Ok(())
     })
 }
@@ -0,0 +1,346 @@
+/*
+ * The Shadow Simulator
+ * See LICENSE for licensing information
+ */
+
+use std::os::unix::io::RawFd;
+
+use nix::errno::Errno;
+use nix::fcntl::{FcntlArg, FdFlag, fcntl};
+use nix::sys::socket::{
+    AddressFamily, SockFlag, SockType, getsockopt, setsockopt, socketpair, sockopt,
+};
+use nix::unistd::{close, pipe, read, write};
+use test_utils::TestEnvironment as TestEnv;
+use test_utils::{ensure_ord, set};
+
+fn main() -> anyhow::Result<()> {
+    // should we restrict the tests we run?
+    let filter_shadow_passing = std::env::args().any(|x| x == "--shadow-passing");
+    let filter_libc_passing = std::env::args().any(|x| x == "--libc-passing");
+
+    // should we summarize the results rather than exit on a failed test
+    let summarize = std::env::args().any(|x| x == "--summarize");
+
+    let all_envs = set![TestEnv::Libc, TestEnv::Shadow];
+    let mut tests: Vec<test_utils::ShadowTest<_, _>> = vec![];
+
+    for sock_type in [SockType::Stream, SockType::Datagram, SockType::SeqPacket] {
+        let append_args = |s| format!("{s} <type={sock_type:?}>");
+
+        tests.extend([
+            test_utils::ShadowTest::new(
+                &append_args("test_pass_fd"),
+                move || test_pass_fd(sock_type),
+                all_envs.clone(),
+            ),
+            test_utils::ShadowTest::new(
+                &append_args("test_pass_fd_truncated"),
+                move || test_pass_fd_truncated(sock_type),
+                all_envs.clone(),
+            ),
+            test_utils::ShadowTest::new(
+                &append_args("test_pass_fd_cloexec"),
+                move || test_pass_fd_cloexec(sock_type),
+                all_envs.clone(),
+            ),
+            test_utils::ShadowTest::new(
+                &append_args("test_pass_credentials"),
+                move || test_pass_credentials(sock_type),
+                all_envs.clone(),
+            ),
+        ]);
+    }
+
+    tests.extend([
+        test_utils::ShadowTest::new(
+            "test_stream_message_boundary",
+            test_stream_message_boundary,
+            all_envs.clone(),
+        ),
+        test_utils::ShadowTest::new("test_bad_fd", test_bad_fd, all_envs),
+    ]);
+
+    if filter_shadow_passing {
+        tests.retain(|x| x.passing(TestEnv::Shadow));
+    }
+    if filter_libc_passing {
+        tests.retain(|x| x.passing(TestEnv::Libc));
+    }
+
+    test_utils::run_tests(&tests, summarize)?;
+
+    println!("Success.");
+    Ok(())
+}
+
+/// Data received with [`recv_with_control`].
+struct Received {
+    len: usize,
+    flags: libc::c_int,
+    fds: Vec<RawFd>,
+    credentials: Option<libc::ucred>,
+}
+
+/// Send `data` over `fd`, passing `fds` with `SCM_RIGHTS`.
+fn send_fds(fd: RawFd, data: &[u8], fds: &[RawFd]) -> Result<usize, Errno> {
+    let fds_len = std::mem::size_of_val(fds);
+
+    // use a u64 buffer so that the control messages are aligned
+    let space = unsafe { libc::CMSG_SPACE(fds_len as u32) } as usize;
+    let mut control = vec![0u64; space.div_ceil(8)];
+
+    let mut iov = libc::iovec {
+        iov_base: data.as_ptr() as *mut libc::c_void,
+        iov_len: data.len(),
+    };
+
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+    msg.msg_controllen = space;
+
+    unsafe {
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(fds_len as u32) as usize;
+        std::ptr::copy_nonoverlapping(fds.as_ptr(), libc::CMSG_DATA(cmsg) as *mut RawFd, fds.len());
+    }
+
+    let rv = unsafe { libc::sendmsg(fd, &msg, 0) };
+    Errno::result(rv).map(|x| x as usize)
+}
+
+/// Receive into `buf` from `fd` with a control buffer of `control_len` bytes, or a null control
+/// buffer if `control_len` is 0.
+fn recv_with_control(
+    fd: RawFd,
+    buf: &mut [u8],
+    control_len: usize,
+    flags: libc::c_int,
+) -> Result<Received, Errno> {
+    // use a u64 buffer so that the control messages are aligned
+    let mut control = vec![0u64; control_len.div_ceil(8)];
+
+    let mut iov = libc::iovec {
+        iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+        iov_len: buf.len(),
+    };
+
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    if control_len > 0 {
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = control_len;
+    }
+
+    let rv = unsafe { libc::recvmsg(fd, &mut msg, flags) };
+    let len = Errno::result(rv)? as usize;
+
+    let mut received = Received {
+        len,
+        flags: msg.msg_flags,
+        fds: vec![],
+        credentials: None,
+    };
+
+    let mut cmsg = unsafe { libc::CMSG_FIRSTHDR(&msg) };
+    while let Some(hdr) = unsafe { cmsg.as_ref() } {
+        let data = unsafe { libc::CMSG_DATA(cmsg) };
+        let data_len = hdr.cmsg_len - unsafe { libc::CMSG_LEN(0) } as usize;
+
+        match (hdr.cmsg_level, hdr.cmsg_type) {
+            (libc::SOL_SOCKET, libc::SCM_RIGHTS) => {
+                for i in 0..(data_len / std::mem::size_of::<RawFd>()) {
+                    let fd = unsafe { (data as *const RawFd).add(i).read_unaligned() };
+                    received.fds.push(fd);
+                }
+            }
+            (libc::SOL_SOCKET, libc::SCM_CREDENTIALS) => {
+                let credentials = unsafe { (data as *const libc::ucred).read_unaligned() };
+                received.credentials = Some(credentials);
+            }
+            x => panic!("Unexpected control message {x:?}"),
+        }
+
+        cmsg = unsafe { libc::CMSG_NXTHDR(&msg, cmsg) };
+    }
+
+    Ok(received)
+}
+
+/// Run `f` with a new unix socket pair of type `sock_type`, closing the sockets afterwards.
+fn with_socketpair(
+    sock_type: SockType,
+    f: impl FnOnce(RawFd, RawFd) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let (fd_a, fd_b) = socketpair(AddressFamily::Unix, sock_type, None, SockFlag::empty())?;
+    let rv = f(fd_a, fd_b);
+    close(fd_a)?;
+    close(fd_b)?;
+    rv
+}
+
+/// Test that a descriptor passed with `SCM_RIGHTS` refers to the same open file.
+fn test_pass_fd(sock_type: SockType) -> anyhow::Result<()> {
+    with_socketpair(sock_type, |fd_a, fd_b| {
+        let (pipe_read, pipe_write) = pipe()?;
+
+        ensure_ord!(send_fds(fd_a, b"hello", &[pipe_write])?, ==, 5);
+
+        // the sender can close its descriptor, and the open file stays open
+        close(pipe_write)?;
+
+        let mut buf = [0u8; 10];
+        let control_len = unsafe { libc::CMSG_SPACE(4) } as usize;
+        let received = recv_with_control(fd_b, &mut buf, control_len, 0)?;
+
+        ensure_ord!(&buf[..received.len], ==, b"hello");
+        ensure_ord!(received.flags & libc::MSG_CTRUNC, ==, 0);
+        ensure_ord!(received.fds.len(), ==, 1);
+
+        // the received descriptor can write to the pipe
+        ensure_ord!(write(received.fds[0], b"world")?, ==, 5);
+        let mut buf = [0u8; 10];
+        ensure_ord!(read(pipe_read, &mut buf)?, ==, 5);
+        ensure_ord!(&buf[..5], ==, b"world");
+
+        // the new descriptor doesn't have FD_CLOEXEC set
+        let flags = FdFlag::from_bits(fcntl(received.fds[0], FcntlArg::F_GETFD)?).unwrap();
+        ensure_ord!(flags.contains(FdFlag::FD_CLOEXEC), ==, false);
+
+        close(received.fds[0])?;
+        close(pipe_read)?;
+
+        Ok(())
+    })
+}
+
+/// Test that descriptors which don't fit in the control buffer are discarded, and that
+/// `MSG_CTRUNC` is set.
+fn test_pass_fd_truncated(sock_type: SockType) -> anyhow::Result<()> {
+    with_socketpair(sock_type, |fd_a, fd_b| {
+        let (pipe_read, pipe_write) = pipe()?;
+
+        ensure_ord!(send_fds(fd_a, b"a", &[pipe_write; 3])?, ==, 1);
+        ensure_ord!(send_fds(fd_a, b"b", &[pipe_write])?, ==, 1);
+        close(pipe_write)?;
+
+        // only enough space for one descriptor
+        let mut buf = [0u8; 1];
+        let control_len = unsafe { libc::CMSG_LEN(4) } as usize;
+        let received = recv_with_control(fd_b, &mut buf, control_len, 0)?;
+        ensure_ord!(received.flags & libc::MSG_CTRUNC, !=, 0);
+        ensure_ord!(received.fds.len(), ==, 1);
+        close(received.fds[0])?;
+
+        // no control buffer
+        let received = recv_with_control(fd_b, &mut buf, 0, 0)?;
+        ensure_ord!(received.len, ==, 1);
+        ensure_ord!(&buf, ==, b"b");
+        ensure_ord!(received.flags & libc::MSG_CTRUNC, !=, 0);
+        ensure_ord!(received.fds.len(), ==, 0);
+
+        // all write descriptors for the pipe have now been closed
+        let mut buf = [0u8; 1];
+        ensure_ord!(read(pipe_read, &mut buf)?, ==, 0);
+
+        close(pipe_read)?;
+
+        Ok(())
+    })
+}
+
+/// Test that `MSG_CMSG_CLOEXEC` sets `FD_CLOEXEC` on received descriptors.
+fn test_pass_fd_cloexec(sock_type: SockType) -> anyhow::Result<()> {
+    with_socketpair(sock_type, |fd_a, fd_b| {
+        let (pipe_read, pipe_write) = pipe()?;
+
+        ensure_ord!(send_fds(fd_a, b"a", &[pipe_write])?, ==, 1);
+
+        let mut buf = [0u8; 1];
+        let control_len = unsafe { libc::CMSG_SPACE(4) } as usize;
+        let received = recv_with_control(fd_b, &mut buf, control_len, libc::MSG_CMSG_CLOEXEC)?;
+        ensure_ord!(received.fds.len(), ==, 1);
+
+        let flags = FdFlag::from_bits(fcntl(received.fds[0], FcntlArg::F_GETFD)?).unwrap();
+        ensure_ord!(flags.contains(FdFlag::FD_CLOEXEC), ==, true);
+
+        close(received.fds[0])?;
+        close(pipe_read)?;
+        close(pipe_write)?;
+
+        Ok(())
+    })
+}
+
+/// Test that the sender's credentials are received when `SO_PASSCRED` is enabled.
+fn test_pass_credentials(sock_type: SockType) -> anyhow::Result<()> {
+    with_socketpair(sock_type, |fd_a, fd_b| {
+        ensure_ord!(getsockopt(fd_b, sockopt::PassCred)?, ==, false);
+        setsockopt(fd_b, sockopt::PassCred, &true)?;
+        ensure_ord!(getsockopt(fd_b, sockopt::PassCred)?, ==, true);
+
+        // the sender doesn't need to send credentials explicitly
+        ensure_ord!(write(fd_a, b"a")?, ==, 1);
+
+        let mut buf = [0u8; 1];
+        let control_len = unsafe { libc::CMSG_SPACE(std::mem::size_of::<libc::ucred>() as u32) };
+        let received = recv_with_control(fd_b, &mut buf, control_len as usize, 0)?;
+
+        let credentials = received.credentials.unwrap();
+        ensure_ord!(credentials.pid, ==, nix::unistd::getpid().as_raw());
+        ensure_ord!(credentials.uid, ==, nix::unistd::getuid().as_raw());
+        ensure_ord!(credentials.gid, ==, nix::unistd::getgid().as_raw());
+
+        Ok(())
+    })
+}
+
+/// Test that a read from a stream socket stops after a message with descriptors.
+fn test_stream_message_boundary() -> anyhow::Result<()> {
+    with_socketpair(SockType::Stream, |fd_a, fd_b| {
+        let (pipe_read, pipe_write) = pipe()?;
+
+        ensure_ord!(write(fd_a, b"ab")?, ==, 2);
+        ensure_ord!(send_fds(fd_a, b"cd", &[pipe_write])?, ==, 2);
+        ensure_ord!(write(fd_a, b"ef")?, ==, 2);
+
+        let mut buf = [0u8; 10];
+        let control_len = unsafe { libc::CMSG_SPACE(4) } as usize;
+
+        let received = recv_with_control(fd_b, &mut buf, control_len, 0)?;
+        ensure_ord!(&buf[..received.len], ==, b"abcd");
+        ensure_ord!(received.fds.len(), ==, 1);
+        close(received.fds[0])?;
+
+        let received = recv_with_control(fd_b, &mut buf, control_len, 0)?;
+        ensure_ord!(&buf[..received.len], ==, b"ef");
+        ensure_ord!(received.fds.len(), ==, 0);
+
+        close(pipe_read)?;
+        close(pipe_write)?;
+
+        Ok(())
+    })
+}
+
+/// Test that passing an invalid descriptor fails without sending any data.
+fn test_bad_fd() -> anyhow::Result<()> {
+    with_socketpair(SockType::Stream, |fd_a, fd_b| {
+        ensure_ord!(send_fds(fd_a, b"a", &[-1]), ==, Err(Errno::EBADF));
+        ensure_ord!(send_fds(fd_a, b"a", &[9999]), ==, Err(Errno::EBADF));
+
+        let mut buf = [0u8; 1];
+        ensure_ord!(
+            recv_with_control(fd_b, &mut buf, 0, libc::MSG_DONTWAIT).err(),
+            ==,
+            Some(Errno::EAGAIN)
+        );
+
+        Ok(())
+    })
+}
@@ -0,0 +1,291 @@
+use std::time::Duration;
+
+use test_utils::{TestEnvironment, ensure_ord, set};
+
+fn main() -> anyhow::Result<()> {
+    // should we restrict the tests we run?
+    let filter_shadow_passing = std::env::args().any(|x| x == "--shadow-passing");
+    let filter_libc_passing = std::env::args().any(|x| x == "--libc-passing");
+    // should we summarize the results rather than exit on a failed test
+    let summarize = std::env::args().any(|x| x == "--summarize");
+
+    let mut tests = get_tests();
+
+    if filter_shadow_passing {
+        tests.retain(|x| x.passing(TestEnvironment::Shadow));
+    }
+    if filter_libc_passing {
+        tests.retain(|x| x.passing(TestEnvironment::Libc));
+    }
+
+    test_utils::run_tests(&tests, summarize)?;
+
+    Ok(())
+}
+
+fn get_tests() -> Vec<test_utils::ShadowTest<(), anyhow::Error>> {
+    let all_envs = set![TestEnvironment::Libc, TestEnvironment::Shadow];
+    // Setting the clock on Linux requires CAP_SYS_TIME, and would change the
+    // clock of the machine running the tests.
+    let shadow_only = set![TestEnvironment::Shadow];
+
+    let tests: Vec<test_utils::ShadowTest<(), anyhow::Error>> = vec![
+        test_utils::ShadowTest::new(
+            "set_monotonic_fails",
+            test_set_monotonic_fails,
+            all_envs.clone(),
+        ),
+        test_utils::ShadowTest::new("invalid_nsec", test_invalid_nsec, all_envs),
+        test_utils::ShadowTest::new(
+            "clock_settime_realtime",
+            test_clock_settime_realtime,
+            shadow_only.clone(),
+        ),
+        test_utils::ShadowTest::new("settimeofday", test_settimeofday, shadow_only.clone()),
+        test_utils::ShadowTest::new(
+            "adjtimex_setoffset",
+            test_adjtimex_setoffset,
+            shadow_only.clone(),
+        ),
+        test_utils::ShadowTest::new("adjtimex_read", test_adjtimex_read, shadow_only.clone()),
+        test_utils::ShadowTest::new(
+            "timerfd_abstime_follows_clock",
+            test_timerfd_abstime_follows_clock,
+            shadow_only.clone(),
+        ),
+        test_utils::ShadowTest::new(
+            "nanosleep_abstime_follows_clock",
+            test_nanosleep_abstime_follows_clock,
+            shadow_only,
+        ),
+    ];
+
+    tests
+}
+
+fn clock_gettime(clockid: libc::clockid_t) -> Duration {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    assert_eq!(unsafe { libc::clock_gettime(clockid, &mut ts) }, 0);
+    Duration::new(
+        ts.tv_sec.try_into().unwrap(),
+        ts.tv_nsec.try_into().unwrap(),
+    )
+}
+
+fn clock_settime(clockid: libc::clockid_t, t: Duration) -> nix::Result<()> {
+    let ts = libc::timespec {
+        tv_sec: t.as_secs().try_into().unwrap(),
+        tv_nsec: t.subsec_nanos().into(),
+    };
+    nix::errno::Errno::result(unsafe { libc::clock_settime(clockid, &ts) }).map(|_| ())
+}
+
+/// Returns the absolute difference between `a` and `b`.
+fn abs_diff(a: Duration, b: Duration) -> Duration {
+    if a > b { a - b } else { b - a }
+}
+
+const TOLERANCE: Duration = Duration::from_millis(10);
+
+fn test_set_monotonic_fails() -> anyhow::Result<()> {
+    let now = clock_gettime(libc::CLOCK_MONOTONIC);
+    let rv = clock_settime(libc::CLOCK_MONOTONIC, now + Duration::from_secs(10));
+    ensure_ord!(rv, ==, Err(nix::errno::Errno::EINVAL));
+    Ok(())
+}
+
+fn test_invalid_nsec() -> anyhow::Result<()> {
+    let ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 1_000_000_000,
+    };
+    let rv = nix::errno::Errno::result(unsafe { libc::clock_settime(libc::CLOCK_REALTIME, &ts) });
+    // EINVAL, or EPERM on Linux when run without CAP_SYS_TIME.
+    ensure_ord!(rv, !=, Ok(0));
+    Ok(())
+}
+
+fn test_clock_settime_realtime() -> anyhow::Result<()> {
+    let monotonic_before = clock_gettime(libc::CLOCK_MONOTONIC);
+    let target = Duration::from_secs(1_700_000_000);
+
+    clock_settime(libc::CLOCK_REALTIME, target)?;
+
+    let realtime = clock_gettime(libc::CLOCK_REALTIME);
+    ensure_ord!(abs_diff(realtime, target), <, TOLERANCE);
+
+    // The monotonic clock is unaffected.
+    let monotonic_after = clock_gettime(libc::CLOCK_MONOTONIC);
+    ensure_ord!(monotonic_after, >=, monotonic_before);
+    ensure_ord!(monotonic_after - monotonic_before, <, TOLERANCE);
+
+    // The realtime clock keeps running from the new time.
+    std::thread::sleep(Duration::from_secs(1));
+    let realtime = clock_gettime(libc::CLOCK_REALTIME);
+    ensure_ord!(
+        abs_diff(realtime, target + Duration::from_secs(1)),
+        <,
+        TOLERANCE
+    );
+
+    Ok(())
+}
+
+fn test_settimeofday() -> anyhow::Result<()> {
+    let target = Duration::from_secs(1_600_000_000);
+    let tv = libc::timeval {
+        tv_sec: target.as_secs().try_into().unwrap(),
+        tv_usec: 0,
+    };
+    nix::errno::Errno::result(unsafe { libc::settimeofday(&tv, std::ptr::null()) })?;
+
+    let mut tv = libc::timeval {
+        tv_sec: 0,
+        tv_usec: 0,
+    };
+    nix::errno::Errno::result(unsafe { libc::gettimeofday(&mut tv, std::ptr::null_mut()) })?;
+    let realtime = Duration::new(
+        tv.tv_sec.try_into().unwrap(),
+        u32::try_from(tv.tv_usec).unwrap() * 1000,
+    );
+    ensure_ord!(abs_diff(realtime, target), <, TOLERANCE);
+
+    // A NULL timeval doesn't change the time.
+    nix::errno::Errno::result(unsafe { libc::settimeofday(std::ptr::null(), std::ptr::null()) })?;
+    let realtime = clock_gettime(libc::CLOCK_REALTIME);
+    ensure_ord!(abs_diff(realtime, target), <, TOLERANCE);
+
+    Ok(())
+}
+
+fn test_adjtimex_setoffset() -> anyhow::Result<()> {
+    let before = clock_gettime(libc::CLOCK_REALTIME);
+    let monotonic_before = clock_gettime(libc::CLOCK_MONOTONIC);
+
+    let mut txc: libc::timex = unsafe { std::mem::zeroed() };
+    txc.modes = libc::ADJ_SETOFFSET | libc::ADJ_NANO;
+    txc.time.tv_sec = 5;
+    txc.time.tv_usec = 500_000_000;
+    nix::errno::Errno::result(unsafe { libc::adjtimex(&mut txc) })?;
+
+    let after = clock_gettime(libc::CLOCK_REALTIME);
+    ensure_ord!(
+        abs_diff(after, before + Duration::from_millis(5_500)),
+        <,
+        TOLERANCE
+    );
+
+    let monotonic_after = clock_gettime(libc::CLOCK_MONOTONIC);
+    ensure_ord!(monotonic_after - monotonic_before, <, TOLERANCE);
+
+    Ok(())
+}
+
+fn test_adjtimex_read() -> anyhow::Result<()> {
+    let mut txc: libc::timex = unsafe { std::mem::zeroed() };
+    txc.modes = 0;
+    let rv = nix::errno::Errno::result(unsafe { libc::adjtimex(&mut txc) })?;
+    ensure_ord!(rv, >=, libc::TIME_OK);
+
+    let realtime = clock_gettime(libc::CLOCK_REALTIME);
+    let reported = Duration::from_secs(txc.time.tv_sec.try_into().unwrap());
+    ensure_ord!(abs_diff(realtime, reported), <, Duration::from_secs(2));
+
+    // Clear STA_UNSYNC, as an NTP daemon would once synchronized.
+    txc.modes = libc::ADJ_STATUS;
+    txc.status = 0;
+    let rv = nix::errno::Errno::result(unsafe { libc::adjtimex(&mut txc) })?;
+    ensure_ord!(rv, ==, libc::TIME_OK);
+    ensure_ord!(txc.status & libc::STA_UNSYNC, ==, 0);
+
+    Ok(())
+}
+
+fn test_timerfd_abstime_follows_clock() -> anyhow::Result<()> {
+    let fd = nix::errno::Errno::result(unsafe { libc::timerfd_create(libc::CLOCK_REALTIME, 0) })?;
+
+    // Arm the timer to expire 3 seconds from now on the realtime clock.
+    let expire = clock_gettime(libc::CLOCK_REALTIME) + Duration::from_secs(3);
+    let new_value = libc::itimerspec {
+        it_interval: libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        },
+        it_value: libc::timespec {
+            tv_sec: expire.as_secs().try_into().unwrap(),
+            tv_nsec: expire.subsec_nanos().into(),
+        },
+    };
+    nix::errno::Errno::result(unsafe {
+        libc::timerfd_settime(
+            fd,
+            libc::TFD_TIMER_ABSTIME,
+            &new_value,
+            std::ptr::null_mut(),
+        )
+    })?;
+
+    // Step the realtime clock forward by 2 seconds, so that the timer should
+    // expire 1 second from now.
+    clock_settime(
+        libc::CLOCK_REALTIME,
+        clock_gettime(libc::CLOCK_REALTIME) + Duration::from_secs(2),
+    )?;
+
+    let monotonic_before = clock_gettime(libc::CLOCK_MONOTONIC);
+    let mut expirations: u64 = 0;
+    let rv = unsafe {
+        libc::read(
+            fd,
+            std::ptr::from_mut(&mut expirations).cast(),
+            std::mem::size_of_val(&expirations),
+        )
+    };
+    ensure_ord!(rv, ==, 8);
+    ensure_ord!(expirations, ==, 1);
+    let elapsed = clock_gettime(libc::CLOCK_MONOTONIC) - monotonic_before;
+    ensure_ord!(abs_diff(elapsed, Duration::from_secs(1)), <, TOLERANCE);
+
+    nix::unistd::close(fd)?;
+    Ok(())
+}
+
+fn test_nanosleep_abstime_follows_clock() -> anyhow::Result<()> {
+    // Sleep until 3 seconds from now on the realtime clock.
+    let wakeup = clock_gettime(libc::CLOCK_REALTIME) + Duration::from_secs(3);
+    let request = libc::timespec {
+        tv_sec: wakeup.as_secs().try_into().unwrap(),
+        tv_nsec: wakeup.subsec_nanos().into(),
+    };
+
+    // While sleeping, step the realtime clock forward by 2 seconds, so that
+    // the sleep should end about 1 second after it started.
+    let stepper = std::thread::spawn(|| {
+        std::thread::sleep(Duration::from_millis(100));
+        clock_settime(
+            libc::CLOCK_REALTIME,
+            clock_gettime(libc::CLOCK_REALTIME) + Duration::from_secs(2),
+        )
+    });
+
+    let monotonic_before = clock_gettime(libc::CLOCK_MONOTONIC);
+    let rv = unsafe {
+        libc::clock_nanosleep(
+            libc::CLOCK_REALTIME,
+            libc::TIMER_ABSTIME,
+            &request,
+            std::ptr::null_mut(),
+        )
+    };
+    // clock_nanosleep returns the error rather than setting errno.
+    ensure_ord!(rv, ==, 0);
+    let elapsed = clock_gettime(libc::CLOCK_MONOTONIC) - monotonic_before;
+    ensure_ord!(abs_diff(elapsed, Duration::from_secs(1)), <, TOLERANCE);
+    ensure_ord!(clock_gettime(libc::CLOCK_REALTIME), >=, wakeup);
+
+    stepper.join().unwrap()?;
+    Ok(())
+}
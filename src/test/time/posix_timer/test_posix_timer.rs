@@ -0,0 +1,393 @@
+use std::sync::atomic::{AtomicI32, AtomicU64, Ordering};
+use std::time::Duration;
+
+use nix::errno::Errno;
+use nix::sys::signal::{SaFlags, SigAction, SigHandler, SigSet, SigmaskHow, Signal};
+use test_utils::{TestEnvironment, ensure_ord, set};
+
+// Counts how many times the SIGUSR1 handler ran.
+static SIGNAL_CTR: AtomicU64 = AtomicU64::new(0);
+// `si_code` of the last SIGUSR1.
+static LAST_CODE: AtomicI32 = AtomicI32::new(0);
+// `si_value` of the last SIGUSR1, as an integer.
+static LAST_VALUE: AtomicI32 = AtomicI32::new(0);
+// Thread that handled the last SIGUSR1.
+static LAST_TID: AtomicI32 = AtomicI32::new(0);
+
+extern "C" fn sigusr1_handler(sig: i32, info: *mut libc::siginfo_t, _ctx: *mut std::ffi::c_void) {
+    assert_eq!(sig, libc::SIGUSR1);
+    let info = unsafe { info.as_ref() }.unwrap();
+    LAST_CODE.store(info.si_code, Ordering::Relaxed);
+    LAST_VALUE.store(
+        unsafe { info.si_value() }.sival_ptr as usize as i32,
+        Ordering::Relaxed,
+    );
+    LAST_TID.store(unsafe { libc::gettid() }, Ordering::Relaxed);
+    SIGNAL_CTR.fetch_add(1, Ordering::Relaxed);
+}
+
+fn reset() {
+    SIGNAL_CTR.store(0, Ordering::Relaxed);
+    LAST_CODE.store(0, Ordering::Relaxed);
+    LAST_VALUE.store(0, Ordering::Relaxed);
+    LAST_TID.store(0, Ordering::Relaxed);
+}
+
+fn sigev_sigusr1(notify: i32, value: i32) -> libc::sigevent {
+    let mut sev: libc::sigevent = unsafe { std::mem::zeroed() };
+    sev.sigev_notify = notify;
+    sev.sigev_signo = libc::SIGUSR1;
+    sev.sigev_value = libc::sigval {
+        sival_ptr: value as usize as *mut std::ffi::c_void,
+    };
+    sev
+}
+
+fn timer_create(
+    clockid: libc::clockid_t,
+    sev: Option<&mut libc::sigevent>,
+) -> nix::Result<libc::timer_t> {
+    let sev = sev.map_or(std::ptr::null_mut(), std::ptr::from_mut);
+    let mut timer: libc::timer_t = std::ptr::null_mut();
+    Errno::result(unsafe { libc::timer_create(clockid, sev, &mut timer) })?;
+    Ok(timer)
+}
+
+fn timer_delete(timer: libc::timer_t) -> nix::Result<()> {
+    Errno::result(unsafe { libc::timer_delete(timer) }).map(|_| ())
+}
+
+fn timespec(d: Duration) -> libc::timespec {
+    libc::timespec {
+        tv_sec: d.as_secs().try_into().unwrap(),
+        tv_nsec: d.subsec_nanos().into(),
+    }
+}
+
+fn duration(ts: libc::timespec) -> Duration {
+    Duration::new(
+        ts.tv_sec.try_into().unwrap(),
+        ts.tv_nsec.try_into().unwrap(),
+    )
+}
+
+fn timer_settime(
+    timer: libc::timer_t,
+    flags: i32,
+    value: Duration,
+    interval: Duration,
+) -> nix::Result<()> {
+    let new_value = libc::itimerspec {
+        it_interval: timespec(interval),
+        it_value: timespec(value),
+    };
+    Errno::result(unsafe { libc::timer_settime(timer, flags, &new_value, std::ptr::null_mut()) })
+        .map(|_| ())
+}
+
+/// Returns the remaining time and interval of `timer`.
+fn timer_gettime(timer: libc::timer_t) -> nix::Result<(Duration, Duration)> {
+    let mut curr_value: libc::itimerspec = unsafe { std::mem::zeroed() };
+    Errno::result(unsafe { libc::timer_gettime(timer, &mut curr_value) })?;
+    Ok((
+        duration(curr_value.it_value),
+        duration(curr_value.it_interval),
+    ))
+}
+
+fn test_create_delete() -> anyhow::Result<()> {
+    let timer = timer_create(libc::CLOCK_MONOTONIC, None)?;
+    timer_delete(timer)?;
+    // Already deleted.
+    ensure_ord!(timer_delete(timer), ==, Err(Errno::EINVAL));
+    Ok(())
+}
+
+fn test_invalid_clock() -> anyhow::Result<()> {
+    ensure_ord!(timer_create(12345, None), ==, Err(Errno::EINVAL));
+    Ok(())
+}
+
+fn test_invalid_notify() -> anyhow::Result<()> {
+    let mut sev = sigev_sigusr1(1234, 0);
+    ensure_ord!(
+        timer_create(libc::CLOCK_MONOTONIC, Some(&mut sev)),
+        ==,
+        Err(Errno::EINVAL)
+    );
+
+    // SIGEV_THREAD_ID requires a thread in the calling process.
+    let mut sev = sigev_sigusr1(libc::SIGEV_THREAD_ID, 0);
+    sev.sigev_notify_thread_id = 1;
+    ensure_ord!(
+        timer_create(libc::CLOCK_MONOTONIC, Some(&mut sev)),
+        ==,
+        Err(Errno::EINVAL)
+    );
+    Ok(())
+}
+
+fn test_initially_unarmed() -> anyhow::Result<()> {
+    for clockid in [
+        libc::CLOCK_REALTIME,
+        libc::CLOCK_MONOTONIC,
+        libc::CLOCK_BOOTTIME,
+    ] {
+        let timer = timer_create(clockid, None)?;
+        ensure_ord!(timer_gettime(timer)?, ==, (Duration::ZERO, Duration::ZERO));
+        timer_delete(timer)?;
+    }
+    Ok(())
+}
+
+fn test_sigev_none() -> anyhow::Result<()> {
+    reset();
+    let mut sev = sigev_sigusr1(libc::SIGEV_NONE, 0);
+    let timer = timer_create(libc::CLOCK_MONOTONIC, Some(&mut sev))?;
+
+    timer_settime(timer, 0, Duration::from_millis(100), Duration::ZERO)?;
+    let (value, interval) = timer_gettime(timer)?;
+    ensure_ord!(value, >, Duration::ZERO);
+    ensure_ord!(value, <=, Duration::from_millis(100));
+    ensure_ord!(interval, ==, Duration::ZERO);
+
+    std::thread::sleep(Duration::from_millis(150));
+
+    // Expired without a notification.
+    ensure_ord!(timer_gettime(timer)?, ==, (Duration::ZERO, Duration::ZERO));
+    ensure_ord!(SIGNAL_CTR.load(Ordering::Relaxed), ==, 0);
+
+    timer_delete(timer)?;
+    Ok(())
+}
+
+fn test_sigev_signal() -> anyhow::Result<()> {
+    reset();
+    let mut sev = sigev_sigusr1(libc::SIGEV_SIGNAL, 42);
+    let timer = timer_create(libc::CLOCK_MONOTONIC, Some(&mut sev))?;
+
+    timer_settime(timer, 0, Duration::from_millis(100), Duration::ZERO)?;
+
+    std::thread::sleep(Duration::from_millis(50));
+    ensure_ord!(SIGNAL_CTR.load(Ordering::Relaxed), ==, 0);
+
+    std::thread::sleep(Duration::from_millis(100));
+    ensure_ord!(SIGNAL_CTR.load(Ordering::Relaxed), ==, 1);
+    ensure_ord!(LAST_CODE.load(Ordering::Relaxed), ==, libc::SI_TIMER);
+    ensure_ord!(LAST_VALUE.load(Ordering::Relaxed), ==, 42);
+
+    // One-shot timers don't fire again.
+    std::thread::sleep(Duration::from_millis(150));
+    ensure_ord!(SIGNAL_CTR.load(Ordering::Relaxed), ==, 1);
+
+    timer_delete(timer)?;
+    Ok(())
+}
+
+fn test_sigev_thread_id() -> anyhow::Result<()> {
+    reset();
+
+    let (tid_sender, tid_receiver) = std::sync::mpsc::channel();
+    let (done_sender, done_receiver) = std::sync::mpsc::channel::<()>();
+
+    // A thread that waits for the signal with SIGUSR1 unblocked, while it stays
+    // blocked in this thread.
+    let thread = std::thread::spawn(move || {
+        tid_sender.send(unsafe { libc::gettid() }).unwrap();
+        done_receiver.recv().unwrap();
+    });
+    let target_tid = tid_receiver.recv().unwrap();
+
+    let mut sigset = SigSet::empty();
+    sigset.add(Signal::SIGUSR1);
+    nix::sys::signal::sigprocmask(SigmaskHow::SIG_BLOCK, Some(&sigset), None)?;
+
+    let mut sev = sigev_sigusr1(libc::SIGEV_THREAD_ID, 7);
+    sev.sigev_notify_thread_id = target_tid;
+    let timer = timer_create(libc::CLOCK_MONOTONIC, Some(&mut sev))?;
+    timer_settime(timer, 0, Duration::from_millis(10), Duration::ZERO)?;
+
+    std::thread::sleep(Duration::from_millis(50));
+
+    ensure_ord!(SIGNAL_CTR.load(Ordering::Relaxed), ==, 1);
+    ensure_ord!(LAST_CODE.load(Ordering::Relaxed), ==, libc::SI_TIMER);
+    ensure_ord!(LAST_VALUE.load(Ordering::Relaxed), ==, 7);
+    ensure_ord!(LAST_TID.load(Ordering::Relaxed), ==, target_tid);
+
+    done_sender.send(()).unwrap();
+    thread.join().unwrap();
+    nix::sys::signal::sigprocmask(SigmaskHow::SIG_UNBLOCK, Some(&sigset), None)?;
+    timer_delete(timer)?;
+    Ok(())
+}
+
+fn test_interval() -> anyhow::Result<()> {
+    reset();
+    let mut sev = sigev_sigusr1(libc::SIGEV_SIGNAL, 0);
+    let timer = timer_create(libc::CLOCK_BOOTTIME, Some(&mut sev))?;
+
+    timer_settime(
+        timer,
+        0,
+        Duration::from_millis(100),
+        Duration::from_millis(100),
+    )?;
+
+    std::thread::sleep(Duration::from_millis(150));
+    ensure_ord!(SIGNAL_CTR.load(Ordering::Relaxed), ==, 1);
+    let (value, interval) = timer_gettime(timer)?;
+    ensure_ord!(value, >, Duration::ZERO);
+    ensure_ord!(value, <, Duration::from_millis(100));
+    ensure_ord!(interval, ==, Duration::from_millis(100));
+
+    std::thread::sleep(Duration::from_millis(100));
+    ensure_ord!(SIGNAL_CTR.load(Ordering::Relaxed), ==, 2);
+
+    timer_delete(timer)?;
+
+    // Deleted timers don't fire.
+    std::thread::sleep(Duration::from_millis(200));
+    ensure_ord!(SIGNAL_CTR.load(Ordering::Relaxed), ==, 2);
+    Ok(())
+}
+
+fn test_overrun() -> anyhow::Result<()> {
+    reset();
+    let mut sev = sigev_sigusr1(libc::SIGEV_SIGNAL, 0);
+    let timer = timer_create(libc::CLOCK_MONOTONIC, Some(&mut sev))?;
+
+    // Block the signal so that expirations accumulate as overruns.
+    let mut sigset = SigSet::empty();
+    sigset.add(Signal::SIGUSR1);
+    nix::sys::signal::sigprocmask(SigmaskHow::SIG_BLOCK, Some(&sigset), None)?;
+
+    // Expires at 10, 20, ..., 100 ms.
+    timer_settime(
+        timer,
+        0,
+        Duration::from_millis(10),
+        Duration::from_millis(10),
+    )?;
+    std::thread::sleep(Duration::from_millis(105));
+
+    nix::sys::signal::sigprocmask(SigmaskHow::SIG_UNBLOCK, Some(&sigset), None)?;
+
+    // Only one signal was delivered for all of the expirations.
+    ensure_ord!(SIGNAL_CTR.load(Ordering::Relaxed), ==, 1);
+    let overrun = Errno::result(unsafe { libc::timer_getoverrun(timer) })?;
+    ensure_ord!(overrun, >=, 8);
+    ensure_ord!(overrun, <=, 10);
+
+    timer_delete(timer)?;
+    Ok(())
+}
+
+fn test_abstime() -> anyhow::Result<()> {
+    reset();
+    let mut sev = sigev_sigusr1(libc::SIGEV_SIGNAL, 0);
+    let timer = timer_create(libc::CLOCK_REALTIME, Some(&mut sev))?;
+
+    let mut now = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    Errno::result(unsafe { libc::clock_gettime(libc::CLOCK_REALTIME, &mut now) })?;
+    let expire = duration(now) + Duration::from_millis(100);
+    timer_settime(timer, libc::TIMER_ABSTIME, expire, Duration::ZERO)?;
+
+    // The remaining time is relative.
+    let (value, _interval) = timer_gettime(timer)?;
+    ensure_ord!(value, >, Duration::ZERO);
+    ensure_ord!(value, <=, Duration::from_millis(100));
+
+    std::thread::sleep(Duration::from_millis(150));
+    ensure_ord!(SIGNAL_CTR.load(Ordering::Relaxed), ==, 1);
+
+    // An absolute time in the past expires immediately.
+    timer_settime(timer, libc::TIMER_ABSTIME, duration(now), Duration::ZERO)?;
+    std::thread::sleep(Duration::from_millis(10));
+    ensure_ord!(SIGNAL_CTR.load(Ordering::Relaxed), ==, 2);
+
+    timer_delete(timer)?;
+    Ok(())
+}
+
+fn test_abstime_past_overrun() -> anyhow::Result<()> {
+    reset();
+    let mut sev = sigev_sigusr1(libc::SIGEV_SIGNAL, 0);
+    let timer = timer_create(libc::CLOCK_MONOTONIC, Some(&mut sev))?;
+
+    let mut now = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    Errno::result(unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) })?;
+
+    // Starting 105 ms ago, the expirations at 0, 10, ..., 100 ms have passed. The timer expires
+    // immediately, and the 10 intervals since its start are overruns of that expiration.
+    let start = duration(now) - Duration::from_millis(105);
+    timer_settime(timer, libc::TIMER_ABSTIME, start, Duration::from_millis(10))?;
+    std::thread::sleep(Duration::from_millis(1));
+
+    ensure_ord!(SIGNAL_CTR.load(Ordering::Relaxed), ==, 1);
+    let overrun = Errno::result(unsafe { libc::timer_getoverrun(timer) })?;
+    ensure_ord!(overrun, ==, 10);
+
+    // The timer stays on the schedule from its start, so the next expiration is at 110 ms.
+    let (value, _interval) = timer_gettime(timer)?;
+    ensure_ord!(value, <=, Duration::from_millis(5));
+
+    timer_delete(timer)?;
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    // Install a SIGUSR1 handler that records the signals it receives.
+    unsafe {
+        nix::sys::signal::sigaction(
+            Signal::SIGUSR1,
+            &SigAction::new(
+                SigHandler::SigAction(sigusr1_handler),
+                SaFlags::SA_SIGINFO,
+                SigSet::empty(),
+            ),
+        )
+        .unwrap()
+    };
+
+    // should we restrict the tests we run?
+    let filter_shadow_passing = std::env::args().any(|x| x == "--shadow-passing");
+    let filter_libc_passing = std::env::args().any(|x| x == "--libc-passing");
+    // should we summarize the results rather than exit on a failed test
+    let summarize = std::env::args().any(|x| x == "--summarize");
+
+    let all_envs = set![TestEnvironment::Libc, TestEnvironment::Shadow];
+    let mut tests: Vec<test_utils::ShadowTest<(), anyhow::Error>> = vec![
+        test_utils::ShadowTest::new("create_delete", test_create_delete, all_envs.clone()),
+        test_utils::ShadowTest::new("invalid_clock", test_invalid_clock, all_envs.clone()),
+        test_utils::ShadowTest::new("invalid_notify", test_invalid_notify, all_envs.clone()),
+        test_utils::ShadowTest::new(
+            "initially_unarmed",
+            test_initially_unarmed,
+            all_envs.clone(),
+        ),
+        test_utils::ShadowTest::new("sigev_none", test_sigev_none, all_envs.clone()),
+        test_utils::ShadowTest::new("sigev_signal", test_sigev_signal, all_envs.clone()),
+        test_utils::ShadowTest::new("sigev_thread_id", test_sigev_thread_id, all_envs.clone()),
+        test_utils::ShadowTest::new("interval", test_interval, all_envs.clone()),
+        test_utils::ShadowTest::new("overrun", test_overrun, all_envs.clone()),
+        test_utils::ShadowTest::new("abstime", test_abstime, all_envs.clone()),
+        test_utils::ShadowTest::new("abstime_past_overrun", test_abstime_past_overrun, all_envs),
+    ];
+
+    if filter_shadow_passing {
+        tests.retain(|x| x.passing(TestEnvironment::Shadow));
+    }
+    if filter_libc_passing {
+        tests.retain(|x| x.passing(TestEnvironment::Libc));
+    }
+
+    test_utils::run_tests(&tests, summarize)?;
+
+    Ok(())
+}